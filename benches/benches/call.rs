@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, Criterion};
+use hebi::*;
+
+const SCORE: &str = indoc::indoc! {
+  r#"#!hebi
+    fn score(a, b):
+      return a * 10 + b
+  "#
+};
+
+pub fn call_loop(c: &mut Criterion) {
+  c.bench_function("call score 10k times", |b| {
+    let mut hebi = Hebi::new();
+    hebi.eval(SCORE).unwrap();
+
+    b.iter(|| {
+      for i in 0..10_000 {
+        black_box(hebi.call("score", (i, 1)).unwrap().as_int());
+      }
+    })
+  });
+}
+
+pub fn call_batch(c: &mut Criterion) {
+  c.bench_function("call_batch score 10k rows", |b| {
+    let mut hebi = Hebi::new();
+    hebi.eval(SCORE).unwrap();
+
+    b.iter(|| {
+      black_box(
+        hebi
+          .call_batch("score", (0..10_000).map(|i| (i, 1)))
+          .unwrap()
+          .len(),
+      );
+    })
+  });
+}
+
+criterion_group!(bench, call_loop, call_batch);
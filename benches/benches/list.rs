@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, Criterion};
+use hebi::*;
+
+pub fn small_lists(c: &mut Criterion) {
+  c.bench_function("small lists 10k", |b| {
+    let mut hebi = Hebi::new();
+
+    let chunk = hebi
+      .compile(indoc::indoc! {
+        r#"#!hebi
+          total := 0
+          for i in 0..10000:
+            pair := [i, i + 1]
+            total += pair[0] + pair[1]
+          total
+        "#,
+      })
+      .unwrap();
+
+    b.iter(|| {
+      black_box(hebi.run(chunk.clone()).unwrap());
+    })
+  });
+}
+
+pub fn closures(c: &mut Criterion) {
+  c.bench_function("closures 10k", |b| {
+    let mut hebi = Hebi::new();
+
+    let chunk = hebi
+      .compile(indoc::indoc! {
+        r#"#!hebi
+          fn adder(n):
+            fn add(v):
+              return v + n
+            return add
+
+          total := 0
+          for i in 0..10000:
+            total = adder(i)(total)
+          total
+        "#,
+      })
+      .unwrap();
+
+    b.iter(|| {
+      black_box(hebi.run(chunk.clone()).unwrap());
+    })
+  });
+}
+
+criterion_group!(bench, small_lists, closures);
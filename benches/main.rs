@@ -1,7 +1,9 @@
 use criterion::criterion_main;
 
 mod benches {
+  pub mod call;
   pub mod fib;
+  pub mod list;
   pub mod primes;
   pub mod startup;
 }
@@ -10,6 +12,7 @@ mod benches {
 criterion_main! {
   benches::fib::bench,
   benches::startup::bench,
+  benches::call::bench,
   benches::primes::bench,
 }
 
@@ -17,4 +20,6 @@ criterion_main! {
 criterion_main! {
   benches::fib::bench,
   benches::startup::bench,
+  benches::call::bench,
+  benches::list::bench,
 }
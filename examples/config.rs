@@ -0,0 +1,95 @@
+use hebi::prelude::*;
+use hebi::Construct;
+
+// Configs are scripts which evaluate to a table. They may compute values,
+// but they may not import modules or loop, so loading one always finishes
+// quickly and never touches anything outside of the VM.
+
+const CONFIG: &str = r#"
+workers := 4
+if profile == "release":
+  workers = 16
+
+{name: "api-" + profile, port: 8000, workers: workers, debug: profile != "release", hosts: ["localhost", host]}
+"#;
+
+struct ServerConfig {
+  name: String,
+  port: i32,
+  workers: i32,
+  debug: bool,
+  hosts: Vec<String>,
+}
+
+impl<'cx> FromValue<'cx> for ServerConfig {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> hebi::Result<Self> {
+    let Some(table) = value.as_object::<Table>(global.clone()) else {
+      hebi::fail!("the config must evaluate to a table, got `{value}`");
+    };
+    let field = |name: &str| -> hebi::Result<Value<'cx>> {
+      match table.get(name) {
+        Some(value) => Ok(value),
+        None => hebi::fail!("the config is missing `{name}`"),
+      }
+    };
+    let hosts = List::from_value(field("hosts")?, global.clone())?;
+    Ok(ServerConfig {
+      name: String::from_value(field("name")?, global.clone())?,
+      port: i32::from_value(field("port")?, global.clone())?,
+      workers: i32::from_value(field("workers")?, global.clone())?,
+      debug: bool::from_value(field("debug")?, global.clone())?,
+      hosts: hosts
+        .iter()
+        .map(|host| String::from_value(host, global.clone()))
+        .collect::<hebi::Result<_>>()?,
+    })
+  }
+}
+
+fn load(hebi: &mut Hebi, source: &str, profile: &str) -> Result<ServerConfig, String> {
+  let vars = [
+    ("profile", profile.to_string()),
+    ("host", "api.example.com".to_string()),
+  ];
+  hebi
+    .eval_with::<ServerConfig>(source, vars)
+    .map_err(|e| e.report(source, false))
+}
+
+fn main() {
+  let mut hebi = Hebi::builder()
+    .forbid(Construct::Import)
+    .forbid(Construct::While)
+    .forbid(Construct::Loop)
+    .forbid(Construct::For)
+    .finish();
+
+  for profile in ["debug", "release"] {
+    match load(&mut hebi, CONFIG, profile) {
+      Ok(config) => {
+        let mode = if config.debug { "debug" } else { "optimized" };
+        println!(
+          "{profile}: {} on port {} with {} {mode} workers, serving {}",
+          config.name,
+          config.port,
+          config.workers,
+          config.hosts.join(", "),
+        );
+      }
+      Err(e) => eprintln!("{e}"),
+    }
+  }
+
+  // configs which do something they are not allowed to, or which evaluate
+  // to the wrong thing, are rejected with an error pointing at the problem
+  for source in [
+    "while true:\n  pass",
+    "{name: \"api\", port: \"8000\", workers: 1, debug: false, hosts: []}",
+    "[1, 2, 3]",
+  ] {
+    match load(&mut hebi, source, "debug") {
+      Ok(config) => println!("unexpectedly loaded `{}`", config.name),
+      Err(e) => println!("{e}"),
+    }
+  }
+}
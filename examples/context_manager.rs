@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+
+use hebi::prelude::*;
+
+fn main() {
+  struct Transaction {
+    pending: RefCell<Vec<String>>,
+  }
+
+  let module = NativeModule::builder("db")
+    .class::<Transaction>("Transaction", |class| {
+      class
+        .init(|_| {
+          Ok(Transaction {
+            pending: RefCell::new(Vec::new()),
+          })
+        })
+        .method("insert", |scope, this| {
+          let row = scope.param::<String>(0)?;
+          this.pending.borrow_mut().push(row);
+          Ok(())
+        })
+        .enter(|_, _| println!("begin"))
+        .exit(|_, this, error| {
+          let pending = this.pending.take();
+          match error {
+            Some(error) => println!("rollback {} rows: {}", pending.len(), error.message),
+            None => println!("commit {} rows", pending.len()),
+          }
+          // don't suppress the error
+          Ok(false)
+        })
+        .finish()
+    })
+    .finish();
+
+  let mut hebi = Hebi::new();
+  hebi.register(&module);
+
+  hebi
+    .eval(
+      r#"
+from db import Transaction
+
+tx := Transaction()
+with tx:
+  tx.insert("a")
+  tx.insert("b")
+
+try:
+  tx := Transaction()
+  with tx:
+    tx.insert("c")
+    throw "constraint violated"
+catch e:
+  print e
+"#,
+    )
+    .unwrap();
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use hebi::prelude::*;
+use hebi::{Cow, ModuleLoader};
+
+// The game owns its entities, and scripts get handles to them through the
+// `engine` module. Each frame, the game calls the `update` function defined
+// by the main script, which moves the entities using the behaviours defined
+// in another script.
+
+const MAIN: &str = r#"
+from engine import entities
+import behaviours
+
+fn update(dt):
+  for entity in entities():
+    if entity.kind == "bouncer":
+      behaviours.bounce(entity, dt)
+    else:
+      behaviours.fall(entity, dt)
+"#;
+
+const BEHAVIOURS: &str = r#"
+gravity := -9.8
+
+fn bounce(entity, dt):
+  entity.x += entity.vx * dt
+  if entity.x < 0.0 || entity.x > 10.0:
+    entity.vx = -entity.vx
+
+fn fall(entity, dt):
+  entity.vy += gravity * dt
+  entity.y += entity.vy * dt
+  if entity.y < 0.0:
+    entity.y = 0.0
+    entity.vy = 0.0
+"#;
+
+struct Entity {
+  kind: &'static str,
+  x: f64,
+  y: f64,
+  vx: f64,
+  vy: f64,
+}
+
+struct EntityHandle(Arc<Mutex<Entity>>);
+
+/// Scripts are part of the game's assets, which would usually be files
+/// shipped next to the executable.
+struct Assets(HashMap<&'static str, &'static str>);
+
+impl ModuleLoader for Assets {
+  fn load(&self, path: &str) -> hebi::Result<Cow<'static, str>> {
+    match self.0.get(path) {
+      Some(source) => Ok(Cow::borrowed(*source)),
+      None => hebi::fail!("there is no script for module `{path}`"),
+    }
+  }
+}
+
+fn main() {
+  let world = vec![
+    Arc::new(Mutex::new(Entity {
+      kind: "bouncer",
+      x: 9.0,
+      y: 0.0,
+      vx: 4.0,
+      vy: 0.0,
+    })),
+    Arc::new(Mutex::new(Entity {
+      kind: "faller",
+      x: 2.0,
+      y: 5.0,
+      vx: 0.0,
+      vy: 0.0,
+    })),
+  ];
+
+  let handles = world.clone();
+  let module = NativeModule::builder("engine")
+    .function("entities", move |scope| {
+      let list = scope.new_list(handles.len());
+      for entity in handles.iter() {
+        list.push(scope.new_instance(EntityHandle(entity.clone()))?);
+      }
+      Ok(list)
+    })
+    .class::<EntityHandle>("Entity", |class| {
+      class
+        .field("kind", |_, this| this.0.lock().unwrap().kind.to_string())
+        .field_mut(
+          "x",
+          |_, this| this.0.lock().unwrap().x,
+          |_, this, value| {
+            this.0.lock().unwrap().x = value;
+            Ok(())
+          },
+        )
+        .field_mut(
+          "y",
+          |_, this| this.0.lock().unwrap().y,
+          |_, this, value| {
+            this.0.lock().unwrap().y = value;
+            Ok(())
+          },
+        )
+        .field_mut(
+          "vx",
+          |_, this| this.0.lock().unwrap().vx,
+          |_, this, value| {
+            this.0.lock().unwrap().vx = value;
+            Ok(())
+          },
+        )
+        .field_mut(
+          "vy",
+          |_, this| this.0.lock().unwrap().vy,
+          |_, this, value| {
+            this.0.lock().unwrap().vy = value;
+            Ok(())
+          },
+        )
+        .finish()
+    })
+    .finish();
+
+  let assets = Assets([("behaviours", BEHAVIOURS)].into_iter().collect());
+  let mut hebi = Hebi::builder().module_loader(assets).finish();
+  hebi.register(&module);
+
+  if let Err(e) = hebi.eval(MAIN) {
+    eprintln!("{}", e.report(MAIN, true));
+    return;
+  }
+
+  let dt = 0.25;
+  for frame in 0..8 {
+    if let Err(e) = hebi.call("update", (dt,)) {
+      eprintln!("frame {frame}: {e}");
+      return;
+    }
+    for entity in world.iter() {
+      let entity = entity.lock().unwrap();
+      println!(
+        "frame {frame}: {} at ({:.2}, {:.2})",
+        entity.kind, entity.x, entity.y
+      );
+    }
+  }
+}
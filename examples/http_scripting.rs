@@ -0,0 +1,92 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+use hebi::prelude::*;
+
+// Scripts make HTTP requests through an async native function, so the VM
+// yields to the runtime while a request is in flight instead of blocking
+// it. The server is a tiny one running on another thread, so that the
+// example does not depend on the network.
+
+const SCRIPT: &str = r#"
+from http import get
+
+user := get("/users/1")
+print "user:", user["name"]
+
+done := 0
+for id in user["todos"]:
+  try:
+    todo := get("/todos/" + id)
+    print "todo:", todo["title"]
+    if todo["done"]:
+      done += 1
+  catch e:
+    print "failed to get todo", id + ":", e["message"]
+
+{user: user["name"], done: done, total: user["todos"].len()}
+"#;
+
+fn route(path: &str) -> Option<&'static str> {
+  match path {
+    "/users/1" => Some(r#"{"name": "Ada", "todos": ["1", "2", "3"]}"#),
+    "/todos/1" => Some(r#"{"title": "write the parser", "done": true}"#),
+    "/todos/2" => Some(r#"{"title": "write the compiler", "done": false}"#),
+    _ => None,
+  }
+}
+
+fn serve() -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = format!("http://{}", listener.local_addr().unwrap());
+  std::thread::spawn(move || {
+    for stream in listener.incoming() {
+      let mut stream = stream.unwrap();
+      let mut request = String::new();
+      BufReader::new(&stream).read_line(&mut request).unwrap();
+      let path = request.split(' ').nth(1).unwrap_or("/");
+      let (status, body) = match route(path) {
+        Some(body) => ("200 OK", body),
+        None => ("404 Not Found", "not found"),
+      };
+      let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+      );
+      stream.write_all(response.as_bytes()).unwrap();
+    }
+  });
+  addr
+}
+
+async fn get(scope: Scope<'_>, client: reqwest::Client, base: String) -> hebi::Result<Value<'_>> {
+  let path = scope.param::<String>(0)?;
+  let response = client
+    .get(format!("{base}{path}"))
+    .send()
+    .await
+    .map_err(hebi::Error::user)?;
+  if !response.status().is_success() {
+    hebi::fail!("GET {path} returned {}", response.status());
+  }
+  let body = response.text().await.map_err(hebi::Error::user)?;
+  scope.global().json_to_value(&body)
+}
+
+#[tokio::main]
+async fn main() {
+  let base = serve();
+  let client = reqwest::Client::default();
+
+  let module = NativeModule::builder("http")
+    .async_function("get", move |scope| get(scope, client.clone(), base.clone()))
+    .finish();
+
+  let mut hebi = Hebi::new();
+  hebi.register(&module);
+
+  match hebi.eval_async(SCRIPT).await {
+    Ok(summary) => println!("summary: {}", summary.to_json_string().unwrap()),
+    Err(e) => eprintln!("{}", e.report(SCRIPT, true)),
+  }
+}
@@ -0,0 +1,107 @@
+use std::io::{BufRead, Write};
+
+use hebi::prelude::*;
+
+// A line-based REPL. Everything declared at the top level of an entry is a
+// global, so it is still there when the next entry is evaluated.
+//
+// Run with `-i` to type into it, otherwise it replays a short session, so
+// that it can run unattended along with the other examples.
+
+const SESSION: &str = r#"
+x := 10
+x * 2
+fn fib(n):
+  if n < 2:
+    return n
+  return fib(n - 1) + fib(n - 2)
+
+fib(x)
+:globals
+fib(
+undefined_variable
+"#;
+
+struct Repl {
+  hebi: Hebi,
+  /// The lines of an entry which spans several lines, such as a function.
+  pending: String,
+  /// The globals which exist before anything is evaluated, which `:globals`
+  /// does not list.
+  builtins: Vec<String>,
+}
+
+impl Repl {
+  fn prompt(&self) -> &'static str {
+    match self.pending.is_empty() {
+      true => "> ",
+      false => ". ",
+    }
+  }
+
+  /// Handle a line of input. Lines which open a block are collected until
+  /// an empty line ends the entry.
+  fn line(&mut self, line: &str) {
+    if !self.pending.is_empty() || line.trim_end().ends_with(':') {
+      if !line.trim().is_empty() {
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        return;
+      }
+      let entry = std::mem::take(&mut self.pending);
+      self.eval(&entry);
+      return;
+    }
+    match line.trim() {
+      "" => {}
+      ":globals" => {
+        for (name, value) in self.hebi.global().entries() {
+          if !self.builtins.iter().any(|v| v == name.as_str()) {
+            println!("{name} = {value}");
+          }
+        }
+      }
+      entry => self.eval(entry),
+    }
+  }
+
+  fn eval(&mut self, entry: &str) {
+    match self.hebi.eval(entry) {
+      Ok(value) if value.is_none() => {}
+      Ok(value) => println!("{value}"),
+      Err(e) => println!("{}", e.report(entry, false)),
+    }
+  }
+}
+
+fn main() {
+  let hebi = Hebi::new();
+  let builtins = hebi
+    .global()
+    .entries()
+    .map(|(name, _)| name.to_string())
+    .collect();
+  let mut repl = Repl {
+    hebi,
+    pending: String::new(),
+    builtins,
+  };
+
+  if std::env::args().any(|arg| arg == "-i") {
+    let stdin = std::io::stdin();
+    loop {
+      print!("{}", repl.prompt());
+      std::io::stdout().flush().unwrap();
+      let mut line = String::new();
+      if stdin.lock().read_line(&mut line).unwrap() == 0 {
+        break;
+      }
+      repl.line(line.trim_end_matches(['\r', '\n']));
+    }
+  } else {
+    for line in SESSION.trim_start().lines() {
+      println!("{}{line}", repl.prompt());
+      repl.line(line);
+    }
+  }
+}
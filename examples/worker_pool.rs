@@ -8,10 +8,11 @@ async fn main() {
   let module = NativeModule::builder("http")
     .async_function("get", move |scope| get(scope, client.clone()))
     .finish();
+  let shared = SharedGlobals::builder().native_module(&module).finish();
 
   const CONCURRENCY: usize = 16;
 
-  let pool = WorkerPool::new(&[module], CONCURRENCY);
+  let pool = WorkerPool::new(&shared, CONCURRENCY);
 
   let mut handles = vec![];
   for index in 0..CONCURRENCY {
@@ -43,13 +44,10 @@ struct WorkerPool {
 }
 
 impl WorkerPool {
-  pub fn new(modules: &[NativeModule], capacity: usize) -> Self {
+  pub fn new(shared: &SharedGlobals, capacity: usize) -> Self {
     let (tx, rx) = flume::bounded(capacity);
     for _ in 0..capacity {
-      let mut worker = Hebi::new();
-      for module in modules {
-        worker.register(module);
-      }
+      let worker = Hebi::builder().shared(shared).finish();
       tx.send(worker).unwrap();
     }
 
@@ -2,3 +2,4 @@ pub mod builder;
 pub mod disasm;
 pub mod opcode;
 pub mod operands;
+pub mod spans;
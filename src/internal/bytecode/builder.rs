@@ -6,6 +6,7 @@ use indexmap::IndexMap;
 use super::opcode::symbolic::*;
 use super::opcode::{self as op, Instruction, Opcode};
 use super::operands::{Operand, Width};
+use super::spans::{SpanMap, SpanMapBuilder};
 use crate::internal::object::{Any, ClassDescriptor, FunctionDescriptor, Ptr, Str};
 use crate::internal::value::constant::{Constant, NonNaNFloat};
 use crate::span::Span;
@@ -15,15 +16,7 @@ pub struct BytecodeBuilder {
   bytecode: Vec<u8>,
   constant_pool_builder: ConstantPoolBuilder,
   unbound_jumps: usize,
-
-  // TODO: encode spans into a flat buffer
-  // use delta-encoding to make the bytes smaller
-  // NOTE: it's fine to store the data in a flat buffer, because
-  // we iterate over the bytecode array and each time we step
-  // through bytecode,  we can also step the span buffer iterator.
-  // for random access, positions in the buffer may be saved and
-  // restored at a later point.
-  spans: Vec<Span>,
+  spans: SpanMapBuilder,
 }
 
 pub struct BasicLabel {
@@ -105,14 +98,13 @@ impl BytecodeBuilder {
       bytecode: Vec::new(),
       constant_pool_builder: ConstantPoolBuilder::new(),
       unbound_jumps: 0,
-
-      spans: Vec::new(),
+      spans: SpanMapBuilder::default(),
     }
   }
 
   fn write(&mut self, instruction: impl Instruction, span: Span) {
+    self.spans.push(self.bytecode.len(), span);
     instruction.encode(&mut self.bytecode);
-    self.spans.push(span);
   }
 
   /// Emit an instruction.
@@ -187,6 +179,49 @@ impl BytecodeBuilder {
     )
   }
 
+  /// Emit a jump which is only taken if the accumulator is `none`. Unlike
+  /// `emit_jump_if_false`, the accumulator is left untouched.
+  pub fn emit_jump_if_none(&mut self, label: &impl Label, span: impl Into<Span>) {
+    assert!(
+      !label.is_used(),
+      "more than one instruction refers to label {}",
+      label.name(),
+    );
+
+    // see [docs/emit.md#jump-instruction-encoding] for a description of how this
+    // works.
+    self.unbound_jumps += 1;
+    label.set_referrer(self.bytecode.len());
+    let offset = self.constant_pool_builder().reserve();
+    self.write(
+      JumpIfNone {
+        offset: op::Offset(offset.0),
+      },
+      span.into(),
+    )
+  }
+
+  /// Emit an instruction which installs an exception handler. The handler
+  /// begins at `label`, which must be bound later.
+  pub fn emit_push_handler(&mut self, label: &impl Label, span: impl Into<Span>) {
+    assert!(
+      !label.is_used(),
+      "more than one instruction refers to label {}",
+      label.name(),
+    );
+
+    // encoded the same way as a forward jump
+    self.unbound_jumps += 1;
+    label.set_referrer(self.bytecode.len());
+    let offset = self.constant_pool_builder().reserve();
+    self.write(
+      PushHandler {
+        offset: op::Offset(offset.0),
+      },
+      span.into(),
+    )
+  }
+
   /// Marks the current offset as a loop header and returns it for use as a
   /// target in `emit_jump_loop`.
   pub fn loop_header(&self) -> LoopHeader {
@@ -215,8 +250,12 @@ impl BytecodeBuilder {
     &mut self.constant_pool_builder
   }
 
-  pub fn finish(self) -> (Vec<u8>, Vec<Constant>) {
-    (self.bytecode, self.constant_pool_builder.constants)
+  pub fn finish(self) -> (Vec<u8>, Vec<Constant>, SpanMap) {
+    (
+      self.bytecode,
+      self.constant_pool_builder.constants,
+      self.spans.finish(),
+    )
   }
 
   fn patch_jump(&mut self, referrer_offset: usize, relative_offset: op::Offset) {
@@ -231,7 +270,7 @@ impl BytecodeBuilder {
         encoded_width = Width::Wide32;
         op = Opcode::new(self.bytecode[referrer_offset + 1]);
       }
-      v @ (Opcode::Jump | Opcode::JumpIfFalse) => {
+      v @ (Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfNone | Opcode::PushHandler) => {
         encoded_width = Width::Normal;
         op = v;
       }
@@ -254,6 +293,8 @@ impl BytecodeBuilder {
       let new_op = match op {
         Opcode::Jump => Opcode::JumpConst as u8,
         Opcode::JumpIfFalse => Opcode::JumpIfFalseConst as u8,
+        Opcode::JumpIfNone => Opcode::JumpIfNoneConst as u8,
+        Opcode::PushHandler => Opcode::PushHandlerConst as u8,
         _ => unreachable!(),
       };
       self.bytecode[opcode_offset] = new_op;
@@ -361,6 +402,8 @@ macro_rules! insert_constant_object {
 insert_constant_object!(Str, String);
 insert_constant_object!(FunctionDescriptor, Function);
 insert_constant_object!(ClassDescriptor, Class);
+#[cfg(feature = "decimal")]
+insert_constant_object!(crate::internal::object::decimal::Decimal, Decimal);
 
 impl private::Sealed for NonNaNFloat {}
 impl InsertConstant for NonNaNFloat {
@@ -16,7 +16,7 @@ fn basic_emit() {
   }, 0..0);
   builder.emit(Print, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(
     bytecode,
@@ -44,7 +44,7 @@ fn emit_constant() {
   builder.emit(LoadConst { idx: b }, 0..0);
   builder.emit(LoadConst { idx: c }, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(
     bytecode,
@@ -79,7 +79,7 @@ fn emit_forward_jump_8bit() {
   builder.bind_label(test);
   builder.emit(Return, 0..0);
 
-  let (bytecode, constants ) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(
     bytecode,
@@ -111,7 +111,7 @@ fn emit_forward_jump_8bit_overflow() {
   builder.bind_label(test);
   builder.emit(Return, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(bytecode[..2], [Opcode::JumpConst as u8, /* index */ 0],);
   assert!(bytecode[2..256].iter().all(|v| *v == Opcode::Nop as u8));
@@ -140,7 +140,7 @@ fn emit_forward_jump_16bit() {
   builder.bind_label(test);
   builder.emit(Return, 0..0);
 
-  let (bytecode, _) = builder.finish();
+  let (bytecode, _, _) = builder.finish();
 
   assert_eq!(
     bytecode[..jump_len],
@@ -179,7 +179,7 @@ fn emit_forward_jump_16bit_overflow() {
   builder.bind_label(test);
   builder.emit(Return, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(
     bytecode[..jump_len],
@@ -222,7 +222,7 @@ fn emit_forward_jump_32bit() {
   builder.bind_label(test);
   builder.emit(Return, 0..0);
 
-  let (bytecode, _) = builder.finish();
+  let (bytecode, _, _) = builder.finish();
 
   assert_eq!(
     bytecode[..jump_len],
@@ -255,7 +255,7 @@ fn emit_jump_loop() {
   builder.emit_jump_loop(&start, 0..0);
   builder.emit(Return, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert!(constants.is_empty());
   assert_eq!(
@@ -274,6 +274,135 @@ fn emit_jump_loop() {
   assert_snapshot!(Disassembly::new(&bytecode, &constants, 0, true).to_string());
 }
 
+#[test]
+fn emit_jump_loop_16bit() {
+  let mut builder = BytecodeBuilder::new();
+
+  let start = builder.loop_header();
+  builder.bind_loop_header(&start);
+  for _ in 0..256 {
+    builder.emit(Nop, 0..0);
+  }
+  builder.emit_jump_loop(&start, 0..0);
+  builder.emit(Return, 0..0);
+
+  let (bytecode, constants, _) = builder.finish();
+
+  // the offset is relative to the start of the instruction, including the
+  // width prefix
+  assert!(constants.is_empty());
+  assert!(bytecode[..256].iter().all(|v| *v == Opcode::Nop as u8));
+  assert_eq!(
+    bytecode[256..],
+    [
+      Opcode::Wide16 as u8,
+      Opcode::JumpLoop as u8,
+      // offset:
+      256u16.to_le_bytes()[0],
+      256u16.to_le_bytes()[1],
+      Opcode::Return as u8,
+    ]
+  );
+}
+
+#[test]
+fn emit_jump_loop_32bit() {
+  let mut builder = BytecodeBuilder::new();
+
+  let start = builder.loop_header();
+  builder.bind_loop_header(&start);
+  for _ in 0..65536 {
+    builder.emit(Nop, 0..0);
+  }
+  builder.emit_jump_loop(&start, 0..0);
+  builder.emit(Return, 0..0);
+
+  let (bytecode, constants, _) = builder.finish();
+
+  assert!(constants.is_empty());
+  assert_eq!(
+    bytecode[65536..],
+    [
+      Opcode::Wide32 as u8,
+      Opcode::JumpLoop as u8,
+      // offset:
+      65536u32.to_le_bytes()[0],
+      65536u32.to_le_bytes()[1],
+      65536u32.to_le_bytes()[2],
+      65536u32.to_le_bytes()[3],
+      Opcode::Return as u8,
+    ]
+  );
+}
+
+#[test]
+fn emit_jump_if_false_8bit_overflow() {
+  let mut builder = BytecodeBuilder::new();
+
+  let test = builder.label("test");
+  builder.emit_jump_if_false(&test, 0..0);
+  for _ in 0..(256 - 2) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(Return, 0..0);
+
+  let (bytecode, constants, _) = builder.finish();
+
+  assert_eq!(
+    bytecode[..2],
+    [Opcode::JumpIfFalseConst as u8, /* index */ 0]
+  );
+  assert_eq!(bytecode[256..], [Opcode::Return as u8]);
+  assert_eq!(constants.last().unwrap().as_offset().unwrap().0, 256);
+}
+
+#[test]
+fn emit_jump_if_none_8bit_overflow() {
+  let mut builder = BytecodeBuilder::new();
+
+  let test = builder.label("test");
+  builder.emit_jump_if_none(&test, 0..0);
+  for _ in 0..(256 - 2) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(Return, 0..0);
+
+  let (bytecode, constants, _) = builder.finish();
+
+  assert_eq!(
+    bytecode[..2],
+    [Opcode::JumpIfNoneConst as u8, /* index */ 0]
+  );
+  assert_eq!(bytecode[256..], [Opcode::Return as u8]);
+  assert_eq!(constants.last().unwrap().as_offset().unwrap().0, 256);
+}
+
+#[test]
+fn emit_push_handler_16bit_overflow() {
+  let mut builder = BytecodeBuilder::new();
+
+  let test = builder.label("test");
+  builder.emit_push_handler(&test, 0..0);
+  for _ in 0..(65536 - 2) {
+    builder.emit(Nop, 0..0);
+  }
+  builder.bind_label(test);
+  builder.emit(Return, 0..0);
+
+  let (bytecode, constants, _) = builder.finish();
+
+  // the reserved constant index fits in 8 bits, so the instruction is not
+  // widened, only its offset is moved into the constant pool
+  assert_eq!(
+    bytecode[..2],
+    [Opcode::PushHandlerConst as u8, /* index */ 0]
+  );
+  assert_eq!(bytecode[65536..], [Opcode::Return as u8]);
+  assert_eq!(constants.last().unwrap().as_offset().unwrap().0, 65536);
+}
+
 #[rustfmt::skip]
 #[test]
 fn emit_multi_label() {
@@ -288,7 +417,7 @@ fn emit_multi_label() {
   builder.bind_label(labels);
   builder.emit(Return, 0..0);
 
-  let (bytecode, constants) = builder.finish();
+  let (bytecode, constants, _) = builder.finish();
 
   assert_eq!(
     bytecode,
@@ -308,3 +437,35 @@ fn emit_multi_label() {
 
   assert_snapshot!(Disassembly::new(&bytecode, &constants, 0, true).to_string());
 }
+
+#[test]
+fn emit_spans() {
+  let mut builder = BytecodeBuilder::new();
+
+  builder.emit(LoadSmi { value: op::Smi(1) }, 10..20);
+  builder.emit(Store { reg: op::Register(300) }, 10..20);
+  builder.emit(LoadSmi { value: op::Smi(2) }, 4..5);
+  let end = builder.label("end");
+  builder.emit_jump(&end, 1000..1002);
+  builder.emit(Nop, 1000..1002);
+  builder.bind_label(end);
+  builder.emit(Return, 30..31);
+
+  let (bytecode, _, spans) = builder.finish();
+
+  assert_eq!(
+    spans.iter().collect::<Vec<_>>(),
+    [
+      (0, Span::from(10..20)),
+      (6, Span::from(4..5)),
+      (8, Span::from(1000..1002)),
+      (11, Span::from(30..31)),
+    ]
+  );
+  // `store` is wide, and shares a span with the previous instruction
+  assert_eq!(spans.span_at(2), Some(Span::from(10..20)));
+  assert_eq!(spans.span_at(5), Some(Span::from(10..20)));
+  assert_eq!(spans.span_at(10), Some(Span::from(1000..1002)));
+  assert_eq!(spans.span_at(bytecode.len() - 1), Some(Span::from(30..31)));
+  assert_eq!(BytecodeBuilder::new().finish().2.span_at(0), None);
+}
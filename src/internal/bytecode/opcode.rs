@@ -7,81 +7,95 @@ use super::disasm;
 use super::operands::Operand;
 pub use super::operands::Width;
 
-// TODO: update `docs/emit.md` instruction list once this stops changing
+// NOTE: `docs/src/instructions.md` is generated from `OPCODES`, run
+// `cargo xtask opcodes` after changing the instruction set.
 // TODO: decoding should return strongly typed operands
 
 instructions! {
   patch_registers, symbolic, decode, Opcode;
-  Nop,
-  Wide16,
-  Wide32,
-  Load(reg: Register),
-  Store(reg: Register),
-  LoadConst(idx: Constant),
-  LoadUpvalue(idx: Upvalue),
-  StoreUpvalue(idx: Upvalue),
-  LoadModuleVar(idx: ModuleVar),
-  StoreModuleVar(idx: ModuleVar),
-  LoadGlobal(name: Constant),
-  StoreGlobal(name: Constant),
-  LoadField(name: Constant),
-  LoadFieldOpt(name: Constant),
-  StoreField(obj: Register, name: Constant),
-  LoadIndex(obj: Register),
-  LoadIndexOpt(obj: Register),
-  StoreIndex(obj: Register, key: Register),
-  LoadSelf,
-  LoadSuper,
-  LoadNone,
-  LoadTrue,
-  LoadFalse,
-  LoadSmi(value: Smi),
-  MakeFn(desc: Constant),
-  MakeClass(desc: Constant),
-  MakeClassDerived(desc: Constant),
-  MakeDataClass(desc: Constant, parts: Register),
-  MakeDataClassDerived(desc: Constant, parts: Register),
+  Nop: None,
+  Wide16: None,
+  Wide32: None,
+  Load(reg: Register): Write,
+  Store(reg: Register): Read,
+  StoreCell(reg: Register): Read,
+  LoadConst(idx: Constant): Write,
+  LoadUpvalue(idx: Upvalue): Write,
+  StoreUpvalue(idx: Upvalue): Read,
+  LoadModuleVar(idx: ModuleVar): Write,
+  StoreModuleVar(idx: ModuleVar): Read,
+  LoadGlobal(name: Constant): Write,
+  StoreGlobal(name: Constant): Read,
+  LoadField(name: Constant): Update,
+  LoadFieldOpt(name: Constant): Update,
+  LoadModuleField(name: Constant, slot: ModuleVar): Update,
+  LoadFieldSlot(name: Constant, slot: Slot): Update,
+  StoreField(obj: Register, name: Constant): Read,
+  StoreFieldSlot(obj: Register, name: Constant, slot: Slot): Read,
+  LoadIndex(obj: Register): Update,
+  LoadIndexOpt(obj: Register): Update,
+  StoreIndex(obj: Register, key: Register): Read,
+  LoadSelf: Write,
+  LoadSuper: Write,
+  LoadNone: Write,
+  LoadTrue: Write,
+  LoadFalse: Write,
+  LoadSmi(value: Smi): Write,
+  MakeFn(desc: Constant): Write,
+  MakeClass(desc: Constant): Write,
+  MakeClassDerived(desc: Constant): Update,
+  MakeDataClass(desc: Constant, parts: Register): Write,
+  MakeDataClassDerived(desc: Constant, parts: Register): Write,
   // TODO: MakeListConst / MakeTableConst for statically known values
-  MakeList(start: Register, count: Count),
-  MakeListEmpty,
-  MakeTable(start: Register, count: Count),
-  MakeTableEmpty,
-  Jump(offset: Offset),
-  JumpConst(offset: Constant),
-  JumpLoop(offset: Offset),
-  JumpIfFalse(offset: Offset),
-  JumpIfFalseConst(offset: Constant),
-  Add(lhs: Register),
-  Sub(lhs: Register),
-  Mul(lhs: Register),
-  Div(lhs: Register),
-  Rem(lhs: Register),
-  Pow(lhs: Register),
-  Inv,
-  Not,
-  CmpEq(lhs: Register),
-  CmpNe(lhs: Register),
-  CmpGt(lhs: Register),
-  CmpGe(lhs: Register),
-  CmpLt(lhs: Register),
-  CmpLe(lhs: Register),
-  CmpType(lhs: Register),
-  Contains(lhs: Register),
-  IsNone,
-  Print,
-  PrintN(start: Register, count: Count),
-  Call(callee: Register, args: Count),
-  Call0,
-  Import(path: Constant),
-  FinalizeModule,
-  Return,
-  Yield,
+  MakeList(start: Register, count: Count): Write,
+  MakeListEmpty: Write,
+  MakeTable(start: Register, count: Count): Write,
+  MakeTableEmpty: Write,
+  Jump(offset: Offset): None,
+  JumpConst(offset: Constant): None,
+  JumpLoop(offset: Offset): None,
+  JumpIfFalse(offset: Offset): Read,
+  JumpIfFalseConst(offset: Constant): Read,
+  JumpIfNone(offset: Offset): None,
+  JumpIfNoneConst(offset: Constant): None,
+  PushHandler(offset: Offset): None,
+  PushHandlerConst(offset: Constant): None,
+  PopHandler: None,
+  Throw: Read,
+  Add(lhs: Register): Update,
+  Sub(lhs: Register): Update,
+  Mul(lhs: Register): Update,
+  Div(lhs: Register): Update,
+  Rem(lhs: Register): Update,
+  Pow(lhs: Register): Update,
+  Inv: Update,
+  Not: Update,
+  CmpEq(lhs: Register): Update,
+  CmpNe(lhs: Register): Update,
+  CmpGt(lhs: Register): Update,
+  CmpGe(lhs: Register): Update,
+  CmpLt(lhs: Register): Update,
+  CmpLe(lhs: Register): Update,
+  CmpType(lhs: Register): Update,
+  Contains(lhs: Register): Update,
+  IsNone: Update,
+  Print: Read,
+  PrintN(start: Register, count: Count): None,
+  Call(callee: Register, args: Count): Write,
+  Call0: Update,
+  CallMethod(obj: Register, name: Constant, args: Count): Write,
+  CallInto(callee: Register, args: Count, dst: Register): Write,
+  Import(path: Constant): Write,
+  FinalizeModule: Write,
+  Return: Read,
+  Yield: None,
 }
 
 operand_type!(Register, u32, "r{v}");
 operand_type!(Constant, u32, "[{v}]");
 operand_type!(Upvalue, u32, "^{v}");
 operand_type!(ModuleVar, u32, "{v}");
+operand_type!(Slot, u32, "{v}");
 operand_type!(Offset, u32, "{v}");
 operand_type!(Smi, i32, "{v}");
 operand_type!(Count, u32, "{v}");
@@ -162,6 +176,52 @@ impl ModuleVar {
   }
 }
 
+impl Slot {
+  pub fn index(&self) -> usize {
+    self.0 as usize
+  }
+}
+
+/// Describes an instruction: its name in disassembly, its operands, and how
+/// it uses the accumulator.
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeInfo {
+  pub opcode: Opcode,
+  pub name: &'static str,
+  pub operands: &'static [OperandInfo],
+  pub acc: AccEffect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OperandInfo {
+  pub name: &'static str,
+  /// The operand type, e.g. `Register` or `Constant`.
+  pub kind: &'static str,
+}
+
+/// What an instruction does with the accumulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccEffect {
+  /// The accumulator is left untouched.
+  None,
+  /// The accumulator is consumed.
+  Read,
+  /// The accumulator is overwritten without being read.
+  Write,
+  /// The accumulator is consumed, and then overwritten with the result.
+  Update,
+}
+
+impl AccEffect {
+  pub fn reads(self) -> bool {
+    matches!(self, AccEffect::Read | AccEffect::Update)
+  }
+
+  pub fn writes(self) -> bool {
+    matches!(self, AccEffect::Write | AccEffect::Update)
+  }
+}
+
 pub trait Operands {
   type Operands: Operand + Sized + Default;
 }
@@ -184,10 +244,28 @@ pub trait Instruction: disasm::Disassemble + private::Sealed {
         | Opcode::JumpLoop
         | Opcode::JumpIfFalse
         | Opcode::JumpIfFalseConst
+        | Opcode::JumpIfNone
+        | Opcode::JumpIfNoneConst
+        | Opcode::PushHandler
+        | Opcode::PushHandlerConst
     )
   }
 }
 
+/// Iterate over the instructions in `buf`, yielding the width, opcode, and
+/// encoded operands of each one.
+pub fn instructions(mut buf: &[u8]) -> impl Iterator<Item = (Width, Opcode, &[u8])> {
+  std::iter::from_fn(move || {
+    if buf.is_empty() {
+      return None;
+    }
+    let (width, opcode, operands) = read_instruction(buf)?;
+    let (operands, remainder) = operands.split_at(opcode.info().operands.len() * width.size());
+    buf = remainder;
+    Some((width, opcode, operands))
+  })
+}
+
 fn read_instruction(buf: &[u8]) -> Option<(Width, Opcode, &[u8])> {
   let width = Width::decode(buf);
   let (opcode, operands) = match width {
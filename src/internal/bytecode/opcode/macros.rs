@@ -45,7 +45,7 @@ macro_rules! __patch_register {
 }
 
 macro_rules! instructions {
-  ($patch_registers:ident, $symbolic:ident, $decode:ident, $Opcode:ident; $($name:ident $(($($operand:ident : $ty:ident),+))?),* $(,)?) => {
+  ($patch_registers:ident, $symbolic:ident, $decode:ident, $Opcode:ident; $($name:ident $(($($operand:ident : $ty:ident),+))? : $acc:ident),* $(,)?) => {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
     #[repr(u8)]
     pub enum $Opcode {
@@ -76,6 +76,25 @@ macro_rules! instructions {
       let _ = ::core::mem::transmute::<$Opcode, u8>;
     };
 
+    /// Every instruction in the instruction set, indexed by opcode.
+    pub static OPCODES: &[OpcodeInfo] = &[
+      $(
+        OpcodeInfo {
+          opcode: $Opcode::$name,
+          name: ::paste::paste!(stringify!([<$name:snake>])),
+          operands: &[$($(OperandInfo { name: stringify!($operand), kind: stringify!($ty) }),+)?],
+          acc: AccEffect::$acc,
+        }
+      ),*
+    ];
+
+    impl $Opcode {
+      #[inline]
+      pub fn info(self) -> &'static OpcodeInfo {
+        &OPCODES[self as usize]
+      }
+    }
+
     pub mod $symbolic {
       use super::*;
 
@@ -105,7 +124,7 @@ macro_rules! instructions {
           fn disassemble(&self, constants: &[crate::internal::value::constant::Constant]) -> disasm::Instruction {
             let Self { $($($operand,)+)? } = self;
 
-            let _name: &'static str = ::paste::paste!(stringify!([<$name:snake>]));
+            let _name: &'static str = $Opcode::$name.info().name;
             let _operands: Vec<&dyn ::std::fmt::Display> = vec![$($(&*$operand),+)?];
             let _constant: Option<crate::internal::value::constant::Constant> =
               __get_constant!(constants; $($(($operand, $ty))+)?);
@@ -38,7 +38,7 @@ fn register_patching() {
 
   let map = vec![127usize; 65537];
 
-  let (mut bytecode, _) = builder.finish();
+  let (mut bytecode, _, _) = builder.finish();
 
   assert_eq!(
     bytecode,
@@ -70,3 +70,35 @@ fn register_patching() {
     ]
   );
 }
+
+#[test]
+fn opcode_table() {
+  assert_eq!(OPCODES.len(), Opcode::Yield as usize + 1);
+  for (index, info) in OPCODES.iter().enumerate() {
+    assert_eq!(info.opcode as usize, index);
+    assert_eq!(Opcode::new(index as u8).info().name, info.name);
+  }
+
+  let store_field = Opcode::StoreField.info();
+  assert_eq!(store_field.name, "store_field");
+  assert_eq!(
+    store_field
+      .operands
+      .iter()
+      .map(|operand| (operand.name, operand.kind))
+      .collect::<Vec<_>>(),
+    [("obj", "Register"), ("name", "Constant")]
+  );
+  assert!(store_field.acc.reads() && !store_field.acc.writes());
+  assert!(Opcode::Add.info().acc.reads() && Opcode::Add.info().acc.writes());
+  assert_eq!(Opcode::Nop.info().operands.len(), 0);
+
+  let mut buf = vec![];
+  StoreIndex {
+    obj: Register(1),
+    key: Register(2),
+  }
+  .encode(&mut buf);
+  let (instruction, _) = decode(&buf).unwrap();
+  assert_eq!(instruction.disassemble(&[]).name, "store_index");
+}
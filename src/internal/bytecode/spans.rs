@@ -0,0 +1,162 @@
+//! Mapping from bytecode offsets back to source spans.
+//!
+//! Every instruction is emitted together with the span of the syntax node
+//! which produced it. Storing a full `Span` per instruction would double the
+//! size of most functions, so the spans are delta-encoded into a flat buffer
+//! of LEB128 varints instead. Each entry is:
+//!
+//! - the distance from the previous entry's offset
+//! - the distance from the previous entry's `span.start` (zigzag-encoded,
+//!   because it may be negative)
+//! - the length of the span
+//!
+//! Consecutive instructions which share a span are only stored once, so an
+//! entry applies to every instruction up to the next entry.
+//!
+//! Random access has to walk the buffer from the start, which is fine for
+//! error reporting. Anything which steps through the bytecode in order can
+//! step through [`SpanMap::iter`] alongside it instead.
+
+use std::sync::Arc;
+
+use crate::span::Span;
+
+/// Cloning a map shares its buffer.
+#[derive(Clone, Default)]
+pub struct SpanMap {
+  data: Arc<[u8]>,
+}
+
+impl SpanMap {
+  /// Find the span of the instruction at `pc`.
+  ///
+  /// `pc` does not have to point to the first byte of an instruction.
+  pub fn span_at(&self, pc: usize) -> Option<Span> {
+    let mut span = None;
+    for (offset, entry) in self.iter() {
+      if offset > pc {
+        break;
+      }
+      span = Some(entry);
+    }
+    span
+  }
+
+  /// Iterate over `(offset, span)` pairs, in ascending order of `offset`.
+  pub fn iter(&self) -> Iter<'_> {
+    Iter {
+      data: &self.data,
+      pc: 0,
+      start: 0,
+    }
+  }
+
+  /// Size of the encoded table in bytes.
+  pub fn size(&self) -> usize {
+    self.data.len()
+  }
+
+  /// The encoded table.
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// A table which was encoded by [`SpanMapBuilder`], as returned by
+  /// [`SpanMap::as_bytes`].
+  pub fn from_bytes(data: Vec<u8>) -> Self {
+    Self { data: data.into() }
+  }
+}
+
+#[derive(Default)]
+pub struct SpanMapBuilder {
+  data: Vec<u8>,
+  last: Option<(usize, Span)>,
+}
+
+impl SpanMapBuilder {
+  /// Record that the instruction at `pc` was emitted for `span`.
+  ///
+  /// `pc` must not be lower than in any previous call.
+  pub fn push(&mut self, pc: usize, span: Span) {
+    let (last_pc, last_start) = match self.last {
+      Some((_, last_span)) if last_span == span => return,
+      Some((last_pc, last_span)) => (last_pc, last_span.start),
+      None => (0, 0),
+    };
+    debug_assert!(pc >= last_pc, "span pushed out of order");
+
+    write_varint(&mut self.data, (pc - last_pc) as u64);
+    write_varint(
+      &mut self.data,
+      zigzag(span.start as i64 - last_start as i64),
+    );
+    write_varint(&mut self.data, (span.end - span.start) as u64);
+    self.last = Some((pc, span));
+  }
+
+  pub fn finish(self) -> SpanMap {
+    SpanMap {
+      data: self.data.into(),
+    }
+  }
+}
+
+pub struct Iter<'a> {
+  data: &'a [u8],
+  pc: usize,
+  start: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+  type Item = (usize, Span);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.data.is_empty() {
+      return None;
+    }
+    let pc_delta = read_varint(&mut self.data) as usize;
+    let start_delta = unzigzag(read_varint(&mut self.data));
+    let len = read_varint(&mut self.data) as usize;
+
+    self.pc += pc_delta;
+    self.start = (self.start as i64 + start_delta) as usize;
+    Some((
+      self.pc,
+      Span {
+        start: self.start,
+        end: self.start + len,
+      },
+    ))
+  }
+}
+
+pub(crate) fn zigzag(v: i64) -> u64 {
+  ((v << 1) ^ (v >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(v: u64) -> i64 {
+  ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+  while v >= 0x80 {
+    buf.push((v as u8) | 0x80);
+    v >>= 7;
+  }
+  buf.push(v as u8);
+}
+
+fn read_varint(buf: &mut &[u8]) -> u64 {
+  let mut value = 0u64;
+  let mut shift = 0;
+  loop {
+    let byte = buf[0];
+    *buf = &buf[1..];
+    value |= ((byte & 0x7F) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return value;
+    }
+    shift += 7;
+  }
+}
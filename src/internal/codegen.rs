@@ -2,11 +2,16 @@
 #![allow(dead_code)]
 
 mod expr;
+mod hoist;
+pub mod hover;
+mod inline;
 mod regalloc;
 mod stmt;
 
 use indexmap::{IndexMap, IndexSet};
 
+pub use self::hover::hover;
+use self::hover::HoverKind;
 use self::regalloc::{RegAlloc, Register, Slice};
 use super::bytecode::builder::{BytecodeBuilder, InsertConstant, LoopHeader, MultiLabel};
 use super::bytecode::opcode::symbolic::*;
@@ -14,38 +19,99 @@ use super::bytecode::opcode::{self as op};
 use super::object;
 use super::object::function;
 use super::object::ptr::Ptr;
-use super::syntax::ast;
-use super::vm::global::Global;
-use crate::span::Span;
+use super::object::string::StrSet;
+use super::syntax::{ast, SyntaxError};
+use super::value::Value;
+use super::vm::global::{Construct, Global};
+use crate::span::{Span, SpannedError};
+use crate::util::did_you_mean;
 use crate::Cow;
 
+/// The maximum number of registers a single function may use.
+pub const MAX_REGISTERS: usize = u16::MAX as usize;
+
+/// The maximum number of entries in a single function's constant pool.
+pub const MAX_CONSTANTS: usize = u32::MAX as usize;
+
 pub fn emit<'src>(
   global: Global,
   ast: &'src ast::Module<'src>,
   name: impl Into<Cow<'src, str>>,
   is_root: bool,
-) -> Ptr<object::ModuleDescriptor> {
+) -> Result<Ptr<object::ModuleDescriptor>, SyntaxError> {
+  emit_with_vars(global, ast, name, is_root, &[])
+}
+
+/// Like [`emit`], but the module root function takes one parameter for
+/// each of `vars`, which are visible to the module as local variables.
+pub fn emit_with_vars<'src>(
+  global: Global,
+  ast: &'src ast::Module<'src>,
+  name: impl Into<Cow<'src, str>>,
+  is_root: bool,
+  vars: &[&'src str],
+) -> Result<Ptr<object::ModuleDescriptor>, SyntaxError> {
   let name = name.into();
 
-  let mut module = State::new(global.clone(), ast, name.clone(), is_root).emit_module();
+  let mut state = State::new(global.clone(), ast, name.clone(), is_root, vars.len());
+  if global.inline_functions() {
+    state.inline = Some(inline::Inlining::new(ast));
+  }
+  state.emit_module(vars);
+  if global.strict_globals() || ast.pragmas.strict {
+    state.check_global_reads();
+  }
 
   let name = global.alloc(object::Str::owned(name));
   // NOTE: no need to handle `.upvalues` here,
   // because the module root never has any upvalues
-  let root = module.functions.pop().unwrap().finish().ptr;
-  let module_vars = module.vars;
+  let root = state
+    .module
+    .functions
+    .pop()
+    .unwrap()
+    .finish((0..0).into(), &mut state.errors)
+    .ptr;
+  let module_vars = state.module.vars;
+  let constructs = state.module.constructs;
+
+  if !state.errors.is_empty() {
+    // functions are finished innermost first, and strict mode checks run
+    // last, so put the errors back into source order
+    let mut errors = state.errors;
+    errors.sort_by_key(|e| e.span.start);
+    return Err(SyntaxError::new(errors));
+  }
 
-  global.alloc(object::ModuleDescriptor {
+  Ok(global.alloc(object::ModuleDescriptor {
     name,
     root,
     module_vars,
-  })
+    constructs,
+  }))
 }
 
 struct State<'src> {
   global: Global,
   ast: &'src ast::Module<'src>,
   module: Module<'src>,
+  errors: Vec<SpannedError>,
+  /// Globals read by the module, used to check for undefined globals in
+  /// strict mode.
+  global_reads: Vec<(Cow<'src, str>, Span)>,
+  /// Globals assigned by the module.
+  global_writes: IndexSet<Cow<'src, str>>,
+  /// Paths of the modules bound by `import` statements, by variable name.
+  imports: IndexMap<Cow<'src, str>, String>,
+  /// Set when emitting only to find what is known at a position, see
+  /// [`hover`].
+  hover: Option<hover::Hovering>,
+  /// Set when calls to tiny functions are inlined.
+  inline: Option<inline::Inlining<'src>>,
+  /// Fields of the classes whose methods are being emitted, innermost last.
+  /// `None` for a class with a parent, where the slots of the fields in its
+  /// instances are only known at runtime.
+  class_fields: Vec<Option<Ptr<object::Table>>>,
 }
 
 impl<'src> State<'src> {
@@ -54,21 +120,51 @@ impl<'src> State<'src> {
     ast: &'src ast::Module<'src>,
     name: impl Into<Cow<'src, str>>,
     is_root: bool,
+    num_vars: usize,
   ) -> Self {
     Self {
       global: global.clone(),
       ast,
       module: Module {
         is_root,
-        vars: IndexSet::new(),
+        vars: StrSet::default(),
+        constructs: Vec::new(),
         functions: vec![Function::new(
           global,
           name,
-          function::Params::default(),
+          function::Params {
+            has_self: false,
+            min: num_vars as u16,
+            max: num_vars as u16,
+          },
+          function::Signature::default(),
           false,
           false,
         )],
       },
+      errors: Vec::new(),
+      global_reads: Vec::new(),
+      global_writes: IndexSet::new(),
+      imports: IndexMap::new(),
+      hover: None,
+      inline: None,
+      class_fields: Vec::new(),
+    }
+  }
+
+  fn check_global_reads(&mut self) {
+    for (name, span) in std::mem::take(&mut self.global_reads) {
+      if !self.global_writes.contains(&name) && self.global.get(&name).is_none() {
+        let globals = self.global.entries().map(|(key, _)| key.to_string());
+        let writes = self.global_writes.iter().map(|name| name.to_string());
+        self.errors.push(SpannedError::new(
+          format!(
+            "undefined global `{name}`{}",
+            did_you_mean(&name, writes.chain(globals))
+          ),
+          span,
+        ));
+      }
     }
   }
 
@@ -101,10 +197,20 @@ impl<'src> State<'src> {
     self.current_function().regalloc.alloc_slice(n)
   }
 
+  /// Allocate the registers for the callee or receiver of a call, followed
+  /// by its arguments. The VM starts the frame of the function it calls at
+  /// these registers, so every register after them must be unused during the
+  /// call, which is only the case if they are allocated right before it.
+  fn alloc_call_window(&mut self, n: usize) -> Slice {
+    self.current_function().regalloc.alloc_window(n)
+  }
+
   fn emit_var(&mut self, name: impl Into<Cow<'src, str>>, span: Span) {
     let name = name.into();
+    let hovered = self.hover.is_some().then(|| name.clone());
     if self.is_global_scope() {
       if self.module.is_root {
+        self.global_writes.insert(name.clone());
         let name = self.constant_name(name);
         self.builder().emit(StoreGlobal { name }, span);
       } else {
@@ -116,15 +222,22 @@ impl<'src> State<'src> {
       self.emit_store(register.clone(), span);
       self.declare_local(name, register);
     }
+    if let Some(name) = hovered {
+      self.remember(&name, None);
+    }
   }
 
   fn declare_local(&mut self, name: impl Into<Cow<'src, str>>, register: Register) {
+    let hovering = self.hover.is_some();
     let function = self.current_function();
 
     let _ = register.access(); // ensure liveness at time of declaration
     let name = name.into();
 
     let key = (function.scope, name);
+    if hovering {
+      function.known.insert(key.clone(), None);
+    }
     let existing = function.locals.insert(key, register.clone());
 
     if let Some(local) = existing {
@@ -207,11 +320,13 @@ impl<'src> State<'src> {
 
   fn emit_get(&mut self, name: impl Into<Cow<'src, str>>, span: Span) {
     let name = name.into();
+    self.hover_variable(&name, span);
     match self.resolve_var(name.clone()) {
       Get::Local(reg) => self.builder().emit(Load { reg: reg.access() }, span),
       Get::Upvalue(idx) => self.builder().emit(LoadUpvalue { idx }, span),
       Get::ModuleVar(idx) => self.builder().emit(LoadModuleVar { idx }, span),
       Get::Global => {
+        self.global_reads.push((name.clone(), span));
         let name = self.constant_name(name);
         self.builder().emit(LoadGlobal { name }, span)
       }
@@ -243,6 +358,7 @@ impl<'src> State<'src> {
       self.global.clone(),
       func.name.lexeme(),
       function::Params::from_ast_func(func),
+      self.signature(func),
       func.has_yield,
       is_init,
     ));
@@ -263,15 +379,34 @@ impl<'src> State<'src> {
     // the point of this is to give access to:
     // - `self` in methods.
     // - the function being called in recursive functions.
+    //
+    // a decorated function refers to itself through the variable which
+    // holds the decorated function instead.
     if let Some(callee) = &callee {
-      self.declare_local(func.name.lexeme(), callee.clone());
+      if func.decorators.is_empty() {
+        self.declare_local(func.name.lexeme(), callee.clone());
+        let known = self.infer_function(func, None);
+        self.remember(func.name.as_str(), known);
+      } else {
+        let _ = callee.access();
+      }
     }
     if let Some(receiver) = &receiver {
       self.declare_local("self", receiver.clone());
     }
 
     // emit default values
+    //
+    // constant defaults are in the function's signature instead, and are
+    // filled in by the call itself
     for (i, param) in func.params.pos.iter().enumerate() {
+      let is_constant = matches!(
+        self.current_function().signature.params[i].default,
+        function::ParamDefault::Constant(_)
+      );
+      if is_constant {
+        continue;
+      }
       if let Some(default) = &param.default {
         let next = self.builder().label("next");
         self.emit_load(positional.get(i), param.span());
@@ -311,7 +446,12 @@ impl<'src> State<'src> {
 
     self.current_function().leave_scope();
 
-    let function = self.module.functions.pop().unwrap().finish();
+    let function = self
+      .module
+      .functions
+      .pop()
+      .unwrap()
+      .finish(func.name.span, &mut self.errors);
 
     self
       .current_function()
@@ -321,9 +461,13 @@ impl<'src> State<'src> {
     function
   }
 
-  fn emit_module(mut self) -> Module<'src> {
-    let callee = self.alloc_register();
+  fn emit_module(&mut self, vars: &[&'src str]) {
+    let param_slice = self.alloc_register_slice(1 + vars.len());
+    let callee = param_slice.get(0);
     self.current_function().enter_scope();
+    for (i, var) in vars.iter().enumerate() {
+      self.declare_local(*var, param_slice.get(i + 1));
+    }
     for stmt in self.ast.body.iter() {
       self.emit_stmt(stmt);
     }
@@ -333,8 +477,58 @@ impl<'src> State<'src> {
     }
     self.builder().emit(Return, 0..0);
     let _ = callee.access();
+  }
+}
+
+impl<'src> State<'src> {
+  fn signature(&self, func: &ast::Func<'src>) -> function::Signature {
+    let params = func
+      .params
+      .pos
+      .iter()
+      .map(|param| function::Param {
+        name: self.global.intern(param.name.to_string()),
+        default: match &param.default {
+          None => function::ParamDefault::None,
+          Some(default) => match self.const_eval(default) {
+            Some(value) => function::ParamDefault::Constant(value),
+            None => function::ParamDefault::Expr,
+          },
+        },
+      })
+      .collect();
+    function::Signature { params }
+  }
 
-    self.module
+  /// Evaluate `expr` at compile time, if it is a constant.
+  ///
+  /// Lists and tables are not constants, because they may be mutated.
+  fn const_eval(&self, expr: &ast::Expr<'src>) -> Option<Value> {
+    match &**expr {
+      ast::ExprKind::Literal(literal) => match &**literal {
+        ast::Literal::None => Some(Value::none()),
+        ast::Literal::Int(v) => Some(Value::int(*v)),
+        ast::Literal::Float(v) => Some(Value::float(*v)),
+        ast::Literal::Bool(v) => Some(Value::bool(*v)),
+        ast::Literal::String(v) => Some(Value::object(self.global.intern(v.to_string()))),
+        #[cfg(feature = "decimal")]
+        ast::Literal::Decimal(_) => None,
+        ast::Literal::List(_) | ast::Literal::Table(_) => None,
+      },
+      ast::ExprKind::Unary(unary) => {
+        let value = self.const_eval(&unary.right)?;
+        match unary.op {
+          ast::UnaryOp::Plus if value.is_int() || value.is_float() => Some(value),
+          ast::UnaryOp::Minus => match (value.clone().to_int(), value.to_float()) {
+            (Some(v), _) => v.checked_neg().map(Value::int),
+            (_, Some(v)) => Some(Value::float(-v)),
+            _ => None,
+          },
+          _ => None,
+        }
+      }
+      _ => None,
+    }
   }
 }
 
@@ -359,7 +553,8 @@ impl function::Params {
 
 struct Module<'src> {
   is_root: bool,
-  vars: IndexSet<Ptr<object::Str>>,
+  vars: StrSet,
+  constructs: Vec<Construct>,
   functions: Vec<Function<'src>>,
 }
 
@@ -380,12 +575,26 @@ struct Function<'src> {
   regalloc: RegAlloc,
 
   params: function::Params,
+  signature: function::Signature,
   locals: IndexMap<(Scope, Cow<'src, str>), Register>,
+  /// What is known about the values of `locals`, only tracked for
+  /// [`hover`].
+  known: IndexMap<(Scope, Cow<'src, str>), Option<HoverKind>>,
   upvalues: IndexMap<Cow<'src, str>, Upvalue>,
   scope: Scope,
 
-  is_in_opt_expr: bool,
+  /// End of the optional chain (`?a.b()[c]`) the current position is in.
+  /// Every link of the chain jumps here if its receiver is `none`.
+  opt_end: Option<MultiLabel>,
   current_loop: Option<Loop>,
+  /// Number of `try` blocks the current position is nested in.
+  try_depth: usize,
+  /// `with` blocks and `for` loops over iterators the current position is
+  /// nested in, innermost last.
+  cleanups: Vec<Cleanup>,
+  /// What was hoisted out of the loops the current position is nested in,
+  /// innermost last.
+  hoisted: Vec<hoist::Hoisted>,
 
   inner_functions: Vec<Ptr<object::FunctionDescriptor>>,
 }
@@ -395,6 +604,7 @@ impl<'src> Function<'src> {
     global: Global,
     name: impl Into<Cow<'src, str>>,
     params: function::Params,
+    signature: function::Signature,
     is_generator: bool,
     is_init: bool,
   ) -> Self {
@@ -409,13 +619,18 @@ impl<'src> Function<'src> {
       regalloc: RegAlloc::new(),
 
       params,
+      signature,
       locals: IndexMap::new(),
+      known: IndexMap::new(),
       upvalues: IndexMap::new(),
 
       scope: Scope(0),
 
-      is_in_opt_expr: false,
+      opt_end: None,
       current_loop: None,
+      try_depth: 0,
+      cleanups: Vec::new(),
+      hoisted: Vec::new(),
 
       inner_functions: Vec::new(),
     }
@@ -434,22 +649,46 @@ impl<'src> Function<'src> {
       }
       retain
     });
+    self.known.retain(|(scope, _), _| *scope != current_scope);
     self.scope.0 -= 1;
   }
 
-  fn enter_loop_body(&mut self, start: LoopHeader, end: MultiLabel) -> Option<Loop> {
-    self.current_loop.replace(Loop { start, end })
+  fn enter_loop_body(
+    &mut self,
+    start: LoopHeader,
+    end: MultiLabel,
+    iter: Option<Register>,
+  ) -> Option<Loop> {
+    let try_depth = self.try_depth;
+    let cleanups = self.cleanups.len();
+    let closes = iter.is_some();
+    if let Some(iter) = iter {
+      self.cleanups.push(Cleanup {
+        kind: CleanupKind::Close(iter),
+        try_depth,
+      });
+    }
+    self.current_loop.replace(Loop {
+      start,
+      end,
+      try_depth,
+      cleanups,
+      closes,
+    })
   }
 
   fn leave_loop_body(&mut self, previous: Option<Loop>) -> Loop {
     let current = self.current_loop.take().unwrap();
+    if current.closes {
+      self.cleanups.pop();
+    }
     if let Some(previous) = previous {
       self.current_loop = Some(previous);
     }
     current
   }
 
-  fn resolve_local(&self, name: &Cow<'src, str>) -> Option<Register> {
+  fn resolve_local(&self, name: &str) -> Option<Register> {
     self
       .locals
       .iter()
@@ -458,9 +697,33 @@ impl<'src> Function<'src> {
       .map(|(_, register)| register.clone())
   }
 
-  fn finish(self) -> EmittedFunction<'src> {
+  /// Errors about exceeded limits are reported at `span`, but the function is
+  /// finished regardless, so that emit can carry on and report every
+  /// function which is too large.
+  fn finish(self, span: Span, errors: &mut Vec<SpannedError>) -> EmittedFunction<'src> {
     let (frame_size, register_map) = self.regalloc.finish();
-    let (mut bytecode, constants) = self.builder.finish();
+    let (mut bytecode, constants, spans) = self.builder.finish();
+
+    if frame_size > MAX_REGISTERS {
+      errors.push(SpannedError::new(
+        format!(
+          "function `{}` is too large: {frame_size} registers needed, but the limit is \
+           {MAX_REGISTERS}",
+          self.name
+        ),
+        span,
+      ));
+    }
+    if constants.len() > MAX_CONSTANTS {
+      errors.push(SpannedError::new(
+        format!(
+          "function `{}` is too large: {} constants needed, but the limit is {MAX_CONSTANTS}",
+          self.name,
+          constants.len()
+        ),
+        span,
+      ));
+    }
 
     // patch registers in bytecode
     op::patch_registers(&mut bytecode, &register_map);
@@ -481,6 +744,7 @@ impl<'src> Function<'src> {
       self.global.intern(self.name.to_string()),
       self.is_generator,
       self.params,
+      self.signature,
       self
         .upvalues
         .values()
@@ -492,6 +756,8 @@ impl<'src> Function<'src> {
       frame_size,
       bytecode,
       constants,
+      spans,
+      span,
     ));
     let upvalues = Upvalues(self.upvalues);
 
@@ -531,6 +797,30 @@ enum Get {
 struct Loop {
   start: LoopHeader,
   end: MultiLabel,
+  /// Value of `Function::try_depth` outside of the loop.
+  try_depth: usize,
+  /// Length of `Function::cleanups` outside of the loop.
+  cleanups: usize,
+  /// Whether the loop is a `for` loop over an iterator, which is closed when
+  /// the loop is left early.
+  closes: bool,
+}
+
+/// A block which has to be cleaned up by any jump out of it.
+struct Cleanup {
+  kind: CleanupKind,
+  /// Value of `Function::try_depth` outside of the block.
+  try_depth: usize,
+}
+
+#[derive(Clone)]
+enum CleanupKind {
+  /// A `with` block, left by calling `__exit__` on the context manager in
+  /// the register.
+  Exit(Register),
+  /// A `for` loop, left early by calling `close` on the iterator in the
+  /// register, if it has one.
+  Close(Register),
 }
 
 #[repr(transparent)]
@@ -19,7 +19,20 @@ impl<'src> State<'src> {
     }
   }
 
+  /// Emit `expr` and store its value in `dst`. A call writes its result to
+  /// `dst` directly, instead of leaving it in the accumulator.
+  pub(super) fn emit_expr_into(&mut self, expr: &'src ast::Expr<'src>, dst: Register) {
+    if let ast::ExprKind::Call(call) = &**expr {
+      if self.emit_call_into(call, &dst, expr.span) {
+        return;
+      }
+    }
+    self.emit_expr(expr);
+    self.emit_store(dst, expr.span);
+  }
+
   fn emit_literal_expr(&mut self, expr: &'src ast::Literal<'src>, span: Span) {
+    self.hover_at(span, |_| hover::literal(expr).map(HoverKind::Literal));
     match expr {
       ast::Literal::None => self.builder().emit(LoadNone, span),
       ast::Literal::Int(v) => self.builder().emit(LoadSmi { value: op::Smi(*v) }, span),
@@ -27,7 +40,13 @@ impl<'src> State<'src> {
         // float is 4 bits so cannot be stored inline,
         // but it is interned
         let num = self.constant_value(NonNaNFloat::try_from(*v).unwrap());
-        self.builder().emit(LoadConst { idx: num }, span);
+        self.emit_load_const(num, span);
+      }
+      #[cfg(feature = "decimal")]
+      ast::Literal::Decimal(v) => {
+        let decimal = self.global.alloc(*v);
+        let decimal = self.constant_value(decimal);
+        self.builder().emit(LoadConst { idx: decimal }, span);
       }
       ast::Literal::Bool(v) => match v {
         true => self.builder().emit(LoadTrue, span),
@@ -36,7 +55,7 @@ impl<'src> State<'src> {
       ast::Literal::String(v) => {
         // `const_` interns the string
         let str = self.constant_name(v);
-        self.builder().emit(LoadConst { idx: str }, span);
+        self.emit_load_const(str, span);
       }
       ast::Literal::List(list) => {
         if list.is_empty() {
@@ -47,8 +66,7 @@ impl<'src> State<'src> {
         let items = self.alloc_register_slice(list.len());
 
         for (i, value) in list.iter().enumerate() {
-          self.emit_expr(value);
-          self.emit_store(items.get(i), value.span);
+          self.emit_expr_into(value, items.get(i));
         }
         self.builder().emit(
           MakeList {
@@ -71,10 +89,8 @@ impl<'src> State<'src> {
         let pairs = self.alloc_register_slice(table.len() * 2);
 
         for (i, (key, value)) in table.iter().enumerate() {
-          self.emit_expr(key);
-          self.emit_store(pairs.get(i * 2), key.span);
-          self.emit_expr(value);
-          self.emit_store(pairs.get(i * 2 + 1), value.span);
+          self.emit_expr_into(key, pairs.get(i * 2));
+          self.emit_expr_into(value, pairs.get(i * 2 + 1));
         }
         self.builder().emit(
           MakeTable {
@@ -99,12 +115,12 @@ impl<'src> State<'src> {
       ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe => {
         return self.emit_logical_expr(expr, span)
       }
+      ast::BinaryOp::Pipe => return self.emit_pipe_expr(expr, span),
       _ => {}
     }
 
     let lhs = self.alloc_register();
-    self.emit_expr(&expr.left);
-    self.emit_store(lhs.clone(), expr.left.span);
+    self.emit_expr_into(&expr.left, lhs.clone());
     self.emit_expr(&expr.right);
 
     let lhs = lhs.access();
@@ -123,7 +139,48 @@ impl<'src> State<'src> {
       ast::BinaryOp::LessEq => self.builder().emit(CmpLe { lhs }, span),
       ast::BinaryOp::Is => self.builder().emit(CmpType { lhs }, span),
       ast::BinaryOp::In => self.builder().emit(Contains { lhs }, span),
-      ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe => unreachable!(),
+      ast::BinaryOp::And | ast::BinaryOp::Or | ast::BinaryOp::Maybe | ast::BinaryOp::Pipe => {
+        unreachable!()
+      }
+    }
+  }
+
+  fn emit_pipe_expr(&mut self, expr: &'src ast::Binary<'src>, span: Span) {
+    /*
+      <left> |> <f>(<args>)
+      v = <left>
+      f(v, <args>)
+
+      the left side is evaluated first, because that is the order
+      in which a pipeline reads.
+    */
+    let (target, args, opt) = match &*expr.right {
+      ast::ExprKind::Call(call) => (&call.target, &call.args[..], call.opt),
+      _ => (&expr.right, &[][..], false),
+    };
+
+    let regs = self.alloc_call_window(2 + args.len());
+    let callee = regs.get(0);
+    self.emit_expr_into(&expr.left, regs.get(1));
+    self.emit_expr(target);
+    let skip = opt.then(|| self.builder().label("skip"));
+    if let Some(skip) = &skip {
+      self.builder().emit_jump_if_none(skip, target.span);
+    }
+    self.emit_store(callee.clone(), target.span);
+    for (i, value) in args.iter().enumerate() {
+      self.emit_expr_into(value, regs.get(2 + i));
+    }
+
+    self.builder().emit(
+      Call {
+        callee: callee.access(),
+        args: op::Count(1 + args.len() as u32),
+      },
+      span,
+    );
+    if let Some(skip) = skip {
+      self.builder().bind_label(skip);
     }
   }
 
@@ -214,11 +271,64 @@ impl<'src> State<'src> {
   fn emit_opt_expr(&mut self, expr: &'src ast::Unary<'src>) {
     assert!(matches!(expr.op, ast::UnaryOp::Opt));
 
-    // - emit_call_expr <- with receiver, `CallMethodOpt` or similar
+    /*
+      ?<a>.b[c]()
+      v = <a>
+      if v is none: goto end
+      v = v.b ?? none
+      if v is none: goto end
+      v = v[c] ?? none
+      if v is none: goto end
+      v = v()
+      end:
 
-    let prev = std::mem::replace(&mut self.current_function().is_in_opt_expr, true);
-    self.emit_expr(&expr.right);
-    let _ = std::mem::replace(&mut self.current_function().is_in_opt_expr, prev);
+      only the receivers are checked, so a `none` anywhere in the chain
+      short-circuits the rest of it, including any keys and arguments.
+    */
+    let end = self.builder().multi_label("opt_end");
+    let prev = self.current_function().opt_end.replace(end);
+    self.emit_opt_link(&expr.right);
+    let end = std::mem::replace(&mut self.current_function().opt_end, prev).unwrap();
+    self.builder().bind_label(end);
+  }
+
+  /// Emit `expr` as part of the optional chain the current position is in.
+  ///
+  /// Only field accesses, indexing and calls continue the chain. Anything
+  /// else is its head, and is emitted as a regular expression.
+  fn emit_opt_link(&mut self, expr: &'src ast::Expr<'src>) {
+    match &**expr {
+      ast::ExprKind::GetField(_) | ast::ExprKind::GetIndex(_) | ast::ExprKind::Call(_) => {
+        self.emit_expr(expr)
+      }
+      _ => self.emit_outside_opt(|this| this.emit_expr(expr)),
+    }
+  }
+
+  /// Emit the receiver of a field access, index or call. Inside of an
+  /// optional chain, this skips the rest of the chain if it is `none`.
+  fn emit_receiver(&mut self, expr: &'src ast::Expr<'src>) {
+    let Some(end) = self.current_function().opt_end.take() else {
+      return self.emit_expr(expr);
+    };
+    self.current_function().opt_end = Some(end);
+    self.emit_opt_link(expr);
+
+    let end = self.current_function().opt_end.take().unwrap();
+    self.builder().emit_jump_if_none(&end, expr.span);
+    self.current_function().opt_end = Some(end);
+  }
+
+  /// Call `f` outside of any optional chain, for expressions which are only
+  /// used by a link in the chain, like keys and arguments.
+  pub(super) fn emit_outside_opt(&mut self, f: impl FnOnce(&mut Self)) {
+    let prev = self.current_function().opt_end.take();
+    f(self);
+    self.current_function().opt_end = prev;
+  }
+
+  fn is_in_opt_expr(&mut self) -> bool {
+    self.current_function().opt_end.is_some()
   }
 
   fn emit_get_var_expr(&mut self, expr: &'src ast::GetVar<'src>, span: Span) {
@@ -226,13 +336,23 @@ impl<'src> State<'src> {
   }
 
   fn emit_set_var_expr(&mut self, expr: &'src ast::SetVar<'src>, span: Span) {
+    let value = self.infer(&expr.value);
     self.emit_expr(&expr.value);
-    match self.resolve_var(expr.target.name.lexeme()) {
-      Get::Local(reg) => self.builder().emit(Store { reg: reg.access() }, span),
+    self.remember(expr.target.name.as_str(), value);
+    self.emit_assign_var(&expr.target, span);
+  }
+
+  /// Assign the accumulator to an existing variable, or to a global if
+  /// there is no such variable.
+  pub(super) fn emit_assign_var(&mut self, target: &'src ast::GetVar<'src>, span: Span) {
+    self.hover_variable(target.name.as_str(), target.name.span);
+    match self.resolve_var(target.name.lexeme()) {
+      Get::Local(reg) => self.builder().emit(StoreCell { reg: reg.access() }, span),
       Get::Upvalue(idx) => self.builder().emit(StoreUpvalue { idx }, span),
       Get::ModuleVar(idx) => self.builder().emit(StoreModuleVar { idx }, span),
       Get::Global => {
-        let name = self.constant_name(&expr.target.name);
+        self.global_writes.insert(target.name.lexeme());
+        let name = self.constant_name(&target.name);
         self.builder().emit(StoreGlobal { name }, span);
       }
     }
@@ -240,14 +360,50 @@ impl<'src> State<'src> {
 
   fn emit_get_field_expr(&mut self, expr: &'src ast::GetField<'src>, span: Span) {
     let name = self.constant_name(&expr.name);
-    self.emit_expr(&expr.target);
-    if self.current_function().is_in_opt_expr {
+    self.emit_receiver(&expr.target);
+    if self.is_in_opt_expr() {
       self.builder().emit(LoadFieldOpt { name }, span);
+    } else if let Some(slot) = self.resolve_module_field(expr) {
+      self.builder().emit(LoadModuleField { name, slot }, span);
+    } else if let Some(slot) = self.resolve_self_field(expr) {
+      self.builder().emit(LoadFieldSlot { name, slot }, span);
     } else {
       self.builder().emit(LoadField { name }, span);
     }
   }
 
+  /// If `expr` is `m.x`, where `m` was bound by `import m` and the module is
+  /// already loaded, returns the slot of `x` in it.
+  ///
+  /// `m` may be reassigned or shadowed, so the slot is only a guess, which
+  /// is checked at runtime.
+  fn resolve_module_field(&self, expr: &'src ast::GetField<'src>) -> Option<op::ModuleVar> {
+    let ast::ExprKind::GetVar(var) = &*expr.target else {
+      return None;
+    };
+    let path = self.imports.get(&var.name.lexeme())?;
+    let rewritten = self.global.rewrite_import(path);
+    let path = rewritten.as_deref().unwrap_or(path);
+    let (_, module) = self.global.get_module_by_name(path)?;
+    let slot = module.module_vars.index_of(expr.name.as_ref())?;
+    Some(op::ModuleVar(slot as u32))
+  }
+
+  /// If `expr` is `self.x` in a method of a class without a parent, which
+  /// declares `x`, returns the slot of `x` in its instances.
+  ///
+  /// The method may be called with any receiver, so the slot is only a
+  /// guess, which is checked at runtime.
+  fn resolve_self_field(&mut self, expr: &'src ast::GetField<'src>) -> Option<op::Slot> {
+    let is_self = matches!(&*expr.target, ast::ExprKind::GetSelf);
+    if !is_self || !self.current_function().params.has_self {
+      return None;
+    }
+    let fields = self.class_fields.last()?.as_ref()?;
+    let slot = fields.index_of(expr.name.as_ref())?;
+    Some(op::Slot(slot as u32))
+  }
+
   fn emit_set_field_expr(&mut self, expr: &'src ast::SetField<'src>, span: Span) {
     let obj = self.alloc_register();
     let get = &expr.target;
@@ -255,21 +411,21 @@ impl<'src> State<'src> {
     self.emit_expr(&get.target);
     self.emit_store(obj.clone(), get.target.span);
     self.emit_expr(&expr.value);
-    self.builder().emit(
-      StoreField {
-        obj: obj.access(),
-        name,
-      },
-      span,
-    );
+    let obj = obj.access();
+    match self.resolve_self_field(get) {
+      Some(slot) => self
+        .builder()
+        .emit(StoreFieldSlot { obj, name, slot }, span),
+      None => self.builder().emit(StoreField { obj, name }, span),
+    }
   }
 
   fn emit_get_index_expr(&mut self, expr: &'src ast::GetIndex<'src>, span: Span) {
     let obj = self.alloc_register();
-    self.emit_expr(&expr.target);
+    self.emit_receiver(&expr.target);
     self.emit_store(obj.clone(), expr.target.span);
-    self.emit_expr(&expr.key);
-    if self.current_function().is_in_opt_expr {
+    self.emit_outside_opt(|this| this.emit_expr(&expr.key));
+    if self.is_in_opt_expr() {
       self
         .builder()
         .emit(LoadIndexOpt { obj: obj.access() }, span);
@@ -297,18 +453,22 @@ impl<'src> State<'src> {
   }
 
   fn emit_call_expr(&mut self, expr: &'src ast::Call<'src>, span: Span) {
-    self.emit_expr(&expr.target);
+    if self.emit_inlined_call(expr) || self.emit_method_call(expr, span) {
+      return;
+    }
+
+    self.emit_receiver(&expr.target);
+
+    // `f?()` skips the call if `f` is none, leaving `none` in the accumulator
+    let skip = expr.opt.then(|| self.builder().label("skip"));
+    if let Some(skip) = &skip {
+      self.builder().emit_jump_if_none(skip, expr.target.span);
+    }
+
     if expr.args.is_empty() {
       self.builder().emit(Call0, span);
     } else {
-      let args = self.alloc_register_slice(1 + expr.args.len());
-      let callee = args.get(0);
-      self.emit_store(callee.clone(), expr.target.span);
-      for (i, value) in expr.args.iter().enumerate() {
-        self.emit_expr(value);
-        self.emit_store(args.get(1 + i), value.span);
-      }
-
+      let callee = self.emit_call_args(expr);
       self.builder().emit(
         Call {
           callee: callee.access(),
@@ -317,6 +477,81 @@ impl<'src> State<'src> {
         span,
       );
     }
+
+    if let Some(skip) = skip {
+      self.builder().bind_label(skip);
+    }
+  }
+
+  /// Emit `call` as a `CallInto`, which writes its result to `dst`. Returns
+  /// `false` if nothing was emitted, because the call may be skipped, has
+  /// no arguments, or is in an optional chain.
+  fn emit_call_into(&mut self, call: &'src ast::Call<'src>, dst: &Register, span: Span) -> bool {
+    if call.opt || call.args.is_empty() || self.is_in_opt_expr() {
+      return false;
+    }
+    if self.emit_inlined_call(call) || self.emit_method_call(call, span) {
+      self.emit_store(dst.clone(), span);
+      return true;
+    }
+
+    self.emit_expr(&call.target);
+    let callee = self.emit_call_args(call);
+    self.builder().emit(
+      CallInto {
+        callee: callee.access(),
+        args: op::Count(call.args.len() as u32),
+        dst: dst.access(),
+      },
+      span,
+    );
+    true
+  }
+
+  /// Store the callee in the accumulator and the arguments of `call` in a
+  /// new call window. Returns the register of the callee, which is the
+  /// start of the window.
+  fn emit_call_args(&mut self, call: &'src ast::Call<'src>) -> Register {
+    let args = self.alloc_call_window(1 + call.args.len());
+    let callee = args.get(0);
+    self.emit_store(callee.clone(), call.target.span);
+    self.emit_outside_opt(|this| {
+      for (i, value) in call.args.iter().enumerate() {
+        this.emit_expr_into(value, args.get(1 + i));
+      }
+    });
+    callee
+  }
+
+  /// Emit `obj.name(args)` as a single `CallMethod`, which calls the method
+  /// without binding it to `obj` first. Returns `false` if nothing was
+  /// emitted, because `call` is not an immediate call of a field.
+  fn emit_method_call(&mut self, call: &'src ast::Call<'src>, span: Span) -> bool {
+    let ast::ExprKind::GetField(get) = &*call.target else {
+      return false;
+    };
+    // optional chains and module fields have their own instructions
+    if call.opt || self.is_in_opt_expr() || self.resolve_module_field(get).is_some() {
+      return false;
+    }
+
+    let name = self.constant_name(&get.name);
+    let regs = self.alloc_call_window(1 + call.args.len());
+    let obj = regs.get(0);
+    self.emit_expr_into(&get.target, obj.clone());
+    for (i, value) in call.args.iter().enumerate() {
+      self.emit_expr_into(value, regs.get(1 + i));
+    }
+
+    self.builder().emit(
+      CallMethod {
+        obj: obj.access(),
+        name,
+        args: op::Count(call.args.len() as u32),
+      },
+      span,
+    );
+    true
   }
 
   fn emit_get_self_expr(&mut self, span: Span) {
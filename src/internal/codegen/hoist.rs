@@ -0,0 +1,193 @@
+//! Hoisting of loop-invariant loads out of loop bodies.
+//!
+//! Loading a constant copies it out of the function's constant pool on
+//! every iteration. Before a loop, the constants which the loop loads are
+//! loaded into registers once, and the loop reads the registers instead.
+//!
+//! Globals and module variables are not hoisted, even if the loop never
+//! assigns them. The host may change a global between two slices of
+//! [`Hebi::step`][crate::Hebi::step], and a native function or another
+//! thread may change either of them while the loop runs.
+
+use super::*;
+use crate::internal::value::constant::NonNaNFloat;
+
+/// The constants which were loaded into registers before the loop the
+/// current position is in.
+#[derive(Default)]
+pub(super) struct Hoisted {
+  constants: IndexMap<u32, Register>,
+}
+
+/// The constants which a loop loads.
+#[derive(Default)]
+struct Scan<'src> {
+  constants: Vec<&'src ast::Literal<'src>>,
+}
+
+impl<'src> State<'src> {
+  /// Load the constants which `body` and `cond` load into registers.
+  ///
+  /// Every call must be paired with a call to [`State::end_hoisted`] after
+  /// the loop's back edge.
+  pub(super) fn hoist_loop_invariants(
+    &mut self,
+    cond: Option<&'src ast::Expr<'src>>,
+    body: &'src [ast::Stmt<'src>],
+    span: Span,
+  ) {
+    let mut scan = Scan::default();
+    scan.exprs(cond);
+    scan.block(body);
+
+    let mut hoisted = Hoisted::default();
+    for literal in scan.constants {
+      let idx = match literal {
+        ast::Literal::Float(v) => self.constant_value(NonNaNFloat::from(*v)),
+        ast::Literal::String(v) => self.constant_name(v),
+        _ => unreachable!("only floats and strings are loaded from the constant pool"),
+      };
+      if hoisted.constants.contains_key(&idx.0) || self.hoisted_constant(idx).is_some() {
+        continue;
+      }
+      let register = self.alloc_register();
+      self.builder().emit(LoadConst { idx }, span);
+      self.emit_store(register.clone(), span);
+      hoisted.constants.insert(idx.0, register);
+    }
+
+    self.current_function().hoisted.push(hoisted);
+  }
+
+  /// Stop using the registers loaded by the matching call to
+  /// [`State::hoist_loop_invariants`], which must stay alive until here
+  /// because the loop reads them again after jumping back.
+  pub(super) fn end_hoisted(&mut self) {
+    let hoisted = self.current_function().hoisted.pop().unwrap();
+    for register in hoisted.constants.values() {
+      let _ = register.access();
+    }
+  }
+
+  fn hoisted_constant(&mut self, idx: op::Constant) -> Option<Register> {
+    let function = self.current_function();
+    let mut scopes = function.hoisted.iter().rev();
+    scopes.find_map(|hoisted| hoisted.constants.get(&idx.0).cloned())
+  }
+
+  /// Load the constant at `idx`, from the register it was hoisted into if
+  /// there is one.
+  pub(super) fn emit_load_const(&mut self, idx: op::Constant, span: Span) {
+    match self.hoisted_constant(idx) {
+      Some(register) => self.emit_load(register, span),
+      None => self.builder().emit(LoadConst { idx }, span),
+    }
+  }
+}
+
+impl<'src> Scan<'src> {
+  fn block(&mut self, body: &'src [ast::Stmt<'src>]) {
+    for stmt in body {
+      self.stmt(stmt);
+    }
+  }
+
+  fn exprs(&mut self, exprs: impl IntoIterator<Item = &'src ast::Expr<'src>>) {
+    for expr in exprs {
+      self.expr(expr);
+    }
+  }
+
+  fn stmt(&mut self, stmt: &'src ast::Stmt<'src>) {
+    match &**stmt {
+      ast::StmtKind::Var(v) => self.expr(&v.value),
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.block(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.block(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => match &**v {
+        ast::Loop::For(v) => {
+          match &v.iter {
+            ast::ForIter::Range(range) => {
+              self.exprs([&range.start, &range.end]);
+              self.exprs(&range.step);
+            }
+            ast::ForIter::Expr(iter) | ast::ForIter::Stream(iter) => self.expr(iter),
+          }
+          self.block(&v.body);
+        }
+        ast::Loop::While(v) => {
+          self.expr(&v.cond);
+          self.block(&v.body);
+        }
+        ast::Loop::Infinite(v) => self.block(&v.body),
+        ast::Loop::Repeat(v) => {
+          self.expr(&v.count);
+          self.block(&v.body);
+        }
+      },
+      ast::StmtKind::Ctrl(v) => match &**v {
+        ast::Ctrl::Return(ast::Return { value }) => self.exprs(value),
+        ast::Ctrl::Yield(_) | ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      // the body is a different function
+      ast::StmtKind::Func(_) => {}
+      ast::StmtKind::Class(v) => self.exprs(v.members.fields.iter().map(|field| &field.default)),
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Pass => {}
+      ast::StmtKind::Print(v) => self.exprs(v.values.iter()),
+      ast::StmtKind::Import(_) | ast::StmtKind::With(_) => {}
+      ast::StmtKind::Try(v) => {
+        self.block(&v.body);
+        for catch in v.catches.iter() {
+          self.exprs(catch.class.as_ref());
+          self.block(&catch.body);
+        }
+      }
+      ast::StmtKind::Throw(v) => self.expr(&v.value),
+      ast::StmtKind::MultiAssign(v) => {
+        for target in v.targets.iter().flatten() {
+          match target {
+            ast::AssignTarget::Var(_) => {}
+            ast::AssignTarget::Field(get) => self.expr(&get.target),
+            ast::AssignTarget::Index(get) => self.exprs([&get.target, &get.key]),
+          }
+        }
+        self.exprs(v.values.iter());
+      }
+    }
+  }
+
+  fn expr(&mut self, expr: &'src ast::Expr<'src>) {
+    match &**expr {
+      ast::ExprKind::Literal(v) => match &**v {
+        ast::Literal::Float(_) | ast::Literal::String(_) => self.constants.push(v),
+        ast::Literal::List(items) => self.exprs(items.iter()),
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries.iter() {
+            self.exprs([key, value]);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => self.exprs([&v.left, &v.right]),
+      ast::ExprKind::Unary(v) => self.expr(&v.right),
+      ast::ExprKind::GetVar(_) => {}
+      ast::ExprKind::SetVar(v) => self.expr(&v.value),
+      ast::ExprKind::GetField(v) => self.expr(&v.target),
+      ast::ExprKind::SetField(v) => self.exprs([&v.target.target, &v.value]),
+      ast::ExprKind::GetIndex(v) => self.exprs([&v.target, &v.key]),
+      ast::ExprKind::SetIndex(v) => self.exprs([&v.target.target, &v.target.key, &v.value]),
+      ast::ExprKind::Call(v) => {
+        self.expr(&v.target);
+        self.exprs(v.args.iter());
+      }
+      ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    }
+  }
+}
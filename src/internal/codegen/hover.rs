@@ -0,0 +1,252 @@
+use super::*;
+use crate::internal::object::function::FunctionInfo;
+
+/// What is known about an expression without running the script, see
+/// [`Hebi::hover`][crate::Hebi::hover].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hover {
+  /// The span of the expression.
+  pub span: Span,
+  pub kind: HoverKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HoverKind {
+  Literal(Literal),
+  /// A function declaration.
+  Function(FunctionInfo),
+  /// A class declaration.
+  Class(ClassInfo),
+  /// An instance of a class declared in the script, created by calling the
+  /// class directly.
+  Instance {
+    class: String,
+  },
+  /// A variable, and what is known about the value it holds at that point.
+  Variable {
+    name: String,
+    binding: Binding,
+    value: Option<Box<HoverKind>>,
+  },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+  None,
+  Bool(bool),
+  Int(i32),
+  Float(f64),
+  String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassInfo {
+  pub name: String,
+  pub parent: Option<String>,
+  pub fields: Vec<String>,
+  /// The names of the methods, not including `init`.
+  pub methods: Vec<String>,
+  /// The span of the class's name.
+  pub span: Span,
+}
+
+/// Where a variable lives, which is decided by the same resolution pass that
+/// compiles the script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+  /// A variable declared in the current function.
+  Local,
+  /// A variable declared in an enclosing function.
+  Captured,
+  /// A variable declared at the top level of a module.
+  Module,
+  /// A global, which may be defined by the script, the host, or not at all.
+  Global,
+}
+
+/// Find what is known about the innermost expression at `offset`, by running
+/// codegen over `ast` and recording what it resolves along the way.
+///
+/// `ast` has already been parsed, so this only ignores errors found while
+/// emitting it, such as an unknown name, so that a script which is still
+/// being edited can be inspected.
+pub fn hover<'src>(global: Global, ast: &'src ast::Module<'src>, offset: usize) -> Option<Hover> {
+  let mut state = State::new(global, ast, "__main__", true, 0);
+  state.hover = Some(Hovering {
+    offset,
+    found: None,
+    globals: IndexMap::new(),
+  });
+  state.emit_module(&[]);
+  let _ = (state.module.functions.pop().unwrap()).finish((0..0).into(), &mut state.errors);
+  state.hover.and_then(|hovering| hovering.found)
+}
+
+pub(super) struct Hovering {
+  offset: usize,
+  found: Option<Hover>,
+  /// What is known about the value of each variable declared at the top
+  /// level of the module.
+  globals: IndexMap<String, Option<HoverKind>>,
+}
+
+impl<'src> State<'src> {
+  /// Record `kind` as the hover at `span`, if the hovered position is inside
+  /// of it, and no smaller span containing it was recorded before.
+  pub(super) fn hover_at(&mut self, span: Span, kind: impl FnOnce(&Self) -> Option<HoverKind>) {
+    let Some(hovering) = &self.hover else {
+      return;
+    };
+    if !(span.start <= hovering.offset && hovering.offset < span.end) {
+      return;
+    }
+    if let Some(found) = &hovering.found {
+      if found.span.end - found.span.start <= span.end - span.start {
+        return;
+      }
+    }
+    if let Some(kind) = kind(self) {
+      self.hover.as_mut().unwrap().found = Some(Hover { span, kind });
+    }
+  }
+
+  pub(super) fn hover_variable(&mut self, name: &str, span: Span) {
+    self.hover_at(span, |this| {
+      let (binding, value) = this.known_var(name);
+      Some(HoverKind::Variable {
+        name: name.to_string(),
+        binding,
+        value: value.map(Box::new),
+      })
+    });
+  }
+
+  /// Remember what `name`, which was just declared or assigned, holds.
+  pub(super) fn remember(&mut self, name: &str, value: Option<HoverKind>) {
+    if self.hover.is_none() {
+      return;
+    }
+    let (binding, _) = self.known_var(name);
+    let known = match binding {
+      Binding::Local => self.current_function().known_local(name),
+      Binding::Captured => {
+        let functions = self.module.functions.iter_mut().rev().skip(1);
+        let mut enclosing = functions.filter(|function| function.resolve_local(name).is_some());
+        enclosing
+          .next()
+          .and_then(|function| function.known_local(name))
+      }
+      Binding::Module | Binding::Global => {
+        let hovering = self.hover.as_mut().unwrap();
+        Some(hovering.globals.entry(name.to_string()).or_default())
+      }
+    };
+    if let Some(known) = known {
+      *known = value;
+    }
+  }
+
+  /// Resolve `name` the same way as [`State::resolve_var`], without
+  /// capturing it, and find what is known about its value.
+  fn known_var(&self, name: &str) -> (Binding, Option<HoverKind>) {
+    let hovering = self.hover.as_ref().unwrap();
+    let (current, enclosing) = self.module.functions.split_last().unwrap();
+    if current.resolve_local(name).is_some() {
+      return (Binding::Local, current.known(name));
+    }
+    if let Some(function) = (enclosing.iter().rev()).find(|f| f.resolve_local(name).is_some()) {
+      return (Binding::Captured, function.known(name));
+    }
+    let binding = match self.resolve_module_var(name) {
+      Some(_) => Binding::Module,
+      None => Binding::Global,
+    };
+    (binding, hovering.globals.get(name).cloned().flatten())
+  }
+
+  /// What is known about the value of `expr`, if anything.
+  pub(super) fn infer(&self, expr: &'src ast::Expr<'src>) -> Option<HoverKind> {
+    self.hover.as_ref()?;
+    match &**expr {
+      ast::ExprKind::Literal(v) => literal(v).map(HoverKind::Literal),
+      ast::ExprKind::GetVar(v) => self.known_var(v.name.as_str()).1,
+      ast::ExprKind::Call(call) => match &*call.target {
+        ast::ExprKind::GetVar(v) => match self.known_var(v.name.as_str()).1 {
+          Some(HoverKind::Class(class)) => Some(HoverKind::Instance { class: class.name }),
+          _ => None,
+        },
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  pub(super) fn infer_function(
+    &self,
+    func: &'src ast::Func<'src>,
+    class: Option<&ast::Ident<'src>>,
+  ) -> Option<HoverKind> {
+    self.hover.as_ref()?;
+    Some(function_info(func, class))
+  }
+
+  pub(super) fn infer_class(&self, class: &'src ast::Class<'src>) -> Option<HoverKind> {
+    self.hover.as_ref()?;
+    Some(class_info(class))
+  }
+}
+
+impl<'src> Function<'src> {
+  fn known(&self, name: &str) -> Option<HoverKind> {
+    let mut locals = self.known.iter().rev();
+    let (_, value) = locals.find(|((_, var), _)| var == name)?;
+    value.clone()
+  }
+
+  fn known_local(&mut self, name: &str) -> Option<&mut Option<HoverKind>> {
+    let mut locals = self.known.iter_mut().rev();
+    locals
+      .find(|((_, var), _)| var == name)
+      .map(|(_, value)| value)
+  }
+}
+
+pub(super) fn literal(value: &ast::Literal<'_>) -> Option<Literal> {
+  match value {
+    ast::Literal::None => Some(Literal::None),
+    ast::Literal::Int(v) => Some(Literal::Int(*v)),
+    ast::Literal::Float(v) => Some(Literal::Float(*v)),
+    ast::Literal::Bool(v) => Some(Literal::Bool(*v)),
+    ast::Literal::String(v) => Some(Literal::String(v.to_string())),
+    #[cfg(feature = "decimal")]
+    ast::Literal::Decimal(_) => None,
+    ast::Literal::List(_) | ast::Literal::Table(_) => None,
+  }
+}
+
+pub(super) fn function_info(func: &ast::Func<'_>, class: Option<&ast::Ident<'_>>) -> HoverKind {
+  let params = function::Params::from_ast_func(func);
+  HoverKind::Function(FunctionInfo {
+    name: func.name.to_string(),
+    class: class.map(|name| name.to_string()),
+    params: func.params.pos.iter().map(|p| p.name.to_string()).collect(),
+    min_args: params.min as usize,
+    max_args: params.max as usize,
+    is_generator: func.has_yield,
+    span: func.name.span,
+  })
+}
+
+pub(super) fn class_info(class: &ast::Class<'_>) -> HoverKind {
+  HoverKind::Class(ClassInfo {
+    name: class.name.to_string(),
+    parent: class.parent.as_ref().map(|name| name.to_string()),
+    fields: (class.members.fields.iter())
+      .map(|field| field.name.to_string())
+      .collect(),
+    methods: (class.members.methods.iter())
+      .map(|method| method.name.to_string())
+      .collect(),
+    span: class.name.span,
+  })
+}
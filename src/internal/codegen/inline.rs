@@ -0,0 +1,361 @@
+//! Inlining of calls to tiny functions, see
+//! [`HebiBuilder::inline_functions`][crate::HebiBuilder::inline_functions].
+
+use super::*;
+
+/// The maximum number of nodes in the returned expression of a function
+/// which is inlined.
+const MAX_INLINE_SIZE: usize = 16;
+
+pub(super) struct Inlining<'src> {
+  /// Functions which may be inlined once they are declared.
+  candidates: Vec<&'src ast::Func<'src>>,
+  /// Candidates which were declared, by name.
+  declared: IndexMap<Cow<'src, str>, Inlinable<'src>>,
+  /// Functions which are being inlined, innermost last. A function is not
+  /// inlined into itself, so that mutual recursion terminates.
+  active: Vec<Cow<'src, str>>,
+}
+
+#[derive(Clone)]
+struct Inlinable<'src> {
+  func: &'src ast::Func<'src>,
+  body: &'src ast::Expr<'src>,
+  /// How the function's own name and the names it uses which are not its
+  /// parameters resolved where it was declared. Calls are only inlined
+  /// where they resolve the same way.
+  names: Vec<(Cow<'src, str>, Resolved)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Resolved {
+  ModuleVar(u32),
+  Global,
+}
+
+impl<'src> Inlining<'src> {
+  pub(super) fn new(ast: &'src ast::Module<'src>) -> Self {
+    let mut bindings = IndexMap::new();
+    count_bindings(&ast.body, &mut bindings);
+    let candidates = ast
+      .body
+      .iter()
+      .filter_map(|stmt| match &**stmt {
+        ast::StmtKind::Func(func) => Some(&**func),
+        _ => None,
+      })
+      .filter(|func| bindings.get(&func.name.lexeme()) == Some(&1))
+      .filter(|func| inlined_body(func).is_some())
+      .collect();
+    Self {
+      candidates,
+      declared: IndexMap::new(),
+      active: Vec::new(),
+    }
+  }
+}
+
+/// The expression returned by `func`, if it is small enough to be inlined.
+fn inlined_body<'src>(func: &'src ast::Func<'src>) -> Option<&'src ast::Expr<'src>> {
+  if !func.decorators.is_empty()
+    || func.has_yield
+    || func.params.has_self
+    || func.params.pos.iter().any(|param| param.default.is_some())
+  {
+    return None;
+  }
+  let [stmt] = &func.body[..] else {
+    return None;
+  };
+  let ast::StmtKind::Ctrl(ctrl) = &**stmt else {
+    return None;
+  };
+  let ast::Ctrl::Return(ast::Return { value: Some(body) }) = &**ctrl else {
+    return None;
+  };
+
+  let mut size = 0;
+  visit_expr(body, &mut |_| size += 1);
+  if size > MAX_INLINE_SIZE || used_names(body).contains(&func.name.lexeme()) {
+    return None;
+  }
+  Some(body)
+}
+
+impl<'src> State<'src> {
+  /// Called after `func` is declared, to start inlining calls to it if it
+  /// is a candidate.
+  pub(super) fn declare_inlinable(&mut self, func: &'src ast::Func<'src>) {
+    let Some(inlining) = &self.inline else {
+      return;
+    };
+    if !inlining.candidates.iter().any(|c| std::ptr::eq(*c, func)) {
+      return;
+    }
+    let body = inlined_body(func).unwrap();
+
+    let mut used = used_names(body);
+    used.insert(func.name.lexeme());
+    let params = &func.params.pos;
+    let mut names = Vec::new();
+    for name in used {
+      if params.iter().any(|param| param.name.lexeme() == name) {
+        continue;
+      }
+      // the function captures a local, so it can't be moved elsewhere
+      let Some(resolved) = self.resolve_outer(&name) else {
+        return;
+      };
+      names.push((name, resolved));
+    }
+
+    let inlining = self.inline.as_mut().unwrap();
+    inlining
+      .declared
+      .insert(func.name.lexeme(), Inlinable { func, body, names });
+  }
+
+  /// Emit `call` by inlining the body of the function it calls, if it is
+  /// possible. Returns `false` if nothing was emitted.
+  pub(super) fn emit_inlined_call(&mut self, call: &'src ast::Call<'src>) -> bool {
+    let Some(inlining) = &self.inline else {
+      return false;
+    };
+    let ast::ExprKind::GetVar(target) = &*call.target else {
+      return false;
+    };
+    let name = target.name.lexeme();
+    let Some(inlinable) = inlining.declared.get(&name) else {
+      return false;
+    };
+    if call.opt
+      || inlining.active.contains(&name)
+      || inlinable.func.params.pos.len() != call.args.len()
+    {
+      return false;
+    }
+    let (func, body) = (inlinable.func, inlinable.body);
+    let names = inlinable.names.clone();
+    if names
+      .iter()
+      .any(|(name, resolved)| self.resolve_outer(name) != Some(*resolved))
+    {
+      return false;
+    }
+
+    // arguments are evaluated before the body, as with a call
+    let args = self.alloc_register_slice(call.args.len());
+    self.emit_outside_opt(|this| {
+      for (i, value) in call.args.iter().enumerate() {
+        this.emit_expr_into(value, args.get(i));
+      }
+    });
+
+    self.inline.as_mut().unwrap().active.push(name);
+    self.current_function().enter_scope();
+    for (i, param) in func.params.pos.iter().enumerate() {
+      self.declare_local(param.name.lexeme(), args.get(i));
+    }
+    self.emit_outside_opt(|this| this.emit_expr(body));
+    self.current_function().leave_scope();
+    self.inline.as_mut().unwrap().active.pop();
+    true
+  }
+
+  /// How `name` resolves, if it is not a local in the current function or
+  /// any of the functions it is nested in.
+  fn resolve_outer(&self, name: &Cow<'src, str>) -> Option<Resolved> {
+    if self
+      .module
+      .functions
+      .iter()
+      .any(|function| function.resolve_local(name).is_some())
+    {
+      return None;
+    }
+    match self.resolve_module_var(name) {
+      Some(idx) => Some(Resolved::ModuleVar(idx.0)),
+      None => Some(Resolved::Global),
+    }
+  }
+}
+
+/// Count how many times each name is bound anywhere in `body`.
+fn count_bindings<'src>(
+  body: &'src [ast::Stmt<'src>],
+  counts: &mut IndexMap<Cow<'src, str>, usize>,
+) {
+  fn bind<'src>(counts: &mut IndexMap<Cow<'src, str>, usize>, name: Cow<'src, str>) {
+    *counts.entry(name).or_default() += 1;
+  }
+  for stmt in body {
+    let mut exprs = Vec::new();
+    match &**stmt {
+      ast::StmtKind::Var(v) => {
+        bind(counts, v.name.lexeme());
+        exprs.push(&v.value);
+      }
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          exprs.push(&branch.cond);
+          count_bindings(&branch.body, counts);
+        }
+        if let Some(default) = &v.default {
+          count_bindings(default, counts);
+        }
+      }
+      ast::StmtKind::Loop(v) => match &**v {
+        ast::Loop::For(v) => {
+          bind(counts, v.item.lexeme());
+          match &v.iter {
+            ast::ForIter::Range(range) => {
+              exprs.extend([&range.start, &range.end]);
+              exprs.extend(&range.step);
+            }
+            ast::ForIter::Expr(iter) | ast::ForIter::Stream(iter) => exprs.push(iter),
+          }
+          count_bindings(&v.body, counts);
+        }
+        ast::Loop::While(v) => {
+          exprs.push(&v.cond);
+          count_bindings(&v.body, counts);
+        }
+        ast::Loop::Infinite(v) => count_bindings(&v.body, counts),
+        ast::Loop::Repeat(v) => {
+          exprs.push(&v.count);
+          count_bindings(&v.body, counts);
+        }
+      },
+      ast::StmtKind::Ctrl(v) => match &**v {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          exprs.extend(value.as_ref())
+        }
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => {
+        bind(counts, v.name.lexeme());
+        exprs.extend(v.decorators.iter());
+        exprs.extend(v.params.pos.iter().filter_map(|p| p.default.as_ref()));
+        count_bindings(&v.body, counts);
+      }
+      ast::StmtKind::Class(v) => {
+        bind(counts, v.name.lexeme());
+        exprs.extend(v.members.fields.iter().map(|field| &field.default));
+        for method in v.members.init.iter().chain(v.members.methods.iter()) {
+          exprs.extend(method.params.pos.iter().filter_map(|p| p.default.as_ref()));
+          count_bindings(&method.body, counts);
+        }
+      }
+      ast::StmtKind::Expr(v) => exprs.push(v),
+      ast::StmtKind::Pass => {}
+      ast::StmtKind::Print(v) => exprs.extend(v.values.iter()),
+      ast::StmtKind::Import(v) => match &**v {
+        ast::Import::Module { path, alias } => {
+          if let Some(name) = alias.as_ref().or(path.last()) {
+            bind(counts, name.lexeme());
+          }
+        }
+        ast::Import::Symbols { symbols, .. } => {
+          for symbol in symbols.iter() {
+            bind(
+              counts,
+              symbol.alias.as_ref().unwrap_or(&symbol.name).lexeme(),
+            );
+          }
+        }
+      },
+      ast::StmtKind::Try(v) => {
+        count_bindings(&v.body, counts);
+        for catch in v.catches.iter() {
+          exprs.extend(catch.class.as_ref());
+          if let Some(binding) = &catch.binding {
+            bind(counts, binding.lexeme());
+          }
+          count_bindings(&catch.body, counts);
+        }
+      }
+      ast::StmtKind::Throw(v) => exprs.push(&v.value),
+      ast::StmtKind::With(v) => {
+        exprs.push(&v.context);
+        if let Some(binding) = &v.binding {
+          bind(counts, binding.lexeme());
+        }
+        count_bindings(&v.body, counts);
+      }
+      ast::StmtKind::MultiAssign(v) => {
+        for target in v.targets.iter().flatten() {
+          match target {
+            ast::AssignTarget::Var(var) => bind(counts, var.name.lexeme()),
+            ast::AssignTarget::Field(get) => exprs.push(&get.target),
+            ast::AssignTarget::Index(get) => exprs.extend([&get.target, &get.key]),
+          }
+        }
+        exprs.extend(v.values.iter());
+      }
+    }
+    for expr in exprs {
+      visit_expr(expr, &mut |expr| {
+        if let ast::ExprKind::SetVar(v) = &**expr {
+          bind(counts, v.target.name.lexeme());
+        }
+      });
+    }
+  }
+}
+
+/// The names of the variables which `expr` reads or assigns.
+fn used_names<'src>(expr: &'src ast::Expr<'src>) -> IndexSet<Cow<'src, str>> {
+  let mut names = IndexSet::new();
+  visit_expr(expr, &mut |expr| match &**expr {
+    ast::ExprKind::GetVar(v) => {
+      names.insert(v.name.lexeme());
+    }
+    ast::ExprKind::SetVar(v) => {
+      names.insert(v.target.name.lexeme());
+    }
+    _ => {}
+  });
+  names
+}
+
+/// Call `f` with `expr` and every expression nested in it.
+fn visit_expr<'src>(expr: &'src ast::Expr<'src>, f: &mut impl FnMut(&'src ast::Expr<'src>)) {
+  f(expr);
+  match &**expr {
+    ast::ExprKind::Literal(v) => match &**v {
+      ast::Literal::List(items) => items.iter().for_each(|item| visit_expr(item, f)),
+      ast::Literal::Table(entries) => {
+        for (key, value) in entries.iter() {
+          visit_expr(key, f);
+          visit_expr(value, f);
+        }
+      }
+      _ => {}
+    },
+    ast::ExprKind::Binary(v) => {
+      visit_expr(&v.left, f);
+      visit_expr(&v.right, f);
+    }
+    ast::ExprKind::Unary(v) => visit_expr(&v.right, f),
+    ast::ExprKind::GetVar(_) | ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    ast::ExprKind::SetVar(v) => visit_expr(&v.value, f),
+    ast::ExprKind::GetField(v) => visit_expr(&v.target, f),
+    ast::ExprKind::SetField(v) => {
+      visit_expr(&v.target.target, f);
+      visit_expr(&v.value, f);
+    }
+    ast::ExprKind::GetIndex(v) => {
+      visit_expr(&v.target, f);
+      visit_expr(&v.key, f);
+    }
+    ast::ExprKind::SetIndex(v) => {
+      visit_expr(&v.target.target, f);
+      visit_expr(&v.target.key, f);
+      visit_expr(&v.value, f);
+    }
+    ast::ExprKind::Call(v) => {
+      visit_expr(&v.target, f);
+      v.args.iter().for_each(|arg| visit_expr(arg, f));
+    }
+  }
+}
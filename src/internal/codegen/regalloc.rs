@@ -43,7 +43,7 @@ impl State {
     (index, register)
   }
 
-  fn alloc_slice(&mut self, n: usize) -> (usize, Range<usize>) {
+  fn alloc_slice(&mut self, n: usize, window: bool) -> (usize, Range<usize>) {
     let index = self.intervals.len();
     let slice = self.registers(n);
     let event = self.event();
@@ -51,7 +51,10 @@ impl State {
     self.intervals.push(Interval {
       start: event,
       end: event,
-      entry: Entry::Slice(slice.clone()),
+      entry: match window {
+        true => Entry::Window(slice.clone()),
+        false => Entry::Slice(slice.clone()),
+      },
     });
 
     (index, slice)
@@ -74,6 +77,9 @@ struct Interval {
 enum Entry {
   Register(usize),
   Slice(Range<usize>),
+  /// A slice which is placed above every register that is in use when it
+  /// is allocated.
+  Window(Range<usize>),
 }
 
 impl RegAlloc {
@@ -91,7 +97,19 @@ impl RegAlloc {
   }
 
   pub fn alloc_slice(&mut self, n: usize) -> Slice {
-    let (index, slice) = self.0.borrow_mut().alloc_slice(n);
+    let (index, slice) = self.0.borrow_mut().alloc_slice(n, false);
+    Slice {
+      state: self.0.clone(),
+      slice,
+      index,
+    }
+  }
+
+  /// Allocate a slice of `n` registers above every register which is in use
+  /// at this point. Only registers which are allocated after it may be
+  /// placed after it.
+  pub fn alloc_window(&mut self, n: usize) -> Slice {
+    let (index, slice) = self.0.borrow_mut().alloc_slice(n, true);
     Slice {
       state: self.0.clone(),
       slice,
@@ -293,8 +311,11 @@ fn linear_scan(intervals: &[Interval], total_intervals: usize) -> (usize, Vec<us
         );
         mapping[*index] = register;
       }
-      Entry::Slice(indices) => {
-        let slice = allocate_slice(indices.len(), &mut free, &mut registers);
+      Entry::Slice(indices) | Entry::Window(indices) => {
+        let slice = match &interval.entry {
+          Entry::Window(_) => allocate_window(indices.len(), &mut free, &active, &mut registers),
+          _ => allocate_slice(indices.len(), &mut free, &mut registers),
+        };
         active.insert(
           interval.entry.clone(),
           (interval.clone(), Allocation::Slice(slice.clone())),
@@ -390,6 +411,29 @@ fn allocate_slice(n: usize, free: &mut Free, registers: &mut usize) -> Range<usi
   }
 }
 
+/// Allocate `n` registers right after the highest register in `active`.
+fn allocate_window(
+  n: usize,
+  free: &mut Free,
+  active: &Active,
+  registers: &mut usize,
+) -> Range<usize> {
+  let start = active
+    .values()
+    .map(|(_, allocation)| match allocation {
+      Allocation::Register(register) => register + 1,
+      Allocation::Slice(slice) => slice.end,
+    })
+    .max()
+    .unwrap_or(0);
+  let window = start..start + n;
+
+  // registers from `start` up are either free or fresh
+  free.retain(|Reverse(register)| !window.contains(register));
+  *registers = (*registers).max(window.end);
+  window
+}
+
 /// Returns a range which can be used to slice `free`
 /// to get a slice of contiguous registers.
 fn find_contiguous_registers(n: usize, free: &Free) -> Option<Range<usize>> {
@@ -481,6 +525,10 @@ impl<T: Ord> SortedVec<T> {
     self.inner.pop()
   }
 
+  fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+    self.inner.retain(f)
+  }
+
   fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> std::vec::Drain<'_, T> {
     self.inner.drain(range)
   }
@@ -108,6 +108,32 @@ fn alloc_register_slice() {
   assert_snapshot!(DisplayGraph(&regalloc.0.borrow(), registers, &map).to_string());
 }
 
+#[test]
+fn alloc_register_window() {
+  let mut regalloc = RegAlloc::new();
+
+  // `b` is free by the time the slice is allocated, but the window is still
+  // placed after `c`, which isn't
+  let a = regalloc.alloc();
+  let b = regalloc.alloc();
+  let c = regalloc.alloc();
+  let _ = b.access();
+  let window = regalloc.alloc_window(2);
+  let _ = window.access(0);
+  let _ = window.access(1);
+  let _ = c.access();
+  let d = regalloc.alloc();
+  let _ = d.access();
+  let _ = a.access();
+
+  let (registers, map) = regalloc.finish();
+  assert_eq!(map[3..5], [3, 4]);
+  // registers freed before the window can still be reused below it
+  assert_eq!(map[5], 1);
+
+  assert_snapshot!(DisplayGraph(&regalloc.0.borrow(), registers, &map).to_string());
+}
+
 #[test]
 fn allocate_slice_duplicate() {
   // registers: [x0, +1, +2]
@@ -194,7 +220,7 @@ impl<'a> std::fmt::Display for DisplayGraph<'a> {
           end: interval.end,
           index: *index,
         }),
-        Entry::Slice(slice) => {
+        Entry::Slice(slice) | Entry::Window(slice) => {
           for index in slice.clone() {
             intervals.push(BasicInterval {
               start: interval.start,
@@ -1,12 +1,16 @@
 use std::ops::Deref;
 
 use super::*;
+use crate::internal::object::string::StrMap;
 use crate::internal::object::Table;
+use crate::internal::syntax::pragma;
 use crate::internal::value::Value;
+use crate::internal::vm::global::Construct;
 use crate::util::JoinIter;
 
 impl<'src> State<'src> {
   pub(super) fn emit_stmt(&mut self, stmt: &'src ast::Stmt<'src>) {
+    self.check_forbidden(stmt);
     match stmt.deref() {
       ast::StmtKind::Var(v) => self.emit_var_stmt(v, stmt.span),
       ast::StmtKind::If(v) => self.emit_if_stmt(v, stmt.span),
@@ -18,6 +22,52 @@ impl<'src> State<'src> {
       ast::StmtKind::Pass => self.emit_pass_stmt(),
       ast::StmtKind::Print(v) => self.emit_print_stmt(v, stmt.span),
       ast::StmtKind::Import(v) => self.emit_import_stmt(v, stmt.span),
+      ast::StmtKind::Try(v) => self.emit_try_stmt(v, stmt.span),
+      ast::StmtKind::Throw(v) => self.emit_throw_stmt(v, stmt.span),
+      ast::StmtKind::With(v) => self.emit_with_stmt(v, stmt.span),
+      ast::StmtKind::MultiAssign(v) => self.emit_multi_assign_stmt(v, stmt.span),
+    }
+  }
+
+  /// Report an error if the host or a pragma of the module forbids the kind
+  /// of statement `stmt` is, and remember that the module uses it.
+  ///
+  /// The error points at the keyword which starts the statement, or at the
+  /// name of a function or class, rather than the whole body.
+  fn check_forbidden(&mut self, stmt: &'src ast::Stmt<'src>) {
+    let keyword = |len: usize| Span::from(stmt.span.start..stmt.span.start + len);
+    let (construct, span) = match stmt.deref() {
+      ast::StmtKind::Import(v) => match &**v {
+        ast::Import::Module { .. } => (Construct::Import, keyword("import".len())),
+        ast::Import::Symbols { .. } => (Construct::Import, keyword("from".len())),
+      },
+      ast::StmtKind::Class(v) => (Construct::Class, v.name.span),
+      ast::StmtKind::Func(v) => (Construct::Function, v.name.span),
+      ast::StmtKind::Loop(v) => match &**v {
+        ast::Loop::For(_) => (Construct::For, keyword("for".len())),
+        ast::Loop::While(_) => (Construct::While, keyword("while".len())),
+        ast::Loop::Infinite(_) | ast::Loop::Repeat(_) => (Construct::Loop, keyword("loop".len())),
+      },
+      ast::StmtKind::Try(_) => (Construct::Try, keyword("try".len())),
+      ast::StmtKind::With(_) => (Construct::With, keyword("with".len())),
+      _ => return,
+    };
+    if !self.module.constructs.contains(&construct) {
+      self.module.constructs.push(construct);
+    }
+    if self.global.forbids(construct) {
+      self.errors.push(SpannedError::new(
+        format!("{construct} are not allowed"),
+        span,
+      ));
+    } else if self.ast.pragmas.forbids(construct) {
+      self.errors.push(SpannedError::new(
+        format!(
+          "{construct} are not allowed by `# pragma: {}`",
+          pragma::forbidding(construct)
+        ),
+        span,
+      ));
     }
   }
 
@@ -28,8 +78,74 @@ impl<'src> State<'src> {
   }
 
   fn emit_var_stmt(&mut self, stmt: &'src ast::Var<'src>, span: Span) {
+    let value = self.infer(&stmt.value);
     self.emit_expr(&stmt.value);
-    self.emit_var(stmt.name.lexeme(), span)
+    self.emit_var(stmt.name.lexeme(), span);
+    self.remember(stmt.name.as_str(), value);
+    self.hover_variable(stmt.name.as_str(), stmt.name.span);
+  }
+
+  fn emit_multi_assign_stmt(&mut self, stmt: &'src ast::MultiAssign<'src>, span: Span) {
+    // `obj` and `key` of each field and index target
+    let mut receivers = Vec::new();
+    for target in stmt.targets.iter().flatten() {
+      match target {
+        ast::AssignTarget::Var(_) => receivers.push((None, None)),
+        ast::AssignTarget::Field(get) => {
+          let obj = self.alloc_register();
+          self.emit_expr(&get.target);
+          self.emit_store(obj.clone(), get.target.span);
+          receivers.push((Some(obj), None));
+        }
+        ast::AssignTarget::Index(get) => {
+          let obj = self.alloc_register();
+          let key = self.alloc_register();
+          self.emit_expr(&get.target);
+          self.emit_store(obj.clone(), get.target.span);
+          self.emit_expr(&get.key);
+          self.emit_store(key.clone(), get.key.span);
+          receivers.push((Some(obj), Some(key)));
+        }
+      }
+    }
+
+    let mut values = Vec::with_capacity(stmt.values.len());
+    for value in stmt.values.iter() {
+      let reg = self.alloc_register();
+      self.emit_expr_into(value, reg.clone());
+      values.push((reg, self.infer(value)));
+    }
+
+    let mut receivers = receivers.into_iter();
+    for group in stmt.targets.iter() {
+      for (target, (value, inferred)) in group.iter().zip(values.iter()) {
+        let (obj, key) = receivers.next().unwrap();
+        self.emit_load(value.clone(), span);
+        match target {
+          ast::AssignTarget::Var(var) => {
+            self.remember(var.name.as_str(), inferred.clone());
+            self.emit_assign_var(var, span);
+          }
+          ast::AssignTarget::Field(get) => {
+            let name = self.constant_name(&get.name);
+            self.builder().emit(
+              StoreField {
+                obj: obj.unwrap().access(),
+                name,
+              },
+              span,
+            );
+          }
+          ast::AssignTarget::Index(_) => self.builder().emit(
+            StoreIndex {
+              obj: obj.unwrap().access(),
+              key: key.unwrap().access(),
+            },
+            span,
+          ),
+        }
+      }
+    }
   }
 
   fn emit_if_stmt(&mut self, stmt: &'src ast::If<'src>, span: Span) {
@@ -64,17 +180,22 @@ impl<'src> State<'src> {
     match stmt {
       ast::Loop::For(v) => match &v.iter {
         ast::ForIter::Range(range) => self.emit_for_range_loop(v, range),
-        ast::ForIter::Expr(iter) => self.emit_for_iter_loop(v, iter),
+        ast::ForIter::Expr(iter) => self.emit_for_iter_loop(v, iter, false),
+        ast::ForIter::Stream(iter) => self.emit_for_iter_loop(v, iter, true),
       },
       ast::Loop::While(v) => self.emit_while_loop(v, span),
       ast::Loop::Infinite(v) => self.emit_inf_loop(v, span),
+      ast::Loop::Repeat(v) => self.emit_repeat_loop(v, span),
     }
   }
 
+  /// A range loop counts up if its step is positive, and down if it is
+  /// negative. A step which is an int literal decides that at compile time,
+  /// any other step is checked each time the loop condition is.
   fn emit_for_range_loop(&mut self, stmt: &'src ast::For<'src>, range: &'src ast::IterRange<'src>) {
     let cond = self.builder().loop_header();
     let latch = self.builder().loop_header();
-    let body = self.builder().label("body");
+    let body = self.builder().multi_label("body");
     let end = self.builder().multi_label("end");
 
     self.current_function().enter_scope();
@@ -89,30 +210,64 @@ impl<'src> State<'src> {
     self.emit_expr(&range.end);
     self.emit_store(end_register.clone(), range.span());
 
+    let step = match &range.step {
+      None => RangeStep::Const(1),
+      Some(step) => match const_int(step) {
+        Some(0) => {
+          self
+            .errors
+            .push(SpannedError::new("range step cannot be zero", step.span));
+          RangeStep::Const(1)
+        }
+        Some(value) => RangeStep::Const(value),
+        None => {
+          let step_register = self.alloc_register();
+          self.emit_expr(step);
+          self.emit_store(step_register.clone(), step.span);
+          self.emit_step_check(step_register.clone(), step.span);
+          RangeStep::Dynamic(step_register)
+        }
+      },
+    };
+    self.hoist_loop_invariants(None, &stmt.body, range.span());
+
     self.builder().bind_loop_header(&cond);
-    self.emit_load(end_register.clone(), range.span());
-    if range.inclusive {
-      self.builder().emit(
-        CmpLe {
-          lhs: item_register.access(),
-        },
-        range.span(),
-      );
-    } else {
-      self.builder().emit(
-        CmpLt {
-          lhs: item_register.access(),
-        },
-        range.span(),
-      );
+    match &step {
+      RangeStep::Const(value) => {
+        self.emit_range_cmp(&item_register, &end_register, *value < 0, range);
+      }
+      RangeStep::Dynamic(step_register) => {
+        let ascending = self.builder().label("ascending");
+        self
+          .builder()
+          .emit(LoadSmi { value: op::Smi(0) }, range.span());
+        self.builder().emit(
+          CmpLt {
+            lhs: step_register.access(),
+          },
+          range.span(),
+        );
+        self.builder().emit_jump_if_false(&ascending, range.span());
+        self.emit_range_cmp(&item_register, &end_register, true, range);
+        self.builder().emit_jump_if_false(&end, range.span());
+        self.builder().emit_jump(&body, range.span());
+        self.builder().bind_label(ascending);
+        self.emit_range_cmp(&item_register, &end_register, false, range);
+      }
     }
     self.builder().emit_jump_if_false(&end, range.span());
     self.builder().emit_jump(&body, range.span());
 
     self.builder().bind_loop_header(&latch);
-    self
-      .builder()
-      .emit(LoadSmi { value: op::Smi(1) }, range.span());
+    match &step {
+      RangeStep::Const(value) => self.builder().emit(
+        LoadSmi {
+          value: op::Smi(*value),
+        },
+        range.span(),
+      ),
+      RangeStep::Dynamic(step_register) => self.emit_load(step_register.clone(), range.span()),
+    }
     self.builder().emit(
       Add {
         lhs: item_register.access(),
@@ -123,9 +278,13 @@ impl<'src> State<'src> {
     self.builder().emit_jump_loop(&cond, range.span());
 
     self.builder().bind_label(body);
-    let (latch, end) = self.emit_loop_body((latch, end), &stmt.body);
+    let (latch, end) = self.emit_loop_body((latch, end), &stmt.body, None);
     self.builder().emit_jump_loop(&latch, range.span());
+    self.end_hoisted();
 
+    if let RangeStep::Dynamic(step_register) = &step {
+      let _ = step_register.access();
+    }
     let _ = end_register.access();
     let _ = item_register.access();
 
@@ -133,7 +292,48 @@ impl<'src> State<'src> {
     self.current_function().leave_scope();
   }
 
-  fn emit_for_iter_loop(&mut self, stmt: &'src ast::For<'src>, iter: &'src ast::Expr<'src>) {
+  /// Compare the item of a range loop to its end, leaving whether the loop
+  /// should go on in the accumulator.
+  fn emit_range_cmp(
+    &mut self,
+    item: &Register,
+    end: &Register,
+    descending: bool,
+    range: &'src ast::IterRange<'src>,
+  ) {
+    self.emit_load(end.clone(), range.span());
+    let lhs = item.access();
+    let span = range.span();
+    match (descending, range.inclusive) {
+      (false, false) => self.builder().emit(CmpLt { lhs }, span),
+      (false, true) => self.builder().emit(CmpLe { lhs }, span),
+      (true, false) => self.builder().emit(CmpGt { lhs }, span),
+      (true, true) => self.builder().emit(CmpGe { lhs }, span),
+    }
+  }
+
+  /// A step of zero would never reach the end of the range.
+  fn emit_step_check(&mut self, step: Register, span: Span) {
+    let nonzero = self.builder().label("nonzero");
+    self.builder().emit(LoadSmi { value: op::Smi(0) }, span);
+    self.builder().emit(CmpEq { lhs: step.access() }, span);
+    self.builder().emit_jump_if_false(&nonzero, span);
+    let message = self.constant_name("range step cannot be zero");
+    self.emit_load_const(message, span);
+    self.builder().emit(Throw, span);
+    self.builder().bind_label(nonzero);
+  }
+
+  /// A `for await` loop over a stream asks for the next item before it asks
+  /// whether the stream is done, and discards the item once it is. This lets
+  /// a stream with an async `next` method find out that it has ended while
+  /// waiting for the next item, instead of having to know it ahead of time.
+  fn emit_for_iter_loop(
+    &mut self,
+    stmt: &'src ast::For<'src>,
+    iter: &'src ast::Expr<'src>,
+    is_stream: bool,
+  ) {
     let iter_register = self.alloc_register();
     let item_register = self.alloc_register();
 
@@ -158,29 +358,31 @@ impl<'src> State<'src> {
     self.builder().emit(LoadNone, iter.span);
     self.emit_store(item_register.clone(), iter.span);
     self.declare_local(stmt.item.lexeme(), item_register.clone());
+    self.hoist_loop_invariants(None, &stmt.body, iter.span);
 
-    // condition
     self.builder().bind_loop_header(&cond);
-    self.emit_load(iter_register.clone(), iter.span);
-    self
-      .builder()
-      .emit(LoadField { name: done_const }, iter.span);
-    self.builder().emit(Call0, iter.span);
-    self.builder().emit(Not, iter.span);
-    // if `iter.done()` jump .end
-    // else jump .body
-    self.builder().emit_jump_if_false(&end, iter.span);
-
-    // `item = iter.next()`
-    self.emit_load(iter_register.clone(), iter.span);
-    self
-      .builder()
-      .emit(LoadField { name: next_const }, iter.span);
-    self.builder().emit(Call0, iter.span);
-    self.emit_store(item_register.clone(), iter.span);
+    if is_stream {
+      self.emit_iter_next(
+        iter_register.clone(),
+        item_register.clone(),
+        next_const,
+        iter.span,
+      );
+      self.emit_iter_done(iter_register.clone(), &end, done_const, iter.span);
+    } else {
+      self.emit_iter_done(iter_register.clone(), &end, done_const, iter.span);
+      self.emit_iter_next(
+        iter_register.clone(),
+        item_register.clone(),
+        next_const,
+        iter.span,
+      );
+    }
 
-    let (cond, end) = self.emit_loop_body((cond, end), &stmt.body);
+    // leaving the loop early closes the iterator
+    let (cond, end) = self.emit_loop_body((cond, end), &stmt.body, Some(iter_register.clone()));
     self.builder().emit_jump_loop(&cond, iter.span);
+    self.end_hoisted();
 
     let _ = item_register.access();
     let _ = iter_register.access();
@@ -189,18 +391,49 @@ impl<'src> State<'src> {
     self.current_function().leave_scope();
   }
 
+  /// `if iter.done(): jump end`
+  fn emit_iter_done(
+    &mut self,
+    iter_register: Register,
+    end: &MultiLabel,
+    done_const: op::Constant,
+    span: Span,
+  ) {
+    self.emit_load(iter_register, span);
+    self.builder().emit(LoadField { name: done_const }, span);
+    self.builder().emit(Call0, span);
+    self.builder().emit(Not, span);
+    self.builder().emit_jump_if_false(end, span);
+  }
+
+  /// `item = iter.next()`
+  fn emit_iter_next(
+    &mut self,
+    iter_register: Register,
+    item_register: Register,
+    next_const: op::Constant,
+    span: Span,
+  ) {
+    self.emit_load(iter_register, span);
+    self.builder().emit(LoadField { name: next_const }, span);
+    self.builder().emit(Call0, span);
+    self.emit_store(item_register, span);
+  }
+
   fn emit_while_loop(&mut self, stmt: &'src ast::While<'src>, span: Span) {
     let start = self.builder().loop_header();
     let end = self.builder().multi_label("end");
 
     self.current_function().enter_scope();
+    self.hoist_loop_invariants(Some(&stmt.cond), &stmt.body, span);
     self.builder().bind_loop_header(&start);
 
     self.emit_expr(&stmt.cond);
     self.builder().emit_jump_if_false(&end, stmt.cond.span);
 
-    let (start, end) = self.emit_loop_body((start, end), &stmt.body);
+    let (start, end) = self.emit_loop_body((start, end), &stmt.body, None);
     self.builder().emit_jump_loop(&start, span);
+    self.end_hoisted();
 
     self.builder().bind_label(end);
     self.current_function().leave_scope();
@@ -211,10 +444,65 @@ impl<'src> State<'src> {
     let end = self.builder().multi_label("end");
 
     self.current_function().enter_scope();
+    self.hoist_loop_invariants(None, &stmt.body, span);
     self.builder().bind_loop_header(&start);
 
-    let (start, end) = self.emit_loop_body((start, end), &stmt.body);
+    let (start, end) = self.emit_loop_body((start, end), &stmt.body, None);
     self.builder().emit_jump_loop(&start, span);
+    self.end_hoisted();
+
+    self.builder().bind_label(end);
+    self.current_function().leave_scope();
+  }
+
+  fn emit_repeat_loop(&mut self, stmt: &'src ast::Repeat<'src>, span: Span) {
+    let cond = self.builder().loop_header();
+    let latch = self.builder().loop_header();
+    let body = self.builder().label("body");
+    let end = self.builder().multi_label("end");
+
+    self.current_function().enter_scope();
+
+    // the number of iterations left, which is not visible to the body
+    let count_register = self.alloc_register();
+    self.emit_expr(&stmt.count);
+    self.emit_store(count_register.clone(), stmt.count.span);
+    self.hoist_loop_invariants(None, &stmt.body, span);
+
+    // `count > 0`
+    self.builder().bind_loop_header(&cond);
+    self
+      .builder()
+      .emit(LoadSmi { value: op::Smi(0) }, stmt.count.span);
+    self.builder().emit(
+      CmpGt {
+        lhs: count_register.access(),
+      },
+      stmt.count.span,
+    );
+    self.builder().emit_jump_if_false(&end, stmt.count.span);
+    self.builder().emit_jump(&body, stmt.count.span);
+
+    // `count -= 1`
+    self.builder().bind_loop_header(&latch);
+    self
+      .builder()
+      .emit(LoadSmi { value: op::Smi(1) }, stmt.count.span);
+    self.builder().emit(
+      Sub {
+        lhs: count_register.access(),
+      },
+      stmt.count.span,
+    );
+    self.emit_store(count_register.clone(), stmt.count.span);
+    self.builder().emit_jump_loop(&cond, stmt.count.span);
+
+    self.builder().bind_label(body);
+    let (latch, end) = self.emit_loop_body((latch, end), &stmt.body, None);
+    self.builder().emit_jump_loop(&latch, span);
+    self.end_hoisted();
+
+    let _ = count_register.access();
 
     self.builder().bind_label(end);
     self.current_function().leave_scope();
@@ -224,8 +512,9 @@ impl<'src> State<'src> {
     &mut self,
     (start, end): (LoopHeader, MultiLabel),
     body: &'src [ast::Stmt<'src>],
+    iter: Option<Register>,
   ) -> (LoopHeader, MultiLabel) {
-    let previous = self.current_function().enter_loop_body(start, end);
+    let previous = self.current_function().enter_loop_body(start, end, iter);
     self.emit_stmt_list(body);
     let current = self.current_function().leave_loop_body(previous);
     (current.start, current.end)
@@ -241,6 +530,13 @@ impl<'src> State<'src> {
         } else {
           self.builder().emit(LoadNone, span);
         }
+        if !self.current_function().cleanups.is_empty() {
+          // `__exit__` and `close` overwrite the accumulator
+          let value = self.alloc_register();
+          self.emit_store(value.clone(), span);
+          self.emit_leave_blocks(0, 0, span);
+          self.emit_load(value, span);
+        }
         self.builder().emit(Return, span);
       }
       ast::Ctrl::Yield(stmt) => {
@@ -252,33 +548,279 @@ impl<'src> State<'src> {
         self.builder().emit(Yield, span);
       }
       ast::Ctrl::Continue => {
-        let function = self.current_function();
-        let loop_ = function
+        let loop_ = self
+          .current_function()
           .current_loop
           .as_ref()
           .expect("attempted to emit continue outside of loop");
+        // the iterator stays open
+        let (try_depth, cleanups) = (loop_.try_depth, loop_.cleanups + loop_.closes as usize);
+        // leave any `try` and `with` blocks inside of the loop
+        self.emit_leave_blocks(try_depth, cleanups, span);
+        let function = self.current_function();
+        let loop_ = function.current_loop.as_ref().unwrap();
         function.builder.emit_jump_loop(&loop_.start, span);
       }
       ast::Ctrl::Break => {
-        let function = self.current_function();
-        let loop_ = function
+        let loop_ = self
+          .current_function()
           .current_loop
           .as_ref()
-          .expect("attempted to emit continue outside of loop");
+          .expect("attempted to emit break outside of loop");
+        let (try_depth, cleanups) = (loop_.try_depth, loop_.cleanups);
+        // leave any `try` and `with` blocks inside of the loop, and close
+        // the iterator
+        self.emit_leave_blocks(try_depth, cleanups, span);
+        let function = self.current_function();
+        let loop_ = function.current_loop.as_ref().unwrap();
         function.builder.emit_jump(&loop_.end, span);
       }
     }
   }
 
+  /// Leave every `try` block nested deeper than `try_depth`, and every
+  /// block past the first `cleanups` in `Function::cleanups`, calling
+  /// `__exit__` on the way out of each `with` block and `close` on the
+  /// iterator of each `for` loop.
+  fn emit_leave_blocks(&mut self, try_depth: usize, cleanups: usize, span: Span) {
+    let cleanups = self.current_function().cleanups[cleanups..]
+      .iter()
+      .rev()
+      .map(|cleanup| (cleanup.kind.clone(), cleanup.try_depth))
+      .collect::<Vec<_>>();
+
+    let mut depth = self.current_function().try_depth;
+    for (kind, cleanup_depth) in cleanups {
+      // the block's own handler must be gone before `__exit__` is called,
+      // otherwise an error in `__exit__` would call it again
+      for _ in cleanup_depth..depth {
+        self.builder().emit(PopHandler, span);
+      }
+      depth = cleanup_depth;
+      match kind {
+        CleanupKind::Exit(context) => self.emit_exit_call(context, None, span),
+        CleanupKind::Close(iter) => self.emit_close_call(iter, span),
+      }
+    }
+    for _ in try_depth..depth {
+      self.builder().emit(PopHandler, span);
+    }
+  }
+
+  /// Emit `iter.close()`, unless `iter` has no `close` method.
+  fn emit_close_call(&mut self, iter: Register, span: Span) {
+    let skip = self.builder().label("skip");
+    let name = self.constant_name("close");
+    self.emit_load(iter, span);
+    self.builder().emit(LoadFieldOpt { name }, span);
+    self.builder().emit_jump_if_none(&skip, span);
+    self.builder().emit(Call0, span);
+    self.builder().bind_label(skip);
+  }
+
+  /// Emit `context.__exit__(error)`, or `context.__exit__(none)` if there is
+  /// no `error`. The result is left in the accumulator.
+  fn emit_exit_call(&mut self, context: Register, error: Option<Register>, span: Span) {
+    let args = self.alloc_call_window(2);
+    let name = self.constant_name("__exit__");
+    self.emit_load(context, span);
+    self.builder().emit(LoadField { name }, span);
+    self.emit_store(args.get(0), span);
+    match error {
+      Some(error) => self.emit_load(error, span),
+      None => self.builder().emit(LoadNone, span),
+    }
+    self.emit_store(args.get(1), span);
+    self.builder().emit(
+      Call {
+        callee: args.get(0).access(),
+        args: op::Count(1),
+      },
+      span,
+    );
+  }
+
+  fn emit_try_stmt(&mut self, stmt: &'src ast::Try<'src>, span: Span) {
+    let catch = self.builder().label("catch");
+    let end = self.builder().multi_label("end");
+
+    self.builder().emit_push_handler(&catch, span);
+    self.current_function().try_depth += 1;
+    self.current_function().enter_scope();
+    self.emit_stmt_list(&stmt.body);
+    self.current_function().leave_scope();
+    self.current_function().try_depth -= 1;
+    self.builder().emit(PopHandler, span);
+    self.builder().emit_jump(&end, span);
+
+    // the handler is popped by the VM before it jumps here,
+    // and the error value is in the accumulator
+    self.builder().bind_label(catch);
+    self.current_function().enter_scope();
+    let error = self.alloc_register();
+    self.emit_store(error.clone(), span);
+
+    let mut catches_all = false;
+    for clause in stmt.catches.iter() {
+      let next = match &clause.class {
+        Some(class) => {
+          let next = self.builder().label("next");
+          self.emit_expr(class);
+          self.builder().emit(
+            CmpType {
+              lhs: error.access(),
+            },
+            class.span,
+          );
+          self.builder().emit_jump_if_false(&next, class.span);
+          Some(next)
+        }
+        None => {
+          catches_all = true;
+          None
+        }
+      };
+
+      self.current_function().enter_scope();
+      if let Some(binding) = &clause.binding {
+        self.emit_load(error.clone(), binding.span);
+        self.emit_var(binding.lexeme(), binding.span);
+      }
+      self.emit_stmt_list(&clause.body);
+      self.current_function().leave_scope();
+      self.builder().emit_jump(&end, span);
+
+      if let Some(next) = next {
+        self.builder().bind_label(next);
+      }
+    }
+
+    // none of the `catch` clauses matched, pass the error on
+    if !catches_all {
+      self.emit_load(error, span);
+      self.builder().emit(Throw, span);
+    }
+    self.current_function().leave_scope();
+
+    self.builder().bind_label(end);
+  }
+
+  fn emit_with_stmt(&mut self, stmt: &'src ast::With<'src>, span: Span) {
+    let catch = self.builder().label("catch");
+    let rethrow = self.builder().label("rethrow");
+    let end = self.builder().multi_label("end");
+
+    self.current_function().enter_scope();
+    let context = self.alloc_register();
+    self.emit_expr(&stmt.context);
+    self.emit_store(context.clone(), stmt.context.span);
+    let enter = self.constant_name("__enter__");
+    self.emit_load(context.clone(), span);
+    self.builder().emit(LoadField { name: enter }, span);
+    self.builder().emit(Call0, span);
+    if let Some(binding) = &stmt.binding {
+      self.emit_var(binding.lexeme(), binding.span);
+    }
+
+    self.builder().emit_push_handler(&catch, span);
+    let try_depth = self.current_function().try_depth;
+    self.current_function().cleanups.push(Cleanup {
+      kind: CleanupKind::Exit(context.clone()),
+      try_depth,
+    });
+    self.current_function().try_depth += 1;
+    self.current_function().enter_scope();
+    self.emit_stmt_list(&stmt.body);
+    self.current_function().leave_scope();
+    self.current_function().try_depth -= 1;
+    self.current_function().cleanups.pop();
+    self.builder().emit(PopHandler, span);
+    self.emit_exit_call(context.clone(), None, span);
+    self.builder().emit_jump(&end, span);
+
+    // the block was left because of an error, which is passed on
+    // unless `__exit__` returns a truthy value
+    self.builder().bind_label(catch);
+    let error = self.alloc_register();
+    self.emit_store(error.clone(), span);
+    self.emit_exit_call(context, Some(error.clone()), span);
+    self.builder().emit_jump_if_false(&rethrow, span);
+    self.builder().emit_jump(&end, span);
+    self.builder().bind_label(rethrow);
+    self.emit_load(error, span);
+    self.builder().emit(Throw, span);
+    self.current_function().leave_scope();
+
+    self.builder().bind_label(end);
+  }
+
+  fn emit_throw_stmt(&mut self, stmt: &'src ast::Throw<'src>, span: Span) {
+    self.emit_expr(&stmt.value);
+    self.builder().emit(Throw, span);
+  }
+
   fn emit_func_stmt(&mut self, stmt: &'src ast::Func<'src>) {
+    self.hover_at(stmt.name.span, |_| Some(hover::function_info(stmt, None)));
+
+    if stmt.decorators.is_empty() {
+      let function = self.emit_function(stmt, false);
+      let desc = self.constant_value(function.ptr);
+      self.builder().emit(MakeFn { desc }, stmt.name.span);
+      function.upvalues.finish();
+      self.emit_var(stmt.name.lexeme(), stmt.name.span);
+      let known = self.infer_function(stmt, None);
+      self.remember(stmt.name.as_str(), known);
+      self.declare_inlinable(stmt);
+      return;
+    }
+
+    // the body refers to the decorated function by name, so that
+    // recursive calls go through the decorators
+    let var = self.declare_var_ahead(stmt.name.lexeme(), stmt.name.span);
     let function = self.emit_function(stmt, false);
     let desc = self.constant_value(function.ptr);
     self.builder().emit(MakeFn { desc }, stmt.name.span);
     function.upvalues.finish();
-    self.emit_var(stmt.name.lexeme(), stmt.name.span);
+
+    // `@a @b fn f` is `f = a(b(f))`
+    for decorator in stmt.decorators.iter().rev() {
+      let args = self.alloc_call_window(2);
+      self.emit_store(args.get(1), decorator.span);
+      self.emit_expr(decorator);
+      self.emit_store(args.get(0), decorator.span);
+      self.builder().emit(
+        Call {
+          callee: args.access(0),
+          args: op::Count(1),
+        },
+        decorator.span,
+      );
+    }
+    self.emit_var_declared_ahead(var, stmt.name.lexeme(), stmt.name.span);
   }
 
   fn emit_class_stmt(&mut self, stmt: &'src ast::Class<'src>) {
+    self.hover_at(stmt.name.span, |_| Some(hover::class_info(stmt)));
+    for method in stmt.members.init.iter().chain(stmt.members.methods.iter()) {
+      self.hover_at(method.name.span, |_| {
+        Some(hover::function_info(method, Some(&stmt.name)))
+      });
+    }
+
+    // methods may refer to the class by name
+    let var = self.declare_var_ahead(stmt.name.lexeme(), stmt.name.span);
+    let known = self.infer_class(stmt);
+    self.remember(stmt.name.as_str(), known.clone());
+
+    let fields = Table::with_capacity(stmt.members.fields.len());
+    for field in stmt.members.fields.iter() {
+      fields.insert(self.global.intern(field.name.to_string()), Value::none());
+    }
+    let fields = self.global.alloc(fields);
+    self
+      .class_fields
+      .push(stmt.parent.is_none().then(|| fields.clone()));
+
     let mut preserve = Vec::new();
 
     let init = match stmt.members.init.as_ref() {
@@ -290,19 +832,15 @@ impl<'src> State<'src> {
       None => None,
     };
 
-    let mut methods = IndexMap::with_capacity(stmt.members.methods.len());
+    let mut methods =
+      StrMap::with_capacity_and_hasher(stmt.members.methods.len(), Default::default());
     for function in stmt.members.methods.iter() {
       let function = self.emit_function(function, false);
       preserve.push(function.upvalues);
       let function = function.ptr;
       methods.insert(function.name.clone(), function.clone());
     }
-
-    let fields = Table::with_capacity(stmt.members.fields.len());
-    for field in stmt.members.fields.iter() {
-      fields.insert(self.global.intern(field.name.to_string()), Value::none());
-    }
-    let fields = self.global.alloc(fields);
+    self.class_fields.pop();
 
     let class = self.global.alloc(object::ClassDescriptor {
       name: self.global.intern(stmt.name.to_string()),
@@ -357,7 +895,48 @@ impl<'src> State<'src> {
       upvalues.finish();
     }
 
-    self.emit_var(stmt.name.lexeme(), stmt.name.span);
+    self.emit_var_declared_ahead(var, stmt.name.lexeme(), stmt.name.span);
+    self.remember(stmt.name.as_str(), known);
+  }
+
+  /// Declare the variable `name` before its value is emitted, so that
+  /// functions emitted in between may refer to it.
+  ///
+  /// Outside of the root module, where variables are not globals, they
+  /// would otherwise not be able to resolve it. The value is assigned by
+  /// [`Self::emit_var_declared_ahead`], through the cell which closures
+  /// captured in the meantime.
+  fn declare_var_ahead(&mut self, name: Cow<'src, str>, span: Span) -> Option<DeclaredVar> {
+    if self.is_global_scope() {
+      match self.module.is_root {
+        true => None,
+        false => Some(DeclaredVar::Module(self.declare_module_var(name))),
+      }
+    } else {
+      let register = self.alloc_register();
+      self.builder().emit(LoadNone, span);
+      self.emit_store(register.clone(), span);
+      self.declare_local(name, register.clone());
+      Some(DeclaredVar::Local(register))
+    }
+  }
+
+  fn emit_var_declared_ahead(
+    &mut self,
+    var: Option<DeclaredVar>,
+    name: Cow<'src, str>,
+    span: Span,
+  ) {
+    match var {
+      Some(DeclaredVar::Local(register)) => self.builder().emit(
+        StoreCell {
+          reg: register.access(),
+        },
+        span,
+      ),
+      Some(DeclaredVar::Module(idx)) => self.builder().emit(StoreModuleVar { idx }, span),
+      None => self.emit_var(name, span),
+    }
   }
 
   fn emit_expr_stmt(&mut self, expr: &'src ast::Expr<'src>) {
@@ -377,8 +956,7 @@ impl<'src> State<'src> {
         let args = self.alloc_register_slice(values.len());
 
         for (i, value) in values.iter().enumerate() {
-          self.emit_expr(value);
-          self.emit_store(args.get(i), span);
+          self.emit_expr_into(value, args.get(i));
         }
 
         self.builder().emit(
@@ -395,13 +973,13 @@ impl<'src> State<'src> {
   fn emit_import_stmt(&mut self, stmt: &'src ast::Import<'src>, span: Span) {
     match stmt {
       ast::Import::Module { path, alias } => {
+        // `import a.b` binds the module to `b`
         let name = alias.as_ref().unwrap_or(path.last().unwrap());
-        let path = path.iter().map(|p| p.as_ref()).join(".");
+        let path = path.iter().map(|p| p.as_ref()).join(".").to_string();
+        self.imports.insert(name.lexeme(), path.clone());
         let path = self.constant_name(path);
-        let dst = self.alloc_register();
-        self.declare_local(name.lexeme(), dst.clone());
         self.builder().emit(Import { path }, span);
-        self.builder().emit(Store { reg: dst.access() }, span);
+        self.emit_var(name.lexeme(), span);
       }
       ast::Import::Symbols { path, symbols } => {
         let path = path.iter().map(|p| p.as_ref()).join(".");
@@ -411,17 +989,39 @@ impl<'src> State<'src> {
         self.builder().emit(Store { reg: temp.access() }, span);
 
         for symbol in symbols {
-          let name = symbol.alias.as_ref().unwrap_or(&symbol.name);
-          let name_idx = self.constant_name(name);
-
+          let name = self.constant_name(&symbol.name);
           self.emit_load(temp.clone(), span);
-          self.builder().emit(LoadField { name: name_idx }, span);
+          self.builder().emit(LoadField { name }, span);
 
-          let dst = self.alloc_register();
-          self.declare_local(name.lexeme(), dst.clone());
-          self.emit_store(dst.clone(), span);
+          let binding = symbol.alias.as_ref().unwrap_or(&symbol.name);
+          self.emit_var(binding.lexeme(), span);
         }
       }
     }
   }
 }
+
+/// A variable declared by [`State::declare_var_ahead`].
+enum DeclaredVar {
+  Local(Register),
+  Module(op::ModuleVar),
+}
+
+enum RangeStep {
+  Const(i32),
+  Dynamic(Register),
+}
+
+/// The value of an int literal, which may be negated.
+fn const_int(expr: &ast::Expr<'_>) -> Option<i32> {
+  match &**expr {
+    ast::ExprKind::Literal(v) => match &**v {
+      ast::Literal::Int(v) => Some(*v),
+      _ => None,
+    },
+    ast::ExprKind::Unary(v) if matches!(v.op, ast::UnaryOp::Minus) => {
+      const_int(&v.right)?.checked_neg()
+    }
+    _ => None,
+  }
+}
@@ -1,5 +1,6 @@
 #[macro_use]
 mod macros;
+mod coverage;
 
 use super::*;
 use crate::internal::syntax;
@@ -84,6 +85,15 @@ check! {
 
 check!(call_arg_subexpr, r#"f(a+b)"#);
 
+check! {
+  call_result_into_register,
+  r#"
+    [f(0), a.b(1)]
+    f(0) + g(1)
+    print f(0), 1
+  "#
+}
+
 check! {
   function_no_params,
   r#"
@@ -327,6 +337,16 @@ check! {
   "#
 }
 
+check! {
+  for_iter_break_continue,
+  r#"
+    for v in items:
+      if v:
+        continue
+      break
+  "#
+}
+
 check! {
   while_nested_while,
   r#"
@@ -399,6 +419,26 @@ check! {
   "#
 }
 
+check! {
+  repeat_10_print,
+  r#"
+    loop 10:
+      print "test"
+  "#
+}
+
+check! {
+  repeat_break_continue,
+  r#"
+    v := 0
+    loop v + 3:
+      if v > 1:
+        break
+      v += 1
+      continue
+  "#
+}
+
 check! {
   for_iter_array,
   r#"
@@ -408,6 +448,14 @@ check! {
   "#
 }
 
+check! {
+  for_await_stream,
+  r#"
+    for await row in rows():
+      print row
+  "#
+}
+
 check!(method_call_0, r#"o.f()"#);
 
 check!(method_call_1, r#"o.f(0)"#);
@@ -448,6 +496,23 @@ check! {
   "#
 }
 
+check! {
+  class_field_slots,
+  r#"
+    class T:
+      a = 0
+      b = 1
+      init(self, b):
+        self.b = b
+      fn sum(self):
+        return self.a + self.b
+    class U(T):
+      c = 2
+      fn get(self):
+        return self.c
+  "#
+}
+
 check! {
   class_with_field_and_closure_method,
   r#"
@@ -595,3 +660,124 @@ check! {
         print b
   "#
 }
+
+check! {
+  pipe_expr,
+  r#"
+    fn add(a, b):
+      return a + b
+    print 1 |> add(2) |> to_str
+  "#
+}
+
+check! {
+  class_derived_method_calls_super,
+  r#"
+    class T(U):
+      fn test(self):
+        return super.test()
+  "#
+}
+
+check! {
+  try_catch,
+  r#"
+    try:
+      throw "error"
+    catch Error as e:
+      print e
+  "#
+}
+
+check! {
+  with_stmt,
+  r#"
+    with open() as v:
+      print v
+  "#
+}
+
+check! {
+  multi_assign,
+  r#"
+    a, b.c, d[e] = b.c, d[e], a
+  "#
+}
+
+check! {
+  inline_calls,
+  inline=true,
+  r#"
+    fn square(x): return x * x
+    fn hypot2(a, b): return square(a) + square(b)
+    fn scaled(v): return { value: v * factor, unit: "m" }
+    print hypot2(3, 4)
+    print scaled(2)
+    print square
+  "#
+}
+
+check! {
+  inline_calls_skipped,
+  inline=true,
+  r#"
+    print early()
+    fn early(): return 1
+    fn countdown(n): return countdown(n - 1)
+    fn replaced(): return 0
+    replaced = none
+    fn with_default(a = 1): return a
+    fn two_statements(a):
+      print a
+      return a
+    fn ping(): return pong
+    fn wrapper():
+      pong := 0
+      return ping()
+    fn identity(a): return a
+    print countdown(3), replaced(), with_default(), two_statements(0)
+    print identity?(0), identity(0, 1)
+  "#
+}
+
+check! {
+  hoist_loop_skips_globals,
+  r#"
+    limit := 10
+    scale := 2
+    total := 0
+    i := 0
+    while i < limit:
+      total += i * scale
+      i += 1
+    print total
+  "#
+}
+
+check! {
+  hoist_loop_skips_module_vars,
+  as_module=true,
+  r#"
+    limit := 10
+    total := 0
+    for i in 0..limit:
+      total += limit - i
+  "#
+}
+
+check! {
+  hoist_loop_skipped,
+  r#"
+    n := 3
+    while n > later:
+      n -= 1
+    for i in 0..3:
+      print n
+      f()
+    fn g():
+      loop 2:
+        for j in 0..n:
+          print n, j
+    later := 0
+  "#
+}
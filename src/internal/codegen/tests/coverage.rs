@@ -0,0 +1,233 @@
+//! Checks that every kind of statement and expression appears in at least
+//! one emit snapshot and one VM snapshot, so that adding a node to the AST
+//! also requires adding tests which compile and run it.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::internal::syntax::{self, ast};
+use crate::internal::vm::global::Global;
+
+#[derive(Default)]
+struct Seen {
+  stmts: BTreeSet<&'static str>,
+  exprs: BTreeSet<&'static str>,
+}
+
+impl Seen {
+  fn block(&mut self, body: &[ast::Stmt]) {
+    for stmt in body {
+      self.stmt(stmt);
+    }
+  }
+
+  fn stmt(&mut self, stmt: &ast::Stmt) {
+    self.stmts.insert(stmt.variant());
+    match &**stmt {
+      ast::StmtKind::Var(v) => self.expr(&v.value),
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.block(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.block(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => match &**v {
+        ast::Loop::For(v) => {
+          match &v.iter {
+            ast::ForIter::Range(range) => {
+              self.expr(&range.start);
+              self.expr(&range.end);
+            }
+            ast::ForIter::Expr(iter) | ast::ForIter::Stream(iter) => self.expr(iter),
+          }
+          self.block(&v.body);
+        }
+        ast::Loop::While(v) => {
+          self.expr(&v.cond);
+          self.block(&v.body);
+        }
+        ast::Loop::Infinite(v) => self.block(&v.body),
+        ast::Loop::Repeat(v) => {
+          self.expr(&v.count);
+          self.block(&v.body);
+        }
+      },
+      ast::StmtKind::Ctrl(v) => match &**v {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => self.func(v),
+      ast::StmtKind::Class(v) => {
+        for field in v.members.fields.iter() {
+          self.expr(&field.default);
+        }
+        for method in v.members.init.iter().chain(v.members.methods.iter()) {
+          self.func(method);
+        }
+      }
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Pass | ast::StmtKind::Import(_) => {}
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Try(v) => {
+        self.block(&v.body);
+        for catch in v.catches.iter() {
+          if let Some(class) = &catch.class {
+            self.expr(class);
+          }
+          self.block(&catch.body);
+        }
+      }
+      ast::StmtKind::Throw(v) => self.expr(&v.value),
+      ast::StmtKind::With(v) => {
+        self.expr(&v.context);
+        self.block(&v.body);
+      }
+      ast::StmtKind::MultiAssign(v) => {
+        for target in v.targets.iter().flatten() {
+          match target {
+            ast::AssignTarget::Var(_) => {}
+            ast::AssignTarget::Field(get) => self.expr(&get.target),
+            ast::AssignTarget::Index(get) => {
+              self.expr(&get.target);
+              self.expr(&get.key);
+            }
+          }
+        }
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+    }
+  }
+
+  fn func(&mut self, func: &ast::Func) {
+    for decorator in func.decorators.iter() {
+      self.expr(decorator);
+    }
+    for param in func.params.pos.iter() {
+      if let Some(default) = &param.default {
+        self.expr(default);
+      }
+    }
+    self.block(&func.body);
+  }
+
+  fn expr(&mut self, expr: &ast::Expr) {
+    self.exprs.insert(expr.variant());
+    match &**expr {
+      ast::ExprKind::Literal(v) => match &**v {
+        ast::Literal::List(items) => {
+          for item in items {
+            self.expr(item);
+          }
+        }
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries {
+            self.expr(key);
+            self.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        self.expr(&v.left);
+        self.expr(&v.right);
+      }
+      ast::ExprKind::Unary(v) => self.expr(&v.right),
+      ast::ExprKind::GetVar(_) | ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+      ast::ExprKind::SetVar(v) => self.expr(&v.value),
+      ast::ExprKind::GetField(v) => self.expr(&v.target),
+      ast::ExprKind::SetField(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        self.expr(&v.target);
+        self.expr(&v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        self.expr(&v.target.target);
+        self.expr(&v.target.key);
+        self.expr(&v.value);
+      }
+      ast::ExprKind::Call(v) => {
+        self.expr(&v.target);
+        for arg in v.args.iter() {
+          self.expr(arg);
+        }
+      }
+    }
+  }
+
+  /// Describe the kinds of nodes which were not seen, if there are any.
+  fn missing(&self) -> Option<String> {
+    let stmts = ast::StmtKind::VARIANTS
+      .iter()
+      .filter(|v| !self.stmts.contains(*v))
+      .map(|v| format!("StmtKind::{v}"));
+    let exprs = ast::ExprKind::VARIANTS
+      .iter()
+      .filter(|v| !self.exprs.contains(*v))
+      .map(|v| format!("ExprKind::{v}"));
+    let missing = stmts.chain(exprs).collect::<Vec<_>>();
+    (!missing.is_empty()).then(|| missing.join(", "))
+  }
+}
+
+/// Parse the source of every snapshot in `dir`, which is the text between
+/// `start` and `end`.
+fn visit_snapshots(dir: &str, start: &str, end: &str) -> Seen {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+  let mut seen = Seen::default();
+  for entry in std::fs::read_dir(&dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.extension() != Some("snap".as_ref()) {
+      continue;
+    }
+    let snapshot = std::fs::read_to_string(&path).unwrap();
+    let Some((source, _)) =
+      (snapshot.split_once(start)).and_then(|(_, source)| source.split_once(end))
+    else {
+      panic!(
+        "{} has no source between `{start}` and `{end}`",
+        path.display()
+      );
+    };
+    match syntax::parse(Global::default(), source) {
+      Ok(module) => seen.block(&module.body),
+      Err(e) => panic!("{} does not parse: {e}", path.display()),
+    };
+  }
+  seen
+}
+
+#[test]
+fn every_node_is_emitted() {
+  let seen = visit_snapshots(
+    "src/internal/codegen/snapshots",
+    "# Input:\n",
+    "\n\n# Func:",
+  );
+  if let Some(missing) = seen.missing() {
+    panic!("no emit snapshot in `codegen/tests.rs` contains {missing}");
+  }
+}
+
+#[test]
+fn every_node_is_executed() {
+  let seen = visit_snapshots("src/internal/vm/snapshots", "# Source:\n", "\n\n# Result:");
+  if let Some(missing) = seen.missing() {
+    panic!("no VM snapshot in `vm/tests.rs` contains {missing}");
+  }
+}
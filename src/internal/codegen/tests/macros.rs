@@ -1,11 +1,13 @@
 macro_rules! check {
-  ($name:ident, $(as_module=$as_module:expr,)? $input:literal) => {
+  ($name:ident, $(as_module=$as_module:expr,)? $(inline=$inline:expr,)? $input:literal) => {
       #[allow(unused_mut, unused_assignments)]
       #[test]
       fn $name() {
       let mut as_module = false;
       $(as_module = $as_module;)?
-      let global = $crate::internal::vm::global::Global::default();
+      let mut config = $crate::internal::vm::Config::default();
+      $(config.inline_functions = $inline;)?
+      let global = $crate::internal::vm::global::Global::new(config);
       let input = indoc::indoc!($input);
       let module = match syntax::parse(global.clone(), input) {
         Ok(module) => module,
@@ -16,7 +18,7 @@ macro_rules! check {
           panic!("Failed to parse source, see errors above.")
         }
       };
-      let module = emit(global, &module, "main", !as_module);
+      let module = emit(global, &module, "main", !as_module).unwrap();
       let snapshot = format!(
         "# Input:\n{input}\n\n# Func:\n{}\n\n",
         module.root.disassemble(),
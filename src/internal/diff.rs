@@ -0,0 +1,174 @@
+//! Structural differences between two values, see [`crate::diff`].
+
+use std::fmt::Write;
+
+use super::object::class::ClassInstance;
+use super::object::{List, Str, Table};
+use super::value::Value;
+use crate::public::diff::{Change, Difference};
+
+/// Lists and tables may contain themselves, so nesting is limited
+/// instead of tracking visited objects. Past the limit, objects are only
+/// compared by identity.
+const MAX_DEPTH: usize = 128;
+
+pub fn diff(lhs: &Value, rhs: &Value) -> Vec<Difference> {
+  let mut differ = Differ {
+    path: String::from("$"),
+    differences: Vec::new(),
+  };
+  differ.value(lhs, rhs, 0);
+  differ.differences
+}
+
+struct Differ {
+  /// The path to the values being compared, such as `$.items[2]`.
+  path: String,
+  differences: Vec<Difference>,
+}
+
+impl Differ {
+  fn push(&mut self, change: Change) {
+    self.differences.push(Difference {
+      path: self.path.clone(),
+      change,
+    });
+  }
+
+  /// Run `f` with `segment` appended to the path.
+  fn nested(&mut self, segment: std::fmt::Arguments, f: impl FnOnce(&mut Self)) {
+    let len = self.path.len();
+    self.path.write_fmt(segment).unwrap();
+    f(self);
+    self.path.truncate(len);
+  }
+
+  fn value(&mut self, lhs: &Value, rhs: &Value, depth: usize) {
+    if let (Some(a), Some(b)) = (to_number(lhs), to_number(rhs)) {
+      if a != b {
+        self.push(changed(lhs, rhs));
+      }
+      return;
+    }
+
+    let (lhs_type, rhs_type) = (type_name(lhs), type_name(rhs));
+    if lhs_type != rhs_type {
+      self.push(Change::Type {
+        lhs: lhs_type,
+        rhs: rhs_type,
+      });
+      return;
+    }
+
+    let (Some(a), Some(b)) = (lhs.clone().to_any(), rhs.clone().to_any()) else {
+      if lhs.clone().to_bool() != rhs.clone().to_bool() {
+        self.push(changed(lhs, rhs));
+      }
+      return;
+    };
+    if a.ptr_eq(&b) {
+      return;
+    }
+    if depth >= MAX_DEPTH {
+      self.push(changed(lhs, rhs));
+      return;
+    }
+
+    if let (Some(a), Some(b)) = (a.clone_cast::<Str>(), b.clone_cast::<Str>()) {
+      if a.as_str() != b.as_str() {
+        self.push(changed(lhs, rhs));
+      }
+    } else if let (Some(a), Some(b)) = (a.clone_cast::<List>(), b.clone_cast::<List>()) {
+      self.list(&a, &b, depth);
+    } else if let (Some(a), Some(b)) = (a.clone_cast::<Table>(), b.clone_cast::<Table>()) {
+      self.table(&a, &b, depth);
+    } else if let (Some(a), Some(b)) = (
+      a.clone_cast::<ClassInstance>(),
+      b.clone_cast::<ClassInstance>(),
+    ) {
+      self.table(&a.fields.to_table(), &b.fields.to_table(), depth);
+    } else {
+      self.push(changed(lhs, rhs));
+    }
+  }
+
+  fn list(&mut self, lhs: &List, rhs: &List, depth: usize) {
+    for i in 0..lhs.len().max(rhs.len()) {
+      self.nested(format_args!("[{i}]"), |this| {
+        match (lhs.get(i), rhs.get(i)) {
+          (Some(a), Some(b)) => this.value(&a, &b, depth + 1),
+          (Some(a), None) => this.push(Change::MissingInRhs { lhs: show(&a) }),
+          (None, Some(b)) => this.push(Change::MissingInLhs { rhs: show(&b) }),
+          (None, None) => {}
+        }
+      });
+    }
+  }
+
+  fn table(&mut self, lhs: &Table, rhs: &Table, depth: usize) {
+    for (key, a) in lhs.entries() {
+      self.nested(format_args!("{}", Key(&key)), |this| match rhs.get(&key) {
+        Some(b) => this.value(&a, &b, depth + 1),
+        None => this.push(Change::MissingInRhs { lhs: show(&a) }),
+      });
+    }
+    for (key, b) in rhs.entries() {
+      if lhs.get(&key).is_none() {
+        self.nested(format_args!("{}", Key(&key)), |this| {
+          this.push(Change::MissingInLhs { rhs: show(&b) })
+        });
+      }
+    }
+  }
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+  if let Some(v) = value.clone().to_int() {
+    Some(v as f64)
+  } else {
+    value.clone().to_float()
+  }
+}
+
+/// The type of `value`, which for instances includes their class.
+fn type_name(value: &Value) -> String {
+  match value.clone().to_object::<ClassInstance>() {
+    Some(instance) => format!("instance of `{}`", instance.name),
+    None => format!("`{}`", value.type_name()),
+  }
+}
+
+fn changed(lhs: &Value, rhs: &Value) -> Change {
+  Change::Value {
+    lhs: show(lhs),
+    rhs: show(rhs),
+  }
+}
+
+/// Strings are quoted, so that they can be told apart from other values.
+fn show(value: &Value) -> String {
+  match value.clone().to_object::<Str>() {
+    Some(v) => format!("{:?}", v.as_str()),
+    None => value.to_string(),
+  }
+}
+
+/// A table key as a path segment, `.name` if it is an identifier, and
+/// `["some key"]` otherwise.
+struct Key<'a>(&'a Str);
+
+impl std::fmt::Display for Key<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let key = self.0.as_str();
+    let mut chars = key.chars();
+    let is_ident = chars
+      .next()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+      && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_ident {
+      write!(f, ".{key}")
+    } else {
+      write!(f, "[{key:?}]")
+    }
+  }
+}
@@ -1,8 +1,12 @@
 use std::error::Error as StdError;
 use std::fmt::Display;
 
+use super::object::class::ClassInstance;
+use super::object::{List, Ptr, Str, Table};
 use super::syntax::SyntaxError;
-use crate::span::SpannedError;
+use super::value::{FloatFormat, Value};
+use super::vm::global::Global;
+use crate::span::{Source, Span, SpannedError};
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
 
@@ -11,6 +15,7 @@ pub enum Error {
   Vm(SpannedError),
   Syntax(SyntaxError),
   User(Box<dyn StdError + Send + Sync + 'static>),
+  Value(ErrorValue),
 }
 
 impl Error {
@@ -18,6 +23,16 @@ impl Error {
     Self::User(Box::new(e))
   }
 
+  /// The value seen by a script which catches this error.
+  pub fn to_error_value(&self) -> ErrorValue {
+    match self {
+      Error::Vm(e) => ErrorValue::new("runtime_error", e.message.clone()),
+      Error::Syntax(e) => ErrorValue::new("syntax_error", e.to_string()),
+      Error::User(e) => ErrorValue::new("runtime_error", e.to_string()),
+      Error::Value(e) => e.clone(),
+    }
+  }
+
   pub fn report(&self, src: &str, use_color: bool) -> String {
     match self {
       Error::Vm(e) => format!("runtime error: {}", e.report(src, use_color)),
@@ -33,6 +48,7 @@ impl Error {
         // TODO: spans in user errors
         format!("runtime error: {e}")
       }
+      Error::Value(e) => format!("runtime error: {}", e.to_spanned().report(src, use_color)),
     }
   }
 }
@@ -61,8 +77,254 @@ impl Display for Error {
       Error::User(e) => {
         write!(f, "{e}")
       }
+      Error::Value(e) => {
+        write!(f, "{e}")
+      }
     }
   }
 }
 
 impl StdError for Error {}
+
+impl From<ErrorValue> for Error {
+  fn from(value: ErrorValue) -> Self {
+    Error::Value(value)
+  }
+}
+
+/// An error which scripts may inspect after catching it.
+///
+/// In a `catch` block, it is seen as a table with the keys `code`,
+/// `message`, and `data`. Use [`error_value`][crate::error_value] to
+/// construct one.
+///
+/// Like other runtime errors, it points to the code which raised it, if that
+/// code came from a named source such as a file.
+#[derive(Clone, Debug)]
+pub struct ErrorValue {
+  pub code: String,
+  pub message: String,
+  pub data: Vec<(String, ErrorData)>,
+  pub span: Span,
+  pub source: Option<Box<Source>>,
+}
+
+impl ErrorValue {
+  pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      code: code.into(),
+      message: message.into(),
+      data: Vec::new(),
+      span: Span::default(),
+      source: None,
+    }
+  }
+
+  /// The error as a [`SpannedError`], which is how its location is
+  /// reported.
+  fn to_spanned(&self) -> SpannedError {
+    SpannedError {
+      span: self.span,
+      message: format!("{}: {}", self.code, self.message),
+      source: self.source.clone(),
+    }
+  }
+
+  /// Attach `value` to the error's data table under `key`.
+  pub fn with(mut self, key: impl Into<String>, value: impl Into<ErrorData>) -> Self {
+    self.data.push((key.into(), value.into()));
+    self
+  }
+
+  /// Convert a thrown value to an error which may leave the VM.
+  ///
+  /// Class instances use the name of their class as the error code, and
+  /// both instances and tables may provide a `message` and `data`.
+  pub(crate) fn from_value(value: Value, format: FloatFormat) -> Self {
+    let (code, message, data) = if let Some(instance) = value.clone().to_object::<ClassInstance>() {
+      let fields = &instance.fields;
+      let code = instance.name.to_string();
+      (code, fields.get("message"), fields.get("data"))
+    } else if let Some(table) = value.clone().to_object::<Table>() {
+      let code = match table.get("code") {
+        Some(code) => code.display(format).to_string(),
+        None => "runtime_error".to_string(),
+      };
+      (code, table.get("message"), table.get("data"))
+    } else {
+      return ErrorValue::new("runtime_error", value.display(format).to_string());
+    };
+
+    let message = match message {
+      Some(message) if !message.is_none() => message.display(format).to_string(),
+      _ => String::new(),
+    };
+    let mut error = ErrorValue::new(code, message);
+    if let Some(data) = data.and_then(|data| data.to_object::<Table>()) {
+      for (key, value) in data.entries() {
+        error = error.with(key.as_str(), ErrorData::from_value(value, format));
+      }
+    }
+    error
+  }
+
+  pub(crate) fn into_value(self, global: &Global) -> Value {
+    Value::object(self.into_table(global))
+  }
+
+  pub(crate) fn into_table(self, global: &Global) -> Ptr<Table> {
+    let data = Table::with_capacity(self.data.len());
+    for (key, value) in self.data {
+      data.insert(global.intern(key), value.into_value(global));
+    }
+
+    let table = Table::with_capacity(3);
+    table.insert(
+      global.intern("code"),
+      Value::object(global.alloc(Str::owned(self.code))),
+    );
+    table.insert(
+      global.intern("message"),
+      Value::object(global.alloc(Str::owned(self.message))),
+    );
+    table.insert(global.intern("data"), Value::object(global.alloc(data)));
+    global.alloc(table)
+  }
+}
+
+/// The location of the error is not compared, so errors raised in different
+/// places are equal if scripts see the same value.
+impl PartialEq for ErrorValue {
+  fn eq(&self, other: &Self) -> bool {
+    self.code == other.code && self.message == other.message && self.data == other.data
+  }
+}
+
+impl Display for ErrorValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.to_spanned())
+  }
+}
+
+impl StdError for ErrorValue {}
+
+/// Data attached to an [`ErrorValue`].
+///
+/// Unlike script values, this may be sent across threads, which is what
+/// allows it to be stored in an [`Error`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorData {
+  None,
+  Bool(bool),
+  Int(i32),
+  Float(f64),
+  Str(String),
+  List(Vec<ErrorData>),
+  Table(Vec<(String, ErrorData)>),
+}
+
+impl ErrorData {
+  /// Copy a script value out of the VM. Anything which is not plain data is
+  /// stored as its string representation.
+  pub(crate) fn from_value(value: Value, format: FloatFormat) -> Self {
+    if value.is_none() {
+      return ErrorData::None;
+    }
+    if let Some(v) = value.clone().to_bool() {
+      return ErrorData::Bool(v);
+    }
+    if let Some(v) = value.clone().to_int() {
+      return ErrorData::Int(v);
+    }
+    if let Some(v) = value.clone().to_float() {
+      return ErrorData::Float(v);
+    }
+    if let Some(v) = value.clone().to_object::<Str>() {
+      return ErrorData::Str(v.as_str().to_string());
+    }
+    if let Some(v) = value.clone().to_object::<List>() {
+      return ErrorData::List(
+        v.iter()
+          .map(|item| ErrorData::from_value(item, format))
+          .collect(),
+      );
+    }
+    if let Some(v) = value.clone().to_object::<Table>() {
+      return ErrorData::Table(
+        v.entries()
+          .map(|(key, value)| (key.to_string(), ErrorData::from_value(value, format)))
+          .collect(),
+      );
+    }
+    ErrorData::Str(value.display(format).to_string())
+  }
+
+  pub(crate) fn into_value(self, global: &Global) -> Value {
+    match self {
+      ErrorData::None => Value::none(),
+      ErrorData::Bool(v) => Value::bool(v),
+      ErrorData::Int(v) => Value::int(v),
+      ErrorData::Float(v) => Value::float(v),
+      ErrorData::Str(v) => Value::object(global.alloc(Str::owned(v))),
+      ErrorData::List(v) => {
+        let list = List::with_capacity(v.len());
+        for item in v {
+          list.push(item.into_value(global));
+        }
+        Value::object(global.alloc(list))
+      }
+      ErrorData::Table(v) => {
+        let table = Table::with_capacity(v.len());
+        for (key, value) in v {
+          table.insert(global.intern(key), value.into_value(global));
+        }
+        Value::object(global.alloc(table))
+      }
+    }
+  }
+}
+
+impl From<bool> for ErrorData {
+  fn from(value: bool) -> Self {
+    ErrorData::Bool(value)
+  }
+}
+
+impl From<i32> for ErrorData {
+  fn from(value: i32) -> Self {
+    ErrorData::Int(value)
+  }
+}
+
+impl From<f64> for ErrorData {
+  fn from(value: f64) -> Self {
+    ErrorData::Float(value)
+  }
+}
+
+impl From<&str> for ErrorData {
+  fn from(value: &str) -> Self {
+    ErrorData::Str(value.to_string())
+  }
+}
+
+impl From<String> for ErrorData {
+  fn from(value: String) -> Self {
+    ErrorData::Str(value)
+  }
+}
+
+impl<T: Into<ErrorData>> From<Vec<T>> for ErrorData {
+  fn from(value: Vec<T>) -> Self {
+    ErrorData::List(value.into_iter().map(Into::into).collect())
+  }
+}
+
+impl<T: Into<ErrorData>> From<Option<T>> for ErrorData {
+  fn from(value: Option<T>) -> Self {
+    match value {
+      Some(value) => value.into(),
+      None => ErrorData::None,
+    }
+  }
+}
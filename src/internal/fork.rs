@@ -0,0 +1,395 @@
+//! Copying the objects of one VM into another, see [`crate::Hebi::fork`].
+
+use indexmap::IndexMap;
+
+use super::error::Result;
+use super::object::builtin::{BuiltinAsyncFunction, BuiltinFunction, BuiltinMethod, BuiltinType};
+use super::object::cancel::Cancellation;
+use super::object::channel::Channel;
+use super::object::class::{ClassInstance, ClassProxy};
+use super::object::function::{Cell, Generator, Param, ParamDefault, Signature};
+use super::object::module::{Module, ModuleDescriptor, ModuleKind};
+use super::object::native::{NativeAsyncFunction, NativeClass, NativeField, NativeFunction};
+use super::object::range::Range;
+use super::object::shape::{Fields, Shape};
+use super::object::string::StrSet;
+use super::object::time::{Duration, Timestamp};
+use super::object::{
+  Any, BoundFunction, ClassDescriptor, ClassType, Function, FunctionDescriptor, List, Ptr, Str,
+  Table, Type,
+};
+use super::stdlib::functools::Memo;
+use super::value::constant::Constant;
+use super::value::Value;
+use super::vm::global::Global;
+
+/// Copies objects into the VM of `global`.
+///
+/// Every object is copied once, so objects which are shared or contain
+/// themselves are copied with the same structure. Lists, tables, cells and
+/// instances are allocated empty and filled by [`Copier::finish`], because
+/// they are the only objects which can be part of a cycle.
+///
+/// Objects cannot be shared between VMs, because their reference counts
+/// are not atomic and the VMs may run on different threads. So this takes
+/// time and memory proportional to everything reachable from the copied
+/// values, including every function and string. Only the parts which are
+/// never modified and are not objects are shared instead of copied: the
+/// instructions and spans of functions, the contents of static strings, and
+/// cached string hashes.
+pub struct Copier {
+  global: Global,
+  /// The copies of objects, by the address of the original.
+  copies: IndexMap<usize, Ptr<Any>>,
+  /// Copies which were allocated but not filled yet, with their originals.
+  pending: Vec<(Ptr<Any>, Ptr<Any>)>,
+}
+
+impl Copier {
+  pub fn new(global: Global) -> Self {
+    Self {
+      global,
+      copies: IndexMap::new(),
+      pending: Vec::new(),
+    }
+  }
+
+  pub fn value(&mut self, value: &Value) -> Result<Value> {
+    match value.clone().to_any() {
+      Some(object) => self.any(&object).map(Value::object),
+      None => Ok(value.clone()),
+    }
+  }
+
+  pub fn object<T: Type>(&mut self, object: &Ptr<T>) -> Result<Ptr<T>> {
+    let copy = self.any(&object.clone().into_any())?;
+    Ok(unsafe { copy.cast_unchecked::<T>() })
+  }
+
+  /// Fill the copies of lists, tables, cells and instances.
+  pub fn finish(&mut self) -> Result<()> {
+    while let Some((original, copy)) = self.pending.pop() {
+      if let Some(original) = original.clone_cast::<List>() {
+        let copy = unsafe { copy.cast_unchecked::<List>() };
+        for value in original.iter() {
+          copy.push(self.value(&value)?);
+        }
+      } else if let Some(original) = original.clone_cast::<Table>() {
+        let copy = unsafe { copy.cast_unchecked::<Table>() };
+        self.fill_table(&original, &copy)?;
+      } else if let Some(original) = original.clone_cast::<Cell>() {
+        let copy = unsafe { copy.cast_unchecked::<Cell>() };
+        copy.set(self.value(&original.get())?);
+      } else if let Some(original) = original.clone_cast::<ClassInstance>() {
+        let copy = unsafe { copy.cast_unchecked::<ClassInstance>() };
+        for (slot, (key, value)) in original.fields.entries().enumerate() {
+          let (key, value) = (self.object(&key)?, self.value(&value)?);
+          copy.fields.set_index_with_key(slot, &key, value);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Copy the entries of `original` into `copy`.
+  pub fn fill_table(&mut self, original: &Table, copy: &Table) -> Result<()> {
+    for (key, value) in original.entries() {
+      copy.insert(self.object(&key)?, self.value(&value)?);
+    }
+    Ok(())
+  }
+
+  fn any(&mut self, object: &Ptr<Any>) -> Result<Ptr<Any>> {
+    if let Some(copy) = self.copies.get(&object.addr()) {
+      return Ok(copy.clone());
+    }
+
+    // objects which may be part of a cycle are filled later
+    let global = self.global.clone();
+    let pending = if let Some(list) = object.clone_cast::<List>() {
+      Some(alloc(&global, List::with_capacity(list.len())))
+    } else if let Some(table) = object.clone_cast::<Table>() {
+      Some(alloc(&global, Table::with_capacity(table.len())))
+    } else if object.is::<Cell>() {
+      Some(alloc(&global, Cell::new(Value::none())))
+    } else if let Some(instance) = object.clone_cast::<ClassInstance>() {
+      let shape = self.shape(&instance.fields.shape())?;
+      let values = vec![Value::none(); shape.len()];
+      Some(alloc(
+        &global,
+        ClassInstance {
+          name: self.object(&instance.name)?,
+          fields: Fields::new(shape, values),
+          parent: self.option(&instance.parent)?,
+          class: self.object(&instance.class)?,
+        },
+      ))
+    } else {
+      None
+    };
+    if let Some(copy) = pending {
+      self.copies.insert(object.addr(), copy.clone());
+      self.pending.push((object.clone(), copy.clone()));
+      return Ok(copy);
+    }
+
+    let copy = self.immutable(object)?;
+    self.copies.insert(object.addr(), copy.clone());
+    Ok(copy)
+  }
+
+  /// Copy an object which can only refer to objects which existed before
+  /// it, so its copy may be created after copying them.
+  fn immutable(&mut self, object: &Ptr<Any>) -> Result<Ptr<Any>> {
+    let global = self.global.clone();
+    let copy = if let Some(v) = object.clone_cast::<Str>() {
+      alloc(&global, v.as_ref().clone())
+    } else if let Some(v) = object.clone_cast::<Function>() {
+      alloc(
+        &global,
+        Function::new(
+          self.object(&v.descriptor)?,
+          self.object(&v.upvalues)?,
+          v.module_id,
+        ),
+      )
+    } else if let Some(v) = object.clone_cast::<Generator>() {
+      alloc(
+        &global,
+        Generator {
+          descriptor: self.object(&v.descriptor)?,
+          upvalues: self.object(&v.upvalues)?,
+          module: v.module,
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<BoundFunction>() {
+      alloc(
+        &global,
+        BoundFunction::new(self.any(v.this())?, self.object(v.function())?),
+      )
+    } else if let Some(v) = object.clone_cast::<FunctionDescriptor>() {
+      alloc(&global, self.function_descriptor(&v)?)
+    } else if let Some(v) = object.clone_cast::<ClassType>() {
+      let mut methods = Vec::with_capacity(v.methods.len());
+      for (name, method) in v.methods.iter() {
+        methods.push((self.object(name)?, self.object(method)?));
+      }
+      alloc(
+        &global,
+        ClassType {
+          name: self.object(&v.name)?,
+          init: self.option(&v.init)?,
+          fields: self.object(&v.fields)?,
+          methods: methods.into_iter().collect(),
+          parent: self.option(&v.parent)?,
+          shape: self.shape(&v.shape)?,
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<ClassDescriptor>() {
+      let mut methods = Vec::with_capacity(v.methods.len());
+      for (name, method) in v.methods.iter() {
+        methods.push((self.object(name)?, self.object(method)?));
+      }
+      alloc(
+        &global,
+        ClassDescriptor {
+          name: self.object(&v.name)?,
+          init: self.option(&v.init)?,
+          methods: methods.into_iter().collect(),
+          fields: self.object(&v.fields)?,
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<ClassProxy>() {
+      alloc(
+        &global,
+        ClassProxy {
+          this: self.object(&v.this)?,
+          class: self.object(&v.class)?,
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<Shape>() {
+      self.shape(&v)?.into_any()
+    } else if let Some(v) = object.clone_cast::<Module>() {
+      let kind = match &v.kind {
+        ModuleKind::Script { root } => ModuleKind::Script {
+          root: self.object(root)?,
+        },
+        ModuleKind::Native => ModuleKind::Native,
+      };
+      alloc(
+        &global,
+        Module {
+          module_id: v.module_id,
+          name: self.object(&v.name)?,
+          module_vars: self.object(&v.module_vars)?,
+          kind,
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<ModuleDescriptor>() {
+      alloc(
+        &global,
+        ModuleDescriptor {
+          name: self.object(&v.name)?,
+          root: self.object(&v.root)?,
+          module_vars: self.str_set(&v.module_vars)?,
+          constructs: v.constructs.clone(),
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<NativeFunction>() {
+      alloc(
+        &global,
+        NativeFunction {
+          name: self.object(&v.name)?,
+          owner: self.option(&v.owner)?,
+          cb: v.cb.clone(),
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<NativeAsyncFunction>() {
+      alloc(
+        &global,
+        NativeAsyncFunction {
+          name: self.object(&v.name)?,
+          owner: self.option(&v.owner)?,
+          cb: v.cb.clone(),
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<NativeClass>() {
+      let mut fields = Vec::with_capacity(v.fields.len());
+      for (name, field) in v.fields.iter() {
+        let field = NativeField {
+          get: self.object(&field.get)?,
+          set: self.option(&field.set)?,
+        };
+        fields.push((self.object(name)?, field));
+      }
+      let mut methods = Vec::with_capacity(v.methods.len());
+      for (name, method) in v.methods.iter() {
+        methods.push((self.object(name)?, self.any(method)?));
+      }
+      let mut static_methods = Vec::with_capacity(v.static_methods.len());
+      for (name, method) in v.static_methods.iter() {
+        static_methods.push((self.object(name)?, self.any(method)?));
+      }
+      alloc(
+        &global,
+        NativeClass {
+          name: self.object(&v.name)?,
+          type_id: v.type_id,
+          init: self.option(&v.init)?,
+          fields: fields.into_iter().collect(),
+          methods: methods.into_iter().collect(),
+          static_methods: static_methods.into_iter().collect(),
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<BuiltinFunction>() {
+      alloc(&global, v.as_ref().clone())
+    } else if let Some(v) = object.clone_cast::<BuiltinAsyncFunction>() {
+      alloc(&global, v.as_ref().clone())
+    } else if let Some(v) = object.clone_cast::<BuiltinType>() {
+      alloc(&global, v.as_ref().clone())
+    } else if let Some(v) = object.clone_cast::<BuiltinMethod>() {
+      let this = self.value(v.this())?;
+      // `this` is a copy of a value of the same type
+      alloc(&global, unsafe { BuiltinMethod::new(this, v.callback()) })
+    } else if let Some(v) = object.clone_cast::<Memo>() {
+      let function = self.any(v.function())?;
+      alloc(&global, v.with_function(function))
+    } else if let Some(v) = object.clone_cast::<Range>() {
+      alloc(&global, *v.as_ref())
+    } else if let Some(v) = object.clone_cast::<Duration>() {
+      alloc(&global, *v.as_ref())
+    } else if let Some(v) = object.clone_cast::<Timestamp>() {
+      alloc(&global, *v.as_ref())
+    } else if let Some(v) = object.clone_cast::<Channel>() {
+      alloc(
+        &global,
+        Channel {
+          inner: v.inner.clone(),
+        },
+      )
+    } else if let Some(v) = object.clone_cast::<Cancellation>() {
+      alloc(
+        &global,
+        Cancellation {
+          token: v.token.clone(),
+        },
+      )
+    } else {
+      #[cfg(feature = "decimal")]
+      if let Some(v) = object.clone_cast::<super::object::decimal::Decimal>() {
+        return Ok(alloc(&global, *v.as_ref()));
+      }
+      fail!("cannot copy `{}` into another VM", object.type_name())
+    };
+    Ok(copy)
+  }
+
+  fn function_descriptor(&mut self, v: &FunctionDescriptor) -> Result<FunctionDescriptor> {
+    let mut params = Vec::with_capacity(v.signature.params.len());
+    for param in v.signature.params.iter() {
+      params.push(Param {
+        name: self.object(&param.name)?,
+        default: match &param.default {
+          ParamDefault::None => ParamDefault::None,
+          ParamDefault::Constant(value) => ParamDefault::Constant(self.value(value)?),
+          ParamDefault::Expr => ParamDefault::Expr,
+        },
+      });
+    }
+    let mut constants = Vec::with_capacity(v.constants().len());
+    for constant in v.constants() {
+      constants.push(match constant {
+        Constant::String(v) => Constant::String(self.object(v)?),
+        Constant::Function(v) => Constant::Function(self.object(v)?),
+        Constant::Class(v) => Constant::Class(self.object(v)?),
+        #[cfg(feature = "decimal")]
+        Constant::Decimal(v) => Constant::Decimal(self.object(v)?),
+        Constant::Reserved | Constant::Offset(_) | Constant::Float(_) => constant.clone(),
+      });
+    }
+    Ok(FunctionDescriptor::new(
+      self.object(&v.name)?,
+      v.is_generator,
+      v.params,
+      Signature { params },
+      v.upvalues.borrow().clone(),
+      v.frame_size,
+      v.code(),
+      constants,
+      v.spans.clone(),
+      v.span,
+    ))
+  }
+
+  /// Shapes are copied without their transitions, which are added again
+  /// as fields are added to instances in the other VM.
+  fn shape(&mut self, shape: &Ptr<Shape>) -> Result<Ptr<Shape>> {
+    if let Some(copy) = self.copies.get(&shape.addr()) {
+      return Ok(unsafe { copy.clone().cast_unchecked::<Shape>() });
+    }
+    let keys = shape.keys().cloned().collect::<StrSet>();
+    let keys = self.str_set(&keys)?;
+    let copy = self.global.alloc(Shape::new(keys));
+    self.copies.insert(shape.addr(), copy.clone().into_any());
+    Ok(copy)
+  }
+
+  fn str_set(&mut self, set: &StrSet) -> Result<StrSet> {
+    let mut copy = StrSet::with_capacity_and_hasher(set.len(), Default::default());
+    for key in set.iter() {
+      copy.insert(self.object(key)?);
+    }
+    Ok(copy)
+  }
+
+  fn option<T: Type>(&mut self, object: &Option<Ptr<T>>) -> Result<Option<Ptr<T>>> {
+    object
+      .as_ref()
+      .map(|object| self.object(object))
+      .transpose()
+  }
+}
+
+fn alloc<T: Type>(global: &Global, object: T) -> Ptr<Any> {
+  global.alloc(object).into_any()
+}
@@ -0,0 +1,400 @@
+//! A minimal JSON encoder and decoder for script values, available without
+//! the `serde` feature.
+
+use std::fmt::Write;
+
+use super::error::Result;
+use super::object::{List, Str, Table};
+use super::value::Value;
+use super::vm::global::Global;
+
+/// Lists and tables may contain themselves, so nesting is limited
+/// instead of tracking visited objects.
+const MAX_DEPTH: usize = 128;
+
+pub fn to_json_string(value: &Value) -> Result<String> {
+  let mut out = String::new();
+  write_value(&mut out, value, 0)?;
+  Ok(out)
+}
+
+fn write_value(out: &mut String, value: &Value, depth: usize) -> Result<()> {
+  if depth > MAX_DEPTH {
+    fail!("cannot convert value to json: nesting limit reached");
+  }
+
+  if let Some(v) = value.clone().to_float() {
+    if !v.is_finite() {
+      fail!("cannot convert `{v}` to json");
+    }
+    write!(out, "{v:?}").unwrap();
+  } else if let Some(v) = value.clone().to_int() {
+    write!(out, "{v}").unwrap();
+  } else if let Some(v) = value.clone().to_bool() {
+    write!(out, "{v}").unwrap();
+  } else if value.is_none() {
+    out.push_str("null");
+  } else if let Some(v) = value.clone().to_object::<Str>() {
+    write_str(out, v.as_str());
+  } else if let Some(v) = value.clone().to_object::<List>() {
+    out.push('[');
+    for (i, item) in v.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      write_value(out, &item, depth + 1)?;
+    }
+    out.push(']');
+  } else if let Some(v) = value.clone().to_object::<Table>() {
+    out.push('{');
+    for (i, (key, item)) in v.entries().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      write_str(out, key.as_str());
+      out.push(':');
+      write_value(out, &item, depth + 1)?;
+    }
+    out.push('}');
+  } else {
+    fail!("cannot convert `{value}` to json");
+  }
+
+  Ok(())
+}
+
+fn write_str(out: &mut String, s: &str) {
+  out.push('"');
+  escape_str(out, s);
+  out.push('"');
+}
+
+/// Write `s` escaped so that it can be placed between the quotes of a JSON
+/// string.
+pub fn escape_str(out: &mut String, s: &str) {
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+}
+
+/// Parse `src` as JSON.
+///
+/// Numbers without a fractional part or exponent which fit in an `int`
+/// become ints, and all other numbers become floats.
+pub fn from_json_str(global: &Global, src: &str) -> Result<Value> {
+  let mut parser = Parser {
+    global,
+    src: src.as_bytes(),
+    pos: 0,
+    depth: 0,
+  };
+  parser.skip_whitespace();
+  let value = parser.value()?;
+  parser.skip_whitespace();
+  if parser.pos < parser.src.len() {
+    return Err(parser.error("trailing characters"));
+  }
+  Ok(value)
+}
+
+struct Parser<'a> {
+  global: &'a Global,
+  src: &'a [u8],
+  pos: usize,
+  depth: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, message: &str) -> crate::Error {
+    error!("invalid json at offset {}: {message}", self.pos).into()
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.src.get(self.pos).copied()
+  }
+
+  fn eat(&mut self, byte: u8) -> bool {
+    if self.peek() == Some(byte) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<()> {
+    if !self.eat(byte) {
+      return Err(self.error(&format!("expected `{}`", byte as char)));
+    }
+    Ok(())
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+      self.pos += 1;
+    }
+  }
+
+  fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value> {
+    if self.src[self.pos..].starts_with(keyword.as_bytes()) {
+      self.pos += keyword.len();
+      Ok(value)
+    } else {
+      Err(self.error("unexpected character"))
+    }
+  }
+
+  fn value(&mut self) -> Result<Value> {
+    match self.peek() {
+      Some(b'n') => self.keyword("null", Value::none()),
+      Some(b't') => self.keyword("true", Value::bool(true)),
+      Some(b'f') => self.keyword("false", Value::bool(false)),
+      Some(b'"') => {
+        let s = self.string()?;
+        Ok(Value::object(self.global.alloc(Str::owned(s))))
+      }
+      Some(b'[') => self.nested(Self::list),
+      Some(b'{') => self.nested(Self::table),
+      Some(b'-' | b'0'..=b'9') => self.number(),
+      Some(_) => Err(self.error("unexpected character")),
+      None => Err(self.error("unexpected end of input")),
+    }
+  }
+
+  fn nested(&mut self, f: fn(&mut Self) -> Result<Value>) -> Result<Value> {
+    if self.depth >= MAX_DEPTH {
+      return Err(self.error("nesting limit reached"));
+    }
+    self.depth += 1;
+    let value = f(self);
+    self.depth -= 1;
+    value
+  }
+
+  fn list(&mut self) -> Result<Value> {
+    self.expect(b'[')?;
+    let list = List::new();
+    self.skip_whitespace();
+    if !self.eat(b']') {
+      loop {
+        self.skip_whitespace();
+        list.push(self.value()?);
+        self.skip_whitespace();
+        if self.eat(b']') {
+          break;
+        }
+        self.expect(b',')?;
+      }
+    }
+    Ok(Value::object(self.global.alloc(list)))
+  }
+
+  fn table(&mut self) -> Result<Value> {
+    self.expect(b'{')?;
+    let table = Table::new();
+    self.skip_whitespace();
+    if !self.eat(b'}') {
+      loop {
+        self.skip_whitespace();
+        if self.peek() != Some(b'"') {
+          return Err(self.error("expected a string key"));
+        }
+        let key = self.string()?;
+        self.skip_whitespace();
+        self.expect(b':')?;
+        self.skip_whitespace();
+        let value = self.value()?;
+        table.insert(self.global.intern(key), value);
+        self.skip_whitespace();
+        if self.eat(b'}') {
+          break;
+        }
+        self.expect(b',')?;
+      }
+    }
+    Ok(Value::object(self.global.alloc(table)))
+  }
+
+  fn number(&mut self) -> Result<Value> {
+    let start = self.pos;
+    let mut is_float = false;
+    self.eat(b'-');
+    match self.peek() {
+      Some(b'0') => self.pos += 1,
+      Some(b'1'..=b'9') => self.digits(),
+      _ => return Err(self.error("expected a digit")),
+    }
+    if self.eat(b'.') {
+      is_float = true;
+      if !matches!(self.peek(), Some(b'0'..=b'9')) {
+        return Err(self.error("expected a digit"));
+      }
+      self.digits();
+    }
+    if let Some(b'e' | b'E') = self.peek() {
+      is_float = true;
+      self.pos += 1;
+      if let Some(b'+' | b'-') = self.peek() {
+        self.pos += 1;
+      }
+      if !matches!(self.peek(), Some(b'0'..=b'9')) {
+        return Err(self.error("expected a digit"));
+      }
+      self.digits();
+    }
+
+    // the slice only contains ascii characters
+    let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+    if !is_float {
+      if let Ok(v) = text.parse::<i32>() {
+        return Ok(Value::int(v));
+      }
+    }
+    match text.parse::<f64>() {
+      Ok(v) if v.is_finite() => Ok(Value::float(v)),
+      _ => Err(self.error("number out of range")),
+    }
+  }
+
+  fn digits(&mut self) {
+    while let Some(b'0'..=b'9') = self.peek() {
+      self.pos += 1;
+    }
+  }
+
+  fn string(&mut self) -> Result<String> {
+    self.expect(b'"')?;
+    let mut out = Vec::new();
+    loop {
+      match self.peek() {
+        Some(b'"') => {
+          self.pos += 1;
+          break;
+        }
+        Some(b'\\') => {
+          self.pos += 1;
+          let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+              self.pos += 1;
+              let c = self.unicode_escape()?;
+              let mut buf = [0u8; 4];
+              out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+              continue;
+            }
+            _ => return Err(self.error("invalid escape sequence")),
+          };
+          self.pos += 1;
+          out.push(c as u8);
+        }
+        Some(0x00..=0x1f) => return Err(self.error("control character in string")),
+        Some(b) => {
+          self.pos += 1;
+          out.push(b);
+        }
+        None => return Err(self.error("unterminated string")),
+      }
+    }
+    // the input is a `&str`, and escapes always produce valid utf-8
+    Ok(String::from_utf8(out).unwrap())
+  }
+
+  fn hex4(&mut self) -> Result<u32> {
+    let Some(digits) = self.src.get(self.pos..self.pos + 4) else {
+      return Err(self.error("invalid unicode escape"));
+    };
+    let digits = std::str::from_utf8(digits).map_err(|_| self.error("invalid unicode escape"))?;
+    let v = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))?;
+    self.pos += 4;
+    Ok(v)
+  }
+
+  fn unicode_escape(&mut self) -> Result<char> {
+    let hi = self.hex4()?;
+    let code = if (0xd800..0xdc00).contains(&hi) {
+      // surrogate pair
+      if !self.src[self.pos..].starts_with(b"\\u") {
+        return Err(self.error("unpaired surrogate"));
+      }
+      self.pos += 2;
+      let lo = self.hex4()?;
+      if !(0xdc00..0xe000).contains(&lo) {
+        return Err(self.error("unpaired surrogate"));
+      }
+      0x10000 + ((hi - 0xd800) << 10) + (lo - 0xdc00)
+    } else {
+      hi
+    };
+    char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn roundtrip(src: &str) -> String {
+    let global = Global::default();
+    let value = from_json_str(&global, src).unwrap();
+    to_json_string(&value).unwrap()
+  }
+
+  #[test]
+  fn json_roundtrip() {
+    assert_eq!(roundtrip("null"), "null");
+    assert_eq!(roundtrip(" true "), "true");
+    assert_eq!(roundtrip("-12"), "-12");
+    assert_eq!(roundtrip("1.5e3"), "1500.0");
+    assert_eq!(roundtrip("10000000000"), "10000000000.0");
+    assert_eq!(
+      roundtrip(r#"{"a": [1, 2.5, "x\ny", {}], "b": {"c": null}}"#),
+      r#"{"a":[1,2.5,"x\ny",{}],"b":{"c":null}}"#
+    );
+    assert_eq!(roundtrip(r#""\u00e9\ud83d\ude00\u0001""#), "\"é😀\\u0001\"");
+  }
+
+  #[test]
+  fn json_errors() {
+    let global = Global::default();
+    for src in [
+      "",
+      "nul",
+      "[1,]",
+      "{\"a\" 1}",
+      "{1: 2}",
+      "01x",
+      "1.",
+      "\"abc",
+      "\"\\ud83d\"",
+      "[] []",
+      &"[".repeat(MAX_DEPTH + 1),
+    ] {
+      assert!(from_json_str(&global, src).is_err(), "{src:?}");
+    }
+
+    let list = global.alloc(List::new());
+    list.push(Value::object(list.clone()));
+    let e = to_json_string(&Value::object(list.clone())).unwrap_err();
+    assert!(e.to_string().contains("nesting limit"), "{e}");
+    // break the cycle
+    list.pop();
+
+    let e = to_json_string(&Value::float(f64::NAN)).unwrap_err();
+    assert!(e.to_string().contains("cannot convert"), "{e}");
+  }
+}
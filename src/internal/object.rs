@@ -37,6 +37,7 @@ macro_rules! declare_object_trait {
       pub(crate) debug_fmt: fn(*const T, &mut std::fmt::Formatter<'_>) -> std::fmt::Result,
 
       pub(crate) type_name: fn(Ptr<T>) -> &'static str,
+      pub(crate) callable: bool,
       pub(crate) instance_of: fn(Ptr<T>, Value) -> Result<bool>,
       $(
         pub(crate) $name : fn(
@@ -48,6 +49,9 @@ macro_rules! declare_object_trait {
     }
 
     pub trait $Object: Debug + Display + Sized + 'static {
+      /// Whether the object implements `call`, see [`is_callable`].
+      const CALLABLE: bool = false;
+
       fn type_name(this: Ptr<Self>) -> &'static str;
       fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool>;
       $(
@@ -133,6 +137,7 @@ macro_rules! declare_object_trait {
               debug_fmt: |ptr, f| <$T as ::std::fmt::Debug>::fmt(unsafe { &*ptr }, f),
 
               type_name: <$T as $crate::internal::object::Object>::type_name,
+              callable: <$T as $crate::internal::object::Object>::CALLABLE,
 
               instance_of: <$T as $crate::internal::object::Object>::instance_of,
               $($name: <$T as $crate::internal::object::Object>::$name),*
@@ -266,12 +271,21 @@ declare_object_trait! {
       fail!("`{this}` does not support `!`")
     }
 
+    fn eq(scope, this, other: Self) -> Result<bool> {
+      let _ = scope;
+      Ok(this.ptr_eq(&other))
+    }
+
     fn cmp(scope, this, other: Self) -> Result<Ordering> {
       let _ = scope;
       let _ = other;
       let this = Self::type_name(this);
       fail!("`{this}` does not support comparison")
     }
+
+    fn partial_cmp(scope, this, other: Self) -> Result<Option<Ordering>> {
+      <Self as Object>::cmp(scope, this, other).map(Some)
+    }
   }
 }
 
@@ -287,10 +301,7 @@ macro_rules! default_instance_of {
 }
 
 pub fn is_callable(v: &Ptr<Any>) -> bool {
-  v.is::<Function>()
-    || v.is::<BoundFunction>()
-    || v.is::<NativeFunction>()
-    || v.is::<NativeAsyncFunction>()
+  unsafe { v.vtable() }.callable
 }
 
 pub fn is_class(v: &Ptr<Any>) -> bool {
@@ -302,13 +313,21 @@ pub type ReturnAddr = Option<usize>;
 #[macro_use]
 pub mod builtin;
 
+pub mod cancel;
+pub mod channel;
 pub mod class;
+#[cfg(feature = "decimal")]
+pub mod decimal;
 pub mod function;
+pub mod int;
 pub mod list;
 pub mod module;
 pub mod native;
+pub mod range;
+pub mod shape;
 pub mod string;
 pub mod table;
+pub mod time;
 
 pub(crate) mod ptr;
 
@@ -324,7 +343,7 @@ pub use string::Str;
 pub use table::Table;
 
 use self::class::{ClassInstance, ClassProxy};
-use self::native::{NativeAsyncFunction, NativeClassInstance, NativeFunction};
+use self::native::NativeClassInstance;
 use super::error::Result;
 use super::value::Value;
 use super::vm::thread::CallResult;
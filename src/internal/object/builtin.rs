@@ -1,14 +1,18 @@
 #![allow(dead_code)] // TEMP
 
 use std::fmt::{Debug, Display};
+use std::time::Instant;
 
 use indexmap::IndexMap;
 
-use super::{List, Object, Ptr, ReturnAddr, Str};
-use crate::internal::error::Result;
+use super::{is_callable, List, Object, Ptr, ReturnAddr, Str};
+use crate::internal::diff;
+use crate::internal::error::{Error, Result};
 use crate::internal::object::native::LocalBoxFuture;
+use crate::internal::object::range::range;
+use crate::internal::object::time::Duration;
 use crate::internal::object::{list, string};
-use crate::internal::value::Value;
+use crate::internal::value::{cmp, Value};
 use crate::internal::vm::global::Global;
 use crate::internal::vm::thread::util::is_truthy;
 use crate::internal::vm::thread::{AsyncFrame, CallResult};
@@ -66,6 +70,7 @@ impl Object for BuiltinFunction {
 
 declare_object_type!(BuiltinFunction);
 
+#[derive(Clone)]
 pub struct BuiltinAsyncFunction {
   pub name: &'static str,
   function: AsyncCallback,
@@ -119,7 +124,7 @@ declare_object_type!(BuiltinAsyncFunction);
 //   // TODO: List, Str, Table, etc. globals
 //   // TODO: special sentinel object type `Type` (also global)
 // }
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BuiltinType {
   pub name: &'static str,
   methods: IndexMap<&'static str, BuiltinFunction>,
@@ -215,6 +220,14 @@ impl BuiltinMethod {
   pub fn call(&self, scope: Scope<'_>) -> Result<Value> {
     (self.function)(self.this.clone(), scope)
   }
+
+  pub fn callback(&self) -> MethodCallback {
+    self.function
+  }
+
+  pub fn this(&self) -> &Value {
+    &self.this
+  }
 }
 
 impl Debug for BuiltinMethod {
@@ -283,12 +296,21 @@ fn to_int(scope: Scope<'_>) -> Result<Value> {
     Ok(value)
   } else if value.is_float() {
     let value = unsafe { value.to_float_unchecked() };
-    Ok(Value::int(value as i32))
+    Ok(Value::int(float_to_int(value)?))
   } else {
     fail!("cannot convert `{value}` to an int")
   }
 }
 
+/// Truncate `value`, failing if the result does not fit into an int.
+fn float_to_int(value: f64) -> Result<i32> {
+  let int = value.trunc();
+  if int.is_nan() || int < i32::MIN as f64 || int > i32::MAX as f64 {
+    fail!("`{value}` is out of range for an int");
+  }
+  Ok(int as i32)
+}
+
 fn to_float(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
   if value.is_int() {
@@ -312,7 +334,8 @@ fn to_str(scope: Scope<'_>) -> Result<Value> {
   if let Some(str) = value.clone().to_object::<Str>() {
     Ok(Value::object(str))
   } else {
-    let str = scope.alloc(Str::owned(value));
+    let format = scope.thread.global.float_format();
+    let str = scope.alloc(Str::owned(value.display(format)));
     Ok(Value::object(str))
   }
 }
@@ -322,7 +345,9 @@ fn parse_int(scope: Scope<'_>) -> Result<Value> {
   if value.is_int() {
     return Ok(value);
   } else if value.is_float() {
-    return Ok(Value::int(unsafe { value.to_float_unchecked() } as i32));
+    return Ok(Value::int(float_to_int(unsafe {
+      value.to_float_unchecked()
+    })?));
   } else if value.is_object() {
     if let Some(value) = value.clone().to_object::<Str>() {
       return Ok(Value::int(
@@ -337,23 +362,159 @@ fn parse_int(scope: Scope<'_>) -> Result<Value> {
   fail!("could not parse `{value}` as int");
 }
 
-fn type_of(scope: Scope<'_>) -> Result<Value> {
+/// Round to `ndigits` decimal places, or to a multiple of `10^-ndigits` if
+/// it is negative. Ties are rounded away from zero, and the result has the
+/// same type as the value.
+fn round(scope: Scope<'_>) -> Result<Value> {
+  let args = scope.args().arity(1..=2)?;
+  let value = args.get::<public::Value>(0, "value")?.unbind();
+  let ndigits = args.get_or::<i32>(1, "ndigits", 0)?;
+
+  if value.is_int() {
+    let value = unsafe { value.to_int_unchecked() };
+    if ndigits >= 0 {
+      return Ok(Value::int(value));
+    }
+    // `10^10` is already larger than every int
+    let scale = 10i64.pow(ndigits.unsigned_abs().min(10));
+    let rounded = (value as i64 + value.signum() as i64 * scale / 2) / scale * scale;
+    match i32::try_from(rounded) {
+      Ok(rounded) => Ok(Value::int(rounded)),
+      Err(_) => fail!("`round({value}, {ndigits})` is out of range for an int"),
+    }
+  } else if value.is_float() {
+    let value = unsafe { value.to_float_unchecked() };
+    let rounded = if ndigits >= 0 {
+      let scale = 10f64.powi(ndigits);
+      let scaled = value * scale;
+      // if scaling overflows, there are no digits left to round away
+      if scaled.is_finite() {
+        scaled.round() / scale
+      } else {
+        value
+      }
+    } else {
+      let scale = 10f64.powi(ndigits.saturating_neg());
+      if scale.is_finite() {
+        (value / scale).round() * scale
+      } else {
+        0.0f64.copysign(value)
+      }
+    };
+    if value.is_finite() && !rounded.is_finite() {
+      fail!("`round({value}, {ndigits})` is out of range for a float");
+    }
+    Ok(Value::float(rounded))
+  } else {
+    fail!("`{value}` is not a number")
+  }
+}
+
+/// Remove the fractional part of a number, rounding towards zero.
+fn trunc(scope: Scope<'_>) -> Result<Value> {
   let value = scope.param::<public::Value>(0)?.unbind();
+  if value.is_int() {
+    Ok(value)
+  } else if value.is_float() {
+    Ok(Value::float(unsafe { value.to_float_unchecked() }.trunc()))
+  } else {
+    fail!("`{value}` is not a number")
+  }
+}
 
-  if value.is_float() {
-    Ok(Value::object(scope.intern("float")))
-  } else if value.is_int() {
-    Ok(Value::object(scope.intern("int")))
-  } else if value.is_bool() {
-    Ok(Value::object(scope.intern("bool")))
-  } else if value.is_none() {
-    Ok(Value::object(scope.intern("none")))
+/// `-1`, `0`, or `1`, depending on the sign of the number. Zero (including
+/// `-0.0`) and NaN are returned as they are.
+fn sign(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if value.is_int() {
+    Ok(Value::int(unsafe { value.to_int_unchecked() }.signum()))
+  } else if value.is_float() {
+    let value = unsafe { value.to_float_unchecked() };
+    if value == 0.0 || value.is_nan() {
+      Ok(Value::float(value))
+    } else {
+      Ok(Value::float(value.signum()))
+    }
   } else {
-    let object = unsafe { value.to_any_unchecked() };
-    Ok(Value::object(scope.intern(object.type_name())))
+    fail!("`{value}` is not a number")
   }
 }
 
+fn isnan(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if value.is_int() {
+    Ok(Value::bool(false))
+  } else if value.is_float() {
+    Ok(Value::bool(unsafe { value.to_float_unchecked() }.is_nan()))
+  } else {
+    fail!("`{value}` is not a number")
+  }
+}
+
+fn isinf(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  if value.is_int() {
+    Ok(Value::bool(false))
+  } else if value.is_float() {
+    Ok(Value::bool(
+      unsafe { value.to_float_unchecked() }.is_infinite(),
+    ))
+  } else {
+    fail!("`{value}` is not a number")
+  }
+}
+
+/// Print the optional prompt, and read a line from the configured input.
+///
+/// Returns `none` at the end of the input.
+fn input(scope: Scope<'_>) -> Result<Value> {
+  let args = scope.args().arity(0..=1)?;
+  let prompt = args.get_opt::<public::Str>(0, "prompt")?;
+
+  let io = scope.thread.global.io();
+  let mut input = io.input.borrow_mut();
+  let Some(input) = input.as_mut() else {
+    fail!("`input` is disabled, because no input was configured");
+  };
+  if let Some(prompt) = prompt {
+    let mut output = io.output.borrow_mut();
+    output
+      .write(prompt.as_str().as_bytes())
+      .and_then(|_| output.flush())
+      .map_err(Error::user)?;
+  }
+
+  match input.read_line().map_err(Error::user)? {
+    Some(line) => Ok(Value::object(scope.alloc(Str::owned(line)))),
+    None => Ok(Value::none()),
+  }
+}
+
+fn type_of(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  Ok(Value::object(scope.intern(value.type_name())))
+}
+
+/// Fail if `lhs` and `rhs` are not equal, listing where they differ.
+pub(crate) fn assert_eq(scope: Scope<'_>) -> Result<Value> {
+  let args = scope.args().arity(2..=2)?;
+  let lhs = args.get::<public::Value>(0, "lhs")?.unbind();
+  let rhs = args.get::<public::Value>(1, "rhs")?.unbind();
+
+  if cmp::equals(scope.clone(), lhs.clone(), rhs.clone())? {
+    return Ok(Value::none());
+  }
+  let differences = diff::diff(&lhs, &rhs);
+  if differences.is_empty() {
+    fail!("assertion failed: `{lhs}` != `{rhs}`");
+  }
+  let mut message = String::from("assertion failed: values are not equal");
+  for difference in differences {
+    message.push_str(&format!("\n  {difference}"));
+  }
+  fail!("{message}")
+}
+
 async fn collect(mut scope: Scope<'_>) -> Result<Value> {
   let iterable = scope.param::<public::Value>(0)?.unbind();
 
@@ -392,6 +553,34 @@ async fn collect(mut scope: Scope<'_>) -> Result<Value> {
   Ok(Value::object(list))
 }
 
+/// Call `function` `iterations` times without arguments, and return how
+/// long that took in total.
+///
+/// Only a monotonic clock is read, so this does not give scripts access to
+/// the current time.
+async fn timeit(mut scope: Scope<'_>) -> Result<Value> {
+  let (function, iterations) = {
+    let args = scope.args().arity(1..=2)?;
+    let function = args.get::<public::Value>(0, "function")?.unbind();
+    let iterations = args.get_or::<i32>(1, "iterations", 1)?;
+    (function, iterations)
+  };
+  let Some(function) = function.clone().to_any().filter(is_callable) else {
+    fail!("`{function}` is not callable");
+  };
+  if iterations < 0 {
+    fail!("iterations must not be negative, got {iterations}");
+  }
+
+  let start = Instant::now();
+  for _ in 0..iterations {
+    scope.thread.call(function.clone(), &[]).await?;
+  }
+  let elapsed = start.elapsed();
+
+  Ok(Value::object(scope.alloc(Duration(elapsed))))
+}
+
 macro_rules! bind_builtin_fn {
   ($global:ident, $builtin:ident) => {{
     let name = stringify!($builtin);
@@ -432,8 +621,19 @@ pub fn register_builtin_functions(global: &Global) {
   bind_builtin_fn!(global, to_str);
   bind_builtin_fn!(global, type_of);
   bind_builtin_fn!(global, parse_int);
+  bind_builtin_fn!(global, round);
+  bind_builtin_fn!(global, trunc);
+  bind_builtin_fn!(global, sign);
+  bind_builtin_fn!(global, isnan);
+  bind_builtin_fn!(global, isinf);
+  bind_builtin_fn!(global, range);
+  bind_builtin_fn!(global, input);
+  bind_builtin_fn!(global, assert_eq);
   bind_builtin_fn!(global, async collect);
+  bind_builtin_fn!(global, async timeit);
 
+  #[cfg(feature = "decimal")]
+  super::decimal::register_builtin_functions(global);
   list::register_builtin_functions(global);
   string::register_builtin_functions(global);
 }
@@ -0,0 +1,109 @@
+use std::fmt::{Debug, Display};
+
+use super::{Object, Ptr, ReturnAddr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::thread::{AsyncFrame, CallResult};
+use crate::public::{CancellationToken, Scope};
+use crate::util::did_you_mean;
+
+/// The script side of a [`CancellationToken`].
+pub struct Cancellation {
+  pub token: CancellationToken,
+}
+
+const METHODS: [&str; 2] = ["cancelled", "wait"];
+
+impl Object for Cancellation {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "CancellationToken"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "cancelled" => Method::Cancelled,
+      "wait" => Method::Wait,
+      _ => fail!(
+        "`{this}` has no field `{name}`{}",
+        did_you_mean(&name, METHODS)
+      ),
+    };
+    Ok(Some(Value::object(scope.alloc(CancellationMethod {
+      cancellation: this,
+      method,
+    }))))
+  }
+}
+
+declare_object_type!(Cancellation);
+
+impl Display for Cancellation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<cancellation token>")
+  }
+}
+
+impl Debug for Cancellation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Cancellation")
+      .field("cancelled", &self.token.is_cancelled())
+      .finish()
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Method {
+  Cancelled,
+  Wait,
+}
+
+/// A method of a [`Cancellation`], bound to it.
+#[derive(Debug)]
+pub struct CancellationMethod {
+  cancellation: Ptr<Cancellation>,
+  method: Method,
+}
+
+impl Object for CancellationMethod {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "CancellationMethod"
+  }
+
+  default_instance_of!();
+
+  fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    let token = this.cancellation.token.clone();
+    match this.method {
+      Method::Cancelled => Ok(CallResult::Return(Value::bool(token.is_cancelled()))),
+      Method::Wait => Ok(CallResult::Poll(AsyncFrame {
+        stack_base: scope.stack_base,
+        fut: Box::pin(async move {
+          token.cancelled().await;
+          Ok(Value::none())
+        }),
+      })),
+    }
+  }
+}
+
+declare_object_type!(CancellationMethod);
+
+impl Display for CancellationMethod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self.method {
+      Method::Cancelled => "cancelled",
+      Method::Wait => "wait",
+    };
+    write!(f, "<cancellation token method `{name}`>")
+  }
+}
@@ -0,0 +1,121 @@
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use super::native::LocalBoxFuture;
+use super::{Object, Ptr, ReturnAddr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::thread::{AsyncFrame, CallResult};
+use crate::public::Scope;
+use crate::util::did_you_mean;
+
+/// The script side of a [`Channel`][crate::Channel], with the type of its
+/// items erased.
+pub trait ScriptChannel: Send + Sync {
+  /// Convert the first argument into an item and wait until there is room
+  /// for it.
+  fn send(&self, scope: Scope<'_>) -> LocalBoxFuture<'static, Result<Value>>;
+  /// Wait for the next item, or `none` once the channel is closed and empty.
+  fn recv(&self, scope: Scope<'_>) -> LocalBoxFuture<'static, Result<Value>>;
+  fn close(&self);
+}
+
+pub struct Channel {
+  pub inner: Arc<dyn ScriptChannel>,
+}
+
+const METHODS: [&str; 3] = ["send", "recv", "close"];
+
+impl Object for Channel {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Channel"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "send" => Method::Send,
+      "recv" => Method::Recv,
+      "close" => Method::Close,
+      _ => fail!(
+        "`{this}` has no field `{name}`{}",
+        did_you_mean(&name, METHODS)
+      ),
+    };
+    Ok(Some(Value::object(scope.alloc(ChannelMethod {
+      channel: this,
+      method,
+    }))))
+  }
+}
+
+declare_object_type!(Channel);
+
+impl Display for Channel {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<channel>")
+  }
+}
+
+impl Debug for Channel {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Channel").finish_non_exhaustive()
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Method {
+  Send,
+  Recv,
+  Close,
+}
+
+/// A method of a [`Channel`], bound to it.
+#[derive(Debug)]
+pub struct ChannelMethod {
+  channel: Ptr<Channel>,
+  method: Method,
+}
+
+impl Object for ChannelMethod {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "ChannelMethod"
+  }
+
+  default_instance_of!();
+
+  fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    let stack_base = scope.stack_base;
+    let fut = match this.method {
+      Method::Send => this.channel.inner.send(scope),
+      Method::Recv => this.channel.inner.recv(scope),
+      Method::Close => {
+        this.channel.inner.close();
+        return Ok(CallResult::Return(Value::none()));
+      }
+    };
+    Ok(CallResult::Poll(AsyncFrame { stack_base, fut }))
+  }
+}
+
+declare_object_type!(ChannelMethod);
+
+impl Display for ChannelMethod {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self.method {
+      Method::Send => "send",
+      Method::Recv => "recv",
+      Method::Close => "close",
+    };
+    write!(f, "<channel method `{name}`>")
+  }
+}
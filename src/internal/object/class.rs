@@ -1,41 +1,129 @@
-use std::fmt::Display;
-
-use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::fmt::{Debug, Display};
 
+use super::builtin::BuiltinMethod;
 use super::ptr::Ptr;
+use super::shape::{Fields, Shape};
+use super::string::{StrMap, StrSet};
 use super::{BoundFunction, Function, FunctionDescriptor, Object, ReturnAddr, Str, Table};
 use crate::internal::error::Result;
-use crate::internal::value::Value;
+use crate::internal::value::{cmp, FloatFormat, Value};
 use crate::internal::vm::global::Global;
 use crate::internal::vm::thread::CallResult;
-use crate::public::Scope;
+use crate::public::{Scope, Unbind};
+use crate::util::did_you_mean;
 
-#[derive(Debug)]
 pub struct ClassInstance {
   pub name: Ptr<Str>,
-  pub fields: Ptr<Table>,
+  pub fields: Fields,
   pub parent: Option<Ptr<ClassType>>,
+  pub class: Ptr<ClassType>,
 }
 
 impl ClassInstance {
-  pub fn new(global: Global, type_: &ClassType) -> Self {
+  pub fn new(type_: Ptr<ClassType>) -> Self {
     let name = type_.name.clone();
-    let fields = global.alloc(type_.fields.copy());
-    for (key, method) in type_.methods.iter() {
-      fields.insert(key.clone(), Value::object(method.clone()));
-    }
+    let shape = type_.shape.clone();
+    // methods come after the fields, unless a method replaces a field
+    let values = if shape.len() == type_.fields.len() + type_.methods.len() {
+      let methods = type_.methods.values();
+      let methods = methods.map(|method| Value::object(method.clone()));
+      type_.fields.values().chain(methods).collect()
+    } else {
+      let value = |key: &Ptr<Str>| match type_.methods.get(key) {
+        Some(method) => Value::object(method.clone()),
+        None => type_.fields.get(key).unwrap_or_else(Value::none),
+      };
+      shape.keys().map(value).collect()
+    };
+    let fields = Fields::new(shape, values);
     let parent = type_.parent.clone();
     Self {
       name,
       fields,
       parent,
+      class: type_,
     }
   }
 }
 
+impl ClassInstance {
+  /// Methods which instances of every data class have, unless the class
+  /// defines its own.
+  fn data_method(scope: Scope<'_>, this: Ptr<Self>, name: &str) -> Option<Value> {
+    if !this.class.is_data() {
+      return None;
+    }
+    let method = match name {
+      "to_table" => builtin_method!(instance_to_table),
+      _ => return None,
+    };
+    Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    }))
+  }
+}
+
+impl Debug for ClassInstance {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // `class` is left out, its methods are already in `fields`
+    f.debug_struct("ClassInstance")
+      .field("name", &self.name)
+      .field("fields", &self.fields)
+      .field("parent", &self.parent)
+      .finish()
+  }
+}
+
 impl Display for ClassInstance {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "<class `{}` instance>", self.name)
+    self.display(f, FloatFormat::Shortest)
+  }
+}
+
+impl ClassInstance {
+  /// Display the instance, formatting the floats stored in its fields
+  /// according to `format`.
+  pub fn display(&self, f: &mut std::fmt::Formatter<'_>, format: FloatFormat) -> std::fmt::Result {
+    if !self.class.is_data() {
+      return write!(f, "<class `{}` instance>", self.name);
+    }
+
+    thread_local! {
+      /// The instances which are being displayed on this thread.
+      static DISPLAYING: RefCell<Vec<*const ClassInstance>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct Pop;
+
+    impl Drop for Pop {
+      fn drop(&mut self) {
+        DISPLAYING.with(|displaying| displaying.borrow_mut().pop());
+      }
+    }
+
+    // an instance which contains itself is displayed as `Point(...)` inside
+    // itself, like lists and tables, which never display their items
+    let this = self as *const ClassInstance;
+    if DISPLAYING.with(|displaying| displaying.borrow().contains(&this)) {
+      return write!(f, "{}(...)", self.name);
+    }
+    DISPLAYING.with(|displaying| displaying.borrow_mut().push(this));
+    let _pop = Pop;
+
+    // `Point(x=1, y=2)`
+    write!(f, "{}(", self.name)?;
+    for (i, key) in self.class.fields.keys().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      let value = self.fields.get(&key).unwrap_or_else(Value::none);
+      match value.clone().to_object::<Str>() {
+        Some(str) => write!(f, "{key}={:?}", str.as_str())?,
+        None => write!(f, "{key}={}", value.display(format))?,
+      }
+    }
+    write!(f, ")")
   }
 }
 
@@ -44,15 +132,33 @@ impl Object for ClassInstance {
     "Instance"
   }
 
-  fn instance_of(_: Ptr<Self>, _: Value) -> Result<bool> {
-    todo!()
+  fn instance_of(this: Ptr<Self>, ty: Value) -> Result<bool> {
+    let Some(ty) = ty.to_object::<ClassType>() else {
+      return Ok(false);
+    };
+
+    // walk up the inheritance chain
+    let mut class = Some(this.class.clone());
+    while let Some(current) = class {
+      if current.ptr_eq(&ty) {
+        return Ok(true);
+      }
+      class = current.parent.clone();
+    }
+    Ok(false)
   }
 
   fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
-    let value = this
-      .fields
-      .get(&name)
-      .ok_or_else(|| error!("`{this}` has no field `{name}`"))?;
+    let Some(value) = this.fields.get(&name) else {
+      return Ok(
+        Self::data_method(scope, this.clone(), &name).ok_or_else(|| {
+          error!(
+            "`{this}` has no field `{name}`{}",
+            did_you_mean(&name, this.fields.keys())
+          )
+        })?,
+      );
+    };
 
     // bind functions
     if let Some(function) = value.clone().to_object::<Function>() {
@@ -65,7 +171,10 @@ impl Object for ClassInstance {
   }
 
   fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
-    let value = this.fields.get(&name);
+    let Some(value) = this.fields.get(&name) else {
+      return Ok(Self::data_method(scope, this, &name));
+    };
+    let value = Some(value);
 
     // bind functions
     if let Some(value) = value.clone() {
@@ -81,14 +190,45 @@ impl Object for ClassInstance {
 
   fn set_named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>, value: Value) -> Result<()> {
     if !this.fields.set(&name, value) {
-      fail!("`{this}` has no field `{name}`");
+      fail!(
+        "`{this}` has no field `{name}`{}",
+        did_you_mean(&name, this.fields.keys())
+      );
     }
 
     Ok(())
   }
+
+  fn eq(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // instances of data classes are equal if all of their fields are
+    if this.ptr_eq(&other) {
+      return Ok(true);
+    }
+    if !this.class.is_data() || !this.class.ptr_eq(&other.class) {
+      return Ok(false);
+    }
+    for key in this.class.fields.keys() {
+      let a = this.fields.get(&key).unwrap_or_else(Value::none);
+      let b = other.fields.get(&key).unwrap_or_else(Value::none);
+      if !cmp::equals(scope.clone(), a, b)? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
 }
 declare_object_type!(ClassInstance);
 
+/// Copy the fields of a data class instance into a table.
+fn instance_to_table(this: Ptr<ClassInstance>, scope: Scope<'_>) -> Result<Value> {
+  let table = Table::with_capacity(this.class.fields.len());
+  for key in this.class.fields.keys() {
+    let value = this.fields.get(&key).unwrap_or_else(Value::none);
+    table.insert(key, value);
+  }
+  Ok(Value::object(scope.alloc(table)))
+}
+
 #[derive(Debug)]
 pub struct ClassProxy {
   pub this: Ptr<ClassInstance>,
@@ -155,33 +295,71 @@ impl Object for ClassProxy {
 
 declare_object_type!(ClassProxy);
 
-#[derive(Debug)]
 pub struct ClassType {
   pub name: Ptr<Str>,
   pub init: Option<Ptr<Function>>,
   pub fields: Ptr<Table>,
-  pub methods: IndexMap<Ptr<Str>, Ptr<Function>>,
+  pub methods: StrMap<Ptr<Function>>,
   pub parent: Option<Ptr<ClassType>>,
+  /// The shape of new instances, with the fields followed by the methods.
+  pub shape: Ptr<Shape>,
 }
 
 impl ClassType {
+  /// Whether the class declares fields, either itself or through its
+  /// parent.
+  pub fn is_data(&self) -> bool {
+    !self.fields.is_empty()
+  }
+
+  /// Methods which every data class has, unless it defines its own.
+  fn data_method(scope: Scope<'_>, this: Ptr<Self>, name: &str) -> Option<Value> {
+    if !this.is_data() {
+      return None;
+    }
+    let method = match name {
+      "from_table" => builtin_method!(class_from_table),
+      _ => return None,
+    };
+    Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    }))
+  }
+
   pub fn new(
+    global: &Global,
     name: Ptr<Str>,
     init: Option<Ptr<Function>>,
     fields: Ptr<Table>,
-    methods: IndexMap<Ptr<Str>, Ptr<Function>>,
+    methods: StrMap<Ptr<Function>>,
     parent: Option<Ptr<ClassType>>,
   ) -> Self {
+    let keys = fields.keys().chain(methods.keys().cloned());
+    let shape = global.alloc(Shape::new(keys.collect::<StrSet>()));
     Self {
       name,
       init,
       fields,
       methods,
       parent,
+      shape,
     }
   }
 }
 
+impl Debug for ClassType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // `shape` is left out, it follows from `fields` and `methods`
+    f.debug_struct("ClassType")
+      .field("name", &self.name)
+      .field("init", &self.init)
+      .field("fields", &self.fields)
+      .field("methods", &self.methods)
+      .field("parent", &self.parent)
+      .finish()
+  }
+}
+
 impl Display for ClassType {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(f, "<class `{}`>", self.name)
@@ -199,26 +377,22 @@ impl Object for ClassType {
     todo!()
   }
 
-  fn named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
-    let value = this
-      .methods
-      .get(&name)
-      .cloned()
-      .map(Value::object)
-      .ok_or_else(|| error!("failed to get field `{name}`"))?;
-    Ok(value)
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      Self::named_field_opt(scope, this, name.clone())?
+        .ok_or_else(|| error!("failed to get field `{name}`"))?,
+    )
   }
 
-  fn named_field_opt(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
-    let value = this.methods.get(&name).cloned().map(Value::object);
-    Ok(value)
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    if let Some(method) = this.methods.get(&name) {
+      return Ok(Some(Value::object(method.clone())));
+    }
+    Ok(Self::data_method(scope, this, &name))
   }
 
   fn call(scope: Scope<'_>, this: Ptr<Self>, return_addr: ReturnAddr) -> Result<CallResult> {
-    let instance = scope.alloc(ClassInstance::new(
-      scope.thread.global.clone(),
-      this.as_ref(),
-    ));
+    let instance = scope.alloc(ClassInstance::new(this.clone()));
 
     match this.init.as_ref() {
       Some(init) => {
@@ -232,11 +406,34 @@ impl Object for ClassType {
 
 declare_object_type!(ClassType);
 
+/// Create an instance of a data class from a table of its fields. Fields
+/// missing from the table keep their defaults, and `init` is not called.
+fn class_from_table(this: Ptr<ClassType>, scope: Scope<'_>) -> Result<Value> {
+  let table = scope.param::<crate::public::Value>(0)?.unbind();
+  let Some(table) = table.clone().to_object::<Table>() else {
+    fail!("`{table}` is not a table");
+  };
+
+  let instance = ClassInstance::new(this.clone());
+  for (key, value) in table.entries() {
+    if this.fields.get(&key).is_none() {
+      fail!(
+        "`{this}` has no field `{key}`{}",
+        did_you_mean(&key, this.fields.keys())
+      );
+    }
+    instance.fields.set(&key, value);
+  }
+  Ok(Value::object(scope.alloc(instance)))
+}
+
 #[derive(Debug)]
 pub struct ClassDescriptor {
   pub name: Ptr<Str>,
   pub init: Option<Ptr<FunctionDescriptor>>,
-  pub methods: IndexMap<Ptr<Str>, Ptr<FunctionDescriptor>>,
+  pub methods: StrMap<Ptr<FunctionDescriptor>>,
+  /// The fields declared by the class, in the order of their slots in the
+  /// instances of a class without a parent.
   pub fields: Ptr<Table>,
 }
 
@@ -0,0 +1,197 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+
+use rust_decimal::prelude::ToPrimitive;
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// An exact decimal number, such as `1.50d`, the script-side counterpart of
+/// `rust_decimal::Decimal`.
+///
+/// Arithmetic is only defined between decimals, other numbers have to be
+/// converted with `to_decimal` first, so that a float never silently ends
+/// up in a calculation which must be exact.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(pub rust_decimal::Decimal);
+
+impl Decimal {
+  /// Parse the lexeme of a decimal literal, such as `1.50d`.
+  pub fn parse_literal(lexeme: &str) -> Option<Self> {
+    let digits = lexeme.strip_suffix('d')?;
+    rust_decimal::Decimal::from_str_exact(digits).ok().map(Self)
+  }
+
+  /// Convert `value`, which may be an int, a float, a string or a decimal,
+  /// into a decimal.
+  pub fn from_value(value: &Value) -> Result<Self> {
+    if let Some(v) = value.clone().to_int() {
+      return Ok(Self(v.into()));
+    }
+    if let Some(v) = value.clone().to_float() {
+      return match rust_decimal::Decimal::from_f64_retain(v) {
+        Some(v) => Ok(Self(v.normalize())),
+        None => fail!("`{v}` cannot be converted to a decimal"),
+      };
+    }
+    if let Some(v) = value.clone().to_object::<Decimal>() {
+      return Ok(*v);
+    }
+    if let Some(v) = value.clone().to_object::<Str>() {
+      return match rust_decimal::Decimal::from_str_exact(v.as_str().trim()) {
+        Ok(v) => Ok(Self(v)),
+        Err(e) => fail!("`{v}` is not a valid decimal: {e}"),
+      };
+    }
+    fail!("`{value}` cannot be converted to a decimal")
+  }
+}
+
+/// Round to `digits` digits after the decimal point, with halves rounded
+/// away from zero.
+fn decimal_round(this: Ptr<Decimal>, scope: Scope<'_>) -> Result<Value> {
+  let args = scope.args().arity(0..=1)?;
+  let digits = args.get_or::<i32>(0, "digits", 0)?;
+  let Ok(digits) = u32::try_from(digits) else {
+    fail!("digits must not be negative, got {digits}");
+  };
+  let rounded = this
+    .0
+    .round_dp_with_strategy(digits, rust_decimal::RoundingStrategy::MidpointAwayFromZero);
+  Ok(Value::object(scope.alloc(Decimal(rounded))))
+}
+
+fn decimal_to_float(this: Ptr<Decimal>, _: Scope<'_>) -> Result<Value> {
+  match this.0.to_f64() {
+    Some(v) => Ok(Value::float(v)),
+    None => fail!("`{this}` cannot be converted to a float"),
+  }
+}
+
+/// Convert to an int, discarding anything after the decimal point.
+fn decimal_to_int(this: Ptr<Decimal>, _: Scope<'_>) -> Result<Value> {
+  match this.0.trunc().to_i32() {
+    Some(v) => Ok(Value::int(v)),
+    None => fail!("`{this}` is out of range for an int"),
+  }
+}
+
+fn decimal_scale(this: Ptr<Decimal>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::int(this.0.scale() as i32))
+}
+
+/// The result of `this op other`, which is `None` if it overflowed.
+fn checked(
+  scope: Scope<'_>,
+  this: Ptr<Decimal>,
+  op: &str,
+  other: Ptr<Decimal>,
+  result: Option<rust_decimal::Decimal>,
+) -> Result<Value> {
+  let Some(value) = result else {
+    fail!("decimal overflow in `{this} {op} {other}`");
+  };
+  Ok(Value::object(scope.alloc(Decimal(value))))
+}
+
+impl Object for Decimal {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Decimal"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "round" => builtin_method!(decimal_round),
+      "to_float" => builtin_method!(decimal_to_float),
+      "to_int" => builtin_method!(decimal_to_int),
+      "scale" => builtin_method!(decimal_scale),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
+  fn add(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let result = this.0.checked_add(other.0);
+    checked(scope, this, "+", other, result)
+  }
+
+  fn subtract(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let result = this.0.checked_sub(other.0);
+    checked(scope, this, "-", other, result)
+  }
+
+  fn multiply(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let result = this.0.checked_mul(other.0);
+    checked(scope, this, "*", other, result)
+  }
+
+  fn divide(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    if other.0.is_zero() {
+      fail!("division by zero in `{this} / {other}`");
+    }
+    let result = this.0.checked_div(other.0);
+    checked(scope, this, "/", other, result)
+  }
+
+  fn remainder(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    if other.0.is_zero() {
+      fail!("division by zero in `{this} % {other}`");
+    }
+    let result = this.0.checked_rem(other.0);
+    checked(scope, this, "%", other, result)
+  }
+
+  fn invert(scope: Scope<'_>, this: Ptr<Self>) -> Result<Value> {
+    Ok(Value::object(scope.alloc(Decimal(-this.0))))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.0 == other.0)
+  }
+
+  fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    Ok(this.0.cmp(&other.0))
+  }
+}
+
+declare_object_type!(Decimal);
+
+impl Display for Decimal {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(&self.0, f)
+  }
+}
+
+impl Debug for Decimal {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Decimal").field(&self.0).finish()
+  }
+}
+
+fn to_decimal(scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<public::Value>(0)?.unbind();
+  let decimal = Decimal::from_value(&value)?;
+  Ok(Value::object(scope.alloc(decimal)))
+}
+
+pub fn register_builtin_functions(global: &Global) {
+  bind_builtin_fn!(global, to_decimal);
+}
@@ -1,19 +1,44 @@
 use std::cell::RefCell;
 use std::fmt::{Debug, Display};
 use std::ptr::NonNull;
+use std::sync::Arc;
 
+use super::builtin::BuiltinMethod;
+use super::class::ClassProxy;
 use super::module::ModuleId;
 use super::ptr::Ptr;
-use super::{Any, List, Object, ReturnAddr, Str};
+use super::{Any, List, Object, ReturnAddr, Str, Table};
+use crate::internal::bytecode::operands::Operand;
+use crate::internal::bytecode::spans::SpanMap;
 use crate::internal::bytecode::{disasm, opcode as op};
 use crate::internal::error::Result;
 use crate::internal::object;
 use crate::internal::value::constant::Constant;
 use crate::internal::value::Value;
 use crate::internal::vm::dispatch::LoadFrame;
+use crate::internal::vm::global::Global;
 use crate::internal::vm::thread::util::check_args;
 use crate::internal::vm::thread::{Args, CallResult, Frame, Slot0, Thread};
 use crate::public::Scope;
+use crate::span::Span;
+
+/// A function or method defined in a compiled script, see
+/// [`Chunk::functions`][crate::Chunk::functions].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionInfo {
+  pub name: String,
+  /// The class the function is a method of.
+  pub class: Option<String>,
+  /// The names of the parameters, not including `self`.
+  pub params: Vec<String>,
+  /// The number of arguments the function must be called with, which is the
+  /// number of parameters without a default value.
+  pub min_args: usize,
+  pub max_args: usize,
+  pub is_generator: bool,
+  /// The span of the function's name.
+  pub span: Span,
+}
 
 #[derive(Debug)]
 pub struct Function {
@@ -35,6 +60,14 @@ impl Function {
     }
   }
 
+  /// Name of the module the function was defined in, or `None` if it was
+  /// defined outside of a module, such as in a script passed to `eval`.
+  pub fn module_name(&self, global: &Global) -> Option<Ptr<Str>> {
+    global
+      .get_module_by_id(self.module_id)
+      .map(|module| module.name.clone())
+  }
+
   pub fn prepare_call_empty_unchecked(
     this: Ptr<Self>,
     thread: &mut Thread,
@@ -78,14 +111,15 @@ impl Function {
     let stack = unsafe { thread.stack.as_mut() };
 
     thread.pc = 0;
+    let stack_base = stack.regs.len();
     stack
       .frames
-      .push(Frame::new(function, stack.regs.len(), return_addr));
+      .push(Frame::new(function, stack_base, return_addr));
 
     stack.regs.reserve(frame_size);
 
     if !descriptor.params.has_self {
-      stack.regs.push(Value::object(this));
+      stack.regs.push(Value::object(this.clone()));
       stack
         .regs
         .extend_from_within(args.start..args.start + args.count);
@@ -100,12 +134,60 @@ impl Function {
         .regs
         .extend((0..frame_size - args.count).map(|_| Value::none()));
     }
+    fill_constant_defaults(descriptor, &mut stack.regs[stack_base..]);
+
+    Ok(LoadFrame { bytecode, pc: 0 })
+  }
+
+  /// Like [`Function::prepare_call`], but `args` must be registers of the
+  /// current frame which follow the register holding the callee. Instead of
+  /// copying them, the new frame starts at the callee's register, or at the
+  /// first argument if that is the receiver. Every register of the caller
+  /// after the callee must be unused until the call returns.
+  pub fn prepare_call_in_place(
+    this: Ptr<Self>,
+    thread: &mut Thread,
+    args: Args,
+    return_addr: usize,
+  ) -> Result<LoadFrame> {
+    let function = this.as_ref();
+    let descriptor = function.descriptor.as_ref();
+    let bytecode = descriptor.instructions;
+    check_args(&descriptor.params, false, args.count)?;
+
+    let has_self = descriptor.params.has_self;
+    let stack_base = if has_self { args.start } else { args.start - 1 };
+    let frame_end = stack_base + descriptor.frame_size;
+    let stack = unsafe { thread.stack.as_mut() };
+    let caller_len = stack.regs.len();
+    paranoid_assert!(args.start + args.count <= caller_len);
+
+    thread.pc = 0;
+    stack.frames.push(Frame::new_in_place(
+      function,
+      stack_base,
+      caller_len,
+      return_addr,
+    ));
+
+    if !has_self {
+      stack.regs[stack_base] = Value::object(this.clone());
+    }
+    // the rest of the frame may still hold values from the caller
+    let reused = args.start + args.count..frame_end.min(caller_len);
+    stack.regs[reused].fill_with(Value::none);
+    if frame_end > caller_len {
+      stack.regs.resize_with(frame_end, Value::none);
+    }
+    fill_constant_defaults(descriptor, &mut stack.regs[stack_base..frame_end]);
 
     Ok(LoadFrame { bytecode, pc: 0 })
   }
 }
 
 impl Object for Function {
+  const CALLABLE: bool = true;
+
   fn type_name(_: Ptr<Self>) -> &'static str {
     "Function"
   }
@@ -114,6 +196,26 @@ impl Object for Function {
     todo!()
   }
 
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      Self::named_field_opt(scope, this.clone(), name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "name" => builtin_method!(function_name),
+      "params" => builtin_method!(function_params),
+      "module" => builtin_method!(function_module),
+      "signature" => builtin_method!(function_signature),
+      _ => return Ok(None),
+    };
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
   fn call(mut scope: Scope<'_>, this: Ptr<Self>, return_addr: ReturnAddr) -> Result<CallResult> {
     Self::prepare_call(this, &mut scope.thread, scope.args, return_addr)
       .map(|_| CallResult::Dispatch)
@@ -157,45 +259,172 @@ pub struct FunctionDescriptor {
   pub name: Ptr<Str>,
   pub is_generator: bool,
   pub params: Params,
+  pub signature: Signature,
   pub upvalues: RefCell<Vec<Upvalue>>,
   pub frame_size: usize,
+  /// Points into `code`.
   pub instructions: NonNull<[u8]>,
+  /// Owns the instructions, which are never modified, so copies of the
+  /// descriptor in other VMs share them.
+  code: Arc<[u8]>,
   pub constants: NonNull<[Constant]>,
-  // TODO: spans
+  pub spans: SpanMap,
+  /// The span of the function's name, or an empty span for the main function
+  /// of a module.
+  pub span: Span,
+  /// Unique across all functions in the process, unlike the address of the
+  /// descriptor, which may be reused once it is freed.
+  #[cfg(feature = "profile")]
+  pub id: u64,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum Upvalue {
   Register(op::Register),
   Upvalue(op::Upvalue),
 }
 
+/// A variable which is shared between a call frame and the closures which
+/// capture it.
+///
+/// A captured variable's register holds the cell instead of its value, so
+/// that assigning to it on either side is seen by the other.
+#[derive(Debug)]
+pub struct Cell {
+  value: RefCell<Value>,
+}
+
+impl Cell {
+  pub fn new(value: Value) -> Self {
+    Self {
+      value: RefCell::new(value),
+    }
+  }
+
+  pub fn get(&self) -> Value {
+    self.value.borrow().clone()
+  }
+
+  pub fn set(&self, value: Value) {
+    *self.value.borrow_mut() = value;
+  }
+}
+
+impl Display for Cell {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<cell {}>", self.value.borrow())
+  }
+}
+
+impl Object for Cell {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Cell"
+  }
+
+  default_instance_of!();
+}
+
+declare_object_type!(Cell);
+
 fn vec_to_nonnull_ptr<T>(v: Vec<T>) -> NonNull<[T]> {
   unsafe { NonNull::new_unchecked(Box::into_raw(v.into_boxed_slice())) }
 }
 
 impl FunctionDescriptor {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     name: Ptr<Str>,
     is_generator: bool,
     params: Params,
+    signature: Signature,
     upvalues: Vec<Upvalue>,
     frame_size: usize,
-    instructions: Vec<u8>,
+    code: impl Into<Arc<[u8]>>,
     constants: Vec<Constant>,
+    spans: SpanMap,
+    span: Span,
   ) -> Self {
-    let instructions = vec_to_nonnull_ptr(instructions);
+    let code = code.into();
+    let instructions = NonNull::from(&*code);
     let constants = vec_to_nonnull_ptr(constants);
     Self {
       name,
       is_generator,
       params,
+      signature,
       upvalues: RefCell::new(upvalues),
       frame_size,
       instructions,
+      code,
       constants,
+      spans,
+      span,
+      #[cfg(feature = "profile")]
+      id: {
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+      },
     }
   }
+
+  /// The source span of the instruction at `pc`.
+  pub fn span_at(&self, pc: usize) -> Option<Span> {
+    self.spans.span_at(pc)
+  }
+
+  /// The instructions, shared with the descriptor.
+  pub fn code(&self) -> Arc<[u8]> {
+    self.code.clone()
+  }
+}
+
+impl FunctionDescriptor {
+  pub fn constants(&self) -> &[Constant] {
+    unsafe { self.constants.as_ref() }
+  }
+
+  /// Call `f` with this function and every function defined inside of it,
+  /// along with the name of the class each method belongs to.
+  pub fn visit(
+    &self,
+    class_name: Option<&Ptr<Str>>,
+    f: &mut dyn FnMut(&FunctionDescriptor, Option<&Ptr<Str>>),
+  ) {
+    f(self, class_name);
+    for constant in self.constants() {
+      match constant {
+        Constant::Function(function) => function.visit(None, f),
+        Constant::Class(class) => {
+          for method in class.init.iter().chain(class.methods.values()) {
+            method.visit(Some(&class.name), f);
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// The names of the globals this function reads or writes, in the order
+  /// they first appear in its bytecode. Functions defined inside of it are
+  /// not included.
+  pub fn globals(&self) -> Vec<Ptr<Str>> {
+    let mut globals = Vec::<Ptr<Str>>::new();
+    for (width, opcode, operands) in op::instructions(unsafe { self.instructions.as_ref() }) {
+      if !matches!(opcode, op::Opcode::LoadGlobal | op::Opcode::StoreGlobal) {
+        continue;
+      }
+      let index = u32::decode(operands, width) as usize;
+      if let Some(Constant::String(name)) = self.constants().get(index) {
+        if !globals
+          .iter()
+          .any(|global| global.as_str() == name.as_str())
+        {
+          globals.push(name.clone());
+        }
+      }
+    }
+    globals
+  }
 }
 
 impl FunctionDescriptor {
@@ -273,7 +502,6 @@ impl<'a> Display for Disassembly<'a> {
 
 impl Drop for FunctionDescriptor {
   fn drop(&mut self) {
-    let _ = unsafe { Box::from_raw(self.instructions.as_ptr()) };
     let _ = unsafe { Box::from_raw(self.constants.as_ptr()) };
   }
 }
@@ -334,6 +562,122 @@ impl Default for Params {
   }
 }
 
+/// Fill in the constant defaults of the parameters of a frame which starts
+/// at `frame`, see [`Signature::fill_constant_defaults`].
+fn fill_constant_defaults(descriptor: &FunctionDescriptor, frame: &mut [Value]) {
+  // only functions with optional parameters have defaults
+  if descriptor.params.min != descriptor.params.max {
+    descriptor.signature.fill_constant_defaults(&mut frame[1..]);
+  }
+}
+
+/// Names and defaults of the parameters of a function, which are known
+/// without calling it.
+///
+/// `self` is not included.
+#[derive(Debug, Default)]
+pub struct Signature {
+  pub params: Vec<Param>,
+}
+
+#[derive(Debug)]
+pub struct Param {
+  pub name: Ptr<Str>,
+  pub default: ParamDefault,
+}
+
+#[derive(Debug)]
+pub enum ParamDefault {
+  /// The parameter is required.
+  None,
+  /// The default value is a constant, evaluated at compile time. Calls
+  /// fill it in, so it is not part of the function's bytecode.
+  Constant(Value),
+  /// The default value is an expression evaluated by each call.
+  Expr,
+}
+
+impl Signature {
+  /// Replace `none` in the registers of parameters which have a constant
+  /// default with that default. `params` starts at the first parameter.
+  fn fill_constant_defaults(&self, params: &mut [Value]) {
+    for (param, value) in self.params.iter().zip(params) {
+      if let ParamDefault::Constant(default) = &param.default {
+        if value.is_none() {
+          *value = default.clone();
+        }
+      }
+    }
+  }
+
+  /// The names of the parameters as a list.
+  fn names(&self, scope: &Scope<'_>) -> Value {
+    let list = List::with_capacity(self.params.len());
+    for param in self.params.iter() {
+      list.push(Value::object(param.name.clone()));
+    }
+    Value::object(scope.alloc(list))
+  }
+
+  /// The signature as a list of tables with the keys `name`, `required`
+  /// and `default`. `default` is `none` if the default value is not a
+  /// constant.
+  fn to_list(&self, scope: &Scope<'_>) -> Value {
+    let global = &scope.thread.global;
+    let list = List::with_capacity(self.params.len());
+    for param in self.params.iter() {
+      let (required, default) = match &param.default {
+        ParamDefault::None => (true, Value::none()),
+        ParamDefault::Constant(value) => (false, value.clone()),
+        ParamDefault::Expr => (false, Value::none()),
+      };
+      let table = Table::with_capacity(3);
+      table.insert(global.intern("name"), Value::object(param.name.clone()));
+      table.insert(global.intern("required"), Value::bool(required));
+      table.insert(global.intern("default"), default);
+      list.push(Value::object(scope.alloc(table)));
+    }
+    Value::object(scope.alloc(list))
+  }
+}
+
+fn function_name(this: Ptr<Function>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this.descriptor.name.clone()))
+}
+
+fn function_params(this: Ptr<Function>, scope: Scope<'_>) -> Result<Value> {
+  Ok(this.descriptor.signature.names(&scope))
+}
+
+fn function_module(this: Ptr<Function>, scope: Scope<'_>) -> Result<Value> {
+  Ok(
+    this
+      .module_name(&scope.thread.global)
+      .map(Value::object)
+      .unwrap_or_else(Value::none),
+  )
+}
+
+fn function_signature(this: Ptr<Function>, scope: Scope<'_>) -> Result<Value> {
+  Ok(this.descriptor.signature.to_list(&scope))
+}
+
+fn bound_function_name(this: Ptr<BoundFunction>, scope: Scope<'_>) -> Result<Value> {
+  function_name(this.function.clone(), scope)
+}
+
+fn bound_function_params(this: Ptr<BoundFunction>, scope: Scope<'_>) -> Result<Value> {
+  function_params(this.function.clone(), scope)
+}
+
+fn bound_function_module(this: Ptr<BoundFunction>, scope: Scope<'_>) -> Result<Value> {
+  function_module(this.function.clone(), scope)
+}
+
+fn bound_function_signature(this: Ptr<BoundFunction>, scope: Scope<'_>) -> Result<Value> {
+  function_signature(this.function.clone(), scope)
+}
+
 // TODO: store name and type_name
 #[derive(Debug)]
 pub struct BoundFunction {
@@ -347,6 +691,23 @@ impl BoundFunction {
 
     Self { this, function }
   }
+
+  pub fn function(&self) -> &Ptr<Function> {
+    &self.function
+  }
+
+  pub fn this(&self) -> &Ptr<Any> {
+    &self.this
+  }
+
+  /// The instance the function is bound to, which is behind the proxy
+  /// for methods accessed through `super`.
+  fn receiver(&self) -> Ptr<Any> {
+    match self.this.clone().cast::<ClassProxy>() {
+      Ok(proxy) => proxy.this.clone().into_any(),
+      Err(this) => this,
+    }
+  }
 }
 
 impl Display for BoundFunction {
@@ -356,6 +717,8 @@ impl Display for BoundFunction {
 }
 
 impl Object for BoundFunction {
+  const CALLABLE: bool = true;
+
   fn type_name(_: Ptr<Self>) -> &'static str {
     "BoundFunction"
   }
@@ -364,6 +727,32 @@ impl Object for BoundFunction {
     todo!()
   }
 
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      Self::named_field_opt(scope, this.clone(), name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "name" => builtin_method!(bound_function_name),
+      "params" => builtin_method!(bound_function_params),
+      "module" => builtin_method!(bound_function_module),
+      "signature" => builtin_method!(bound_function_signature),
+      _ => return Ok(None),
+    };
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // every access to a method binds it again, so two bound methods are
+    // equal if they bind the same function to the same instance
+    Ok(this.function.ptr_eq(&other.function) && this.receiver().ptr_eq(&other.receiver()))
+  }
+
   fn call(mut scope: Scope<'_>, this: Ptr<Self>, return_addr: ReturnAddr) -> Result<CallResult> {
     let bound_function = this.as_ref();
     let function = bound_function.function.as_ref();
@@ -376,11 +765,14 @@ impl Object for BoundFunction {
       .frames
       .push(Frame::new(function, stack.regs.len(), return_addr));
 
+    let stack_base = stack.regs.len();
     let _ = scope.enter_nested(
       Slot0::Receiver(Value::object(this.this.clone())),
       scope.args,
       Some(descriptor.frame_size),
     );
+    let stack = unsafe { scope.thread.stack.as_mut() };
+    fill_constant_defaults(descriptor, &mut stack.regs[stack_base..]);
 
     Ok(CallResult::Dispatch)
   }
@@ -0,0 +1,80 @@
+//! Methods on ints.
+//!
+//! Ints are not objects, so their methods are looked up here by the VM,
+//! and are bound to the int they were loaded from.
+//!
+//! The arithmetic methods each decide what happens when the result does
+//! not fit in an int:
+//! - `checked_*` returns `none`
+//! - `saturating_*` returns the smallest or largest int
+//! - `wrapping_*` wraps around, like two's complement arithmetic
+
+use super::builtin::{BuiltinMethod, MethodCallback};
+use super::{Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::public::Scope;
+
+type IntMethodCallback = fn(i32, Scope<'_>) -> Result<Value>;
+
+macro_rules! int_method {
+  ($function:expr) => {{
+    let cb: MethodCallback = |this: Value, scope: Scope<'_>| {
+      let this = unsafe { this.to_int_unchecked() };
+      let function: IntMethodCallback = $function;
+      function(this, scope)
+    };
+    cb
+  }};
+}
+
+macro_rules! arithmetic {
+  ($($name:ident: $kind:ident $op:ident),* $(,)?) => {
+    $(
+      fn $name(this: i32, scope: Scope<'_>) -> Result<Value> {
+        let other = scope.param::<i32>(0)?;
+        Ok(arithmetic!(@$kind this.$op(other)))
+      }
+    )*
+  };
+  (@checked $value:expr) => {
+    match $value {
+      Some(value) => Value::int(value),
+      None => Value::none(),
+    }
+  };
+  (@plain $value:expr) => {
+    Value::int($value)
+  };
+}
+
+arithmetic! {
+  int_checked_add: checked checked_add,
+  int_checked_sub: checked checked_sub,
+  int_checked_mul: checked checked_mul,
+  int_saturating_add: plain saturating_add,
+  int_saturating_sub: plain saturating_sub,
+  int_saturating_mul: plain saturating_mul,
+  int_wrapping_add: plain wrapping_add,
+  int_wrapping_sub: plain wrapping_sub,
+  int_wrapping_mul: plain wrapping_mul,
+}
+
+pub fn named_field_opt(scope: Scope<'_>, this: i32, name: Ptr<Str>) -> Result<Option<Value>> {
+  let method = match name.as_str() {
+    "checked_add" => int_method!(int_checked_add),
+    "checked_sub" => int_method!(int_checked_sub),
+    "checked_mul" => int_method!(int_checked_mul),
+    "saturating_add" => int_method!(int_saturating_add),
+    "saturating_sub" => int_method!(int_saturating_sub),
+    "saturating_mul" => int_method!(int_saturating_mul),
+    "wrapping_add" => int_method!(int_wrapping_add),
+    "wrapping_sub" => int_method!(int_wrapping_sub),
+    "wrapping_mul" => int_method!(int_wrapping_mul),
+    _ => return Ok(None),
+  };
+
+  Ok(Some(Value::object(unsafe {
+    scope.alloc(BuiltinMethod::new(Value::int(this), method))
+  })))
+}
@@ -1,19 +1,28 @@
 use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::vec::Vec;
 
+use smallvec::SmallVec;
+
 use super::builtin::BuiltinMethod;
 use super::{Object, Ptr, Str};
 use crate::internal::error::Result;
-use crate::internal::value::Value;
+use crate::internal::value::{cmp, Value};
 use crate::internal::vm::global::Global;
 use crate::public;
 use crate::public::{Scope, Unbind};
 use crate::util::{JoinIter, MAX_SAFE_INT, MIN_SAFE_INT};
 
+/// The number of elements a list can hold without allocating.
+///
+/// Most lists are small, such as short literals or the upvalues of a
+/// closure, so their elements are stored inline.
+const INLINE: usize = 4;
+
 #[derive(Default)]
 pub struct List {
-  data: RefCell<Vec<Value>>,
+  data: RefCell<SmallVec<[Value; INLINE]>>,
 }
 
 impl List {
@@ -23,7 +32,7 @@ impl List {
 
   pub fn with_capacity(n: usize) -> Self {
     Self {
-      data: RefCell::new(Vec::with_capacity(n)),
+      data: RefCell::new(SmallVec::with_capacity(n)),
     }
   }
 
@@ -56,7 +65,7 @@ impl List {
   ///
   /// - `index` must be within the bounds of `self`
   pub unsafe fn get_unchecked(&self, index: usize) -> Value {
-    debug_assert!(index < self.len(), "index {index} out of bounds");
+    paranoid_assert!(index < self.len(), "index {index} out of bounds");
     self.data.borrow().get_unchecked(index).clone()
   }
 
@@ -70,11 +79,15 @@ impl List {
     }
   }
 
+  pub fn swap(&self, a: usize, b: usize) {
+    self.data.borrow_mut().swap(a, b);
+  }
+
   /// # Safety
   ///
   /// - `index` must be within the bounds of `self`
   pub unsafe fn set_unchecked(&self, index: usize, value: Value) {
-    debug_assert!(index < self.len(), "index {index} out of bounds");
+    paranoid_assert!(index < self.len(), "index {index} out of bounds");
     *self.data.borrow_mut().get_mut(index).unwrap_unchecked() = value;
   }
 
@@ -109,7 +122,7 @@ impl<'a> Iterator for Iter<'a> {
 impl From<Vec<Value>> for List {
   fn from(values: Vec<Value>) -> Self {
     Self {
-      data: RefCell::new(values),
+      data: RefCell::new(SmallVec::from_vec(values)),
     }
   }
 }
@@ -171,10 +184,52 @@ fn list_extend(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
   Ok(Value::none())
 }
 
+/// Sorts the list in place, see [`cmp`] for how values are ordered.
+fn list_sort(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  // comparisons may call back into the list, so it can't stay borrowed
+  let mut items = this.data.borrow().clone();
+  let mut error = None;
+  items.sort_by(|a, b| {
+    if error.is_some() {
+      return Ordering::Equal;
+    }
+    cmp::total_cmp(scope.clone(), a.clone(), b.clone()).unwrap_or_else(|e| {
+      error = Some(e);
+      Ordering::Equal
+    })
+  });
+  if let Some(e) = error {
+    return Err(e);
+  }
+  *this.data.borrow_mut() = items;
+  Ok(Value::none())
+}
+
+/// Sort a list of strings with the collator for the locale passed as the
+/// optional parameter, see [`super::string::collator`].
+#[cfg(feature = "collation")]
+fn list_sort_collated(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
+  let locale = super::string::locale_param(&scope, 0)?;
+  let collator = super::string::collator(locale.as_deref())?;
+  let mut items = Vec::with_capacity(this.len());
+  for item in this.iter() {
+    match item.clone().to_object::<Str>() {
+      Some(str) => items.push(str),
+      None => fail!("`sort_collated` can only sort strings, found `{item}`"),
+    }
+  }
+  items.sort_by(|a, b| collator.compare(a.as_str(), b.as_str()));
+  *this.data.borrow_mut() = items.into_iter().map(Value::object).collect();
+  Ok(Value::none())
+}
+
 fn list_join(this: Ptr<List>, scope: Scope<'_>) -> Result<Value> {
   let sep = scope.param::<public::Str>(0)?;
+  let format = scope.thread.global.float_format();
+  let items = this.iter().collect::<Vec<_>>();
+  let items = items.iter().map(|item| item.display(format));
   Ok(Value::object(
-    scope.alloc(Str::owned(this.iter().join(sep.as_str()))),
+    scope.alloc(Str::owned(items.join(sep.as_str()))),
   ))
 }
 
@@ -231,7 +286,7 @@ impl Object for ListIter {
       "iter" => builtin_method!(list_iter_iter),
       "next" => builtin_method!(list_iter_next),
       "done" => builtin_method!(list_iter_done),
-      _ => fail!("`{this}` has no field `{name}`"),
+      _ => return Ok(None),
     };
 
     Ok(Some(Value::object(unsafe {
@@ -280,6 +335,9 @@ impl Object for List {
       "pop" => builtin_method!(list_pop),
       "extend" => builtin_method!(list_extend),
       "join" => builtin_method!(list_join),
+      "sort" => builtin_method!(list_sort),
+      #[cfg(feature = "collation")]
+      "sort_collated" => builtin_method!(list_sort_collated),
       "iter" => builtin_method!(list_iter),
       _ => fail!("`{this}` has no field `{name}`"),
     };
@@ -312,6 +370,26 @@ impl Object for List {
     };
     Ok(())
   }
+
+  fn eq(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    if this.len() != other.len() {
+      return Ok(false);
+    }
+    for (a, b) in this.iter().zip(other.iter()) {
+      if !cmp::equals(scope.clone(), a, b)? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  fn cmp(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    cmp::cmp_seq(scope, this.iter(), other.iter())
+  }
+
+  fn partial_cmp(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Option<Ordering>> {
+    cmp::partial_cmp_seq(scope, this.iter(), other.iter())
+  }
 }
 
 pub fn register_builtin_functions(global: &Global) {
@@ -326,6 +404,7 @@ pub fn register_builtin_functions(global: &Global) {
       pop: builtin_method_static!(List, list_pop),
       extend: builtin_method_static!(List, list_extend),
       join: builtin_method_static!(List, list_join),
+      sort: builtin_method_static!(List, list_sort),
       iter: builtin_method_static!(List, list_iter)
     })
   );
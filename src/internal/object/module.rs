@@ -1,16 +1,18 @@
 use std::fmt::{Debug, Display};
 use std::num::NonZeroU64;
 
-use indexmap::{IndexMap, IndexSet};
+use indexmap::IndexMap;
 
 use super::native::{NativeAsyncFunction, NativeClass, NativeFunction};
 use super::ptr::Ptr;
-use super::{Function, FunctionDescriptor, Object, Str, Table};
+use super::string::{StrMap, StrSet};
+use super::{ClassType, Function, FunctionDescriptor, Object, Str, Table};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
-use crate::internal::vm::global::Global;
+use crate::internal::vm::global::{Construct, Global};
 use crate::public::module::NativeModule;
 use crate::public::Scope;
+use crate::util::did_you_mean;
 use crate::Cow;
 
 pub trait ModuleLoader: Send {
@@ -44,7 +46,7 @@ impl Display for ModuleId {
 #[derive(Debug)]
 pub struct Registry {
   pub next_module_id: NonZeroU64,
-  pub index: IndexMap<Ptr<Str>, ModuleId>,
+  pub index: StrMap<ModuleId>,
   pub modules: IndexMap<ModuleId, Ptr<Module>>,
 }
 
@@ -52,7 +54,7 @@ impl Registry {
   pub fn new() -> Self {
     Self {
       next_module_id: unsafe { NonZeroU64::new_unchecked(1) },
-      index: IndexMap::new(),
+      index: StrMap::default(),
       modules: IndexMap::new(),
     }
   }
@@ -110,7 +112,7 @@ impl Module {
     global: Global,
     name: Ptr<Str>,
     root: Ptr<Function>,
-    module_vars: &IndexSet<Ptr<Str>>,
+    module_vars: &StrSet,
     module_id: ModuleId,
   ) -> Self {
     let module_vars = {
@@ -136,32 +138,56 @@ impl Module {
     module_id: ModuleId,
   ) -> Self {
     let module_vars = global.alloc(Table::with_capacity(module.data.fns.len()));
+    let owner = Some(name.clone());
 
     for (name, f) in module.data.fns.iter() {
-      let name = global.alloc(Str::owned(name.clone()));
+      let name = global.intern(name.clone());
       let f = Value::object(global.alloc(NativeFunction {
         name: name.clone(),
+        owner: owner.clone(),
         cb: f.clone(),
       }));
       module_vars.insert(name, f);
     }
 
     for (name, f) in module.data.async_fns.iter() {
-      let name = global.alloc(Str::owned(name.clone()));
+      let name = global.intern(name.clone());
       let f = Value::object(global.alloc(NativeAsyncFunction {
         name: name.clone(),
+        owner: owner.clone(),
         cb: f.clone(),
       }));
       module_vars.insert(name, f);
     }
 
     for (name, desc) in module.data.classes.iter() {
-      let name = global.alloc(Str::owned(name.clone()));
+      let name = global.intern(name.clone());
       let class = global.alloc(NativeClass::new(global.clone(), desc));
       global.register_type_raw(class.type_id, class.clone());
       module_vars.insert(name, Value::object(class));
     }
 
+    let mut error_classes = IndexMap::<_, Ptr<ClassType>>::new();
+    for (name, parent) in module.data.error_classes.iter() {
+      let class_name = global.alloc(Str::owned(name.clone()));
+      let fields = global.alloc(Table::with_capacity(3));
+      fields.insert(global.intern("code"), Value::object(class_name.clone()));
+      fields.insert(global.intern("message"), Value::none());
+      fields.insert(global.intern("data"), Value::none());
+      let parent = parent.as_ref().map(|parent| error_classes[parent].clone());
+      let class = global.alloc(ClassType::new(
+        &global,
+        class_name.clone(),
+        None,
+        fields,
+        StrMap::default(),
+        parent,
+      ));
+      global.register_error_class(name.clone(), class.clone());
+      module_vars.insert(class_name, Value::object(class.clone()));
+      error_classes.insert(name, class);
+    }
+
     Self {
       module_id,
       name,
@@ -179,10 +205,14 @@ impl Object for Module {
   default_instance_of!();
 
   fn named_field(_: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
-    let value = this
-      .module_vars
-      .get(&name)
-      .ok_or_else(|| error!("module `{}` has no export `{}`", this.name, name))?;
+    let value = this.module_vars.get(&name).ok_or_else(|| {
+      error!(
+        "module `{}` has no export `{}`{}",
+        this.name,
+        name,
+        did_you_mean(&name, this.module_vars.keys())
+      )
+    })?;
     Ok(value)
   }
 
@@ -203,7 +233,10 @@ impl Display for Module {
 pub struct ModuleDescriptor {
   pub name: Ptr<Str>,
   pub root: Ptr<FunctionDescriptor>,
-  pub module_vars: IndexSet<Ptr<Str>>,
+  pub module_vars: StrSet,
+  /// The kinds of statements which the module uses and a host may forbid,
+  /// so that they can be checked again when the compiled module is loaded.
+  pub constructs: Vec<Construct>,
 }
 
 impl Object for ModuleDescriptor {
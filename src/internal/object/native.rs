@@ -6,12 +6,14 @@ use std::sync::Arc;
 
 use indexmap::IndexMap;
 
+use super::string::StrMap;
 use super::{Any, Object, Ptr, ReturnAddr, Str};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
 use crate::internal::vm::global::Global;
 use crate::internal::vm::thread::{AsyncFrame, CallResult, Slot0};
 use crate::public::Scope;
+use crate::util::did_you_mean;
 
 pub type LocalBoxFuture<'a, T> = Pin<Box<dyn core::future::Future<Output = T> + 'a>>;
 
@@ -21,11 +23,43 @@ pub type AsyncCallback = Callback<LocalBoxFuture<'static, Result<Value>>>;
 
 pub struct NativeFunction {
   pub name: Ptr<Str>,
+  /// The module or class the function belongs to, which tells apart
+  /// functions with the same name in profiles.
+  pub owner: Option<Ptr<Str>>,
   pub cb: SyncCallback,
 }
 
 impl NativeFunction {
   pub fn call(&self, scope: Scope) -> Result<Value> {
+    if let Some(policy) = scope.thread.global.policy() {
+      policy.call_native(self.name.as_str())?;
+    }
+    if !scope.thread.global.logs_native_calls() {
+      return self.call_host(scope);
+    }
+    let global = scope.thread.global.clone();
+    let args = args(&scope);
+    if let Some(result) = global.replay_native_call(self.name.as_str(), &args) {
+      return result;
+    }
+    let index = global.start_native_call(self.name.as_str(), global.to_data(&args));
+    let result = self.call_host(scope);
+    if let Some(index) = index {
+      global.finish_native_call(index, &result);
+    }
+    result
+  }
+
+  fn call_host(&self, scope: Scope) -> Result<Value> {
+    #[cfg(feature = "profile")]
+    {
+      let global = scope.thread.global.clone();
+      let start = std::time::Instant::now();
+      let result = (self.cb)(scope);
+      global.record_native_call(&self.owner, &self.name, start.elapsed());
+      result
+    }
+    #[cfg(not(feature = "profile"))]
     (self.cb)(scope)
   }
 }
@@ -45,6 +79,8 @@ impl Display for NativeFunction {
 }
 
 impl Object for NativeFunction {
+  const CALLABLE: bool = true;
+
   fn type_name(_: Ptr<Self>) -> &'static str {
     "NativeFunction"
   }
@@ -60,13 +96,60 @@ impl Object for NativeFunction {
 
 declare_object_type!(NativeFunction);
 
+/// The arguments of the call `scope` belongs to.
+fn args(scope: &Scope<'_>) -> Vec<Value> {
+  let stack = unsafe { scope.thread.stack.as_ref() };
+  stack.regs[scope.args.start..scope.args.start + scope.args.count].to_vec()
+}
+
 pub struct NativeAsyncFunction {
   pub name: Ptr<Str>,
+  /// See [`NativeFunction::owner`].
+  pub owner: Option<Ptr<Str>>,
   pub cb: AsyncCallback,
 }
 
 impl NativeAsyncFunction {
   pub fn call(&self, scope: Scope) -> LocalBoxFuture<'static, Result<Value>> {
+    if let Some(policy) = scope.thread.global.policy() {
+      if let Err(e) = policy.call_native(self.name.as_str()) {
+        return Box::pin(std::future::ready(Err(e)));
+      }
+    }
+    if !scope.thread.global.logs_native_calls() {
+      return self.call_host(scope);
+    }
+    let global = scope.thread.global.clone();
+    let args = args(&scope);
+    if let Some(result) = global.replay_native_call(self.name.as_str(), &args) {
+      return Box::pin(std::future::ready(result));
+    }
+    let index = global.start_native_call(self.name.as_str(), global.to_data(&args));
+    let fut = self.call_host(scope);
+    Box::pin(async move {
+      let result = fut.await;
+      if let Some(index) = index {
+        global.finish_native_call(index, &result);
+      }
+      result
+    })
+  }
+
+  fn call_host(&self, scope: Scope) -> LocalBoxFuture<'static, Result<Value>> {
+    #[cfg(feature = "profile")]
+    {
+      let global = scope.thread.global.clone();
+      let owner = self.owner.clone();
+      let name = self.name.clone();
+      let start = std::time::Instant::now();
+      let fut = (self.cb)(scope);
+      Box::pin(async move {
+        let result = fut.await;
+        global.record_native_call(&owner, &name, start.elapsed());
+        result
+      })
+    }
+    #[cfg(not(feature = "profile"))]
     (self.cb)(scope)
   }
 }
@@ -86,6 +169,8 @@ impl Display for NativeAsyncFunction {
 }
 
 impl Object for NativeAsyncFunction {
+  const CALLABLE: bool = true;
+
   fn type_name(_: Ptr<Self>) -> &'static str {
     "NativeAsyncFunction"
   }
@@ -141,7 +226,13 @@ impl Object for NativeClassInstance {
         method.clone(),
       ))))
     } else {
-      fail!("`{this}` has no field `{name}`")
+      let fields = (this.class.fields.keys())
+        .chain(this.class.methods.keys())
+        .map(|key| key.as_str());
+      fail!(
+        "`{this}` has no field `{name}`{}",
+        did_you_mean(&name, fields)
+      )
     }
   }
 
@@ -187,7 +278,11 @@ impl Object for NativeClassInstance {
       scope.leave();
       result
     } else {
-      fail!("`{this}` has no field `{name}`")
+      let fields = this.class.fields.keys().map(|key| key.as_str());
+      fail!(
+        "`{this}` has no field `{name}`{}",
+        did_you_mean(&name, fields)
+      )
     }
   }
 }
@@ -199,35 +294,38 @@ pub struct NativeClass {
   pub name: Ptr<Str>,
   pub type_id: TypeId,
   pub init: Option<Ptr<NativeFunction>>,
-  pub fields: IndexMap<Ptr<Str>, NativeField>,
-  pub methods: IndexMap<Ptr<Str>, Ptr<Any>>,
-  pub static_methods: IndexMap<Ptr<Str>, Ptr<Any>>,
+  pub fields: StrMap<NativeField>,
+  pub methods: StrMap<Ptr<Any>>,
+  pub static_methods: StrMap<Ptr<Any>>,
 }
 
 impl NativeClass {
   pub fn new(global: Global, desc: &NativeClassDescriptor) -> Self {
-    let name = global.alloc(Str::owned(desc.name.clone()));
+    let class_name = global.alloc(Str::owned(desc.name.clone()));
 
     let type_id = desc.type_id;
 
     let init = desc.init.clone().map(|init| {
       global.alloc(NativeFunction {
         name: global.intern("__init__"),
+        owner: Some(class_name.clone()),
         cb: init,
       })
     });
 
-    let mut fields = IndexMap::with_capacity(desc.fields.len());
+    let mut fields = StrMap::with_capacity_and_hasher(desc.fields.len(), Default::default());
     for (name, desc) in desc.fields.iter() {
       let name = global.alloc(Str::owned(name.clone()));
       let field = NativeField {
         get: global.alloc(NativeFunction {
           name: global.intern("__get__"),
+          owner: Some(class_name.clone()),
           cb: desc.get.clone(),
         }),
         set: desc.set.as_ref().map(|set| {
           global.alloc(NativeFunction {
             name: global.intern("__set__"),
+            owner: Some(class_name.clone()),
             cb: set.clone(),
           })
         }),
@@ -235,22 +333,23 @@ impl NativeClass {
       fields.insert(name, field);
     }
 
-    let mut methods = IndexMap::with_capacity(desc.methods.len());
+    let mut methods = StrMap::with_capacity_and_hasher(desc.methods.len(), Default::default());
     for (name, desc) in desc.methods.iter() {
       let name = global.alloc(Str::owned(name.clone()));
-      let method = desc.to_function(name.clone(), &global);
+      let method = desc.to_function(name.clone(), &class_name, &global);
       methods.insert(name, method);
     }
 
-    let mut static_methods = IndexMap::with_capacity(desc.static_methods.len());
+    let mut static_methods =
+      StrMap::with_capacity_and_hasher(desc.static_methods.len(), Default::default());
     for (name, desc) in desc.static_methods.iter() {
       let name = global.alloc(Str::owned(name.clone()));
-      let method = desc.to_function(name.clone(), &global);
+      let method = desc.to_function(name.clone(), &class_name, &global);
       static_methods.insert(name, method);
     }
 
     Self {
-      name,
+      name: class_name,
       type_id,
       init,
       fields,
@@ -393,17 +492,19 @@ pub enum NativeMethodDescriptor {
 }
 
 impl NativeMethodDescriptor {
-  fn to_function(&self, name: Ptr<Str>, global: &Global) -> Ptr<Any> {
+  fn to_function(&self, name: Ptr<Str>, owner: &Ptr<Str>, global: &Global) -> Ptr<Any> {
     match self {
       NativeMethodDescriptor::Sync(cb) => global
         .alloc(NativeFunction {
           name,
+          owner: Some(owner.clone()),
           cb: cb.clone(),
         })
         .into_any(),
       NativeMethodDescriptor::Async(cb) => global
         .alloc(NativeAsyncFunction {
           name,
+          owner: Some(owner.clone()),
           cb: cb.clone(),
         })
         .into_any(),
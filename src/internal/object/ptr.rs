@@ -5,11 +5,12 @@ use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::ptr::{self, NonNull};
+use std::rc::Rc;
 use std::{alloc, mem};
 
 use super::{Type, VTable};
 use crate::internal::error::Result;
-use crate::internal::vm::global::Global;
+use crate::internal::vm::global::{Global, Heap};
 
 // TODO: identity eq specialization similar to `std::rc::Rc`
 
@@ -17,11 +18,25 @@ use crate::internal::vm::global::Global;
 struct Repr<T: Sized + 'static> {
   layout: Layout,
   type_id: TypeId,
+  /// The reference count, and [`HAS_HEAP`].
   refs: Cell<u64>,
   vtable: &'static super::VTable<T>,
   data: T,
 }
 
+/// Set in the reference count of objects which were allocated by the
+/// [`Heap`] of a VM, which does not use the global allocator or counts its
+/// objects. Such an object is preceded by a reference to the heap, so that
+/// it is given back to the heap when it is freed, even after the VM is
+/// gone. Other objects only take up a [`Repr`].
+const HAS_HEAP: u64 = 1 << 63;
+
+/// The layout of an object which is preceded by a reference to its heap,
+/// and the offset of the object in it.
+fn heap_layout(layout: Layout) -> (Layout, usize) {
+  Layout::new::<Rc<Heap>>().extend(layout).unwrap()
+}
+
 pub struct Ptr<T: Sized + 'static> {
   repr: NonNull<Repr<T>>,
 }
@@ -32,7 +47,12 @@ impl<T: Sized + 'static> Ptr<T> {
   }
 
   pub(crate) fn refs(&self) -> u64 {
-    self.repr().refs.get()
+    self.repr().refs.get() & !HAS_HEAP
+  }
+
+  /// The address of the object, which identifies it while it is alive.
+  pub(crate) fn addr(&self) -> usize {
+    self.repr.as_ptr() as usize
   }
 
   pub(crate) fn into_addr(self) -> usize {
@@ -93,11 +113,23 @@ impl<T: Sized + 'static> Drop for Ptr<T> {
       unsafe { Self::decref(self.repr) };
     } else {
       unsafe { ptr::drop_in_place((&mut self.repr.as_mut().data) as *mut _) };
-
       let ptr = self.repr.as_ptr() as *mut u8;
       let layout = self.repr().layout;
-      // TODO: replace with `alloc::Global.deallocate` when `alloc::Global` is stable
-      unsafe { alloc::dealloc(ptr, layout) }
+      if self.repr().refs.get() & HAS_HEAP == 0 {
+        // TODO: replace with `alloc::Global.deallocate` when `alloc::Global` is stable
+        unsafe { alloc::dealloc(ptr, layout) };
+        return;
+      }
+
+      let (layout, offset) = heap_layout(layout);
+      let ptr = unsafe { ptr.sub(offset) };
+      let heap = unsafe { ptr::read(ptr as *const Rc<Heap>) };
+      #[cfg(feature = "count_objects")]
+      heap.count_free(self.repr().type_id);
+      match &heap.allocator {
+        Some(allocator) => unsafe { allocator.dealloc(ptr, layout) },
+        None => unsafe { alloc::dealloc(ptr, layout) },
+      }
     }
   }
 }
@@ -161,22 +193,50 @@ impl<T> AsRef<T> for Ptr<T> {
 
 impl<T: Type + Sized + 'static> Ptr<T> {
   pub(crate) unsafe fn alloc_raw(v: T) -> Self {
-    let object = Box::new(Repr {
-      layout: Layout::new::<Repr<T>>(),
+    Self::alloc_in(v, None)
+  }
+
+  unsafe fn alloc_in(v: T, heap: Option<Rc<Heap>>) -> Self {
+    let layout = Layout::new::<Repr<T>>();
+    let (ptr, refs) = match heap {
+      Some(heap) => {
+        let (heap_layout, offset) = heap_layout(layout);
+        let ptr = match &heap.allocator {
+          Some(allocator) => allocator.alloc(heap_layout),
+          None => alloc::alloc(heap_layout),
+        };
+        if ptr.is_null() {
+          alloc::handle_alloc_error(heap_layout);
+        }
+        (ptr as *mut Rc<Heap>).write(heap);
+        (ptr.add(offset), HAS_HEAP | 1)
+      }
+      None => (alloc::alloc(layout), 1),
+    };
+    let Some(repr) = NonNull::new(ptr as *mut Repr<T>) else {
+      alloc::handle_alloc_error(layout);
+    };
+    repr.as_ptr().write(Repr {
+      layout,
       type_id: TypeId::of::<T>(),
-      refs: Cell::new(1),
+      refs: Cell::new(refs),
       vtable: <T as Type>::vtable(),
       data: v,
     });
-    Ptr {
-      repr: NonNull::new_unchecked(Box::into_raw(object)),
-    }
+    Ptr { repr }
   }
 }
 
 impl Global {
   pub fn alloc<T: Type + 'static>(&self, v: T) -> Ptr<T> {
-    unsafe { Ptr::alloc_raw(v) }
+    let ptr = unsafe { Ptr::alloc_in(v, self.heap().cloned()) };
+    #[cfg(feature = "count_objects")]
+    if let Some(heap) = self.heap() {
+      let (layout, _) = heap_layout(Layout::new::<Repr<T>>());
+      let size = layout.size();
+      heap.count_alloc(TypeId::of::<T>(), size, || T::type_name(ptr.clone()));
+    }
+    ptr
   }
 }
 
@@ -0,0 +1,292 @@
+use std::cell::Cell;
+use std::fmt::{Debug, Display};
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::public::{Scope, Unbind};
+
+/// The ints from `start` up to, but not including, `end`, `step` apart.
+///
+/// A negative step counts down from `start` to `end`. The bounds are kept
+/// wider than an int, so that reversing a range which ends at the smallest
+/// or largest int does not overflow, but every item is an int.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+  start: i64,
+  end: i64,
+  step: i64,
+}
+
+impl Range {
+  pub fn new(start: i32, end: i32, step: i32) -> Result<Self> {
+    if step == 0 {
+      fail!("range step cannot be zero");
+    }
+    Ok(Self {
+      start: start as i64,
+      end: end as i64,
+      step: step as i64,
+    })
+  }
+
+  pub fn len(&self) -> usize {
+    let distance = match self.step > 0 {
+      true => self.end - self.start,
+      false => self.start - self.end,
+    };
+    if distance <= 0 {
+      return 0;
+    }
+    ((distance - 1) / self.step.abs() + 1) as usize
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn get(&self, index: usize) -> Option<i32> {
+    if index >= self.len() {
+      return None;
+    }
+    Some((self.start + index as i64 * self.step) as i32)
+  }
+
+  pub fn contains_int(&self, value: i64) -> bool {
+    let in_bounds = match self.step > 0 {
+      true => self.start <= value && value < self.end,
+      false => self.end < value && value <= self.start,
+    };
+    in_bounds && (value - self.start) % self.step == 0
+  }
+
+  /// Whether `value` is one of the items. A float is, if it is equal to
+  /// one of them.
+  pub fn contains_value(&self, value: Value) -> bool {
+    if let Some(value) = value.clone().to_int() {
+      self.contains_int(value as i64)
+    } else if let Some(value) = value.to_float() {
+      value.fract() == 0.0 && value.abs() <= i64::MAX as f64 && self.contains_int(value as i64)
+    } else {
+      false
+    }
+  }
+
+  /// The same items in the opposite order.
+  pub fn rev(&self) -> Self {
+    match self.len() {
+      // all empty ranges with the same step are equal, so this one
+      // can start and end in the same place
+      0 => Self {
+        start: self.start,
+        end: self.start,
+        step: -self.step,
+      },
+      len => Self {
+        start: self.start + (len as i64 - 1) * self.step,
+        end: self.start - self.step,
+        step: -self.step,
+      },
+    }
+  }
+}
+
+fn range_len(this: Ptr<Range>, _: Scope<'_>) -> Result<Value> {
+  // `range(-2^31, 2^31 - 1)` has more items than fit in an int
+  match i32::try_from(this.len()) {
+    Ok(len) => Ok(Value::int(len)),
+    Err(_) => Ok(Value::float(this.len() as f64)),
+  }
+}
+
+fn range_is_empty(this: Ptr<Range>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.is_empty()))
+}
+
+fn range_contains(this: Ptr<Range>, scope: Scope<'_>) -> Result<Value> {
+  let value = scope.param::<crate::public::Value>(0)?.unbind();
+  Ok(Value::bool(this.contains_value(value)))
+}
+
+fn range_rev(this: Ptr<Range>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(this.rev())))
+}
+
+fn range_iter(this: Ptr<Range>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(scope.alloc(RangeIter {
+    range: *this.as_ref(),
+    index: Cell::new(0),
+  })))
+}
+
+impl Object for Range {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Range"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "len" => builtin_method!(range_len),
+      "is_empty" => builtin_method!(range_is_empty),
+      "contains" => builtin_method!(range_contains),
+      "rev" => builtin_method!(range_rev),
+      "iter" => builtin_method!(range_iter),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
+  fn contains(_: Scope<'_>, this: Ptr<Self>, item: Value) -> Result<bool> {
+    Ok(this.contains_value(item))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // ranges are equal if they have the same items
+    let (len, other_len) = (this.len(), other.len());
+    Ok(match len {
+      0 => other_len == 0,
+      1 => other_len == 1 && this.start == other.start,
+      _ => len == other_len && this.start == other.start && this.step == other.step,
+    })
+  }
+}
+
+declare_object_type!(Range);
+
+impl Display for Range {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.step {
+      1 => write!(f, "range({}, {})", self.start, self.end),
+      step => write!(f, "range({}, {}, {step})", self.start, self.end),
+    }
+  }
+}
+
+impl Debug for Range {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(self, f)
+  }
+}
+
+#[derive(Debug)]
+pub struct RangeIter {
+  range: Range,
+  index: Cell<usize>,
+}
+
+impl Display for RangeIter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<range iter>")
+  }
+}
+
+fn range_iter_iter(this: Ptr<RangeIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(this))
+}
+
+fn range_iter_next(this: Ptr<RangeIter>, _: Scope<'_>) -> Result<Value> {
+  match this.range.get(this.index.get()) {
+    Some(item) => {
+      this.index.set(this.index.get() + 1);
+      Ok(Value::int(item))
+    }
+    None => Ok(Value::none()),
+  }
+}
+
+fn range_iter_done(this: Ptr<RangeIter>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::bool(this.index.get() >= this.range.len()))
+}
+
+impl Object for RangeIter {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "RangeIter"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "iter" => builtin_method!(range_iter_iter),
+      "next" => builtin_method!(range_iter_next),
+      "done" => builtin_method!(range_iter_done),
+      _ => return Ok(None),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+}
+
+declare_object_type!(RangeIter);
+
+/// `range(end)`, `range(start, end)`, or `range(start, end, step)`.
+pub fn range(scope: Scope<'_>) -> Result<Value> {
+  let args = scope.args().arity(1..=3)?;
+  let (start, end) = match args.len() {
+    1 => (0, args.get::<i32>(0, "end")?),
+    _ => (args.get::<i32>(0, "start")?, args.get::<i32>(1, "end")?),
+  };
+  let step = args.get_or::<i32>(2, "step", 1)?;
+  Ok(Value::object(scope.alloc(Range::new(start, end, step)?)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn items(range: Range) -> Vec<i32> {
+    (0..range.len()).map(|i| range.get(i).unwrap()).collect()
+  }
+
+  #[test]
+  fn range_items() {
+    let range = |start, end, step| Range::new(start, end, step).unwrap();
+    assert_eq!(items(range(0, 5, 1)), [0, 1, 2, 3, 4]);
+    assert_eq!(items(range(0, 10, 3)), [0, 3, 6, 9]);
+    assert_eq!(items(range(10, 0, -3)), [10, 7, 4, 1]);
+    assert_eq!(items(range(10, 0, 1)), Vec::<i32>::new());
+    assert_eq!(items(range(0, 10, -1)), Vec::<i32>::new());
+    assert_eq!(items(range(0, 10, 3).rev()), [9, 6, 3, 0]);
+    assert_eq!(items(range(10, 0, -3).rev()), [1, 4, 7, 10]);
+    assert_eq!(items(range(0, 10, 3).rev().rev()), [0, 3, 6, 9]);
+    assert!(range(5, 5, 1).rev().is_empty());
+
+    let edge = range(i32::MAX - 2, i32::MAX, 1);
+    assert_eq!(items(edge.rev()), [i32::MAX - 1, i32::MAX - 2]);
+    let edge = range(i32::MIN, i32::MIN + 2, 1);
+    assert_eq!(items(edge.rev()), [i32::MIN + 1, i32::MIN]);
+    assert_eq!(range(i32::MIN, i32::MAX, 1).len(), u32::MAX as usize);
+
+    assert!(range(0, 10, 3).contains_int(9));
+    assert!(!range(0, 10, 3).contains_int(10));
+    assert!(!range(0, 10, 3).contains_int(4));
+    assert!(range(10, 0, -3).contains_int(1));
+    assert!(!range(10, 0, -3).contains_int(0));
+
+    assert!(Range::new(0, 1, 0).is_err());
+  }
+}
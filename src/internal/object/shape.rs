@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+use indexmap::Equivalent;
+
+use super::ptr::Ptr;
+use super::string::{StrMap, StrSet};
+use super::{Object, Str, Table};
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+
+/// The layout of the fields of class instances, which maps each field to the
+/// slot its value is stored in.
+///
+/// An instance starts out with the shape of its class. Adding a field moves
+/// it to a shape with the field in the next slot, which is created the first
+/// time and reused after that, so instances which had the same fields added
+/// in the same order share a shape.
+#[derive(Debug)]
+pub struct Shape {
+  keys: StrSet,
+  /// The shapes reached by adding a field to this one, by the field's key.
+  transitions: RefCell<StrMap<Ptr<Shape>>>,
+}
+
+impl Shape {
+  pub fn new(keys: StrSet) -> Self {
+    Self {
+      keys,
+      transitions: RefCell::default(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.keys.len()
+  }
+
+  /// The slot of the field `key`.
+  pub fn slot<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K) -> Option<usize> {
+    self.keys.get_index_of(key)
+  }
+
+  /// The key of the field in `slot`.
+  pub fn key(&self, slot: usize) -> Option<&Ptr<Str>> {
+    self.keys.get_index(slot)
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = &Ptr<Str>> {
+    self.keys.iter()
+  }
+
+  /// The shape with the field `key` added after the fields of `this`.
+  pub fn with_field(this: &Ptr<Self>, global: &Global, key: Ptr<Str>) -> Ptr<Self> {
+    if let Some(shape) = this.transitions.borrow().get(&key) {
+      return shape.clone();
+    }
+    let mut keys = this.keys.clone();
+    keys.insert(key.clone());
+    let shape = global.alloc(Shape::new(keys));
+    this.transitions.borrow_mut().insert(key, shape.clone());
+    shape
+  }
+}
+
+impl Display for Shape {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<shape>")
+  }
+}
+
+impl Object for Shape {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Shape"
+  }
+
+  default_instance_of!();
+}
+
+declare_object_type!(Shape);
+
+/// The fields of a class instance, with their values stored in the slots
+/// given by the instance's shape.
+pub struct Fields {
+  shape: RefCell<Ptr<Shape>>,
+  values: RefCell<Vec<Value>>,
+}
+
+impl Fields {
+  /// `values` holds the value of every field of `shape`, in slot order.
+  pub fn new(shape: Ptr<Shape>, values: Vec<Value>) -> Self {
+    debug_assert_eq!(shape.len(), values.len());
+    Self {
+      shape: RefCell::new(shape),
+      values: RefCell::new(values),
+    }
+  }
+
+  pub fn shape(&self) -> Ptr<Shape> {
+    self.shape.borrow().clone()
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.borrow().len()
+  }
+
+  pub fn get<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K) -> Option<Value> {
+    let slot = self.shape.borrow().slot(key)?;
+    Some(self.values.borrow()[slot].clone())
+  }
+
+  /// Set the field `key` to `value`. Returns `false` if there is no such
+  /// field.
+  pub fn set<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K, value: Value) -> bool {
+    let Some(slot) = self.shape.borrow().slot(key) else {
+      return false;
+    };
+    self.values.borrow_mut()[slot] = value;
+    true
+  }
+
+  /// Set the field `key` to `value`, adding the field if there is no such
+  /// field yet.
+  pub fn insert(&self, global: &Global, key: Ptr<Str>, value: Value) {
+    let slot = self.shape.borrow().slot(&key);
+    match slot {
+      Some(slot) => self.values.borrow_mut()[slot] = value,
+      None => {
+        let shape = Shape::with_field(&self.shape.borrow(), global, key);
+        *self.shape.borrow_mut() = shape;
+        self.values.borrow_mut().push(value);
+      }
+    }
+  }
+
+  /// The value in `slot`, if the field in `slot` is `key`.
+  pub fn get_index_with_key(&self, slot: usize, key: &Ptr<Str>) -> Option<Value> {
+    let shape = self.shape.borrow();
+    let k = shape.key(slot)?;
+    (k.ptr_eq(key) || k == key).then(|| self.values.borrow()[slot].clone())
+  }
+
+  /// Set the value in `slot` to `value`, if the field in `slot` is `key`.
+  /// Returns `false` if it isn't.
+  pub fn set_index_with_key(&self, slot: usize, key: &Ptr<Str>, value: Value) -> bool {
+    let shape = self.shape.borrow();
+    match shape.key(slot) {
+      Some(k) if k.ptr_eq(key) || k == key => {
+        self.values.borrow_mut()[slot] = value;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = Ptr<Str>> {
+    let shape = self.shape();
+    (0..shape.len()).map(move |slot| shape.key(slot).unwrap().clone())
+  }
+
+  pub fn entries(&self) -> impl Iterator<Item = (Ptr<Str>, Value)> + '_ {
+    self.keys().zip(0..).map(|(key, slot)| {
+      let value = self.values.borrow()[slot].clone();
+      (key, value)
+    })
+  }
+
+  /// Copy the fields into a table.
+  pub fn to_table(&self) -> Table {
+    let table = Table::with_capacity(self.len());
+    for (key, value) in self.entries() {
+      table.insert(key, value);
+    }
+    table
+  }
+}
+
+impl Debug for Fields {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut s = f.debug_map();
+    for (key, value) in self.entries() {
+      s.entry(&key, &value);
+    }
+    s.finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fields_added_in_the_same_order_share_a_shape() {
+    let global = Global::default();
+    let keys = StrSet::from_iter([global.intern("a")]);
+    let shape = global.alloc(Shape::new(keys));
+    let new = || Fields::new(shape.clone(), vec![Value::int(0)]);
+
+    let (x, y) = (new(), new());
+    for fields in [&x, &y] {
+      fields.insert(&global, global.intern("b"), Value::int(1));
+      fields.insert(&global, global.intern("c"), Value::int(2));
+    }
+    assert!(x.shape().ptr_eq(&y.shape()));
+    assert_eq!(x.shape().slot("c"), Some(2));
+    assert_eq!(y.get("b").and_then(|v| v.to_int()), Some(1));
+
+    // setting a field which exists doesn't change the shape
+    let before = x.shape();
+    x.insert(&global, global.intern("a"), Value::int(3));
+    assert!(x.shape().ptr_eq(&before));
+
+    // a different order is a different shape
+    let z = new();
+    z.insert(&global, global.intern("c"), Value::int(2));
+    z.insert(&global, global.intern("b"), Value::int(1));
+    assert!(!z.shape().ptr_eq(&x.shape()));
+    assert_eq!(z.shape().slot("c"), Some(1));
+  }
+}
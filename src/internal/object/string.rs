@@ -1,20 +1,31 @@
 use std::borrow::Borrow;
 use std::cell::Cell;
 use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
 use std::fmt::{Debug, Display};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
 use std::ops::Deref;
+use std::sync::OnceLock;
+
+use indexmap::{IndexMap, IndexSet};
 
 use super::builtin::BuiltinMethod;
 use super::{Object, Ptr};
 use crate::internal::error::Result;
 use crate::internal::value::Value;
 use crate::internal::vm::global::Global;
+use crate::public;
 use crate::public::Scope;
 use crate::Cow;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Cloning a string shares its contents if they are static, and its hash if
+/// it was already computed.
+#[derive(Clone)]
 pub struct Str {
   data: Cow<'static, str>,
+  /// The hash of `data`, computed the first time it is needed.
+  /// `0` if it hasn't been computed yet.
+  hash: Cell<u64>,
 }
 
 impl Str {
@@ -22,12 +33,14 @@ impl Str {
   pub fn borrowed(data: &'static str) -> Self {
     Self {
       data: Cow::borrowed(data),
+      hash: Cell::new(0),
     }
   }
 
   pub fn owned(data: impl ToString) -> Self {
     Self {
       data: Cow::owned(data.to_string()),
+      hash: Cell::new(0),
     }
   }
 
@@ -35,6 +48,24 @@ impl Str {
     self.data.as_ref()
   }
 
+  /// The hash of the string's contents, which is the same as the hash of
+  /// an equal `str` under [`StrHasher`].
+  pub fn hash_value(&self) -> u64 {
+    match self.hash.get() {
+      0 => {
+        let hash = hash_bytes(self.as_bytes());
+        self.hash.set(hash);
+        hash
+      }
+      hash => hash,
+    }
+  }
+
+  /// The hash of the string, if it was already computed.
+  fn cached_hash(&self) -> Option<u64> {
+    Some(self.hash.get()).filter(|hash| *hash != 0)
+  }
+
   pub fn concat(&self, other: &str) -> Self {
     let mut out = String::with_capacity(self.len() + other.len());
     out.push_str(self.as_str());
@@ -43,6 +74,83 @@ impl Str {
   }
 }
 
+/// `s` with its case folded, so that strings which only differ in case fold
+/// to the same string.
+///
+/// This converts to upper case and then back to lower case, which matches
+/// Unicode full case folding for practically all text, for example `ß` and
+/// `SS` both fold to `ss`.
+pub fn casefold(s: &str) -> String {
+  if s.is_ascii() {
+    return s.to_ascii_lowercase();
+  }
+  s.to_uppercase().to_lowercase()
+}
+
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+  if a.is_ascii() && b.is_ascii() {
+    return a.eq_ignore_ascii_case(b);
+  }
+  casefold(a) == casefold(b)
+}
+
+/// A collator which orders strings the way people who speak `locale`
+/// expect, or in a way which works for most languages if it is `None`.
+#[cfg(feature = "collation")]
+pub fn collator(locale: Option<&str>) -> Result<icu_collator::Collator> {
+  let locale = match locale {
+    Some(locale) => match locale.parse::<icu_locid::Locale>() {
+      Ok(locale) => locale,
+      Err(_) => fail!("invalid locale `{locale}`"),
+    },
+    None => icu_locid::Locale::UND,
+  };
+  let options = icu_collator::CollatorOptions::new();
+  match icu_collator::Collator::try_new(&(&locale).into(), options) {
+    Ok(collator) => Ok(collator),
+    Err(e) => fail!("cannot collate strings for locale `{locale}`: {e}"),
+  }
+}
+
+/// The locale passed as the optional parameter `n`.
+#[cfg(feature = "collation")]
+pub fn locale_param(scope: &Scope<'_>, n: usize) -> Result<Option<String>> {
+  match scope.num_args() > n {
+    true => scope.param::<Option<String>>(n),
+    false => Ok(None),
+  }
+}
+
+/// `s` with the characters which are special in HTML text and attribute
+/// values replaced by entities.
+pub fn escape_html(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+/// `s` quoted so that a POSIX shell reads it as a single word, without
+/// expanding anything in it.
+///
+/// Strings which only contain characters that are never special are left
+/// as they are, and all others are wrapped in single quotes.
+pub fn quote_shell(s: &str) -> String {
+  let safe = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+  if !s.is_empty() && s.chars().all(safe) {
+    return s.to_string();
+  }
+  format!("'{}'", s.replace('\'', "'\\''"))
+}
+
 fn str_len(this: Ptr<Str>, _: Scope<'_>) -> Result<Value> {
   Ok(Value::int(this.len() as i32))
 }
@@ -51,6 +159,45 @@ fn str_is_empty(this: Ptr<Str>, _: Scope<'_>) -> Result<Value> {
   Ok(Value::bool(this.is_empty()))
 }
 
+fn str_casefold(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(
+    scope.alloc(Str::owned(casefold(this.as_str()))),
+  ))
+}
+
+fn str_escape_json(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  let mut out = String::with_capacity(this.len());
+  crate::internal::json::escape_str(&mut out, this.as_str());
+  Ok(Value::object(scope.alloc(Str::owned(out))))
+}
+
+fn str_escape_html(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(
+    scope.alloc(Str::owned(escape_html(this.as_str()))),
+  ))
+}
+
+fn str_quote_shell(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  Ok(Value::object(
+    scope.alloc(Str::owned(quote_shell(this.as_str()))),
+  ))
+}
+
+fn str_eq_ignore_case(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  let other = scope.param::<public::Str>(0)?;
+  Ok(Value::bool(eq_ignore_case(this.as_str(), other.as_str())))
+}
+
+/// `-1`, `0` or `1` depending on whether `this` sorts before, with or after
+/// the other string, see [`collator`].
+#[cfg(feature = "collation")]
+fn str_collate(this: Ptr<Str>, scope: Scope<'_>) -> Result<Value> {
+  let other = scope.param::<public::Str>(0)?;
+  let locale = locale_param(&scope, 1)?;
+  let ordering = collator(locale.as_deref())?.compare(this.as_str(), other.as_str());
+  Ok(Value::int(ordering as i32))
+}
+
 pub struct LinesIter {
   str: Ptr<Str>,
   offset: Cell<Option<usize>>,
@@ -96,7 +243,7 @@ impl Object for LinesIter {
       "iter" => builtin_method!(str_lines_iter),
       "next" => builtin_method!(str_lines_next),
       "done" => builtin_method!(str_lines_done),
-      _ => fail!("`{this}` has no field `{name}`"),
+      _ => return Ok(None),
     };
 
     Ok(Some(Value::object(unsafe {
@@ -181,6 +328,13 @@ impl Object for Str {
       "len" => builtin_method!(str_len),
       "is_empty" => builtin_method!(str_is_empty),
       "lines" => builtin_method!(str_lines),
+      "casefold" => builtin_method!(str_casefold),
+      "eq_ignore_case" => builtin_method!(str_eq_ignore_case),
+      "escape_json" => builtin_method!(str_escape_json),
+      "escape_html" => builtin_method!(str_escape_html),
+      "quote_shell" => builtin_method!(str_quote_shell),
+      #[cfg(feature = "collation")]
+      "collate" => builtin_method!(str_collate),
       _ => fail!("`{this}` has no field `{name}`"),
     };
 
@@ -193,6 +347,11 @@ impl Object for Str {
     Ok(Value::object(scope.alloc(this.concat(other.as_str()))))
   }
 
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    // a string is always equal to itself, without comparing the contents
+    Ok(this.ptr_eq(&other) || *this == *other)
+  }
+
   fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
     Ok(this.as_str().cmp(other.as_str()))
   }
@@ -204,13 +363,108 @@ pub fn register_builtin_functions(global: &Global) {
     builtin_type!(Str {
       len: builtin_method_static!(Str, str_len),
       is_empty: builtin_method_static!(Str, str_is_empty),
-      lines: builtin_method_static!(Str, str_lines)
+      lines: builtin_method_static!(Str, str_lines),
+      casefold: builtin_method_static!(Str, str_casefold),
+      eq_ignore_case: builtin_method_static!(Str, str_eq_ignore_case),
+      escape_json: builtin_method_static!(Str, str_escape_json),
+      escape_html: builtin_method_static!(Str, str_escape_html),
+      quote_shell: builtin_method_static!(Str, str_quote_shell)
     })
   );
 }
 
 declare_object_type!(Str);
 
+impl PartialEq for Str {
+  fn eq(&self, other: &Self) -> bool {
+    if let (Some(a), Some(b)) = (self.cached_hash(), other.cached_hash()) {
+      if a != b {
+        return false;
+      }
+    }
+    self.as_str() == other.as_str()
+  }
+}
+
+impl Eq for Str {}
+
+impl PartialOrd for Str {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Str {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+impl Hash for Str {
+  /// The same as hashing the `str`, as required by `Borrow<str>`.
+  /// [`StrHasher`] uses the cached hash instead of the contents.
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    let bytes = self.as_bytes();
+    CACHED_HASH.with(|cached| cached.set((bytes.as_ptr(), bytes.len(), self.hash_value())));
+    self.as_str().hash(state);
+    CACHED_HASH.with(|cached| cached.set((std::ptr::null(), 0, 0)));
+  }
+}
+
+thread_local! {
+  /// The bytes of the [`Str`] which is being hashed, and their cached hash.
+  static CACHED_HASH: Cell<(*const u8, usize, u64)> =
+    const { Cell::new((std::ptr::null(), 0, 0)) };
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+  static STATE: OnceLock<RandomState> = OnceLock::new();
+  let mut hasher = STATE.get_or_init(RandomState::new).build_hasher();
+  hasher.write(bytes);
+  match hasher.finish() {
+    // `0` marks the hash of a `Str` as not computed yet
+    0 => 1,
+    hash => hash,
+  }
+}
+
+/// A hasher for maps keyed by strings, which uses the hash cached in each
+/// [`Str`] instead of hashing its contents again.
+///
+/// The result is the same as hashing the contents, so maps keyed by
+/// `Ptr<Str>` can still be queried by `&str`. Any other type of key is not
+/// hashed properly.
+#[derive(Default)]
+pub struct StrHasher {
+  hash: u64,
+}
+
+impl Hasher for StrHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    // a `str` is hashed as its bytes, followed by `write_u8(0xff)`
+    let (ptr, len, hash) = CACHED_HASH.with(Cell::get);
+    self.hash = if ptr == bytes.as_ptr() && len == bytes.len() {
+      hash
+    } else {
+      hash_bytes(bytes)
+    };
+  }
+
+  fn write_u8(&mut self, _: u8) {}
+
+  fn finish(&self) -> u64 {
+    self.hash
+  }
+}
+
+pub type BuildStrHasher = BuildHasherDefault<StrHasher>;
+
+/// A map keyed by strings, see [`StrHasher`].
+pub type StrMap<V> = IndexMap<Ptr<Str>, V, BuildStrHasher>;
+
+/// A set of strings, see [`StrHasher`].
+pub type StrSet = IndexSet<Ptr<Str>, BuildStrHasher>;
+
 impl Display for Str {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     Display::fmt(&self.data, f)
@@ -260,3 +514,54 @@ impl<'a> PartialEq<&'a str> for Ptr<Str> {
     self.as_str() == *other
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hash(v: &(impl Hash + ?Sized)) -> u64 {
+    BuildStrHasher::default().hash_one(v)
+  }
+
+  #[test]
+  fn cached_hash_matches_str() {
+    let v = Str::owned("some_field");
+    assert_eq!(hash(&v), hash("some_field"));
+    // the second time, the cached hash is used
+    assert_eq!(hash(&v), hash("some_field"));
+    assert_ne!(hash(&v), hash("other_field"));
+  }
+
+  #[test]
+  fn hash_matches_str_under_any_hasher() {
+    let state = std::collections::hash_map::RandomState::new();
+    let v = Str::owned("some_field");
+    v.hash_value();
+    assert_eq!(state.hash_one(&v), state.hash_one("some_field"));
+
+    let mut map = std::collections::HashMap::new();
+    map.insert(unsafe { Ptr::alloc_raw(Str::owned("x")) }, 1);
+    assert_eq!(map.get("x"), Some(&1));
+  }
+
+  #[test]
+  fn eq_with_cached_hash() {
+    let (a, b, c) = (Str::owned("a"), Str::owned("a"), Str::owned("b"));
+    assert!(a == b);
+    a.hash_value();
+    assert!(a == b);
+    b.hash_value();
+    c.hash_value();
+    assert!(a == b);
+    assert!(a != c);
+  }
+
+  #[test]
+  fn lookup_by_str() {
+    let mut map = StrMap::default();
+    let key = unsafe { Ptr::alloc_raw(Str::owned("x")) };
+    map.insert(key, 1);
+    assert_eq!(map.get("x"), Some(&1));
+    assert_eq!(map.get("y"), None);
+  }
+}
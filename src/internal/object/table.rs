@@ -2,17 +2,18 @@ use std::cell::RefCell;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
-use indexmap::{Equivalent, IndexMap};
+use indexmap::Equivalent;
 
 use super::ptr::Ptr;
+use super::string::StrMap;
 use super::{Object, Str};
 use crate::internal::error::Result;
-use crate::internal::value::Value;
+use crate::internal::value::{cmp, Value};
 use crate::public::Scope;
 
 #[derive(Default)]
 pub struct Table {
-  data: RefCell<IndexMap<Ptr<Str>, Value>>,
+  data: RefCell<StrMap<Value>>,
 }
 
 impl Table {
@@ -22,7 +23,7 @@ impl Table {
 
   pub fn with_capacity(n: usize) -> Self {
     Self {
-      data: RefCell::new(IndexMap::with_capacity(n)),
+      data: RefCell::new(StrMap::with_capacity_and_hasher(n, Default::default())),
     }
   }
 
@@ -52,6 +53,31 @@ impl Table {
     }
   }
 
+  /// The index of the entry with `key`.
+  pub fn index_of<K: Equivalent<Ptr<Str>> + ?Sized + Hash>(&self, key: &K) -> Option<usize> {
+    self.data.borrow().get_index_of(key)
+  }
+
+  /// The value at `index`, if the key at `index` is `key`.
+  pub fn get_index_with_key(&self, index: usize, key: &Ptr<Str>) -> Option<Value> {
+    let data = self.data.borrow();
+    let (k, v) = data.get_index(index)?;
+    (k.ptr_eq(key) || k == key).then(|| v.clone())
+  }
+
+  /// Set the value at `index` to `value`, if the key at `index` is `key`.
+  /// Returns `false` if it isn't.
+  pub fn set_index_with_key(&self, index: usize, key: &Ptr<Str>, value: Value) -> bool {
+    let mut data = self.data.borrow_mut();
+    match data.get_index_mut(index) {
+      Some((k, slot)) if k.ptr_eq(key) || k == key => {
+        *slot = value;
+        true
+      }
+      _ => false,
+    }
+  }
+
   pub fn get_index(&self, index: usize) -> Option<Value> {
     self
       .data
@@ -204,6 +230,21 @@ impl Object for Table {
     this.insert(key, value);
     Ok(())
   }
+
+  fn eq(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    if this.len() != other.len() {
+      return Ok(false);
+    }
+    for (key, value) in this.entries() {
+      let Some(other) = other.get(&key) else {
+        return Ok(false);
+      };
+      if !cmp::equals(scope.clone(), value, other)? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
 }
 
 declare_object_type!(Table);
@@ -0,0 +1,230 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+use std::time::{Duration as StdDuration, SystemTime, UNIX_EPOCH};
+
+use super::builtin::BuiltinMethod;
+use super::{Object, Ptr, Str};
+use crate::internal::error::Result;
+use crate::internal::value::Value;
+use crate::public;
+use crate::public::{Scope, Unbind};
+
+/// A span of time, the script-side counterpart of `std::time::Duration`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration(pub StdDuration);
+
+fn duration_secs(this: Ptr<Duration>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::float(this.0.as_secs_f64()))
+}
+
+fn duration_millis(this: Ptr<Duration>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::float(this.0.as_secs_f64() * 1000.0))
+}
+
+impl Object for Duration {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Duration"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "secs" => builtin_method!(duration_secs),
+      "millis" => builtin_method!(duration_millis),
+      _ => fail!("`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
+  fn add(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let Some(value) = this.0.checked_add(other.0) else {
+      fail!("duration overflow in `{this} + {other}`");
+    };
+    Ok(Value::object(scope.alloc(Duration(value))))
+  }
+
+  fn subtract(scope: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Value> {
+    let Some(value) = this.0.checked_sub(other.0) else {
+      fail!("duration underflow in `{this} - {other}`");
+    };
+    Ok(Value::object(scope.alloc(Duration(value))))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.0 == other.0)
+  }
+
+  fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    Ok(this.0.cmp(&other.0))
+  }
+}
+
+declare_object_type!(Duration);
+
+impl Display for Duration {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    Debug::fmt(&self.0, f)
+  }
+}
+
+impl Debug for Duration {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Duration").field(&self.0).finish()
+  }
+}
+
+/// A point in time, the script-side counterpart of `std::time::SystemTime`.
+///
+/// Stored as the offset from the unix epoch, which may be negative for
+/// times before it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+  before_epoch: bool,
+  offset: StdDuration,
+}
+
+impl Timestamp {
+  pub fn from_system_time(time: SystemTime) -> Self {
+    match time.duration_since(UNIX_EPOCH) {
+      Ok(offset) => Self {
+        before_epoch: false,
+        offset,
+      },
+      Err(e) => Self {
+        before_epoch: true,
+        offset: e.duration(),
+      },
+    }
+  }
+
+  pub fn to_system_time(self) -> Option<SystemTime> {
+    if self.before_epoch {
+      UNIX_EPOCH.checked_sub(self.offset)
+    } else {
+      UNIX_EPOCH.checked_add(self.offset)
+    }
+  }
+
+  #[cfg(feature = "chrono")]
+  pub fn from_chrono(time: chrono::DateTime<chrono::Utc>) -> Self {
+    let offset = time.signed_duration_since(chrono::DateTime::UNIX_EPOCH);
+    Self {
+      before_epoch: offset < chrono::TimeDelta::zero(),
+      // `TimeDelta` is at most `i64::MAX` milliseconds long, which fits
+      offset: offset.abs().to_std().unwrap(),
+    }
+  }
+
+  #[cfg(feature = "chrono")]
+  pub fn to_chrono(self) -> Option<chrono::DateTime<chrono::Utc>> {
+    let offset = chrono::TimeDelta::from_std(self.offset).ok()?;
+    if self.before_epoch {
+      chrono::DateTime::UNIX_EPOCH.checked_sub_signed(offset)
+    } else {
+      chrono::DateTime::UNIX_EPOCH.checked_add_signed(offset)
+    }
+  }
+
+  /// Seconds since the unix epoch.
+  pub fn unix(&self) -> f64 {
+    let secs = self.offset.as_secs_f64();
+    if self.before_epoch {
+      -secs
+    } else {
+      secs
+    }
+  }
+
+  fn signed_nanos(&self) -> i128 {
+    let nanos = self.offset.as_nanos() as i128;
+    if self.before_epoch {
+      -nanos
+    } else {
+      nanos
+    }
+  }
+}
+
+fn timestamp_unix(this: Ptr<Timestamp>, _: Scope<'_>) -> Result<Value> {
+  Ok(Value::float(this.unix()))
+}
+
+fn timestamp_since(this: Ptr<Timestamp>, scope: Scope<'_>) -> Result<Value> {
+  let other = scope.param::<public::Value>(0)?.unbind();
+  let Some(other) = other.clone().to_object::<Timestamp>() else {
+    fail!("`{other}` is not a timestamp");
+  };
+  let nanos = this.signed_nanos() - other.signed_nanos();
+  if nanos < 0 {
+    fail!("`{other}` is later than `{this}`");
+  }
+  let duration = StdDuration::new(
+    (nanos / 1_000_000_000) as u64,
+    (nanos % 1_000_000_000) as u32,
+  );
+  Ok(Value::object(scope.alloc(Duration(duration))))
+}
+
+impl Object for Timestamp {
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Timestamp"
+  }
+
+  default_instance_of!();
+
+  fn named_field(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Value> {
+    Ok(
+      this
+        .named_field_opt(scope, name.clone())?
+        .ok_or_else(|| error!("`{this}` has no field `{name}`"))?,
+    )
+  }
+
+  fn named_field_opt(scope: Scope<'_>, this: Ptr<Self>, name: Ptr<Str>) -> Result<Option<Value>> {
+    let method = match name.as_str() {
+      "unix" => builtin_method!(timestamp_unix),
+      "since" => builtin_method!(timestamp_since),
+      _ => fail!("`{this}` has no field `{name}`"),
+    };
+
+    Ok(Some(Value::object(unsafe {
+      scope.alloc(BuiltinMethod::new(Value::object(this), method))
+    })))
+  }
+
+  fn eq(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<bool> {
+    Ok(this.signed_nanos() == other.signed_nanos())
+  }
+
+  fn cmp(_: Scope<'_>, this: Ptr<Self>, other: Ptr<Self>) -> Result<Ordering> {
+    Ok(this.signed_nanos().cmp(&other.signed_nanos()))
+  }
+}
+
+declare_object_type!(Timestamp);
+
+impl Display for Timestamp {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<timestamp {}>", self.unix())
+  }
+}
+
+impl Debug for Timestamp {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Timestamp")
+      .field("unix", &self.unix())
+      .finish()
+  }
+}
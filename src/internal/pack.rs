@@ -0,0 +1,597 @@
+//! The `.hebipack` format, which stores the modules of a bundle in a single
+//! file, see [`Bundle::to_bytes`][crate::Bundle::to_bytes].
+//!
+//! A pack is:
+//!
+//! - the magic bytes `HEBIPACK`
+//! - the version of the format, see [`FORMAT_VERSION`]
+//! - the version of hebi which wrote it, which has to be the version of
+//!   the VM which loads it, because the bytecode may change in any release
+//! - the capabilities which the code in the pack needs from the VM, so that
+//!   loading fails before any of the code is read if the VM lacks one
+//! - the manifest, which is the number of modules, followed by the name of
+//!   each module and whether its source is included
+//! - for each module in the order of the manifest, its source if it is
+//!   included, and its compiled code
+//!
+//! The first three stay the same in every version of the format, so that
+//! a VM can tell which version of hebi wrote a pack which it cannot load.
+//!
+//! Integers are LEB128 varints, zigzag-encoded if they may be negative.
+//! Strings and byte buffers are prefixed with their length. The compiled
+//! code of a module is its tree of descriptors, with every function and
+//! class nested where it is referenced from a constant pool.
+
+use super::bytecode::opcode as op;
+use super::bytecode::spans::{unzigzag, write_varint, zigzag, SpanMap};
+use super::error::Result;
+use super::object::function::{Param, ParamDefault, Params, Signature, Upvalue};
+use super::object::module::ModuleDescriptor;
+use super::object::string::StrSet;
+use super::object::{ClassDescriptor, FunctionDescriptor, Ptr, Str, Table};
+use super::value::constant::Constant;
+use super::value::Value;
+use super::vm::global::{Capability, Construct, Global};
+use crate::span::{Source, Span};
+use crate::util::JoinIter;
+
+const MAGIC: &[u8] = b"HEBIPACK";
+/// The version of the format, which has to be bumped whenever the layout of
+/// a pack changes. A pack can only be loaded by a VM which uses the same
+/// version, and which is the same version of hebi.
+pub const FORMAT_VERSION: u32 = 1;
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Constructs by their tag.
+const CONSTRUCTS: [Construct; 8] = [
+  Construct::Import,
+  Construct::Class,
+  Construct::Function,
+  Construct::For,
+  Construct::While,
+  Construct::Loop,
+  Construct::Try,
+  Construct::With,
+];
+
+type PackedModule = (Ptr<Str>, Option<Source>, Ptr<ModuleDescriptor>);
+
+/// Write `modules` into a pack, with their sources if `include_sources` is
+/// set.
+pub fn write(modules: &[PackedModule], include_sources: bool) -> Result<Vec<u8>> {
+  let mut w = Writer {
+    buf: MAGIC.to_vec(),
+  };
+  w.uint(FORMAT_VERSION as usize);
+  w.str(VERSION);
+  let capabilities = capabilities(modules);
+  w.uint(capabilities.len());
+  for capability in capabilities {
+    match capability {
+      Capability::Construct(construct) => {
+        w.byte(tag::CONSTRUCT);
+        w.construct(construct);
+      }
+      Capability::Decimal => w.byte(tag::DECIMAL),
+    }
+  }
+  w.uint(modules.len());
+  for (name, source, _) in modules {
+    w.str(name.as_str());
+    w.bool(include_sources && source.is_some());
+  }
+  for (_, source, module) in modules {
+    if let (true, Some(source)) = (include_sources, source) {
+      w.str(&source.name);
+      w.str(&source.text);
+    }
+    w.module(module)?;
+  }
+  Ok(w.buf)
+}
+
+/// Read the modules of the pack `data` into the VM of `global`.
+pub fn read(global: &Global, data: &[u8]) -> Result<Vec<PackedModule>> {
+  let Some(data) = data.strip_prefix(MAGIC) else {
+    fail!("not a hebi pack");
+  };
+  let mut r = Reader {
+    global: global.clone(),
+    data,
+  };
+  let format = r.u32()?;
+  let version = r.str()?;
+  if format > FORMAT_VERSION {
+    fail!(
+      "the pack was written by hebi {version} in format {format}, but this VM only supports format {FORMAT_VERSION}, so hebi has to be updated to load it"
+    );
+  }
+  if format < FORMAT_VERSION {
+    fail!(
+      "the pack was written by hebi {version} in format {format}, which this VM no longer supports, so it has to be compiled again"
+    );
+  }
+  if version != VERSION {
+    fail!(
+      "the pack was written by hebi {version}, which is not this version ({VERSION}), so it has to be compiled again"
+    );
+  }
+  let mut missing = Vec::new();
+  for _ in 0..r.uint()? {
+    let capability = match r.byte()? {
+      tag::CONSTRUCT => Capability::Construct(r.construct()?),
+      tag::DECIMAL => Capability::Decimal,
+      v => fail!("the pack is corrupted: unknown capability {v}"),
+    };
+    if !global.supports(capability) {
+      missing.push(capability);
+    }
+  }
+  if !missing.is_empty() {
+    fail!(
+      "the pack needs {}, which this VM does not allow",
+      missing.iter().join(", ")
+    );
+  }
+  let mut manifest = Vec::new();
+  for _ in 0..r.uint()? {
+    manifest.push((r.str()?.to_string(), r.bool()?));
+  }
+  let mut modules = Vec::with_capacity(manifest.len());
+  for (name, has_source) in manifest {
+    let source = match has_source {
+      true => Some(Source::new(r.str()?, r.str()?)),
+      false => None,
+    };
+    let module = r.module()?;
+    if module.name.as_str() != name {
+      fail!(
+        "the pack is corrupted: module `{}` is listed as `{name}`",
+        module.name
+      );
+    }
+    modules.push((module.name.clone(), source, module));
+  }
+  if !r.data.is_empty() {
+    fail!("the pack is corrupted: unexpected data after the last module");
+  }
+  Ok(modules)
+}
+
+/// The capabilities needed by the code of `modules`, see
+/// [`Bundle::capabilities`][crate::Bundle::capabilities].
+pub fn capabilities(modules: &[PackedModule]) -> Vec<Capability> {
+  let mut capabilities = Vec::new();
+  for (_, _, module) in modules {
+    for construct in module.constructs.iter() {
+      let capability = Capability::Construct(*construct);
+      if !capabilities.contains(&capability) {
+        capabilities.push(capability);
+      }
+    }
+  }
+  #[cfg(feature = "decimal")]
+  {
+    let mut decimal = false;
+    for (_, _, module) in modules {
+      module.root.visit(None, &mut |function, _| {
+        decimal |= (function.constants().iter()).any(|c| matches!(c, Constant::Decimal(_)));
+      });
+    }
+    if decimal {
+      capabilities.push(Capability::Decimal);
+    }
+  }
+  capabilities
+}
+
+mod tag {
+  pub const NONE: u8 = 0;
+  pub const BOOL: u8 = 1;
+  pub const INT: u8 = 2;
+  pub const FLOAT: u8 = 3;
+  pub const STRING: u8 = 4;
+  pub const FUNCTION: u8 = 5;
+  pub const CLASS: u8 = 6;
+  pub const OFFSET: u8 = 7;
+  pub const RESERVED: u8 = 8;
+  pub const DECIMAL: u8 = 9;
+  pub const CONSTRUCT: u8 = 10;
+
+  pub const REQUIRED: u8 = 0;
+  pub const CONSTANT: u8 = 1;
+  pub const EXPR: u8 = 2;
+
+  pub const REGISTER: u8 = 0;
+  pub const UPVALUE: u8 = 1;
+}
+
+struct Writer {
+  buf: Vec<u8>,
+}
+
+impl Writer {
+  fn byte(&mut self, v: u8) {
+    self.buf.push(v);
+  }
+
+  fn bool(&mut self, v: bool) {
+    self.byte(v as u8);
+  }
+
+  fn uint(&mut self, v: usize) {
+    write_varint(&mut self.buf, v as u64);
+  }
+
+  fn bytes(&mut self, v: &[u8]) {
+    self.uint(v.len());
+    self.buf.extend_from_slice(v);
+  }
+
+  fn str(&mut self, v: &str) {
+    self.bytes(v.as_bytes());
+  }
+
+  fn construct(&mut self, v: Construct) {
+    let tag = CONSTRUCTS.iter().position(|c| *c == v).unwrap();
+    self.byte(tag as u8);
+  }
+
+  fn module(&mut self, v: &ModuleDescriptor) -> Result<()> {
+    self.str(v.name.as_str());
+    self.uint(v.module_vars.len());
+    for var in v.module_vars.iter() {
+      self.str(var.as_str());
+    }
+    self.uint(v.constructs.len());
+    for construct in v.constructs.iter() {
+      self.construct(*construct);
+    }
+    self.function(&v.root)
+  }
+
+  fn function(&mut self, v: &FunctionDescriptor) -> Result<()> {
+    self.str(v.name.as_str());
+    self.bool(v.is_generator);
+    self.bool(v.params.has_self);
+    self.uint(v.params.min as usize);
+    self.uint(v.params.max as usize);
+    self.uint(v.signature.params.len());
+    for param in v.signature.params.iter() {
+      self.str(param.name.as_str());
+      match &param.default {
+        ParamDefault::None => self.byte(tag::REQUIRED),
+        ParamDefault::Constant(value) => {
+          self.byte(tag::CONSTANT);
+          self.value(value)?;
+        }
+        ParamDefault::Expr => self.byte(tag::EXPR),
+      }
+    }
+    let upvalues = v.upvalues.borrow();
+    self.uint(upvalues.len());
+    for upvalue in upvalues.iter() {
+      match upvalue {
+        Upvalue::Register(r) => {
+          self.byte(tag::REGISTER);
+          self.uint(r.0 as usize);
+        }
+        Upvalue::Upvalue(u) => {
+          self.byte(tag::UPVALUE);
+          self.uint(u.0 as usize);
+        }
+      }
+    }
+    self.uint(v.frame_size);
+    self.bytes(unsafe { v.instructions.as_ref() });
+    self.uint(v.constants().len());
+    for constant in v.constants() {
+      match constant {
+        Constant::Reserved => self.byte(tag::RESERVED),
+        Constant::String(v) => {
+          self.byte(tag::STRING);
+          self.str(v.as_str());
+        }
+        Constant::Function(v) => {
+          self.byte(tag::FUNCTION);
+          self.function(v)?;
+        }
+        Constant::Class(v) => {
+          self.byte(tag::CLASS);
+          self.class(v)?;
+        }
+        Constant::Offset(v) => {
+          self.byte(tag::OFFSET);
+          self.uint(v.0 as usize);
+        }
+        Constant::Float(v) => {
+          self.byte(tag::FLOAT);
+          self.buf.extend_from_slice(&v.value().to_le_bytes());
+        }
+        #[cfg(feature = "decimal")]
+        Constant::Decimal(v) => {
+          self.byte(tag::DECIMAL);
+          self.buf.extend_from_slice(&v.0.serialize());
+        }
+      }
+    }
+    self.bytes(v.spans.as_bytes());
+    self.uint(v.span.start);
+    self.uint(v.span.end);
+    Ok(())
+  }
+
+  fn class(&mut self, v: &ClassDescriptor) -> Result<()> {
+    self.str(v.name.as_str());
+    match &v.init {
+      Some(init) => {
+        self.bool(true);
+        self.function(init)?;
+      }
+      None => self.bool(false),
+    }
+    self.uint(v.methods.len());
+    for (name, method) in v.methods.iter() {
+      self.str(name.as_str());
+      self.function(method)?;
+    }
+    self.uint(v.fields.len());
+    for (name, value) in v.fields.entries() {
+      self.str(name.as_str());
+      self.value(&value)?;
+    }
+    Ok(())
+  }
+
+  /// Only the values which can be the default value of a parameter or a
+  /// field can be written.
+  fn value(&mut self, v: &Value) -> Result<()> {
+    if v.is_none() {
+      self.byte(tag::NONE);
+    } else if let Some(v) = v.clone().to_bool() {
+      self.byte(tag::BOOL);
+      self.bool(v);
+    } else if let Some(v) = v.clone().to_int() {
+      self.byte(tag::INT);
+      write_varint(&mut self.buf, zigzag(v as i64));
+    } else if let Some(v) = v.clone().to_float() {
+      self.byte(tag::FLOAT);
+      self.buf.extend_from_slice(&v.to_le_bytes());
+    } else if let Some(v) = v.clone().to_object::<Str>() {
+      self.byte(tag::STRING);
+      self.str(v.as_str());
+    } else {
+      fail!("cannot write `{v}` into a pack");
+    }
+    Ok(())
+  }
+}
+
+struct Reader<'a> {
+  global: Global,
+  data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+    if self.data.len() < n {
+      fail!("the pack is truncated");
+    }
+    let (v, rest) = self.data.split_at(n);
+    self.data = rest;
+    Ok(v)
+  }
+
+  fn byte(&mut self) -> Result<u8> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn bool(&mut self) -> Result<bool> {
+    match self.byte()? {
+      0 => Ok(false),
+      1 => Ok(true),
+      v => fail!("the pack is corrupted: expected a bool, got {v}"),
+    }
+  }
+
+  fn varint(&mut self) -> Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+      let byte = self.byte()?;
+      if shift >= 64 {
+        fail!("the pack is corrupted: integer is too large");
+      }
+      value |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+      shift += 7;
+    }
+  }
+
+  fn uint(&mut self) -> Result<usize> {
+    self.varint().map(|v| v as usize)
+  }
+
+  fn u16(&mut self) -> Result<u16> {
+    match u16::try_from(self.varint()?) {
+      Ok(v) => Ok(v),
+      Err(_) => fail!("the pack is corrupted: integer is too large"),
+    }
+  }
+
+  fn u32(&mut self) -> Result<u32> {
+    match u32::try_from(self.varint()?) {
+      Ok(v) => Ok(v),
+      Err(_) => fail!("the pack is corrupted: integer is too large"),
+    }
+  }
+
+  fn f64(&mut self) -> Result<f64> {
+    let bytes = self.take(8)?;
+    Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+  }
+
+  fn bytes(&mut self) -> Result<&'a [u8]> {
+    let len = self.uint()?;
+    self.take(len)
+  }
+
+  fn str(&mut self) -> Result<&'a str> {
+    match std::str::from_utf8(self.bytes()?) {
+      Ok(v) => Ok(v),
+      Err(_) => fail!("the pack is corrupted: string is not valid UTF-8"),
+    }
+  }
+
+  fn intern(&mut self) -> Result<Ptr<Str>> {
+    let v = self.str()?;
+    Ok(self.global.intern(v.to_string()))
+  }
+
+  fn construct(&mut self) -> Result<Construct> {
+    match CONSTRUCTS.get(self.byte()? as usize) {
+      Some(construct) => Ok(*construct),
+      None => fail!("the pack is corrupted: unknown construct"),
+    }
+  }
+
+  fn module(&mut self) -> Result<Ptr<ModuleDescriptor>> {
+    let name = self.intern()?;
+    let len = self.uint()?;
+    let mut module_vars = StrSet::with_capacity_and_hasher(len.min(1024), Default::default());
+    for _ in 0..len {
+      module_vars.insert(self.intern()?);
+    }
+    let mut constructs = Vec::new();
+    for _ in 0..self.uint()? {
+      constructs.push(self.construct()?);
+    }
+    let root = self.function()?;
+    Ok(self.global.alloc(ModuleDescriptor {
+      name,
+      root,
+      module_vars,
+      constructs,
+    }))
+  }
+
+  fn function(&mut self) -> Result<Ptr<FunctionDescriptor>> {
+    let name = self.intern()?;
+    let is_generator = self.bool()?;
+    let params = Params {
+      has_self: self.bool()?,
+      min: self.u16()?,
+      max: self.u16()?,
+    };
+    let mut signature = Vec::new();
+    for _ in 0..self.uint()? {
+      let name = self.intern()?;
+      let default = match self.byte()? {
+        tag::REQUIRED => ParamDefault::None,
+        tag::CONSTANT => ParamDefault::Constant(self.value()?),
+        tag::EXPR => ParamDefault::Expr,
+        v => fail!("the pack is corrupted: unknown parameter default {v}"),
+      };
+      signature.push(Param { name, default });
+    }
+    let mut upvalues = Vec::new();
+    for _ in 0..self.uint()? {
+      upvalues.push(match self.byte()? {
+        tag::REGISTER => Upvalue::Register(op::Register(self.u32()?)),
+        tag::UPVALUE => Upvalue::Upvalue(op::Upvalue(self.u32()?)),
+        v => fail!("the pack is corrupted: unknown upvalue {v}"),
+      });
+    }
+    let frame_size = self.uint()?;
+    let instructions = self.bytes()?.to_vec();
+    let mut constants = Vec::new();
+    for _ in 0..self.uint()? {
+      constants.push(match self.byte()? {
+        tag::RESERVED => Constant::Reserved,
+        tag::STRING => Constant::String(self.intern()?),
+        tag::FUNCTION => Constant::Function(self.function()?),
+        tag::CLASS => Constant::Class(self.class()?),
+        tag::OFFSET => Constant::Offset(op::Offset(self.u32()?)),
+        tag::FLOAT => match self.f64()? {
+          v if v.is_nan() => fail!("the pack is corrupted: float constant is NaN"),
+          v => Constant::Float(v.into()),
+        },
+        tag::DECIMAL => self.decimal()?,
+        v => fail!("the pack is corrupted: unknown constant {v}"),
+      });
+    }
+    let spans = SpanMap::from_bytes(self.bytes()?.to_vec());
+    let span = Span {
+      start: self.uint()?,
+      end: self.uint()?,
+    };
+    Ok(self.global.alloc(FunctionDescriptor::new(
+      name,
+      is_generator,
+      params,
+      Signature { params: signature },
+      upvalues,
+      frame_size,
+      instructions,
+      constants,
+      spans,
+      span,
+    )))
+  }
+
+  #[cfg(feature = "decimal")]
+  fn decimal(&mut self) -> Result<Constant> {
+    let bytes = self.take(16)?.try_into().unwrap();
+    let v = super::object::decimal::Decimal(rust_decimal::Decimal::deserialize(bytes));
+    Ok(Constant::Decimal(self.global.alloc(v)))
+  }
+
+  #[cfg(not(feature = "decimal"))]
+  fn decimal(&mut self) -> Result<Constant> {
+    fail!("the pack uses decimals, which require the `decimal` feature")
+  }
+
+  fn class(&mut self) -> Result<Ptr<ClassDescriptor>> {
+    let name = self.intern()?;
+    let init = match self.bool()? {
+      true => Some(self.function()?),
+      false => None,
+    };
+    let mut methods = Vec::new();
+    for _ in 0..self.uint()? {
+      methods.push((self.intern()?, self.function()?));
+    }
+    let len = self.uint()?;
+    let fields = Table::with_capacity(len.min(1024));
+    for _ in 0..len {
+      let name = self.intern()?;
+      let value = self.value()?;
+      fields.insert(name, value);
+    }
+    Ok(self.global.alloc(ClassDescriptor {
+      name,
+      init,
+      methods: methods.into_iter().collect(),
+      fields: self.global.alloc(fields),
+    }))
+  }
+
+  fn value(&mut self) -> Result<Value> {
+    Ok(match self.byte()? {
+      tag::NONE => Value::none(),
+      tag::BOOL => Value::bool(self.bool()?),
+      tag::INT => {
+        let v = unzigzag(self.varint()?);
+        match i32::try_from(v) {
+          Ok(v) => Value::int(v),
+          Err(_) => fail!("the pack is corrupted: integer is too large"),
+        }
+      }
+      tag::FLOAT => Value::float(self.f64()?),
+      tag::STRING => Value::object(self.intern()?),
+      v => fail!("the pack is corrupted: unknown value {v}"),
+    })
+  }
+}
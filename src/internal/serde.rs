@@ -56,7 +56,7 @@ impl Serialize for Table {
   {
     let mut map = serializer.serialize_map(Some(self.len()))?;
     for (key, value) in self.entries() {
-      map.serialize_entry(key.as_ref(), &value)?;
+      map.serialize_entry(key.as_str(), &value)?;
     }
     map.end()
   }
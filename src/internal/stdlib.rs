@@ -0,0 +1,14 @@
+//! Native modules which are registered in every VM.
+
+pub mod crypto;
+pub mod functools;
+pub mod random;
+pub mod sys;
+
+use super::vm::Vm;
+
+pub fn register_std_modules(vm: &mut Vm) {
+  vm.register(&random::module());
+  vm.register(&crypto::module());
+  vm.register(&functools::module());
+}
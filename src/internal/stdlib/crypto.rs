@@ -0,0 +1,381 @@
+use crate::internal::error::Result;
+use crate::public::{IntoValue, List, NativeModule, Scope, Str, Value};
+
+/// Hebi has no dedicated byte string type, so byte inputs may be given
+/// either as a string (hashed as its UTF-8 encoding) or as a list of
+/// integers in `0..=255`.
+fn to_bytes<'cx>(scope: &Scope<'cx>, value: Value<'cx>) -> Result<Vec<u8>> {
+  if let Some(str) = value.as_object::<Str<'cx>>(scope.global()) {
+    return Ok(str.as_str().as_bytes().to_vec());
+  }
+  if let Some(list) = value.as_object::<List<'cx>>(scope.global()) {
+    let mut bytes = Vec::with_capacity(list.len());
+    for item in list.iter() {
+      match item.as_int() {
+        Some(byte @ 0..=255) => bytes.push(byte as u8),
+        _ => fail!("`{item}` is not a byte"),
+      }
+    }
+    return Ok(bytes);
+  }
+  fail!("expected a string or a list of bytes, got `{value}`")
+}
+
+fn into_string(bytes: Vec<u8>, function: &str) -> Result<String> {
+  String::from_utf8(bytes).map_err(|e| {
+    error!("decoded data is not valid utf-8: {e}, use `{function}_bytes` to get the raw bytes")
+      .into()
+  })
+}
+
+fn into_list<'cx>(scope: &Scope<'cx>, bytes: Vec<u8>) -> Result<List<'cx>> {
+  let list = scope.new_list(bytes.len());
+  for byte in bytes {
+    list.push((byte as i32).into_value(scope.global())?);
+  }
+  Ok(list)
+}
+
+/// UUIDs are often used as tokens which must not be guessable, so they are
+/// generated from the operating system's source of randomness, and not from
+/// the `random` module's generator, which scripts may seed.
+fn uuid4(scope: Scope<'_>) -> Result<String> {
+  scope.params::<()>()?;
+  let mut bytes = [0u8; 16];
+  getrandom::getrandom(&mut bytes)
+    .map_err(|e| error!("failed to get random bytes from the operating system: {e}"))?;
+  // version 4, variant 1
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex = hex_encode_bytes(&bytes);
+  Ok(format!(
+    "{}-{}-{}-{}-{}",
+    &hex[0..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..32]
+  ))
+}
+
+fn sha256<'cx>(scope: Scope<'cx>) -> Result<String> {
+  let data = to_bytes(&scope, scope.param::<Value<'cx>>(0)?)?;
+  Ok(hex_encode_bytes(&sha256_digest(&data)))
+}
+
+fn md5<'cx>(scope: Scope<'cx>) -> Result<String> {
+  let data = to_bytes(&scope, scope.param::<Value<'cx>>(0)?)?;
+  Ok(hex_encode_bytes(&md5_digest(&data)))
+}
+
+fn hex_encode<'cx>(scope: Scope<'cx>) -> Result<String> {
+  let data = to_bytes(&scope, scope.param::<Value<'cx>>(0)?)?;
+  Ok(hex_encode_bytes(&data))
+}
+
+fn hex_decode(scope: Scope<'_>) -> Result<String> {
+  let data = scope.param::<String>(0)?;
+  into_string(hex_decode_bytes(&data)?, "hex_decode")
+}
+
+fn hex_decode_to_list<'cx>(scope: Scope<'cx>) -> Result<List<'cx>> {
+  let data = scope.param::<String>(0)?;
+  into_list(&scope, hex_decode_bytes(&data)?)
+}
+
+fn base64_encode<'cx>(scope: Scope<'cx>) -> Result<String> {
+  let data = to_bytes(&scope, scope.param::<Value<'cx>>(0)?)?;
+  Ok(base64_encode_bytes(&data))
+}
+
+fn base64_decode(scope: Scope<'_>) -> Result<String> {
+  let data = scope.param::<String>(0)?;
+  into_string(base64_decode_bytes(&data)?, "base64_decode")
+}
+
+fn base64_decode_to_list<'cx>(scope: Scope<'cx>) -> Result<List<'cx>> {
+  let data = scope.param::<String>(0)?;
+  into_list(&scope, base64_decode_bytes(&data)?)
+}
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("crypto")
+    .function("uuid4", uuid4)
+    .function("sha256", sha256)
+    .function("md5", md5)
+    .function("hex_encode", hex_encode)
+    .function("hex_decode", hex_decode)
+    .function("hex_decode_bytes", hex_decode_to_list)
+    .function("base64_encode", base64_encode)
+    .function("base64_decode", base64_decode)
+    .function("base64_decode_bytes", base64_decode_to_list)
+    .finish()
+}
+
+pub fn hex_encode_bytes(data: &[u8]) -> String {
+  const DIGITS: &[u8; 16] = b"0123456789abcdef";
+  let mut out = String::with_capacity(data.len() * 2);
+  for byte in data {
+    out.push(DIGITS[(byte >> 4) as usize] as char);
+    out.push(DIGITS[(byte & 0xf) as usize] as char);
+  }
+  out
+}
+
+pub fn hex_decode_bytes(data: &str) -> Result<Vec<u8>> {
+  fn digit(c: u8) -> Result<u8> {
+    match c {
+      b'0'..=b'9' => Ok(c - b'0'),
+      b'a'..=b'f' => Ok(c - b'a' + 10),
+      b'A'..=b'F' => Ok(c - b'A' + 10),
+      _ => fail!("`{}` is not a hex digit", c as char),
+    }
+  }
+
+  let data = data.as_bytes();
+  if !data.len().is_multiple_of(2) {
+    fail!("hex string has odd length {}", data.len());
+  }
+  data
+    .chunks_exact(2)
+    .map(|pair| Ok((digit(pair[0])? << 4) | digit(pair[1])?))
+    .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode_bytes(data: &[u8]) -> String {
+  let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+  for chunk in data.chunks(3) {
+    let b = [
+      chunk[0],
+      chunk.get(1).copied().unwrap_or(0),
+      chunk.get(2).copied().unwrap_or(0),
+    ];
+    let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+/// Decodes standard base64, which must be padded with `=` to a multiple of
+/// four characters.
+pub fn base64_decode_bytes(data: &str) -> Result<Vec<u8>> {
+  if !data.len().is_multiple_of(4) {
+    fail!(
+      "base64 string has length {}, which is not a multiple of 4",
+      data.len()
+    );
+  }
+  let unpadded = data.trim_end_matches('=');
+  let padding = data.len() - unpadded.len();
+  if padding > 2 || (padding > 0 && unpadded.len() % 4 != 4 - padding) {
+    fail!("base64 string has invalid padding");
+  }
+  let data = unpadded.as_bytes();
+  let mut out = Vec::with_capacity(data.len() * 3 / 4);
+  for chunk in data.chunks(4) {
+    let mut n = 0u32;
+    for (i, &c) in chunk.iter().enumerate() {
+      let Some(v) = BASE64_ALPHABET.iter().position(|&a| a == c) else {
+        fail!("`{}` is not a base64 character", c as char);
+      };
+      n |= (v as u32) << (18 - 6 * i);
+    }
+    let bytes = n.to_be_bytes();
+    // the bits of the last character which do not make up a whole byte
+    // must be zero, or there would be more than one encoding of the data
+    let unused = 8 * (3 - (chunk.len() - 1));
+    if n & ((1 << unused) - 1) != 0 {
+      fail!("base64 string has invalid trailing bits");
+    }
+    out.extend_from_slice(&bytes[1..chunk.len()]);
+  }
+  Ok(out)
+}
+
+pub fn sha256_digest(data: &[u8]) -> [u8; 32] {
+  const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+  ];
+  let mut h: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+  ];
+
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+  for block in message.chunks_exact(64) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+      w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+      let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+      let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+      w[i] = w[i - 16]
+        .wrapping_add(s0)
+        .wrapping_add(w[i - 7])
+        .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+    for i in 0..64 {
+      let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+      let ch = (e & f) ^ (!e & g);
+      let t1 = hh
+        .wrapping_add(s1)
+        .wrapping_add(ch)
+        .wrapping_add(K[i])
+        .wrapping_add(w[i]);
+      let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+      let maj = (a & b) ^ (a & c) ^ (b & c);
+      let t2 = s0.wrapping_add(maj);
+      hh = g;
+      g = f;
+      f = e;
+      e = d.wrapping_add(t1);
+      d = c;
+      c = b;
+      b = a;
+      a = t1.wrapping_add(t2);
+    }
+    for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+      *h = h.wrapping_add(v);
+    }
+  }
+
+  let mut out = [0u8; 32];
+  for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+    chunk.copy_from_slice(&word.to_be_bytes());
+  }
+  out
+}
+
+pub fn md5_digest(data: &[u8]) -> [u8; 16] {
+  const S: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+  ];
+  let k: [u32; 64] = std::array::from_fn(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32);
+  let mut h: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+
+  for block in message.chunks_exact(64) {
+    let mut m = [0u32; 16];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+      m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = h;
+    for i in 0..64 {
+      let (f, g) = match i / 16 {
+        0 => ((b & c) | (!b & d), i),
+        1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+        2 => (b ^ c ^ d, (3 * i + 5) % 16),
+        _ => (c ^ (b | !d), (7 * i) % 16),
+      };
+      let f = f.wrapping_add(a).wrapping_add(k[i]).wrapping_add(m[g]);
+      a = d;
+      d = c;
+      c = b;
+      b = b.wrapping_add(f.rotate_left(S[i]));
+    }
+    for (h, v) in h.iter_mut().zip([a, b, c, d]) {
+      *h = h.wrapping_add(v);
+    }
+  }
+
+  let mut out = [0u8; 16];
+  for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+    chunk.copy_from_slice(&word.to_le_bytes());
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sha256_known_vectors() {
+    assert_eq!(
+      hex_encode_bytes(&sha256_digest(b"")),
+      "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+      hex_encode_bytes(&sha256_digest(b"abc")),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+  }
+
+  #[test]
+  fn md5_known_vectors() {
+    assert_eq!(
+      hex_encode_bytes(&md5_digest(b"")),
+      "d41d8cd98f00b204e9800998ecf8427e"
+    );
+    assert_eq!(
+      hex_encode_bytes(&md5_digest(b"The quick brown fox jumps over the lazy dog")),
+      "9e107d9d372bb6826bd81d3542a419d6"
+    );
+  }
+
+  #[test]
+  fn base64_round_trip() {
+    for input in ["", "f", "fo", "foo", "foob", "fooba", "foobar"] {
+      let encoded = base64_encode_bytes(input.as_bytes());
+      assert_eq!(base64_decode_bytes(&encoded).unwrap(), input.as_bytes());
+    }
+    assert_eq!(base64_encode_bytes(b"foobar"), "Zm9vYmFy");
+    assert_eq!(base64_encode_bytes(b"fooba"), "Zm9vYmE=");
+    assert_eq!(base64_decode_bytes("/w==").unwrap(), b"\xff");
+  }
+
+  #[test]
+  fn base64_rejects_bad_padding() {
+    for input in [
+      "Zm9vYmE",
+      "Zm9vYg",
+      "Zm9vYmE==",
+      "Zm9vYg=",
+      "Zm9v====",
+      "Zg=x",
+      "Zh==",
+    ] {
+      assert!(base64_decode_bytes(input).is_err(), "{input}");
+    }
+  }
+
+  #[test]
+  fn hex_round_trip() {
+    assert_eq!(hex_encode_bytes(b"\x00\xffhi"), "00ff6869");
+    assert_eq!(hex_decode_bytes("00FF6869").unwrap(), b"\x00\xffhi");
+    assert!(hex_decode_bytes("0").is_err());
+  }
+}
@@ -0,0 +1,348 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::internal::error::Result;
+use crate::internal::object::class::ClassInstance;
+use crate::internal::object::{is_callable, Any, List, Object, Ptr, ReturnAddr, Str, Table};
+use crate::internal::value::Value as OwnedValue;
+use crate::internal::vm::thread::{AsyncFrame, CallResult};
+use crate::public::{Bind, NativeModule, Scope, Unbind, Value};
+
+/// A function which caches its results by arguments.
+///
+/// At most `max_size` results are kept, and the least recently used one is
+/// evicted to make room for a new one.
+///
+/// Every call copies the contents of list, table and instance arguments to
+/// compare them with the arguments of earlier calls, see [`KeyPart`].
+#[derive(Debug)]
+pub struct Memo {
+  function: Ptr<Any>,
+  max_size: Option<usize>,
+  cache: RefCell<Lru>,
+}
+
+impl Memo {
+  pub fn function(&self) -> &Ptr<Any> {
+    &self.function
+  }
+
+  /// A memo of `function` with the same size limit as this one, and an
+  /// empty cache.
+  pub fn with_function(&self, function: Ptr<Any>) -> Self {
+    Self {
+      function,
+      max_size: self.max_size,
+      cache: RefCell::default(),
+    }
+  }
+
+  fn get(&self, key: &Key) -> Option<OwnedValue> {
+    self.cache.borrow_mut().get(key)
+  }
+
+  fn insert(&self, key: Key, value: OwnedValue) {
+    self.cache.borrow_mut().insert(key, value, self.max_size);
+  }
+}
+
+/// Cached results, ordered from the least to the most recently used.
+///
+/// The order is a doubly linked list through the indices of the entries,
+/// so that every operation is O(1).
+#[derive(Debug, Default)]
+struct Lru {
+  entries: IndexMap<Key, Entry>,
+  first: Option<usize>,
+  last: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Entry {
+  value: OwnedValue,
+  prev: Option<usize>,
+  next: Option<usize>,
+}
+
+impl Lru {
+  fn get(&mut self, key: &Key) -> Option<OwnedValue> {
+    let index = self.entries.get_index_of(key)?;
+    self.unlink(index);
+    self.push_last(index);
+    Some(self.entries[index].value.clone())
+  }
+
+  fn insert(&mut self, key: Key, value: OwnedValue, max_size: Option<usize>) {
+    // a recursive call with the same arguments may have finished first
+    if let Some(index) = self.entries.get_index_of(&key) {
+      self.entries[index].value = value;
+      self.unlink(index);
+      self.push_last(index);
+      return;
+    }
+
+    if let (Some(max_size), Some(first)) = (max_size, self.first) {
+      if self.entries.len() >= max_size {
+        self.remove(first);
+      }
+    }
+    let entry = Entry {
+      value,
+      prev: None,
+      next: None,
+    };
+    let (index, _) = self.entries.insert_full(key, entry);
+    self.push_last(index);
+  }
+
+  fn unlink(&mut self, index: usize) {
+    let (prev, next) = (self.entries[index].prev, self.entries[index].next);
+    match prev {
+      Some(prev) => self.entries[prev].next = next,
+      None => self.first = next,
+    }
+    match next {
+      Some(next) => self.entries[next].prev = prev,
+      None => self.last = prev,
+    }
+  }
+
+  fn push_last(&mut self, index: usize) {
+    self.entries[index].prev = self.last;
+    self.entries[index].next = None;
+    match self.last {
+      Some(last) => self.entries[last].next = Some(index),
+      None => self.first = Some(index),
+    }
+    self.last = Some(index);
+  }
+
+  fn remove(&mut self, index: usize) {
+    self.unlink(index);
+    self.entries.swap_remove_index(index);
+    if index == self.entries.len() {
+      return;
+    }
+    // the entry which was last in the map took the place of the removed one
+    let (prev, next) = (self.entries[index].prev, self.entries[index].next);
+    match prev {
+      Some(prev) => self.entries[prev].next = Some(index),
+      None => self.first = Some(index),
+    }
+    match next {
+      Some(next) => self.entries[next].prev = Some(index),
+      None => self.last = Some(index),
+    }
+  }
+}
+
+impl Display for Memo {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "<memo {}>", self.function)
+  }
+}
+
+impl Object for Memo {
+  const CALLABLE: bool = true;
+
+  fn type_name(_: Ptr<Self>) -> &'static str {
+    "Memo"
+  }
+
+  default_instance_of!();
+
+  fn call(scope: Scope<'_>, this: Ptr<Self>, _: ReturnAddr) -> Result<CallResult> {
+    let args = (0..scope.num_args())
+      .map(|i| scope.param::<Value>(i).map(|arg| arg.unbind()))
+      .collect::<Result<Vec<_>>>()?;
+    let mut path = Vec::new();
+    let key = (args.iter().cloned())
+      .map(|arg| KeyPart::new(arg, &mut path))
+      .collect::<Result<Key>>()?;
+    if let Some(value) = this.get(&key) {
+      return Ok(CallResult::Return(value));
+    }
+
+    let stack_base = scope.stack_base;
+    let mut thread = scope.thread;
+    Ok(CallResult::Poll(AsyncFrame {
+      stack_base,
+      fut: Box::pin(async move {
+        let value = thread.call(this.function.clone(), &args).await?;
+        this.insert(key, value.clone());
+        Ok(value)
+      }),
+    }))
+  }
+}
+
+declare_object_type!(Memo);
+
+/// The arguments of a call, reduced to what they are compared by.
+type Key = Vec<KeyPart>;
+
+/// Numbers, booleans, `none` and strings are compared by value. Lists,
+/// tables and class instances are compared by their contents at the time
+/// of the call, so a call with an argument which was mutated since is not
+/// answered from the cache. All other objects are compared by identity.
+#[derive(Debug)]
+enum KeyPart {
+  None,
+  Bool(bool),
+  /// Ints and floats are equal if they have the same value, so both are
+  /// stored as the bits of a float.
+  Number(u64),
+  Str(Ptr<Str>),
+  List(Vec<KeyPart>),
+  /// Entries sorted by key, because tables with the same entries are equal
+  /// regardless of their order.
+  Table(Vec<(KeyPart, KeyPart)>),
+  /// Instances of the same data class are equal if their fields are, so
+  /// `owner` is the class for those, and the instance itself otherwise.
+  Instance {
+    owner: Ptr<Any>,
+    fields: Vec<(KeyPart, KeyPart)>,
+  },
+  Object(Ptr<Any>),
+}
+
+impl KeyPart {
+  /// `path` holds the lists, tables and instances which `value` is nested
+  /// in, so that a value which contains itself is rejected instead of
+  /// being copied forever.
+  fn new(value: OwnedValue, path: &mut Vec<Ptr<Any>>) -> Result<Self> {
+    if let Some(v) = value.clone().to_int() {
+      return Ok(Self::number(v as f64));
+    }
+    if let Some(v) = value.clone().to_float() {
+      return Ok(Self::number(v));
+    }
+    if let Some(v) = value.clone().to_bool() {
+      return Ok(Self::Bool(v));
+    }
+    let Some(object) = value.to_any() else {
+      return Ok(Self::None);
+    };
+    let object = match object.cast::<Str>() {
+      Ok(str) => return Ok(Self::Str(str)),
+      Err(object) => object,
+    };
+    if path.iter().any(|outer| outer.ptr_eq(&object)) {
+      fail!("`memo` arguments cannot contain themselves");
+    }
+    path.push(object.clone());
+    let part = if let Ok(list) = object.clone().cast::<List>() {
+      let items = list.iter().map(|item| Self::new(item, path));
+      Self::List(items.collect::<Result<_>>()?)
+    } else if let Ok(table) = object.clone().cast::<Table>() {
+      Self::Table(Self::entries(table.entries(), path)?)
+    } else if let Ok(instance) = object.clone().cast::<ClassInstance>() {
+      let owner = match instance.class.is_data() {
+        true => instance.class.clone().into_any(),
+        false => object,
+      };
+      let fields = Self::entries(instance.fields.entries(), path)?;
+      Self::Instance { owner, fields }
+    } else {
+      Self::Object(object)
+    };
+    path.pop();
+    Ok(part)
+  }
+
+  fn entries(
+    entries: impl Iterator<Item = (Ptr<Str>, OwnedValue)>,
+    path: &mut Vec<Ptr<Any>>,
+  ) -> Result<Vec<(KeyPart, KeyPart)>> {
+    let mut entries = entries
+      .map(|(key, value)| Ok((key, Self::new(value, path)?)))
+      .collect::<Result<Vec<_>>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    Ok(
+      entries
+        .into_iter()
+        .map(|(key, value)| (Self::Str(key), value))
+        .collect(),
+    )
+  }
+
+  fn number(v: f64) -> Self {
+    // `0.0 == -0.0`
+    let v = if v == 0.0 { 0.0 } else { v };
+    Self::Number(v.to_bits())
+  }
+}
+
+impl PartialEq for KeyPart {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Self::None, Self::None) => true,
+      (Self::Bool(a), Self::Bool(b)) => a == b,
+      (Self::Number(a), Self::Number(b)) => a == b,
+      (Self::Str(a), Self::Str(b)) => a.as_str() == b.as_str(),
+      (Self::List(a), Self::List(b)) => a == b,
+      (Self::Table(a), Self::Table(b)) => a == b,
+      (
+        Self::Instance { owner, fields },
+        Self::Instance {
+          owner: other_owner,
+          fields: other_fields,
+        },
+      ) => owner.ptr_eq(other_owner) && fields == other_fields,
+      (Self::Object(a), Self::Object(b)) => a.ptr_eq(b),
+      _ => false,
+    }
+  }
+}
+
+impl Eq for KeyPart {}
+
+impl Hash for KeyPart {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    std::mem::discriminant(self).hash(state);
+    match self {
+      Self::None => {}
+      Self::Bool(v) => v.hash(state),
+      Self::Number(v) => v.hash(state),
+      Self::Str(v) => v.as_str().hash(state),
+      Self::List(v) => v.hash(state),
+      Self::Table(v) => v.hash(state),
+      Self::Instance { owner, fields } => {
+        owner.ptr_hash(state);
+        fields.hash(state);
+      }
+      Self::Object(v) => v.ptr_hash(state),
+    }
+  }
+}
+
+fn memo<'cx>(scope: Scope<'cx>) -> Result<Value<'cx>> {
+  let function = scope.param::<Value>(0)?.unbind();
+  let Some(function) = function.clone().to_any().filter(is_callable) else {
+    fail!("`{function}` is not callable");
+  };
+  let max_size = match scope.num_args() {
+    1 => None,
+    _ => match scope.param::<Option<i32>>(1)? {
+      Some(max_size) if max_size > 0 => Some(max_size as usize),
+      Some(max_size) => fail!("max_size must be greater than zero, got {max_size}"),
+      None => None,
+    },
+  };
+
+  let memo = scope.alloc(Memo {
+    function,
+    max_size,
+    cache: RefCell::default(),
+  });
+  Ok(unsafe { OwnedValue::object(memo).bind_raw::<'cx>() })
+}
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("functools")
+    .function("memo", memo)
+    .finish()
+}
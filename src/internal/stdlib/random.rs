@@ -0,0 +1,192 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::internal::error::Result;
+use crate::public::{List, NativeModule, Scope, Value};
+
+/// Pseudo-random number generator used by the `random` module.
+///
+/// This is `xoshiro256**` seeded through `splitmix64`. It is implemented
+/// here instead of pulling in a dependency so that the sequence produced
+/// for a given seed never changes, which is what makes a seeded VM
+/// replayable.
+#[derive(Debug, Clone)]
+pub struct Rng {
+  state: [u64; 4],
+  /// The second value produced by the Box-Muller transform in `gauss`.
+  spare_normal: Option<f64>,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    let mut sm = seed;
+    let mut next = || {
+      sm = sm.wrapping_add(0x9e3779b97f4a7c15);
+      let mut z = sm;
+      z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+      z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+      z ^ (z >> 31)
+    };
+    Self {
+      state: [next(), next(), next(), next()],
+      spare_normal: None,
+    }
+  }
+
+  /// Seed from the system's source of randomness.
+  pub fn from_entropy() -> Self {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(time) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+      hasher.write_u128(time.as_nanos());
+    }
+    Self::new(hasher.finish())
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    let [s0, s1, s2, s3] = &mut self.state;
+    let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = *s1 << 17;
+    *s2 ^= *s0;
+    *s3 ^= *s1;
+    *s1 ^= *s2;
+    *s0 ^= *s3;
+    *s2 ^= t;
+    *s3 = s3.rotate_left(45);
+    result
+  }
+
+  /// Uniformly distributed float in `[0, 1)`.
+  pub fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+  }
+
+  /// Uniformly distributed integer in `[0, n)`.
+  ///
+  /// `n` must be greater than zero.
+  pub fn below(&mut self, n: u64) -> u64 {
+    debug_assert!(n > 0);
+    // rejection sampling to avoid modulo bias
+    let zone = u64::MAX - (u64::MAX - n + 1) % n;
+    loop {
+      let v = self.next_u64();
+      if v <= zone {
+        return v % n;
+      }
+    }
+  }
+
+  /// Normally distributed float with mean `mu` and standard deviation
+  /// `sigma`.
+  pub fn gauss(&mut self, mu: f64, sigma: f64) -> f64 {
+    let z = match self.spare_normal.take() {
+      Some(z) => z,
+      None => {
+        // Box-Muller, `u1` must not be zero
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        let r = (-2.0 * u1.ln()).sqrt();
+        let theta = std::f64::consts::TAU * u2;
+        self.spare_normal = Some(r * theta.sin());
+        r * theta.cos()
+      }
+    };
+    mu + z * sigma
+  }
+}
+
+fn to_f64(value: &Value<'_>, what: &str) -> Result<f64> {
+  if let Some(value) = value.as_float() {
+    Ok(value)
+  } else if let Some(value) = value.as_int() {
+    Ok(value as f64)
+  } else {
+    fail!("{what} must be a number, got `{value}`")
+  }
+}
+
+fn random(scope: Scope<'_>) -> Result<f64> {
+  scope.params::<()>()?;
+  Ok(scope.thread.global.rng().next_f64())
+}
+
+/// Seed the generator the same way as [`HebiBuilder::seed`][crate::HebiBuilder::seed].
+///
+/// Ints are 32 bits, so the seed is their bits read as a `u32`, which makes
+/// every seed from `0` to `2^32 - 1` expressible. Seeds above `2^31 - 1` are
+/// written as negative ints, for example `-1` is `2^32 - 1`.
+fn seed(scope: Scope<'_>) -> Result<()> {
+  let seed = scope.param::<i32>(0)?;
+  scope.thread.global.seed_rng(seed as u32 as u64);
+  Ok(())
+}
+
+fn range(scope: Scope<'_>) -> Result<i32> {
+  let (start, end) = scope.params::<(i32, i32)>()?;
+  if start >= end {
+    fail!("empty range {start}..{end}");
+  }
+  let span = (end as i64 - start as i64) as u64;
+  let offset = scope.thread.global.rng().below(span);
+  Ok((start as i64 + offset as i64) as i32)
+}
+
+fn gauss<'cx>(scope: Scope<'cx>) -> Result<f64> {
+  let (mu, sigma) = scope.params::<(Value<'cx>, Value<'cx>)>()?;
+  let mu = to_f64(&mu, "mu")?;
+  let sigma = to_f64(&sigma, "sigma")?;
+  Ok(scope.thread.global.rng().gauss(mu, sigma))
+}
+
+fn choice<'cx>(scope: Scope<'cx>) -> Result<Value<'cx>> {
+  let list = scope.param::<List<'cx>>(0)?;
+  if list.is_empty() {
+    fail!("cannot choose from an empty list");
+  }
+  let index = scope.thread.global.rng().below(list.len() as u64) as usize;
+  Ok(list.get(index).unwrap())
+}
+
+fn shuffle<'cx>(scope: Scope<'cx>) -> Result<()> {
+  let list = scope.param::<List<'cx>>(0)?;
+  // Fisher-Yates
+  for i in (1..list.len()).rev() {
+    let j = scope.thread.global.rng().below(i as u64 + 1) as usize;
+    list.swap(i, j);
+  }
+  Ok(())
+}
+
+pub fn module() -> NativeModule {
+  NativeModule::builder("random")
+    .function("random", random)
+    .function("seed", seed)
+    .function("range", range)
+    .function("gauss", gauss)
+    .function("choice", choice)
+    .function("shuffle", shuffle)
+    .finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn same_seed_same_sequence() {
+    let mut a = Rng::new(1234);
+    let mut b = Rng::new(1234);
+    for _ in 0..100 {
+      assert_eq!(a.next_u64(), b.next_u64());
+    }
+  }
+
+  #[test]
+  fn bounds() {
+    let mut rng = Rng::new(0);
+    for _ in 0..1000 {
+      let v = rng.next_f64();
+      assert!((0.0..1.0).contains(&v));
+      assert!(rng.below(7) < 7);
+    }
+  }
+}
@@ -0,0 +1,84 @@
+//! The `sys` module, which tells scripts about the VM they are running in.
+//!
+//! Unlike the other modules, it is not registered, because some of its
+//! fields depend on who imports it. Each importing module gets its own copy,
+//! which is created on its first import and reused after that.
+
+use crate::internal::error::Result;
+use crate::internal::object::builtin::BuiltinFunction;
+use crate::internal::object::module::{Module, ModuleId, ModuleKind};
+use crate::internal::object::{Ptr, Table};
+use crate::internal::value::Value;
+use crate::internal::vm::global::Global;
+use crate::public::Scope;
+
+pub const NAME: &str = "sys";
+
+/// The `sys` module as seen by the module `importer`.
+pub fn module(global: &Global, importer: ModuleId) -> Ptr<Module> {
+  global.sys_module(importer, |module_id| create(global, importer, module_id))
+}
+
+fn create(global: &Global, importer: ModuleId, module_id: ModuleId) -> Ptr<Module> {
+  let module_name = match global.get_module_by_id(importer) {
+    Some(module) => module.name.clone(),
+    None => global.intern("__main__"),
+  };
+  let platform = match global.exposes_platform() {
+    true => Value::object(global.intern(std::env::consts::OS)),
+    false => Value::none(),
+  };
+
+  let module_vars = global.alloc(Table::with_capacity(5));
+  let field = |name: &'static str, value: Value| module_vars.insert(global.intern(name), value);
+  field(
+    "version",
+    Value::object(global.intern(env!("CARGO_PKG_VERSION"))),
+  );
+  field("module_name", Value::object(module_name));
+  field("platform", platform);
+  field(
+    "fuel_remaining",
+    Value::object(global.alloc(BuiltinFunction::new("fuel_remaining", fuel_remaining))),
+  );
+  field(
+    "memory_used",
+    Value::object(global.alloc(BuiltinFunction::new("memory_used", memory_used))),
+  );
+  global.alloc(Module {
+    module_id,
+    name: global.intern(NAME),
+    module_vars,
+    kind: ModuleKind::Native,
+  })
+}
+
+/// A count which is an int if it fits, and a float otherwise.
+fn count(v: usize) -> Value {
+  match i32::try_from(v) {
+    Ok(v) => Value::int(v),
+    Err(_) => Value::float(v as f64),
+  }
+}
+
+/// How many more instructions the script may execute before it is paused,
+/// or `none` if it may run until it finishes.
+///
+/// Scripts which are not stepped still count down from `usize::MAX`, so any
+/// budget too large to be an int is treated as unlimited.
+fn fuel_remaining(scope: Scope<'_>) -> Result<Value> {
+  let steps = unsafe { scope.thread.stack.as_ref() }.steps;
+  match i32::try_from(steps) {
+    Ok(steps) => Ok(Value::int(steps)),
+    Err(_) => Ok(Value::none()),
+  }
+}
+
+/// How many bytes live objects take up, or `none` if objects are not
+/// counted.
+fn memory_used(scope: Scope<'_>) -> Result<Value> {
+  match scope.thread.global.memory_used() {
+    Some(bytes) => Ok(count(bytes)),
+    None => Ok(Value::none()),
+  }
+}
@@ -1,14 +1,16 @@
 pub mod ast;
+pub mod frontmatter;
 pub mod lexer;
 pub mod parser;
+pub mod pragma;
 
 use std::error::Error as StdError;
 use std::fmt::Display;
 
 pub use ast::Module;
-pub use parser::parse;
+pub use parser::{parse, parse_ast_template, parse_code, parse_header, parse_template};
 
-use crate::span::SpannedError;
+use crate::span::{Source, SpannedError};
 use crate::util::JoinIter;
 
 #[derive(Debug)]
@@ -17,13 +19,23 @@ pub struct SyntaxError {
 }
 
 impl SyntaxError {
-  fn new(errors: Vec<SpannedError>) -> Self {
+  pub(crate) fn new(errors: Vec<SpannedError>) -> Self {
     Self { errors }
   }
 
   pub fn errors(&self) -> &[SpannedError] {
     &self.errors
   }
+
+  pub(crate) fn with_source(self, source: &Source) -> Self {
+    Self {
+      errors: self
+        .errors
+        .into_iter()
+        .map(|e| e.with_source(source.clone()))
+        .collect(),
+    }
+  }
 }
 
 impl Display for SyntaxError {
@@ -3,6 +3,7 @@
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
 
+use super::pragma::Pragmas;
 use crate::span::{Span, Spanned};
 use crate::Cow;
 
@@ -43,14 +44,31 @@ impl<'src> DerefMut for Ident<'src> {
   }
 }
 
-#[cfg_attr(test, derive(Debug))]
 pub struct Module<'src> {
   pub body: Vec<Stmt<'src>>,
+  pub pragmas: Pragmas,
+}
+
+// pragmas are only shown if there are any, so that snapshots of modules
+// without them stay the same
+#[cfg(test)]
+impl<'src> std::fmt::Debug for Module<'src> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut s = f.debug_struct("Module");
+    s.field("body", &self.body);
+    if self.pragmas != Pragmas::default() {
+      s.field("pragmas", &self.pragmas);
+    }
+    s.finish()
+  }
 }
 
 impl<'src> Module<'src> {
   pub fn new() -> Self {
-    Self { body: vec![] }
+    Self {
+      body: vec![],
+      pragmas: Pragmas::default(),
+    }
   }
 }
 
@@ -62,18 +80,96 @@ impl<'src> Default for Module<'src> {
 
 pub type Stmt<'src> = Spanned<StmtKind<'src>>;
 
+/// Declares an enum of node kinds, along with the names of its variants,
+/// which the tests use to check that every kind of node is covered.
+macro_rules! node_kinds {
+  (
+    $(#[$meta:meta])*
+    pub enum $name:ident<'src> {
+      $($variant:ident $(($ty:ty))?),* $(,)?
+    }
+  ) => {
+    $(#[$meta])*
+    pub enum $name<'src> {
+      $($variant $(($ty))?),*
+    }
+
+    #[cfg(test)]
+    impl<'src> $name<'src> {
+      pub const VARIANTS: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+      pub fn variant(&self) -> &'static str {
+        match self {
+          $(Self::$variant { .. } => stringify!($variant)),*
+        }
+      }
+    }
+  };
+}
+
+node_kinds! {
+  #[cfg_attr(test, derive(Debug))]
+  pub enum StmtKind<'src> {
+    Var(Box<Var<'src>>),
+    If(Box<If<'src>>),
+    Loop(Box<Loop<'src>>),
+    Ctrl(Box<Ctrl<'src>>),
+    Func(Box<Func<'src>>),
+    Class(Box<Class<'src>>),
+    Expr(Box<Expr<'src>>),
+    Pass,
+    Print(Box<Print<'src>>),
+    Import(Box<Import<'src>>),
+    Try(Box<Try<'src>>),
+    Throw(Box<Throw<'src>>),
+    With(Box<With<'src>>),
+    MultiAssign(Box<MultiAssign<'src>>),
+  }
+}
+
+/// `a = b = c` or `a, b = b, a`.
+///
+/// The receivers and keys of the targets are evaluated first, then the
+/// values, and only then are the targets assigned, from left to right.
+/// Everything is evaluated exactly once.
+#[cfg_attr(test, derive(Debug))]
+pub struct MultiAssign<'src> {
+  /// Groups of targets separated by `=`, each with one target per value.
+  pub targets: Vec<Vec<AssignTarget<'src>>>,
+  pub values: Vec<Expr<'src>>,
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub enum AssignTarget<'src> {
+  Var(GetVar<'src>),
+  Field(GetField<'src>),
+  Index(GetIndex<'src>),
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Try<'src> {
+  pub body: Vec<Stmt<'src>>,
+  pub catches: Vec<Catch<'src>>,
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Catch<'src> {
+  /// Only errors which are instances of this class are caught.
+  pub class: Option<Expr<'src>>,
+  pub binding: Option<Ident<'src>>,
+  pub body: Vec<Stmt<'src>>,
+}
+
+#[cfg_attr(test, derive(Debug))]
+pub struct Throw<'src> {
+  pub value: Expr<'src>,
+}
+
 #[cfg_attr(test, derive(Debug))]
-pub enum StmtKind<'src> {
-  Var(Box<Var<'src>>),
-  If(Box<If<'src>>),
-  Loop(Box<Loop<'src>>),
-  Ctrl(Box<Ctrl<'src>>),
-  Func(Box<Func<'src>>),
-  Class(Box<Class<'src>>),
-  Expr(Box<Expr<'src>>),
-  Pass,
-  Print(Box<Print<'src>>),
-  Import(Box<Import<'src>>),
+pub struct With<'src> {
+  pub context: Expr<'src>,
+  pub binding: Option<Ident<'src>>,
+  pub body: Vec<Stmt<'src>>,
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -100,6 +196,8 @@ pub struct Func<'src> {
   pub params: Params<'src>,
   pub body: Vec<Stmt<'src>>,
   pub has_yield: bool,
+  /// `@decorator` expressions, outermost first.
+  pub decorators: Vec<Expr<'src>>,
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -172,6 +270,7 @@ pub enum Loop<'src> {
   For(For<'src>),
   While(While<'src>),
   Infinite(Infinite<'src>),
+  Repeat(Repeat<'src>),
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -185,6 +284,8 @@ pub struct For<'src> {
 pub enum ForIter<'src> {
   Range(IterRange<'src>),
   Expr(Expr<'src>),
+  /// `for await item in stream`
+  Stream(Expr<'src>),
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -192,11 +293,14 @@ pub struct IterRange<'src> {
   pub start: Expr<'src>,
   pub end: Expr<'src>,
   pub inclusive: bool,
+  /// `start..end by step`
+  pub step: Option<Expr<'src>>,
 }
 
 impl<'src> IterRange<'src> {
   pub fn span(&self) -> Span {
-    self.start.span.join(self.end.span)
+    let end = self.step.as_ref().unwrap_or(&self.end);
+    self.start.span.join(end.span)
   }
 }
 
@@ -211,6 +315,13 @@ pub struct Infinite<'src> {
   pub body: Vec<Stmt<'src>>,
 }
 
+/// `loop count: body`, which runs `body` `count` times.
+#[cfg_attr(test, derive(Debug))]
+pub struct Repeat<'src> {
+  pub count: Expr<'src>,
+  pub body: Vec<Stmt<'src>>,
+}
+
 #[cfg_attr(test, derive(Debug))]
 pub struct Print<'src> {
   pub values: Vec<Expr<'src>>,
@@ -218,21 +329,41 @@ pub struct Print<'src> {
 
 pub type Expr<'src> = Spanned<ExprKind<'src>>;
 
-#[cfg_attr(test, derive(Debug))]
-#[derive(Clone)]
-pub enum ExprKind<'src> {
-  Literal(Box<Literal<'src>>),
-  Binary(Box<Binary<'src>>),
-  Unary(Box<Unary<'src>>),
-  GetVar(Box<GetVar<'src>>),
-  SetVar(Box<SetVar<'src>>),
-  GetField(Box<GetField<'src>>),
-  SetField(Box<SetField<'src>>),
-  GetIndex(Box<GetIndex<'src>>),
-  SetIndex(Box<SetIndex<'src>>),
-  Call(Box<Call<'src>>),
-  GetSelf,
-  GetSuper,
+node_kinds! {
+  #[cfg_attr(test, derive(Debug))]
+  #[derive(Clone)]
+  pub enum ExprKind<'src> {
+    Literal(Box<Literal<'src>>),
+    Binary(Box<Binary<'src>>),
+    Unary(Box<Unary<'src>>),
+    GetVar(Box<GetVar<'src>>),
+    SetVar(Box<SetVar<'src>>),
+    GetField(Box<GetField<'src>>),
+    SetField(Box<SetField<'src>>),
+    GetIndex(Box<GetIndex<'src>>),
+    SetIndex(Box<SetIndex<'src>>),
+    Call(Box<Call<'src>>),
+    GetSelf,
+    GetSuper,
+  }
+}
+
+impl<'src> ExprKind<'src> {
+  /// The keyword this expression consists of, if it is a keyword such as
+  /// `none` or `self`.
+  pub fn keyword(&self) -> Option<&'static str> {
+    match self {
+      ExprKind::Literal(v) => match **v {
+        Literal::None => Some("none"),
+        Literal::Bool(true) => Some("true"),
+        Literal::Bool(false) => Some("false"),
+        _ => None,
+      },
+      ExprKind::GetSelf => Some("self"),
+      ExprKind::GetSuper => Some("super"),
+      _ => None,
+    }
+  }
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -241,6 +372,8 @@ pub enum Literal<'src> {
   None,
   Int(i32),
   Float(f64),
+  #[cfg(feature = "decimal")]
+  Decimal(crate::internal::object::decimal::Decimal),
   Bool(bool),
   String(Cow<'src, str>),
   List(Vec<Expr<'src>>),
@@ -274,6 +407,8 @@ pub enum BinaryOp {
   Maybe,
   Is,
   In,
+  /// `x |> f` calls `f(x)`, and `x |> f(y)` calls `f(x, y)`.
+  Pipe,
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -382,6 +517,8 @@ pub struct Return<'src> {
 pub struct Call<'src> {
   pub target: Expr<'src>,
   pub args: Vec<Expr<'src>>,
+  /// `f?()`, which evaluates to `none` instead of calling `f` if it is `none`.
+  pub opt: bool,
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -463,6 +600,46 @@ pub fn break_stmt<'src>(s: impl Into<Span>) -> Stmt<'src> {
   Stmt::new(s, StmtKind::Ctrl(Box::new(Ctrl::Break)))
 }
 
+pub fn try_stmt<'src>(
+  s: impl Into<Span>,
+  body: Vec<Stmt<'src>>,
+  catches: Vec<Catch<'src>>,
+) -> Stmt<'src> {
+  Stmt::new(s, StmtKind::Try(Box::new(Try { body, catches })))
+}
+
+pub fn catch_clause<'src>(
+  class: Option<Expr<'src>>,
+  binding: Option<Ident<'src>>,
+  body: Vec<Stmt<'src>>,
+) -> Catch<'src> {
+  Catch {
+    class,
+    binding,
+    body,
+  }
+}
+
+pub fn throw_stmt(s: impl Into<Span>, value: Expr) -> Stmt {
+  Stmt::new(s, StmtKind::Throw(Box::new(Throw { value })))
+}
+
+pub fn with_stmt<'src>(
+  s: impl Into<Span>,
+  context: Expr<'src>,
+  binding: Option<Ident<'src>>,
+  body: Vec<Stmt<'src>>,
+) -> Stmt<'src> {
+  Stmt::new(
+    s,
+    StmtKind::With(Box::new(With {
+      context,
+      binding,
+      body,
+    })),
+  )
+}
+
 pub fn pass_stmt<'src>(s: impl Into<Span>) -> Stmt<'src> {
   Stmt::new(s, StmtKind::Pass)
 }
@@ -489,7 +666,29 @@ pub fn expr_call<'src>(
   target: Expr<'src>,
   args: Vec<Expr<'src>>,
 ) -> Expr<'src> {
-  Expr::new(s, ExprKind::Call(Box::new(Call { target, args })))
+  Expr::new(
+    s,
+    ExprKind::Call(Box::new(Call {
+      target,
+      args,
+      opt: false,
+    })),
+  )
+}
+
+pub fn expr_call_opt<'src>(
+  s: impl Into<Span>,
+  target: Expr<'src>,
+  args: Vec<Expr<'src>>,
+) -> Expr<'src> {
+  Expr::new(
+    s,
+    ExprKind::Call(Box::new(Call {
+      target,
+      args,
+      opt: true,
+    })),
+  )
 }
 
 pub fn expr_get_field<'src>(
@@ -557,6 +756,7 @@ pub fn func<'src>(
     params,
     body,
     has_yield,
+    decorators: Vec::new(),
   }
 }
 
@@ -607,6 +807,26 @@ pub fn assign<'src>(target: Expr<'src>, kind: AssignKind, value: Expr<'src>) ->
   }
 }
 
+pub fn multi_assign_stmt<'src>(
+  s: impl Into<Span>,
+  targets: Vec<Vec<AssignTarget<'src>>>,
+  values: Vec<Expr<'src>>,
+) -> Stmt<'src> {
+  Stmt::new(
+    s,
+    StmtKind::MultiAssign(Box::new(MultiAssign { targets, values })),
+  )
+}
+
+pub fn assign_target(target: Expr) -> Option<AssignTarget> {
+  match target.into_inner() {
+    ExprKind::GetVar(target) => Some(AssignTarget::Var(*target)),
+    ExprKind::GetField(target) => Some(AssignTarget::Field(*target)),
+    ExprKind::GetIndex(target) => Some(AssignTarget::Index(*target)),
+    _ => None,
+  }
+}
+
 fn desugar_assign<'src, T>(
   span: impl Into<Span>,
   target: &T,
@@ -654,6 +874,17 @@ pub fn loop_stmt(s: impl Into<Span>, body: Vec<Stmt>) -> Stmt {
   )
 }
 
+pub fn repeat_loop_stmt<'src>(
+  s: impl Into<Span>,
+  count: Expr<'src>,
+  body: Vec<Stmt<'src>>,
+) -> Stmt<'src> {
+  Stmt::new(
+    s,
+    StmtKind::Loop(Box::new(Loop::Repeat(Repeat { count, body }))),
+  )
+}
+
 pub fn while_loop_stmt<'src>(
   s: impl Into<Span>,
   cond: Expr<'src>,
@@ -721,6 +952,25 @@ pub mod lit {
     ))
   }
 
+  #[cfg(feature = "decimal")]
+  pub fn decimal<'src>(s: impl Into<Span>, lexeme: &'src str) -> Result<Expr<'src>, SpannedError> {
+    let s = s.into();
+    let value = crate::internal::object::decimal::Decimal::parse_literal(lexeme)
+      .ok_or_else(|| SpannedError::new("decimal literal is out of range", s))?;
+    Ok(Expr::new(
+      s,
+      ExprKind::Literal(Box::new(Literal::Decimal(value))),
+    ))
+  }
+
+  #[cfg(not(feature = "decimal"))]
+  pub fn decimal<'src>(s: impl Into<Span>, _: &'src str) -> Result<Expr<'src>, SpannedError> {
+    Err(SpannedError::new(
+      "decimal literals require the `decimal` feature",
+      s.into(),
+    ))
+  }
+
   pub fn str<'src>(s: impl Into<Span>, lexeme: &'src str) -> Option<Expr<'src>> {
     let s = s.into();
     let lexeme = lexeme.strip_prefix('"').unwrap_or(lexeme);
@@ -0,0 +1,118 @@
+//! Metadata at the top of a script file.
+//!
+//! A script may start with a shebang line, followed by a block of
+//! `key: value` pairs fenced by `---` lines:
+//!
+//! ```text
+//! #!/usr/bin/env hebi
+//! ---
+//! owner: platform-team
+//! description: rotates the signing keys
+//! ---
+//! print "hello"
+//! ```
+//!
+//! The block is read without parsing the rest of the script, so tooling can
+//! inspect it without compiling or executing anything. The shebang line is
+//! a comment, so it is accepted with or without a frontmatter block.
+
+use crate::span::SpannedError;
+
+const FENCE: &str = "---";
+
+#[derive(Debug, Default)]
+pub struct Frontmatter<'src> {
+  pub entries: Vec<(&'src str, &'src str)>,
+  /// Offset of the first byte after the frontmatter, where the script's
+  /// code starts.
+  pub end: usize,
+}
+
+pub fn parse(src: &str) -> Result<Frontmatter<'_>, SpannedError> {
+  let mut lines = Lines::new(src, 0);
+  let mut frontmatter = Frontmatter::default();
+
+  if src.starts_with("#!") {
+    lines.next();
+  }
+  let open = match lines.next() {
+    Some((start, line)) if line == FENCE => start..start + FENCE.len(),
+    // no frontmatter
+    _ => return Ok(frontmatter),
+  };
+
+  loop {
+    let Some((start, line)) = lines.next() else {
+      return Err(SpannedError::new(
+        "unterminated frontmatter, expected a closing `---`",
+        open,
+      ));
+    };
+    let span = start..start + line.len();
+    if line == FENCE {
+      break;
+    }
+    if line.trim().is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let Some((key, value)) = line.split_once(':') else {
+      return Err(SpannedError::new("expected `key: value`", span));
+    };
+    let key = key.trim();
+    if key.is_empty()
+      || !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+      return Err(SpannedError::new(
+        format!("invalid frontmatter key `{key}`"),
+        span,
+      ));
+    }
+    if frontmatter.entries.iter().any(|(k, _)| *k == key) {
+      return Err(SpannedError::new(
+        format!("duplicate frontmatter key `{key}`"),
+        span,
+      ));
+    }
+    frontmatter.entries.push((key, value.trim()));
+  }
+
+  frontmatter.end = lines.pos;
+  Ok(frontmatter)
+}
+
+/// Like [`str::lines`], but also yields the offset of each line.
+pub(super) struct Lines<'src> {
+  src: &'src str,
+  pos: usize,
+}
+
+impl<'src> Lines<'src> {
+  /// The lines of `src`, starting at the offset `pos`.
+  pub(super) fn new(src: &'src str, pos: usize) -> Self {
+    Self { src, pos }
+  }
+}
+
+impl<'src> Iterator for Lines<'src> {
+  type Item = (usize, &'src str);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.pos >= self.src.len() {
+      return None;
+    }
+    let start = self.pos;
+    let rest = &self.src[start..];
+    let (line, len) = match rest.find('\n') {
+      Some(end) => (&rest[..end], end + 1),
+      None => (rest, rest.len()),
+    };
+    self.pos += len;
+    Some((start, line.strip_suffix('\r').unwrap_or(line)))
+  }
+}
+
+#[cfg(test)]
+mod tests;
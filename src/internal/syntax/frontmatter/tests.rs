@@ -0,0 +1,56 @@
+use super::*;
+
+fn entries(src: &str) -> Vec<(&str, &str)> {
+  parse(src).unwrap().entries
+}
+
+fn error(src: &str) -> String {
+  let e = parse(src).unwrap_err();
+  format!("{} at {}", e.message, e.span)
+}
+
+#[test]
+fn no_frontmatter() {
+  assert_eq!(parse("print 1\n").unwrap().end, 0);
+  assert_eq!(parse("#!/usr/bin/env hebi\nprint 1\n").unwrap().end, 0);
+  assert_eq!(parse("").unwrap().end, 0);
+  // the fence has to be on the first line
+  assert_eq!(parse("\n---\na: b\n---\n").unwrap().end, 0);
+}
+
+#[test]
+fn frontmatter_block() {
+  let src = "---\nowner: ops\ndescription:  rotates keys: daily \n---\nprint 1\n";
+  let frontmatter = parse(src).unwrap();
+  assert_eq!(
+    frontmatter.entries,
+    vec![("owner", "ops"), ("description", "rotates keys: daily")]
+  );
+  assert_eq!(&src[frontmatter.end..], "print 1\n");
+}
+
+#[test]
+fn frontmatter_after_shebang() {
+  let src = "#!/usr/bin/env hebi\r\n---\r\n# comment\r\n\r\npermissions: net\r\n---";
+  let frontmatter = parse(src).unwrap();
+  assert_eq!(frontmatter.entries, vec![("permissions", "net")]);
+  assert_eq!(frontmatter.end, src.len());
+  assert_eq!(entries("---\n---\n"), vec![]);
+}
+
+#[test]
+fn frontmatter_errors() {
+  assert_eq!(
+    error("---\nowner: ops\n"),
+    "unterminated frontmatter, expected a closing `---` at 0..3"
+  );
+  assert_eq!(error("---\nowner\n---\n"), "expected `key: value` at 4..9");
+  assert_eq!(
+    error("---\nthe owner: ops\n---\n"),
+    "invalid frontmatter key `the owner` at 4..18"
+  );
+  assert_eq!(
+    error("---\na: 1\na: 2\n---\n"),
+    "duplicate frontmatter key `a` at 9..13"
+  );
+}
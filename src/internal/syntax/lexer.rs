@@ -34,6 +34,13 @@ pub struct Lexer<'src> {
 
 impl<'src> Lexer<'src> {
   pub fn new(src: &'src str) -> Self {
+    Self::with_offset(src, 0)
+  }
+
+  /// Start lexing at byte `offset` instead of the start of `src`.
+  ///
+  /// `offset` must be at the start of a line.
+  pub fn with_offset(src: &'src str, offset: usize) -> Self {
     let end = src.len();
     let eof = Token {
       ws: None,
@@ -41,9 +48,11 @@ impl<'src> Lexer<'src> {
       kind: TokenKind::Tok_Eof,
     };
 
+    let mut inner = TokenKind::lexer(src);
+    inner.bump(offset);
     let mut lex = Self {
       src,
-      inner: TokenKind::lexer(src),
+      inner,
       previous: eof.clone(),
       current: eof.clone(),
       ws: Some(0),
@@ -69,6 +78,23 @@ impl<'src> Lexer<'src> {
     &self.src[Range::from(token.span)]
   }
 
+  /// The `{name}` placeholder which starts at the current token, if there
+  /// is one. It is made up of three tokens, with nothing between them.
+  pub fn placeholder(&self) -> Option<&'src str> {
+    if !self.current.is(TokenKind::Brk_CurlyL) {
+      return None;
+    }
+    let rest = &self.src[self.current.span.start..];
+    let end = rest.find('}')?;
+    let name = &rest[1..end];
+    let mut chars = name.chars();
+    let is_ident = chars
+      .next()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+      && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_ident.then(|| &rest[..=end])
+  }
+
   #[inline]
   pub fn bump(&mut self) {
     std::mem::swap(&mut self.previous, &mut self.current);
@@ -114,6 +140,14 @@ impl<'src> Lexer<'src> {
   }
 }
 
+/// Whether `name` is a valid identifier, which a keyword is not.
+pub fn is_ident(name: &str) -> bool {
+  let mut lexer = TokenKind::lexer(name);
+  matches!(lexer.next(), Some(Ok(TokenKind::Lit_Ident)))
+    && lexer.span() == (0..name.len())
+    && lexer.next().is_none()
+}
+
 // When adding a token, if it is matched using `token` directive only,
 // then it should also be added to the `known` module below.
 #[derive(Clone, Copy, Debug, Logos, PartialEq)]
@@ -163,6 +197,14 @@ pub enum TokenKind {
   Kw_Else,
   #[token("pass")]
   Kw_Pass,
+  #[token("try")]
+  Kw_Try,
+  #[token("catch")]
+  Kw_Catch,
+  #[token("throw")]
+  Kw_Throw,
+  #[token("with")]
+  Kw_With,
 
   // Brackets
   #[token("{")]
@@ -189,6 +231,8 @@ pub enum TokenKind {
   Tok_Colon,
   #[token("?")]
   Tok_Question,
+  #[token("@")]
+  Tok_At,
 
   // Equals operators
   #[token("=")]
@@ -241,6 +285,8 @@ pub enum TokenKind {
   Op_LessEqual,
   #[token("||")]
   Op_PipePipe,
+  #[token("|>")]
+  Op_PipeMore,
   #[token("&&")]
   Op_AndAnd,
   #[token("..")]
@@ -257,6 +303,9 @@ pub enum TokenKind {
   /// `0`, `1.0`, `5e10`, etc.
   #[regex(r"[0-9]+(\.[0-9]+)?([Ee][+-]?[0-9]+)?")]
   Lit_Float,
+  /// `1d`, `1.50d`, etc.
+  #[regex(r"[0-9]+(\.[0-9]+)?d")]
+  Lit_Decimal,
   /// `true` or `false`
   #[token("true")]
   #[token("false")]
@@ -306,6 +355,10 @@ impl TokenKind {
       TokenKind::Kw_Elif => "elif",
       TokenKind::Kw_Else => "else",
       TokenKind::Kw_Pass => "pass",
+      TokenKind::Kw_Try => "try",
+      TokenKind::Kw_Catch => "catch",
+      TokenKind::Kw_Throw => "throw",
+      TokenKind::Kw_With => "with",
       TokenKind::Brk_CurlyL => "{",
       TokenKind::Brk_CurlyR => "}",
       TokenKind::Brk_ParenL => "(",
@@ -317,6 +370,7 @@ impl TokenKind {
       TokenKind::Tok_Semicolon => ";",
       TokenKind::Tok_Colon => ":",
       TokenKind::Tok_Question => "?",
+      TokenKind::Tok_At => "@",
       TokenKind::Op_Equal => "=",
       TokenKind::Op_EqualEqual => "==",
       TokenKind::Op_PlusEqual => "+=",
@@ -341,12 +395,14 @@ impl TokenKind {
       TokenKind::Op_Less => "<",
       TokenKind::Op_LessEqual => "<=",
       TokenKind::Op_PipePipe => "||",
+      TokenKind::Op_PipeMore => "|>",
       TokenKind::Op_AndAnd => "&&",
       TokenKind::Op_Range => "..",
       TokenKind::Op_RangeInc => "..=",
       TokenKind::Lit_None => "none",
       TokenKind::Lit_Int => "int",
       TokenKind::Lit_Float => "float",
+      TokenKind::Lit_Decimal => "decimal",
       TokenKind::Lit_Bool => "bool",
       TokenKind::Lit_String => "string",
       TokenKind::Lit_Ident => "identifier",
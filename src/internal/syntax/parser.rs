@@ -2,9 +2,11 @@
 #![allow(dead_code, clippy::needless_update)]
 
 use self::indent::IndentStack;
+use super::frontmatter::Frontmatter;
 use super::lexer::TokenKind::*;
 use super::lexer::{Lexer, Token, TokenKind};
-use super::{ast, SyntaxError};
+use super::pragma::Pragmas;
+use super::{ast, frontmatter, pragma, SyntaxError};
 use crate::internal::vm::global::Global;
 use crate::span::{Span, SpannedError};
 use crate::Cow;
@@ -13,9 +15,56 @@ use crate::Cow;
 // TODO: `async`/`await` - maybe post-MVP
 
 pub fn parse(global: Global, src: &str) -> Result<ast::Module, SyntaxError> {
-  let lexer = Lexer::new(src);
+  let (frontmatter, pragmas) = parse_header(src)?;
+  parse_code(global, src, &frontmatter, pragmas)
+}
+
+/// Read the frontmatter and the pragmas at the top of `src`, without parsing
+/// the rest of it.
+pub fn parse_header(src: &str) -> Result<(Frontmatter<'_>, Pragmas), SyntaxError> {
+  let frontmatter = frontmatter::parse(src).map_err(|e| SyntaxError::new(vec![e]))?;
+  let pragmas = pragma::parse(src, frontmatter.end).map_err(|e| SyntaxError::new(vec![e]))?;
+  Ok((frontmatter, pragmas))
+}
+
+/// Parse the code of `src`, which follows the header read by
+/// [`parse_header`].
+pub fn parse_code<'src>(
+  global: Global,
+  src: &'src str,
+  frontmatter: &Frontmatter<'src>,
+  pragmas: Pragmas,
+) -> Result<ast::Module<'src>, SyntaxError> {
+  let lexer = Lexer::with_offset(src, frontmatter.end);
   let parser = Parser::new(global, lexer);
-  parser.module().map_err(SyntaxError::new)
+  let mut module = parser.module().map_err(SyntaxError::new)?;
+  module.pragmas = pragmas;
+  Ok(module)
+}
+
+/// Parse `src` as an AST template, where `{name}` placeholders may appear
+/// in place of identifiers and expressions. A placeholder is kept as an
+/// identifier which includes the braces, so it cannot clash with any other
+/// identifier.
+///
+/// The top level of a template is parsed as if it was in a loop inside of a
+/// function, so that fragments such as `return x` may be parsed on their
+/// own. Whether they end up in a function is checked once they are used.
+///
+/// Identifiers are copied out of `src`, so the module does not borrow from
+/// it.
+pub fn parse_ast_template(global: Global, src: &str) -> Result<ast::Module<'static>, SyntaxError> {
+  let mut parser = Parser::new(global, Lexer::new(src));
+  parser.allow_placeholders = true;
+  parser.copy_idents = true;
+  parser.state = State::default()
+    .with_func(Cow::borrowed("__template__"), false)
+    .with_loop();
+  let module = parser.module().map_err(SyntaxError::new)?;
+  // SAFETY: identifiers are the only part of the AST which may borrow from
+  // the source, and they were all copied. String literals are always copied,
+  // because they are unescaped.
+  Ok(unsafe { std::mem::transmute::<ast::Module<'_>, ast::Module<'static>>(module) })
 }
 
 #[derive(Clone)]
@@ -110,6 +159,12 @@ struct Parser<'src> {
   errors: Vec<SpannedError>,
   indent: IndentStack,
   state: State<'src>,
+  depth: usize,
+  /// Whether `{name}` placeholders are allowed, see [`parse_ast_template`].
+  allow_placeholders: bool,
+  /// Whether identifiers are copied instead of borrowed from the source,
+  /// see [`parse_ast_template`].
+  copy_idents: bool,
 }
 
 impl<'src> Parser<'src> {
@@ -121,6 +176,9 @@ impl<'src> Parser<'src> {
       errors: Vec::new(),
       indent: IndentStack::new(),
       state: State::default(),
+      depth: 0,
+      allow_placeholders: false,
+      copy_idents: false,
     }
   }
 
@@ -247,14 +305,15 @@ impl<'src> Parser<'src> {
     self.bump();
     while !self.current().is(Tok_Eof) {
       // break when exiting a block (dedent)
-      // but not in an if statement, because it is composed of multiple blocks
-      if self.dedent().is_ok() && ![Kw_Else, Kw_Elif].contains(&self.current().kind) {
+      // but not in an if or try statement, because it is composed of multiple blocks
+      if self.dedent().is_ok() && ![Kw_Else, Kw_Elif, Kw_Catch].contains(&self.current().kind) {
         break;
       }
 
       match self.current().kind {
         // break on keywords that begin statements
-        Kw_Import | Kw_From | Kw_Fn | Kw_Class | Kw_For | Kw_While | Kw_Loop | Kw_If => break,
+        Kw_Import | Kw_From | Kw_Fn | Kw_Class | Kw_For | Kw_While | Kw_Loop | Kw_If | Kw_Try
+        | Kw_With => break,
         // handle any errors
         Tok_Error => self.errors.push(SpannedError::new(
           format!("invalid token `{}`", self.lex.lexeme(self.current())),
@@ -273,8 +332,35 @@ mod expr;
 mod indent;
 mod module;
 mod stmt;
+mod template;
+
+pub use self::template::{parse_template, OUTPUT_VAR};
 
 impl<'a> Parser<'a> {
+  /// Maximum number of nested blocks and expressions.
+  ///
+  /// Unlike the remaining stack space, this does not depend on the platform,
+  /// so a given source either parses everywhere or nowhere.
+  const MAX_NESTING_DEPTH: usize = 256;
+
+  /// Calls `f` one nesting level deeper, failing with a syntax error instead
+  /// of recursing if the nesting limit has been reached.
+  fn nested<T>(
+    &mut self,
+    f: impl FnOnce(&mut Self) -> Result<T, SpannedError>,
+  ) -> Result<T, SpannedError> {
+    let span = self.current().span;
+    if self.depth >= Self::MAX_NESTING_DEPTH {
+      return Err(SpannedError::new("nesting limit reached", span));
+    }
+    self.check_recursion_limit(span)?;
+
+    self.depth += 1;
+    let result = f(self);
+    self.depth -= 1;
+    result
+  }
+
   // On average, a single parse_XXX() method consumes between 10 and 700 bytes of
   // stack space. Assuming ~50 recursive calls per dive and 700 bytes of stack
   // space per call, we'll require 50 * 700 = 35k bytes of stack space in order
@@ -2,12 +2,37 @@ use super::*;
 use crate::span::Spanned;
 
 impl<'src> Parser<'src> {
+  /// The `{name}` placeholder at the current token, if placeholders are
+  /// allowed and there is one.
+  pub(super) fn placeholder(&self) -> Option<&'src str> {
+    self
+      .allow_placeholders
+      .then(|| self.lex.placeholder())
+      .flatten()
+  }
+
   pub(super) fn ident(&mut self) -> Result<ast::Ident<'src>, SpannedError> {
+    if let Some(placeholder) = self.placeholder() {
+      let start = self.current().span.start;
+      self.bump();
+      self.bump();
+      self.bump();
+      return Ok(ast::Ident::new(
+        start..self.previous().span.end,
+        self.lexeme(placeholder),
+      ));
+    }
+
     self.expect(Lit_Ident)?;
-    Ok(ast::Ident::new(
-      self.previous().span,
-      Cow::from(self.lex.lexeme(self.previous())),
-    ))
+    let lexeme = self.lex.lexeme(self.previous());
+    Ok(ast::Ident::new(self.previous().span, self.lexeme(lexeme)))
+  }
+
+  fn lexeme(&self, lexeme: &'src str) -> Cow<'src, str> {
+    match self.copy_idents {
+      true => Cow::owned(lexeme.to_string()),
+      false => Cow::borrowed(lexeme),
+    }
   }
 
   pub(super) fn yield_(&mut self) -> Result<Spanned<ast::Yield<'src>>, SpannedError> {
@@ -17,7 +42,12 @@ impl<'src> Parser<'src> {
 
     self.expect(Kw_Yield)?;
     let start = self.previous().span.start;
-    let value = self.no_indent().ok().map(|_| self.expr()).transpose()?;
+    let value = self
+      .no_indent()
+      .ok()
+      .filter(|_| !self.current().is(Tok_Eof))
+      .map(|_| self.expr())
+      .transpose()?;
     let end = self.previous().span.end;
 
     let current_func = self
@@ -2,7 +2,22 @@ use super::*;
 
 impl<'src> Parser<'src> {
   pub(super) fn expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
-    self.maybe_expr()
+    self.nested(Self::pipe_expr)
+  }
+
+  fn pipe_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
+    let mut left = self.maybe_expr()?;
+    while self.no_indent().is_ok() && self.bump_if(Op_PipeMore) {
+      self.no_indent()?;
+      let right = self.maybe_expr()?;
+      left = ast::expr_binary(
+        left.span.start..right.span.end,
+        ast::BinaryOp::Pipe,
+        left,
+        right,
+      );
+    }
+    Ok(left)
   }
 
   fn maybe_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
@@ -145,7 +160,7 @@ impl<'src> Parser<'src> {
     self.bump(); // bump operator
     let start = self.previous().span.start;
     self.no_indent()?;
-    let right = self.unary_expr()?;
+    let right = self.nested(Self::unary_expr)?;
     Ok(ast::expr_unary(start..right.span.end, op, right))
   }
 
@@ -157,6 +172,14 @@ impl<'src> Parser<'src> {
           let args = self.call_args()?; // bumps `(`
           expr = ast::expr_call(expr.span.start..self.previous().span.end, expr, args);
         }
+        Tok_Question => {
+          self.bump(); // bump `?`
+          if !self.current().is(Brk_ParenL) {
+            fail!(@self.previous().span, "expected `(` after `?`, optional access is written as `?a.b`");
+          }
+          let args = self.call_args()?; // bumps `(`
+          expr = ast::expr_call_opt(expr.span.start..self.previous().span.end, expr, args);
+        }
         Brk_SquareL => {
           self.bump(); // bump `[`
           let key = self.expr()?;
@@ -175,8 +198,6 @@ impl<'src> Parser<'src> {
   }
 
   fn primary_expr(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
-    self.check_recursion_limit(self.current().span)?;
-
     if self.bump_if(Lit_None) {
       return Ok(ast::lit::none(self.previous().span));
     }
@@ -196,6 +217,11 @@ impl<'src> Parser<'src> {
       return ast::lit::float(token.span, self.lex.lexeme(token));
     }
 
+    if self.bump_if(Lit_Decimal) {
+      let token = self.previous();
+      return ast::lit::decimal(token.span, self.lex.lexeme(token));
+    }
+
     if self.bump_if(Lit_String) {
       let token = self.previous();
       match ast::lit::str(token.span, self.lex.lexeme(token)) {
@@ -220,6 +246,10 @@ impl<'src> Parser<'src> {
       return Ok(ast::expr_list(start..end, items));
     }
 
+    if self.placeholder().is_some() {
+      return Ok(ast::expr_get_var(self.ident()?));
+    }
+
     if self.bump_if(Brk_CurlyL) {
       let start = self.previous().span.start;
 
@@ -304,6 +334,10 @@ impl<'src> Parser<'src> {
   }
 
   fn table_key(&mut self) -> Result<ast::Expr<'src>, SpannedError> {
+    // placeholders stand for the key itself, rather than its name
+    if self.placeholder().is_some() {
+      return Ok(ast::expr_get_var(self.ident()?));
+    }
     if self.bump_if(Brk_SquareL) {
       let key = self.expr()?;
       self.expect(Brk_SquareR)?;
@@ -23,7 +23,10 @@ impl<'src> Parser<'src> {
       Kw_For => Some(self.for_loop_stmt()?),
       Kw_While => Some(self.while_loop_stmt()?),
       Kw_Loop => Some(self.loop_stmt()?),
+      Kw_Try => Some(self.try_stmt()?),
+      Kw_With => Some(self.with_stmt()?),
       Kw_Fn => Some(self.func_stmt()?),
+      Tok_At => Some(self.decorated_func_stmt()?),
       Kw_Class => Some(self.class_stmt()?),
       Kw_Import | Kw_From => Some(self.import_stmt()?),
       _ => None,
@@ -126,11 +129,20 @@ impl<'src> Parser<'src> {
     self.expect(Kw_For)?;
     let start = self.previous().span.start;
     self.no_indent()?;
-    let item = self.ident()?;
+    let mut item = self.ident()?;
+    // `await` is only special right before the item, so it may still be
+    // used as a variable name
+    let is_await = item == "await" && self.no_indent().is_ok() && self.current().is(Lit_Ident);
+    if is_await {
+      item = self.ident()?;
+    }
     self.no_indent()?;
     self.expect(Kw_In)?;
     self.no_indent()?;
-    let iter = self.for_iter()?;
+    let iter = match is_await {
+      true => ast::ForIter::Stream(self.expr()?),
+      false => self.for_iter()?,
+    };
     self.no_indent()?;
     self.expect(Tok_Colon)?;
     let body = self.loop_body()?;
@@ -138,7 +150,7 @@ impl<'src> Parser<'src> {
     Ok(ast::for_loop_stmt(start..end, item, iter, body))
   }
 
-  fn for_iter(&mut self) -> Result<ast::ForIter<'src>, SpannedError> {
+  pub(super) fn for_iter(&mut self) -> Result<ast::ForIter<'src>, SpannedError> {
     let start = self.expr()?;
     let inclusive = match self.current().kind {
       Op_Range => false,
@@ -149,10 +161,20 @@ impl<'src> Parser<'src> {
     self.bump(); // bump op
     self.no_indent()?;
     let end = self.expr()?;
+    // `by` is not a keyword, so that it may still be used as a name
+    let step = if self.current().is(Lit_Ident) && self.lex.lexeme(self.current()) == "by" {
+      self.no_indent()?;
+      self.bump(); // bump `by`
+      self.no_indent()?;
+      Some(self.expr()?)
+    } else {
+      None
+    };
     Ok(ast::ForIter::Range(ast::IterRange {
       start,
       end,
       inclusive,
+      step,
     }))
   }
 
@@ -172,10 +194,81 @@ impl<'src> Parser<'src> {
     self.expect(Kw_Loop)?;
     let start = self.previous().span.start;
     self.no_indent()?;
+    if self.bump_if(Tok_Colon) {
+      let body = self.loop_body()?;
+      let end = self.previous().span.end;
+      return Ok(ast::loop_stmt(start..end, body));
+    }
+    let count = self.expr()?;
+    self.no_indent()?;
     self.expect(Tok_Colon)?;
     let body = self.loop_body()?;
     let end = self.previous().span.end;
-    Ok(ast::loop_stmt(start..end, body))
+    Ok(ast::repeat_loop_stmt(start..end, count, body))
+  }
+
+  fn try_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    self.expect(Kw_Try)?;
+    let start = self.previous().span.start;
+    self.no_indent()?;
+    self.expect(Tok_Colon)?;
+    let body = self.body()?;
+    self.indent_eq()?; // `catch` on same indentation level
+    let mut catches = vec![self.catch_clause()?];
+    while self.indent_eq().is_ok() && self.current().is(Kw_Catch) {
+      if matches!(catches.last(), Some(ast::Catch { class: None, .. })) {
+        fail!(@self.current().span, "unreachable `catch`, the previous one catches every error");
+      }
+      catches.push(self.catch_clause()?);
+    }
+    let end = self.previous().span.end;
+    Ok(ast::try_stmt(start..end, body, catches))
+  }
+
+  fn catch_clause(&mut self) -> Result<ast::Catch<'src>, SpannedError> {
+    self.expect(Kw_Catch)?;
+    self.no_indent()?;
+    let (class, binding) = if self.current().is(Tok_Colon) {
+      (None, None)
+    } else {
+      // catch <class> as <binding>
+      // catch <binding>
+      let expr = self.expr()?;
+      self.no_indent()?;
+      if self.bump_if(Kw_As) {
+        self.no_indent()?;
+        let binding = self.ident()?;
+        self.no_indent()?;
+        (Some(expr), Some(binding))
+      } else if let ast::ExprKind::GetVar(get) = &*expr {
+        (None, Some(get.name.clone()))
+      } else {
+        fail!(@expr.span, "expected `<class> as <name>` or `<name>`");
+      }
+    };
+    self.expect(Tok_Colon)?;
+    let body = self.body()?;
+    Ok(ast::catch_clause(class, binding, body))
+  }
+
+  fn with_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    self.expect(Kw_With)?;
+    let start = self.previous().span.start;
+    self.no_indent()?;
+    let context = self.expr()?;
+    self.no_indent()?;
+    let binding = if self.bump_if(Kw_As) {
+      self.no_indent()?;
+      let binding = self.ident()?;
+      self.no_indent()?;
+      Some(binding)
+    } else {
+      None
+    };
+    self.expect(Tok_Colon)?;
+    let body = self.body()?;
+    let end = self.previous().span.end;
+    Ok(ast::with_stmt(start..end, context, binding, body))
   }
 
   fn loop_body(&mut self) -> Result<Vec<ast::Stmt<'src>>, SpannedError> {
@@ -198,6 +291,30 @@ impl<'src> Parser<'src> {
     Ok(ast::func_stmt(start..end, func))
   }
 
+  /// ```text
+  /// @decorator
+  /// fn name(...):
+  ///   ...
+  /// ```
+  fn decorated_func_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    let start = self.current().span.start;
+    let mut decorators = vec![];
+    while self.bump_if(Tok_At) {
+      self.no_indent()?;
+      decorators.push(self.expr()?);
+      self.indent_eq()?;
+    }
+    if !self.current().is(Kw_Fn) {
+      fail!(@self.current().span, "expected a function after decorators");
+    }
+    let mut stmt = self.func_stmt()?;
+    stmt.span = (start..stmt.span.end).into();
+    if let ast::StmtKind::Func(func) = &mut *stmt {
+      func.decorators = decorators;
+    }
+    Ok(stmt)
+  }
+
   fn func(&mut self, name: ast::Ident<'src>) -> Result<ast::Func<'src>, SpannedError> {
     let params = self.func_params()?;
     self.no_indent()?;
@@ -376,7 +493,10 @@ impl<'src> Parser<'src> {
   }
 
   fn body(&mut self) -> Result<Vec<ast::Stmt<'src>>, SpannedError> {
-    self.check_recursion_limit(self.current().span)?;
+    self.nested(Self::block)
+  }
+
+  fn block(&mut self) -> Result<Vec<ast::Stmt<'src>>, SpannedError> {
     if self.no_indent().is_ok() {
       Ok(vec![self.simple_stmt()?])
     } else {
@@ -400,6 +520,7 @@ impl<'src> Parser<'src> {
       Kw_Break => self.break_stmt(),
       Kw_Yield => self.yield_().map(ast::yield_stmt),
       Kw_Print => self.print_stmt(),
+      Kw_Throw => self.throw_stmt(),
       _ => self.expr_stmt(),
     }
   }
@@ -419,7 +540,12 @@ impl<'src> Parser<'src> {
     if current_fn_name == "init" && self.state.current_class.is_some() && self.no_indent().is_ok() {
       fail!(@self.current().span, "return in `init` may not return a value");
     }
-    let value = self.no_indent().ok().map(|_| self.expr()).transpose()?;
+    let value = self
+      .no_indent()
+      .ok()
+      .filter(|_| !self.current().is(Tok_Eof))
+      .map(|_| self.expr())
+      .transpose()?;
     let end = self.previous().span.end;
     Ok(ast::return_stmt(start..end, value))
   }
@@ -461,33 +587,124 @@ impl<'src> Parser<'src> {
     Ok(ast::print_stmt(start.join(end), values))
   }
 
+  fn throw_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
+    self.expect(Kw_Throw)?;
+    let start = self.previous().span.start;
+    self.no_indent()?;
+    let value = self.expr()?;
+    let end = self.previous().span.end;
+    Ok(ast::throw_stmt(start..end, value))
+  }
+
   fn expr_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
     self.assign_stmt()
   }
 
   fn assign_stmt(&mut self) -> Result<ast::Stmt<'src>, SpannedError> {
-    let target = self.expr()?;
+    let mut targets = self.assign_list()?;
 
     'assign: {
       if self.no_indent().is_ok() {
         let Some(kind) = self.assign_kind() else {
           break 'assign;
         };
-        let error_span = target.span.start..self.previous().span.end;
+        let error_span = targets[0].span.start..self.previous().span.end;
         self.no_indent()?;
-        let value = self.expr()?;
+        let values = self.assign_list()?;
+        let is_multi = targets.len() > 1
+          || values.len() > 1
+          || self.no_indent().is_ok() && self.current().is(Op_Equal);
+        if is_multi {
+          if let ast::AssignKind::Decl = kind {
+            fail!(@error_span, "cannot declare multiple variables at once");
+          }
+          if let ast::AssignKind::Op(Some(_)) = kind {
+            fail!(@error_span, "only `=` may assign to multiple targets");
+          }
+          return self.multi_assign_stmt(targets, values);
+        }
+
+        let target = targets.pop().unwrap();
+        let value = values.into_iter().next().unwrap();
+        let keyword = target.keyword();
         let Some(stmt) = ast::assign(target, kind, value) else {
-          let msg = match kind {
-            ast::AssignKind::Decl => "invalid variable declaration",
-            ast::AssignKind::Op(_) => "invalid assignment target",
-          };
-          fail!(@error_span, "{msg}");
+          match (kind, keyword) {
+            (ast::AssignKind::Decl, Some(keyword)) => fail!(
+              @error_span,
+              "cannot declare a variable named `{keyword}`, because it is a keyword"
+            ),
+            (ast::AssignKind::Op(_), Some(keyword)) => fail!(
+              @error_span,
+              "cannot assign to `{keyword}`, because it is a keyword"
+            ),
+            (ast::AssignKind::Decl, None) => fail!(@error_span, "invalid variable declaration"),
+            (ast::AssignKind::Op(_), None) => fail!(@error_span, "invalid assignment target"),
+          }
         };
         return Ok(stmt);
       }
     }
 
-    Ok(ast::expr_stmt(target))
+    if targets.len() > 1 {
+      let span = targets[0].span.start..targets.last().unwrap().span.end;
+      fail!(@span, "expected `=` after a list of assignment targets");
+    }
+    Ok(ast::expr_stmt(targets.pop().unwrap()))
+  }
+
+  /// `a, b = c, d = e, f`, where `targets` is `a, b` and `values` is `c, d`.
+  /// Every list except for the last one is a list of targets.
+  fn multi_assign_stmt(
+    &mut self,
+    targets: Vec<ast::Expr<'src>>,
+    mut values: Vec<ast::Expr<'src>>,
+  ) -> Result<ast::Stmt<'src>, SpannedError> {
+    let start = targets[0].span.start;
+    let mut lists = vec![targets];
+    while self.no_indent().is_ok() && self.bump_if(Op_Equal) {
+      self.no_indent()?;
+      let next = self.assign_list()?;
+      lists.push(std::mem::replace(&mut values, next));
+    }
+    let end = self.previous().span.end;
+
+    let mut groups = Vec::with_capacity(lists.len());
+    for list in lists {
+      if list.len() != values.len() {
+        let span = list[0].span.start..list.last().unwrap().span.end;
+        fail!(
+          @span,
+          "expected {} values, found {}",
+          list.len(),
+          values.len()
+        );
+      }
+      let mut group = Vec::with_capacity(list.len());
+      for target in list {
+        let span = target.span;
+        let keyword = target.keyword();
+        let Some(target) = ast::assign_target(target) else {
+          match keyword {
+            Some(keyword) => fail!(@span, "cannot assign to `{keyword}`, because it is a keyword"),
+            None => fail!(@span, "invalid assignment target"),
+          }
+        };
+        group.push(target);
+      }
+      groups.push(group);
+    }
+
+    Ok(ast::multi_assign_stmt(start..end, groups, values))
+  }
+
+  /// One or more expressions separated by commas, on either side of an `=`.
+  fn assign_list(&mut self) -> Result<Vec<ast::Expr<'src>>, SpannedError> {
+    let mut list = vec![self.expr()?];
+    while self.no_indent().is_ok() && self.bump_if(Tok_Comma) {
+      self.no_indent()?;
+      list.push(self.expr()?);
+    }
+    Ok(list)
   }
 
   fn assign_kind(&mut self) -> Option<ast::AssignKind> {
@@ -0,0 +1,289 @@
+//! Text templates.
+//!
+//! A template is text with embedded `{{ expr }}` expressions and
+//! `{% ... %}` blocks:
+//!
+//! ```text
+//! Hello, {{ name }}!
+//! {% for item in items %}
+//!   - {{ item }}
+//! {% endfor %}
+//! {% if items.len() == 0 %}nothing to do{% else %}all done{% endif %}
+//! ```
+//!
+//! Blocks are `if`/`elif`/`else`/`endif` and `for`/`endfor`, with the same
+//! syntax as the statements they stand for.
+//!
+//! A template is parsed into a regular module, so the expressions go
+//! through the same parser and emitter as scripts. Every piece of text and
+//! the value of every expression is appended to the list in
+//! [`OUTPUT_VAR`], and the module evaluates to the joined list.
+
+use super::*;
+
+/// Name of the variable which holds the rendered pieces of a template.
+///
+/// It is not a valid identifier, so templates cannot access it.
+pub const OUTPUT_VAR: &str = "@out";
+
+pub fn parse_template(global: Global, src: &str) -> Result<ast::Module<'_>, SyntaxError> {
+  let mut template = Template {
+    global,
+    src,
+    pieces: pieces(src)
+      .map_err(|e| SyntaxError::new(vec![e]))?
+      .into_iter(),
+  };
+  template.module().map_err(|e| SyntaxError::new(vec![e]))
+}
+
+#[derive(Clone, Copy)]
+enum Piece {
+  Text(Span),
+  /// `{{ expr }}`, the span excludes the braces.
+  Expr(Span),
+  /// `{% tag %}`, the span excludes the braces.
+  Tag(Span),
+}
+
+/// Split `src` into text, expressions and tags.
+fn pieces(src: &str) -> Result<Vec<Piece>, SpannedError> {
+  let mut pieces = vec![];
+  let mut pos = 0;
+  while pos < src.len() {
+    let Some(open) = [src[pos..].find("{{"), src[pos..].find("{%")]
+      .into_iter()
+      .flatten()
+      .min()
+      .map(|offset| pos + offset)
+    else {
+      pieces.push(Piece::Text((pos..src.len()).into()));
+      break;
+    };
+    if open > pos {
+      pieces.push(Piece::Text((pos..open).into()));
+    }
+
+    let (close, piece): (_, fn(Span) -> Piece) = match &src[open..open + 2] {
+      "{{" => ("}}", Piece::Expr),
+      _ => ("%}", Piece::Tag),
+    };
+    let start = open + 2;
+    let Some(end) = src[start..].find(close).map(|offset| start + offset) else {
+      return Err(SpannedError::new(
+        format!(
+          "unterminated `{}`, expected a closing `{close}`",
+          &src[open..start]
+        ),
+        open..start,
+      ));
+    };
+    pieces.push(piece((start..end).into()));
+    pos = end + 2;
+  }
+  Ok(pieces)
+}
+
+enum Tag<'src> {
+  If(ast::Expr<'src>),
+  Elif(ast::Expr<'src>),
+  Else,
+  EndIf,
+  For(ast::Ident<'src>, ast::ForIter<'src>),
+  EndFor,
+}
+
+impl<'src> Tag<'src> {
+  fn name(&self) -> &'static str {
+    match self {
+      Tag::If(_) => "if",
+      Tag::Elif(_) => "elif",
+      Tag::Else => "else",
+      Tag::EndIf => "endif",
+      Tag::For(..) => "for",
+      Tag::EndFor => "endfor",
+    }
+  }
+}
+
+struct Template<'src> {
+  global: Global,
+  src: &'src str,
+  pieces: std::vec::IntoIter<Piece>,
+}
+
+impl<'src> Template<'src> {
+  fn module(&mut self) -> Result<ast::Module<'src>, SpannedError> {
+    let (mut body, end) = self.body()?;
+    if let Some((tag, span)) = end {
+      fail!(@span, "unexpected `{{% {} %}}`", tag.name());
+    }
+
+    let end = Span::from(self.src.len()..self.src.len());
+    let output = output_method(end, "join", vec![string(end, "")]);
+    body.push(ast::expr_stmt(output));
+
+    let mut module = ast::Module::new();
+    module.body = body;
+    Ok(module)
+  }
+
+  /// Parse pieces until a tag which ends the current block, or the end of
+  /// the template.
+  #[allow(clippy::type_complexity)]
+  fn body(&mut self) -> Result<(Vec<ast::Stmt<'src>>, Option<(Tag<'src>, Span)>), SpannedError> {
+    let mut body = vec![];
+    while let Some(piece) = self.pieces.next() {
+      match piece {
+        Piece::Text(span) => {
+          let text = string(span, &self.src[span.range()]);
+          body.push(ast::expr_stmt(output_method(span, "push", vec![text])));
+        }
+        Piece::Expr(span) => {
+          let mut parser = self.parser(span);
+          let expr = parser.expr()?;
+          parser.finish("}}")?;
+          let to_str = ast::expr_get_var(ast::Ident::new(span, Cow::from("to_str")));
+          let value = ast::expr_call(span, to_str, vec![expr]);
+          body.push(ast::expr_stmt(output_method(span, "push", vec![value])));
+        }
+        Piece::Tag(span) => match self.tag(span)? {
+          Tag::If(cond) => body.push(self.if_block(cond, span)?),
+          Tag::For(item, iter) => body.push(self.for_block(item, iter, span)?),
+          tag => return Ok((body, Some((tag, span)))),
+        },
+      }
+    }
+    Ok((body, None))
+  }
+
+  fn if_block(
+    &mut self,
+    cond: ast::Expr<'src>,
+    span: Span,
+  ) -> Result<ast::Stmt<'src>, SpannedError> {
+    let mut branches = vec![];
+    let mut cond = cond;
+    loop {
+      let (body, end) = self.body()?;
+      branches.push(ast::branch(cond, body));
+      match end {
+        Some((Tag::Elif(next), _)) => cond = next,
+        Some((Tag::Else, _)) => {
+          let (default, end) = self.body()?;
+          let end = expect_end(end, Tag::EndIf, span)?;
+          return Ok(ast::if_stmt(span.start..end.end, branches, Some(default)));
+        }
+        end => {
+          let end = expect_end(end, Tag::EndIf, span)?;
+          return Ok(ast::if_stmt(span.start..end.end, branches, None));
+        }
+      }
+    }
+  }
+
+  fn for_block(
+    &mut self,
+    item: ast::Ident<'src>,
+    iter: ast::ForIter<'src>,
+    span: Span,
+  ) -> Result<ast::Stmt<'src>, SpannedError> {
+    let (body, end) = self.body()?;
+    let end = expect_end(end, Tag::EndFor, span)?;
+    Ok(ast::for_loop_stmt(span.start..end.end, item, iter, body))
+  }
+
+  fn tag(&self, span: Span) -> Result<Tag<'src>, SpannedError> {
+    let mut parser = self.parser(span);
+    let token = parser.current().clone();
+    let tag = match token.kind {
+      Kw_If => {
+        parser.bump();
+        Tag::If(parser.expr()?)
+      }
+      Kw_Elif => {
+        parser.bump();
+        Tag::Elif(parser.expr()?)
+      }
+      Kw_Else => {
+        parser.bump();
+        Tag::Else
+      }
+      Kw_For => {
+        parser.bump();
+        let item = parser.ident()?;
+        parser.expect(Kw_In)?;
+        Tag::For(item, parser.for_iter()?)
+      }
+      Lit_Ident if parser.lex.lexeme(&token) == "endif" => {
+        parser.bump();
+        Tag::EndIf
+      }
+      Lit_Ident if parser.lex.lexeme(&token) == "endfor" => {
+        parser.bump();
+        Tag::EndFor
+      }
+      _ => fail!(@span, "unknown template tag `{}`", self.src[span.range()].trim()),
+    };
+    parser.finish("%}")?;
+    Ok(tag)
+  }
+
+  /// A parser for the code in `span`. Indentation is meaningless inside of
+  /// a template, so it is ignored.
+  fn parser(&self, span: Span) -> Parser<'src> {
+    let lexer = Lexer::with_offset(&self.src[..span.end], span.start);
+    let mut parser = Parser::new(self.global.clone(), lexer);
+    parser.state = parser.state.with_ignore_indent();
+    parser
+  }
+}
+
+impl<'src> Parser<'src> {
+  /// Ensure that everything until the closing delimiter was parsed.
+  fn finish(mut self, close: &str) -> Result<(), SpannedError> {
+    if !self.errors.is_empty() {
+      return Err(self.errors.remove(0));
+    }
+    if !self.current().is(Tok_Eof) {
+      fail!(@self.current().span, "expected `{close}`");
+    }
+    Ok(())
+  }
+}
+
+/// Ensure that a block which started at `open` was closed by `expected`.
+fn expect_end(end: Option<(Tag, Span)>, expected: Tag, open: Span) -> Result<Span, SpannedError> {
+  match end {
+    Some((tag, span)) if tag.name() == expected.name() => Ok(span),
+    Some((tag, span)) => fail!(
+      @span,
+      "unexpected `{{% {} %}}`, expected `{{% {} %}}`",
+      tag.name(),
+      expected.name()
+    ),
+    None => fail!(
+      @open,
+      "unclosed block, expected `{{% {} %}}`",
+      expected.name()
+    ),
+  }
+}
+
+/// `@out.<method>(args)`
+fn output_method<'src>(
+  span: Span,
+  method: &'static str,
+  args: Vec<ast::Expr<'src>>,
+) -> ast::Expr<'src> {
+  let output = ast::expr_get_var(ast::Ident::new(span, Cow::from(OUTPUT_VAR)));
+  let method = ast::expr_get_field(span, output, ast::Ident::new(span, Cow::from(method)));
+  ast::expr_call(span, method, args)
+}
+
+fn string<'src>(span: Span, value: &'src str) -> ast::Expr<'src> {
+  ast::Expr::new(
+    span,
+    ast::ExprKind::Literal(Box::new(ast::Literal::String(Cow::from(value)))),
+  )
+}
@@ -159,6 +159,33 @@ fn binary_expr() {
   }
 }
 
+#[test]
+fn pipe_expr() {
+  check_expr!(r#"a |> b"#);
+  check_expr!(r#"a |> b(c) |> d.e"#);
+  check_expr!(r#"a ?? b |> c"#);
+  check_expr!(r#"?a.b |> c"#);
+
+  check_error! {
+    r#"
+      a |>
+        b
+    "#
+  }
+}
+
+#[test]
+fn opt_call_expr() {
+  check_expr!(r#"a?()"#);
+  check_expr!(r#"a.b?(c)?()"#);
+
+  check_error! {
+    r#"
+      a?.b
+    "#
+  }
+}
+
 #[test]
 fn unary_expr() {
   check_expr!(r#"+a"#);
@@ -342,6 +369,63 @@ fn assign_expr() {
   check_error! {
     r#"a() = b"#
   }
+
+  check_error! {
+    r#"none = 5"#
+  }
+
+  check_error! {
+    r#"false := b"#
+  }
+
+  check_error! {
+    r#"
+      class T:
+        fn f(self):
+          self = b
+          super ??= b
+    "#
+  }
+}
+
+#[test]
+fn multi_assign_stmt() {
+  check_module! {
+    r#"
+      a, b = b, a
+      a.b, c[d] = c[d], a.b
+      a = b.c = d[e] = f
+      a, b = c, d = e, f
+    "#
+  }
+
+  check_error! {
+    r#"a, b = c"#
+  }
+
+  check_error! {
+    r#"a = b, c = d"#
+  }
+
+  check_error! {
+    r#"a, b := c, d"#
+  }
+
+  check_error! {
+    r#"a, b += c, d"#
+  }
+
+  check_error! {
+    r#"a, none = c, d"#
+  }
+
+  check_error! {
+    r#"a, b() = c, d"#
+  }
+
+  check_error! {
+    r#"a, b"#
+  }
 }
 
 #[test]
@@ -505,6 +589,10 @@ fn loop_stmts() {
       for i in a()..=b(): pass
       for i in a()..=b():
         pass
+      for i in 10..0 by -1: pass
+      for i in a()..=b() by c():
+        pass
+      for i in 0..by: pass
     "#
   }
 
@@ -523,6 +611,106 @@ fn loop_stmts() {
           a
     "#
   }
+
+  check_module! {
+    r#"
+      loop 10: pass
+      loop n + 1:
+        pass
+    "#
+  }
+
+  check_error! {
+    r#"
+      loop 10
+        pass
+    "#
+  }
+
+  check_module! {
+    r#"
+      for await row in rows(): pass
+      for await in values:
+        pass
+    "#
+  }
+}
+
+#[test]
+fn try_stmt() {
+  check_module! {
+    r#"
+      try: a()
+      catch: pass
+      try:
+        a()
+      catch e:
+        print e
+    "#
+  }
+
+  check_error! {
+    r#"
+      try:
+        a()
+    "#
+  }
+
+  check_error! {
+    r#"
+      try:
+        a()
+      catch e
+        pass
+    "#
+  }
+}
+
+#[test]
+fn try_stmt_catch_class() {
+  check_module! {
+    r#"
+      try:
+        throw Error("a")
+      catch errors.NetworkError as e:
+        print e
+      catch Error as e: pass
+      catch: pass
+    "#
+  }
+
+  check_error! {
+    r#"
+      try: pass
+      catch e: pass
+      catch Error as e: pass
+    "#
+  }
+
+  check_error! {
+    r#"
+      try: pass
+      catch a.b: pass
+    "#
+  }
+}
+
+#[test]
+fn with_stmt() {
+  check_module! {
+    r#"
+      with open("a") as f: pass
+      with lock:
+        print "locked"
+    "#
+  }
+
+  check_error! {
+    r#"
+      with open("a") as:
+        pass
+    "#
+  }
 }
 
 #[test]
@@ -558,6 +746,40 @@ fn func_stmt() {
   }
 }
 
+#[test]
+fn decorated_func_stmt() {
+  check_module! {
+    r#"
+      @memo
+      fn f(a): pass
+
+      @outer
+      @inner(1)
+      fn g():
+        @memo
+        fn h(): pass
+    "#
+  }
+
+  check_error! {
+    r#"
+      @memo
+      class T: pass
+    "#
+  }
+  check_error! {
+    r#"
+      @memo
+    "#
+  }
+  check_error! {
+    r#"
+      @memo
+        fn f(): pass
+    "#
+  }
+}
+
 #[test]
 fn ctrl_stmt() {
   check_module! {
@@ -1083,6 +1305,82 @@ fn whole_module() {
   }
 }
 
+fn nesting_error(input: &str) -> Option<String> {
+  let global = Global::default();
+  match parse(global, input) {
+    Ok(_) => None,
+    Err(e) => Some(e.errors()[0].message.clone()),
+  }
+}
+
+#[test]
+fn nesting_limit() {
+  let depth = 10_000;
+  let inputs = [
+    format!("{}0{}", "(".repeat(depth), ")".repeat(depth)),
+    format!("{}0{}", "[".repeat(depth), "]".repeat(depth)),
+    format!("{}0{}", "{a:".repeat(depth), "}".repeat(depth)),
+    format!("{}0{}", "f(".repeat(depth), ")".repeat(depth)),
+    format!("a{}0{}", "[a".repeat(depth), "]".repeat(depth)),
+    format!("{}1", "-".repeat(depth)),
+    format!("{}1", "!".repeat(depth)),
+    (0..depth)
+      .map(|i| format!("{}if true:\n", " ".repeat(i)))
+      .collect::<String>()
+      + &" ".repeat(depth)
+      + "pass",
+    (0..depth)
+      .map(|i| format!("{}fn f():\n", " ".repeat(i)))
+      .collect::<String>()
+      + &" ".repeat(depth)
+      + "pass",
+  ];
+  for input in inputs.iter() {
+    assert_eq!(
+      nesting_error(input).as_deref(),
+      Some("nesting limit reached"),
+      "{}",
+      &input[..32]
+    );
+  }
+
+  // anything below the limit is fine
+  let depth = 120;
+  let input = format!("{}0{}", "(".repeat(depth), ")".repeat(depth));
+  assert_eq!(nesting_error(&input), None);
+  let input = (0..depth)
+    .map(|i| format!("{}if true:\n", " ".repeat(i)))
+    .collect::<String>()
+    + &" ".repeat(depth)
+    + "pass";
+  assert_eq!(nesting_error(&input), None);
+}
+
+#[test]
+fn fuzz_adversarial_input() {
+  use crate::internal::stdlib::random::Rng;
+
+  const FRAGMENTS: &[&str] = &[
+    "(", ")", "[", "]", "{", "}", ":", ",", ".", "?", "?.", "=", ":=", "+", "-", "!", "**", "??",
+    "..", "..=", "a", "0", "1.5", "\"s\"", "if", "elif", "else", "for", "in", "while", "loop",
+    "fn", "class", "try", "catch", "return", "yield", "break", "continue", "print", "import",
+    "from", "self", "super", "pass", "none", "\n", "\n  ", "\n    ", " ", "#", "@",
+  ];
+
+  let mut rng = Rng::new(0x5eed);
+  for _ in 0..2000 {
+    let len = rng.below(256) as usize;
+    let mut input = String::new();
+    for _ in 0..len {
+      let fragment = FRAGMENTS[rng.below(FRAGMENTS.len() as u64) as usize];
+      input.push_str(fragment);
+      input.push(' ');
+    }
+    // may succeed or fail, but must never panic or overflow the stack
+    let _ = parse(Global::default(), &input);
+  }
+}
+
 /* #[test]
 fn _temp() {
   check_error! {
@@ -0,0 +1,109 @@
+//! File-level directives, written as comments at the top of a script:
+//!
+//! ```text
+//! # pragma: strict
+//! # pragma: no-import
+//! print "hello"
+//! ```
+//!
+//! Pragmas let a script opt into stricter checks on its own, on top of
+//! whatever the host configured. They can only take something away, so a
+//! script cannot use them to do anything the host does not allow.
+//!
+//! They have to come before any code, after the frontmatter if there is
+//! one. Other comments and blank lines may be mixed in with them. Only the
+//! lines before the first line of code are read, so anything after it
+//! which looks like a pragma, for example in a multi-line string, is not
+//! one.
+
+use super::frontmatter::Lines;
+use crate::internal::vm::global::Construct;
+use crate::span::SpannedError;
+
+const PREFIX: &str = "pragma:";
+
+/// The constructs which have a `no-*` pragma, and its name.
+const FORBIDDABLE: [(&str, Construct); 8] = [
+  ("no-import", Construct::Import),
+  ("no-class", Construct::Class),
+  ("no-function", Construct::Function),
+  ("no-for", Construct::For),
+  ("no-while", Construct::While),
+  ("no-loop", Construct::Loop),
+  ("no-try", Construct::Try),
+  ("no-with", Construct::With),
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pragmas {
+  /// `strict`: reject reads of globals which are never defined, like
+  /// [`HebiBuilder::strict_globals`][crate::HebiBuilder::strict_globals].
+  pub strict: bool,
+  /// `no-*`: constructs which the script may not use.
+  pub forbidden: Vec<Construct>,
+}
+
+impl Pragmas {
+  pub fn forbids(&self, construct: Construct) -> bool {
+    self.forbidden.contains(&construct)
+  }
+
+  /// The names of the pragmas, in a fixed order.
+  pub fn names(&self) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if self.strict {
+      names.push("strict");
+    }
+    for (name, construct) in FORBIDDABLE {
+      if self.forbids(construct) {
+        names.push(name);
+      }
+    }
+    names
+  }
+}
+
+/// The name of the pragma which forbids `construct`.
+pub fn forbidding(construct: Construct) -> &'static str {
+  FORBIDDABLE
+    .iter()
+    .find(|(_, c)| *c == construct)
+    .map(|(name, _)| *name)
+    .unwrap_or_default()
+}
+
+/// Read the pragmas of `src`, where the code starts at the offset `start`.
+pub fn parse(src: &str, start: usize) -> Result<Pragmas, SpannedError> {
+  let mut pragmas = Pragmas::default();
+
+  for (offset, line) in Lines::new(src, start) {
+    let trimmed = line.trim();
+    if !trimmed.is_empty() && !trimmed.starts_with('#') {
+      break;
+    }
+    let pragma = trimmed
+      .strip_prefix('#')
+      .and_then(|comment| comment.trim_start().strip_prefix(PREFIX));
+    let Some(name) = pragma else {
+      continue;
+    };
+
+    let span = offset..offset + line.len();
+    match name.trim() {
+      "strict" => pragmas.strict = true,
+      name => match FORBIDDABLE.iter().find(|(n, _)| *n == name) {
+        Some((_, construct)) => {
+          if !pragmas.forbids(*construct) {
+            pragmas.forbidden.push(*construct);
+          }
+        }
+        None => return Err(SpannedError::new(format!("unknown pragma `{name}`"), span)),
+      },
+    }
+  }
+
+  Ok(pragmas)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,56 @@
+use super::*;
+
+fn names(src: &str) -> Vec<&'static str> {
+  parse(src, 0).unwrap().names()
+}
+
+fn error(src: &str) -> String {
+  let e = parse(src, 0).unwrap_err();
+  format!("{} at {}", e.message, e.span)
+}
+
+#[test]
+fn no_pragmas() {
+  assert_eq!(parse("print 1\n", 0).unwrap(), Pragmas::default());
+  assert_eq!(parse("", 0).unwrap(), Pragmas::default());
+  assert_eq!(names("# just a comment\nprint 1\n"), Vec::<&str>::new());
+}
+
+#[test]
+fn pragma_header() {
+  let src = "#!/usr/bin/env hebi\n# pragma: strict\n\n# a comment\n#pragma:no-import\r\n  # pragma: no-while  \nprint 1\n";
+  let pragmas = parse(src, 0).unwrap();
+  assert!(pragmas.strict);
+  assert_eq!(pragmas.forbidden, [Construct::Import, Construct::While]);
+  assert_eq!(pragmas.names(), ["strict", "no-import", "no-while"]);
+
+  // repeating a pragma is harmless
+  assert_eq!(names("# pragma: no-for\n# pragma: no-for\n"), ["no-for"]);
+}
+
+#[test]
+fn pragma_after_frontmatter() {
+  let src = "---\n# pragma: strict\n---\n# pragma: no-try\n";
+  // pragmas are read from where the code starts, so the comment in the
+  // frontmatter is not one
+  let start = super::super::frontmatter::parse(src).unwrap().end;
+  assert_eq!(parse(src, start).unwrap().names(), ["no-try"]);
+}
+
+#[test]
+fn pragma_errors() {
+  assert_eq!(error("# pragma: fast\n"), "unknown pragma `fast` at 0..14");
+}
+
+#[test]
+fn pragma_after_code() {
+  // only the header is read, so these are ordinary comments and strings
+  assert_eq!(
+    names("# pragma: strict\nprint 1\n# pragma: no-import\n"),
+    ["strict"]
+  );
+  assert_eq!(
+    names("s := \"\n# pragma: whatever\n\"\nprint s\n"),
+    Vec::<&str>::new()
+  );
+}
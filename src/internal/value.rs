@@ -8,21 +8,93 @@ mod portable;
 #[cfg(not(feature = "nanbox"))]
 pub use portable::Value;
 
+pub mod cmp;
 pub mod constant;
 
 use std::fmt::{Debug, Display};
 
+use crate::internal::object::class::ClassInstance;
+
+impl Value {
+  /// The name of the value's type, as returned by the `type_of` builtin.
+  pub fn type_name(&self) -> &'static str {
+    if self.is_float() {
+      "float"
+    } else if self.is_int() {
+      "int"
+    } else if self.is_bool() {
+      "bool"
+    } else if self.is_none() {
+      "none"
+    } else {
+      let object = unsafe { self.clone().to_any_unchecked() };
+      object.type_name()
+    }
+  }
+
+  /// Display the value, formatting floats according to `format`.
+  pub fn display(&self, format: FloatFormat) -> DisplayValue<'_> {
+    DisplayValue {
+      value: self,
+      format,
+    }
+  }
+}
+
 impl Default for Value {
   fn default() -> Self {
     Self::none()
   }
 }
 
+/// How floats are converted to strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatFormat {
+  /// The shortest representation which parses back to the same float.
+  /// Very large and very small numbers use exponent notation, and whole
+  /// numbers keep a trailing `.0`, e.g. `0.30000000000000004`, `1e300` and
+  /// `2.0`.
+  #[default]
+  Shortest,
+  /// A fixed number of digits after the decimal point.
+  Fixed(usize),
+}
+
+impl FloatFormat {
+  pub fn write(&self, f: &mut std::fmt::Formatter<'_>, v: f64) -> std::fmt::Result {
+    match self {
+      // `Debug` for floats is shortest round-trip, and unlike `Display`,
+      // switches to exponent notation for extreme values
+      FloatFormat::Shortest => write!(f, "{v:?}"),
+      FloatFormat::Fixed(precision) => write!(f, "{v:.precision$}"),
+    }
+  }
+}
+
+/// Displays a value with a specific [`FloatFormat`].
+pub struct DisplayValue<'a> {
+  value: &'a Value,
+  format: FloatFormat,
+}
+
+impl Display for DisplayValue<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(v) = self.value.clone().to_float() {
+      return self.format.write(f, v);
+    }
+    // data class instances display their fields, which may contain floats
+    if let Some(instance) = self.value.clone().to_object::<ClassInstance>() {
+      return instance.display(f, self.format);
+    }
+    Display::fmt(self.value, f)
+  }
+}
+
 impl Display for Value {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let v = self.clone();
     if let Some(v) = v.clone().to_float() {
-      write!(f, "{v}")?;
+      FloatFormat::Shortest.write(f, v)?;
     } else if let Some(v) = v.clone().to_int() {
       write!(f, "{v}")?;
     } else if let Some(v) = v.clone().to_bool() {
@@ -0,0 +1,175 @@
+//! Equality and ordering of values.
+//!
+//! - Any two values may be tested for equality. Values of different types
+//!   are never equal, except for ints and floats, which are equal if they
+//!   have the same numeric value (`1 == 1.0`). `nan` is not equal to
+//!   anything, including itself.
+//! - Ordering (`<`, `<=`, `>`, `>=`) is only defined between values of the
+//!   same type, where ints and floats count as the same type. Ordering
+//!   values of different types is an error, and so is ordering objects
+//!   which do not support comparison. Every ordering comparison involving
+//!   `nan` is `false`.
+//! - `false` is less than `true`, and `none` is equal to `none`.
+//! - Strings are ordered by their unicode code points. There is no
+//!   locale-aware collation.
+//! - Lists are ordered lexicographically by their items, which are compared
+//!   by the same rules, so two lists are unordered if the first pair of
+//!   items which are not equal is unordered (`[nan] < [1]` is `false`).
+//!
+//! Sorting uses [`total_cmp`], which is the same as the above, except that
+//! `nan` is equal to itself and greater than every other number, including
+//! when it is nested in a list.
+//!
+//! Comparing values which are nested more than [`MAX_DEPTH`] levels deep is
+//! an error, so that comparing values which contain themselves fails
+//! instead of overflowing the stack.
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+
+use super::Value;
+use crate::internal::error::Result;
+use crate::internal::object::{Any, Object, Ptr};
+use crate::public::Scope;
+
+const MAX_DEPTH: usize = 128;
+
+thread_local! {
+  /// How many objects are being compared on this thread.
+  static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Compare two objects with `f`, one level deeper than the current
+/// comparison.
+fn nested<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+  struct Restore(usize);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      DEPTH.with(|depth| depth.set(self.0));
+    }
+  }
+
+  let depth = DEPTH.with(Cell::get);
+  if depth >= MAX_DEPTH {
+    fail!(
+      "cannot compare values nested more than {MAX_DEPTH} levels deep, they may contain themselves"
+    );
+  }
+  let _restore = Restore(depth);
+  DEPTH.with(|d| d.set(depth + 1));
+  f()
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+  if let Some(v) = value.clone().to_int() {
+    Some(v as f64)
+  } else {
+    value.clone().to_float()
+  }
+}
+
+pub fn equals(scope: Scope<'_>, lhs: Value, rhs: Value) -> Result<bool> {
+  if let (Some(lhs), Some(rhs)) = (to_number(&lhs), to_number(&rhs)) {
+    return Ok(lhs == rhs);
+  }
+  if let (Some(lhs), Some(rhs)) = (lhs.clone().to_bool(), rhs.clone().to_bool()) {
+    return Ok(lhs == rhs);
+  }
+  if lhs.is_none() && rhs.is_none() {
+    return Ok(true);
+  }
+  if let (Some(lhs), Some(rhs)) = (lhs.to_any(), rhs.to_any()) {
+    if lhs.ptr_eq(&rhs) {
+      return Ok(true);
+    }
+    if lhs.ty() != rhs.ty() {
+      return Ok(false);
+    }
+    return nested(|| <Any as Object>::eq(scope, lhs, rhs));
+  }
+  Ok(false)
+}
+
+pub fn partial_cmp(scope: Scope<'_>, lhs: Value, rhs: Value) -> Result<Option<Ordering>> {
+  if let (Some(lhs), Some(rhs)) = (to_number(&lhs), to_number(&rhs)) {
+    return Ok(lhs.partial_cmp(&rhs));
+  }
+  if let Some((lhs, rhs)) = same_type_objects(&lhs, &rhs) {
+    return nested(|| <Any as Object>::partial_cmp(scope, lhs, rhs));
+  }
+  cmp_primitive(lhs, rhs).map(Some)
+}
+
+pub fn total_cmp(scope: Scope<'_>, lhs: Value, rhs: Value) -> Result<Ordering> {
+  if let (Some(lhs), Some(rhs)) = (to_number(&lhs), to_number(&rhs)) {
+    return Ok(match (lhs.is_nan(), rhs.is_nan()) {
+      (true, true) => Ordering::Equal,
+      (true, false) => Ordering::Greater,
+      (false, true) => Ordering::Less,
+      (false, false) => lhs.partial_cmp(&rhs).unwrap(),
+    });
+  }
+  if let Some((lhs, rhs)) = same_type_objects(&lhs, &rhs) {
+    return nested(|| <Any as Object>::cmp(scope, lhs, rhs));
+  }
+  cmp_primitive(lhs, rhs)
+}
+
+fn same_type_objects(lhs: &Value, rhs: &Value) -> Option<(Ptr<Any>, Ptr<Any>)> {
+  let (lhs, rhs) = (lhs.clone().to_any()?, rhs.clone().to_any()?);
+  (lhs.ty() == rhs.ty()).then_some((lhs, rhs))
+}
+
+fn cmp_primitive(lhs: Value, rhs: Value) -> Result<Ordering> {
+  if let (Some(lhs), Some(rhs)) = (lhs.clone().to_bool(), rhs.clone().to_bool()) {
+    return Ok(lhs.cmp(&rhs));
+  }
+  if lhs.is_none() && rhs.is_none() {
+    return Ok(Ordering::Equal);
+  }
+  fail!(
+    "cannot compare `{}` with `{}`",
+    lhs.type_name(),
+    rhs.type_name()
+  )
+}
+
+/// Lexicographic ordering of two sequences of values, using [`partial_cmp`].
+/// `None` if the first pair of items which are not equal is unordered.
+pub fn partial_cmp_seq(
+  scope: Scope<'_>,
+  mut lhs: impl Iterator<Item = Value>,
+  mut rhs: impl Iterator<Item = Value>,
+) -> Result<Option<Ordering>> {
+  loop {
+    match (lhs.next(), rhs.next()) {
+      (Some(a), Some(b)) => match partial_cmp(scope.clone(), a, b)? {
+        Some(Ordering::Equal) => continue,
+        ordering => return Ok(ordering),
+      },
+      (Some(_), None) => return Ok(Some(Ordering::Greater)),
+      (None, Some(_)) => return Ok(Some(Ordering::Less)),
+      (None, None) => return Ok(Some(Ordering::Equal)),
+    }
+  }
+}
+
+/// Lexicographic ordering of two sequences of values, using [`total_cmp`].
+pub fn cmp_seq(
+  scope: Scope<'_>,
+  mut lhs: impl Iterator<Item = Value>,
+  mut rhs: impl Iterator<Item = Value>,
+) -> Result<Ordering> {
+  loop {
+    match (lhs.next(), rhs.next()) {
+      (Some(a), Some(b)) => match total_cmp(scope.clone(), a, b)? {
+        Ordering::Equal => continue,
+        ordering => return Ok(ordering),
+      },
+      (Some(_), None) => return Ok(Ordering::Greater),
+      (None, Some(_)) => return Ok(Ordering::Less),
+      (None, None) => return Ok(Ordering::Equal),
+    }
+  }
+}
@@ -6,6 +6,8 @@ use std::ops::Deref;
 
 use super::Value;
 use crate::internal::bytecode::opcode as op;
+#[cfg(feature = "decimal")]
+use crate::internal::object::decimal::Decimal;
 use crate::internal::object::ptr::Ptr;
 use crate::internal::object::{ClassDescriptor, FunctionDescriptor, Str};
 
@@ -17,6 +19,8 @@ pub enum Constant {
   Class(Ptr<ClassDescriptor>),
   Offset(op::Offset),
   Float(NonNaNFloat),
+  #[cfg(feature = "decimal")]
+  Decimal(Ptr<Decimal>),
 }
 
 impl Constant {
@@ -30,6 +34,8 @@ impl Constant {
       Constant::Class(v) => Value::object(v),
       Constant::Offset(_) => panic!("cannot convert constant jump offset to value"),
       Constant::Float(v) => Value::float(v.value()),
+      #[cfg(feature = "decimal")]
+      Constant::Decimal(v) => Value::object(v),
     }
   }
 }
@@ -62,6 +68,8 @@ impl Display for Constant {
       Constant::Class(v) => Display::fmt(v, f),
       Constant::Offset(v) => Display::fmt(&v.0, f),
       Constant::Float(v) => Display::fmt(&v.0, f),
+      #[cfg(feature = "decimal")]
+      Constant::Decimal(v) => write!(f, "{v}d"),
     }
   }
 }
@@ -4,29 +4,37 @@ pub mod dispatch;
 pub mod global;
 pub mod thread;
 
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::future::Future;
+use std::path::Path;
 use std::ptr::NonNull;
+use std::sync::Arc;
+use std::time::Instant;
 
 use global::Global;
 use module::Module;
 
-use self::global::{Input, Output};
-use self::thread::{Stack, Thread};
+use self::global::{
+  Allocator, Capability, Construct, ImportRewriter, Input, Io, NativeLog, Output, Policy,
+};
+use self::thread::{Stack, Status, Thread};
 use super::error::{Error, Result};
 use super::object::function::Disassembly;
-use super::object::module::{ModuleId, ModuleLoader};
-use super::object::{builtin, module, Any, Function, List, Ptr, Str};
-use super::value::Value;
-use super::{codegen, syntax};
-use crate::public::NativeModule;
-use crate::span::SpannedError;
+use super::object::module::{ModuleDescriptor, ModuleId, ModuleKind, ModuleLoader};
+use super::object::{builtin, module, Any, Function, FunctionDescriptor, List, Ptr, Str};
+use super::value::{FloatFormat, Value};
+use super::{codegen, pack, stdlib, syntax};
+use crate::public::{CompileTimings, Metadata, NativeModule, SharedGlobals};
+use crate::span::{Source, SpannedError};
 use crate::Cow;
 
 pub struct Vm {
   pub(crate) global: Global,
   pub(crate) root: Thread,
   pub(crate) stack: NonNull<Stack>,
+  /// Threads created by [`Vm::spawn`], in the order they are resumed.
+  threads: VecDeque<Task>,
 }
 
 impl Debug for Vm {
@@ -34,6 +42,7 @@ impl Debug for Vm {
     f.debug_struct("Vm")
       .field("global", &self.global)
       .field("root", &self.root)
+      .field("threads", &self.threads.len())
       .finish()
   }
 }
@@ -52,18 +61,57 @@ impl module::ModuleLoader for DefaultModuleLoader {
 
 pub struct Config {
   pub module_loader: Option<Box<dyn ModuleLoader>>,
+  /// Source of the `input` builtin. If `None`, scripts may not read input.
   pub input: Option<Box<dyn Input>>,
   pub output: Option<Box<dyn Output>>,
+  /// Seed for the `random` module. If `None`, the generator
+  /// is seeded from the system's source of randomness.
+  pub seed: Option<u64>,
+  pub shared: Option<SharedGlobals>,
+  /// How `print`, `to_str` and `join` format floats.
+  pub float_format: FloatFormat,
+  /// Reject reads of globals which are never defined at compile time.
+  pub strict_globals: bool,
+  /// Inline calls to tiny module-level functions.
+  pub inline_functions: bool,
+  /// Statements which scripts may not use.
+  pub forbidden: Vec<Construct>,
+  /// Checks sensitive operations. If `None`, everything is allowed.
+  pub policy: Option<Box<dyn Policy>>,
+  /// Rewrites import paths before they are loaded. If `None`, they are
+  /// loaded as written.
+  pub rewrite_imports: Option<Box<ImportRewriter>>,
+  /// Keep count of live objects by type, which costs some time on every
+  /// allocation.
+  #[cfg(feature = "count_objects")]
+  pub count_objects: bool,
+  /// Where objects are allocated. If `None`, the global allocator is used.
+  pub allocator: Option<Arc<dyn Allocator>>,
+  /// Whether calls to native functions are recorded or replayed. If `None`,
+  /// they are neither.
+  pub native_log: Option<NativeLog>,
+  /// Write the time taken by each phase of every compile to the error
+  /// stream.
+  pub log_compile_phases: bool,
+  /// Let scripts read the name of the operating system from `sys.platform`.
+  pub expose_platform: bool,
+}
+
+/// Whether `HEBI_LOG_COMPILE_PHASES` asks for compile phases to be logged.
+pub(crate) fn log_compile_phases_from_env() -> bool {
+  std::env::var_os("HEBI_LOG_COMPILE_PHASES").is_some_and(|v| v != "0")
 }
 
 impl Config {
-  fn resolve(self) -> (Box<dyn ModuleLoader>, Box<dyn Input>, Box<dyn Output>) {
+  fn resolve(self) -> (Box<dyn ModuleLoader>, Io) {
     (
       self
         .module_loader
         .unwrap_or_else(|| Box::new(DefaultModuleLoader {})),
-      self.input.unwrap_or_else(|| Box::new(std::io::stdin())),
-      self.output.unwrap_or_else(|| Box::new(std::io::stdout())),
+      Io::new(
+        self.input,
+        self.output.unwrap_or_else(|| Box::new(std::io::stdout())),
+      ),
     )
   }
 }
@@ -72,8 +120,22 @@ impl Default for Config {
   fn default() -> Self {
     Self {
       module_loader: Some(Box::new(DefaultModuleLoader {})),
-      input: Some(Box::new(std::io::stdin())),
+      input: None,
       output: Some(Box::new(std::io::stdout())),
+      seed: None,
+      shared: None,
+      float_format: FloatFormat::default(),
+      strict_globals: false,
+      inline_functions: false,
+      forbidden: Vec::new(),
+      policy: None,
+      rewrite_imports: None,
+      #[cfg(feature = "count_objects")]
+      count_objects: false,
+      allocator: None,
+      native_log: None,
+      log_compile_phases: log_compile_phases_from_env(),
+      expose_platform: false,
     }
   }
 }
@@ -90,11 +152,37 @@ impl Vm {
     builtin::register_builtin_functions(&global);
     let stack = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(Stack::new()))) };
     let root = Thread::new(global.clone(), stack);
-    Self {
+    let mut vm = Self {
       global,
       root,
       stack,
+      threads: VecDeque::new(),
+    };
+    stdlib::register_std_modules(&mut vm);
+    if let Some(shared) = vm.global.shared().cloned() {
+      for module in shared.native_modules() {
+        vm.register(module);
+      }
+      shared.define_constants(&vm.global);
+    }
+    vm
+  }
+
+  /// A VM with a copy of the state of this one, see
+  /// [`Hebi::fork`][crate::Hebi::fork].
+  pub fn fork(&self) -> Result<Self> {
+    if !self.threads.is_empty() {
+      fail!("cannot fork a VM while spawned threads are running");
     }
+    let global = self.global.fork()?;
+    let stack = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(Stack::new()))) };
+    let root = Thread::new(global.clone(), stack);
+    Ok(Self {
+      global,
+      root,
+      stack,
+      threads: VecDeque::new(),
+    })
   }
 
   pub async fn eval(&mut self, code: &str) -> Result<Value> {
@@ -102,19 +190,171 @@ impl Vm {
     self.entry(chunk).await
   }
 
-  pub fn compile(&self, code: &str) -> Result<Chunk> {
-    let ast = syntax::parse(self.global.clone(), code).map_err(Error::Syntax)?;
-    let module = codegen::emit(self.global.clone(), &ast, "__main__", true);
-    let module_id = ModuleId::global();
+  /// Evaluate `code` with `vars` bound to local variables of the script.
+  ///
+  /// The variables only exist for this evaluation, so unlike globals they
+  /// do not leak into any later evaluations.
+  pub async fn eval_with(&mut self, code: &str, vars: &[(&str, Value)]) -> Result<Value> {
+    let names = vars.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    let values = vars
+      .iter()
+      .map(|(_, value)| value.clone())
+      .collect::<Vec<_>>();
+    let chunk = compile_with_vars(&self.global, code, &names)?;
+    let result = self.root.call(chunk.main.into_any(), &values).await;
+    self.flush_output(result).await
+  }
+
+  /// Render the text template `src`, with `vars` bound to local variables
+  /// of the template.
+  pub async fn render(&mut self, src: &str, vars: &[(&str, Value)]) -> Result<String> {
+    let ast = syntax::parse_template(self.global.clone(), src).map_err(Error::Syntax)?;
+    let mut names = vars.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    names.push(syntax::parser::OUTPUT_VAR);
+    let module = codegen::emit_with_vars(self.global.clone(), &ast, "__template__", true, &names)
+      .map_err(Error::Syntax)?;
     let upvalues = self.global.alloc(List::new());
-    let main = module.root.clone();
-    let main = self.global.alloc(Function::new(main, upvalues, module_id));
+    let main = self.global.alloc(Function::new(
+      module.root.clone(),
+      upvalues,
+      ModuleId::global(),
+    ));
 
-    Ok(Chunk { main })
+    let mut values = vars
+      .iter()
+      .map(|(_, value)| value.clone())
+      .collect::<Vec<_>>();
+    values.push(Value::object(self.global.alloc(List::new())));
+    let result = self.root.call(main.into_any(), &values).await;
+    let value = self.flush_output(result).await?;
+    match value.to_object::<Str>() {
+      Some(str) => Ok(str.as_str().to_string()),
+      None => unreachable!("template did not render to a string"),
+    }
+  }
+
+  pub fn compile(&self, code: &str) -> Result<Chunk> {
+    compile_with_vars(&self.global, code, &[])
+  }
+
+  /// Compile a module which was built by the host instead of parsed.
+  pub fn compile_ast(&self, ast: &syntax::ast::Module) -> Result<Chunk> {
+    compile_ast_with_vars(
+      &self.global,
+      ast,
+      &[],
+      Metadata::default(),
+      CompileTimings::default(),
+    )
+  }
+
+  /// Run the script at `path` as a module named after the file, which other
+  /// scripts can then import.
+  ///
+  /// Errors raised by the script report their location in the file.
+  pub async fn eval_file(&mut self, path: &Path) -> Result<Value> {
+    let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+      Some(name) => name,
+      None => fail!("`{}` is not a valid module path", path.display()),
+    };
+    let text = match std::fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(e) => fail!("failed to read `{}`: {e}", path.display()),
+    };
+
+    let name = self.global.alloc(Str::owned(name.to_string()));
+    let source = Source::new(path.display().to_string(), text);
+    let module_id = self.global.next_module_id();
+    let module = self.root.compile_module(name.clone(), source, module_id)?;
+    self.run_module(name, module, module_id).await
+  }
+
+  /// Compile `files`, which are pairs of a module name and its source code,
+  /// into a bundle. Imports of the modules in the bundle are resolved
+  /// among them, see [`Vm::run_bundle`].
+  ///
+  /// Syntax errors are collected from all of the files.
+  pub fn compile_project(&self, files: &[(&str, &str)]) -> Result<Bundle> {
+    if files.is_empty() {
+      fail!("a project must have at least one file");
+    }
+    let mut modules = Vec::with_capacity(files.len());
+    let mut errors = Vec::new();
+    for (i, (name, text)) in files.iter().enumerate() {
+      if files[..i].iter().any(|(other, _)| other == name) {
+        fail!("module `{name}` is in the project more than once");
+      }
+      let name = self.global.alloc(Str::owned(name.to_string()));
+      let source = Source::new(name.as_str(), text.to_string());
+      match self.root.emit_module(&name, &source) {
+        Ok(module) => modules.push((name, Some(source), module)),
+        Err(e) => errors.extend(e.errors().iter().cloned()),
+      }
+    }
+    if !errors.is_empty() {
+      return Err(Error::Syntax(syntax::SyntaxError::new(errors)));
+    }
+    Ok(Bundle { modules })
+  }
+
+  /// Read a bundle written by [`Bundle::to_bytes`].
+  ///
+  /// # Safety
+  ///
+  /// The bytecode in `data` is not verified, so it must be a pack which was
+  /// written by this version of hebi and not modified since.
+  pub unsafe fn load_bundle(&self, data: &[u8]) -> Result<Bundle> {
+    let modules = pack::read(&self.global, data)?;
+    if modules.is_empty() {
+      fail!("a bundle must have at least one module");
+    }
+    Ok(Bundle { modules })
+  }
+
+  /// Run the first module of `bundle`. Any of its modules which are
+  /// imported are created from the bundle, instead of being loaded by the
+  /// module loader.
+  pub async fn run_bundle(&mut self, bundle: &Bundle) -> Result<Value> {
+    for (name, source, module) in bundle.modules.iter() {
+      (self.global).add_bundled_module(name.as_str(), source.clone(), module.clone());
+    }
+    let (name, source, module) = &bundle.modules[0];
+    let module_id = self.global.next_module_id();
+    let module = (self.root).instantiate_module(name.clone(), source.clone(), module, module_id);
+    self.run_module(name.clone(), module, module_id).await
+  }
+
+  async fn run_module(
+    &mut self,
+    name: Ptr<Str>,
+    module: Ptr<Module>,
+    module_id: ModuleId,
+  ) -> Result<Value> {
+    self.global.define_module(module_id, name, module.clone());
+
+    let ModuleKind::Script { root } = &module.kind else {
+      unreachable!("compiled module is not a script");
+    };
+    let result = self.root.entry(root.clone()).await;
+    if result.is_err() {
+      self.global.finish_module(module_id, false);
+    }
+    self.flush_output(result).await
   }
 
   pub async fn entry(&mut self, chunk: Chunk) -> Result<Value> {
-    self.root.entry(chunk.main).await
+    let result = self.root.entry(chunk.main).await;
+    self.flush_output(result).await
+  }
+
+  /// Flush the output at the end of a script run. An error raised by the
+  /// script takes precedence over one raised by the flush.
+  async fn flush_output(&self, result: Result<Value>) -> Result<Value> {
+    let flushed = self.global.io().output.borrow_mut().flush_async();
+    let flushed = flushed.await;
+    let value = result?;
+    flushed.map_err(Error::user)?;
+    Ok(value)
   }
 
   pub fn call<'a>(
@@ -125,6 +365,73 @@ impl Vm {
     self.root.call(callable, args)
   }
 
+  pub fn call_batch<'a>(
+    &'a mut self,
+    callable: Ptr<Any>,
+    arity: usize,
+    next_row: impl FnMut(&mut [Value]) -> Option<Result<()>> + 'a,
+  ) -> impl Future<Output = Result<Vec<Value>>> + 'a {
+    self.root.call_batch(callable, arity, next_row)
+  }
+
+  /// Look up the global `name`, which must be callable.
+  pub fn get_callable(&self, name: &str) -> Result<Ptr<Any>> {
+    match self.global.get(name) {
+      Some(value) => match value.to_any() {
+        Some(callable) => Ok(callable),
+        None => fail!("`{name}` is not callable"),
+      },
+      None => fail!("`{name}` is not defined"),
+    }
+  }
+
+  /// Create a thread which calls `callable` with `args` once it is resumed
+  /// by [`Vm::run_until_idle`].
+  pub fn spawn(&mut self, callable: Ptr<Any>, args: &[Value]) -> Result<()> {
+    let Some(function) = callable.clone_cast::<Function>() else {
+      fail!("only script functions can be spawned, got `{callable}`");
+    };
+    let mut task = Task::new(self.global.clone());
+    task.thread.start(function, args)?;
+    self.threads.push_back(task);
+    Ok(())
+  }
+
+  /// Resume spawned threads in turn, each until it yields or finishes,
+  /// until none are left.
+  ///
+  /// Threads are scheduled cooperatively, so a thread which never yields
+  /// does not let any of the others run until it finishes. A thread which
+  /// fails is removed, and its error is returned without resuming the rest.
+  pub async fn run_until_idle(&mut self) -> Result<()> {
+    self.step(usize::MAX).await.map(drop)
+  }
+
+  /// Like [`Vm::run_until_idle`], but stop after `steps` instructions.
+  ///
+  /// A thread which runs out of steps is resumed first by the next call.
+  /// Returns `true` once no threads are left.
+  pub async fn step(&mut self, steps: usize) -> Result<bool> {
+    let mut steps = steps;
+    let mut result = Ok(Value::none());
+    while let Some(mut task) = self.threads.pop_front() {
+      match task.thread.run_steps(&mut steps).await {
+        Ok(Status::Yielded) => self.threads.push_back(task),
+        Ok(Status::Paused) => {
+          self.threads.push_front(task);
+          break;
+        }
+        Ok(Status::Finished(_)) => {}
+        Err(e) => {
+          result = Err(e);
+          break;
+        }
+      }
+    }
+    self.flush_output(result).await?;
+    Ok(self.threads.is_empty())
+  }
+
   pub fn register(&mut self, module: &NativeModule) {
     let name = self.global.alloc(Str::owned(module.data.name.clone()));
     let module_id = self.root.global.next_module_id();
@@ -141,13 +448,104 @@ impl Vm {
 
 #[derive(Clone)]
 pub struct Chunk {
-  main: Ptr<Function>,
+  pub(crate) main: Ptr<Function>,
+  pub(crate) metadata: Metadata,
+  pub(crate) timings: CompileTimings,
 }
 
 impl Chunk {
   pub fn disassemble(&self) -> Disassembly {
     self.main.descriptor.disassemble()
   }
+
+  pub fn descriptor(&self) -> &FunctionDescriptor {
+    &self.main.descriptor
+  }
+}
+
+/// Compile `code` as an entry point into the global module, with `vars`
+/// bound to its parameters.
+pub(crate) fn compile_with_vars(global: &Global, code: &str, vars: &[&str]) -> Result<Chunk> {
+  let start = Instant::now();
+  let (frontmatter, pragmas) = syntax::parse_header(code).map_err(Error::Syntax)?;
+  let metadata = Metadata::new(&frontmatter, &pragmas);
+  let mut timings = CompileTimings {
+    metadata: start.elapsed(),
+    ..Default::default()
+  };
+  let start = Instant::now();
+  let ast =
+    syntax::parse_code(global.clone(), code, &frontmatter, pragmas).map_err(Error::Syntax)?;
+  timings.parse = start.elapsed();
+  compile_ast_with_vars(global, &ast, vars, metadata, timings)
+}
+
+fn compile_ast_with_vars<'src>(
+  global: &Global,
+  ast: &'src syntax::ast::Module<'src>,
+  vars: &[&'src str],
+  metadata: Metadata,
+  mut timings: CompileTimings,
+) -> Result<Chunk> {
+  let start = Instant::now();
+  let module =
+    codegen::emit_with_vars(global.clone(), ast, "__main__", true, vars).map_err(Error::Syntax)?;
+  timings.emit = start.elapsed();
+  global.log_compile("__main__", &timings);
+  let module_id = ModuleId::global();
+  let upvalues = global.alloc(List::new());
+  let main = module.root.clone();
+  let main = global.alloc(Function::new(main, upvalues, module_id));
+
+  Ok(Chunk {
+    main,
+    metadata,
+    timings,
+  })
+}
+
+/// Modules which were compiled together, see [`Vm::compile_project`].
+#[derive(Clone)]
+pub struct Bundle {
+  /// The first one is the entry point. Modules read from a pack which was
+  /// written without sources have none.
+  modules: Vec<(Ptr<Str>, Option<Source>, Ptr<ModuleDescriptor>)>,
+}
+
+impl Bundle {
+  pub fn modules(&self) -> impl Iterator<Item = &str> {
+    self.modules.iter().map(|(name, _, _)| name.as_str())
+  }
+
+  /// What the code of the bundle needs from a VM to run there.
+  pub fn capabilities(&self) -> Vec<Capability> {
+    pack::capabilities(&self.modules)
+  }
+
+  /// Write the bundle into a pack, which [`Vm::load_bundle`] reads.
+  pub fn to_bytes(&self, include_sources: bool) -> Result<Vec<u8>> {
+    pack::write(&self.modules, include_sources)
+  }
+}
+
+/// A spawned thread, which owns its stack.
+struct Task {
+  thread: Thread,
+}
+
+impl Task {
+  fn new(global: Global) -> Self {
+    let stack = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(Stack::new()))) };
+    Self {
+      thread: Thread::new(global, stack),
+    }
+  }
+}
+
+impl Drop for Task {
+  fn drop(&mut self) {
+    let _ = unsafe { Box::from_raw(self.thread.stack.as_ptr()) };
+  }
 }
 
 impl Drop for Vm {
@@ -10,25 +10,54 @@ use crate::internal::bytecode::opcode::Opcode;
 use crate::internal::bytecode::operands::Width;
 use crate::internal::error::Result;
 
+/// Execute instructions starting at `pc`, until the outermost frame returns,
+/// something has to be awaited, or `steps` instructions have executed.
+///
+/// `steps` is decremented for every instruction. If it runs out, the pc of
+/// the next instruction is yielded, and nothing else is done. Steps are only
+/// counted if `LIMITED` is set, so that the check is compiled out when there
+/// is no limit.
 #[inline(never)]
-pub fn dispatch<T: Handler>(
+pub fn dispatch<T: Handler, const LIMITED: bool>(
   handler: &mut T,
   bytecode: NonNull<[u8]>,
   pc: usize,
+  steps: &mut usize,
 ) -> Result<ControlFlow, T::Error> {
   let mut bytecode = bytecode;
   let mut pc = pc;
 
   'load_frame: loop {
     let ip = bytecode.as_ptr() as *mut u8;
-    debug_assert!(pc < bytecode.len(), "unexpected end of bytecode stream");
+    paranoid_assert!(pc < bytecode.len(), "unexpected end of bytecode stream");
 
     let end = unsafe { ip.add(bytecode.len()) };
     let mut ip = unsafe { ip.add(pc) };
     let mut width = Width::Normal;
+    let mut start = ip;
+
+    // tells the handler which instruction an error came from
+    macro_rules! op {
+      ($e:expr) => {
+        match $e {
+          Ok(v) => v,
+          Err(e) => return Err(handler.locate_error(e, get_pc!(start, bytecode))),
+        }
+      };
+    }
 
     loop {
-      let start = ip;
+      // jump offsets are relative to the start of the instruction,
+      // which includes its width prefix
+      if width.is_normal() {
+        start = ip;
+        if LIMITED {
+          if *steps == 0 {
+            return Ok(ControlFlow::Yield(get_pc!(ip, bytecode)));
+          }
+          *steps -= 1;
+        }
+      }
       match read_opcode!(ip, end) {
         Opcode::Nop => {
           continue;
@@ -43,286 +72,347 @@ pub fn dispatch<T: Handler>(
         }
         Opcode::Load => {
           let (reg,) = read_operands!(Load, ip, end, width);
-          handler.op_load(reg)?;
+          op!(handler.op_load(reg));
           continue;
         }
         Opcode::Store => {
           let (reg,) = read_operands!(Store, ip, end, width);
-          handler.op_store(reg)?;
+          op!(handler.op_store(reg));
+          continue;
+        }
+        Opcode::StoreCell => {
+          let (reg,) = read_operands!(StoreCell, ip, end, width);
+          op!(handler.op_store_cell(reg));
           continue;
         }
         Opcode::LoadConst => {
           let (idx,) = read_operands!(LoadConst, ip, end, width);
-          handler.op_load_const(idx)?;
+          op!(handler.op_load_const(idx));
           continue;
         }
         Opcode::LoadUpvalue => {
           let (idx,) = read_operands!(LoadUpvalue, ip, end, width);
-          handler.op_load_upvalue(idx)?;
+          op!(handler.op_load_upvalue(idx));
           continue;
         }
         Opcode::StoreUpvalue => {
           let (idx,) = read_operands!(StoreUpvalue, ip, end, width);
-          handler.op_store_upvalue(idx)?;
+          op!(handler.op_store_upvalue(idx));
           continue;
         }
         Opcode::LoadModuleVar => {
           let (idx,) = read_operands!(LoadModuleVar, ip, end, width);
-          handler.op_load_module_var(idx)?;
+          op!(handler.op_load_module_var(idx));
           continue;
         }
         Opcode::StoreModuleVar => {
           let (idx,) = read_operands!(StoreModuleVar, ip, end, width);
-          handler.op_store_module_var(idx)?;
+          op!(handler.op_store_module_var(idx));
           continue;
         }
         Opcode::LoadGlobal => {
           let (name,) = read_operands!(LoadGlobal, ip, end, width);
-          handler.op_load_global(name)?;
+          op!(handler.op_load_global(name));
           continue;
         }
         Opcode::StoreGlobal => {
           let (name,) = read_operands!(StoreGlobal, ip, end, width);
-          handler.op_store_global(name)?;
+          op!(handler.op_store_global(name));
           continue;
         }
         Opcode::LoadField => {
           let (name,) = read_operands!(LoadField, ip, end, width);
-          handler.op_load_field(name)?;
+          op!(handler.op_load_field(name));
           continue;
         }
         Opcode::LoadFieldOpt => {
           let (name,) = read_operands!(LoadFieldOpt, ip, end, width);
-          handler.op_load_field_opt(name)?;
+          op!(handler.op_load_field_opt(name));
+          continue;
+        }
+        Opcode::LoadModuleField => {
+          let (name, slot) = read_operands!(LoadModuleField, ip, end, width);
+          op!(handler.op_load_module_field(name, slot));
+          continue;
+        }
+        Opcode::LoadFieldSlot => {
+          let (name, slot) = read_operands!(LoadFieldSlot, ip, end, width);
+          op!(handler.op_load_field_slot(name, slot));
           continue;
         }
         Opcode::StoreField => {
           let (obj, name) = read_operands!(StoreField, ip, end, width);
-          handler.op_store_field(obj, name)?;
+          op!(handler.op_store_field(obj, name));
+          continue;
+        }
+        Opcode::StoreFieldSlot => {
+          let (obj, name, slot) = read_operands!(StoreFieldSlot, ip, end, width);
+          op!(handler.op_store_field_slot(obj, name, slot));
           continue;
         }
         Opcode::LoadIndex => {
           let (name,) = read_operands!(LoadIndex, ip, end, width);
-          handler.op_load_index(name)?;
+          op!(handler.op_load_index(name));
           continue;
         }
         Opcode::LoadIndexOpt => {
           let (name,) = read_operands!(LoadIndexOpt, ip, end, width);
-          handler.op_load_index_opt(name)?;
+          op!(handler.op_load_index_opt(name));
           continue;
         }
         Opcode::StoreIndex => {
           let (obj, key) = read_operands!(StoreIndex, ip, end, width);
-          handler.op_store_index(obj, key)?;
+          op!(handler.op_store_index(obj, key));
           continue;
         }
         Opcode::LoadSelf => {
           let () = read_operands!(LoadSelf, ip, end, width);
-          handler.op_load_self()?;
+          op!(handler.op_load_self());
           continue;
         }
         Opcode::LoadSuper => {
           let () = read_operands!(LoadSuper, ip, end, width);
-          handler.op_load_super()?;
+          op!(handler.op_load_super());
           continue;
         }
         Opcode::LoadNone => {
           let () = read_operands!(LoadNone, ip, end, width);
-          handler.op_load_none()?;
+          op!(handler.op_load_none());
           continue;
         }
         Opcode::LoadTrue => {
           let () = read_operands!(LoadTrue, ip, end, width);
-          handler.op_load_true()?;
+          op!(handler.op_load_true());
           continue;
         }
         Opcode::LoadFalse => {
           let () = read_operands!(LoadFalse, ip, end, width);
-          handler.op_load_false()?;
+          op!(handler.op_load_false());
           continue;
         }
         Opcode::LoadSmi => {
           let (smi,) = read_operands!(LoadSmi, ip, end, width);
-          handler.op_load_smi(smi)?;
+          op!(handler.op_load_smi(smi));
           continue;
         }
         Opcode::MakeFn => {
           let (desc,) = read_operands!(MakeFn, ip, end, width);
-          handler.op_make_fn(desc)?;
+          op!(handler.op_make_fn(desc));
           continue;
         }
         Opcode::MakeClass => {
           let (desc,) = read_operands!(MakeClass, ip, end, width);
-          handler.op_make_class(desc)?;
+          op!(handler.op_make_class(desc));
           continue;
         }
         Opcode::MakeClassDerived => {
           let (desc,) = read_operands!(MakeClassDerived, ip, end, width);
-          handler.op_make_class_derived(desc)?;
+          op!(handler.op_make_class_derived(desc));
           continue;
         }
         Opcode::MakeDataClass => {
           let (desc, parts) = read_operands!(MakeDataClass, ip, end, width);
-          handler.op_make_data_class(desc, parts)?;
+          op!(handler.op_make_data_class(desc, parts));
           continue;
         }
         Opcode::MakeDataClassDerived => {
           let (desc, parts) = read_operands!(MakeDataClassDerived, ip, end, width);
-          handler.op_make_data_class_derived(desc, parts)?;
+          op!(handler.op_make_data_class_derived(desc, parts));
           continue;
         }
         Opcode::MakeList => {
           let (start, count) = read_operands!(MakeList, ip, end, width);
-          handler.op_make_list(start, count)?;
+          op!(handler.op_make_list(start, count));
           continue;
         }
         Opcode::MakeListEmpty => {
           let () = read_operands!(MakeListEmpty, ip, end, width);
-          handler.op_make_list_empty()?;
+          op!(handler.op_make_list_empty());
           continue;
         }
         Opcode::MakeTable => {
           let (start, count) = read_operands!(MakeTable, ip, end, width);
-          handler.op_make_table(start, count)?;
+          op!(handler.op_make_table(start, count));
           continue;
         }
         Opcode::MakeTableEmpty => {
           let () = read_operands!(MakeTableEmpty, ip, end, width);
-          handler.op_make_table_empty()?;
+          op!(handler.op_make_table_empty());
           continue;
         }
         Opcode::Jump => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
           let (offset,) = read_operands!(Jump, ip, end, width);
-          let offset = handler.op_jump(offset)?;
-          unsafe { ip = start.add(offset.value()) };
+          let offset = op!(handler.op_jump(offset));
+          jump!(ip = start + offset, bytecode);
           continue;
         }
         Opcode::JumpConst => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
           let (idx,) = read_operands!(JumpConst, ip, end, width);
-          let offset = handler.op_jump_const(idx)?;
-          unsafe { ip = start.add(offset.value()) };
+          let offset = op!(handler.op_jump_const(idx));
+          jump!(ip = start + offset, bytecode);
           continue;
         }
         Opcode::JumpLoop => {
           #[allow(unused_assignments)] // ip is overwritten by start-offset
           let (offset,) = read_operands!(JumpLoop, ip, end, width);
-          let offset = handler.op_jump_loop(offset)?;
-          unsafe { ip = start.sub(offset.value()) }
+          let offset = op!(handler.op_jump_loop(offset));
+          jump!(ip = start - offset, bytecode);
           continue;
         }
         Opcode::JumpIfFalse => {
           let (offset,) = read_operands!(JumpIfFalse, ip, end, width);
-          let offset = handler.op_jump_if_false(offset)?;
+          let offset = op!(handler.op_jump_if_false(offset));
           match offset {
-            Jump::Move(offset) => unsafe { ip = start.add(offset.value()) },
+            Jump::Move(offset) => jump!(ip = start + offset, bytecode),
             Jump::Skip => {}
           }
           continue;
         }
         Opcode::JumpIfFalseConst => {
           let (idx,) = read_operands!(JumpIfFalseConst, ip, end, width);
-          let offset = handler.op_jump_if_false_const(idx)?;
+          let offset = op!(handler.op_jump_if_false_const(idx));
+          match offset {
+            Jump::Move(offset) => jump!(ip = start + offset, bytecode),
+            Jump::Skip => {}
+          }
+          continue;
+        }
+        Opcode::JumpIfNone => {
+          let (offset,) = read_operands!(JumpIfNone, ip, end, width);
+          let offset = op!(handler.op_jump_if_none(offset));
           match offset {
-            Jump::Move(offset) => unsafe { ip = start.add(offset.value()) },
+            Jump::Move(offset) => jump!(ip = start + offset, bytecode),
             Jump::Skip => {}
           }
           continue;
         }
+        Opcode::JumpIfNoneConst => {
+          let (idx,) = read_operands!(JumpIfNoneConst, ip, end, width);
+          let offset = op!(handler.op_jump_if_none_const(idx));
+          match offset {
+            Jump::Move(offset) => jump!(ip = start + offset, bytecode),
+            Jump::Skip => {}
+          }
+          continue;
+        }
+        Opcode::PushHandler => {
+          let (offset,) = read_operands!(PushHandler, ip, end, width);
+          let catch_pc = get_pc!(start, bytecode) + offset.value();
+          op!(handler.op_push_handler(catch_pc));
+          continue;
+        }
+        Opcode::PushHandlerConst => {
+          let (idx,) = read_operands!(PushHandlerConst, ip, end, width);
+          let offset = op!(handler.op_push_handler_const(idx));
+          let catch_pc = get_pc!(start, bytecode) + offset.value();
+          op!(handler.op_push_handler(catch_pc));
+          continue;
+        }
+        Opcode::PopHandler => {
+          let () = read_operands!(PopHandler, ip, end, width);
+          op!(handler.op_pop_handler());
+          continue;
+        }
+        Opcode::Throw => {
+          let () = read_operands!(Throw, ip, end, width);
+          op!(handler.op_throw());
+          continue;
+        }
         Opcode::Add => {
           let (lhs,) = read_operands!(Add, ip, end, width);
-          handler.op_add(lhs)?;
+          op!(handler.op_add(lhs));
           continue;
         }
         Opcode::Sub => {
           let (lhs,) = read_operands!(Sub, ip, end, width);
-          handler.op_sub(lhs)?;
+          op!(handler.op_sub(lhs));
           continue;
         }
         Opcode::Mul => {
           let (lhs,) = read_operands!(Mul, ip, end, width);
-          handler.op_mul(lhs)?;
+          op!(handler.op_mul(lhs));
           continue;
         }
         Opcode::Div => {
           let (lhs,) = read_operands!(Div, ip, end, width);
-          handler.op_div(lhs)?;
+          op!(handler.op_div(lhs));
           continue;
         }
         Opcode::Rem => {
           let (lhs,) = read_operands!(Rem, ip, end, width);
-          handler.op_rem(lhs)?;
+          op!(handler.op_rem(lhs));
           continue;
         }
         Opcode::Pow => {
           let (lhs,) = read_operands!(Pow, ip, end, width);
-          handler.op_pow(lhs)?;
+          op!(handler.op_pow(lhs));
           continue;
         }
         Opcode::Inv => {
           let () = read_operands!(Inv, ip, end, width);
-          handler.op_inv()?;
+          op!(handler.op_inv());
           continue;
         }
         Opcode::Not => {
           let () = read_operands!(Not, ip, end, width);
-          handler.op_not()?;
+          op!(handler.op_not());
           continue;
         }
         Opcode::CmpEq => {
           let (lhs,) = read_operands!(CmpEq, ip, end, width);
-          handler.op_cmp_eq(lhs)?;
+          op!(handler.op_cmp_eq(lhs));
           continue;
         }
         Opcode::CmpNe => {
           let (lhs,) = read_operands!(CmpNe, ip, end, width);
-          handler.op_cmp_ne(lhs)?;
+          op!(handler.op_cmp_ne(lhs));
           continue;
         }
         Opcode::CmpGt => {
           let (lhs,) = read_operands!(CmpGt, ip, end, width);
-          handler.op_cmp_gt(lhs)?;
+          op!(handler.op_cmp_gt(lhs));
           continue;
         }
         Opcode::CmpGe => {
           let (lhs,) = read_operands!(CmpGe, ip, end, width);
-          handler.op_cmp_ge(lhs)?;
+          op!(handler.op_cmp_ge(lhs));
           continue;
         }
         Opcode::CmpLt => {
           let (lhs,) = read_operands!(CmpLt, ip, end, width);
-          handler.op_cmp_lt(lhs)?;
+          op!(handler.op_cmp_lt(lhs));
           continue;
         }
         Opcode::CmpLe => {
           let (lhs,) = read_operands!(CmpLe, ip, end, width);
-          handler.op_cmp_le(lhs)?;
+          op!(handler.op_cmp_le(lhs));
           continue;
         }
         Opcode::CmpType => {
           let (lhs,) = read_operands!(CmpType, ip, end, width);
-          handler.op_cmp_type(lhs)?;
+          op!(handler.op_cmp_type(lhs));
           continue;
         }
         Opcode::Contains => {
           let (lhs,) = read_operands!(Contains, ip, end, width);
-          handler.op_contains(lhs)?;
+          op!(handler.op_contains(lhs));
           continue;
         }
         Opcode::IsNone => {
           let () = read_operands!(IsNone, ip, end, width);
-          handler.op_is_none()?;
+          op!(handler.op_is_none());
           continue;
         }
         Opcode::Print => {
           let () = read_operands!(Print, ip, end, width);
-          handler.op_print()?;
+          op!(handler.op_print());
           continue;
         }
         Opcode::PrintN => {
           let (start, count) = read_operands!(PrintN, ip, end, width);
-          handler.op_print_n(start, count)?;
+          op!(handler.op_print_n(start, count));
           continue;
         }
         Opcode::Call => {
@@ -330,7 +420,8 @@ pub fn dispatch<T: Handler>(
           #[allow(unused_assignments)]
           let (callee, args) = read_operands!(Call, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_call(return_addr, callee, args)? {
+          handler.set_steps(*steps);
+          match op!(handler.op_call(return_addr, callee, args)) {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -345,7 +436,40 @@ pub fn dispatch<T: Handler>(
           #[allow(unused_assignments)]
           let () = read_operands!(Call0, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_call0(return_addr)? {
+          handler.set_steps(*steps);
+          match op!(handler.op_call0(return_addr)) {
+            Call::LoadFrame(new_frame) => {
+              bytecode = new_frame.bytecode;
+              pc = new_frame.pc;
+              continue 'load_frame;
+            }
+            Call::Continue => continue,
+            Call::Yield => return Ok(ControlFlow::Yield(get_pc!(ip, bytecode))),
+          }
+        }
+        Opcode::CallMethod => {
+          // frame is reloaded so neither `ip` nor `width` are read
+          #[allow(unused_assignments)]
+          let (obj, name, args) = read_operands!(CallMethod, ip, end, width);
+          let return_addr = get_pc!(ip, bytecode);
+          handler.set_steps(*steps);
+          match op!(handler.op_call_method(return_addr, obj, name, args)) {
+            Call::LoadFrame(new_frame) => {
+              bytecode = new_frame.bytecode;
+              pc = new_frame.pc;
+              continue 'load_frame;
+            }
+            Call::Continue => continue,
+            Call::Yield => return Ok(ControlFlow::Yield(get_pc!(ip, bytecode))),
+          }
+        }
+        Opcode::CallInto => {
+          // frame is reloaded so neither `ip` nor `width` are read
+          #[allow(unused_assignments)]
+          let (callee, args, dst) = read_operands!(CallInto, ip, end, width);
+          let return_addr = get_pc!(ip, bytecode);
+          handler.set_steps(*steps);
+          match op!(handler.op_call_into(return_addr, callee, args, dst)) {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -358,7 +482,7 @@ pub fn dispatch<T: Handler>(
         Opcode::Import => {
           let (path,) = read_operands!(Import, ip, end, width);
           let return_addr = get_pc!(ip, bytecode);
-          match handler.op_import(path, return_addr)? {
+          match op!(handler.op_import(path, return_addr)) {
             Call::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -370,13 +494,13 @@ pub fn dispatch<T: Handler>(
         }
         Opcode::FinalizeModule => {
           let () = read_operands!(FinalizeModule, ip, end, width);
-          handler.op_finalize_module()?;
+          op!(handler.op_finalize_module());
           continue;
         }
         Opcode::Return => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
           let () = read_operands!(Return, ip, end, width);
-          match handler.op_return()? {
+          match op!(handler.op_return()) {
             Return::LoadFrame(new_frame) => {
               bytecode = new_frame.bytecode;
               pc = new_frame.pc;
@@ -388,7 +512,7 @@ pub fn dispatch<T: Handler>(
         Opcode::Yield => {
           #[allow(unused_assignments)] // ip is overwritten by start+offset
           let () = read_operands!(Yield, ip, end, width);
-          handler.op_yield()?;
+          op!(handler.op_yield());
           return Ok(ControlFlow::Yield(get_pc!(ip, bytecode)));
         }
       }
@@ -440,8 +564,17 @@ impl From<LoadFrame> for Return {
 pub trait Handler {
   type Error: StdError;
 
+  /// Called with every error returned by an instruction handler, along with
+  /// the offset of the instruction.
+  fn locate_error(&mut self, error: Self::Error, pc: usize) -> Self::Error;
+
+  /// Called before every call with the number of steps which are left, so
+  /// that the callee can find out how long it may still run.
+  fn set_steps(&mut self, steps: usize);
+
   fn op_load(&mut self, reg: op::Register) -> Result<(), Self::Error>;
   fn op_store(&mut self, reg: op::Register) -> Result<(), Self::Error>;
+  fn op_store_cell(&mut self, reg: op::Register) -> Result<(), Self::Error>;
   fn op_load_const(&mut self, idx: op::Constant) -> Result<(), Self::Error>;
   fn op_load_upvalue(&mut self, idx: op::Upvalue) -> Result<(), Self::Error>;
   fn op_store_upvalue(&mut self, idx: op::Upvalue) -> Result<(), Self::Error>;
@@ -451,7 +584,19 @@ pub trait Handler {
   fn op_store_global(&mut self, name: op::Constant) -> Result<(), Self::Error>;
   fn op_load_field(&mut self, name: op::Constant) -> Result<(), Self::Error>;
   fn op_load_field_opt(&mut self, name: op::Constant) -> Result<(), Self::Error>;
+  fn op_load_module_field(
+    &mut self,
+    name: op::Constant,
+    slot: op::ModuleVar,
+  ) -> Result<(), Self::Error>;
+  fn op_load_field_slot(&mut self, name: op::Constant, slot: op::Slot) -> Result<(), Self::Error>;
   fn op_store_field(&mut self, obj: op::Register, name: op::Constant) -> Result<(), Self::Error>;
+  fn op_store_field_slot(
+    &mut self,
+    obj: op::Register,
+    name: op::Constant,
+    slot: op::Slot,
+  ) -> Result<(), Self::Error>;
   fn op_load_index(&mut self, obj: op::Register) -> Result<(), Self::Error>;
   fn op_load_index_opt(&mut self, obj: op::Register) -> Result<(), Self::Error>;
   fn op_store_index(&mut self, obj: op::Register, key: op::Register) -> Result<(), Self::Error>;
@@ -483,6 +628,12 @@ pub trait Handler {
   fn op_jump_loop(&mut self, offset: op::Offset) -> Result<op::Offset, Self::Error>;
   fn op_jump_if_false(&mut self, offset: op::Offset) -> Result<Jump, Self::Error>;
   fn op_jump_if_false_const(&mut self, idx: op::Constant) -> Result<Jump, Self::Error>;
+  fn op_jump_if_none(&mut self, offset: op::Offset) -> Result<Jump, Self::Error>;
+  fn op_jump_if_none_const(&mut self, idx: op::Constant) -> Result<Jump, Self::Error>;
+  fn op_push_handler(&mut self, catch_pc: usize) -> Result<(), Self::Error>;
+  fn op_push_handler_const(&mut self, idx: op::Constant) -> Result<op::Offset, Self::Error>;
+  fn op_pop_handler(&mut self) -> Result<(), Self::Error>;
+  fn op_throw(&mut self) -> Result<(), Self::Error>;
   fn op_add(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
   fn op_sub(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
   fn op_mul(&mut self, lhs: op::Register) -> Result<(), Self::Error>;
@@ -509,6 +660,20 @@ pub trait Handler {
     args: op::Count,
   ) -> Result<Call, Self::Error>;
   fn op_call0(&mut self, return_addr: usize) -> Result<Call, Self::Error>;
+  fn op_call_method(
+    &mut self,
+    return_addr: usize,
+    obj: op::Register,
+    name: op::Constant,
+    args: op::Count,
+  ) -> Result<Call, Self::Error>;
+  fn op_call_into(
+    &mut self,
+    return_addr: usize,
+    callee: op::Register,
+    args: op::Count,
+    dst: op::Register,
+  ) -> Result<Call, Self::Error>;
   fn op_import(&mut self, path: op::Constant, return_addr: usize) -> Result<Call, Self::Error>;
   fn op_finalize_module(&mut self) -> Result<(), Self::Error>;
   fn op_return(&mut self) -> Result<Return, Self::Error>;
@@ -1,11 +1,11 @@
 macro_rules! read_opcode {
   ($ip:ident, $end:ident) => {
     unsafe {
-      debug_assert!($ip < $end, "unexpected end of bytecode stream");
+      paranoid_assert!($ip < $end, "unexpected end of bytecode stream");
       let opcode = $ip.read();
       $ip = $ip.add(1);
 
-      debug_assert!(
+      paranoid_assert!(
         $crate::internal::bytecode::opcode::Opcode::try_from(opcode).is_ok(),
         "illegal instruction"
       );
@@ -33,7 +33,10 @@ macro_rules! read_operands {
 
     if LENGTH > 0 {
       unsafe {
-        debug_assert!($ip < $end, "unexpected end of bytecode stream");
+        paranoid_assert!(
+          ($end as usize) - ($ip as usize) >= LENGTH * ($width as usize),
+          "unexpected end of bytecode stream"
+        );
 
         let operands = $crate::internal::vm::dispatch::macros::__read_tuple::<LENGTH, Operands>($ip, $width);
         $ip = $ip.add(LENGTH * ($width as usize));
@@ -46,6 +49,24 @@ macro_rules! read_operands {
   }};
 }
 
+/// Move `ip` by `offset` from `start`, the start of the current instruction.
+macro_rules! jump {
+  ($ip:ident = $start:ident + $offset:ident, $code:ident) => {{
+    paranoid_assert!(
+      get_pc!($start, $code) + $offset.value() < $code.len(),
+      "jump out of bounds"
+    );
+    unsafe { $ip = $start.add($offset.value()) }
+  }};
+  ($ip:ident = $start:ident - $offset:ident, $code:ident) => {{
+    paranoid_assert!(
+      $offset.value() <= get_pc!($start, $code),
+      "jump out of bounds"
+    );
+    unsafe { $ip = $start.sub($offset.value()) }
+  }};
+}
+
 macro_rules! get_pc {
   ($ip:ident, $code:ident) => {
     ($ip as usize) - ($code.as_ptr() as *mut u8 as usize)
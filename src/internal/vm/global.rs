@@ -1,17 +1,36 @@
+use std::alloc::Layout;
 use std::any::TypeId;
-use std::cell::RefCell;
-use std::fmt::Debug;
+use std::cell::{Cell, RefCell, RefMut};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 
 use indexmap::{IndexMap, IndexSet};
 
 use super::Config;
-use crate::internal::error::Result;
-use crate::internal::object::module::{Module, ModuleId};
+use crate::internal::error::{Error, ErrorData, Result};
+use crate::internal::fork::Copier;
+#[cfg(feature = "profile")]
+use crate::internal::object::builtin::BuiltinMethod;
+#[cfg(feature = "profile")]
+use crate::internal::object::function::{BoundFunction, Function, FunctionDescriptor};
+use crate::internal::object::module::{Module, ModuleDescriptor, ModuleId};
+use crate::internal::object::native::LocalBoxFuture;
+#[cfg(feature = "profile")]
+use crate::internal::object::native::NativeBoundFunction;
 use crate::internal::object::native::NativeClass;
-use crate::internal::object::{module, table, Ptr, Str, Table};
-use crate::internal::value::Value;
+use crate::internal::object::{module, table, Any, ClassType, Ptr, Str, Table};
+use crate::internal::stdlib::random::Rng;
+use crate::internal::value::{FloatFormat, Value};
+use crate::public::io::StringWriter;
+use crate::public::replay::{NativeCall, Recording};
+use crate::public::{CompileTimings, SharedGlobals};
+use crate::span::Source;
+#[cfg(feature = "profile")]
+use crate::span::Span;
 use crate::Cow;
 
 #[derive(Debug, Clone)]
@@ -34,20 +53,395 @@ impl<T: Send + Sync + 'static> IoBase for T {
   }
 }
 
-pub trait Output: std::io::Write + IoBase {}
-impl<T: std::io::Write + IoBase> Output for T {}
+/// Where scripts write their output to.
+///
+/// Implemented for every [`std::io::Write`], so `Vec<u8>`, files and
+/// [`std::io::Stdout`] may be used directly. Output written to `Stdout` as an
+/// error goes to [`std::io::Stderr`]. To capture output as text, use a
+/// [`StringWriter`][crate::io::StringWriter].
+pub trait Output: IoBase {
+  /// Write all of `buf` to the output stream.
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<()>;
 
-pub trait Input: std::io::Read + IoBase {}
-impl<T: std::io::Read + IoBase> Input for T {}
+  /// Write all of `buf` to the error stream.
+  ///
+  /// Outputs without a separate error stream write it to the output stream.
+  fn write_err(&mut self, buf: &[u8]) -> std::io::Result<()> {
+    self.write(buf)
+  }
+
+  /// Flush any buffered output.
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+
+  /// Flush any buffered output at the end of a script run, which only
+  /// finishes once the output has been written.
+  ///
+  /// Defaults to [`Output::flush`]. Outputs which write in the background
+  /// should override it, so that no output is lost when the host exits.
+  fn flush_async(&mut self) -> LocalBoxFuture<'static, std::io::Result<()>> {
+    Box::pin(std::future::ready(self.flush()))
+  }
+
+  /// Whether the output is an interactive terminal, which hosts may use to
+  /// decide whether to emit colors or prompts.
+  fn is_tty(&self) -> bool {
+    false
+  }
+}
+
+impl<W: std::io::Write + IoBase> Output for W {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+    std::io::Write::write_all(self, buf)
+  }
+
+  fn write_err(&mut self, buf: &[u8]) -> std::io::Result<()> {
+    if (*self).as_any().is::<std::io::Stdout>() {
+      std::io::Write::write_all(&mut std::io::stderr(), buf)
+    } else {
+      std::io::Write::write_all(self, buf)
+    }
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    std::io::Write::flush(self)
+  }
+
+  fn is_tty(&self) -> bool {
+    use std::io::IsTerminal;
+
+    let any = self.as_any();
+    if let Some(stdout) = any.downcast_ref::<std::io::Stdout>() {
+      stdout.is_terminal()
+    } else if let Some(stderr) = any.downcast_ref::<std::io::Stderr>() {
+      stderr.is_terminal()
+    } else {
+      false
+    }
+  }
+}
+
+/// Where the `input` builtin reads lines from.
+///
+/// Implemented for [`std::io::Stdin`]. Any other [`std::io::BufRead`] can be
+/// wrapped in a [`Reader`][crate::io::Reader].
+pub trait Input: IoBase {
+  /// Read the next line, without its line terminator.
+  ///
+  /// Returns `None` at the end of the input.
+  fn read_line(&mut self) -> std::io::Result<Option<String>>;
+}
+
+impl Input for std::io::Stdin {
+  fn read_line(&mut self) -> std::io::Result<Option<String>> {
+    read_line(&mut self.lock())
+  }
+}
+
+pub(crate) fn read_line(reader: &mut impl std::io::BufRead) -> std::io::Result<Option<String>> {
+  let mut line = String::new();
+  if reader.read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+  if line.ends_with('\n') {
+    line.pop();
+    if line.ends_with('\r') {
+      line.pop();
+    }
+  }
+  Ok(Some(line))
+}
+
+/// Decides whether scripts may perform sensitive operations.
+///
+/// Each method is called before the operation it is named after, and may
+/// deny it by returning an error, which is raised in the script as if the
+/// operation itself had failed. Everything is allowed by default.
+pub trait Policy: Send + Sync + 'static {
+  /// Called when a script imports the module at `path`.
+  fn import(&self, path: &str) -> Result<()> {
+    let _ = path;
+    Ok(())
+  }
+
+  /// Called when a script assigns to the global `name`.
+  fn store_global(&self, name: &str) -> Result<()> {
+    let _ = name;
+    Ok(())
+  }
+
+  /// Called when a function named `name` which was registered by the host
+  /// is called, either by a script or by the host itself. Builtin functions
+  /// are not checked.
+  fn call_native(&self, name: &str) -> Result<()> {
+    let _ = name;
+    Ok(())
+  }
+}
+
+/// Rewrites the path of an import before the module is loaded, see
+/// [`HebiBuilder::rewrite_imports`][crate::HebiBuilder::rewrite_imports].
+pub type ImportRewriter = dyn Fn(&str) -> Option<String> + Send + Sync + 'static;
+
+/// A kind of statement which the host may forbid scripts from using, see
+/// [`HebiBuilder::forbid`][crate::HebiBuilder::forbid].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Construct {
+  /// `import m` and `from m import x`.
+  Import,
+  Class,
+  /// Function declarations, not including methods.
+  Function,
+  For,
+  While,
+  /// `loop:` and `loop n:`.
+  Loop,
+  Try,
+  With,
+}
+
+impl Display for Construct {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Construct::Import => write!(f, "imports"),
+      Construct::Class => write!(f, "classes"),
+      Construct::Function => write!(f, "functions"),
+      Construct::For => write!(f, "`for` loops"),
+      Construct::While => write!(f, "`while` loops"),
+      Construct::Loop => write!(f, "`loop` statements"),
+      Construct::Try => write!(f, "`try` statements"),
+      Construct::With => write!(f, "`with` statements"),
+    }
+  }
+}
+
+/// Something which compiled code needs from the VM that runs it, see
+/// [`Bundle::capabilities`][crate::Bundle::capabilities].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+  /// The code uses the construct, so it must not be forbidden.
+  Construct(Construct),
+  /// The code has decimal literals, which need the `decimal` feature.
+  Decimal,
+}
+
+impl Display for Capability {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Capability::Construct(construct) => write!(f, "{construct}"),
+      Capability::Decimal => write!(f, "decimals"),
+    }
+  }
+}
+
+/// Provides the memory which objects allocated by a VM live in.
+///
+/// Only the objects themselves come from the allocator. What they own,
+/// such as the items of a list or the contents of a string, comes from the
+/// global allocator, so the allocator sees only part of the memory which a
+/// VM uses.
+///
+/// The allocator is stored once per VM, and every object it allocated is
+/// given back to it when it is freed, so the allocator is kept alive until
+/// the last of those objects is gone, even if that outlives the VM.
+///
+/// # Safety
+///
+/// `alloc` must return either null or a pointer to memory which is valid
+/// for `layout` and not used by anything else until it is passed to
+/// `dealloc`, like [`std::alloc::GlobalAlloc::alloc`].
+pub unsafe trait Allocator: Send + Sync + 'static {
+  fn alloc(&self, layout: Layout) -> *mut u8;
+
+  /// # Safety
+  ///
+  /// `ptr` was returned by [`Allocator::alloc`] on this allocator with the
+  /// same `layout`, and is not used again.
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The name, the size in bytes, and the number of live objects of each type.
+#[cfg(feature = "count_objects")]
+type ObjectCounts = IndexMap<TypeId, (&'static str, usize, Cell<usize>)>;
+
+/// The number of calls and total time, by the owner and name of the native
+/// function.
+#[cfg(feature = "profile")]
+type NativeTimings = IndexMap<(Option<Ptr<Str>>, Ptr<Str>), (u64, std::time::Duration)>;
+
+/// Where the objects of a VM are allocated, and how many of them are live.
+///
+/// This is stored once per VM, on its `Global`, and only exists if the VM
+/// has a custom allocator or counts its objects. In that case, each object
+/// is preceded by a reference to it, which is allocated along with the
+/// object, see `Ptr`.
+pub(crate) struct Heap {
+  pub(crate) allocator: Option<Arc<dyn Allocator>>,
+  #[cfg(feature = "count_objects")]
+  counts: Option<RefCell<ObjectCounts>>,
+}
+
+impl Heap {
+  #[cfg_attr(not(feature = "count_objects"), allow(unused_variables))]
+  fn new(allocator: Option<Arc<dyn Allocator>>, count_objects: bool) -> Option<Rc<Self>> {
+    let heap = Heap {
+      allocator,
+      #[cfg(feature = "count_objects")]
+      counts: count_objects.then(|| RefCell::new(IndexMap::new())),
+    };
+    (heap.allocator.is_some() || heap.counts_objects()).then(|| Rc::new(heap))
+  }
+
+  #[cfg(feature = "count_objects")]
+  fn counts_objects(&self) -> bool {
+    self.counts.is_some()
+  }
+
+  #[cfg(not(feature = "count_objects"))]
+  fn counts_objects(&self) -> bool {
+    false
+  }
+
+  /// Count a new object of the type `type_id`, which takes up `size` bytes.
+  #[cfg(feature = "count_objects")]
+  pub(crate) fn count_alloc(
+    &self,
+    type_id: TypeId,
+    size: usize,
+    type_name: impl FnOnce() -> &'static str,
+  ) {
+    let Some(counts) = &self.counts else {
+      return;
+    };
+    let mut counts = counts.borrow_mut();
+    let (_, _, live) = counts
+      .entry(type_id)
+      .or_insert_with(|| (type_name(), size, Cell::new(0)));
+    live.set(live.get() + 1);
+  }
+
+  /// Stop counting an object of the type `type_id`, which was just freed.
+  #[cfg(feature = "count_objects")]
+  pub(crate) fn count_free(&self, type_id: TypeId) {
+    let Some(counts) = &self.counts else {
+      return;
+    };
+    if let Some((_, _, live)) = counts.borrow().get(&type_id) {
+      live.set(live.get() - 1);
+    }
+  }
+}
+
+/// The calls made by a call site, see [`Global::record_call_site`].
+#[cfg(feature = "profile")]
+struct CallSiteStats {
+  function: String,
+  span: Span,
+  module_id: ModuleId,
+  calls: u64,
+  /// The different functions which were called, and one more once the site
+  /// is megamorphic.
+  targets: Vec<CallTarget>,
+}
+
+/// The code a call runs, which is the same for every closure created from a
+/// function and every method bound to a receiver.
+#[cfg(feature = "profile")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CallTarget {
+  Function(u64),
+  Object(usize),
+  Builtin(usize),
+}
+
+#[cfg(feature = "profile")]
+impl CallTarget {
+  fn of(callee: &Ptr<Any>) -> Self {
+    if let Some(f) = callee.clone_cast::<Function>() {
+      CallTarget::Function(f.descriptor.id)
+    } else if let Some(f) = callee.clone_cast::<BoundFunction>() {
+      CallTarget::Function(f.function().descriptor.id)
+    } else if let Some(f) = callee.clone_cast::<NativeBoundFunction>() {
+      CallTarget::Object(f.function.addr())
+    } else if let Some(f) = callee.clone_cast::<BuiltinMethod>() {
+      CallTarget::Builtin(f.callback() as usize)
+    } else {
+      CallTarget::Object(callee.addr())
+    }
+  }
+}
+
+/// What happens to calls to native functions.
+#[derive(Clone)]
+pub enum NativeLog {
+  /// Calls are made and added to the recording.
+  Record(Vec<NativeCall>),
+  /// Calls are not made, they return the results of the recorded calls,
+  /// which are left in the order they have to be made in.
+  Replay(VecDeque<NativeCall>),
+}
+
+/// The source of a module in a bundle, if it has one, and its compiled
+/// code.
+pub type BundledModule = (Option<Source>, Ptr<ModuleDescriptor>);
+
+/// A type-erased coercion hook, see
+/// [`Hebi::register_coercion`][crate::Hebi::register_coercion].
+pub type Coercion = Arc<dyn std::any::Any + Send + Sync>;
 
 pub struct State {
   globals: Ptr<Table>,
   io: Io,
   module_registry: RefCell<module::Registry>,
-  module_loader: Box<dyn module::ModuleLoader>,
+  /// Shared with the VMs forked from this one.
+  module_loader: Arc<Mutex<Box<dyn module::ModuleLoader>>>,
   module_visited_set: RefCell<IndexSet<ModuleId>>,
+  module_sources: RefCell<IndexMap<ModuleId, Source>>,
+  /// Modules compiled as part of a bundle, which are imported instead of
+  /// loading them, by name.
+  bundled_modules: RefCell<IndexMap<String, BundledModule>>,
   string_table: RefCell<IndexMap<Cow<'static, str>, Ptr<Str>>>,
   type_map: RefCell<IndexMap<TypeId, Ptr<NativeClass>>>,
+  error_classes: RefCell<IndexMap<String, Ptr<ClassType>>>,
+  coercions: RefCell<IndexMap<TypeId, Coercion>>,
+  /// Types which a coercion hook is currently converting into.
+  active_coercions: RefCell<IndexSet<TypeId>>,
+  rng: RefCell<Rng>,
+  shared: Option<SharedGlobals>,
+  float_format: FloatFormat,
+  strict_globals: bool,
+  inline_functions: bool,
+  forbidden: Vec<Construct>,
+  policy: Option<Arc<dyn Policy>>,
+  rewrite_imports: Option<Arc<ImportRewriter>>,
+  /// The `sys` module as seen by each module which imported it, which all
+  /// have the same module id, see [`sys::module`][crate::internal::stdlib::sys::module].
+  sys_modules: RefCell<IndexMap<ModuleId, Ptr<Module>>>,
+  sys_module_id: Cell<Option<ModuleId>>,
+  /// `None` if objects are allocated with the global allocator and are not
+  /// counted.
+  heap: Option<Rc<Heap>>,
+  /// `None` if native calls are neither recorded nor replayed.
+  native_log: Option<RefCell<NativeLog>>,
+  log_compile_phases: bool,
+  expose_platform: bool,
+  /// Calls to native functions, and the time spent in them, by owner and
+  /// name.
+  #[cfg(feature = "profile")]
+  native_timings: RefCell<NativeTimings>,
+  /// Calls made by each call site, by the id of the function the call is in
+  /// and the offset of the call.
+  #[cfg(feature = "profile")]
+  call_sites: RefCell<IndexMap<(u64, usize), CallSiteStats>>,
+  /// Unique across all VMs in the process.
+  id: u64,
+  /// Objects kept alive on behalf of the host, by id.
+  roots: RefCell<IndexMap<u64, Ptr<Any>>>,
+  next_root_id: Cell<u64>,
+  /// Roots whose handles were dropped, possibly on another thread, and which
+  /// are removed the next time the roots are used.
+  released_roots: Arc<Mutex<Vec<u64>>>,
 }
 
 impl Debug for State {
@@ -58,29 +452,58 @@ impl Debug for State {
       .field("module_registry", &self.module_registry)
       .field("module_loader", &"<...>")
       .field("module_visited_set", &self.module_visited_set)
+      .field("module_sources", &self.module_sources.borrow().keys())
+      .field("bundled_modules", &self.bundled_modules.borrow().keys())
       .field("string_table", &self.string_table)
       .field("type_map", &self.type_map)
+      .field("error_classes", &self.error_classes)
+      .field("coercions", &self.coercions.borrow().len())
+      .field("rng", &self.rng)
+      .field("shared", &self.shared.is_some())
+      .field("float_format", &self.float_format)
+      .field("strict_globals", &self.strict_globals)
+      .field("inline_functions", &self.inline_functions)
+      .field("forbidden", &self.forbidden)
+      .field("policy", &self.policy.is_some())
+      .field("rewrite_imports", &self.rewrite_imports.is_some())
+      .field(
+        "object_counts",
+        &self.heap.as_ref().is_some_and(|heap| heap.counts_objects()),
+      )
+      .field(
+        "allocator",
+        &self
+          .heap
+          .as_ref()
+          .is_some_and(|heap| heap.allocator.is_some()),
+      )
+      .field("native_log", &self.native_log.is_some())
+      .field("log_compile_phases", &self.log_compile_phases)
+      .field("expose_platform", &self.expose_platform)
+      .field("id", &self.id)
+      .field("roots", &self.roots)
       .finish()
   }
 }
 
 pub struct Io {
-  pub(crate) input: RefCell<Box<dyn Input>>,
+  /// `None` if scripts may not read input.
+  pub(crate) input: RefCell<Option<Box<dyn Input>>>,
   pub(crate) output: RefCell<Box<dyn Output>>,
 }
 
 impl Io {
-  pub fn new(input: Box<dyn Input>, output: Box<dyn Output>) -> Self {
+  pub fn new(input: Option<Box<dyn Input>>, output: Box<dyn Output>) -> Self {
     Self {
-      input: RefCell::new(Box::new(input)),
-      output: RefCell::new(Box::new(output)),
+      input: RefCell::new(input),
+      output: RefCell::new(output),
     }
   }
 }
 
 impl Default for Io {
   fn default() -> Self {
-    Self::new(Box::new(std::io::stdin()), Box::new(std::io::stdout()))
+    Self::new(None, Box::new(std::io::stdout()))
   }
 }
 
@@ -91,26 +514,149 @@ impl Default for Global {
 }
 
 impl Global {
-  pub fn new(config: Config) -> Self {
-    let (module_loader, input, output) = config.resolve();
-    let io = Io {
-      input: RefCell::new(input),
-      output: RefCell::new(output),
+  pub fn new(mut config: Config) -> Self {
+    let rng = match config.seed {
+      Some(seed) => Rng::new(seed),
+      None => Rng::from_entropy(),
     };
+    let shared = config.shared.clone();
+    let float_format = config.float_format;
+    let strict_globals = config.strict_globals;
+    let inline_functions = config.inline_functions;
+    let forbidden = std::mem::take(&mut config.forbidden);
+    let policy = config.policy.take().map(Arc::from);
+    let rewrite_imports = config.rewrite_imports.take().map(Arc::from);
+    #[cfg(feature = "count_objects")]
+    let heap = Heap::new(config.allocator.take(), config.count_objects);
+    #[cfg(not(feature = "count_objects"))]
+    let heap = Heap::new(config.allocator.take(), false);
+    let native_log = config.native_log.take().map(RefCell::new);
+    let log_compile_phases = config.log_compile_phases;
+    let expose_platform = config.expose_platform;
+    let (module_loader, io) = config.resolve();
 
     Self {
       inner: Rc::new(State {
         globals: unsafe { Ptr::alloc_raw(Table::with_capacity(0)) },
         io,
         module_registry: RefCell::new(module::Registry::new()),
-        module_loader,
+        module_loader: Arc::new(Mutex::new(module_loader)),
         module_visited_set: RefCell::new(IndexSet::new()),
+        module_sources: RefCell::new(IndexMap::new()),
+        bundled_modules: RefCell::new(IndexMap::new()),
         string_table: RefCell::new(IndexMap::new()),
         type_map: RefCell::new(IndexMap::new()),
+        error_classes: RefCell::new(IndexMap::new()),
+        coercions: RefCell::new(IndexMap::new()),
+        active_coercions: RefCell::new(IndexSet::new()),
+        rng: RefCell::new(rng),
+        shared,
+        float_format,
+        strict_globals,
+        inline_functions,
+        forbidden,
+        policy,
+        rewrite_imports,
+        sys_modules: RefCell::new(IndexMap::new()),
+        sys_module_id: Cell::new(None),
+        heap,
+        native_log,
+        log_compile_phases,
+        expose_platform,
+        #[cfg(feature = "profile")]
+        native_timings: RefCell::new(IndexMap::new()),
+        #[cfg(feature = "profile")]
+        call_sites: RefCell::new(IndexMap::new()),
+        id: next_id(),
+        roots: RefCell::new(IndexMap::new()),
+        next_root_id: Cell::new(0),
+        released_roots: Arc::new(Mutex::new(Vec::new())),
       }),
     }
   }
 
+  /// A VM with a copy of the globals, modules and types of this one, see
+  /// [`Hebi::fork`][crate::Hebi::fork].
+  pub fn fork(&self) -> Result<Self> {
+    let global = Self {
+      inner: Rc::new(State {
+        globals: unsafe { Ptr::alloc_raw(Table::with_capacity(self.globals.len())) },
+        io: Io::new(None, Box::new(StringWriter::new())),
+        module_registry: RefCell::new(module::Registry::new()),
+        module_loader: self.module_loader.clone(),
+        module_visited_set: RefCell::new(IndexSet::new()),
+        module_sources: RefCell::new(self.module_sources.borrow().clone()),
+        bundled_modules: RefCell::new(IndexMap::new()),
+        string_table: RefCell::new(IndexMap::new()),
+        type_map: RefCell::new(IndexMap::new()),
+        error_classes: RefCell::new(IndexMap::new()),
+        coercions: RefCell::new(self.coercions.borrow().clone()),
+        active_coercions: RefCell::new(IndexSet::new()),
+        rng: RefCell::new(self.rng.borrow().clone()),
+        shared: self.shared.clone(),
+        float_format: self.float_format,
+        strict_globals: self.strict_globals,
+        inline_functions: self.inline_functions,
+        forbidden: self.forbidden.clone(),
+        policy: self.policy.clone(),
+        rewrite_imports: self.rewrite_imports.clone(),
+        sys_modules: RefCell::new(IndexMap::new()),
+        sys_module_id: Cell::new(self.sys_module_id.get()),
+        heap: (self.heap.as_ref())
+          .and_then(|heap| Heap::new(heap.allocator.clone(), heap.counts_objects())),
+        native_log: (self.native_log.as_ref()).map(|log| RefCell::new(log.borrow().clone())),
+        log_compile_phases: self.log_compile_phases,
+        expose_platform: self.expose_platform,
+        #[cfg(feature = "profile")]
+        native_timings: RefCell::new(IndexMap::new()),
+        #[cfg(feature = "profile")]
+        call_sites: RefCell::new(IndexMap::new()),
+        id: next_id(),
+        roots: RefCell::new(IndexMap::new()),
+        next_root_id: Cell::new(0),
+        released_roots: Arc::new(Mutex::new(Vec::new())),
+      }),
+    };
+
+    let mut copier = Copier::new(global.clone());
+    for (s, v) in self.string_table.borrow().iter() {
+      let v = copier.object(v)?;
+      global.string_table.borrow_mut().insert(s.clone(), v);
+    }
+    copier.fill_table(&self.globals, &global.globals)?;
+    {
+      let registry = self.module_registry.borrow();
+      let mut copy = global.module_registry.borrow_mut();
+      copy.next_module_id = registry.next_module_id;
+      for (name, module_id) in registry.index.iter() {
+        copy.index.insert(copier.object(name)?, *module_id);
+      }
+      for (module_id, module) in registry.modules.iter() {
+        copy.modules.insert(*module_id, copier.object(module)?);
+      }
+    }
+    for (name, (source, module)) in self.bundled_modules.borrow().iter() {
+      let module = copier.object(module)?;
+      global
+        .bundled_modules
+        .borrow_mut()
+        .insert(name.clone(), (source.clone(), module));
+    }
+    for (type_id, class) in self.type_map.borrow().iter() {
+      let class = copier.object(class)?;
+      global.type_map.borrow_mut().insert(*type_id, class);
+    }
+    for (code, class) in self.error_classes.borrow().iter() {
+      let class = copier.object(class)?;
+      global
+        .error_classes
+        .borrow_mut()
+        .insert(code.clone(), class);
+    }
+    copier.finish()?;
+    Ok(global)
+  }
+
   pub fn get(&self, key: &str) -> Option<Value> {
     self.globals.get(key)
   }
@@ -127,6 +673,32 @@ impl Global {
     self.module_registry.borrow().get_by_id(module_id)
   }
 
+  /// The `sys` module created for `importer` by `init`, which is only
+  /// called on its first import, with the id shared by every `sys` module.
+  pub fn sys_module(
+    &self,
+    importer: ModuleId,
+    init: impl FnOnce(ModuleId) -> Ptr<Module>,
+  ) -> Ptr<Module> {
+    if let Some(module) = self.sys_modules.borrow().get(&importer) {
+      return module.clone();
+    }
+    let module_id = match self.sys_module_id.get() {
+      Some(module_id) => module_id,
+      None => {
+        let module_id = self.next_module_id();
+        self.sys_module_id.set(Some(module_id));
+        module_id
+      }
+    };
+    let module = init(module_id);
+    self
+      .sys_modules
+      .borrow_mut()
+      .insert(importer, module.clone());
+    module
+  }
+
   pub fn get_module_by_name(&self, name: &str) -> Option<(ModuleId, Ptr<Module>)> {
     self.module_registry.borrow().get_by_name(name)
   }
@@ -135,15 +707,313 @@ impl Global {
     self.module_visited_set.borrow_mut().remove(&module_id);
     if !success {
       self.module_registry.borrow_mut().remove(module_id);
+      self.module_sources.borrow_mut().remove(&module_id);
     }
   }
 
+  /// Remember the source code of the module `module_id`, so that runtime
+  /// errors raised by its code can point into it.
+  pub fn set_module_source(&self, module_id: ModuleId, source: Source) {
+    self.module_sources.borrow_mut().insert(module_id, source);
+  }
+
+  pub fn get_module_source(&self, module_id: ModuleId) -> Option<Source> {
+    self.module_sources.borrow().get(&module_id).cloned()
+  }
+
+  /// Import the module `name` from `module` instead of loading it.
+  pub fn add_bundled_module(
+    &self,
+    name: &str,
+    source: Option<Source>,
+    module: Ptr<ModuleDescriptor>,
+  ) {
+    self
+      .bundled_modules
+      .borrow_mut()
+      .insert(name.to_string(), (source, module));
+  }
+
+  pub fn get_bundled_module(&self, name: &str) -> Option<BundledModule> {
+    self.bundled_modules.borrow().get(name).cloned()
+  }
+
   pub fn next_module_id(&self) -> ModuleId {
     self.module_registry.borrow_mut().next_module_id()
   }
 
   pub fn load_module(&self, path: &str) -> Result<Cow<'static, str>> {
-    self.module_loader.load(path)
+    let loader = self.module_loader.lock();
+    loader.unwrap_or_else(|e| e.into_inner()).load(path)
+  }
+
+  pub fn shared(&self) -> Option<&SharedGlobals> {
+    self.inner.shared.as_ref()
+  }
+
+  pub fn float_format(&self) -> FloatFormat {
+    self.inner.float_format
+  }
+
+  pub fn strict_globals(&self) -> bool {
+    self.inner.strict_globals
+  }
+
+  pub fn inline_functions(&self) -> bool {
+    self.inner.inline_functions
+  }
+
+  pub fn forbids(&self, construct: Construct) -> bool {
+    self.inner.forbidden.contains(&construct)
+  }
+
+  /// Whether code which needs `capability` can run in this VM.
+  pub fn supports(&self, capability: Capability) -> bool {
+    match capability {
+      Capability::Construct(construct) => !self.forbids(construct),
+      Capability::Decimal => cfg!(feature = "decimal"),
+    }
+  }
+
+  pub fn policy(&self) -> Option<&dyn Policy> {
+    self.inner.policy.as_deref()
+  }
+
+  /// The path which the host wants `import path` to load instead, if any.
+  pub fn rewrite_import(&self, path: &str) -> Option<String> {
+    let rewrite = self.inner.rewrite_imports.as_ref()?;
+    rewrite(path).filter(|rewritten| rewritten != path)
+  }
+
+  pub(crate) fn heap(&self) -> Option<&Rc<Heap>> {
+    self.inner.heap.as_ref()
+  }
+
+  /// The number of live objects of each type which has any, or `None` if
+  /// objects are not counted.
+  #[cfg(feature = "count_objects")]
+  pub fn object_counts(&self) -> Option<Vec<(&'static str, usize)>> {
+    let counts = self.heap()?.counts.as_ref()?.borrow();
+    Some(
+      counts
+        .values()
+        .filter(|(_, _, counter)| counter.get() > 0)
+        .map(|(name, _, counter)| (*name, counter.get()))
+        .collect(),
+    )
+  }
+
+  /// The number of bytes taken up by live objects, or `None` if objects are
+  /// not counted.
+  ///
+  /// Only the objects themselves are measured, not what they own on the
+  /// heap, such as the items of a list.
+  #[cfg(feature = "count_objects")]
+  pub fn memory_used(&self) -> Option<usize> {
+    let counts = self.heap()?.counts.as_ref()?.borrow();
+    Some(
+      counts
+        .values()
+        .map(|(_, size, counter)| size * counter.get())
+        .sum(),
+    )
+  }
+
+  #[cfg(not(feature = "count_objects"))]
+  pub fn memory_used(&self) -> Option<usize> {
+    None
+  }
+
+  /// Write how long compiling the module `name` took to the error stream,
+  /// if compile phases are logged.
+  pub fn log_compile(&self, name: &str, timings: &CompileTimings) {
+    if !self.log_compile_phases {
+      return;
+    }
+    let line = format!("compiled `{name}`: {timings}\n");
+    let _ = self.io().output.borrow_mut().write_err(line.as_bytes());
+  }
+
+  /// Whether scripts may read the name of the operating system.
+  pub fn exposes_platform(&self) -> bool {
+    self.expose_platform
+  }
+
+  /// Whether calls to native functions are recorded or replayed.
+  pub fn logs_native_calls(&self) -> bool {
+    self.inner.native_log.is_some()
+  }
+
+  /// The recorded result of the next call to a native function, which must
+  /// be a call to `name` with `args`, or `None` unless native calls are
+  /// replayed.
+  pub fn replay_native_call(&self, name: &str, args: &[Value]) -> Option<Result<Value>> {
+    let mut log = self.inner.native_log.as_ref()?.borrow_mut();
+    let NativeLog::Replay(calls) = &mut *log else {
+      return None;
+    };
+    let Some(call) = calls.pop_front() else {
+      return Some(Err(
+        error!("replay has no recorded call left for this call to `{name}`").into(),
+      ));
+    };
+    if call.name != name {
+      return Some(Err(
+        error!(
+          "replay expected a call to `{}`, but `{name}` was called",
+          call.name
+        )
+        .into(),
+      ));
+    }
+    if call.args != self.to_data(args) {
+      return Some(Err(
+        error!("replay expected a call to `{name}` with the recorded arguments, but they differ")
+          .into(),
+      ));
+    }
+    // the host is not called, so neither are the calls it made
+    calls.drain(..call.nested.min(calls.len()));
+    Some(match call.result {
+      Ok(value) => Ok(value.into_value(self)),
+      Err(e) => Err(Error::Value(e)),
+    })
+  }
+
+  /// Add a call to the native function `name` to the recording before it is
+  /// made, so that calls it makes come after it, and return where it was
+  /// added, or `None` unless native calls are recorded. `args` must be
+  /// converted before the call, which may change them.
+  ///
+  /// The result is `none` until [`Global::finish_native_call`] is called.
+  pub fn start_native_call(&self, name: &str, args: Vec<ErrorData>) -> Option<usize> {
+    let NativeLog::Record(calls) = &mut *self.inner.native_log.as_ref()?.borrow_mut() else {
+      return None;
+    };
+    calls.push(NativeCall {
+      name: name.to_string(),
+      args,
+      result: Ok(ErrorData::None),
+      nested: 0,
+    });
+    Some(calls.len() - 1)
+  }
+
+  /// Fill in the result of the call which was added to the recording at
+  /// `index` by [`Global::start_native_call`].
+  pub fn finish_native_call(&self, index: usize, result: &Result<Value>) {
+    let Some(log) = &self.inner.native_log else {
+      return;
+    };
+    if let NativeLog::Record(calls) = &mut *log.borrow_mut() {
+      let nested = calls.len() - index - 1;
+      let call = &mut calls[index];
+      call.nested = nested;
+      call.result = match result {
+        Ok(value) => Ok(ErrorData::from_value(value.clone(), self.float_format)),
+        Err(e) => Err(e.to_error_value()),
+      };
+    }
+  }
+
+  /// The calls to native functions recorded so far, or `None` unless they
+  /// are recorded.
+  pub fn recording(&self) -> Option<Recording> {
+    match &*self.inner.native_log.as_ref()?.borrow() {
+      NativeLog::Record(calls) => Some(Recording {
+        calls: calls.clone(),
+      }),
+      NativeLog::Replay(_) => None,
+    }
+  }
+
+  pub fn to_data(&self, values: &[Value]) -> Vec<ErrorData> {
+    (values.iter())
+      .map(|value| ErrorData::from_value(value.clone(), self.float_format))
+      .collect()
+  }
+
+  /// Add a call to the native function `name` which took `elapsed`.
+  #[cfg(feature = "profile")]
+  pub fn record_native_call(
+    &self,
+    owner: &Option<Ptr<Str>>,
+    name: &Ptr<Str>,
+    elapsed: std::time::Duration,
+  ) {
+    let mut timings = self.inner.native_timings.borrow_mut();
+    let (calls, total) = timings.entry((owner.clone(), name.clone())).or_default();
+    *calls += 1;
+    *total += elapsed;
+  }
+
+  #[cfg(feature = "profile")]
+  pub fn native_timings(&self) -> Vec<crate::public::profile::NativeTiming> {
+    let timings = self.inner.native_timings.borrow();
+    timings
+      .iter()
+      .map(
+        |((owner, name), (calls, total))| crate::public::profile::NativeTiming {
+          owner: owner.as_ref().map(|owner| owner.as_str().to_string()),
+          name: name.as_str().to_string(),
+          calls: *calls,
+          total: *total,
+        },
+      )
+      .collect()
+  }
+
+  /// Add a call to `callee` made by the call at offset `pc` in `function`.
+  #[cfg(feature = "profile")]
+  pub fn record_call_site(
+    &self,
+    function: &FunctionDescriptor,
+    module_id: ModuleId,
+    pc: usize,
+    callee: &Ptr<Any>,
+  ) {
+    let target = CallTarget::of(callee);
+    let mut call_sites = self.inner.call_sites.borrow_mut();
+    let site = (call_sites.entry((function.id, pc))).or_insert_with(|| CallSiteStats {
+      function: function.name.as_str().to_string(),
+      span: function.span_at(pc).unwrap_or_default(),
+      module_id,
+      calls: 0,
+      targets: Vec::new(),
+    });
+    site.calls += 1;
+    // the targets of megamorphic sites aren't told apart any further
+    let limit = crate::public::profile::CallSite::POLYMORPHIC_LIMIT;
+    if site.targets.len() <= limit && !site.targets.contains(&target) {
+      site.targets.push(target);
+    }
+  }
+
+  #[cfg(feature = "profile")]
+  pub fn call_sites(&self) -> Vec<crate::public::profile::CallSite> {
+    let call_sites = self.inner.call_sites.borrow();
+    call_sites
+      .values()
+      .map(|site| crate::public::profile::CallSite {
+        function: site.function.clone(),
+        span: site.span,
+        source: self.get_module_source(site.module_id),
+        calls: site.calls,
+        targets: site.targets.len(),
+      })
+      .collect()
+  }
+
+  /// Names of all modules which are loaded or being loaded, in the order
+  /// they were first imported or registered.
+  pub fn module_names(&self) -> Vec<Ptr<Str>> {
+    self
+      .module_registry
+      .borrow()
+      .index
+      .keys()
+      .cloned()
+      .collect()
   }
 
   pub fn define_module(&self, module_id: ModuleId, name: Ptr<Str>, module: Ptr<Module>) {
@@ -183,6 +1053,53 @@ impl Global {
       .cloned()
   }
 
+  /// Errors with the code `code` will be caught as instances of `class`.
+  pub fn register_error_class(&self, code: impl Into<String>, class: Ptr<ClassType>) {
+    self
+      .inner
+      .error_classes
+      .borrow_mut()
+      .insert(code.into(), class);
+  }
+
+  pub fn get_error_class(&self, code: &str) -> Option<Ptr<ClassType>> {
+    self.inner.error_classes.borrow().get(code).cloned()
+  }
+
+  /// Register the coercion hook for `type_id`, unless there already is one,
+  /// in which case `false` is returned.
+  pub fn register_coercion(&self, type_id: TypeId, coercion: Coercion) -> bool {
+    let mut coercions = self.inner.coercions.borrow_mut();
+    if coercions.contains_key(&type_id) {
+      return false;
+    }
+    coercions.insert(type_id, coercion);
+    true
+  }
+
+  /// The coercion hook for `type_id`, unless there is none or it is already
+  /// running, in which case `None` is returned. The hook must be released
+  /// with [`Global::finish_coercion`] once it returns.
+  pub fn start_coercion(&self, type_id: TypeId) -> Option<Coercion> {
+    let coercion = self.inner.coercions.borrow().get(&type_id).cloned()?;
+    if !self.inner.active_coercions.borrow_mut().insert(type_id) {
+      return None;
+    }
+    Some(coercion)
+  }
+
+  pub fn finish_coercion(&self, type_id: TypeId) {
+    self.inner.active_coercions.borrow_mut().remove(&type_id);
+  }
+
+  pub fn rng(&self) -> RefMut<'_, Rng> {
+    self.inner.rng.borrow_mut()
+  }
+
+  pub fn seed_rng(&self, seed: u64) {
+    *self.inner.rng.borrow_mut() = Rng::new(seed);
+  }
+
   pub fn io(&self) -> &Io {
     &self.inner.io
   }
@@ -190,6 +1107,51 @@ impl Global {
   pub fn entries(&self) -> table::Entries<'_> {
     self.inner.globals.entries()
   }
+
+  /// Identifies this VM among all VMs in the process.
+  pub fn id(&self) -> u64 {
+    self.inner.id
+  }
+
+  /// Keep `object` alive until [`Global::unroot`] is called with the
+  /// returned id, the id is pushed to [`Global::released_roots`], or the VM
+  /// is dropped.
+  pub fn root(&self, object: Ptr<Any>) -> u64 {
+    self.remove_released_roots();
+    let id = self.inner.next_root_id.get();
+    self.inner.next_root_id.set(id + 1);
+    self.inner.roots.borrow_mut().insert(id, object);
+    id
+  }
+
+  pub fn get_root(&self, id: u64) -> Option<Ptr<Any>> {
+    self.remove_released_roots();
+    self.inner.roots.borrow().get(&id).cloned()
+  }
+
+  /// Ids of roots which should no longer be kept alive. Unlike
+  /// [`Global::unroot`], this may be used from any thread.
+  pub fn released_roots(&self) -> Weak<Mutex<Vec<u64>>> {
+    Arc::downgrade(&self.inner.released_roots)
+  }
+
+  pub fn remove_released_roots(&self) {
+    let released = std::mem::take(&mut *self.inner.released_roots.lock().unwrap());
+    let mut roots = self.inner.roots.borrow_mut();
+    for id in released {
+      roots.swap_remove(&id);
+    }
+  }
+
+  pub fn unroot(&self, id: u64) -> Option<Ptr<Any>> {
+    self.inner.roots.borrow_mut().swap_remove(&id)
+  }
+}
+
+/// A new id for [`State::id`].
+fn next_id() -> u64 {
+  static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+  NEXT_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Deref for Global {
@@ -34,6 +34,18 @@ check! {
   "#
 }
 
+check! {
+  print_n_in_function,
+  r#"#!hebi
+    fn test(a, b):
+      c := a + b
+      print a, b, c
+
+    print "before", 0
+    test(1, 2)
+  "#
+}
+
 check! {
   closure_call,
   r#"#!hebi
@@ -47,6 +59,157 @@ check! {
   "#
 }
 
+check! {
+  closure_shared_capture,
+  r#"#!hebi
+    fn run():
+      v := 0
+      fn inc():
+        v += 1
+      inc()
+      inc()
+      print v
+      v = 10
+      inc()
+      return v
+    run()
+  "#
+}
+
+check! {
+  closure_counter,
+  r#"#!hebi
+    fn make_counter():
+      n := 0
+      fn inc():
+        n += 1
+        return n
+      return inc
+
+    a := make_counter()
+    b := make_counter()
+    a()
+    a()
+    b()
+    print a(), b()
+  "#
+}
+
+check! {
+  closure_loop_capture,
+  r#"#!hebi
+    fns := []
+    for i in 0..3:
+      fn get():
+        return i
+      fns.push(get)
+    print fns[0](), fns[1](), fns[2]()
+  "#
+}
+
+check! {
+  repeat_loop,
+  r#"#!hebi
+    n := 0
+    loop 3:
+      n += 1
+    print n
+
+    loop 0:
+      print "unreachable"
+    loop -1:
+      print "unreachable"
+
+    count := 5
+    loop count:
+      count -= 1
+      if count == 3:
+        continue
+      if count == 1:
+        break
+      print count
+  "#
+}
+
+check! {
+  for_range_step,
+  r#"#!hebi
+    for i in 0..10 by 3:
+      print i
+    for i in 0..=9 by 3:
+      print i
+    for i in 3..0 by -1:
+      print i
+    for i in 3..=0 by -1:
+      print i
+    for i in 10..0:
+      print "unreachable"
+    for i in 0..10 by -1:
+      print "unreachable"
+  "#
+}
+
+check! {
+  for_range_dynamic_step,
+  r#"#!hebi
+    fn count(start, end, step):
+      items := []
+      for i in start..end by step:
+        items.push(i)
+      return items.len()
+
+    print count(0, 10, 2), count(10, 0, -2), count(0, 10, -2), count(10, 0, 2)
+  "#
+}
+
+check! {
+  for_range_zero_step,
+  r#"#!hebi
+    step := 0
+    for i in 0..10 by step:
+      print "unreachable"
+  "#
+}
+
+check! {
+  range_object,
+  r#"#!hebi
+    r := range(0, 10, 3)
+    print r, r.len(), r.is_empty()
+    print r.contains(9), r.contains(10), 6 in r, 6.0 in r, "6" in r
+    print r.rev(), r.rev().rev() == r
+    for i in r.rev():
+      print i
+    print range(3), range(3, 0, -1).len(), range(5, 0).is_empty()
+    print range(0, 0) == range(5, 5), range(0, 3) == range(0, 3, 1)
+    print ?r.missing
+  "#
+}
+
+#[test]
+fn for_range_literal_zero_step() {
+  let mut hebi = crate::public::Hebi::new();
+  let e = hebi.compile("for i in 0..10 by 0:\n  pass").err().unwrap();
+  assert!(e.to_string().contains("range step cannot be zero"), "{e}");
+  let e = hebi.eval("range(0, 10, 0)").unwrap_err();
+  assert!(e.to_string().contains("range step cannot be zero"), "{e}");
+}
+
+check! {
+  int_overflow_methods,
+  r#"#!hebi
+    max := 2147483647
+    min := -2147483647 - 1
+    print max.checked_add(1), max.checked_sub(1), min.checked_sub(1), min.checked_mul(-1)
+    print max.saturating_add(1), min.saturating_sub(1), max.saturating_mul(-2), 2.saturating_mul(3)
+    print max.wrapping_add(1), min.wrapping_sub(1), max.wrapping_mul(2)
+
+    add := max.wrapping_add
+    print add(2), ?max.checked_div
+    max.checked_div(1)
+  "#
+}
+
 check! {
   make_fn_with_args,
   r#"#!hebi
@@ -270,6 +433,86 @@ check! {
   "#
 }
 
+check! {
+  module
+  import_alias,
+  {
+    test: "value := 100"
+  },
+  r#"#!hebi
+    import test as t
+    print t.value
+    from test import value as v
+    print v
+  "#
+}
+
+check! {
+  module
+  import_symbol_alias_multi,
+  {
+    test: r#"#!hebi
+      a := 1
+      b := 2
+    "#
+  },
+  r#"#!hebi
+    from test import a as b, b as a
+    print a, b
+  "#
+}
+
+check! {
+  module
+  import_alias_in_nested_scope,
+  {
+    test: "value := 100"
+  },
+  r#"#!hebi
+    fn foo():
+      import test as t
+      from test import value as v
+      print t.value, v
+    foo()
+  "#
+}
+
+check! {
+  module
+  import_reexport,
+  {
+    inner: "value := 100",
+    outer: "from inner import value as renamed"
+  },
+  r#"#!hebi
+    from outer import renamed
+    import outer as o
+    print renamed, o.renamed
+  "#
+}
+
+check! {
+  module
+  import_missing_symbol,
+  {
+    test: "value := 100"
+  },
+  r#"#!hebi
+    from test import missing as value
+  "#
+}
+
+check! {
+  frontmatter,
+  r#"#!hebi
+    #!/usr/bin/env hebi
+    ---
+    owner: ops
+    ---
+    print "hello"
+  "#
+}
+
 check! {
   simple_class,
   r#"#!hebi
@@ -657,6 +900,32 @@ check! {
   "#
 }
 
+check! {
+  class_field_slots,
+  r#"#!hebi
+    class T:
+      a = 1
+      b = 2
+      init(self, b):
+        self.b = b
+      fn sum(self):
+        return self.a * 10 + self.b
+      fn set_a(self, a):
+        self.a = a
+    class U(T):
+      c = 3
+    class V:
+      b = 4
+      a = 5
+    t := T(3)
+    t.set_a(2)
+    u := U(4)
+    v := V()
+    T.set_a(v, 6)
+    [t.sum(), u.sum(), T.sum(v), v.a]
+  "#
+}
+
 check! {
   class_derived_with_init,
   r#"#!hebi
@@ -848,6 +1117,56 @@ check! {
   "#
 }
 
+check! {
+  call_class_method__error_not_enough_args,
+  r#"#!hebi
+    class T:
+      fn test(self, v):
+        return v
+    T().test()
+  "#
+}
+
+check! {
+  call_class_method__error_too_many_args,
+  r#"#!hebi
+    class T:
+      fn test(self, v):
+        return v
+    T().test(1, 2)
+  "#
+}
+
+check! {
+  call_class_method__error_no_field,
+  r#"#!hebi
+    class T:
+      pass
+    T().test()
+  "#
+}
+
+check! {
+  call_method_of_non_instance,
+  r#"#!hebi
+    fn double(v):
+      return v * 2
+    class T:
+      f = double
+    l := [T().f(1)]
+    l.push(double(2))
+    l
+  "#
+}
+
+check! {
+  call_method_of_primitive,
+  r#"#!hebi
+    v := 1
+    v.test()
+  "#
+}
+
 check! {
   call_class_method_derived_static,
   r#"#!hebi
@@ -953,6 +1272,55 @@ async fn subsequent_eval_with_error() {
   }
 }
 
+#[tokio::test]
+async fn module_field_by_slot() {
+  fn double(scope: Scope<'_>) -> Result<i32> {
+    Ok(scope.param::<i32>(0)? * 2)
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("util")
+      .function("double", double)
+      .finish(),
+  );
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      import util
+
+      class Fake:
+        fn double(self, v):
+          return -v
+
+      fn call_double(util, v):
+        return util.double(v)
+
+      print util.double(2)
+      print call_double(util, 3)
+      print call_double(Fake(), 4)
+    "#
+  );
+  let disassembly = hebi.compile(source).unwrap().disassemble().to_string();
+  assert!(disassembly.contains("load_module_field"), "{disassembly}");
+
+  hebi.eval_async(source).await.unwrap();
+  // `util` no longer refers to the module the field was resolved in
+  hebi
+    .eval_async("import util\nutil = Fake()\nprint util.double(5)")
+    .await
+    .unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.unwrap(), "4\n6\n-4\n-5\n");
+}
+
 check! {
   nested_optional_access,
   r#"#!hebi
@@ -962,6 +1330,37 @@ check! {
   "#
 }
 
+check! {
+  pipe_expr,
+  r#"#!hebi
+    fn double(v):
+      return v * 2
+    fn add(a, b):
+      return a + b
+    class Scale:
+      factor = 3
+      fn apply(self, v):
+        return v * self.factor
+
+    print 1 |> double
+    print 1 |> add(2) |> double
+    print 2 |> Scale().apply
+    print 1 + 2 |> double
+  "#
+}
+
+check! {
+  pipe_expr_optional,
+  r#"#!hebi
+    fn or_default(v, default):
+      return v ?? default
+
+    v := none
+    print ?v.a.b |> or_default("default")
+    print v ?? { a: 1 } |> to_str
+  "#
+}
+
 check! {
   empty_table,
   r#"#!hebi
@@ -1104,6 +1503,92 @@ check! {
   "#
 }
 
+check! {
+  optional_call,
+  r#"#!hebi
+    class Greeter:
+      fn greet(self, name):
+        return "hello " + name
+
+    fn side_effect():
+      print "evaluated"
+      return "key"
+
+    g := Greeter()
+    v := none
+    print ?g.greet("a")
+    print ?v.greet("b")
+    print ?g.missing("c")
+    print ?v.greet(side_effect())
+    print ?v[side_effect()].greet()
+    print ?v()
+  "#
+}
+
+check! {
+  optional_chain_with_values,
+  r#"#!hebi
+    class Node:
+      value = 0
+      next = none
+      init(self, value, next):
+        self.value = value
+        self.next = next
+      fn get_next(self):
+        return self.next
+
+    a := Node(1, Node(2, none))
+    t := { items: [a] }
+    print ?t["items"][0].next.value, ?t["items"][1].next.value, ?t["x"][0]
+    print ?a.get_next().value, ?a.get_next().get_next().value
+    print ?(1).x, ?(1)[0]
+  "#
+}
+
+check! {
+  optional_chain_args_are_not_optional,
+  r#"#!hebi
+    class Id:
+      fn id(self, x):
+        return x
+    v := Id()
+    ?v.id(v.missing)
+  "#
+}
+
+check! {
+  opt_call,
+  r#"#!hebi
+    class Hooks:
+      on_ready = none
+      on_exit = none
+
+    fn side_effect():
+      print "evaluated"
+
+    hooks := Hooks()
+    print hooks.on_ready?()
+    print hooks.on_exit?(side_effect())
+    fn ready(name):
+      return "ready " + name
+    hooks.on_ready = ready
+    print hooks.on_ready?("a")
+
+    callback := none
+    print callback?()
+    print 1 |> callback?()
+    print "b" |> ready?()
+  "#
+}
+
+check! {
+  opt_call_not_callable,
+  r#"#!hebi
+    callback := 1
+    callback?()
+  "#
+}
+
 check! {
   logical_or_expr_return_lhs,
   r#"#!hebi
@@ -1132,6 +1617,81 @@ check! {
   "#
 }
 
+check! {
+  multi_assign_swap,
+  r#"#!hebi
+    a := 1
+    b := 2
+    a, b = b, a
+    print a, b
+
+    l := [1, 2, 3]
+    l[0], l[2] = l[2], l[0]
+    print l[0], l[1], l[2]
+
+    fn f():
+      x := "x"
+      y := "y"
+      fn g():
+        x, y = y, x
+      g()
+      return [x, y]
+    f()
+  "#
+}
+
+check! {
+  multi_assign_chained,
+  r#"#!hebi
+    n := 0
+    fn next():
+      n += 1
+      return n
+
+    class T:
+      x = 0
+    t := T()
+    l := [0, 0]
+    a = t.x = l[1] = next()
+    print a, t.x, l[0], l[1]
+    n
+  "#
+}
+
+check! {
+  multi_assign_evaluation_order,
+  r#"#!hebi
+    fn log(name, value):
+      print name
+      return value
+
+    class T:
+      x = 0
+    t := T()
+    l := [0, 0]
+    log("t", t).x, log("l", l)[log("key", 1)] = log("first", 1), log("second", 2)
+    print t.x, l[0], l[1]
+  "#
+}
+
+check! {
+  multi_assign_grouped,
+  r#"#!hebi
+    a, b = c, d = 1, 2
+    print a, b, c, d
+  "#
+}
+
+check! {
+  list_set_index,
+  r#"#!hebi
+    l := [0, 1, 2]
+    l[1] = "a"
+    l[-1] += 10
+    print l[0], l[1], l[2]
+  "#
+}
+
 check! {
   list_indexing_zero,
   r#"#!hebi
@@ -1238,9 +1798,80 @@ check! {
 }
 
 check! {
-  builtin_list_methods,
+  for_iter_close,
   r#"#!hebi
-    v := [0, 1, 2]
+    class Cursor:
+      name = none
+      n = 0
+      max = 0
+
+      init(self, name, max):
+        self.name = name
+        self.max = max
+
+      fn iter(self):
+        return self
+
+      fn next(self):
+        n := self.n
+        self.n += 1
+        return n
+
+      fn done(self):
+        return self.n >= self.max
+
+      fn close(self):
+        print "close", self.name
+
+    # running out does not close the iterator
+    for v in Cursor("exhausted", 2):
+      pass
+
+    for v in Cursor("break", 10):
+      if v == 1:
+        break
+
+    for v in Cursor("continue", 3):
+      if v == 1:
+        continue
+      print v
+
+    fn find(target):
+      for v in Cursor("return", 10):
+        if v == target:
+          return v
+
+    print find(3)
+
+    # only the loop which is left is closed
+    for a in Cursor("outer", 2):
+      for b in Cursor("inner", 10):
+        break
+      while true:
+        break
+      print "next", a
+
+    class Context:
+      fn __enter__(self):
+        pass
+      fn __exit__(self, error):
+        print "exit"
+
+    for v in Cursor("with", 10):
+      with Context():
+        break
+
+    # iterators without `close` are left as they are
+    for v in [1, 2, 3]:
+      break
+    print "done"
+  "#
+}
+
+check! {
+  builtin_list_methods,
+  r#"#!hebi
+    v := [0, 1, 2]
 
     print "len", v.len()
     print "is_empty", v.is_empty()
@@ -1350,6 +1981,28 @@ check! {
   "#
 }
 
+check! {
+  builtin_str_case,
+  r#"#!hebi
+    print "Straße".casefold(), "STRASSE".casefold()
+    print "ΣΊΣΥΦΟΣ".casefold(), "σίσυφος".casefold()
+    print "Hebi".eq_ignore_case("hEBI"), "straße".eq_ignore_case("STRASSE")
+    print "ǅ".eq_ignore_case("ǆ"), "a".eq_ignore_case("b")
+    print Str.casefold("ÀB"), Str.eq_ignore_case("Ünïcode", "ÜNÏCODE")
+  "#
+}
+
+check! {
+  builtin_str_escape,
+  r#"#!hebi
+    print "say \"hi\"\n\tto\\you\u{1}".escape_json()
+    print "<a href=\"x?a=1&b='2'\">é</a>".escape_html()
+    print "file.txt".quote_shell(), "".quote_shell()
+    print "it's $HOME; rm -rf *".quote_shell()
+    print Str.escape_json("é"), Str.quote_shell("a b")
+  "#
+}
+
 check! {
   builtin_collect,
   r#"#!hebi
@@ -1389,6 +2042,128 @@ check! {
     print parse_int(10)
     print parse_int(10.0)
     print parse_int("10")
+    print parse_int(-10.9)
+    parse_int(-3000000000.5)
+  "#
+}
+
+check! {
+  builtin_round,
+  r#"#!hebi
+    print round(2.5), round(-2.5), round(0.4), round(-0.4)
+    print round(3.14159, 2), round(-3.14159, 3), round(1234.5, -2), round(1e300, 400)
+    print round(7), round(15, -1), round(-15, -1), round(14, -1), round(123, -10)
+    print round(1.0 / 0.0), round(-1.0 / 0.0, 2)
+    round(2147483647, -1)
+  "#
+}
+
+check! {
+  builtin_trunc_sign,
+  r#"#!hebi
+    print trunc(2.7), trunc(-2.7), trunc(5)
+    print sign(-3), sign(0), sign(7)
+    print sign(-0.5), sign(0.0), sign(-0.0), sign(2.5)
+    trunc("1.5")
+  "#
+}
+
+check! {
+  builtin_isnan_isinf,
+  r#"#!hebi
+    nan := 0.0 / 0.0
+    inf := 1.0 / 0.0
+    print isnan(nan), isnan(inf), isnan(1.0), isnan(1)
+    print isinf(inf), isinf(-inf), isinf(nan), isinf(1e308), isinf(1)
+    print isnan(sign(nan))
+  "#
+}
+
+check! {
+  builtin_timeit,
+  r#"#!hebi
+    calls := 0
+    fn work():
+      calls += 1
+
+    elapsed := timeit(work, 3)
+    print type_of(elapsed), calls, elapsed.secs() >= 0.0
+    timeit(work)
+    timeit(work, 0)
+    print calls
+    timeit(work, -1)
+  "#
+}
+
+check! {
+  builtin_assert_eq,
+  r#"#!hebi
+    class Point:
+      x = 0
+      y = 0
+      init(self, x, y):
+        self.x = x
+        self.y = y
+
+    assert_eq(1, 1.0)
+    assert_eq([1, { a: "b" }], [1, { a: "b" }])
+    assert_eq(Point(1, 2), Point(1, 2))
+    print "ok"
+    assert_eq(
+      { name: "a", items: [1, 2, 3], at: Point(0, 0), ["a key"]: true },
+      { name: "b", items: [1, "2"], at: Point(0, 1), ["a key"]: false, extra: none },
+    )
+  "#
+}
+
+check! {
+  builtin_assert_eq__nan,
+  r#"#!hebi
+    nan := 0.0 / 0.0
+    assert_eq(nan, nan)
+  "#
+}
+
+check! {
+  did_you_mean_global,
+  r#"#!hebi
+    counter := 1
+    print countr
+  "#
+}
+
+check! {
+  did_you_mean_builtin,
+  r#"#!hebi
+    to_itn(1.5)
+  "#
+}
+
+check! {
+  did_you_mean_nothing_close,
+  r#"#!hebi
+    x := 1
+    y
+  "#
+}
+
+check! {
+  did_you_mean_field,
+  r#"#!hebi
+    class Point:
+      x = 0
+      fn length(self):
+        return self.x
+    Point().lenght()
+  "#
+}
+
+check! {
+  did_you_mean_set_field,
+  r#"#!hebi
+    class Config:
+      timeout = 0
+    Config().timout = 5
   "#
 }
 
@@ -1442,6 +2217,13 @@ check! {
   "#
 }
 
+check! {
+  global_builtin_functions__to_int__out_of_range,
+  r#"#!hebi
+    to_int(3000000000.0)
+  "#
+}
+
 check! {
   global_builtin_functions__to_int__bad_input,
   r#"#!hebi
@@ -1633,3 +2415,4464 @@ check! {
     print (a + f())
   "#
 }
+
+check! {
+  random_module,
+  r#"#!hebi
+    import random
+
+    random.seed(42)
+    v := [1, 2, 3, 4, 5]
+    random.shuffle(v)
+    print v.join(", ")
+    print random.choice(v)
+    print random.range(0, 10)
+    print random.range(-5, -2)
+    print random.random()
+    print random.gauss(0, 1)
+    print random.gauss(10.0, 0.5)
+  "#
+}
+
+check! {
+  random_module_choice_empty,
+  r#"#!hebi
+    from random import choice
+    choice([])
+  "#
+}
+
+check! {
+  random_module_range_empty,
+  r#"#!hebi
+    from random import range
+    range(10, 10)
+  "#
+}
+
+#[tokio::test]
+async fn random_module_is_replayable() {
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from random import random, range, gauss
+      [random(), range(0, 1000), gauss(0, 1), random()]
+    "#
+  );
+
+  async fn run(seed: u64, source: &str) -> String {
+    let mut hebi = crate::public::Hebi::builder().seed(seed).finish();
+    format!("{:?}", hebi.eval_async(source).await.unwrap())
+  }
+
+  assert_eq!(run(7, source).await, run(7, source).await);
+  assert_ne!(run(7, source).await, run(8, source).await);
+
+  // negative seeds stand for seeds above `i32::MAX`
+  let reseeded = format!("from random import seed\nseed(-1)\n{source}");
+  assert_eq!(run(0, &reseeded).await, run(u32::MAX as u64, source).await);
+}
+
+check! {
+  crypto_module,
+  r#"#!hebi
+    import crypto
+
+    print crypto.sha256("abc")
+    print crypto.sha256([97, 98, 99])
+    print crypto.md5("")
+    print crypto.hex_encode("hi")
+    print crypto.hex_decode("6869")
+    print crypto.base64_encode("hello")
+    print crypto.base64_decode("aGVsbG8=")
+    print crypto.hex_decode_bytes("00ff").join(", ")
+    print crypto.base64_decode_bytes("/w==").join(", ")
+    try:
+      crypto.base64_decode("/w==")
+    catch e:
+      print e["message"]
+    try:
+      crypto.base64_decode("aGVsbG8")
+    catch e:
+      print e["message"]
+  "#
+}
+
+#[tokio::test]
+async fn crypto_uuid4_ignores_random_seed() {
+  let source = "import crypto\nimport random\nrandom.seed(0)\ncrypto.uuid4()";
+  let mut uuids = Vec::new();
+  for _ in 0..2 {
+    let mut hebi = crate::public::Hebi::new();
+    uuids.push(hebi.eval_async(source).await.unwrap().to_string());
+  }
+  assert_ne!(uuids[0], uuids[1]);
+  for uuid in &uuids {
+    let groups = uuid.split('-').map(str::len).collect::<Vec<_>>();
+    assert_eq!(groups, [8, 4, 4, 4, 12], "{uuid}");
+    assert_eq!(&uuid[14..15], "4", "{uuid}");
+    assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"), "{uuid}");
+  }
+}
+
+check! {
+  crypto_module_invalid_input,
+  r#"#!hebi
+    from crypto import sha256
+    sha256([256])
+  "#
+}
+
+#[tokio::test]
+async fn time_values_cross_the_boundary() {
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  fn timeout(_: Scope<'_>) -> Duration {
+    Duration::from_millis(1500)
+  }
+
+  fn epoch_plus(scope: Scope<'_>) -> Result<SystemTime> {
+    let offset = scope.param::<Duration>(0)?;
+    Ok(UNIX_EPOCH + offset)
+  }
+
+  fn double(scope: Scope<'_>) -> Result<Duration> {
+    Ok(scope.param::<Duration>(0)? * 2)
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("time")
+      .function("timeout", timeout)
+      .function("epoch_plus", epoch_plus)
+      .function("double", double)
+      .finish(),
+  );
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from time import timeout, epoch_plus, double
+
+      t := timeout()
+      print t, t.secs(), t.millis()
+      print t + t, double(t), double(2)
+      print t < double(1), t == t
+      a := epoch_plus(t)
+      b := epoch_plus(double(t))
+      print a.unix(), b.since(a), a < b
+    "#
+  );
+  hebi.eval_async(source).await.unwrap();
+
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(
+    output,
+    "1.5s 1.5 1500.0\n3s 3s 4s\ntrue true\n1.5 1.5s true\n"
+  );
+
+  let global = hebi.global();
+  let b = global.get("b").unwrap();
+  let b = <SystemTime as crate::public::FromValue>::from_value(b, global).unwrap();
+  assert_eq!(b, UNIX_EPOCH + Duration::from_secs(3));
+}
+
+#[cfg(feature = "chrono")]
+#[tokio::test]
+async fn chrono_values_cross_the_boundary() {
+  use std::time::Duration;
+
+  use chrono::{DateTime, TimeZone, Utc};
+
+  fn times() -> [DateTime<Utc>; 3] {
+    [
+      Utc.with_ymd_and_hms(2024, 2, 29, 12, 30, 15).unwrap(),
+      Utc.timestamp_opt(-86_400, 250_000_000).unwrap(),
+      DateTime::UNIX_EPOCH,
+    ]
+  }
+
+  fn time_at(scope: Scope<'_>) -> Result<DateTime<Utc>> {
+    let index = scope.param::<i32>(0)?;
+    Ok(times()[index as usize])
+  }
+
+  fn later(scope: Scope<'_>) -> Result<DateTime<Utc>> {
+    let time = scope.param::<DateTime<Utc>>(0)?;
+    let offset = scope.param::<Duration>(1)?;
+    Ok(time + chrono::TimeDelta::from_std(offset).unwrap())
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("time")
+      .function("time_at", time_at)
+      .function("later", later)
+      .finish(),
+  );
+  hebi
+    .eval_async("from time import time_at, later")
+    .await
+    .unwrap();
+
+  let get = |hebi: &crate::public::Hebi, name: &str| {
+    let global = hebi.global();
+    let value = global.get(name).unwrap();
+    <DateTime<Utc> as crate::public::FromValue>::from_value(value, global).unwrap()
+  };
+  for (i, time) in times().into_iter().enumerate() {
+    let source = format!("t := time_at({i})\nmoved := later(t, 1.5)\nbefore := t < moved");
+    hebi.eval_async(&source).await.unwrap();
+    assert_eq!(get(&hebi, "t"), time);
+    assert_eq!(
+      get(&hebi, "moved"),
+      time + chrono::TimeDelta::milliseconds(1500)
+    );
+    assert_eq!(hebi.global().get("before").unwrap().as_bool(), Some(true));
+  }
+
+  // a timestamp made from a `SystemTime` converts to the same instant
+  let value = crate::public::IntoValue::into_value(
+    std::time::UNIX_EPOCH + Duration::from_secs(2),
+    hebi.global(),
+  )
+  .unwrap();
+  let value =
+    <DateTime<Utc> as crate::public::FromValue>::from_value(value, hebi.global()).unwrap();
+  assert_eq!(value.timestamp(), 2);
+}
+
+#[tokio::test]
+async fn native_callbacks() {
+  use std::collections::HashMap;
+  use std::sync::{Arc, Mutex};
+
+  use crate::public::Callback;
+
+  let handlers = Arc::new(Mutex::new(HashMap::<String, Callback>::new()));
+  let module = NativeModule::builder("events")
+    .function("on_event", {
+      let handlers = handlers.clone();
+      move |scope: Scope<'_>| -> Result<()> {
+        let name = scope.param::<String>(0)?;
+        let handler = scope.param::<Callback>(1)?;
+        handlers.lock().unwrap().insert(name, handler);
+        Ok(())
+      }
+    })
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&module);
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        from events import on_event
+
+        count := 0
+        fn on_click(n):
+          count += n
+          return count
+        on_event("click", on_click)
+
+        class Counter:
+          total = 0
+          fn add(self, n):
+            self.total += n
+            return self.total
+        counter := Counter()
+        on_event("add", counter.add)
+
+        fn apply(f, v):
+          return f(v)
+      "#
+    ))
+    .await
+    .unwrap();
+
+  let click = handlers.lock().unwrap().remove("click").unwrap();
+  let add = handlers.lock().unwrap().remove("add").unwrap();
+  assert_eq!(
+    click.call_async(&mut hebi, (1,)).await.unwrap().as_int(),
+    Some(1)
+  );
+  assert_eq!(
+    click.call_async(&mut hebi, (2,)).await.unwrap().as_int(),
+    Some(3)
+  );
+  assert_eq!(add.call(&mut hebi, (5,)).unwrap().as_int(), Some(5));
+  assert_eq!(hebi.eval("counter.total").unwrap().as_int(), Some(5));
+  // callbacks may be passed back into scripts
+  assert_eq!(hebi.call("apply", (&add, 10)).unwrap().as_int(), Some(15));
+
+  let e = hebi.eval("on_event(\"x\", 1)").unwrap_err();
+  assert!(e.to_string().contains("`1` is not callable"), "{e}");
+
+  let mut other = crate::public::Hebi::new();
+  let e = click.call(&mut other, (1,)).unwrap_err();
+  assert!(
+    e.to_string().contains("callback belongs to a different VM"),
+    "{e}"
+  );
+
+  click.release(&mut hebi);
+  assert!(hebi.global().inner.get_root(0).is_none());
+  // dropping a callback releases it too, even on another thread
+  assert!(hebi.global().inner.get_root(1).is_some());
+  std::thread::spawn(move || drop(add)).join().unwrap();
+  assert!(hebi.global().inner.get_root(1).is_none());
+}
+
+#[tokio::test]
+async fn coercion_hooks() {
+  use crate::public::{Coerced, FromValue, Table};
+
+  #[derive(Debug, PartialEq)]
+  struct EntityId(u32);
+
+  #[derive(Debug, PartialEq)]
+  struct Config {
+    width: i32,
+    title: String,
+  }
+
+  fn describe(scope: Scope<'_>) -> Result<String> {
+    let Coerced(EntityId(id)) = scope.param(0)?;
+    let Coerced(config) = scope.param::<Coerced<Config>>(1)?;
+    Ok(format!("#{id} {} {}", config.width, config.title))
+  }
+
+  fn shout(scope: Scope<'_>) -> Result<String> {
+    Ok(scope.param::<String>(0)?.to_uppercase())
+  }
+
+  let module = NativeModule::builder("host")
+    .function("describe", describe)
+    .function("shout", shout)
+    .finish();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .register_coercion(|value, global| {
+      if let Some(id) = value.as_int() {
+        return Ok(EntityId(id as u32));
+      }
+      let id = String::from_value(value, global)?;
+      match id.strip_prefix("entity:").and_then(|id| id.parse().ok()) {
+        Some(id) => Ok(EntityId(id)),
+        None => fail!("`{id}` is not an entity id"),
+      }
+    })
+    .unwrap();
+  hebi
+    .register_coercion(|value, global| {
+      let table = Table::from_value(value, global.clone())?;
+      let field = |name: &str| {
+        table
+          .get(name)
+          .ok_or_else(|| crate::internal::error::Error::from(error!("missing `{name}`")))
+      };
+      Ok(Config {
+        width: i32::from_value(field("width")?, global.clone())?,
+        title: String::from_value(field("title")?, global)?,
+      })
+    })
+    .unwrap();
+  // ints are accepted wherever a string is expected
+  hebi
+    .register_coercion(|value, _| match value.as_int() {
+      Some(v) => Ok(v.to_string()),
+      None => fail!("value is not a string or an int"),
+    })
+    .unwrap();
+  hebi.register(&module);
+  hebi
+    .eval_async("from host import describe, shout")
+    .await
+    .unwrap();
+
+  let value = hebi
+    .eval_async(r#"describe("entity:7", {width: 10, title: "main"})"#)
+    .await
+    .unwrap();
+  assert_eq!(value.to_string(), "#7 10 main");
+  // the `Config` hook converts `title` with the `String` hook
+  let value = hebi
+    .eval_async(r#"describe(7, {width: 10, title: 1})"#)
+    .await
+    .unwrap();
+  assert_eq!(value.to_string(), "#7 10 1");
+  assert_eq!(
+    hebi.eval_async("shout(\"a\")").await.unwrap().to_string(),
+    "A"
+  );
+  assert_eq!(
+    hebi.eval_async("shout(10)").await.unwrap().to_string(),
+    "10"
+  );
+
+  let e = hebi.eval_async("shout(none)").await.unwrap_err();
+  assert!(
+    e.to_string().contains("value is not a string or an int"),
+    "{e}"
+  );
+  let e = hebi
+    .eval_async(r#"describe("7", {width: 10, title: 1})"#)
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("`7` is not an entity id"), "{e}");
+  let e = hebi
+    .eval_async(r#"describe("entity:7", {width: 10})"#)
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("missing `title`"), "{e}");
+
+  // a second hook for the same type does not replace the first
+  let e = hebi.register_coercion(|_, _| Ok(EntityId(0))).unwrap_err();
+  assert!(e.to_string().contains("already registered"), "{e}");
+  let value = hebi
+    .eval_async(r#"describe(7, {width: 10, title: "main"})"#)
+    .await
+    .unwrap();
+  assert_eq!(value.to_string(), "#7 10 main");
+
+  // without a hook, the built-in conversion fails as usual
+  fn id(scope: Scope<'_>) -> Result<u32> {
+    let Coerced(EntityId(id)) = scope.param(0)?;
+    Ok(id)
+  }
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&NativeModule::builder("plain").function("id", id).finish());
+  let e = hebi
+    .eval_async("from plain import id\nid(\"entity:7\")")
+    .await
+    .unwrap_err();
+  assert!(
+    e.to_string().contains("value cannot be converted into"),
+    "{e}"
+  );
+}
+
+#[tokio::test]
+async fn integers_cross_the_boundary() {
+  use crate::public::Lossy;
+
+  fn port(scope: Scope<'_>) -> Result<u16> {
+    scope.param::<u16>(0)
+  }
+
+  fn size(_: Scope<'_>) -> usize {
+    4096
+  }
+
+  fn huge(_: Scope<'_>) -> u64 {
+    u64::MAX
+  }
+
+  fn lossy_huge(_: Scope<'_>) -> Lossy<u64> {
+    Lossy(u64::MAX)
+  }
+
+  fn lossy_byte(scope: Scope<'_>) -> Result<i32> {
+    Ok(scope.param::<Lossy<u8>>(0)?.0 as i32)
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("ints")
+      .function("port", port)
+      .function("size", size)
+      .function("huge", huge)
+      .function("lossy_huge", lossy_huge)
+      .function("lossy_byte", lossy_byte)
+      .finish(),
+  );
+  hebi
+    .eval_async("from ints import port, size, huge, lossy_huge, lossy_byte")
+    .await
+    .unwrap();
+
+  assert_eq!(
+    hebi.eval_async("port(8080)").await.unwrap().as_int(),
+    Some(8080)
+  );
+  assert_eq!(
+    hebi.eval_async("size()").await.unwrap().as_int(),
+    Some(4096)
+  );
+  assert_eq!(
+    hebi.eval_async("lossy_huge()").await.unwrap().as_float(),
+    Some(u64::MAX as f64)
+  );
+  assert_eq!(
+    hebi.eval_async("lossy_byte(300)").await.unwrap().as_int(),
+    Some(255)
+  );
+  assert_eq!(
+    hebi.eval_async("lossy_byte(-1.5)").await.unwrap().as_int(),
+    Some(0)
+  );
+
+  let e = hebi.eval_async("port(70000)").await.unwrap_err();
+  assert!(
+    e.to_string()
+      .contains("`70000` is out of range for u16 (0..=65535)"),
+    "{e}"
+  );
+  let e = hebi.eval_async("port(-1)").await.unwrap_err();
+  assert!(
+    e.to_string().contains("`-1` is out of range for u16"),
+    "{e}"
+  );
+  let e = hebi.eval_async("port(1.0)").await.unwrap_err();
+  assert!(e.to_string().contains("value is not an `int`"), "{e}");
+  let e = hebi.eval_async("huge()").await.unwrap_err();
+  assert!(
+    e.to_string()
+      .contains(&format!("`{}` is out of range for an int", u64::MAX)),
+    "{e}"
+  );
+}
+
+check! {
+  try_catch,
+  r#"#!hebi
+    try:
+      print "before"
+      undefined_variable
+      print "unreachable"
+    catch e:
+      print e["code"], e["message"]
+
+    fn div(a, b):
+      try:
+        return a / b
+      catch:
+        return none
+
+    print div(1, 2), div(1, 0)
+
+    try:
+      print "no error"
+    catch:
+      print "unreachable"
+  "#
+}
+
+check! {
+  try_catch_unwinds_calls,
+  r#"#!hebi
+    fn inner():
+      return 1 / 0
+    fn outer():
+      return inner() + 1
+
+    try:
+      outer()
+    catch e:
+      print e["message"]
+
+    try:
+      try:
+        1 / 0
+      catch:
+        print "inner"
+        undefined_variable
+    catch e:
+      print "outer", e["code"]
+  "#
+}
+
+check! {
+  call_in_place_keeps_caller_registers,
+  r#"#!hebi
+    fn add(a, b):
+      x := a * 10
+      y := b * 100
+      return x + y
+    fn fails(a):
+      b := a + 1
+      c := b + 1
+      return c / 0
+    fn outer(v):
+      l := [add(v, 1), add(add(1, 2), v)]
+      r := 0
+      try:
+        r = fails(v)
+      catch:
+        r = l
+      return [v, r, add(v, v)]
+    outer(3)
+  "#
+}
+
+check! {
+  try_catch_in_loop,
+  r#"#!hebi
+    i := 0
+    loop:
+      i += 1
+      try:
+        if i < 3:
+          continue
+        break
+      catch:
+        print "unreachable"
+    print i
+
+    # the handlers must be gone, so this is not caught
+    1 / 0
+  "#
+}
+
+check! {
+  try_catch_class,
+  r#"#!hebi
+    class Error:
+      message = none
+      init(self, message):
+        self.message = message
+    class NetworkError(Error): pass
+    class TimeoutError(NetworkError): pass
+    class ParseError(Error): pass
+
+    fn handle(error):
+      try:
+        throw error
+      catch TimeoutError as e:
+        print "timeout", e.message
+      catch NetworkError as e:
+        print "network", e.message
+      catch Error as e:
+        print "error", e.message
+      catch e:
+        print "other", e
+
+    handle(TimeoutError("slow"))
+    handle(NetworkError("down"))
+    handle(ParseError("bad"))
+    handle("oops")
+
+    # unmatched errors are passed on
+    try:
+      try:
+        throw ParseError("inner")
+      catch NetworkError as e:
+        print "unreachable"
+    catch ParseError as e:
+      print "outer", e.message
+
+    # builtin errors are tables, so only a catch-all matches them
+    try:
+      1 / 0
+    catch Error as e:
+      print "unreachable"
+    catch e:
+      print e["code"]
+
+    print TimeoutError("") is Error, ParseError("") is NetworkError
+  "#
+}
+
+check! {
+  throw_uncaught,
+  r#"#!hebi
+    class Error:
+      message = none
+      init(self, message):
+        self.message = message
+    class Other: pass
+    try:
+      throw Error("unhandled")
+    catch Other as e:
+      pass
+  "#
+}
+
+check! {
+  with_stmt,
+  r#"#!hebi
+    class Resource:
+      name = none
+      suppress = false
+      init(self, name, suppress):
+        self.name = name
+        self.suppress = suppress
+      fn __enter__(self):
+        print "enter", self.name
+        return self.name + "!"
+      fn __exit__(self, error):
+        if error == none:
+          print "exit", self.name
+        else:
+          print "exit", self.name, error["code"]
+        return self.suppress
+
+    with Resource("a", false) as value:
+      print "body", value
+
+    fn early():
+      with Resource("b", false):
+        try:
+          return "returned"
+        catch:
+          pass
+    print early()
+
+    i := 0
+    loop:
+      i += 1
+      with Resource("c", false):
+        if i < 2:
+          continue
+        break
+
+    with Resource("d", true):
+      1 / 0
+    print "suppressed"
+
+    try:
+      with Resource("e", false):
+        with Resource("f", false):
+          undefined_variable
+    catch e:
+      print "caught", e["code"]
+  "#
+}
+
+check! {
+  compare_equality,
+  r#"#!hebi
+    nan := 0.0 / 0.0
+    print 1 == 1.0, 1 != 1.5, nan == nan, nan != nan
+    print none == none, none == false, 0 == false, "1" == 1
+    print true == true, "a" == "a", "a" == "b"
+    print [1, "a", [none]] == [1.0, "a", [none]], [1, 2] == [1]
+    print {a: 1, b: [2]} == {b: [2.0], a: 1}, {a: 1} == {a: 2}
+    fn f():
+      pass
+    fn h():
+      pass
+    g := f
+    print f == g, f == [f][0], f == h
+  "#
+}
+
+check! {
+  compare_ordering,
+  r#"#!hebi
+    nan := 0.0 / 0.0
+    print 1 < 1.5, 2.0 >= 2, -1 > -1.5
+    print nan < 1, nan > 1, nan <= nan, 1 >= nan
+    print false < true, none <= none
+    print "a" < "b", "B" < "a", "ab" > "a", "é" > "z"
+    print [1, 2] < [1, 3], [1, 2] < [1, 2, 0], [2] > [1, 9], [1] <= [1.0]
+    print [nan] < [1], [nan] > [1], [nan] >= [nan], [1, nan] < [2, nan]
+  "#
+}
+
+check! {
+  compare_cyclic,
+  r#"#!hebi
+    a := [1]
+    a.push(a)
+    b := [1]
+    b.push(b)
+    print a == a
+    try:
+      print a == b
+    catch e:
+      print e["message"]
+    try:
+      print a < b
+    catch e:
+      print e["message"]
+    assert_eq(a, b)
+  "#
+}
+
+check! {
+  data_class_cyclic,
+  r#"#!hebi
+    class Node:
+      value = 0
+      next = none
+    a := Node()
+    a.next = a
+    b := Node()
+    b.next = b
+    print a
+    c := Node()
+    c.next = a
+    print c
+    print a == a
+    try:
+      print a == b
+    catch e:
+      print e["message"]
+  "#
+}
+
+check! {
+  compare_cross_type,
+  r#"#!hebi
+    1 < "1"
+  "#
+}
+
+check! {
+  compare_unsupported,
+  r#"#!hebi
+    {a: 1} < {a: 2}
+  "#
+}
+
+check! {
+  list_sort,
+  r#"#!hebi
+    nan := 0.0 / 0.0
+    v := [3, 1.5, nan, -2, 10, 1]
+    v.sort()
+    print v[0], v[1], v[2], v[3], v[4], v[5]
+
+    v := ["b", "a", "B", "ab"]
+    v.sort()
+    print v.join(" ")
+
+    v := [[2], [1, 2], [1]]
+    v.sort()
+    print v[0][0], v[1].len(), v[2][0]
+
+    v := [2, "a", 1]
+    try:
+      v.sort()
+    catch e:
+      print e["message"]
+    print v[0], v[1], v[2]
+  "#
+}
+
+check! {
+  float_format,
+  r#"#!hebi
+    print 0.1 + 0.2, 4 / 2, 1 / 3, -0.0
+    print 10.0 ** 300, 10.0 ** -7, 0.0 / 0.0
+    print to_str(2.0) + "|" + to_str(0.5), [1, 1.0, 1.25].join(",")
+  "#
+}
+
+#[tokio::test]
+async fn native_error_value() {
+  fn lookup(scope: Scope<'_>) -> Result<i32> {
+    let key = scope.param::<crate::public::Str>(0)?;
+    Err(crate::error_value!(
+      "not_found",
+      "no entry named `{}`", key.as_str();
+      { "key" => key.as_str(), "tried" => vec![1, 2] }
+    ))
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("store")
+      .function("lookup", lookup)
+      .finish(),
+  );
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from store import lookup
+      try:
+        lookup("a")
+      catch e:
+        print e["code"], e["message"]
+        print e["data"]["key"], e["data"]["tried"][1]
+    "#
+  );
+  hebi.eval_async(source).await.unwrap();
+
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(output, "not_found no entry named `a`\na 2\n");
+
+  // uncaught errors keep their value
+  let e = hebi
+    .eval_async("from store import lookup\nlookup(\"b\")")
+    .await
+    .unwrap_err();
+  match e {
+    crate::Error::Value(e) => {
+      assert_eq!(e.code, "not_found");
+      assert_eq!(
+        e.data,
+        vec![
+          ("key".into(), "b".into()),
+          ("tried".into(), vec![1, 2].into())
+        ]
+      );
+    }
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[tokio::test]
+async fn native_error_class() {
+  fn fetch(scope: Scope<'_>) -> Result<i32> {
+    let url = scope.param::<crate::public::Str>(0)?;
+    Err(crate::error_value!(
+      "TimeoutError",
+      "timed out fetching {}", url.as_str();
+      { "url" => url.as_str() }
+    ))
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("net")
+      .function("fetch", fetch)
+      .error_class("NetworkError")
+      .error_subclass("TimeoutError", "NetworkError")
+      .finish(),
+  );
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from net import fetch, NetworkError, TimeoutError
+      try:
+        fetch("a")
+      catch NetworkError as e:
+        print e is TimeoutError, e.code, e.message, e.data["url"]
+
+      class RetryError(NetworkError): pass
+      try:
+        throw RetryError()
+      catch NetworkError as e:
+        print e is RetryError
+    "#
+  );
+  hebi.eval_async(source).await.unwrap();
+
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(output, "true TimeoutError timed out fetching a a\ntrue\n");
+
+  // thrown instances leave the VM with their class name as the code
+  let e = hebi
+    .eval_async("from net import NetworkError\ne := NetworkError()\ne.message = \"down\"\nthrow e")
+    .await
+    .unwrap_err();
+  match e {
+    crate::Error::Value(e) => {
+      assert_eq!(e.code, "NetworkError");
+      assert_eq!(e.message, "down");
+    }
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[tokio::test]
+async fn native_context_manager() {
+  use std::sync::{Arc, Mutex};
+
+  struct Transaction {
+    log: Arc<Mutex<Vec<String>>>,
+  }
+
+  let log = Arc::new(Mutex::new(Vec::new()));
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+  hebi.register(&{
+    let log = log.clone();
+    NativeModule::builder("db")
+      .class::<Transaction>("Transaction", move |class| {
+        let log = log.clone();
+        class
+          .init(move |_| Ok(Transaction { log: log.clone() }))
+          .enter(|_, this| {
+            this.log.lock().unwrap().push("begin".into());
+            1
+          })
+          .exit(|_, this, error| {
+            let entry = match error {
+              Some(error) => format!("rollback {}", error.message),
+              None => "commit".into(),
+            };
+            this.log.lock().unwrap().push(entry);
+            Ok(false)
+          })
+          .finish()
+      })
+      .finish()
+  });
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from db import Transaction
+      with Transaction() as id:
+        print id
+      with Transaction():
+        throw "conflict"
+    "#
+  );
+  let e = hebi.eval_async(source).await.unwrap_err();
+  assert_eq!(e.to_error_value().message, "conflict");
+  assert_eq!(
+    *log.lock().unwrap(),
+    ["begin", "commit", "begin", "rollback conflict"]
+  );
+}
+
+#[tokio::test]
+async fn for_await_stream() {
+  use std::collections::VecDeque;
+  use std::sync::{Arc, Mutex};
+
+  let queue = Arc::new(Mutex::new(VecDeque::from([1, 2, 3])));
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.register(&{
+    let queue = queue.clone();
+    NativeModule::builder("stream")
+      .async_function("fetch", move |_| {
+        let queue = queue.clone();
+        async move {
+          // the stream only finds out that it ended while waiting
+          tokio::task::yield_now().await;
+          queue.lock().unwrap().pop_front()
+        }
+      })
+      .finish()
+  });
+
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        from stream import fetch
+        class Rows:
+          last = none
+          fn iter(self):
+            return self
+          fn next(self):
+            self.last = fetch()
+            return self.last
+          fn done(self):
+            return self.last == none
+        for row in Rows():
+          print "never", row
+        for await row in Rows():
+          print row
+        for await row in Rows():
+          print "empty", row
+      "#
+    ))
+    .await
+    .unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("1\n2\n3\n"));
+  assert!(queue.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn channel() {
+  let mut hebi = crate::public::Hebi::new();
+  let input = hebi.channel::<i32>(1);
+  let output = hebi.channel::<i32>(1);
+  hebi.global().define("input", &input).unwrap();
+  hebi.global().define("output", &output).unwrap();
+
+  let host = async {
+    for n in 1..=3 {
+      input.send(n).await.unwrap();
+    }
+    input.close();
+    let mut received = vec![];
+    while let Some(n) = output.recv().await {
+      received.push(n);
+    }
+    received
+  };
+  let script = hebi.eval_async(indoc::indoc!(
+    r#"#!hebi
+      loop:
+        n := input.recv()
+        if n == none:
+          break
+        output.send(n * 10)
+      output.close()
+      input.send(4)
+    "#
+  ));
+  let (received, result) = futures_util::join!(host, script);
+  assert_eq!(received, [10, 20, 30]);
+  assert!(result
+    .unwrap_err()
+    .to_string()
+    .contains("channel is closed"));
+  assert!(input.is_empty() && output.is_closed());
+}
+
+#[tokio::test]
+async fn cancellation_token() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  let token = crate::public::CancellationToken::new();
+  hebi.global().define("ctx", &token).unwrap();
+
+  let host = async {
+    tokio::task::yield_now().await;
+    token.cancel();
+  };
+  let script = hebi.eval_async(indoc::indoc!(
+    r#"#!hebi
+      print ctx.cancelled()
+      ctx.wait()
+      print ctx.cancelled()
+      ctx.wait()
+    "#
+  ));
+  let ((), result) = futures_util::join!(host, script);
+  result.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("false\ntrue\n"));
+
+  let e = hebi.eval("ctx.canceled()").unwrap_err().to_string();
+  assert!(e.contains("did you mean `cancelled`"), "{e}");
+}
+
+#[cfg(feature = "profile")]
+#[tokio::test]
+async fn profile_natives() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("host")
+      .function("fast", |_| {})
+      .function("fails", |_| -> crate::Result<()> {
+        Err(error!("failed").into())
+      })
+      .async_function("slow", |_| async {
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+      })
+      .finish(),
+  );
+  assert!(hebi.profile().natives.is_empty());
+
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        from host import fast, fails, slow
+        for _ in 0..10:
+          fast()
+        slow()
+        slow()
+        fails()
+      "#
+    ))
+    .await
+    .unwrap_err();
+  let natives = hebi.profile().natives;
+  let calls = natives
+    .iter()
+    .map(|timing| (timing.name.as_str(), timing.calls))
+    .collect::<Vec<_>>();
+  assert_eq!(calls, [("fast", 10), ("slow", 2), ("fails", 1)]);
+  assert!(natives[1].total >= std::time::Duration::from_millis(4));
+  assert!(natives[1].mean() >= std::time::Duration::from_millis(2));
+}
+
+#[cfg(feature = "profile")]
+#[test]
+fn profile_natives_by_module() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(&NativeModule::builder("a").function("f", |_| {}).finish());
+  hebi.register(&NativeModule::builder("b").function("f", |_| {}).finish());
+
+  hebi
+    .eval(indoc::indoc!(
+      r#"#!hebi
+        import a
+        import b
+        a.f()
+        b.f()
+        b.f()
+      "#
+    ))
+    .unwrap();
+  let calls = (hebi.profile().natives.iter())
+    .map(|timing| (timing.owner.clone(), timing.name.clone(), timing.calls))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    calls,
+    [
+      (Some("a".to_string()), "f".to_string(), 1),
+      (Some("b".to_string()), "f".to_string(), 2),
+    ]
+  );
+}
+
+#[cfg(feature = "profile")]
+#[test]
+fn profile_call_sites() {
+  use crate::public::profile::{CallSite, Dispatch};
+
+  let code = indoc::indoc!(
+    r#"#!hebi
+      fn make():
+        fn f(): pass
+        return f
+      fn a(): pass
+      fn b(): pass
+      fn c(): pass
+      fn d(): pass
+      fn e(): pass
+      fn each(fs):
+        for f in fs:
+          f()
+      each([a, b, c, d, e])
+      each([make(), make(), make()])
+      each([a, b])
+    "#
+  );
+  let mut hebi = crate::public::Hebi::new();
+  hebi.eval(code).unwrap();
+  let profile = hebi.profile();
+  let sites = |function: &str| {
+    (profile.call_sites.iter())
+      .filter(|site| site.function == function)
+      .map(|site| (site.calls, site.dispatch()))
+      .collect::<Vec<_>>()
+  };
+  // `iter`, `done` and `next` of the loop, then `f()`
+  assert_eq!(
+    sites("each"),
+    [
+      (3, Dispatch::Monomorphic),
+      (13, Dispatch::Monomorphic),
+      (10, Dispatch::Monomorphic),
+      (10, Dispatch::Megamorphic),
+    ]
+  );
+  assert!(sites("__main__")
+    .iter()
+    .all(|(_, dispatch)| *dispatch == Dispatch::Monomorphic));
+
+  let [hot] = &profile.megamorphic()[..] else {
+    panic!("{:?}", profile.megamorphic());
+  };
+  assert_eq!(&code[hot.span.range()], "f()");
+  // six functions were called, but counting stops after the fifth
+  assert_eq!(hot.targets, CallSite::POLYMORPHIC_LIMIT + 1);
+}
+
+#[cfg(feature = "decimal")]
+#[test]
+fn decimal() {
+  use rust_decimal::Decimal;
+
+  use crate::public::FromValue;
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.global().define("fee", Decimal::new(125, 2)).unwrap();
+  hebi
+    .eval(indoc::indoc!(
+      r#"#!hebi
+        price := 0.10d
+        total = 0d
+        for _ in 0..3:
+          total += price
+        print total, total == 0.30d, 0.1 + 0.2 == 0.3
+        print 1.50d * 2d, -(10d / 4d), 10d % 3d, 2.675d.round(2), 1.5d.scale()
+        print to_decimal(3) < 3.01d, to_decimal("1.10"), type_of(total)
+        print ?total.missing
+        fees = fee * 2d
+      "#
+    ))
+    .unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(
+    output.as_deref(),
+    Some("0.30 true false\n3.00 -2.50 1 2.68 1\ntrue 1.10 Decimal\nnone\n")
+  );
+  let global = hebi.global();
+  let total = Decimal::from_value(global.get("total").unwrap(), global.clone()).unwrap();
+  assert_eq!(total, Decimal::new(30, 2));
+  let fees = Decimal::from_value(global.get("fees").unwrap(), global.clone()).unwrap();
+  assert_eq!(fees, Decimal::new(250, 2));
+
+  for (code, error) in [
+    ("1d + 1", "operands must have the same type"),
+    ("1d / 0d", "division by zero"),
+    ("1d.missing", "has no field `missing`"),
+    ("to_decimal([])", "cannot be converted to a decimal"),
+    ("99999999999999999999999999999999d", "out of range"),
+  ] {
+    let e = hebi.eval(code).unwrap_err().to_string();
+    assert!(e.contains(error), "{code}: {e}");
+  }
+}
+
+#[cfg(not(feature = "decimal"))]
+#[test]
+fn decimal_requires_feature() {
+  let mut hebi = crate::public::Hebi::new();
+  let e = hebi.eval("1.50d").unwrap_err().to_string();
+  assert!(e.contains("require the `decimal` feature"), "{e}");
+}
+
+#[tokio::test]
+async fn compile_ast() {
+  use crate::public::ast::{BinaryOp, Expr, Module, Param, Stmt, Target};
+
+  // fn evens(n):
+  //   out := []
+  //   for i in 0..n:
+  //     if i % 2 == 1:
+  //       continue
+  //     out.push(i)
+  //   return out
+  // items := evens(7)
+  // items[0] = "zero"
+  // print items[0], items[3]
+  // items.len()
+  let module = Module::new([
+    Stmt::func(
+      "evens",
+      [Param::new("n")],
+      [
+        Stmt::decl("out", Expr::list([])),
+        Stmt::for_range(
+          "i",
+          Expr::int(0),
+          Expr::var("n"),
+          [
+            Stmt::if_(
+              [(
+                Expr::binary(
+                  BinaryOp::Eq,
+                  Expr::binary(BinaryOp::Rem, Expr::var("i"), Expr::int(2)),
+                  Expr::int(1),
+                ),
+                vec![Stmt::continue_()],
+              )],
+              None,
+            ),
+            Stmt::expr(Expr::var("out").field("push").call([Expr::var("i")])),
+          ],
+        ),
+        Stmt::return_(Some(Expr::var("out"))),
+      ],
+    ),
+    Stmt::decl("items", Expr::var("evens").call([Expr::int(7)])),
+    Stmt::assign(
+      Target::Index(Expr::var("items"), Expr::int(0)),
+      Expr::str("zero"),
+    ),
+    Stmt::print([
+      Expr::var("items").index(Expr::int(0)),
+      Expr::var("items").index(Expr::int(3)),
+    ]),
+    Stmt::expr(Expr::var("items").field("len").call([])),
+  ]);
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  let chunk = hebi.compile_ast(&module).unwrap();
+  let len = hebi.run(chunk).unwrap().as_int();
+  assert_eq!(len, Some(4));
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("zero 6\n"));
+
+  // errors point at the span of the node they are about
+  let hebi = crate::public::Hebi::builder().strict_globals(true).finish();
+  let module = Module::new([Stmt::expr(Expr::var("missing").at(10..20))]);
+  let Err(crate::Error::Syntax(error)) = hebi.compile_ast(&module) else {
+    panic!("expected a syntax error");
+  };
+  assert_eq!(error.errors()[0].span, (10..20).into());
+}
+
+#[tokio::test]
+async fn compile_ast_checks() {
+  use crate::public::ast::{Expr, Module, Param, Stmt};
+
+  let hebi = crate::public::Hebi::new();
+  let check = |body: Vec<Stmt>| match hebi.compile_ast(&Module::new(body)) {
+    Ok(_) => panic!("module compiled successfully"),
+    Err(e) => e.to_string(),
+  };
+
+  assert!(check(vec![Stmt::break_().at(0..5)]).contains("break outside of loop"));
+  assert!(check(vec![Stmt::return_(None)]).contains("return outside of function"));
+  // a function in a loop is not itself in the loop
+  let error = check(vec![Stmt::loop_([Stmt::func(
+    "f",
+    [],
+    [Stmt::yield_(None), Stmt::continue_()],
+  )])]);
+  assert!(error.contains("continue outside of loop"), "{error}");
+  let error = check(vec![Stmt::func(
+    "f",
+    [Param::optional("a", Expr::none()), Param::new("a")],
+    [],
+  )]);
+  assert!(error.contains("duplicate argument `a`"), "{error}");
+  assert!(
+    error.contains("non-default argument follows default argument"),
+    "{error}"
+  );
+  assert!(check(vec![Stmt::expr(Expr::float(f64::NAN))]).contains("must not be NaN"));
+
+  let mut deep = Expr::int(0);
+  for _ in 0..300 {
+    deep = Expr::list([deep]);
+  }
+  assert!(check(vec![Stmt::expr(deep)]).contains("nesting limit reached"));
+}
+
+#[tokio::test]
+async fn ast_template() {
+  use crate::public::ast::{self, BinaryOp, Expr, Module, Param, Stmt};
+
+  // a generator which emits a counter class and a function using it
+  let class = crate::ast_template!(
+    "class {name}:\n  n = {start}\n  fn {method}(self, {params}):\n    {body}\n    self.n += by\n    return self.n",
+    name = "Counter",
+    start = Expr::int(10),
+    method = "add",
+    params = vec![Param::new("by"), Param::optional("times", Expr::int(1))],
+    body = ast::template("{by} *= times", [("by", "by".into())]).unwrap(),
+  )
+  .unwrap();
+  let func = crate::ast_template!(
+    "fn {name}({arg}):\n  c := Counter()\n  {steps}\n  return {result}",
+    name = "run",
+    arg = "k",
+    steps = vec![
+      Stmt::expr(ast::template_expr("c.add(k)", []).unwrap()),
+      Stmt::expr(ast::template_expr("c.add(1, {t})", [("t", Expr::int(3).into())]).unwrap()),
+    ],
+    result = Expr::var("c").field("n"),
+  )
+  .unwrap();
+  let table =
+    ast::template_expr("{ {key}: 1, other: 2 }", [("key", Expr::str("a b").into())]).unwrap();
+
+  let mut body = class;
+  body.extend(func);
+  body.push(Stmt::decl("t", table));
+  body.push(Stmt::expr(Expr::binary(
+    BinaryOp::Add,
+    Expr::var("run").call([Expr::int(5)]),
+    Expr::var("t").index(Expr::str("a b")),
+  )));
+  let mut hebi = crate::public::Hebi::new();
+  let chunk = hebi.compile_ast(&Module::new(body)).unwrap();
+  assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(19));
+
+  let error = |result: crate::Result<Vec<Stmt>>| match result {
+    Ok(_) => panic!("template filled successfully"),
+    Err(e) => e.to_string(),
+  };
+  let e = error(ast::template("{a} + 1", []));
+  assert!(e.contains("no fragment for `{a}`"), "{e}");
+  let e = error(crate::ast_template!("a + 1", b = "b"));
+  assert!(e.contains("`{b}` does not appear in the template"), "{e}");
+  let e = error(crate::ast_template!(
+    "if a:\n  {body}\nelse:\n  {body}",
+    body = Stmt::pass()
+  ));
+  assert!(e.contains("may only be used once"), "{e}");
+  let e = error(crate::ast_template!(
+    "{f}.{field}",
+    f = "f",
+    field = Expr::int(0)
+  ));
+  assert!(e.contains("`{field}` must be a name here"), "{e}");
+  let e = error(crate::ast_template!("{x} := 0", x = vec![Param::new("p")]));
+  assert!(e.contains("`{x}` must be a name here"), "{e}");
+  let e = match ast::template_expr("a := 1", []) {
+    Ok(_) => panic!("template filled successfully"),
+    Err(e) => e.to_string(),
+  };
+  assert!(e.contains("not a single expression"), "{e}");
+  for name in ["while", "none", "a b", "1a", ""] {
+    let e = error(crate::ast_template!("{x} := 0", x = name));
+    assert!(e.contains("which is not a valid name"), "{e}");
+  }
+
+  // templates may be built at runtime
+  let src = format!("{} := {{v}}", ["x"; 2].join("_"));
+  let body = ast::template(&src, [("v", Expr::int(7).into())]).unwrap();
+  drop(src);
+  let mut body = body;
+  body.push(Stmt::expr(Expr::var("x_x")));
+  let chunk = hebi.compile_ast(&Module::new(body)).unwrap();
+  assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(7));
+
+  // fragments are only checked once they are compiled
+  let body = ast::template("return 0", []).unwrap();
+  let e = match hebi.compile_ast(&Module::new(body)) {
+    Ok(_) => panic!("module compiled successfully"),
+    Err(e) => e.to_string(),
+  };
+  assert!(e.contains("return outside of function"), "{e}");
+}
+
+#[test]
+fn inline_functions() {
+  let source = indoc::indoc!(
+    r#"
+      trace := ""
+      fn log(v):
+        trace = trace + to_str(v) + " "
+        return v
+      fn sub(a, b): return a - b
+      fn inc(v): return v + 1
+      fn twice(v): return inc(inc(v))
+      fn norm(p): return p.x * p.x + p.y * p.y
+      class Point:
+        x = 3
+        y = 4
+      fn shadowed():
+        inc := fn_inc
+        return inc(0)
+      fn fn_inc(v): return v + 10
+      p := Point()
+      print sub(log(5), log(3)), twice(1), norm(p), shadowed(), trace
+    "#
+  );
+
+  let run = |inline: bool| {
+    let mut hebi = crate::public::Hebi::builder()
+      .output(crate::public::io::StringWriter::new())
+      .inline_functions(inline)
+      .finish();
+    hebi.eval(source).unwrap();
+    let output = hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<crate::public::io::StringWriter>()
+      .map(|output| output.as_str().to_string());
+    output.unwrap()
+  };
+  assert_eq!(run(true), "2 3 25 10 5 3 \n");
+  assert_eq!(run(true), run(false));
+}
+
+#[tokio::test]
+async fn hoist_loop_invariants() {
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        step := 1
+        total := 0
+        for i in 0..5:
+          total += step
+        fn bump():
+          step = 10
+        for i in 0..3:
+          total += step
+          bump()
+        while total < 0:
+          print missing
+        total
+      "#
+    ))
+    .await
+    .unwrap();
+  // a loop which calls functions sees the globals they assign, and one
+  // which never runs doesn't read globals which were never defined
+  assert_eq!(value.as_int(), Some(26));
+}
+
+#[tokio::test]
+async fn loop_sees_globals_changed_by_host() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        done := false
+        fn wait():
+          while !done:
+            pass
+      "#
+    ))
+    .await
+    .unwrap();
+
+  hebi.spawn("wait", ()).unwrap();
+  assert!(!hebi.step_async(100).await.unwrap());
+  hebi.global().define("done", true).unwrap();
+  let mut slices = 0;
+  while !hebi.step_async(100).await.unwrap() {
+    slices += 1;
+    assert!(slices < 10, "the loop did not see the new value of `done`");
+  }
+}
+
+#[tokio::test]
+async fn long_jumps() {
+  // machine-generated bodies large enough to need wide and
+  // constant-pool jump offsets
+  for n in [100, 10_000] {
+    let if_body = "    total += 1\n".repeat(n);
+    let try_body = "  total += 1\n".repeat(n);
+    let source = format!(
+      "total := 0\n\
+       i := 0\n\
+       while i < 3:\n  if i != 1:\n{if_body}  i += 1\n\
+       try:\n{try_body}  undefined()\n\
+       catch:\n{try_body}\
+       total\n"
+    );
+
+    let mut hebi = Vm::default();
+    let value = hebi.eval(&source).await.unwrap().to_int();
+    assert_eq!(value, Some(4 * n as i32), "n = {n}");
+  }
+}
+
+#[tokio::test]
+async fn many_locals() {
+  // machine-generated functions whose locals need wide register operands,
+  // including ones captured by closures
+  for n in [300i32, 1500] {
+    let locals = (0..n)
+      .map(|i| format!("  v{i} := {i}\n"))
+      .collect::<String>();
+    let source = format!(
+      "fn f():\n{locals}  \
+       fn get():\n    return v{last}\n  \
+       fn set(v):\n    v{last} = v\n  \
+       set(v{last} + v{mid})\n  \
+       return get() + v0\n\
+       f()\n",
+      last = n - 1,
+      mid = n / 2,
+    );
+
+    let mut hebi = Vm::default();
+    let value = hebi.eval(&source).await.unwrap().to_int();
+    assert_eq!(value, Some(n - 1 + n / 2), "n = {n}");
+  }
+}
+
+#[tokio::test]
+async fn function_too_large() {
+  // every list element is evaluated into its own register
+  let list = |n: usize| format!("[{}]", "0, ".repeat(n));
+
+  let source = format!(
+    "fn big():\n  return {}\nbig()\n",
+    list(codegen::MAX_REGISTERS + 1)
+  );
+  let mut hebi = Vm::default();
+  let e = hebi.eval(&source).await.unwrap_err();
+  match e {
+    Error::Syntax(e) => {
+      assert_eq!(e.errors().len(), 1);
+      assert_eq!(e.errors()[0].span, (3..6).into());
+      assert!(
+        e.errors()[0]
+          .message
+          .starts_with("function `big` is too large"),
+        "{}",
+        e.errors()[0].message
+      );
+    }
+    e => panic!("expected syntax error, got {e}"),
+  }
+
+  let source = format!("fn big():\n  return {}\nbig()\n", list(1000));
+  hebi.eval(&source).await.unwrap();
+}
+
+#[test]
+fn emit_errors_in_source_order() {
+  let list = |n: usize| format!("[{}]", "0, ".repeat(n));
+
+  let hebi = crate::public::Hebi::builder().strict_globals(true).finish();
+  let source = format!(
+    "fn outer():\n  a := missing\n  fn inner():\n    return {}\n  return {}\nfn big():\n  return      {}\nother\n",
+    list(codegen::MAX_REGISTERS + 1),
+    list(codegen::MAX_REGISTERS + 1),
+    list(codegen::MAX_REGISTERS + 1),
+  );
+  let e = hebi.compile(&source).err().unwrap();
+  match e {
+    Error::Syntax(e) => {
+      let errors = e
+        .errors()
+        .iter()
+        .map(|e| {
+          let message = e.message.split(':').next().unwrap();
+          (message, &source.as_str()[e.span])
+        })
+        .collect::<Vec<_>>();
+      assert_eq!(
+        errors,
+        [
+          ("function `outer` is too large", "outer"),
+          ("undefined global `missing`", "missing"),
+          ("function `inner` is too large", "inner"),
+          ("function `big` is too large", "big"),
+          ("undefined global `other`", "other"),
+        ]
+      );
+    }
+    e => panic!("expected syntax error, got {e}"),
+  }
+}
+
+#[test]
+fn shared_globals_across_threads() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<crate::public::SharedGlobals>();
+
+  fn double(scope: Scope<'_>) -> Result<i32> {
+    Ok(scope.param::<i32>(0)? * 2)
+  }
+
+  let shared = crate::public::SharedGlobals::builder()
+    .constant("LIMIT", 10)
+    .constant("NAMES", vec!["a", "b"])
+    .module("util", "fn add(a, b):\n  return a + b\n")
+    .native_module(
+      &NativeModule::builder("native")
+        .function("double", double)
+        .finish(),
+    )
+    .finish();
+
+  let handles = (0..4)
+    .map(|i| {
+      let shared = shared.clone();
+      std::thread::spawn(move || {
+        let mut hebi = crate::public::Hebi::builder().shared(&shared).finish();
+        let source = format!(
+          "from util import add\nfrom native import double\n\
+           LIMIT = LIMIT + {i}\n\
+           v := none\n\
+           if NAMES[1] == \"b\":\n  v = add(double(LIMIT), 1)\n\
+           v"
+        );
+        hebi
+          .eval(&source)
+          .map(|v| v.to_string())
+          .unwrap_or_else(|e| e.to_string())
+      })
+    })
+    .collect::<Vec<_>>();
+  let results = handles
+    .into_iter()
+    .map(|h| h.join().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(results, ["21", "23", "25", "27"]);
+}
+
+#[test]
+fn shared_modules_are_compiled_once() {
+  let shared = crate::public::SharedGlobals::builder()
+    .module("util", "fn add(a, b):\n  return a + b\n")
+    .finish();
+
+  let compiled = || {
+    let mut hebi = crate::public::Hebi::builder()
+      .shared(&shared)
+      .output(crate::public::io::StringWriter::new())
+      .log_compile_phases(true)
+      .finish();
+    let value = hebi.eval("from util import add\nadd(1, 2)").unwrap();
+    assert_eq!(value.as_int(), Some(3));
+    let output = hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<crate::public::io::StringWriter>()
+      .map(|output| output.as_str().to_string());
+    output.unwrap().contains("compiled `util`")
+  };
+  assert!(compiled());
+  assert!(!compiled());
+  assert!(!compiled());
+}
+
+#[test]
+fn json_interchange() {
+  let mut hebi = crate::public::Hebi::new();
+
+  let value = hebi
+    .eval(r#"{name: "hebi", tags: ["a", "b"], version: 0.1, stable: false, extra: none}"#)
+    .unwrap();
+  assert_eq!(
+    value.to_json_string().unwrap(),
+    r#"{"name":"hebi","tags":["a","b"],"version":0.1,"stable":false,"extra":null}"#
+  );
+
+  let e = hebi
+    .eval("fn f():\n  pass\nf")
+    .unwrap()
+    .to_json_string()
+    .unwrap_err();
+  assert!(e.to_string().starts_with("cannot convert"), "{e}");
+
+  let value = hebi.json_to_value(r#"{"a": [1, 2.5, "x"]}"#).unwrap();
+  let table = value
+    .as_object::<crate::public::Table>(hebi.global())
+    .unwrap();
+  let list = table
+    .get("a")
+    .unwrap()
+    .as_object::<crate::public::List>(hebi.global())
+    .unwrap();
+  assert_eq!(list.len(), 3);
+  assert_eq!(list.get(0).unwrap().as_int(), Some(1));
+  assert_eq!(list.get(1).unwrap().as_float(), Some(2.5));
+
+  assert!(hebi.json_to_value("[1, 2").is_err());
+}
+
+#[test]
+fn fixed_float_format() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .float_format(FloatFormat::Fixed(2))
+    .finish();
+  let value = hebi
+    .eval("print 1 / 3, 2, 0.0 / 0.0\nto_str(10.0 ** 300).len()")
+    .unwrap();
+  assert_eq!(value.as_int(), Some(304));
+  // host-side `Display` is not affected
+  let value = hebi.eval("1 / 3").unwrap();
+  assert_eq!(value.to_string(), "0.3333333333333333");
+
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(output, "0.33 2 NaN\n");
+
+  // floats nested in data class instances use the same format
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .float_format(FloatFormat::Fixed(2))
+    .finish();
+  hebi
+    .eval(indoc::indoc!(
+      r#"
+        class Point:
+          x = 0.0
+          y = 0.0
+        class Line:
+          a = none
+          b = none
+        p := Point()
+        p.x = 1.0 / 3
+        p.y = 2.0
+        line := Line()
+        line.a = p
+        line.b = Point()
+        print p
+        print line, [1.0 / 3]
+      "#
+    ))
+    .unwrap();
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(
+    output,
+    "Point(x=0.33, y=2.00)\nLine(a=Point(x=0.33, y=2.00), b=Point(x=0.00, y=0.00)) <list>\n"
+  );
+}
+
+#[tokio::test]
+async fn native_argument_validation() {
+  fn greet(scope: Scope<'_>) -> Result<String> {
+    let args = scope.args().arity(1..=3)?;
+    let name = args.get::<String>(0, "name")?;
+    let times = args.get_or(1, "times", 1)?;
+    let options = args.kwargs(2, "options")?;
+    options.deny_unknown(&["shout"])?;
+    let greeting = format!("hello {name}").repeat(times as usize);
+    match options.get_or("shout", false)? {
+      true => Ok(greeting.to_uppercase()),
+      false => Ok(greeting),
+    }
+  }
+
+  fn exact(scope: Scope<'_>) -> Result<()> {
+    scope.args().arity(2..3)?;
+    Ok(())
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("greet")
+      .function("greet", greet)
+      .function("exact", exact)
+      .finish(),
+  );
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from greet import greet, exact
+      print greet("a"), greet("b", 2), greet("c", none, {shout: true})
+      try:
+        greet()
+      catch e:
+        print e["message"]
+      try:
+        greet(1, 2, 3, 4)
+      catch e:
+        print e["message"]
+      try:
+        greet(1)
+      catch e:
+        print e["message"]
+      try:
+        greet("a", "b")
+      catch e:
+        print e["message"]
+      try:
+        greet("a", 1, {loud: true})
+      catch e:
+        print e["message"]
+      try:
+        greet("a", 1, {shout: 1})
+      catch e:
+        print e["message"]
+      try:
+        greet("a", 1, 2)
+      catch e:
+        print e["message"]
+      try:
+        exact(1)
+      catch e:
+        print e["message"]
+    "#
+  );
+  hebi.eval_async(source).await.unwrap();
+
+  let output = String::from_utf8(
+    hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<Vec<u8>>()
+      .cloned()
+      .unwrap(),
+  )
+  .unwrap();
+  assert_eq!(
+    output,
+    indoc::indoc!(
+      "
+      hello a hello bhello b HELLO C
+      expected 1 to 3 arguments, got 0
+      expected 1 to 3 arguments, got 4
+      invalid argument `name`: value is not a `str` (got `int`)
+      invalid argument `times`: value is not an `int` (got `str`)
+      unknown keyword argument `loud`
+      invalid argument `shout`: value is not a `bool` (got `int`)
+      invalid argument `options`: value is not a `table` (got `int`)
+      expected 2 arguments, got 1
+      "
+    )
+  );
+}
+
+#[test]
+fn call_batch() {
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval(indoc::indoc!(
+      r#"#!hebi
+        fn score(row, weight):
+          if row < 0:
+            negative_row()
+          try:
+            undefined()
+          catch e:
+            pass
+          return row * weight + 1
+      "#
+    ))
+    .unwrap();
+
+  let scores = hebi
+    .call_batch("score", (0..100_000).map(|i| (i, 2)))
+    .unwrap()
+    .iter()
+    .map(|v| v.as_int().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(scores.len(), 100_000);
+  assert_eq!(scores[..3], [1, 3, 5]);
+  assert_eq!(scores[99_999], 199_999);
+
+  let e = hebi
+    .call_batch("score", [(1, 1), (-1, 1), (2, 1)])
+    .unwrap_err();
+  assert!(e.to_string().contains("negative_row"), "{e}");
+  let e = hebi.call("missing", ()).unwrap_err();
+  assert!(e.to_string().contains("`missing` is not defined"), "{e}");
+  let e = hebi.call("score", (1,)).unwrap_err();
+  assert!(e.to_string().contains("expected"), "{e}");
+
+  // the stack is left clean after errors
+  let value = hebi.call("score", (3, 3)).unwrap();
+  assert_eq!(value.as_int(), Some(10));
+  let value = hebi.eval("score(1, 1)").unwrap();
+  assert_eq!(value.as_int(), Some(2));
+}
+
+#[tokio::test]
+async fn call_batch_native_async() {
+  async fn double(scope: Scope<'_>) -> Result<i32> {
+    let value = scope.param::<i32>(0)?;
+    tokio::task::yield_now().await;
+    Ok(value * 2)
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("math")
+      .async_function("double", double)
+      .finish(),
+  );
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        from math import double
+        fn twice(v):
+          return double(v)
+        fn quad(v):
+          return double(double(v))
+      "#
+    ))
+    .await
+    .unwrap();
+
+  let values = hebi
+    .call_batch_async("twice", (0..3).map(|i| (i,)))
+    .await
+    .unwrap()
+    .iter()
+    .map(|v| v.as_int().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(values, [0, 2, 4]);
+
+  let values = hebi
+    .call_batch_async("quad", (0..3).map(|i| (i,)))
+    .await
+    .unwrap()
+    .iter()
+    .map(|v| v.as_int().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(values, [0, 4, 8]);
+}
+
+#[test]
+fn strict_globals() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .strict_globals(true)
+    .finish();
+  hebi.global().define("limit", 10).unwrap();
+
+  // globals defined by the host or assigned by the script are fine
+  let value = hebi
+    .eval(indoc::indoc!(
+      r#"#!hebi
+        fn get():
+          return total + limit
+        total := 5
+        get()
+      "#
+    ))
+    .unwrap();
+  assert_eq!(value.as_int(), Some(15));
+
+  let source = "fn f():\n  return missing\nlimit + other\n";
+  let e = hebi.compile(source).err().unwrap();
+  match e {
+    Error::Syntax(e) => {
+      let errors = e
+        .errors()
+        .iter()
+        .map(|e| (e.message.as_str(), source[e.span].to_string()))
+        .collect::<Vec<_>>();
+      assert_eq!(
+        errors,
+        [
+          ("undefined global `missing`", "missing".to_string()),
+          ("undefined global `other`", "other".to_string())
+        ]
+      );
+    }
+    e => panic!("expected syntax error, got {e}"),
+  }
+
+  let e = hebi.compile("counter := 0\nprint countr\n").err().unwrap();
+  match e {
+    Error::Syntax(e) => assert_eq!(
+      e.errors()[0].message,
+      "undefined global `countr`, did you mean `counter`?"
+    ),
+    e => panic!("expected syntax error, got {e}"),
+  }
+
+  // reads which pass the compile time check may still fail at runtime
+  let e = hebi
+    .eval("if false:\n  missing = 1\nfn f():\n  return missing\nf()\n")
+    .unwrap_err();
+  match e {
+    Error::Value(e) => {
+      assert_eq!(e.code, "name_error");
+      assert_eq!(e.message, "undefined global `missing`");
+      assert_eq!(e.data, vec![("name".into(), "missing".into())]);
+    }
+    e => panic!("expected name error, got {e}"),
+  }
+
+  // without strict mode, the read is a plain runtime error
+  let mut hebi = crate::public::Hebi::new();
+  let e = hebi.eval("fn f():\n  return missing\nf()\n").unwrap_err();
+  match e {
+    Error::Vm(e) => assert_eq!(e.message, "undefined global missing"),
+    e => panic!("expected runtime error, got {e}"),
+  }
+}
+
+#[test]
+fn forbidden_constructs() {
+  use crate::public::Construct;
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .forbid(Construct::Import)
+    .forbid(Construct::Class)
+    .forbid(Construct::While)
+    .forbid(Construct::Loop)
+    .finish();
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from math import floor
+      class T:
+        fn f(self):
+          while true:
+            pass
+      fn f():
+        loop 3:
+          pass
+        for i in 0..3:
+          pass
+      import math
+    "#
+  );
+  let e = hebi.compile(source).err().unwrap();
+  match e {
+    Error::Syntax(e) => {
+      let errors = e
+        .errors()
+        .iter()
+        .map(|e| (e.message.as_str(), source[e.span].to_string()))
+        .collect::<Vec<_>>();
+      assert_eq!(
+        errors,
+        [
+          ("imports are not allowed", "from".to_string()),
+          ("classes are not allowed", "T".to_string()),
+          ("`while` loops are not allowed", "while".to_string()),
+          ("`loop` statements are not allowed", "loop".to_string()),
+          ("imports are not allowed", "import".to_string()),
+        ]
+      );
+    }
+    e => panic!("expected syntax error, got {e}"),
+  }
+
+  // everything else is still allowed
+  let value = hebi
+    .eval(indoc::indoc!(
+      r#"#!hebi
+        fn sum(n):
+          total := 0
+          for i in 0..n:
+            total += i
+          return total
+        sum(4)
+      "#
+    ))
+    .unwrap();
+  assert_eq!(value.as_int(), Some(6));
+}
+
+#[test]
+fn pragmas() {
+  use crate::public::Construct;
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .module_loader(TestModuleLoader::new(&[(
+      "util",
+      "# pragma: no-class\nclass T:\n  pass\n",
+    )]))
+    .forbid(Construct::While)
+    .finish();
+  let errors = |hebi: &crate::public::Hebi, source: &str| match hebi.compile(source).err() {
+    Some(Error::Syntax(e)) => e
+      .errors()
+      .iter()
+      .map(|e| (e.message.clone(), source[e.span].to_string()))
+      .collect::<Vec<_>>(),
+    e => panic!("expected syntax error, got {e:?}"),
+  };
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      # pragma: strict
+      # pragma: no-import
+      # pragma: no-while
+      import math
+      while true:
+        print missing
+    "#
+  );
+  assert_eq!(
+    errors(&hebi, source),
+    [
+      (
+        "imports are not allowed by `# pragma: no-import`".to_string(),
+        "import".to_string()
+      ),
+      // the host forbids it too, which is what gets reported
+      (
+        "`while` loops are not allowed".to_string(),
+        "while".to_string()
+      ),
+      (
+        "undefined global `missing`".to_string(),
+        "missing".to_string()
+      ),
+    ]
+  );
+
+  assert_eq!(
+    errors(&hebi, "# pragma: no-everything\n"),
+    [(
+      "unknown pragma `no-everything`".to_string(),
+      "# pragma: no-everything".to_string()
+    )]
+  );
+
+  // pragmas apply to the module they are in, not to the modules it imports
+  // or the ones which import it
+  let e = hebi.eval("import util").unwrap_err();
+  assert!(e
+    .to_string()
+    .contains("classes are not allowed by `# pragma: no-class`"));
+  let value = hebi
+    .eval("# pragma: no-for\nclass T:\n  x = 1\nT().x")
+    .unwrap();
+  assert_eq!(value.as_int(), Some(1));
+
+  // text after the first line of code is not a pragma
+  let value = hebi
+    .eval("s := \"\n# pragma: anything\n\"\ns.len()")
+    .unwrap();
+  assert_eq!(value.as_int(), Some(20));
+}
+
+#[tokio::test]
+async fn eval_file() {
+  let dir = std::env::temp_dir().join(format!("hebi-eval-file-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let config = dir.join("config.hebi");
+  std::fs::write(
+    &config,
+    indoc::indoc!(
+      r#"
+        limit := 10
+        fn check(v):
+          if v > limit:
+            return v + "!"
+          return v
+        fn reject():
+          throw {code: "rejected", message: "no"}
+      "#
+    ),
+  )
+  .unwrap();
+  let broken = dir.join("broken.hebi");
+  std::fs::write(&broken, "x := 1\ny := )\n").unwrap();
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.eval_file_async(&config).await.unwrap();
+
+  // the file is importable by name
+  let value = hebi
+    .eval_async("import config\nconfig.check(5)")
+    .await
+    .unwrap();
+  assert_eq!(value.as_int(), Some(5));
+
+  let e = hebi
+    .eval_async("import config\nconfig.check(50)")
+    .await
+    .unwrap_err();
+  let location = format!("{}:4:12", config.display());
+  assert!(e.to_string().starts_with(&location), "{e}");
+  assert!(e.report("", false).contains("return v + \"!\""), "{e}");
+
+  // errors thrown as values are located too
+  let e = hebi
+    .eval_async("import config\nconfig.reject()")
+    .await
+    .unwrap_err();
+  assert!(matches!(e, crate::Error::Value(_)), "{e}");
+  let location = format!("{}:7:3", config.display());
+  assert!(
+    e.to_string()
+      .starts_with(&format!("{location}: rejected: no")),
+    "{e}"
+  );
+  assert!(e.report("", false).contains("throw {code:"), "{e}");
+
+  let e = hebi.eval_file_async(&broken).await.unwrap_err();
+  let location = format!("{}:2:6", broken.display());
+  assert!(e.to_string().starts_with(&location), "{e}");
+  assert!(hebi.eval_async("import broken").await.is_err());
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn import_nested_path() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .module_loader(TestModuleLoader::new(&[(
+      "pkg.util",
+      "fn double(v):\n  return v * 2\n",
+    )]))
+    .finish();
+
+  // each import is a global, so it is visible to later evals
+  hebi.eval_async("import pkg.util").await.unwrap();
+  let value = hebi.eval_async("util.double(1)").await.unwrap();
+  assert_eq!(value.as_int(), Some(2));
+
+  hebi.eval_async("import pkg.util as u").await.unwrap();
+  let value = hebi.eval_async("u.double(2)").await.unwrap();
+  assert_eq!(value.as_int(), Some(4));
+
+  hebi
+    .eval_async("from pkg.util import double as twice")
+    .await
+    .unwrap();
+  let value = hebi.eval_async("twice(3)").await.unwrap();
+  assert_eq!(value.as_int(), Some(6));
+}
+
+#[tokio::test]
+async fn eval_with_vars() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+
+  let passed = hebi
+    .eval_with_async::<bool>("score > threshold", [("score", 10), ("threshold", 5)])
+    .await
+    .unwrap();
+  assert!(passed);
+
+  // the vars shadow globals, and don't outlive the evaluation
+  hebi.eval_async("limit := 100").await.unwrap();
+  let value = hebi
+    .eval_with_async::<i32>(
+      "fn clamp(v):\n  if v > limit: return limit\n  return v\nclamp(x)",
+      [("x", 250), ("limit", 200)],
+    )
+    .await
+    .unwrap();
+  assert_eq!(value, 200);
+  let value = hebi.eval_async("limit").await.unwrap();
+  assert_eq!(value.as_int(), Some(100));
+  assert!(hebi.global().get("x").is_none());
+
+  // assigning to a var doesn't define a global either
+  let value = hebi
+    .eval_with_async::<i32>("x += 1\nx", [("x", 1)])
+    .await
+    .unwrap();
+  assert_eq!(value, 2);
+  assert!(hebi.global().get("x").is_none());
+}
+
+#[tokio::test]
+async fn render_template() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+
+  hebi
+    .eval_async("fn greet(name):\n  return \"Hello, \" + name")
+    .await
+    .unwrap();
+  let text = hebi
+    .render_async(
+      "{{ greet(\"world\") }}! {{ 1 + 2 }} {{ none }}",
+      [("unused", 0)],
+    )
+    .await
+    .unwrap();
+  assert_eq!(text, "Hello, world! 3 none");
+
+  let template = indoc::indoc!(
+    r#"
+      {% for i in 0..=n %}
+      {% if i % 15 == 0 %}fizzbuzz{% elif i % 3 == 0 %}fizz{% elif i % 5 == 0 %}buzz{% else %}{{ i }}{% endif %}
+      {% endfor %}
+    "#
+  );
+  let text = hebi.render_async(template, [("n", 5)]).await.unwrap();
+  assert_eq!(text, "\nfizzbuzz\n\n1\n\n2\n\nfizz\n\n4\n\nbuzz\n\n");
+
+  // template vars don't leak into globals
+  assert!(hebi.global().get("n").is_none());
+  assert!(hebi.global().get("i").is_none());
+}
+
+#[tokio::test]
+async fn render_template_errors() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Vec::<u8>::new())
+    .finish();
+
+  for (template, message) in [
+    ("{{ 1 + }}", "unexpected token"),
+    ("a {{ b", "unterminated `{{`, expected a closing `}}`"),
+    ("{% if x %}", "unclosed block, expected `{% endif %}`"),
+    (
+      "{% for x in y %}{% endif %}",
+      "unexpected `{% endif %}`, expected `{% endfor %}`",
+    ),
+    ("{% endfor %}", "unexpected `{% endfor %}`"),
+    ("{% while x %}", "unknown template tag `while x`"),
+    ("{{ a b }}", "expected `}}`"),
+  ] {
+    let e = hebi
+      .render_async(template, [("x", true)])
+      .await
+      .unwrap_err();
+    assert!(e.to_string().contains(message), "{template}: {e}");
+  }
+
+  let e = hebi
+    .render_async("{{ missing }}", [("x", true)])
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("missing"), "{e}");
+}
+
+#[tokio::test]
+async fn frontmatter_metadata() {
+  let source = indoc::indoc!(
+    r#"
+      #!/usr/bin/env hebi
+      ---
+      owner: ops
+      permissions: net, fs
+      ---
+      value := 1
+    "#
+  );
+
+  let metadata = crate::public::Metadata::read(source).unwrap();
+  assert_eq!(metadata.get("owner"), Some("ops"));
+  assert_eq!(
+    metadata.iter().collect::<Vec<_>>(),
+    vec![("owner", "ops"), ("permissions", "net, fs")]
+  );
+
+  let hebi = crate::public::Hebi::new();
+  let chunk = hebi.compile(source).unwrap();
+  assert_eq!(chunk.metadata(), &metadata);
+  assert!(hebi.compile("value := 1").unwrap().metadata().is_empty());
+
+  let Err(e) = hebi.compile("---\nowner ops\n---\n") else {
+    panic!("expected a syntax error");
+  };
+  assert_eq!(
+    e.report("", false),
+    "syntax error:\nexpected `key: value`\n"
+  );
+}
+
+#[tokio::test]
+async fn output_streams() {
+  #[derive(Default)]
+  struct Streams {
+    out: String,
+    err: String,
+    flushes: usize,
+  }
+
+  impl crate::public::io::Output for Streams {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<()> {
+      self.out.push_str(std::str::from_utf8(buf).unwrap());
+      Ok(())
+    }
+
+    fn write_err(&mut self, buf: &[u8]) -> std::io::Result<()> {
+      self.err.push_str(std::str::from_utf8(buf).unwrap());
+      Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      self.flushes += 1;
+      Ok(())
+    }
+  }
+
+  fn warn(scope: Scope<'_>) -> Result<()> {
+    let message = scope.param::<crate::public::Str>(0)?;
+    scope
+      .global()
+      .eprintln(format_args!("warning: {}", message.as_str()))
+  }
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(Streams::default())
+    .finish();
+  hebi.register(&NativeModule::builder("log").function("warn", warn).finish());
+  hebi
+    .eval_async("from log import warn\nprint 1, 2\nwarn(\"careful\")\nprint 3")
+    .await
+    .unwrap();
+
+  let mut global = hebi.global();
+  let output = global.output();
+  let streams = output.as_any().downcast_ref::<Streams>().unwrap();
+  assert_eq!(streams.out, "1 2\n3\n");
+  assert_eq!(streams.err, "warning: careful\n");
+  assert_eq!(streams.flushes, 1);
+}
+
+#[tokio::test]
+async fn output_adapters() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.eval_async("print \"a\", 1.5").await.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("a 1.5\n"));
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(std::io::Cursor::new(Vec::new()))
+    .finish();
+  hebi.eval_async("print \"b\"").await.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<std::io::Cursor<Vec<u8>>>()
+    .map(|cursor| cursor.get_ref().clone());
+  assert_eq!(output.as_deref(), Some(&b"b\n"[..]));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn output_async_writer() {
+  use futures_util::FutureExt;
+  use tokio::io::AsyncReadExt;
+
+  let (writer, mut reader) = tokio::io::duplex(64);
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::AsyncWriter::new(writer))
+    .finish();
+  hebi.eval_async("for i in 0..3:\n  print i").await.unwrap();
+
+  // the run only finishes once the output has been written
+  let mut output = [0; 64];
+  let n = reader
+    .read(&mut output)
+    .now_or_never()
+    .expect("output was not flushed")
+    .unwrap();
+  assert_eq!(&output[..n], b"0\n1\n2\n");
+}
+
+#[tokio::test]
+async fn input_lines() {
+  let mut hebi = crate::public::Hebi::builder()
+    .input(crate::public::io::Reader::new(std::io::Cursor::new(
+      "alice\r\n42\n",
+    )))
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        name := input("name: ")
+        age := parse_int(input())
+        print name, age, input("more? ")
+      "#
+    ))
+    .await
+    .unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("name: more? alice 42 none\n"));
+
+  // scripts may not read input unless the host allows it
+  let mut hebi = crate::public::Hebi::new();
+  assert!(hebi.global().input().is_none());
+  let e = hebi.eval_async("input()").await.unwrap_err();
+  assert_eq!(
+    e.to_string(),
+    "`input` is disabled, because no input was configured"
+  );
+}
+
+#[tokio::test]
+async fn spawn_threads() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        counter := 0
+        fn tick(name, steps):
+          for i in 0..steps:
+            counter += 1
+            print name, i, counter
+            yield
+          return steps
+        fn fail(after):
+          for i in 0..after:
+            yield
+          throw "stopped"
+        class Pending:
+          n = 0
+          fn iter(self):
+            return self
+          fn next(self):
+            # native functions can't be suspended, so this doesn't switch threads
+            yield
+            self.n += 1
+            return self.n
+          fn done(self):
+            return self.n >= 2
+        fn drain():
+          items := collect(Pending())
+          print items[0], items[1]
+      "#
+    ))
+    .await
+    .unwrap();
+
+  hebi.spawn("tick", ("a".to_string(), 2)).unwrap();
+  hebi.spawn("tick", ("b".to_string(), 3)).unwrap();
+  hebi.run_until_idle_async().await.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(
+    output.as_deref(),
+    Some("a 0 1\nb 0 2\na 1 3\nb 1 4\nb 2 5\n")
+  );
+
+  // a failed thread is discarded, and the others keep going
+  hebi.spawn("fail", (1,)).unwrap();
+  hebi.spawn("tick", ("c".to_string(), 2)).unwrap();
+  let e = hebi.run_until_idle_async().await.unwrap_err();
+  assert_eq!(e.to_string(), "runtime_error: stopped");
+  hebi.run_until_idle_async().await.unwrap();
+  assert_eq!(hebi.global().get("counter").unwrap().as_int(), Some(7));
+
+  // `yield` is a no-op outside of spawned threads
+  hebi.eval_async("tick(\"d\", 1)").await.unwrap();
+  assert_eq!(hebi.global().get("counter").unwrap().as_int(), Some(8));
+
+  hebi.spawn("drain", ()).unwrap();
+  hebi.spawn("tick", ("e".to_string(), 1)).unwrap();
+  hebi.run_until_idle_async().await.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string())
+    .unwrap();
+  assert!(output.ends_with("1 2\ne 0 9\n"), "{output}");
+
+  let e = hebi.spawn("to_str", (1,)).unwrap_err();
+  assert!(e
+    .to_string()
+    .contains("only script functions can be spawned"));
+  assert!(hebi.spawn("tick", ()).is_err());
+}
+
+#[tokio::test]
+async fn step_threads() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        fn count(name, n):
+          for i in 0..n:
+            print name, i
+      "#
+    ))
+    .await
+    .unwrap();
+
+  // no threads, nothing to do
+  assert!(hebi.step_async(10).await.unwrap());
+
+  hebi.spawn("count", ("a".to_string(), 3)).unwrap();
+  hebi.spawn("count", ("b".to_string(), 1)).unwrap();
+  assert!(!hebi.step_async(0).await.unwrap());
+
+  // the same work takes the same number of steps, however it is split up
+  let mut steps = 0;
+  while !hebi.step_async(1).await.unwrap() {
+    steps += 1;
+  }
+  let mut total = 0;
+  hebi.spawn("count", ("a".to_string(), 3)).unwrap();
+  hebi.spawn("count", ("b".to_string(), 1)).unwrap();
+  while !hebi.step_async(7).await.unwrap() {
+    total += 7;
+  }
+  assert!(total <= steps && steps < total + 7);
+
+  // without a `yield`, the first thread runs to completion first
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(
+    output.as_deref(),
+    Some("a 0\na 1\na 2\nb 0\na 0\na 1\na 2\nb 0\n")
+  );
+
+  // a paused thread keeps its state
+  hebi
+    .eval_async("fn fail():\n  x := 1\n  throw \"oops\"\n")
+    .await
+    .unwrap();
+  hebi.spawn("fail", ()).unwrap();
+  assert!(!hebi.step_async(1).await.unwrap());
+  let e = hebi.step_async(100).await.unwrap_err();
+  assert_eq!(e.to_string(), "runtime_error: oops");
+  assert!(hebi.step_async(1).await.unwrap());
+}
+
+#[tokio::test]
+async fn policy_hooks() {
+  use std::sync::{Arc, Mutex};
+
+  struct Audit {
+    log: Arc<Mutex<Vec<String>>>,
+  }
+
+  impl crate::public::Policy for Audit {
+    fn import(&self, path: &str) -> Result<()> {
+      self.log.lock().unwrap().push(format!("import {path}"));
+      if path == "crypto" {
+        fail!("module `{path}` is not allowed");
+      }
+      Ok(())
+    }
+
+    fn store_global(&self, name: &str) -> Result<()> {
+      self.log.lock().unwrap().push(format!("store {name}"));
+      if name.starts_with('_') {
+        fail!("global `{name}` is reserved");
+      }
+      Ok(())
+    }
+
+    fn call_native(&self, name: &str) -> Result<()> {
+      self.log.lock().unwrap().push(format!("call {name}"));
+      if name == "write" {
+        fail!("`{name}` is not allowed");
+      }
+      Ok(())
+    }
+  }
+
+  let log = Arc::new(Mutex::new(Vec::new()));
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .policy(Audit { log: log.clone() })
+    .finish();
+  hebi.register(
+    &crate::public::NativeModule::builder("fs")
+      .function("read", |_| 0)
+      .function("write", |_| 0)
+      .finish(),
+  );
+
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        from fs import read, write
+        size := read()
+        print to_str(size)
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(
+    *log.lock().unwrap(),
+    [
+      "import fs",
+      "store read",
+      "store write",
+      "call read",
+      "store size"
+    ]
+  );
+
+  // a denied operation is an error which scripts can catch
+  let value = hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        denied := []
+        try:
+          import crypto
+        catch e:
+          denied.push(e["message"])
+        try:
+          _hidden := 1
+        catch e:
+          denied.push(e["message"])
+        try:
+          write()
+        catch e:
+          denied.push(e["message"])
+        denied.join(", ")
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(
+    value.to_string(),
+    "module `crypto` is not allowed, global `_hidden` is reserved, `write` is not allowed"
+  );
+  assert!(hebi.global().get("_hidden").is_none());
+
+  // calls made by the host are checked too
+  let e = hebi.call_async("write", ()).await.unwrap_err();
+  assert!(e.to_string().contains("`write` is not allowed"));
+}
+
+#[tokio::test]
+async fn rewrite_imports() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .module_loader(TestModuleLoader::new(&[(
+      "vendor.utils",
+      "seen := []\nfn double(n):\n  return n * 2\n",
+    )]))
+    .rewrite_imports(|path| match path {
+      "std.json" => Some("host_json".into()),
+      "team.utils" | "utils" => Some("vendor.utils".into()),
+      path if path.starts_with("blocked.") => Some("missing".into()),
+      _ => None,
+    })
+    .finish();
+  hebi.register(
+    &crate::public::NativeModule::builder("host_json")
+      .function("version", |_| 2)
+      .finish(),
+  );
+
+  let value = hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        from std.json import version
+        import team.utils as shared
+        from utils import double
+
+        # both names load the same module
+        shared.seen.push(1)
+        from utils import seen
+        [version(), shared.double(3), double(4), seen.len()]
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(value.to_json_string().unwrap(), "[2,6,8,1]");
+
+  // errors name the module which was looked for
+  let e = hebi.eval_async("import blocked.x").await.unwrap_err();
+  assert!(e.to_string().contains("module `missing` not found"), "{e}");
+}
+
+#[tokio::test]
+async fn introspection() {
+  let hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  assert_eq!(hebi.modules(), ["random", "crypto", "functools"]);
+}
+
+#[cfg(feature = "count_objects")]
+#[tokio::test]
+async fn object_counts() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .count_objects(true)
+    .finish();
+  let count = |hebi: &crate::public::Hebi, ty: &str| {
+    hebi
+      .object_counts()
+      .unwrap()
+      .into_iter()
+      .find(|(name, _)| *name == ty)
+      .map(|(_, count)| count)
+      .unwrap_or(0)
+  };
+
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        class Point:
+          x = 0
+        points := [Point(), Point(), Point()]
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(count(&hebi, "Instance"), 3);
+
+  // dropping the last reference frees the objects
+  hebi.eval_async("points = none").await.unwrap();
+  assert_eq!(count(&hebi, "Instance"), 0);
+
+  let used = hebi
+    .eval_async("import sys\nsys.memory_used()")
+    .await
+    .unwrap()
+    .as_int()
+    .unwrap();
+  assert!(used > 0);
+  assert!(hebi.memory_used().unwrap() > 0);
+
+  // nothing is counted unless it is enabled
+  let hebi = crate::public::Hebi::new();
+  assert!(hebi.object_counts().is_none());
+  assert!(hebi.memory_used().is_none());
+}
+
+#[tokio::test]
+async fn custom_allocator() {
+  use std::alloc::{GlobalAlloc, Layout, System};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+
+  #[derive(Default)]
+  struct Counts {
+    allocs: AtomicUsize,
+    live: AtomicUsize,
+  }
+
+  struct Counting(Arc<Counts>);
+
+  unsafe impl crate::public::Allocator for Counting {
+    fn alloc(&self, layout: Layout) -> *mut u8 {
+      self.0.allocs.fetch_add(1, Ordering::Relaxed);
+      self.0.live.fetch_add(1, Ordering::Relaxed);
+      unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+      self.0.live.fetch_sub(1, Ordering::Relaxed);
+      System.dealloc(ptr, layout)
+    }
+  }
+
+  let counts = Arc::new(Counts::default());
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .allocator(Counting(counts.clone()))
+    .finish();
+
+  let before = counts.live.load(Ordering::Relaxed);
+  hebi.eval_async("values := [[1], [2], [3]]").await.unwrap();
+  assert!(counts.live.load(Ordering::Relaxed) >= before + 4);
+
+  // freed objects are given back to the allocator
+  hebi.eval_async("values = none").await.unwrap();
+  let after = counts.live.load(Ordering::Relaxed);
+  assert!(after < before + 4);
+
+  drop(hebi);
+  assert!(counts.allocs.load(Ordering::Relaxed) > 0);
+  assert_eq!(counts.live.load(Ordering::Relaxed), 0);
+}
+
+check! {
+  class_factory,
+  r#"#!hebi
+    fn make_point_class(scale):
+      class Point:
+        x = 0
+        y = 0
+        fn len_sq(self):
+          return (self.x * self.x + self.y * self.y) * scale
+      return Point
+
+    P := make_point_class(2)
+    Q := make_point_class(10)
+    p := P()
+    p.x = 1
+    p.y = 2
+    q := Q()
+    q.x = 1
+    q.y = 2
+    print p.len_sq(), q.len_sq()
+  "#
+}
+
+check! {
+  class_factory_derived,
+  r#"#!hebi
+    class Base:
+      v = none
+      init(self, v):
+        self.v = v
+      fn get(self):
+        return self.v
+
+    fn make(prefix):
+      class Derived(Base):
+        init(self, v):
+          super(v)
+        fn get(self):
+          return prefix + to_str(super.get())
+      return Derived
+
+    fn make_counter_class():
+      count := 0
+      class Counter:
+        init(self):
+          count += 1
+        fn count(self):
+          return count
+      return Counter
+
+    D := make("v=")
+    print D(1).get()
+    C := make_counter_class()
+    C()
+    C()
+    print C().count()
+  "#
+}
+
+check! {
+  fn_factory_nested,
+  r#"#!hebi
+    fn adder(a):
+      fn add(b):
+        fn add_c(c):
+          return a + b + c
+        return add_c
+      return add
+
+    adder(1)(2)(3)
+  "#
+}
+
+check! {
+  class_factory_self_reference,
+  r#"#!hebi
+    fn make():
+      class Node:
+        value = 0
+        fn with_value(self, v):
+          node := Node()
+          node.value = v
+          return node
+      return Node
+
+    N := make()
+    N().with_value(5).value
+  "#
+}
+
+check! {
+  module
+  module_class_self_reference,
+  {
+    test: r#"
+class Node:
+  value = 0
+  fn with_value(self, v):
+    node := Node()
+    node.value = v
+    return node
+"#
+  },
+  r#"#!hebi
+    from test import Node
+    Node().with_value(5).value
+  "#
+}
+
+check! {
+  data_class_eq,
+  r#"#!hebi
+    class Point:
+      x = 0
+      y = 0
+    class Other:
+      x = 0
+      y = 0
+    a := Point()
+    b := Point()
+    print a == b, a != b
+    b.x = 1
+    print a == b
+    print a == Other()
+  "#
+}
+
+check! {
+  data_class_to_str,
+  r#"#!hebi
+    class Config:
+      name = "app"
+      port = 8080
+      debug = false
+    class Server(Config):
+      host = none
+    print Config()
+    print to_str(Server())
+  "#
+}
+
+check! {
+  data_class_to_table_from_table,
+  r#"#!hebi
+    class Config:
+      name = "app"
+      port = 8080
+    t := Config().to_table()
+    print t["name"], t["port"]
+    c := Config.from_table({ port: 9090 })
+    print c
+    print Config.from_table(c.to_table()) == c
+  "#
+}
+
+check! {
+  data_class_from_table_unknown_field,
+  r#"#!hebi
+    class Config:
+      port = 8080
+    Config.from_table({ host: "localhost" })
+  "#
+}
+
+check! {
+  data_class_own_method_takes_precedence,
+  r#"#!hebi
+    class Config:
+      port = 8080
+      fn to_table(self):
+        return "custom"
+    Config().to_table()
+  "#
+}
+
+check! {
+  function_signature,
+  r#"#!hebi
+    fn connect(host, port = 8080, timeout = -1.5, name = "db", retries = [1, 2]):
+      pass
+
+    for param in connect.signature():
+      print param["name"], param["required"], param["default"]
+
+    class Client:
+      fn send(self, data, flush = true):
+        pass
+    print Client().send.signature()[1]["default"]
+  "#
+}
+
+check! {
+  constant_param_defaults,
+  r#"#!hebi
+    fn connect(host, port = 8080, name = "db", retries = []):
+      retries.push(host)
+      print host, port, name, retries.len()
+
+    connect("a")
+    connect("b", none, none)
+    connect("c", 1, "x", [0])
+
+    class Client:
+      fn send(self, data, flush = true):
+        print data, flush
+    Client().send(1)
+    Client().send(2, false)
+    send := Client().send
+    send(3)
+  "#
+}
+
+check! {
+  module
+  function_reflection,
+  {
+    handlers: r#"
+fn on_request(path, method = "GET"):
+  pass
+
+class Router:
+  fn route(self, path):
+    pass
+"#
+  },
+  r#"#!hebi
+    from handlers import on_request, Router
+    print on_request.name(), on_request.module()
+    for param in on_request.params():
+      print param
+    route := Router().route
+    print route.name(), route.params()[0], route.module()
+
+    fn local(a, b):
+      pass
+    print local.name(), local.module()
+  "#
+}
+
+#[tokio::test]
+async fn function_reflection_api() {
+  use crate::public::object::function::Function;
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        fn handler(request, retries = 3):
+          pass
+      "#
+    ))
+    .await
+    .unwrap();
+
+  let global = hebi.global();
+  let handler = global
+    .get("handler")
+    .unwrap()
+    .as_object::<Function>(global.clone())
+    .unwrap();
+  assert_eq!(handler.name(), "handler");
+  assert_eq!(handler.params(), ["request", "retries"]);
+  assert_eq!(handler.arity(), (1, 2));
+  assert!(handler.module(global).is_none());
+}
+
+check! {
+  bound_method_equality,
+  r#"#!hebi
+    class Button:
+      fn on_click(self):
+        pass
+      fn on_hover(self):
+        pass
+    class Derived(Button):
+      fn on_click(self):
+        return super.on_click == self.on_click
+      fn parent_click(self):
+        return super.on_hover == self.on_hover
+
+    a := Button()
+    b := Button()
+    print a.on_click == a.on_click, a.on_click != a.on_click
+    print a.on_click == a.on_hover
+    print a.on_click == b.on_click
+    d := Derived()
+    print d.on_click(), d.parent_click()
+  "#
+}
+
+check! {
+  memo_decorator,
+  r#"#!hebi
+    from functools import memo
+
+    calls := 0
+
+    @memo
+    fn fib(n):
+      calls += 1
+      if n < 2:
+        return n
+      return fib(n - 1) + fib(n - 2)
+
+    print fib(30), calls
+    print fib(30), calls
+
+    fn outer():
+      @memo
+      fn fact(n):
+        if n <= 1:
+          return 1
+        return n * fact(n - 1)
+      return fact
+    outer()(10)
+  "#
+}
+
+check! {
+  memo_max_size,
+  r#"#!hebi
+    from functools import memo
+
+    calls := 0
+    fn square(n):
+      calls += 1
+      return n * n
+    sq := memo(square, 2)
+
+    sq(1)
+    sq(2)
+    sq(1)
+    print calls
+    # evicts 2, the least recently used
+    sq(3)
+    sq(1)
+    print calls
+    sq(2)
+    print calls
+    # ints and floats with the same value are the same argument
+    sq(2.0)
+    print calls
+
+    # a result cached by a recursive call with the same arguments does not
+    # evict anything when the outer call finishes
+    calls = 0
+    recurse := false
+    fn once(n):
+      calls += 1
+      if recurse:
+        recurse = false
+        cached(n)
+      return n
+    cached := memo(once, 2)
+    cached(1)
+    cached(2)
+    recurse = true
+    cached(3)
+    cached(2)
+    print calls
+  "#
+}
+
+check! {
+  memo_args_by_value,
+  r#"#!hebi
+    from functools import memo
+
+    fn total(items):
+      n := 0
+      for item in items:
+        n += item
+      return n
+    total := memo(total)
+
+    items := [1, 2]
+    print total(items)
+    print total([1, 2, 3])
+    print total(items)
+    items.push(3)
+    # the same list, but with different items, so the result is not cached
+    print total(items)
+
+    calls := 0
+    fn size(table):
+      calls += 1
+      return table["a"]
+    size := memo(size)
+    # tables with the same entries are equal, whatever their order
+    size({a: 1, b: 2})
+    size({b: 2, a: 1})
+    print calls
+
+    a := [1]
+    a.push(a)
+    try:
+      size(a)
+    catch e:
+      print e["message"]
+
+    calls := 0
+    fn greet(name):
+      calls += 1
+      return "hi " + name
+    greet := memo(greet)
+    # strings are compared by value
+    greet("a" + "b")
+    greet("ab")
+    print calls
+  "#
+}
+
+#[test]
+fn compile_artifacts() {
+  use crate::public::{Constant, FunctionInfo};
+
+  let hebi = crate::public::Hebi::new();
+  let source = indoc::indoc!(
+    r#"#!hebi
+      limit := 2.5
+
+      fn check(v, scale = 1):
+        fn inner():
+          return banned(v)
+        return inner() * scale < limit
+
+      class Rule:
+        init(self, name):
+          self.name = name
+        fn apply(self, v):
+          return check(v)
+
+      print Rule("r").apply(1)
+    "#
+  );
+  let chunk = hebi.compile(source).unwrap();
+
+  let functions = chunk
+    .functions()
+    .into_iter()
+    .map(
+      |FunctionInfo {
+         name,
+         class,
+         params,
+         min_args,
+         max_args,
+         span,
+         ..
+       }| {
+        let class = class.map(|class| format!("{class}.")).unwrap_or_default();
+        format!(
+          "{class}{name}({}) {min_args}..{max_args} `{}`",
+          params.join(", "),
+          &source[span.range()]
+        )
+      },
+    )
+    .collect::<Vec<_>>();
+  assert_eq!(
+    functions,
+    [
+      "check(v, scale) 1..2 `check`",
+      "inner() 0..0 `inner`",
+      "Rule.init(name) 1..1 `init`",
+      "Rule.apply(v) 1..1 `apply`",
+    ]
+  );
+
+  assert_eq!(
+    chunk.referenced_globals(),
+    ["limit", "check", "Rule", "banned"]
+  );
+
+  let constants = chunk.constants();
+  assert!(constants.contains(&Constant::Float(2.5)));
+  assert!(constants.contains(&Constant::Function("check".into())));
+  assert!(constants.contains(&Constant::Class("Rule".into())));
+  assert!(constants.contains(&Constant::String("name".into())));
+}
+
+#[test]
+fn estimate() {
+  use crate::public::Estimate;
+
+  let hebi = crate::public::Hebi::new();
+
+  assert_eq!(
+    hebi.estimate("print 1").unwrap(),
+    Estimate {
+      instructions: 3,
+      has_loops: false,
+      max_nesting: 0,
+      max_loop_nesting: 0,
+    }
+  );
+
+  let estimate = hebi
+    .estimate(indoc::indoc!(
+      r#"#!hebi
+        class Grid:
+          fn cells(self, n):
+            for i in 0..n:
+              if i > 0:
+                fn cell(j):
+                  while j > 0:
+                    j -= 1
+                print cell(i)
+      "#
+    ))
+    .unwrap();
+  assert!(estimate.instructions > 10);
+  assert!(estimate.has_loops);
+  assert_eq!(estimate.max_nesting, 6);
+  assert_eq!(estimate.max_loop_nesting, 1);
+
+  assert!(hebi.estimate("fn f(:").is_err());
+}
+
+#[test]
+fn hover() {
+  use crate::public::hover::{Binding, HoverKind, Literal};
+
+  let hebi = crate::public::Hebi::new();
+  let source = indoc::indoc!(
+    r#"#!hebi
+      limit := 2.5
+      name := "rule"
+
+      class Rule(Base):
+        init(self, name):
+          self.name = name
+        fn apply(self, v):
+          return v < limit
+
+      fn check(v, scale = 1):
+        rule := Rule(name)
+        fn inner():
+          return rule.apply(v * scale)
+        v = none
+        return inner()
+
+      print check(limit)
+    "#
+  );
+  let at = |needle: &str, nth: usize| -> Option<HoverKind> {
+    let offset = source.match_indices(needle).nth(nth).unwrap().0;
+    hebi.hover(source, offset).unwrap().map(|hover| hover.kind)
+  };
+  let variable = |name: &str, binding, value: Option<HoverKind>| HoverKind::Variable {
+    name: name.into(),
+    binding,
+    value: value.map(Box::new),
+  };
+
+  assert_eq!(at("2.5", 0), Some(HoverKind::Literal(Literal::Float(2.5))));
+  assert_eq!(
+    at("limit", 1),
+    Some(variable(
+      "limit",
+      Binding::Global,
+      Some(HoverKind::Literal(Literal::Float(2.5)))
+    ))
+  );
+  assert_eq!(at("Base", 0), Some(variable("Base", Binding::Global, None)));
+  let Some(HoverKind::Class(class)) = at("Rule", 0) else {
+    panic!("expected a class");
+  };
+  assert_eq!(
+    (class.parent.as_deref(), &class.methods[..]),
+    (Some("Base"), &["apply".to_string()][..])
+  );
+  let Some(HoverKind::Function(apply)) = at("apply", 0) else {
+    panic!("expected a function");
+  };
+  assert_eq!(
+    (apply.class.as_deref(), &apply.params[..]),
+    (Some("Rule"), &["v".to_string()][..])
+  );
+  let Some(HoverKind::Function(check)) = at("check", 0) else {
+    panic!("expected a function");
+  };
+  assert_eq!((check.min_args, check.max_args), (1, 2));
+
+  // the parameter shadows the global
+  assert_eq!(at("name", 3), Some(variable("name", Binding::Local, None)));
+  assert_eq!(
+    at("name", 4),
+    Some(variable(
+      "name",
+      Binding::Global,
+      Some(HoverKind::Literal(Literal::String("rule".into())))
+    ))
+  );
+  assert_eq!(
+    at("rule", 2),
+    Some(variable(
+      "rule",
+      Binding::Captured,
+      Some(HoverKind::Instance {
+        class: "Rule".into()
+      })
+    ))
+  );
+  assert_eq!(
+    at("scale", 1),
+    Some(variable("scale", Binding::Captured, None))
+  );
+  assert_eq!(
+    at("v = none", 0),
+    Some(variable(
+      "v",
+      Binding::Local,
+      Some(HoverKind::Literal(Literal::None))
+    ))
+  );
+  assert!(matches!(
+    at("check(limit)", 0),
+    Some(HoverKind::Variable {
+      value: Some(value),
+      ..
+    }) if matches!(*value, HoverKind::Function(_))
+  ));
+  assert_eq!(at("print", 0), None);
+  let offset = source.rfind("limit").unwrap();
+  let hover = hebi.hover(source, offset + 2).unwrap().unwrap();
+  assert_eq!(hover.span.range(), offset..offset + "limit".len());
+
+  assert!(hebi.hover("fn f(:", 0).is_err());
+}
+
+#[tokio::test]
+async fn call_result_into_register() {
+  async fn double(scope: Scope<'_>) -> Result<i32> {
+    let value = scope.param::<i32>(0)?;
+    tokio::task::yield_now().await;
+    Ok(value * 2)
+  }
+
+  let mut hebi = crate::public::Hebi::new();
+  hebi.register(
+    &NativeModule::builder("math")
+      .async_function("double", double)
+      .finish(),
+  );
+  // each of these calls writes its result to a register with `CallInto`,
+  // whether it returns from a frame, from a native function, or once a
+  // native future finishes
+  let value = hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        from math import double
+        fn add(a, b):
+          return a + b
+        fn fail(v):
+          throw "bad"
+        class T:
+          fn get(self, v):
+            return v + 1
+        get := T().get
+        r := [add(1, 2), double(add(2, 3)), get(double(1)), add(double(2), 1) + add(1, 1)]
+        try:
+          r.push(add(fail(0), 1))
+        catch e:
+          r.push(add(-1, 0))
+        r.push(to_str(add(4, 5)))
+        r
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(
+    format!("{value:?}"),
+    r#"Object([Int(3), Int(10), Int(3), Int(7), Int(-1), Object("9")])"#
+  );
+}
+
+#[tokio::test]
+async fn fork() {
+  struct Handle;
+
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.register(
+    &NativeModule::builder("host")
+      .class::<Handle>("Handle", |class| class.init(|_| Ok(Handle)).finish())
+      .finish(),
+  );
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"#!hebi
+        class Point:
+          x = 0
+          y = 0
+          fn move(self, dx):
+            self.x += dx
+        fn counter():
+          n := 0
+          fn next():
+            n += 1
+            return n
+          return next
+        point := Point()
+        items := [1, 2]
+        both := [items, items]
+        cycle := [0]
+        cycle[0] = cycle
+        next := counter()
+        next()
+      "#
+    ))
+    .await
+    .unwrap();
+
+  let mut fork = hebi.fork().unwrap();
+  let source = indoc::indoc!(
+    r#"#!hebi
+      point.move(5)
+      both[0].push(3)
+      [point.x, both[1], cycle[0][0] == cycle, next()]
+    "#
+  );
+  let value = fork.eval_async(source).await.unwrap();
+  assert_eq!(
+    format!("{value:?}"),
+    r#"Object([Int(5), Object([Int(1), Int(2), Int(3)]), Bool(true), Int(2)])"#
+  );
+
+  // functions are copied, but share their bytecode
+  let code = |hebi: &crate::public::Hebi| {
+    let next = hebi.global().inner.get("next").unwrap().to_any().unwrap();
+    let next = next
+      .clone_cast::<crate::internal::object::Function>()
+      .unwrap();
+    next.descriptor.instructions.as_ptr()
+  };
+  assert_eq!(code(&hebi), code(&fork));
+
+  // the original is unchanged
+  let value = hebi.eval_async("[point.x, both[1], next()]").await.unwrap();
+  assert_eq!(
+    format!("{value:?}"),
+    r#"Object([Int(0), Object([Int(1), Int(2)]), Int(2)])"#
+  );
+
+  // the fork writes to its own output
+  fork.eval_async("print \"forked\"").await.unwrap();
+  let output = fork
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string());
+  assert_eq!(output.as_deref(), Some("forked\n"));
+
+  // native class instances can't be copied
+  hebi
+    .eval_async("from host import Handle\nhandle := Handle()")
+    .await
+    .unwrap();
+  let e = hebi.fork().unwrap_err();
+  assert!(e.to_string().contains("cannot copy"), "{e}");
+}
+
+#[tokio::test]
+async fn record_replay_native_calls() {
+  use std::sync::atomic::{AtomicI32, Ordering};
+  use std::sync::Arc;
+
+  fn host(counter: Arc<AtomicI32>) -> NativeModule {
+    let next = counter.clone();
+    NativeModule::builder("host")
+      .function("next", move |_| next.fetch_add(1, Ordering::SeqCst))
+      .function("lookup", |scope: Scope<'_>| -> Result<i32> {
+        let key = scope.param::<crate::public::Str>(0)?;
+        Err(crate::error_value!("not_found", "no `{}`", key.as_str()))
+      })
+      .async_function("fetch", move |scope| {
+        let counter = counter.clone();
+        async move {
+          let n = scope.param::<i32>(0)?;
+          Ok::<_, crate::Error>(n + counter.load(Ordering::SeqCst))
+        }
+      })
+      .finish()
+  }
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from host import next, lookup, fetch
+      r := [next(), next(), fetch(10)]
+      try:
+        lookup("a")
+      catch e:
+        r.push(e["message"])
+      r
+    "#
+  );
+
+  let counter = Arc::new(AtomicI32::new(100));
+  let mut hebi = crate::public::Hebi::builder()
+    .record_native_calls(true)
+    .finish();
+  hebi.register(&host(counter.clone()));
+  let recorded = format!("{:?}", hebi.eval_async(source).await.unwrap());
+  assert_eq!(
+    recorded,
+    r#"Object([Int(100), Int(101), Int(112), Object("no `a`")])"#
+  );
+  let recording = hebi.recording().unwrap();
+  let names = (recording.calls.iter())
+    .map(|call| call.name.as_str())
+    .collect::<Vec<_>>();
+  assert_eq!(names, ["next", "next", "fetch", "lookup"]);
+  assert_eq!(recording.calls[2].args, [crate::ErrorData::Int(10)]);
+
+  // the host is not called during a replay
+  let counter = Arc::new(AtomicI32::new(0));
+  let mut hebi = crate::public::Hebi::builder()
+    .replay_native_calls(recording.clone())
+    .finish();
+  hebi.register(&host(counter.clone()));
+  let replayed = format!("{:?}", hebi.eval_async(source).await.unwrap());
+  assert_eq!(replayed, recorded);
+  assert_eq!(counter.load(Ordering::SeqCst), 0);
+  assert!(hebi.recording().is_none());
+
+  // a run which makes different calls fails at the first difference
+  let mut hebi = crate::public::Hebi::builder()
+    .replay_native_calls(recording)
+    .finish();
+  hebi.register(&host(counter));
+  let e = hebi
+    .eval_async("from host import next, fetch\nnext()\nfetch(1)")
+    .await
+    .unwrap_err();
+  assert!(e.to_string().contains("expected a call to `next`"), "{e}");
+}
+
+#[tokio::test]
+async fn record_replay_nested_native_calls() {
+  use std::sync::atomic::{AtomicI32, Ordering};
+  use std::sync::Arc;
+
+  async fn call(mut scope: Scope<'_>) -> Result<crate::public::Value<'_>> {
+    let f = scope.param::<crate::public::Any>(0)?;
+    scope.call(f, &[]).await
+  }
+
+  fn host(counter: Arc<AtomicI32>) -> NativeModule {
+    NativeModule::builder("host")
+      .function("next", move |_| counter.fetch_add(1, Ordering::SeqCst))
+      .async_function("call", call)
+      .finish()
+  }
+
+  let source = indoc::indoc!(
+    r#"#!hebi
+      from host import next, call
+      fn f():
+        return next() * 10
+      [next(), call(f), next()]
+    "#
+  );
+
+  let mut hebi = crate::public::Hebi::builder()
+    .record_native_calls(true)
+    .finish();
+  hebi.register(&host(Arc::new(AtomicI32::new(1))));
+  let recorded = format!("{:?}", hebi.eval_async(source).await.unwrap());
+  assert_eq!(recorded, "Object([Int(1), Int(20), Int(3)])");
+  let recording = hebi.recording().unwrap();
+  let calls = (recording.calls.iter())
+    .map(|call| (call.name.as_str(), call.nested))
+    .collect::<Vec<_>>();
+  assert_eq!(calls, [("next", 0), ("call", 1), ("next", 0), ("next", 0)]);
+
+  // `f` is not called again, so neither is the `next` inside it
+  let counter = Arc::new(AtomicI32::new(0));
+  let mut hebi = crate::public::Hebi::builder()
+    .replay_native_calls(recording)
+    .finish();
+  hebi.register(&host(counter.clone()));
+  let replayed = format!("{:?}", hebi.eval_async(source).await.unwrap());
+  assert_eq!(replayed, recorded);
+  assert_eq!(counter.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn log_compile_phases() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .module_loader(TestModuleLoader::new(&[("util", "fn f():\n  pass\n")]))
+    .log_compile_phases(true)
+    .finish();
+  hebi
+    .eval_async("import util\nprint \"done\"")
+    .await
+    .unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string())
+    .unwrap();
+  let lines = output.lines().collect::<Vec<_>>();
+  assert_eq!(lines.len(), 3, "{output}");
+  assert!(lines[0].starts_with("compiled `__main__`: metadata "));
+  assert!(lines[1].starts_with("compiled `util`: metadata 0ns, parse "));
+  assert_eq!(lines[2], "done");
+
+  // compiling an AST skips parsing
+  use crate::public::ast::{Expr, Module, Stmt};
+  let ast = Module::new(vec![Stmt::expr(Expr::int(1))]);
+  let chunk = hebi.compile_ast(&ast).unwrap();
+  assert_eq!(chunk.timings().parse, std::time::Duration::ZERO);
+}
+
+#[tokio::test]
+async fn compile_project() {
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .module_loader(TestModuleLoader::new(&[("extra", "v := 3\n")]))
+    .finish();
+  let bundle = hebi
+    .compile_project(&[
+      (
+        "main",
+        "import util\nimport extra\nprint util.double(extra.v)\nfrom util import n\nprint n",
+      ),
+      (
+        "util",
+        "import helper\nfn double(x):\n  return helper.mul(x, 2)\nn := 10",
+      ),
+      ("helper", "fn mul(a, b):\n  return a * b"),
+    ])
+    .unwrap();
+  assert_eq!(bundle.modules(), ["main", "util", "helper"]);
+  hebi.run_bundle_async(bundle).await.unwrap();
+  let output = hebi
+    .global()
+    .output()
+    .as_any()
+    .downcast_ref::<crate::public::io::StringWriter>()
+    .map(|output| output.as_str().to_string())
+    .unwrap();
+  assert_eq!(output, "6\n10\n");
+
+  // syntax errors are reported from every file
+  let e = hebi
+    .compile_project(&[("a", "x := ("), ("b", "y := 1"), ("c", "fn:")])
+    .map(drop)
+    .unwrap_err();
+  let crate::Error::Syntax(e) = e else {
+    panic!("expected a syntax error, got {e}");
+  };
+  assert_eq!(e.errors().len(), 2, "{e}");
+
+  let e = hebi
+    .compile_project(&[("a", "pass"), ("a", "pass")])
+    .map(drop)
+    .unwrap_err();
+  assert!(e.to_string().contains("more than once"), "{e}");
+}
+
+#[tokio::test]
+async fn load_bundle() {
+  let main = r#"
+import shapes
+from shapes import Point, scale
+p := scale(Point(1, 2))
+print p.x, p.y, p.norm()
+counter := shapes.make_counter()
+counter()
+print counter()
+for i in 0..3:
+  print shapes.double(i)
+print shapes.greet()
+print shapes.greet("you")
+"#;
+  let shapes = r#"
+class Point:
+  x = 0
+  y = 0
+  init(self, x, y):
+    self.x = x
+    self.y = y
+  fn norm(self):
+    return self.x * self.x + self.y * self.y
+fn scale(p, by=2.5):
+  return Point(p.x * by, p.y * by)
+fn make_counter():
+  n := 0
+  fn next():
+    n += 1
+    return n
+  return next
+fn double(n):
+  return n * 2
+fn greet(name="world"):
+  return "hello, " + name
+fn fail():
+  return none + 1
+"#;
+  let files = [("main", main), ("shapes", shapes)];
+  let hebi = crate::public::Hebi::new();
+  let bundle = hebi.compile_project(&files).unwrap();
+  let with_sources = bundle.to_bytes(true).unwrap();
+  let without_sources = bundle.to_bytes(false).unwrap();
+  assert!(without_sources.len() < with_sources.len());
+
+  let run = |bytes: &[u8]| {
+    let mut hebi = crate::public::Hebi::builder()
+      .output(crate::public::io::StringWriter::new())
+      .finish();
+    let bundle = unsafe { hebi.load_bundle(bytes) }.unwrap();
+    assert_eq!(bundle.modules(), ["main", "shapes"]);
+    hebi.run_bundle(bundle).unwrap();
+    let output = hebi
+      .global()
+      .output()
+      .as_any()
+      .downcast_ref::<crate::public::io::StringWriter>()
+      .map(|output| output.as_str().to_string())
+      .unwrap();
+    assert_eq!(
+      output,
+      "2.5 5.0 31.25\n2\n0\n2\n4\nhello, world\nhello, you\n"
+    );
+    let e = hebi
+      .eval("from shapes import fail\nfail()")
+      .map(drop)
+      .unwrap_err();
+    e.to_string()
+  };
+  // errors only point into the source if it was included
+  let e = run(&with_sources);
+  assert!(e.starts_with("shapes:23:10"), "{e}");
+  let e = run(&without_sources);
+  assert!(!e.starts_with("shapes:"), "{e}");
+
+  // loading checks the header, and fails instead of reading past the end
+  let hebi = crate::public::Hebi::new();
+  let load = |bytes: &[u8]| {
+    unsafe { hebi.load_bundle(bytes) }
+      .map(drop)
+      .unwrap_err()
+      .to_string()
+  };
+  assert!(load(b"not a pack").contains("not a hebi pack"));
+  let truncated = &with_sources[..with_sources.len() - 1];
+  assert!(load(truncated).contains("truncated"), "{}", load(truncated));
+  // the format version follows the magic bytes
+  let mut newer = with_sources.clone();
+  newer[8] += 1;
+  assert!(
+    load(&newer).contains("hebi has to be updated"),
+    "{}",
+    load(&newer)
+  );
+  let mut older = with_sources.clone();
+  older[8] -= 1;
+  assert!(load(&older).contains("compiled again"), "{}", load(&older));
+  // followed by the version of hebi, which has to match exactly
+  let mut other = with_sources.clone();
+  other[10] = b'9';
+  assert!(
+    load(&other).contains("which is not this version"),
+    "{}",
+    load(&other)
+  );
+
+  // constructs used by the code are checked before it is loaded, the
+  // others may be forbidden
+  use crate::public::{Capability, Construct};
+  assert_eq!(
+    bundle.capabilities(),
+    [
+      Construct::Import,
+      Construct::For,
+      Construct::Class,
+      Construct::Function
+    ]
+    .map(Capability::Construct)
+  );
+  let hebi = crate::public::Hebi::builder()
+    .forbid(Construct::For)
+    .forbid(Construct::Class)
+    .forbid(Construct::With)
+    .finish();
+  let e = unsafe { hebi.load_bundle(&with_sources) }
+    .map(drop)
+    .unwrap_err();
+  assert_eq!(
+    e.to_string(),
+    "the pack needs `for` loops, classes, which this VM does not allow"
+  );
+  let hebi = crate::public::Hebi::builder()
+    .forbid(Construct::With)
+    .finish();
+  unsafe { hebi.load_bundle(&with_sources) }.unwrap();
+
+  #[cfg(feature = "decimal")]
+  {
+    let bundle = hebi.compile_project(&[("main", "v := 1.50d")]).unwrap();
+    assert_eq!(bundle.capabilities(), [Capability::Decimal]);
+  }
+}
+
+#[test]
+fn testing_helpers() {
+  use crate::public::testing;
+
+  let mut hebi = testing::vm();
+  hebi
+    .eval(
+      r#"
+from test import assert, assert_eq
+assert_eq([1, {a: "b"}], [1, {a: "b"}])
+assert(true)
+print "ok"
+"#,
+    )
+    .unwrap();
+  assert_eq!(testing::take_output(&mut hebi), "ok\n");
+  assert_eq!(testing::output(&hebi), "");
+
+  let err = |hebi: &mut crate::public::Hebi, code: &str| hebi.eval(code).unwrap_err().to_string();
+  // `test.assert_eq` is the builtin `assert_eq`
+  assert_eq!(
+    err(
+      &mut hebi,
+      "from test import assert_eq\nassert_eq([1, 2], [1, 3])"
+    ),
+    err(&mut hebi, "assert_eq([1, 2], [1, 3])"),
+  );
+  assert_eq!(
+    err(
+      &mut hebi,
+      "from test import assert_eq\nassert_eq([1, 2], [1, 3])"
+    ),
+    "assertion failed: values are not equal\n  $[1]: 2 != 3"
+  );
+  assert_eq!(
+    err(&mut hebi, "from test import assert\nassert(false)"),
+    "assertion failed"
+  );
+  assert_eq!(
+    err(&mut hebi, "from test import assert\nassert(1 > 2, \"bad\")"),
+    "bad"
+  );
+  assert_eq!(
+    err(&mut hebi, "from test import fail\nfail(\"nope\")"),
+    "nope"
+  );
+
+  crate::assert_script_eq!(hebi, "[1, 2][1]", 2);
+  crate::assert_script_err!(hebi, "from test import fail\nfail(\"x\")", "x");
+}
+
+#[test]
+fn string_encodings() {
+  use crate::public::{Encoding, EncodingError};
+
+  let hebi = crate::public::Hebi::new();
+  let s = hebi.new_string("añ😀");
+  assert_eq!(s.as_bytes(), "añ😀".as_bytes());
+  assert_eq!(s.to_utf16(), "añ😀".encode_utf16().collect::<Vec<_>>());
+  assert_eq!(
+    hebi.new_string_from_utf16(&s.to_utf16()).unwrap().as_str(),
+    "añ😀"
+  );
+  assert_eq!(
+    s.to_latin1(),
+    Err(EncodingError {
+      encoding: Encoding::Latin1,
+      offset: 3
+    })
+  );
+  let latin1 = hebi.new_string("añ").to_latin1().unwrap();
+  assert_eq!(latin1, b"a\xf1");
+  assert_eq!(hebi.new_string_from_latin1(&latin1).as_str(), "añ");
+
+  let e = hebi.new_string_from_utf8(b"ab\xffc").unwrap_err();
+  assert_eq!(e.to_string(), "invalid UTF-8 at offset 2");
+  assert_eq!(
+    hebi.new_string_from_utf8_lossy(b"ab\xffc").as_str(),
+    "ab\u{fffd}c"
+  );
+
+  let units = [0x61, 0xd83d, 0xd83d, 0xde00];
+  let e = hebi.new_string_from_utf16(&units).unwrap_err();
+  assert_eq!(e.to_string(), "invalid UTF-16 at offset 1");
+  assert_eq!(
+    hebi.new_string_from_utf16_lossy(&units).as_str(),
+    "a\u{fffd}😀"
+  );
+  let e: crate::Error = hebi.new_string("→").to_latin1().unwrap_err().into();
+  assert_eq!(
+    e.to_string(),
+    "the character at offset 0 cannot be encoded as Latin-1"
+  );
+}
+
+#[cfg(feature = "collation")]
+#[test]
+fn collation() {
+  let mut hebi = crate::public::Hebi::new();
+  let value = hebi
+    .eval(
+      r#"
+names := ["zoe", "Émile", "ångström", "Zack", "adam", "Öberg"]
+names.sort_collated()
+a := names.join(" ")
+names.sort_collated("sv")
+b := names.join(" ")
+[a, b, "a".collate("B"), "ö".collate("z", "de"), "ö".collate("z", "sv")].join(" | ")
+"#,
+    )
+    .unwrap()
+    .to_string();
+  assert_eq!(
+    value,
+    "adam ångström Émile Öberg Zack zoe | adam Émile Zack zoe ångström Öberg | -1 | -1 | 1"
+  );
+
+  let e = hebi.eval("[1, \"a\"].sort_collated()").unwrap_err();
+  assert!(e.to_string().contains("can only sort strings"), "{e}");
+  let e = hebi
+    .eval("\"a\".collate(\"b\", \"not a locale!\")")
+    .unwrap_err();
+  assert!(e.to_string().contains("invalid locale"), "{e}");
+}
+
+#[tokio::test]
+async fn sys_module() {
+  let mut hebi = crate::public::Hebi::builder()
+    .module_loader(TestModuleLoader::new(&[(
+      "util",
+      "import sys\nname := sys.module_name\n",
+    )]))
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+
+  let version = hebi.eval_async("import sys\nsys.version").await.unwrap();
+  assert_eq!(version.to_string(), env!("CARGO_PKG_VERSION"));
+  let name = hebi
+    .eval_async("from sys import module_name\nmodule_name")
+    .await
+    .unwrap();
+  assert_eq!(name.to_string(), "__main__");
+  let name = hebi
+    .eval_async("from util import name\nname")
+    .await
+    .unwrap();
+  assert_eq!(name.to_string(), "util");
+
+  // a module gets the same `sys` every time it imports it
+  let same = hebi
+    .eval_async("import sys as a\nimport sys as b\na == b")
+    .await
+    .unwrap();
+  assert_eq!(same.as_bool(), Some(true));
+
+  // the platform is hidden unless the host exposes it
+  assert!(hebi
+    .eval_async("import sys\nsys.platform")
+    .await
+    .unwrap()
+    .is_none());
+
+  // there is no fuel limit outside of `step`
+  assert!(hebi
+    .eval_async("import sys\nsys.fuel_remaining()")
+    .await
+    .unwrap()
+    .is_none());
+  hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        import sys
+        fuel := []
+        fn burn():
+          for i in 0..3:
+            fuel.push(sys.fuel_remaining())
+      "#
+    ))
+    .await
+    .unwrap();
+  hebi.spawn("burn", ()).unwrap();
+  assert!(hebi.step_async(1000).await.unwrap());
+  let fuel = hebi.global().get("fuel").unwrap();
+  let fuel = fuel
+    .as_object::<crate::public::List>(hebi.global())
+    .unwrap()
+    .iter()
+    .map(|v| v.as_int().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(fuel.len(), 3);
+  assert!(
+    fuel.windows(2).all(|w| w[0] > w[1]) && fuel[0] < 1000,
+    "{fuel:?}"
+  );
+
+  let mut hebi = crate::public::Hebi::builder()
+    .expose_platform(true)
+    .finish();
+  let platform = hebi.eval_async("import sys\nsys.platform").await.unwrap();
+  assert_eq!(platform.to_string(), std::env::consts::OS);
+  assert!(hebi
+    .eval_async("import sys\nsys.memory_used()")
+    .await
+    .unwrap()
+    .is_none());
+}
+
+#[tokio::test]
+async fn nested_eval() {
+  let module = crate::public::NativeModule::builder("host")
+    .async_function("run", |mut scope| async move {
+      let code = scope.param::<String>(0)?;
+      scope.eval(&code).await
+    })
+    .finish();
+  let mut hebi = crate::public::Hebi::builder()
+    .output(crate::public::io::StringWriter::new())
+    .finish();
+  hebi.register(&module);
+
+  let value = hebi
+    .eval_async(indoc::indoc!(
+      r#"
+        from host import run
+        base := 10
+        fn add(n):
+          return base + n
+
+        # nested code shares globals with the code that runs it
+        run("defined := add(1)")
+        a := run("defined + 1")
+
+        # and can run more code itself
+        b := run("run(\"add(5)\") * 2")
+
+        # errors propagate to the caller
+        c := none
+        try:
+          run("throw \"inner\"")
+        catch e:
+          c = e
+
+        [a, b, c, defined]
+      "#
+    ))
+    .await
+    .unwrap();
+  assert_eq!(value.to_json_string().unwrap(), "[12,30,\"inner\",11]");
+
+  // syntax errors in nested code are reported like any other error
+  let e = hebi
+    .eval_async("from host import run\nrun(\"1 +\")")
+    .await
+    .unwrap_err();
+  assert!(matches!(e, crate::Error::Syntax(..)), "{e:?}");
+
+  // the stack is balanced afterwards
+  let value = hebi.eval_async("run(\"base\")").await.unwrap();
+  assert_eq!(value.as_int(), Some(10));
+}
@@ -7,27 +7,30 @@ use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::mem::take;
 use std::ptr::NonNull;
-
-use indexmap::IndexMap;
+use std::time::Instant;
 
 use self::util::*;
 use super::dispatch::{dispatch, Call, ControlFlow, Handler, LoadFrame, Return};
 use super::global::Global;
 use crate::internal::bytecode::opcode as op;
-use crate::internal::error::{Error, Result};
+use crate::internal::error::{Error, ErrorValue, Result};
 use crate::internal::object::class::{ClassInstance, ClassProxy};
-use crate::internal::object::function::Params;
-use crate::internal::object::module::{ModuleId, ModuleKind};
+use crate::internal::object::function::{Cell, Params};
+use crate::internal::object::module::{ModuleDescriptor, ModuleId, ModuleKind};
 use crate::internal::object::native::LocalBoxFuture;
+use crate::internal::object::string::StrMap;
 use crate::internal::object::{
-  function, Any, ClassDescriptor, ClassType, Function, FunctionDescriptor, List, Module, Object,
-  Ptr, Str, Table, Type,
+  function, int, Any, ClassDescriptor, ClassType, Function, FunctionDescriptor, List, Module,
+  Object, Ptr, Str, Table, Type,
 };
+use crate::internal::stdlib::sys;
+use crate::internal::syntax::SyntaxError;
 use crate::internal::value::constant::Constant;
-use crate::internal::value::Value;
-use crate::internal::{codegen, syntax};
-use crate::public::Scope;
-use crate::util::JoinIter;
+use crate::internal::value::{cmp, Value};
+use crate::internal::{codegen, pack, syntax};
+use crate::public::{CompileTimings, Scope};
+use crate::span::{Source, Span, SpannedError};
+use crate::util::{did_you_mean, JoinIter};
 
 pub struct Thread {
   pub(crate) global: Global,
@@ -35,6 +38,11 @@ pub struct Thread {
   acc: Value,
   pub(crate) pc: usize,
   poll: Option<AsyncFrame>,
+  /// The register which the result of `poll` is written to, if it was called
+  /// by `CallInto`.
+  poll_dst: Option<op::Register>,
+  /// Set by `yield`, and cleared once the interpreter loop sees it.
+  yielded: bool,
 }
 
 impl Clone for Thread {
@@ -45,6 +53,8 @@ impl Clone for Thread {
       acc: self.acc.clone(),
       pc: self.pc,
       poll: None,
+      poll_dst: None,
+      yielded: false,
     }
   }
 }
@@ -53,6 +63,14 @@ impl Clone for Thread {
 pub struct Stack {
   pub(crate) frames: Vec<Frame>,
   pub(crate) regs: Vec<Value>,
+  pub(crate) handlers: Vec<TryHandler>,
+  /// The value passed to the most recent `throw`, and the error it was
+  /// converted to. A `catch` block which receives that error sees the
+  /// original value instead.
+  pub(crate) thrown: Option<(Value, ErrorValue)>,
+  /// The number of steps which were left when the most recent call was
+  /// made, see [`Handler::set_steps`].
+  pub(crate) steps: usize,
 }
 
 impl Stack {
@@ -60,10 +78,33 @@ impl Stack {
     Self {
       frames: Vec::with_capacity(8),
       regs: Vec::with_capacity(64),
+      handlers: Vec::new(),
+      thrown: None,
+      steps: usize::MAX,
     }
   }
 }
 
+/// Why [`Thread::run_steps`] stopped.
+#[derive(Debug)]
+pub enum Status {
+  /// The script executed a `yield` statement.
+  Yielded,
+  /// The script ran out of steps.
+  Paused,
+  /// The outermost call frame returned this value.
+  Finished(Value),
+}
+
+/// An active `try` block.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TryHandler {
+  /// Index of the call frame which the `try` block belongs to.
+  frame: usize,
+  /// Start of the `catch` block.
+  pc: usize,
+}
+
 impl Thread {
   pub fn new(global: Global, stack: NonNull<Stack>) -> Self {
     Thread {
@@ -74,60 +115,187 @@ impl Thread {
       pc: 0,
 
       poll: None,
+      poll_dst: None,
+      yielded: false,
     }
   }
 
-  fn unwind_stack(&mut self, stop_at_index: Option<usize>) {
+  /// Pop every call frame at or above `start`, along with its registers and
+  /// `try` handlers.
+  fn unwind_stack(&mut self, start: usize) {
     let stack = unsafe { self.stack.as_mut() };
-    let start = stop_at_index.map(|v| v + 1).unwrap_or(0);
     for frame in stack.frames.drain(start..).rev() {
-      stack.regs.truncate(frame.stack_base);
+      stack.regs.truncate(frame.caller_len);
+    }
+    while matches!(stack.handlers.last(), Some(handler) if handler.frame >= start) {
+      stack.handlers.pop();
+    }
+    if stack.frames.is_empty() {
+      // nothing is left to catch it
+      stack.thrown = None;
+    }
+  }
+
+  /// Transfer control to the innermost `try` block which belongs to a call
+  /// frame at or above `lowest_frame`, making `error` available to its
+  /// `catch` block.
+  ///
+  /// Returns the error if there is no such `try` block.
+  fn catch_error(&mut self, error: Error, lowest_frame: usize) -> Result<()> {
+    let stack = unsafe { self.stack.as_mut() };
+    let handler = match stack.handlers.last() {
+      Some(handler) if handler.frame >= lowest_frame => *handler,
+      _ => return Err(error),
+    };
+    stack.handlers.pop();
+
+    for frame in stack.frames.drain(handler.frame + 1..).rev() {
+      stack.regs.truncate(frame.caller_len);
+    }
+    let frame_end = current_call_frame!(self).end();
+    stack_mut!(self).truncate(frame_end);
+
+    self.pc = handler.pc;
+    self.acc = match stack.thrown.take() {
+      Some((value, thrown)) if matches!(&error, Error::Value(e) if *e == thrown) => value,
+      _ => self.error_to_value(error.to_error_value()),
+    };
+    Ok(())
+  }
+
+  /// The value seen by a `catch` block for an error which did not come from
+  /// a `throw`.
+  ///
+  /// If a class is registered for the error's code, the error is an instance
+  /// of that class, otherwise it is a plain table.
+  fn error_to_value(&self, error: ErrorValue) -> Value {
+    let Some(class) = self.global.get_error_class(&error.code) else {
+      return error.into_value(&self.global);
+    };
+    let instance = ClassInstance::new(class);
+    for (key, value) in error.into_table(&self.global).entries() {
+      instance.fields.insert(&self.global, key, value);
     }
+    Value::object(self.global.alloc(instance))
   }
 
   pub async fn entry(&mut self, main: Ptr<Function>) -> Result<Value> {
     Function::prepare_call_empty_unchecked(main.clone(), self, None);
+    let mut steps = usize::MAX;
+    let result = loop {
+      match self.run_steps(&mut steps).await {
+        // `yield` outside of a spawned thread does nothing
+        Ok(Status::Yielded | Status::Paused) => continue,
+        Ok(Status::Finished(value)) => break Ok(value),
+        Err(e) => break Err(e),
+      }
+    };
+    if !unsafe { self.stack.as_ref().regs.is_empty() } {
+      eprintln!("{self:?}");
+      panic!("stack is not empty upon exit from vm.entry");
+    }
+    result
+  }
+
+  /// Push a call frame for `function`, which is entered by the next call
+  /// to [`Thread::run_steps`].
+  pub(crate) fn start(&mut self, function: Ptr<Function>, args: &[Value]) -> Result<()> {
+    let args = self.push_args(args);
+    if let Err(e) = Function::prepare_call(function, self, args, None) {
+      self.pop_args(args);
+      return Err(e);
+    }
+    Ok(())
+  }
+
+  /// Run the interpreter until the script yields, the outermost call frame
+  /// returns, or `steps` instructions have executed.
+  ///
+  /// `steps` is decremented for every executed instruction, except for
+  /// instructions in calls made by native functions, which always run to
+  /// completion. `usize::MAX` steps means there is no limit, and nothing is
+  /// counted. An error which is not caught unwinds the entire call stack.
+  pub async fn run_steps(&mut self, steps: &mut usize) -> Result<Status> {
     loop {
-      if let Err(e) = self.run() {
-        self.unwind_stack(None);
-        if !unsafe { self.stack.as_ref().regs.is_empty() } {
-          eprintln!("{self:?}");
-          panic!("stack is not empty upon exit from vm.entry");
-        }
-        break Err(e);
+      if let Err(e) = self.run(steps) {
+        let Err(e) = self.catch_error(e, 0) else {
+          continue;
+        };
+        self.unwind_stack(0);
+        return Err(e);
       }
       if let Some(frame) = self.poll.take() {
+        let dst = self.poll_dst.take();
         let result = frame.fut.await;
         self.truncate_stack(frame.stack_base);
         match result {
-          Ok(value) => {
-            self.acc = value;
-            continue;
-          }
+          Ok(value) => self.write_result(dst, value),
           Err(e) => {
-            self.unwind_stack(None);
-            if !unsafe { self.stack.as_ref().regs.is_empty() } {
-              eprintln!("{self:?}");
-              panic!("stack is not empty upon exit from vm.entry");
-            }
-            break Err(e);
+            let Err(e) = self.catch_error(e, 0) else {
+              continue;
+            };
+            self.unwind_stack(0);
+            return Err(e);
           }
         }
+      } else if take(&mut self.yielded) {
+        return Ok(Status::Yielded);
+      } else if !unsafe { self.stack.as_ref().frames.is_empty() } {
+        return Ok(Status::Paused);
       } else {
-        let value = take(&mut self.acc);
-        if !unsafe { self.stack.as_ref().regs.is_empty() } {
-          eprintln!("{self:?}");
-          panic!("stack is not empty upon exit from vm.entry");
-        }
-        break Ok(value);
+        return Ok(Status::Finished(take(&mut self.acc)));
       }
     }
   }
 
   pub async fn call(&mut self, callable: Ptr<Any>, args: &[Value]) -> Result<Value> {
+    let args = self.push_args(args);
+    let result = self.call_in_place(callable, args).await;
+    self.pop_args(args);
+    result
+  }
+
+  /// Call `callable` once for every row of arguments written by `next_row`,
+  /// until it returns `None`.
+  ///
+  /// The argument registers are only pushed once, and each row overwrites
+  /// the previous one.
+  pub async fn call_batch(
+    &mut self,
+    callable: Ptr<Any>,
+    arity: usize,
+    mut next_row: impl FnMut(&mut [Value]) -> Option<Result<()>>,
+  ) -> Result<Vec<Value>> {
+    let start = stack!(self).len();
+    stack_mut!(self).resize_with(start + arity, Value::none);
+    let args = Args {
+      start,
+      count: arity,
+    };
+
+    let mut results = Vec::new();
+    let result = loop {
+      match next_row(&mut stack_mut!(self)[start..start + arity]) {
+        Some(Ok(())) => {}
+        Some(Err(e)) => break Err(e),
+        None => break Ok(()),
+      }
+      match self.call_in_place(callable.clone(), args).await {
+        Ok(value) => results.push(value),
+        Err(e) => break Err(e),
+      }
+      // async native functions may leave their scope on the stack
+      self.truncate_stack(start + arity);
+    };
+
+    self.pop_args(args);
+    result.map(|_| results)
+  }
+
+  /// Call `callable` with `args`, which must already be on the stack.
+  async fn call_in_place(&mut self, callable: Ptr<Any>, args: Args) -> Result<Value> {
     let current_frame_index = unsafe { self.stack.as_ref().frames.len() };
 
-    let args = self.push_args(args);
     let result = match callable.call(self.get_scope(args), None) {
       Ok(call) => match call {
         CallResult::Return(value) => Ok(value),
@@ -139,20 +307,34 @@ impl Thread {
         CallResult::Dispatch => {
           // the call pushed a frame onto the call stack,
           // so all we have to do is enter the interpreter
+          let mut steps = usize::MAX;
           loop {
-            if let Err(e) = self.run() {
+            if let Err(e) = self.run(&mut steps) {
+              let Err(e) = self.catch_error(e, current_frame_index) else {
+                continue;
+              };
               break Err(e);
             }
             if let Some(frame) = self.poll.take() {
+              let dst = self.poll_dst.take();
               let result = frame.fut.await;
               self.truncate_stack(frame.stack_base);
               match result {
                 Ok(value) => {
-                  self.acc = value;
+                  self.write_result(dst, value);
                   continue;
                 }
-                Err(e) => break Err(e),
+                Err(e) => {
+                  let Err(e) = self.catch_error(e, current_frame_index) else {
+                    continue;
+                  };
+                  break Err(e);
+                }
               };
+            } else if take(&mut self.yielded) {
+              // a native function cannot be suspended,
+              // so the script keeps running
+              continue;
             } else {
               break Ok(take(&mut self.acc));
             }
@@ -162,23 +344,22 @@ impl Thread {
       Err(e) => Err(e),
     };
 
-    match result {
-      Ok(value) => {
-        self.pop_args(args);
-        Ok(value)
-      }
-      Err(e) => {
-        self.unwind_stack(Some(current_frame_index));
-        Err(e)
-      }
+    if result.is_err() {
+      self.unwind_stack(current_frame_index);
     }
+    result
   }
 
-  fn run(&mut self) -> Result<()> {
+  fn run(&mut self, steps: &mut usize) -> Result<()> {
     let instructions = current_call_frame_mut!(self).instructions;
     let pc = self.pc;
 
-    match dispatch(self, instructions, pc)? {
+    let flow = if *steps == usize::MAX {
+      dispatch::<_, false>(self, instructions, pc, steps)?
+    } else {
+      dispatch::<_, true>(self, instructions, pc, steps)?
+    };
+    match flow {
       ControlFlow::Yield(pc) => {
         self.pc = pc;
         Ok(())
@@ -205,6 +386,44 @@ impl Thread {
     stack_mut!(self).truncate(to)
   }
 
+  /// Call `function` with `args`, which are in the registers of the current
+  /// frame right after the register holding the callee.
+  ///
+  /// A script function runs in a frame which starts at the callee's register
+  /// instead of copying the arguments to the top of the stack.
+  fn do_call_in_place(
+    &mut self,
+    function: Ptr<Any>,
+    args: Args,
+    return_addr: usize,
+  ) -> Result<Call> {
+    if function.is::<Function>() {
+      let function = unsafe { function.cast_unchecked::<Function>() };
+      return Function::prepare_call_in_place(function, self, args, return_addr)
+        .map(Call::LoadFrame);
+    }
+    self.do_call(function, args, return_addr)
+  }
+
+  /// Store the result of a call in `dst`, or in the accumulator if the call
+  /// has no destination register.
+  fn write_result(&mut self, dst: Option<op::Register>, value: Value) {
+    match dst {
+      Some(dst) => self.set_register(dst, value),
+      None => self.acc = value,
+    }
+  }
+
+  /// Count a call to `callee` by the call instruction which ends right
+  /// before `return_addr`, see [`Global::record_call_site`].
+  #[cfg(feature = "profile")]
+  fn record_call_site(&self, return_addr: usize, callee: &Ptr<Any>) {
+    let frame = current_call_frame!(self);
+    // the span table finds an instruction from any of its bytes
+    let pc = return_addr - 1;
+    (self.global).record_call_site(&frame.descriptor, frame.module_id, pc, callee);
+  }
+
   fn do_call(&mut self, function: Ptr<Any>, args: Args, return_addr: usize) -> Result<Call> {
     if function.is::<Function>() {
       let function = unsafe { function.cast_unchecked::<Function>() };
@@ -240,10 +459,10 @@ impl Thread {
     upvalues.resize_with(num_upvalues, Value::none);
     for (i, upvalue) in desc.upvalues.borrow().iter().enumerate() {
       let value = match upvalue {
-        function::Upvalue::Register(register) => self.get_register(*register),
+        function::Upvalue::Register(register) => self.capture_register(*register),
         function::Upvalue::Upvalue(upvalue) => {
           let parent_upvalues = &current_call_frame!(self).upvalues;
-          debug_assert!(upvalue.index() < parent_upvalues.len());
+          paranoid_assert!(upvalue.index() < parent_upvalues.len());
           unsafe { parent_upvalues.get_unchecked(upvalue.index()) }
         }
       };
@@ -259,6 +478,22 @@ impl Thread {
     ))
   }
 
+  /// The cell of the variable in `reg`, which replaces its value the first
+  /// time it is captured.
+  fn capture_register(&mut self, reg: op::Register) -> Value {
+    let value = self.get_register_raw(reg);
+    if value.clone().to_object::<Cell>().is_some() {
+      return value;
+    }
+    let cell = Value::object(self.global.alloc(Cell::new(value)));
+    // the VM reads the callee or receiver in the first register directly,
+    // so it is captured by value
+    if reg.index() != 0 {
+      self.set_register(reg, cell.clone());
+    }
+    cell
+  }
+
   fn make_class(
     &mut self,
     desc: Ptr<ClassDescriptor>,
@@ -267,7 +502,7 @@ impl Thread {
   ) -> Ptr<ClassType> {
     let mut init = desc.init.as_ref().map(|init| self.make_fn(init.clone()));
     let fields = fields.unwrap_or_else(|| self.global.alloc(Table::new()));
-    let mut methods = IndexMap::with_capacity(desc.methods.len());
+    let mut methods = StrMap::with_capacity_and_hasher(desc.methods.len(), Default::default());
 
     // inherit `init` and methods
     if let Some(parent) = parent.as_ref() {
@@ -285,6 +520,7 @@ impl Thread {
     }
 
     self.global.alloc(ClassType::new(
+      &self.global,
       desc.name.clone(),
       init,
       fields,
@@ -294,6 +530,13 @@ impl Thread {
   }
 
   fn load_module(&mut self, path: Ptr<Str>, return_addr: usize) -> Result<Call> {
+    let path = match self.global.rewrite_import(path.as_str()) {
+      Some(rewritten) => self.global.intern(rewritten),
+      None => path,
+    };
+    if let Some(policy) = self.global.policy() {
+      policy.import(path.as_str())?;
+    }
     if let Some((module_id, module)) = self.global.get_module_by_name(path.as_str()) {
       // module is in cache
       if self.global.is_module_visited(module_id) {
@@ -302,24 +545,27 @@ impl Thread {
       self.acc = Value::object(module);
       return Ok(Call::Continue);
     }
+    if path.as_str() == sys::NAME {
+      let module_id = current_call_frame!(self).module_id;
+      self.acc = Value::object(sys::module(&self.global, module_id));
+      return Ok(Call::Continue);
+    }
 
     // module is not in cache, actually load it
     let module_id = self.global.next_module_id();
-    let module = self.global.load_module(path.as_str())?.to_string();
-    let module = syntax::parse(self.global.clone(), &module).map_err(Error::Syntax)?;
-    let module = codegen::emit(self.global.clone(), &module, path.as_str(), false);
-    let main = self.global.alloc(Function::new(
-      module.root.clone(),
-      self.global.alloc(List::new()),
-      module_id,
-    ));
-    let module = self.global.alloc(Module::script(
-      self.global.clone(),
-      path.clone(),
-      main,
-      &module.module_vars,
-      module_id,
-    ));
+    let module = match self.global.get_bundled_module(path.as_str()) {
+      Some((source, module)) => self.instantiate_module(path.clone(), source, &module, module_id),
+      None => match self.load_shared_module(&path, module_id)? {
+        Some(module) => module,
+        None => {
+          let source = Source::new(
+            path.as_str(),
+            self.global.load_module(path.as_str())?.to_string(),
+          );
+          self.compile_module(path.clone(), source, module_id)?
+        }
+      },
+    };
     self.global.define_module(module_id, path, module.clone());
 
     let ModuleKind::Script { root } = &module.kind else {
@@ -333,10 +579,138 @@ impl Thread {
     }))
   }
 
+  /// Parse and emit `source` as the script module `name`.
+  ///
+  /// The source is kept around for runtime errors raised by the module.
+  pub(crate) fn compile_module(
+    &self,
+    name: Ptr<Str>,
+    source: Source,
+    module_id: ModuleId,
+  ) -> Result<Ptr<Module>> {
+    let module = self.emit_module(&name, &source).map_err(Error::Syntax)?;
+    Ok(self.instantiate_module(name, Some(source), &module, module_id))
+  }
+
+  /// Create the script module `name` from the [`SharedGlobals`] of the VM,
+  /// unless it isn't one of them. It is only compiled if no other VM which
+  /// shares them has compiled it yet.
+  ///
+  /// [`SharedGlobals`]: crate::SharedGlobals
+  fn load_shared_module(
+    &self,
+    name: &Ptr<Str>,
+    module_id: ModuleId,
+  ) -> Result<Option<Ptr<Module>>> {
+    let Some(shared) = self.global.shared().cloned() else {
+      return Ok(None);
+    };
+    let Some(text) = shared.module_source(name.as_str()) else {
+      return Ok(None);
+    };
+    let source = Source::new(name.as_str(), text.to_string());
+    // functions inlined from other modules depend on what this VM loaded
+    if self.global.inline_functions() {
+      return self
+        .compile_module(name.clone(), source, module_id)
+        .map(Some);
+    }
+
+    let strict = self.global.strict_globals();
+    let packed = shared
+      .compiled_module(name.as_str(), strict)
+      .and_then(|pack| pack::read(&self.global, &pack).ok())
+      .and_then(|mut modules| modules.pop());
+    let module = match packed {
+      Some((_, _, module)) => module,
+      // a pack which this VM can't load, for example because its policy
+      // forbids a construct, is compiled to report the error
+      None => {
+        let module = self.emit_module(name, &source).map_err(Error::Syntax)?;
+        if let Ok(pack) = pack::write(&[(name.clone(), None, module.clone())], false) {
+          shared.set_compiled_module(name.as_str(), strict, pack);
+        }
+        module
+      }
+    };
+    Ok(Some(self.instantiate_module(
+      name.clone(),
+      Some(source),
+      &module,
+      module_id,
+    )))
+  }
+
+  /// Parse and emit `source` as the script module `name`, without creating
+  /// the module.
+  pub(crate) fn emit_module(
+    &self,
+    name: &Ptr<Str>,
+    source: &Source,
+  ) -> Result<Ptr<ModuleDescriptor>, SyntaxError> {
+    let start = Instant::now();
+    let ast =
+      syntax::parse(self.global.clone(), &source.text).map_err(|e| e.with_source(source))?;
+    let parse = start.elapsed();
+    let start = Instant::now();
+    let module = codegen::emit(self.global.clone(), &ast, name.as_str(), false)
+      .map_err(|e| e.with_source(source))?;
+    let timings = CompileTimings {
+      parse,
+      emit: start.elapsed(),
+      ..Default::default()
+    };
+    self.global.log_compile(name.as_str(), &timings);
+    Ok(module)
+  }
+
+  /// Create the script module `name` from its compiled code, which has to
+  /// be run before the module is usable. Errors raised by the module only
+  /// point into its source if there is one.
+  pub(crate) fn instantiate_module(
+    &self,
+    name: Ptr<Str>,
+    source: Option<Source>,
+    module: &ModuleDescriptor,
+    module_id: ModuleId,
+  ) -> Ptr<Module> {
+    let main = self.global.alloc(Function::new(
+      module.root.clone(),
+      self.global.alloc(List::new()),
+      module_id,
+    ));
+    if let Some(source) = source {
+      self.global.set_module_source(module_id, source);
+    }
+    self.global.alloc(Module::script(
+      self.global.clone(),
+      name,
+      main,
+      &module.module_vars,
+      module_id,
+    ))
+  }
+
   fn get_empty_scope(&self) -> Scope {
     self.get_scope(Args::empty())
   }
 
+  /// Fields of values which are not objects. Only ints have any, see
+  /// [`int`].
+  fn primitive_field_opt(&self, receiver: Value, name: Ptr<Str>) -> Result<Option<Value>> {
+    match receiver.to_int() {
+      Some(this) => int::named_field_opt(self.get_empty_scope(), this, name),
+      None => Ok(None),
+    }
+  }
+
+  fn primitive_field(&self, receiver: Value, name: Ptr<Str>) -> Result<Value> {
+    match self.primitive_field_opt(receiver.clone(), name.clone())? {
+      Some(value) => Ok(value),
+      None => fail!("`{receiver}` has no field `{name}`"),
+    }
+  }
+
   fn get_scope(&self, args: Args) -> Scope {
     Scope::new(self, stack!(self).len(), args)
   }
@@ -434,6 +808,7 @@ impl Debug for Thread {
       .field("acc", &self.acc)
       .field("pc", &self.pc)
       .field("poll", &self.poll)
+      .field("yielded", &self.yielded)
       .finish()
   }
 }
@@ -458,8 +833,16 @@ pub(crate) struct Frame {
   upvalues: Ptr<List>,
   stack_base: usize,
   frame_size: usize,
+  /// The length of the register stack before the frame was entered, which
+  /// is restored when it is left. A frame which was entered in place starts
+  /// below it, inside of its caller's registers.
+  caller_len: usize,
   return_addr: Option<usize>,
+  /// The register of the caller which the return value is written to, if
+  /// the frame was entered by `CallInto`.
+  return_reg: Option<op::Register>,
   module_id: ModuleId,
+  descriptor: Ptr<FunctionDescriptor>,
 }
 
 impl Debug for Frame {
@@ -470,7 +853,9 @@ impl Debug for Frame {
       .field("upvalues", &self.upvalues)
       .field("stack_base", &self.stack_base)
       .field("frame_size", &self.frame_size)
+      .field("caller_len", &self.caller_len)
       .field("return_addr", &self.return_addr)
+      .field("return_reg", &self.return_reg)
       .field("module_id", &self.module_id)
       .finish()
   }
@@ -486,10 +871,32 @@ impl Frame {
       upvalues: f.upvalues.clone(),
       stack_base,
       frame_size: desc.frame_size,
+      caller_len: stack_base,
       return_addr,
+      return_reg: None,
       module_id: f.module_id,
+      descriptor: f.descriptor.clone(),
+    }
+  }
+
+  /// Enter `f` in place, in a frame starting at `stack_base`, which is below
+  /// `caller_len`, the current length of the register stack.
+  pub(crate) fn new_in_place(
+    f: &Function,
+    stack_base: usize,
+    caller_len: usize,
+    return_addr: usize,
+  ) -> Self {
+    Self {
+      caller_len,
+      ..Self::new(f, stack_base, Some(return_addr))
     }
   }
+
+  /// The length of the register stack while this is the current frame.
+  fn end(&self) -> usize {
+    (self.stack_base + self.frame_size).max(self.caller_len)
+  }
 }
 
 impl Thread {
@@ -499,11 +906,24 @@ impl Thread {
 
   fn get_constant_object<T: Type>(&self, idx: op::Constant) -> Ptr<T> {
     let object = self.get_constant(idx).into_value();
+    paranoid_assert!(
+      matches!(object.clone().to_any(), Some(object) if object.is::<T>()),
+      "constant {idx} is not a {}",
+      std::any::type_name::<T>()
+    );
     unsafe { object.to_any_unchecked().cast_unchecked::<T>() }
   }
 
+  /// The value of the variable in `reg`, which may be captured in a cell.
   fn get_register(&self, reg: op::Register) -> Value {
-    debug_assert!(
+    match self.get_register_raw(reg).try_to_object::<Cell>() {
+      Ok(cell) => cell.get(),
+      Err(value) => value,
+    }
+  }
+
+  fn get_register_raw(&self, reg: op::Register) -> Value {
+    paranoid_assert!(
       self.stack_base() + reg.index() < stack!(self).len(),
       "register out of bounds {reg:?}"
     );
@@ -515,7 +935,7 @@ impl Thread {
   }
 
   fn set_register(&mut self, reg: op::Register, value: Value) {
-    debug_assert!(
+    paranoid_assert!(
       self.stack_base() + reg.index() < stack!(self).len(),
       "register out of bounds {reg:?}"
     );
@@ -548,6 +968,37 @@ macro_rules! vprintln {
 impl Handler for Thread {
   type Error = crate::internal::vm::Error;
 
+  fn set_steps(&mut self, steps: usize) {
+    unsafe { self.stack.as_mut() }.steps = steps;
+  }
+
+  fn locate_error(&mut self, error: Self::Error, pc: usize) -> Self::Error {
+    let located = match &error {
+      Error::Vm(e) => e.source.is_some() || e.span != Span::default(),
+      Error::Value(e) => e.source.is_some() || e.span != Span::default(),
+      _ => true,
+    };
+    if located {
+      return error;
+    }
+    let frame = current_call_frame!(self);
+    let (Some(source), Some(span)) = (
+      self.global.get_module_source(frame.module_id),
+      frame.descriptor.span_at(pc),
+    ) else {
+      return error;
+    };
+    match error {
+      Error::Vm(e) => Error::Vm(SpannedError { span, ..e }.with_source(source)),
+      Error::Value(e) => Error::Value(ErrorValue {
+        span,
+        source: Some(Box::new(source)),
+        ..e
+      }),
+      error => error,
+    }
+  }
+
   fn op_load(&mut self, reg: op::Register) -> Result<()> {
     self.print_stack();
     vprintln!("load {reg}");
@@ -568,6 +1019,19 @@ impl Handler for Thread {
     Ok(())
   }
 
+  fn op_store_cell(&mut self, reg: op::Register) -> Result<()> {
+    self.print_stack();
+    vprintln!("store_cell {reg}");
+
+    let value = take(&mut self.acc);
+    match self.get_register_raw(reg).try_to_object::<Cell>() {
+      Ok(cell) => cell.set(value),
+      Err(_) => self.set_register(reg, value),
+    }
+
+    Ok(())
+  }
+
   fn op_load_const(&mut self, idx: op::Constant) -> Result<()> {
     self.print_stack();
     vprintln!("load_const {idx}");
@@ -584,12 +1048,12 @@ impl Handler for Thread {
 
     let call_frame = current_call_frame!(self);
     let upvalues = &call_frame.upvalues;
-    debug_assert!(
+    paranoid_assert!(
       idx.index() < upvalues.len(),
       "upvalue index is out of bounds {idx:?}"
     );
-    let value = unsafe { call_frame.upvalues.get_unchecked(idx.index()) };
-    self.acc = value;
+    let cell = unsafe { call_frame.upvalues.get_unchecked(idx.index()) };
+    self.acc = unsafe { cell.to_object_unchecked::<Cell>() }.get();
 
     Ok(())
   }
@@ -600,12 +1064,12 @@ impl Handler for Thread {
 
     let call_frame = current_call_frame!(self);
     let upvalues = &call_frame.upvalues;
-    debug_assert!(
+    paranoid_assert!(
       idx.index() < upvalues.len(),
       "upvalue index is out of bounds {idx:?}"
     );
-    let value = take(&mut self.acc);
-    unsafe { call_frame.upvalues.set_unchecked(idx.index(), value) };
+    let cell = unsafe { call_frame.upvalues.get_unchecked(idx.index()) };
+    unsafe { cell.to_object_unchecked::<Cell>() }.set(take(&mut self.acc));
 
     Ok(())
   }
@@ -663,7 +1127,23 @@ impl Handler for Thread {
     let name = self.get_constant_object::<Str>(name);
     let value = match self.global.get(&name) {
       Some(value) => value,
-      None => fail!("undefined global {name}"),
+      None if self.global.strict_globals() => {
+        return Err(
+          ErrorValue::new(
+            "name_error",
+            format!(
+              "undefined global `{name}`{}",
+              did_you_mean(&name, self.global.entries().map(|(key, _)| key))
+            ),
+          )
+          .with("name", name.as_str())
+          .into(),
+        )
+      }
+      None => fail!(
+        "undefined global {name}{}",
+        did_you_mean(&name, self.global.entries().map(|(key, _)| key))
+      ),
     };
     self.acc = value;
 
@@ -675,6 +1155,9 @@ impl Handler for Thread {
     vprintln!("store_global {name}");
 
     let name = self.get_constant_object::<Str>(name);
+    if let Some(policy) = self.global.policy() {
+      policy.store_global(name.as_str())?;
+    }
     let value = take(&mut self.acc);
     self.global.set(name, value);
 
@@ -692,16 +1175,53 @@ impl Handler for Thread {
     // native class methods
     // class methods
 
-    if let Some(object) = receiver.to_any() {
+    if let Some(object) = receiver.clone().to_any() {
       self.acc = object.named_field(self.get_empty_scope(), name)?;
     } else {
-      // TODO: fields on primitives
-      todo!("fields on primitives")
+      self.acc = self.primitive_field(receiver, name)?;
     }
 
     Ok(())
   }
 
+  fn op_load_module_field(&mut self, name: op::Constant, slot: op::ModuleVar) -> Result<()> {
+    self.print_stack();
+    vprintln!("load_module_field {name} {slot}");
+
+    let key = self.get_constant_object::<Str>(name);
+    let module = self
+      .acc
+      .clone()
+      .to_any()
+      .and_then(|v| v.cast::<Module>().ok());
+    if let Some(value) = module.and_then(|m| m.module_vars.get_index_with_key(slot.index(), &key)) {
+      self.acc = value;
+      return Ok(());
+    }
+
+    // the receiver is not the module which the field was resolved in
+    self.op_load_field(name)
+  }
+
+  fn op_load_field_slot(&mut self, name: op::Constant, slot: op::Slot) -> Result<()> {
+    self.print_stack();
+    vprintln!("load_field_slot {name} {slot}");
+
+    let key = self.get_constant_object::<Str>(name);
+    let instance = self.acc.clone().to_object::<ClassInstance>();
+    if let Some(value) = instance.and_then(|i| i.fields.get_index_with_key(slot.index(), &key)) {
+      // methods are bound to the instance when they are loaded
+      if let Err(value) = value.try_to_object::<Function>() {
+        self.acc = value;
+        return Ok(());
+      }
+    }
+
+    // the receiver is not an instance of the class which the field was
+    // resolved in
+    self.op_load_field(name)
+  }
+
   fn op_load_field_opt(&mut self, name: op::Constant) -> Result<()> {
     self.print_stack();
     vprintln!("load_field_opt {name}");
@@ -714,13 +1234,14 @@ impl Handler for Thread {
       return Ok(());
     }
 
-    if let Some(object) = receiver.to_any() {
+    if let Some(object) = receiver.clone().to_any() {
       self.acc = object
         .named_field_opt(self.get_empty_scope(), name)?
         .unwrap_or_else(Value::none);
     } else {
-      // TODO: fields on primitives
-      todo!("fields on primitives")
+      self.acc = self
+        .primitive_field_opt(receiver, name)?
+        .unwrap_or_else(Value::none);
     }
 
     Ok(())
@@ -744,6 +1265,32 @@ impl Handler for Thread {
     Ok(())
   }
 
+  fn op_store_field_slot(
+    &mut self,
+    obj: op::Register,
+    name: op::Constant,
+    slot: op::Slot,
+  ) -> Result<()> {
+    self.print_stack();
+    vprintln!("store_field_slot {obj}, {name} {slot}");
+
+    let key = self.get_constant_object::<Str>(name);
+    let instance = self.get_register(obj).to_object::<ClassInstance>();
+    if let Some(instance) = instance {
+      if instance
+        .fields
+        .set_index_with_key(slot.index(), &key, self.acc.clone())
+      {
+        self.acc = Value::none();
+        return Ok(());
+      }
+    }
+
+    // the receiver is not an instance of the class which the field was
+    // resolved in
+    self.op_store_field(obj, name)
+  }
+
   fn op_load_index(&mut self, obj: op::Register) -> Result<()> {
     self.print_stack();
     vprintln!("load_index {obj}");
@@ -778,8 +1325,8 @@ impl Handler for Thread {
         .keyed_field_opt(self.get_empty_scope(), key)?
         .unwrap_or_else(Value::none);
     } else {
-      // TODO: fields on primitives
-      todo!()
+      // primitives can't be indexed
+      self.acc = Value::none();
     };
 
     Ok(())
@@ -1028,7 +1575,7 @@ impl Handler for Thread {
     vprintln!("jump_const {idx}");
 
     let offset = self.get_constant(idx).as_offset().cloned();
-    debug_assert!(offset.is_some());
+    paranoid_assert!(offset.is_some());
     let offset = unsafe { offset.unwrap_unchecked() };
     Ok(offset)
   }
@@ -1055,15 +1602,85 @@ impl Handler for Thread {
     vprintln!("jump_if_false_const {idx}");
 
     let offset = self.get_constant(idx).as_offset().cloned();
-    debug_assert!(offset.is_some());
+    paranoid_assert!(offset.is_some());
     let offset = unsafe { offset.unwrap_unchecked() };
 
     match is_truthy(take(&mut self.acc)) {
+      true => Ok(super::dispatch::Jump::Skip),
+      false => Ok(super::dispatch::Jump::Move(offset)),
+    }
+  }
+
+  fn op_jump_if_none(&mut self, offset: op::Offset) -> Result<super::dispatch::Jump> {
+    self.print_stack();
+    vprintln!("jump_if_none {offset}");
+
+    match self.acc.is_none() {
+      true => Ok(super::dispatch::Jump::Move(offset)),
+      false => Ok(super::dispatch::Jump::Skip),
+    }
+  }
+
+  fn op_jump_if_none_const(&mut self, idx: op::Constant) -> Result<super::dispatch::Jump> {
+    self.print_stack();
+    vprintln!("jump_if_none_const {idx}");
+
+    let offset = self.get_constant(idx).as_offset().cloned();
+    paranoid_assert!(offset.is_some());
+    let offset = unsafe { offset.unwrap_unchecked() };
+
+    match self.acc.is_none() {
       true => Ok(super::dispatch::Jump::Move(offset)),
       false => Ok(super::dispatch::Jump::Skip),
     }
   }
 
+  fn op_push_handler(&mut self, catch_pc: usize) -> Result<()> {
+    self.print_stack();
+    vprintln!("push_handler {catch_pc}");
+
+    let stack = unsafe { self.stack.as_mut() };
+    debug_assert!(!stack.frames.is_empty());
+    stack.handlers.push(TryHandler {
+      frame: stack.frames.len() - 1,
+      pc: catch_pc,
+    });
+
+    Ok(())
+  }
+
+  fn op_push_handler_const(&mut self, idx: op::Constant) -> Result<op::Offset> {
+    self.print_stack();
+    vprintln!("push_handler_const {idx}");
+
+    let offset = self.get_constant(idx).as_offset().cloned();
+    paranoid_assert!(offset.is_some());
+    let offset = unsafe { offset.unwrap_unchecked() };
+    Ok(offset)
+  }
+
+  fn op_pop_handler(&mut self) -> Result<()> {
+    self.print_stack();
+    vprintln!("pop_handler");
+
+    let stack = unsafe { self.stack.as_mut() };
+    debug_assert!(!stack.handlers.is_empty());
+    stack.handlers.pop();
+
+    Ok(())
+  }
+
+  fn op_throw(&mut self) -> Result<()> {
+    self.print_stack();
+    vprintln!("throw");
+
+    let value = take(&mut self.acc);
+    let error = ErrorValue::from_value(value.clone(), self.global.float_format());
+    unsafe { self.stack.as_mut() }.thrown = Some((value, error.clone()));
+
+    Err(error.into())
+  }
+
   fn op_add(&mut self, lhs: op::Register) -> Result<()> {
     self.print_stack();
     vprintln!("add {lhs}");
@@ -1206,12 +1823,9 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs == rhs),
-      f64 => Value::bool(lhs == rhs),
-      any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Equal)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs == rhs);
+    let is_equal = cmp::equals(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(is_equal);
     Ok(())
   }
 
@@ -1221,12 +1835,9 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs != rhs),
-      f64 => Value::bool(lhs != rhs),
-      any => Value::bool(!matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Equal)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs != rhs);
+    let is_equal = cmp::equals(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(!is_equal);
     Ok(())
   }
 
@@ -1236,12 +1847,9 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs > rhs),
-      f64 => Value::bool(lhs > rhs),
-      any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Greater)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs > rhs);
+    let ordering = cmp::partial_cmp(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(matches!(ordering, Some(Ordering::Greater)));
     Ok(())
   }
 
@@ -1251,12 +1859,12 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs >= rhs),
-      f64 => Value::bool(lhs >= rhs),
-      any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Greater | Ordering::Equal)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs >= rhs);
+    let ordering = cmp::partial_cmp(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(matches!(
+      ordering,
+      Some(Ordering::Greater | Ordering::Equal)
+    ));
     Ok(())
   }
 
@@ -1266,12 +1874,9 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs < rhs),
-      f64 => Value::bool(lhs < rhs),
-      any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Less)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs < rhs);
+    let ordering = cmp::partial_cmp(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(matches!(ordering, Some(Ordering::Less)));
     Ok(())
   }
 
@@ -1281,12 +1886,9 @@ impl Handler for Thread {
 
     let lhs = self.get_register(lhs);
     let rhs = take(&mut self.acc);
-    let value = binary!(lhs, rhs {
-      i32 => Value::bool(lhs <= rhs),
-      f64 => Value::bool(lhs <= rhs),
-      any => Value::bool(matches!(lhs.cmp(self.get_empty_scope(), rhs)?, Ordering::Less | Ordering::Equal)),
-    });
-    self.acc = value;
+    cmp_int_fast_path!(self, lhs <= rhs);
+    let ordering = cmp::partial_cmp(self.get_empty_scope(), lhs, rhs)?;
+    self.acc = Value::bool(matches!(ordering, Some(Ordering::Less | Ordering::Equal)));
     Ok(())
   }
 
@@ -1342,8 +1944,10 @@ impl Handler for Thread {
     self.print_stack();
     vprintln!("print");
 
+    let format = self.global.float_format();
+    let line = format!("{}\n", take(&mut self.acc).display(format));
     let mut output = self.global.io().output.borrow_mut();
-    writeln!(&mut output, "{}", take(&mut self.acc)).map_err(Error::user)?;
+    output.write(line.as_bytes()).map_err(Error::user)?;
     Ok(())
   }
 
@@ -1353,9 +1957,14 @@ impl Handler for Thread {
 
     debug_assert!(self.stack_base() + start.index() + count.value() <= stack!(self).len());
 
+    let format = self.global.float_format();
+    let start = self.stack_base() + start.index();
+    let values = stack!(self)[start..start + count.value()]
+      .iter()
+      .map(|value| value.display(format));
+    let line = format!("{}\n", values.join(" "));
     let mut output = self.global.io().output.borrow_mut();
-    let values = stack!(self)[start.index()..start.index() + count.value()].iter();
-    writeln!(&mut output, "{}", values.join(" ")).map_err(Error::user)?;
+    output.write(line.as_bytes()).map_err(Error::user)?;
 
     Ok(())
   }
@@ -1373,8 +1982,10 @@ impl Handler for Thread {
     let Some(function) = function.clone().to_any() else {
       fail!("`{function}` is not callable");
     };
+    #[cfg(feature = "profile")]
+    self.record_call_site(return_addr, &function);
 
-    self.do_call(function, args, return_addr)
+    self.do_call_in_place(function, args, return_addr)
   }
 
   fn op_call0(&mut self, return_addr: usize) -> Result<Call> {
@@ -1390,10 +2001,99 @@ impl Handler for Thread {
     let Some(function) = function.clone().to_any() else {
       fail!("`{function}` is not callable");
     };
+    #[cfg(feature = "profile")]
+    self.record_call_site(return_addr, &function);
 
     self.do_call(function, args, return_addr)
   }
 
+  fn op_call_method(
+    &mut self,
+    return_addr: usize,
+    obj: op::Register,
+    name: op::Constant,
+    args: op::Count,
+  ) -> Result<Call> {
+    self.print_stack();
+    vprintln!("call_method {obj}, {name}, {args} (ret={return_addr})");
+
+    let name = self.get_constant_object::<Str>(name);
+    let receiver = self.get_register(obj);
+    let start = self.stack_base() + obj.index();
+
+    // a method of a class instance is called with the receiver in `obj` as
+    // its `self`, instead of binding it to the receiver first
+    if let Some(instance) = receiver.clone().to_object::<ClassInstance>() {
+      let method = instance
+        .fields
+        .get(&name)
+        .and_then(|v| v.to_object::<Function>());
+      if let Some(method) = method.filter(|m| m.descriptor.params.has_self) {
+        // arity errors don't count the receiver, as with a bound method
+        check_args(&method.descriptor.params, true, args.value())?;
+        #[cfg(feature = "profile")]
+        self.record_call_site(return_addr, &method.clone().into_any());
+        let args = Args {
+          start,
+          count: 1 + args.value(),
+        };
+        return Function::prepare_call_in_place(method, self, args, return_addr)
+          .map(Call::LoadFrame);
+      }
+    }
+
+    let function = match receiver.clone().to_any() {
+      Some(object) => object.named_field(self.get_empty_scope(), name)?,
+      None => self.primitive_field(receiver, name)?,
+    };
+    let Some(function) = function.clone().to_any() else {
+      fail!("`{function}` is not callable");
+    };
+    #[cfg(feature = "profile")]
+    self.record_call_site(return_addr, &function);
+    let args = Args {
+      start: start + 1,
+      count: args.value(),
+    };
+
+    self.do_call_in_place(function, args, return_addr)
+  }
+
+  fn op_call_into(
+    &mut self,
+    return_addr: usize,
+    callee: op::Register,
+    args: op::Count,
+    dst: op::Register,
+  ) -> Result<Call> {
+    self.print_stack();
+    vprintln!("call_into {callee}, {args}, {dst} (ret={return_addr})");
+
+    let function = self.get_register(callee);
+    let args = Args {
+      start: self.stack_base() + callee.index() + 1,
+      count: args.value(),
+    };
+
+    let Some(function) = function.clone().to_any() else {
+      fail!("`{function}` is not callable");
+    };
+    #[cfg(feature = "profile")]
+    self.record_call_site(return_addr, &function);
+
+    let call = self.do_call_in_place(function, args, return_addr)?;
+    match call {
+      // the callee's frame is on top of the call stack
+      Call::LoadFrame(_) => current_call_frame_mut!(self).return_reg = Some(dst),
+      Call::Continue => {
+        let value = take(&mut self.acc);
+        self.set_register(dst, value);
+      }
+      Call::Yield => self.poll_dst = Some(dst),
+    }
+    Ok(call)
+  }
+
   fn op_import(&mut self, path: op::Constant, return_addr: usize) -> Result<Call> {
     self.print_stack();
     vprintln!("import {path} (ret={return_addr})");
@@ -1424,14 +2124,24 @@ impl Handler for Thread {
     let stack = unsafe { self.stack.as_mut() };
 
     // pop frame
-    debug_assert!(!stack.frames.is_empty());
+    paranoid_assert!(!stack.frames.is_empty());
     let frame = unsafe { stack.frames.pop().unwrap_unchecked() };
 
     // truncate stack
-    stack.regs.truncate(frame.stack_base);
+    stack.regs.truncate(frame.caller_len);
+
+    // discard handlers of any `try` blocks the frame returned from
+    let frame_index = stack.frames.len();
+    while matches!(stack.handlers.last(), Some(handler) if handler.frame >= frame_index) {
+      stack.handlers.pop();
+    }
 
     if let Some(current_frame) = stack.frames.last() {
       if let Some(return_addr) = frame.return_addr {
+        if let Some(dst) = frame.return_reg {
+          let value = take(&mut self.acc);
+          self.set_register(dst, value);
+        }
         self.pc = return_addr;
         return Ok(Return::LoadFrame(LoadFrame {
           bytecode: current_frame.instructions,
@@ -1447,6 +2157,7 @@ impl Handler for Thread {
     self.print_stack();
     vprintln!("yield");
 
-    todo!()
+    self.yielded = true;
+    Ok(())
   }
 }
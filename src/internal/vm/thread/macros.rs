@@ -37,7 +37,7 @@ macro_rules! call_frames_mut {
 macro_rules! current_call_frame {
   ($self:ident) => {{
     let call_frames = call_frames!($self);
-    debug_assert!(!call_frames.is_empty(), "call frame stack is empty");
+    paranoid_assert!(!call_frames.is_empty(), "call frame stack is empty");
     unsafe { call_frames.last().unwrap_unchecked() }
   }};
 }
@@ -45,7 +45,7 @@ macro_rules! current_call_frame {
 macro_rules! current_call_frame_mut {
   ($self:ident) => {{
     let call_frames = call_frames_mut!($self);
-    debug_assert!(!call_frames.is_empty(), "call frame stack is empty");
+    paranoid_assert!(!call_frames.is_empty(), "call frame stack is empty");
     unsafe { call_frames.last_mut().unwrap_unchecked() }
   }};
 }
@@ -88,3 +88,16 @@ macro_rules! binary {
     }
   }};
 }
+
+/// Compare two ints directly, before falling back to the generic comparison
+/// in [`cmp`][crate::internal::value::cmp], which is much slower.
+macro_rules! cmp_int_fast_path {
+  ($self:ident, $lhs:ident $op:tt $rhs:ident) => {
+    if $lhs.is_int() && $rhs.is_int() {
+      let $lhs = unsafe { $lhs.to_int_unchecked() };
+      let $rhs = unsafe { $rhs.to_int_unchecked() };
+      $self.acc = Value::bool($lhs $op $rhs);
+      return Ok(());
+    }
+  };
+}
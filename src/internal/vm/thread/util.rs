@@ -30,7 +30,7 @@ pub fn clone_from_raw_slice<T: Clone>(ptr: *mut [T], index: usize) -> T {
   }
   let Components { ptr, len } = unsafe { std::mem::transmute::<_, Components<T>>(ptr) };
 
-  debug_assert!(index < len, "index out of bounds {index}");
+  paranoid_assert!(index < len, "index out of bounds {index}");
 
   let value = unsafe { std::mem::ManuallyDrop::new(std::ptr::read(ptr.add(index))) };
   std::mem::ManuallyDrop::into_inner(value.clone())
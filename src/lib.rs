@@ -12,8 +12,13 @@ mod internal {
 
   pub(crate) mod bytecode;
   pub(crate) mod codegen;
+  pub(crate) mod diff;
+  pub(crate) mod fork;
+  pub(crate) mod json;
+  pub(crate) mod pack;
   #[cfg(feature = "serde")]
   pub(crate) mod serde;
+  pub(crate) mod stdlib;
   pub(crate) mod syntax;
   pub(crate) mod value;
   pub(crate) mod vm;
@@ -28,11 +33,16 @@ pub mod span;
 
 pub use beef::lean::Cow;
 
+/// Instruction set metadata for external tooling.
+pub mod op {
+  pub use crate::internal::bytecode::opcode::{AccEffect, Opcode, OpcodeInfo, OperandInfo, OPCODES};
+}
+
 pub mod prelude {
   pub use super::public::*;
   #[cfg(feature = "serde")]
   pub use super::serde::ValueDeserializer;
 }
 
-pub use internal::error::{Error, Result};
+pub use internal::error::{Error, ErrorData, ErrorValue, Result};
 pub use public::*;
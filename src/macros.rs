@@ -46,3 +46,102 @@ macro_rules! __delegate {
     )*
   };
 }
+
+/// Construct an [`Error`][crate::Error] which scripts can inspect in a
+/// `catch` block.
+///
+/// ```rust
+/// use hebi::{Hebi, NativeModule, Scope};
+///
+/// fn open(scope: Scope<'_>) -> hebi::Result<()> {
+///   let name = scope.param::<String>(0)?;
+///   if name.is_empty() {
+///     // code and message
+///     return Err(hebi::error_value!("invalid_name", "file names may not be empty"));
+///   }
+///   // code, message, and data
+///   Err(hebi::error_value!("not_found", "no file named {name}"; { "name" => name }))
+/// }
+///
+/// let module = NativeModule::builder("fs").function("open", open).finish();
+/// let mut hebi = Hebi::new();
+/// hebi.register(&module);
+///
+/// let source = r#"
+/// from fs import open
+/// result := none
+/// try:
+///   open("a.txt")
+/// catch e:
+///   result = e["code"] + ": " + e["message"] + " (" + e["data"]["name"] + ")"
+/// result
+/// "#;
+/// let result = hebi.eval(source).unwrap().to_string();
+/// assert_eq!(result, "not_found: no file named a.txt (a.txt)");
+/// ```
+#[macro_export]
+macro_rules! error_value {
+  ($code:expr, $fmt:literal $(, $arg:expr)* $(; { $($key:literal => $value:expr),* $(,)? })?) => {
+    $crate::Error::Value(
+      $crate::ErrorValue::new($code, format!($fmt $(, $arg)*))
+        $($(.with($key, $value))*)?
+    )
+  };
+}
+
+/// Build statements out of an AST template, with each `{name}` placeholder
+/// filled by the argument called `name`. See [`ast::template`][crate::ast::template].
+///
+/// ```rust
+/// use hebi::ast::{Expr, Module};
+///
+/// let body = hebi::ast_template!(
+///   "{total} := {start}\nfor i in 0..{n}:\n  {total} += i\n{total}",
+///   total = "sum",
+///   start = Expr::int(100),
+///   n = Expr::int(4),
+/// )
+/// .unwrap();
+/// let mut hebi = hebi::Hebi::new();
+/// let chunk = hebi.compile_ast(&Module::new(body)).unwrap();
+/// assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(106));
+/// ```
+#[macro_export]
+macro_rules! ast_template {
+  ($src:expr $(, $name:ident = $value:expr)* $(,)?) => {
+    $crate::ast::template(
+      $src,
+      [$((stringify!($name), $crate::ast::Fragment::from($value))),*],
+    )
+  };
+}
+
+/// Evaluate `code` in `hebi` and check that the result converts into a value
+/// equal to `expected`. Panics with the error report if the script fails.
+/// See [`testing`][crate::testing].
+///
+/// ```rust
+/// let mut hebi = hebi::testing::vm();
+/// hebi::assert_script_eq!(hebi, "1 + 1", 2);
+/// hebi::assert_script_eq!(hebi, "[1, 2].len()", 2);
+/// ```
+#[macro_export]
+macro_rules! assert_script_eq {
+  ($hebi:expr, $code:expr, $expected:expr $(,)?) => {
+    $crate::testing::assert_eval_eq(&mut $hebi, $code, $expected)
+  };
+}
+
+/// Evaluate `code` in `hebi` and check that it fails with an error whose
+/// report contains `message`. See [`testing`][crate::testing].
+///
+/// ```rust
+/// let mut hebi = hebi::testing::vm();
+/// hebi::assert_script_err!(hebi, "none + 1", "operands must have the same type");
+/// ```
+#[macro_export]
+macro_rules! assert_script_err {
+  ($hebi:expr, $code:expr, $message:expr $(,)?) => {
+    $crate::testing::assert_eval_err(&mut $hebi, $code, $message)
+  };
+}
@@ -8,15 +8,22 @@ use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 use futures_util::TryFutureExt;
+use indexmap::{IndexMap, IndexSet};
 
 use self::value::FromValuePack;
 use crate::internal::error::{Error, Result};
 use crate::internal::object::function::Disassembly;
 use crate::internal::object::native::NativeClassInstance;
 use crate::internal::object::{table, Ptr, Type};
+use crate::internal::syntax;
+use crate::internal::syntax::frontmatter::Frontmatter;
+use crate::internal::syntax::pragma::Pragmas;
 use crate::internal::value::Value as OwnedValue;
 use crate::internal::vm;
 use crate::internal::vm::global::{Input, Output};
@@ -25,19 +32,47 @@ use crate::internal::vm::{global, Config, Vm};
 use crate::Cow;
 
 // public API
+pub mod args;
+pub mod ast;
+pub mod callback;
+pub mod cancel;
+pub mod channel;
+pub mod diff;
+pub mod estimate;
+pub mod hover;
+pub mod io;
 pub mod module;
 pub mod object;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod replay;
+pub mod shared;
+pub mod testing;
 pub mod value;
 
 pub use crate::fail;
+pub use crate::internal::object::function::FunctionInfo;
 pub use crate::internal::object::module::ModuleLoader;
 pub use crate::internal::object::native::LocalBoxFuture;
+pub use crate::internal::value::FloatFormat;
+pub use crate::internal::vm::global::{Allocator, Capability, Construct, ImportRewriter, Policy};
+pub use crate::public::args::{Arguments, Kwargs};
+pub use crate::public::callback::Callback;
+pub use crate::public::cancel::CancellationToken;
+pub use crate::public::channel::Channel;
+pub use crate::public::diff::{diff, Diff};
+pub use crate::public::estimate::Estimate;
+pub use crate::public::hover::Hover;
 pub use crate::public::module::NativeModule;
 pub use crate::public::object::list::List;
-pub use crate::public::object::string::Str;
+pub use crate::public::object::string::{Encoding, EncodingError, Str};
 pub use crate::public::object::table::Table;
 pub use crate::public::object::Any;
-pub use crate::public::value::{FromValue, IntoValue, Value};
+#[cfg(feature = "profile")]
+pub use crate::public::profile::Profile;
+pub use crate::public::replay::{NativeCall, Recording};
+pub use crate::public::shared::{SharedGlobals, SharedValue};
+pub use crate::public::value::{Coerced, FromValue, IntoValue, IntoValuePack, Lossy, Value};
 
 #[derive(Default)]
 pub struct Hebi {
@@ -55,23 +90,24 @@ pub struct Hebi {
 // In summary:
 // - User cannot obtain owned `Rc<T>` from the VM
 // - User cannot clone the VM and move it to another thread
+// - `Hebi::fork` copies every object into the new VM instead of sharing it
 //
 // Thus it should be safe even if the reference counts are not atomic, as they
 // will never be accessed from two or more threads at the same time.
 unsafe impl Send for Hebi {}
 
-struct ForceSendFuture<F: Future<Output = Result<OwnedValue>>> {
+struct ForceSendFuture<F: Future> {
   fut: F,
 }
-impl<F: Future<Output = Result<OwnedValue>>> ForceSendFuture<F> {
+impl<F: Future> ForceSendFuture<F> {
   pub unsafe fn new(fut: F) -> Self {
     Self { fut }
   }
 }
-unsafe impl<F: Future<Output = Result<OwnedValue>>> Send for ForceSendFuture<F> {}
+unsafe impl<F: Future> Send for ForceSendFuture<F> {}
 impl<F> Future for ForceSendFuture<F>
 where
-  F: Future<Output = Result<OwnedValue>>,
+  F: Future,
 {
   type Output = F::Output;
 
@@ -89,6 +125,20 @@ pub struct HebiBuilder<M, I, O> {
   module_loader: Option<Box<dyn crate::internal::object::module::ModuleLoader>>,
   input: Option<Box<dyn crate::internal::vm::global::Input>>,
   output: Option<Box<dyn crate::internal::vm::global::Output>>,
+  seed: Option<u64>,
+  shared: Option<SharedGlobals>,
+  float_format: FloatFormat,
+  strict_globals: bool,
+  inline_functions: bool,
+  forbidden: Vec<Construct>,
+  policy: Option<Box<dyn Policy>>,
+  rewrite_imports: Option<Box<ImportRewriter>>,
+  #[cfg(feature = "count_objects")]
+  count_objects: bool,
+  allocator: Option<Arc<dyn Allocator>>,
+  native_log: Option<global::NativeLog>,
+  log_compile_phases: bool,
+  expose_platform: bool,
   __: PhantomData<(M, I, O)>,
 }
 
@@ -104,6 +154,20 @@ impl<I, O> HebiBuilder<(), I, O> {
       module_loader: Some(Box::new(module_loader)),
       input: self.input,
       output: self.output,
+      seed: self.seed,
+      shared: self.shared,
+      float_format: self.float_format,
+      strict_globals: self.strict_globals,
+      inline_functions: self.inline_functions,
+      forbidden: self.forbidden,
+      policy: self.policy,
+      rewrite_imports: self.rewrite_imports,
+      #[cfg(feature = "count_objects")]
+      count_objects: self.count_objects,
+      allocator: self.allocator,
+      native_log: self.native_log,
+      log_compile_phases: self.log_compile_phases,
+      expose_platform: self.expose_platform,
       __: PhantomData,
     }
   }
@@ -113,11 +177,29 @@ pub struct HasInput {
   __: (),
 }
 impl<M, O> HebiBuilder<M, (), O> {
+  /// Allow scripts to read lines from `input` using the `input` builtin.
+  ///
+  /// Without an input, `input` fails, so scripts cannot block waiting for
+  /// input which never arrives.
   pub fn input(self, input: impl Input + 'static) -> HebiBuilder<M, HasInput, O> {
     HebiBuilder {
       module_loader: self.module_loader,
       input: Some(Box::new(input)),
       output: self.output,
+      seed: self.seed,
+      shared: self.shared,
+      float_format: self.float_format,
+      strict_globals: self.strict_globals,
+      inline_functions: self.inline_functions,
+      forbidden: self.forbidden,
+      policy: self.policy,
+      rewrite_imports: self.rewrite_imports,
+      #[cfg(feature = "count_objects")]
+      count_objects: self.count_objects,
+      allocator: self.allocator,
+      native_log: self.native_log,
+      log_compile_phases: self.log_compile_phases,
+      expose_platform: self.expose_platform,
       __: PhantomData,
     }
   }
@@ -132,18 +214,310 @@ impl<M, I> HebiBuilder<M, I, ()> {
       module_loader: self.module_loader,
       input: self.input,
       output: Some(Box::new(output)),
+      seed: self.seed,
+      shared: self.shared,
+      float_format: self.float_format,
+      strict_globals: self.strict_globals,
+      inline_functions: self.inline_functions,
+      forbidden: self.forbidden,
+      policy: self.policy,
+      rewrite_imports: self.rewrite_imports,
+      #[cfg(feature = "count_objects")]
+      count_objects: self.count_objects,
+      allocator: self.allocator,
+      native_log: self.native_log,
+      log_compile_phases: self.log_compile_phases,
+      expose_platform: self.expose_platform,
       __: PhantomData,
     }
   }
 }
 
 impl<M, I, O> HebiBuilder<M, I, O> {
+  /// Seed the `random` module.
+  ///
+  /// Two VMs created with the same seed will produce the same sequence
+  /// of random values, which makes script execution replayable.
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.seed = Some(seed);
+    self
+  }
+
+  /// Back the VM with read-only globals and modules which may be shared
+  /// between many VMs.
+  pub fn shared(mut self, shared: &SharedGlobals) -> Self {
+    self.shared = Some(shared.clone());
+    self
+  }
+
+  /// Set how floats are formatted by `print`, `to_str` and `join`.
+  ///
+  /// This does not affect the `Display` implementation of values, which
+  /// always uses [`FloatFormat::Shortest`].
+  pub fn float_format(mut self, format: FloatFormat) -> Self {
+    self.float_format = format;
+    self
+  }
+
+  /// Make reading a global which is not defined anywhere a compile error.
+  ///
+  /// A global is defined if it exists when the script is compiled, or if
+  /// the script assigns to it. Use [`Global::define`] to declare the globals
+  /// which scripts are allowed to use. Reads which can't be checked at
+  /// compile time still fail at runtime with a `name_error`.
+  pub fn strict_globals(mut self, enabled: bool) -> Self {
+    self.strict_globals = enabled;
+    self
+  }
+
+  /// Replace calls to tiny functions with their bodies, saving the cost of
+  /// the call.
+  ///
+  /// A function is inlined if it is declared at the top level of a module,
+  /// has no decorators and no default parameters, consists of a single
+  /// `return` of a small expression, does not call itself, and is not
+  /// assigned anywhere else in the module. Only calls which come after its
+  /// declaration are inlined.
+  ///
+  /// Inlined calls do not appear in stack traces, and keep calling the
+  /// original function even if the host replaces it afterwards.
+  pub fn inline_functions(mut self, enabled: bool) -> Self {
+    self.inline_functions = enabled;
+    self
+  }
+
+  /// Make using `construct` in a script a compile error, so that scripts
+  /// which would do something the host does not allow are rejected before
+  /// they run.
+  ///
+  /// ```rust
+  /// use hebi::Construct;
+  ///
+  /// let mut hebi = hebi::Hebi::builder()
+  ///   .forbid(Construct::While)
+  ///   .forbid(Construct::Loop)
+  ///   .finish();
+  /// let e = hebi.eval("while true:\n  pass").unwrap_err();
+  /// assert!(e.to_string().contains("`while` loops are not allowed"));
+  /// hebi.eval("for i in 0..3:\n  pass").unwrap();
+  /// ```
+  pub fn forbid(mut self, construct: Construct) -> Self {
+    if !self.forbidden.contains(&construct) {
+      self.forbidden.push(construct);
+    }
+    self
+  }
+
+  /// Check imports, global writes and native calls made by scripts with
+  /// `policy`, which may deny any of them.
+  ///
+  /// ```rust
+  /// struct NoGlobals;
+  ///
+  /// impl hebi::Policy for NoGlobals {
+  ///   fn store_global(&self, name: &str) -> hebi::Result<()> {
+  ///     hebi::fail!("scripts may not define `{name}`")
+  ///   }
+  /// }
+  ///
+  /// let mut hebi = hebi::Hebi::builder().policy(NoGlobals).finish();
+  /// let e = hebi.eval("x := 1").unwrap_err();
+  /// assert!(e.to_string().contains("scripts may not define `x`"));
+  /// ```
+  pub fn policy(mut self, policy: impl Policy + 'static) -> Self {
+    self.policy = Some(Box::new(policy));
+    self
+  }
+
+  /// Rewrite the path of every import with `rewrite` before the module is
+  /// loaded. If it returns `None`, the path is used as written.
+  ///
+  /// This lets scripts keep importing modules by the same names when the
+  /// host moves them around. A module imported by two names which are
+  /// rewritten to the same path is only loaded once. The
+  /// [`policy`][Self::policy] sees the rewritten path, as that is the
+  /// module which is actually loaded.
+  ///
+  /// ```rust
+  /// let json = hebi::NativeModule::builder("host_json")
+  ///   .function("version", |_| 2)
+  ///   .finish();
+  ///
+  /// let mut hebi = hebi::Hebi::builder()
+  ///   .rewrite_imports(|path| match path {
+  ///     "std.json" => Some("host_json".into()),
+  ///     _ => None,
+  ///   })
+  ///   .finish();
+  /// hebi.register(&json);
+  ///
+  /// let value = hebi.eval("from std.json import version\nversion()").unwrap();
+  /// assert_eq!(value.as_int(), Some(2));
+  /// ```
+  pub fn rewrite_imports(
+    mut self,
+    rewrite: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+  ) -> Self {
+    self.rewrite_imports = Some(Box::new(rewrite));
+    self
+  }
+
+  /// Keep count of live objects by type, which can then be read with
+  /// [`Hebi::object_counts`] and [`Hebi::memory_used`].
+  ///
+  /// Counting makes every allocation a little slower, and every object a
+  /// pointer larger, so it is disabled by default. Only the objects are
+  /// counted, not the memory which they own.
+  #[cfg(feature = "count_objects")]
+  pub fn count_objects(mut self, enabled: bool) -> Self {
+    self.count_objects = enabled;
+    self
+  }
+
+  /// Allocate the objects created by the VM with `allocator`, for example to
+  /// track how many objects each VM holds.
+  ///
+  /// Only the fixed-size part of each object comes from `allocator`, so it
+  /// cannot be used as a budget for the memory of a VM. Memory used by the
+  /// VM itself, such as its stack, and by the contents of objects, such as
+  /// the items of a list or the text of a string, still comes from the
+  /// global allocator.
+  ///
+  /// ```rust
+  /// use std::alloc::{GlobalAlloc, Layout, System};
+  /// use std::sync::atomic::{AtomicUsize, Ordering};
+  /// use std::sync::Arc;
+  ///
+  /// struct Tracked(Arc<AtomicUsize>);
+  ///
+  /// unsafe impl hebi::Allocator for Tracked {
+  ///   fn alloc(&self, layout: Layout) -> *mut u8 {
+  ///     self.0.fetch_add(layout.size(), Ordering::Relaxed);
+  ///     unsafe { System.alloc(layout) }
+  ///   }
+  ///
+  ///   unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+  ///     self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+  ///     System.dealloc(ptr, layout)
+  ///   }
+  /// }
+  ///
+  /// let bytes = Arc::new(AtomicUsize::new(0));
+  /// let mut hebi = hebi::Hebi::builder()
+  ///   .allocator(Tracked(bytes.clone()))
+  ///   .finish();
+  /// hebi.eval("v := [1, 2, 3]").unwrap();
+  /// assert!(bytes.load(Ordering::Relaxed) > 0);
+  /// ```
+  pub fn allocator(mut self, allocator: impl Allocator) -> Self {
+    self.allocator = Some(Arc::new(allocator));
+    self
+  }
+
+  /// Write how long each phase of compiling a script took to the error
+  /// stream of the VM's output, one line per script or imported module.
+  ///
+  /// Enabled by default if the environment variable
+  /// `HEBI_LOG_COMPILE_PHASES` is set to anything other than `0`. The same
+  /// timings are available from [`Chunk::timings`].
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::builder()
+  ///   .output(hebi::io::StringWriter::new())
+  ///   .log_compile_phases(true)
+  ///   .finish();
+  /// hebi.eval("1 + 1").unwrap();
+  /// let output = hebi.global().output().as_any().downcast_ref::<hebi::io::StringWriter>().cloned();
+  /// assert!(output.unwrap().starts_with("compiled `__main__`: metadata "));
+  /// ```
+  pub fn log_compile_phases(mut self, enabled: bool) -> Self {
+    self.log_compile_phases = enabled;
+    self
+  }
+
+  /// Let scripts read the name of the operating system the VM runs on from
+  /// `sys.platform`.
+  ///
+  /// Disabled by default, because it tells scripts something about the host
+  /// which they may not otherwise find out. Without it, `sys.platform` is
+  /// `none`.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::builder().expose_platform(true).finish();
+  /// let platform = hebi.eval("import sys\nsys.platform").unwrap();
+  /// assert_eq!(platform.to_string(), std::env::consts::OS);
+  /// ```
+  pub fn expose_platform(mut self, enabled: bool) -> Self {
+    self.expose_platform = enabled;
+    self
+  }
+
+  /// Record every call that scripts make to a native function, with its
+  /// arguments and result, which can then be read with
+  /// [`Hebi::recording`].
+  ///
+  /// A recording can be replayed with [`HebiBuilder::replay_native_calls`],
+  /// for example to reproduce a run which depended on the time, on random
+  /// numbers provided by the host, or on the responses of other services.
+  pub fn record_native_calls(mut self, enabled: bool) -> Self {
+    self.native_log = enabled.then(|| global::NativeLog::Record(Vec::new()));
+    self
+  }
+
+  /// Serve calls to native functions from `recording` instead of calling
+  /// them, so that a recorded run can be repeated exactly.
+  ///
+  /// The calls have to be made in the same order and with the same
+  /// arguments as they were recorded in. Otherwise, the call which differs
+  /// fails, because the run is no longer the one which was recorded.
+  ///
+  /// ```rust
+  /// use std::time::{SystemTime, UNIX_EPOCH};
+  ///
+  /// let clock = hebi::NativeModule::builder("clock")
+  ///   .function("now", |_| {
+  ///     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as f64
+  ///   })
+  ///   .finish();
+  /// let script = "from clock import now\nnow()";
+  ///
+  /// let mut hebi = hebi::Hebi::builder().record_native_calls(true).finish();
+  /// hebi.register(&clock);
+  /// let recorded = hebi.eval(script).unwrap().as_float();
+  /// let recording = hebi.recording().unwrap();
+  ///
+  /// let mut hebi = hebi::Hebi::builder()
+  ///   .replay_native_calls(recording)
+  ///   .finish();
+  /// hebi.register(&clock);
+  /// assert_eq!(hebi.eval(script).unwrap().as_float(), recorded);
+  /// ```
+  pub fn replay_native_calls(mut self, recording: Recording) -> Self {
+    let calls = recording.calls.into_iter().collect();
+    self.native_log = Some(global::NativeLog::Replay(calls));
+    self
+  }
+
   pub fn finish(self) -> Hebi {
     Hebi {
       vm: Vm::with_config(Config {
         module_loader: self.module_loader,
         input: self.input,
         output: self.output,
+        seed: self.seed,
+        shared: self.shared,
+        float_format: self.float_format,
+        strict_globals: self.strict_globals,
+        inline_functions: self.inline_functions,
+        forbidden: self.forbidden,
+        policy: self.policy,
+        rewrite_imports: self.rewrite_imports,
+        #[cfg(feature = "count_objects")]
+        count_objects: self.count_objects,
+        allocator: self.allocator,
+        native_log: self.native_log,
+        log_compile_phases: self.log_compile_phases,
+        expose_platform: self.expose_platform,
       }),
     }
   }
@@ -159,10 +533,28 @@ impl Hebi {
       module_loader: None,
       input: None,
       output: None,
+      seed: None,
+      shared: None,
+      float_format: FloatFormat::default(),
+      strict_globals: false,
+      inline_functions: false,
+      forbidden: Vec::new(),
+      policy: None,
+      rewrite_imports: None,
+      #[cfg(feature = "count_objects")]
+      count_objects: false,
+      allocator: None,
+      native_log: None,
+      log_compile_phases: vm::log_compile_phases_from_env(),
+      expose_platform: false,
       __: PhantomData,
     }
   }
 
+  /// Evaluate `code` in the global module.
+  ///
+  /// Native functions cannot call this while a script is running, see
+  /// [`Scope::eval`] instead.
   pub fn eval<'cx, 'src>(&'cx mut self, code: &'src str) -> Result<Value<'cx>>
   where
     'src: 'cx,
@@ -181,6 +573,120 @@ impl Hebi {
     unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
   }
 
+  /// Evaluate `code` with `vars` bound to variables, and convert the result
+  /// to `T`.
+  ///
+  /// The variables are local to this evaluation, so they shadow any globals
+  /// with the same names, and are gone once it finishes.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// let passed = hebi
+  ///   .eval_with::<bool>("score > threshold", [("score", 10), ("threshold", 5)])
+  ///   .unwrap();
+  /// assert!(passed);
+  /// assert!(hebi.global().get("score").is_none());
+  /// ```
+  pub fn eval_with<'cx, T>(
+    &'cx mut self,
+    code: &str,
+    vars: impl IntoIterator<Item = (&'cx str, impl IntoValue<'cx>)>,
+  ) -> Result<T>
+  where
+    T: FromValue<'cx>,
+  {
+    pollster::block_on(self.eval_with_async(code, vars))
+  }
+
+  pub fn eval_with_async<'cx, T>(
+    &'cx mut self,
+    code: &str,
+    vars: impl IntoIterator<Item = (&'cx str, impl IntoValue<'cx>)>,
+  ) -> impl Future<Output = Result<T>> + Send + 'cx
+  where
+    T: FromValue<'cx>,
+  {
+    let global = Global::<'cx> {
+      inner: self.vm.root.global.clone(),
+      lifetime: PhantomData,
+    };
+    let vars = self.bind_vars(vars);
+    let code = code.to_string();
+    let fut = async move {
+      let value = self.vm.eval_with(&code, &vars?).await?;
+      T::from_value(unsafe { value.bind_raw::<'cx>() }, global)
+    };
+    unsafe { ForceSendFuture::new(fut) }
+  }
+
+  /// Render a text template with `vars` bound to variables.
+  ///
+  /// `{{ expr }}` is replaced by the value of `expr`, and `{% if %}` and
+  /// `{% for %}` blocks work like the statements they stand for. The
+  /// template is compiled to a function, so expressions may be anything a
+  /// script could evaluate.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// let text = hebi
+  ///   .render(
+  ///     "{% for i in 0..n %}{{ i }}{% if i < n - 1 %}, {% endif %}{% endfor %}",
+  ///     [("n", 3)],
+  ///   )
+  ///   .unwrap();
+  /// assert_eq!(text, "0, 1, 2");
+  /// ```
+  pub fn render<'cx>(
+    &'cx mut self,
+    template: &str,
+    vars: impl IntoIterator<Item = (&'cx str, impl IntoValue<'cx>)>,
+  ) -> Result<String> {
+    pollster::block_on(self.render_async(template, vars))
+  }
+
+  pub fn render_async<'cx>(
+    &'cx mut self,
+    template: &str,
+    vars: impl IntoIterator<Item = (&'cx str, impl IntoValue<'cx>)>,
+  ) -> impl Future<Output = Result<String>> + Send + 'cx {
+    let vars = self.bind_vars(vars);
+    let template = template.to_string();
+    let fut = async move { self.vm.render(&template, &vars?).await };
+    unsafe { ForceSendFuture::new(fut) }
+  }
+
+  fn bind_vars<'cx>(
+    &self,
+    vars: impl IntoIterator<Item = (&'cx str, impl IntoValue<'cx>)>,
+  ) -> Result<Vec<(&'cx str, OwnedValue)>> {
+    let global = Global::<'cx> {
+      inner: self.vm.root.global.clone(),
+      lifetime: PhantomData,
+    };
+    vars
+      .into_iter()
+      .map(|(name, value)| Ok((name, value.into_value(global.clone())?.unbind())))
+      .collect()
+  }
+
+  /// Run the script at `path` as a module, and return the module.
+  ///
+  /// The module is named after the file, so `scripts/config.hebi` may be
+  /// imported by other scripts as `config`. Errors raised by the script
+  /// report their location in the file, e.g. `scripts/config.hebi:12:5`.
+  pub fn eval_file<'cx>(&'cx mut self, path: impl AsRef<Path>) -> Result<Value<'cx>> {
+    pollster::block_on(self.eval_file_async(path))
+  }
+
+  pub fn eval_file_async<'cx>(
+    &'cx mut self,
+    path: impl AsRef<Path>,
+  ) -> impl Future<Output = Result<Value<'cx>>> + Send + 'cx {
+    let path = path.as_ref().to_path_buf();
+    let fut = async move { self.vm.eval_file(&path).await };
+    unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
+  }
+
   pub fn compile<'cx>(&self, code: &str) -> Result<Chunk<'cx>> {
     self.vm.compile(code).map(|chunk| Chunk {
       inner: chunk,
@@ -188,6 +694,141 @@ impl Hebi {
     })
   }
 
+  /// Compile a module which was built with [`ast`] instead of parsed.
+  ///
+  /// Since the module was never parsed, it is checked for the mistakes the
+  /// parser would have caught first, such as `break` outside of a loop.
+  pub fn compile_ast<'cx>(&self, module: &ast::Module) -> Result<Chunk<'cx>> {
+    let module = module.check()?;
+    self.vm.compile_ast(module).map(|chunk| Chunk {
+      inner: chunk,
+      lifetime: PhantomData,
+    })
+  }
+
+  /// Compile `files`, which are pairs of a module name and its source code,
+  /// together into a bundle. The first file is the entry point.
+  ///
+  /// When the bundle is run, the files import each other by name without
+  /// going through the module loader. Imports of modules which are not in
+  /// the bundle are loaded as usual. Syntax errors are reported for all of
+  /// the files at once, not just the first one which has any.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::builder().output(hebi::io::StringWriter::new()).finish();
+  /// let bundle = hebi
+  ///   .compile_project(&[
+  ///     ("main", "from util import double\nprint double(21)"),
+  ///     ("util", "fn double(x):\n  return x * 2"),
+  ///   ])
+  ///   .unwrap();
+  /// hebi.run_bundle(bundle).unwrap();
+  /// let output = hebi.global().output().as_any().downcast_ref::<hebi::io::StringWriter>().cloned();
+  /// assert_eq!(output.as_deref(), Some("42\n"));
+  /// ```
+  pub fn compile_project<'cx>(&self, files: &[(&str, &str)]) -> Result<Bundle<'cx>> {
+    self.vm.compile_project(files).map(|bundle| Bundle {
+      inner: bundle,
+      lifetime: PhantomData,
+    })
+  }
+
+  /// Read a bundle from a `.hebipack` file written by
+  /// [`Bundle::to_bytes`], without compiling it again.
+  ///
+  /// This fails before any of the code is read if the pack was written in
+  /// another [format][Bundle::FORMAT_VERSION] or by another version of
+  /// hebi, or if its code needs any
+  /// [capabilities][Bundle::capabilities] which this VM does not support,
+  /// such as a construct which is [forbidden][HebiBuilder::forbid].
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// let bundle = hebi
+  ///   .compile_project(&[("main", "import util\nprint util.greet()"), ("util", "fn greet():\n  return \"hi\"")])
+  ///   .unwrap();
+  /// let bytes = bundle.to_bytes(false).unwrap();
+  ///
+  /// let mut hebi = hebi::Hebi::builder().output(hebi::io::StringWriter::new()).finish();
+  /// let bundle = unsafe { hebi.load_bundle(&bytes) }.unwrap();
+  /// hebi.run_bundle(bundle).unwrap();
+  /// let output = hebi.global().output().as_any().downcast_ref::<hebi::io::StringWriter>().cloned();
+  /// assert_eq!(output.as_deref(), Some("hi\n"));
+  /// ```
+  ///
+  /// # Safety
+  ///
+  /// The bytecode in the pack is run as is, so `bytes` must have been
+  /// written by [`Bundle::to_bytes`] and not modified since. Only load packs
+  /// from sources which are trusted to the same degree as native code.
+  pub unsafe fn load_bundle<'cx>(&self, bytes: &[u8]) -> Result<Bundle<'cx>> {
+    self.vm.load_bundle(bytes).map(|bundle| Bundle {
+      inner: bundle,
+      lifetime: PhantomData,
+    })
+  }
+
+  /// Create a [`Channel`] which holds at most `capacity` values, for passing
+  /// values between the host and scripts.
+  pub fn channel<T>(&self, capacity: usize) -> Channel<T> {
+    Channel::new(capacity)
+  }
+
+  /// Estimate how much work `code` does without running it.
+  ///
+  /// This compiles the script, but does not keep the result around, so it
+  /// is cheap enough to call on every script a host receives before deciding
+  /// whether to run it.
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// let estimate = hebi
+  ///   .estimate(
+  ///     r#"
+  /// for i in 0..10:
+  ///   for j in 0..10:
+  ///     print i * j
+  /// "#,
+  ///   )
+  ///   .unwrap();
+  /// assert!(estimate.has_loops);
+  /// assert_eq!(estimate.max_nesting, 2);
+  /// assert_eq!(estimate.max_loop_nesting, 2);
+  /// ```
+  pub fn estimate(&self, code: &str) -> Result<Estimate> {
+    estimate::estimate(self.vm.global.clone(), code)
+  }
+
+  /// Find what is known without running `code` about the innermost
+  /// expression which contains the byte `offset`, for example to show it
+  /// when hovering over the expression in an editor.
+  ///
+  /// This resolves variables the same way as compiling the script does, and
+  /// knows the values of literals and of variables which were assigned one,
+  /// as well as the functions and classes the script declares. It does not
+  /// follow control flow, so a variable is assumed to hold whatever was
+  /// last assigned to it before `offset` in the source. Fails only if `code`
+  /// cannot be parsed.
+  ///
+  /// ```rust
+  /// use hebi::hover::{Binding, HoverKind, Literal};
+  ///
+  /// let hebi = hebi::Hebi::new();
+  /// let code = "limit := 10\nprint limit";
+  /// let hover = hebi.hover(code, code.rfind("limit").unwrap()).unwrap().unwrap();
+  /// assert_eq!(
+  ///   hover.kind,
+  ///   HoverKind::Variable {
+  ///     name: "limit".into(),
+  ///     binding: Binding::Global,
+  ///     value: Some(Box::new(HoverKind::Literal(Literal::Int(10)))),
+  ///   }
+  /// );
+  /// ```
+  pub fn hover(&self, code: &str, offset: usize) -> Result<Option<Hover>> {
+    hover::hover(self.vm.global.clone(), code, offset)
+  }
+
   pub fn run<'cx>(&'cx mut self, chunk: Chunk<'cx>) -> Result<Value<'cx>> {
     pollster::block_on(self.run_async(chunk))
   }
@@ -200,6 +841,239 @@ impl Hebi {
     unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
   }
 
+  /// Run the entry point of `bundle` as a module, and return the module.
+  pub fn run_bundle<'cx>(&'cx mut self, bundle: Bundle<'cx>) -> Result<Value<'cx>> {
+    pollster::block_on(self.run_bundle_async(bundle))
+  }
+
+  pub fn run_bundle_async<'cx>(
+    &'cx mut self,
+    bundle: Bundle<'cx>,
+  ) -> impl Future<Output = Result<Value<'cx>>> + Send + 'cx {
+    let fut = async move { self.vm.run_bundle(&bundle.inner).await };
+    unsafe { ForceSendFuture::new(fut) }.map_ok(|value| unsafe { value.bind_raw::<'cx>() })
+  }
+
+  /// Call the global function `name` with `args`.
+  pub fn call<'cx, A>(&'cx mut self, name: &str, args: A) -> Result<Value<'cx>>
+  where
+    A: IntoValuePack<'cx> + Send + 'cx,
+  {
+    pollster::block_on(self.call_async(name, args))
+  }
+
+  pub fn call_async<'cx, A>(
+    &'cx mut self,
+    name: &str,
+    args: A,
+  ) -> impl Future<Output = Result<Value<'cx>>> + Send + 'cx
+  where
+    A: IntoValuePack<'cx> + Send + 'cx,
+  {
+    self
+      .call_batch_async(name, std::iter::once(args))
+      .map_ok(|mut values| values.pop().unwrap())
+  }
+
+  /// Call the global function `name` once for each item in `args`, and
+  /// return the results in the same order.
+  ///
+  /// This is much faster than calling [`Hebi::call`] in a loop, because the
+  /// function is only looked up once, and the argument registers are reused
+  /// between calls. Stops at the first error.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("fn score(a, b):\n  return a * 10 + b\n").unwrap();
+  /// let scores = hebi
+  ///   .call_batch("score", (0..3).map(|i| (i, 1)))
+  ///   .unwrap()
+  ///   .iter()
+  ///   .map(|v| v.as_int().unwrap())
+  ///   .collect::<Vec<_>>();
+  /// assert_eq!(scores, [1, 11, 21]);
+  /// ```
+  pub fn call_batch<'cx, I>(&'cx mut self, name: &str, args: I) -> Result<Vec<Value<'cx>>>
+  where
+    I: IntoIterator,
+    I::IntoIter: Send + 'cx,
+    I::Item: IntoValuePack<'cx>,
+  {
+    pollster::block_on(self.call_batch_async(name, args))
+  }
+
+  pub fn call_batch_async<'cx, I>(
+    &'cx mut self,
+    name: &str,
+    args: I,
+  ) -> impl Future<Output = Result<Vec<Value<'cx>>>> + Send + 'cx
+  where
+    I: IntoIterator,
+    I::IntoIter: Send + 'cx,
+    I::Item: IntoValuePack<'cx>,
+  {
+    let callable = self.vm.get_callable(name);
+    self.call_batch_callable_async(callable, args)
+  }
+
+  fn call_batch_callable_async<'cx, I>(
+    &'cx mut self,
+    callable: Result<Ptr<crate::internal::object::Any>>,
+    args: I,
+  ) -> impl Future<Output = Result<Vec<Value<'cx>>>> + Send + 'cx
+  where
+    I: IntoIterator,
+    I::IntoIter: Send + 'cx,
+    I::Item: IntoValuePack<'cx>,
+  {
+    let global = Global::<'cx> {
+      inner: self.vm.root.global.clone(),
+      lifetime: PhantomData,
+    };
+    let mut rows = args.into_iter();
+    let fut = async move {
+      let values = self
+        .vm
+        .call_batch(callable?, <I::Item>::len(), move |out| {
+          rows
+            .next()
+            .map(|row| row.into_value_pack(global.clone(), out))
+        })
+        .await?;
+      Ok(
+        values
+          .into_iter()
+          .map(|value| unsafe { value.bind_raw::<'cx>() })
+          .collect(),
+      )
+    };
+    unsafe { ForceSendFuture::new(fut) }
+  }
+
+  /// Create a thread which calls the global function `name` with `args`.
+  ///
+  /// Spawned threads only run during [`Hebi::run_until_idle`], which
+  /// switches to the next thread whenever the current one executes a
+  /// `yield` statement. This lets many scripts advance in lockstep without
+  /// any OS threads.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::builder().output(hebi::io::StringWriter::new()).finish();
+  /// hebi
+  ///   .eval(
+  ///     r#"
+  /// fn worker(name, steps):
+  ///   for i in 0..steps:
+  ///     print name, i
+  ///     yield
+  /// "#,
+  ///   )
+  ///   .unwrap();
+  /// hebi.spawn("worker", ("a".to_string(), 2)).unwrap();
+  /// hebi.spawn("worker", ("b".to_string(), 3)).unwrap();
+  /// hebi.run_until_idle().unwrap();
+  /// let output = hebi.global().output().as_any().downcast_ref::<hebi::io::StringWriter>().cloned();
+  /// assert_eq!(output.as_deref(), Some("a 0\nb 0\na 1\nb 1\nb 2\n"));
+  /// ```
+  pub fn spawn<'cx, A>(&'cx mut self, name: &str, args: A) -> Result<()>
+  where
+    A: IntoValuePack<'cx>,
+  {
+    let callable = self.vm.get_callable(name)?;
+    let global = Global::<'cx> {
+      inner: self.vm.root.global.clone(),
+      lifetime: PhantomData,
+    };
+    let mut values = Vec::new();
+    values.resize_with(A::len(), OwnedValue::none);
+    args.into_value_pack(global, &mut values)?;
+    self.vm.spawn(callable, &values)
+  }
+
+  /// Run spawned threads until all of them have finished.
+  ///
+  /// Stops at the first error. The thread which raised it is discarded,
+  /// and the rest continue during the next call.
+  pub fn run_until_idle(&mut self) -> Result<()> {
+    pollster::block_on(self.run_until_idle_async())
+  }
+
+  pub fn run_until_idle_async(&mut self) -> impl Future<Output = Result<()>> + Send + '_ {
+    unsafe { ForceSendFuture::new(self.vm.run_until_idle()) }
+  }
+
+  /// Run spawned threads for at most `steps` instructions, and return
+  /// `true` once all of them have finished.
+  ///
+  /// Execution picks up where it stopped during the next call, so a host
+  /// can give scripts a fixed budget every frame, or stop after every
+  /// instruction to show the progress of a script. `usize::MAX` steps is
+  /// the same as [`Hebi::run_until_idle`].
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi
+  ///   .eval(
+  ///     r#"
+  /// fn sum(n):
+  ///   total := 0
+  ///   for i in 0..n:
+  ///     total += i
+  ///   return total
+  /// "#,
+  ///   )
+  ///   .unwrap();
+  /// hebi.spawn("sum", (100,)).unwrap();
+  /// let mut frames = 1;
+  /// while !hebi.step(50).unwrap() {
+  ///   frames += 1;
+  /// }
+  /// assert!(frames > 1);
+  /// ```
+  pub fn step(&mut self, steps: usize) -> Result<bool> {
+    pollster::block_on(self.step_async(steps))
+  }
+
+  pub fn step_async(&mut self, steps: usize) -> impl Future<Output = Result<bool>> + Send + '_ {
+    unsafe { ForceSendFuture::new(self.vm.step(steps)) }
+  }
+
+  /// Create a VM which starts out with a copy of the globals and loaded
+  /// modules of this one, and runs independently of it from then on.
+  ///
+  /// This makes it possible to set up a script once, then try out several
+  /// continuations of it, or to keep a checkpoint to go back to. This is
+  /// not copy-on-write: all objects reachable from the VM are copied, so the
+  /// cost is proportional to everything the VM holds, including the
+  /// functions of every loaded module. Only the bytecode of functions and
+  /// the contents of static strings are shared with the fork.
+  ///
+  /// Some things can't be copied:
+  /// - The fork has no input, and its output is collected in a
+  ///   [`StringWriter`][crate::io::StringWriter].
+  /// - Forking fails if the VM holds instances of native classes, which
+  ///   can't be cloned, or iterators, or if spawned threads are still
+  ///   running.
+  /// - Native functions, the module loader, the policy, channels and
+  ///   cancellation tokens are shared with the fork, so state held by them
+  ///   on the host's side is shared as well.
+  /// - [`Callback`]s only work with the VM they were created by.
+  /// - Memoized functions start out with empty caches.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.eval("counts := [0]").unwrap();
+  /// let mut fork = hebi.fork().unwrap();
+  /// fork.eval("counts[0] += 1").unwrap();
+  /// assert_eq!(fork.eval("counts[0]").unwrap().as_int(), Some(1));
+  /// assert_eq!(hebi.eval("counts[0]").unwrap().as_int(), Some(0));
+  /// ```
+  pub fn fork(&self) -> Result<Hebi> {
+    Ok(Hebi {
+      vm: self.vm.fork()?,
+    })
+  }
+
   pub fn global(&self) -> Global {
     Global {
       inner: self.vm.root.global.clone(),
@@ -207,9 +1081,154 @@ impl Hebi {
     }
   }
 
+  /// Names of all loaded modules, including native ones, in the order they
+  /// were loaded.
+  ///
+  /// Globals can be listed with [`Global::entries`].
+  pub fn modules(&self) -> Vec<String> {
+    self
+      .vm
+      .global
+      .module_names()
+      .iter()
+      .map(|name| name.as_str().to_string())
+      .collect()
+  }
+
+  /// The number of live objects of each type, or `None` unless the VM was
+  /// built with [`HebiBuilder::count_objects`].
+  ///
+  /// An object is live from the moment it is allocated until the last
+  /// reference to it is dropped, so this is what a long-running VM has
+  /// accumulated, including objects kept alive by reference cycles.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::builder().count_objects(true).finish();
+  /// hebi.eval("items := [[1], [2], [3]]").unwrap();
+  /// let counts = hebi.object_counts().unwrap();
+  /// let lists = counts.iter().find(|(ty, _)| *ty == "List").unwrap().1;
+  /// assert_eq!(lists, 4);
+  /// ```
+  #[cfg(feature = "count_objects")]
+  pub fn object_counts(&self) -> Option<Vec<(&'static str, usize)>> {
+    self.vm.global.object_counts()
+  }
+
+  /// The number of bytes taken up by live objects, or `None` unless the VM
+  /// was built with [`HebiBuilder::count_objects`].
+  ///
+  /// This only measures the objects themselves, not what they own, so a long
+  /// list counts as much as an empty one, and neither does it include the
+  /// memory of the VM itself. It is meant for spotting growth, not for
+  /// precise accounting or for enforcing a memory limit.
+  #[cfg(feature = "count_objects")]
+  pub fn memory_used(&self) -> Option<usize> {
+    self.vm.global.memory_used()
+  }
+
+  /// The calls to native functions made so far, or `None` unless the VM was
+  /// built with [`HebiBuilder::record_native_calls`].
+  pub fn recording(&self) -> Option<Recording> {
+    self.vm.global.recording()
+  }
+
+  /// Where the VM has spent its time so far.
+  ///
+  /// Calls to native functions are timed individually, so a host can tell
+  /// whether a slow script is slow because of its own code or because of the
+  /// functions the host registered. Calls made by the script are counted by
+  /// the call site they were made from, along with how many different
+  /// functions each site called, see [`Profile::megamorphic`]. Only
+  /// available with the `profile` feature.
+  ///
+  /// ```rust
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.register(
+  ///   &hebi::NativeModule::builder("host")
+  ///     .function("work", |_| {
+  ///       std::thread::sleep(std::time::Duration::from_millis(1));
+  ///     })
+  ///     .finish(),
+  /// );
+  /// hebi
+  ///   .eval("from host import work\nfor _ in 0..3:\n  work()")
+  ///   .unwrap();
+  /// let work = &hebi.profile().natives[0];
+  /// assert_eq!((work.name.as_str(), work.calls), ("work", 3));
+  /// assert!(work.total >= std::time::Duration::from_millis(3));
+  /// ```
+  #[cfg(feature = "profile")]
+  pub fn profile(&self) -> Profile {
+    Profile {
+      natives: self.vm.global.native_timings(),
+      call_sites: self.vm.global.call_sites(),
+    }
+  }
+
   pub fn register(&mut self, module: &NativeModule) {
     self.vm.register(module)
   }
+
+  /// Register a hook which converts script values into `T`.
+  ///
+  /// The hook is called whenever a value is converted into `T` and the
+  /// built-in conversion fails, for example when a native function takes a
+  /// [`String`] and is passed an int. Types without a built-in conversion
+  /// are taken as [`Coerced<T>`]. There is one hook per type in each VM,
+  /// so registering a second one for `T` fails.
+  ///
+  /// The hook is not called again for the same type while it is running.
+  /// If it panics, the panic unwinds out of the native call and the
+  /// [`Hebi::eval`] which made it, like a panic in a native function.
+  ///
+  /// ```rust
+  /// use hebi::{Coerced, FromValue, Hebi, NativeModule, Scope};
+  ///
+  /// #[derive(Debug, PartialEq)]
+  /// struct EntityId(u32);
+  ///
+  /// fn entity_name(scope: Scope<'_>) -> hebi::Result<String> {
+  ///   let Coerced(EntityId(id)) = scope.param(0)?;
+  ///   Ok(format!("entity #{id}"))
+  /// }
+  ///
+  /// let mut hebi = Hebi::new();
+  /// hebi
+  ///   .register_coercion(|value, global| {
+  ///     let name = String::from_value(value, global)?;
+  ///     match name.strip_prefix("entity:") {
+  ///       Some(id) => Ok(EntityId(id.parse().map_err(hebi::Error::user)?)),
+  ///       None => hebi::fail!("`{name}` is not an entity"),
+  ///     }
+  ///   })
+  ///   .unwrap();
+  /// hebi.register(
+  ///   &NativeModule::builder("world")
+  ///     .function("entity_name", entity_name)
+  ///     .finish(),
+  /// );
+  /// let name = hebi
+  ///   .eval("from world import entity_name\nentity_name(\"entity:7\")")
+  ///   .unwrap();
+  /// assert_eq!(name.to_string(), "entity #7");
+  /// ```
+  pub fn register_coercion<T: 'static>(
+    &mut self,
+    f: impl for<'cx> Fn(Value<'cx>, Global<'cx>) -> Result<T> + Send + Sync + 'static,
+  ) -> Result<()> {
+    let f: value::CoercionFn<T> = Box::new(f);
+    if !self
+      .vm
+      .global
+      .register_coercion(std::any::TypeId::of::<T>(), Arc::new(f))
+    {
+      fail!(
+        "a coercion hook for `{}` is already registered",
+        std::any::type_name::<T>()
+      );
+    }
+    Ok(())
+  }
 }
 
 impl Debug for Hebi {
@@ -218,6 +1237,48 @@ impl Debug for Hebi {
   }
 }
 
+/// Modules compiled together by [`Hebi::compile_project`].
+#[derive(Clone)]
+pub struct Bundle<'cx> {
+  pub(crate) inner: vm::Bundle,
+  pub(crate) lifetime: PhantomData<&'cx ()>,
+}
+
+impl<'cx> Bundle<'cx> {
+  /// The version of the `.hebipack` format written by this version of hebi,
+  /// which is the only one it can load. Packs written by other versions of
+  /// hebi can't be loaded either, even in the same format.
+  pub const FORMAT_VERSION: u32 = crate::internal::pack::FORMAT_VERSION;
+
+  /// The names of the modules in the bundle, starting with the entry point.
+  pub fn modules(&self) -> Vec<String> {
+    self.inner.modules().map(String::from).collect()
+  }
+
+  /// What the code of the bundle needs from a VM to run there, which is
+  /// written into its pack.
+  ///
+  /// ```rust
+  /// use hebi::{Capability, Construct};
+  ///
+  /// let hebi = hebi::Hebi::new();
+  /// let bundle = hebi.compile_project(&[("main", "for i in 0..3:\n  print i")]).unwrap();
+  /// assert_eq!(bundle.capabilities(), [Capability::Construct(Construct::For)]);
+  /// ```
+  pub fn capabilities(&self) -> Vec<Capability> {
+    self.inner.capabilities()
+  }
+
+  /// Write the bundle into a `.hebipack` file, which holds the compiled code
+  /// of its modules, see [`Hebi::load_bundle`].
+  ///
+  /// Without `include_sources`, the pack is smaller, but errors raised by
+  /// its modules are reported without their location in the source.
+  pub fn to_bytes(&self, include_sources: bool) -> Result<Vec<u8>> {
+    self.inner.to_bytes(include_sources)
+  }
+}
+
 #[derive(Clone)]
 pub struct Chunk<'cx> {
   pub(crate) inner: vm::Chunk,
@@ -228,6 +1289,219 @@ impl<'cx> Chunk<'cx> {
   pub fn disassemble(&self) -> Disassembly {
     self.inner.disassemble()
   }
+
+  /// The frontmatter of the compiled script.
+  pub fn metadata(&self) -> &Metadata {
+    &self.inner.metadata
+  }
+
+  /// How long each phase of compiling the script took.
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// let chunk = hebi.compile("fn f(x):\n  return x + 1\nf(1)").unwrap();
+  /// let timings = chunk.timings();
+  /// assert_eq!(timings.total(), timings.metadata + timings.parse + timings.emit);
+  /// ```
+  pub fn timings(&self) -> CompileTimings {
+    self.inner.timings
+  }
+
+  /// The constants used by the script and every function defined in it.
+  ///
+  /// Integers are stored in the instructions which use them, so they do not
+  /// appear here.
+  pub fn constants(&self) -> Vec<Constant> {
+    let mut constants = vec![];
+    self.inner.descriptor().visit(None, &mut |function, _| {
+      constants.extend(function.constants().iter().filter_map(|constant| {
+        use crate::internal::value::constant::Constant as C;
+        match constant {
+          C::String(v) => Some(Constant::String(v.as_str().to_string())),
+          C::Float(v) => Some(Constant::Float(v.value())),
+          C::Function(v) => Some(Constant::Function(v.name.as_str().to_string())),
+          C::Class(v) => Some(Constant::Class(v.name.as_str().to_string())),
+          #[cfg(feature = "decimal")]
+          C::Decimal(v) => Some(Constant::Decimal(v.0)),
+          C::Reserved | C::Offset(_) => None,
+        }
+      }))
+    });
+    constants
+  }
+
+  /// The functions and methods defined in the script, in the order they
+  /// appear in it.
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// let chunk = hebi
+  ///   .compile(
+  ///     r#"
+  /// fn greet(name, greeting = "hello"):
+  ///   print greeting, name
+  /// "#,
+  ///   )
+  ///   .unwrap();
+  /// let functions = chunk.functions();
+  /// assert_eq!(functions[0].name, "greet");
+  /// assert_eq!(functions[0].params, ["name", "greeting"]);
+  /// assert_eq!((functions[0].min_args, functions[0].max_args), (1, 2));
+  /// ```
+  pub fn functions(&self) -> Vec<FunctionInfo> {
+    let main = self.inner.descriptor();
+    let mut functions = vec![];
+    main.visit(None, &mut |function, class_name| {
+      if std::ptr::eq(function, main) {
+        return;
+      }
+      functions.push(FunctionInfo {
+        name: function.name.as_str().to_string(),
+        class: class_name.map(|name| name.as_str().to_string()),
+        params: (function.signature.params.iter())
+          .map(|param| param.name.as_str().to_string())
+          .collect(),
+        min_args: function.params.min as usize,
+        max_args: function.params.max as usize,
+        is_generator: function.is_generator,
+        span: function.span,
+      });
+    });
+    functions
+  }
+
+  /// The names of the globals the script reads or writes, including the ones
+  /// it defines itself, in the order they first appear in it.
+  ///
+  /// This can be used to check which of the host's globals a script uses
+  /// without running it:
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// let chunk = hebi.compile("print env[\"HOME\"]").unwrap();
+  /// assert_eq!(chunk.referenced_globals(), ["env"]);
+  /// ```
+  pub fn referenced_globals(&self) -> Vec<String> {
+    let mut globals = IndexSet::new();
+    self.inner.descriptor().visit(None, &mut |function, _| {
+      globals.extend((function.globals().iter()).map(|name| name.as_str().to_string()));
+    });
+    globals.into_iter().collect()
+  }
+}
+
+/// A constant from a compiled script, see [`Chunk::constants`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+  String(String),
+  Float(f64),
+  /// A function, by name.
+  Function(String),
+  /// A class, by name.
+  Class(String),
+  #[cfg(feature = "decimal")]
+  Decimal(rust_decimal::Decimal),
+}
+
+/// How long each phase of compiling a script took, see [`Chunk::timings`].
+///
+/// Tokens are lexed as the parser asks for them, and names are resolved
+/// while bytecode is emitted, so lexing is part of `parse`, and resolving
+/// is part of `emit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompileTimings {
+  /// Reading the frontmatter and pragmas.
+  pub metadata: Duration,
+  /// Lexing and parsing the script into an AST. Zero for a script compiled
+  /// from an AST built by the host.
+  pub parse: Duration,
+  /// Resolving names and emitting bytecode.
+  pub emit: Duration,
+}
+
+impl CompileTimings {
+  pub fn total(&self) -> Duration {
+    self.metadata + self.parse + self.emit
+  }
+}
+
+impl Display for CompileTimings {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "metadata {:?}, parse {:?}, emit {:?}",
+      self.metadata, self.parse, self.emit
+    )
+  }
+}
+
+/// The `key: value` pairs in a script's frontmatter block, and the pragmas
+/// which follow it:
+///
+/// ```text
+/// #!/usr/bin/env hebi
+/// ---
+/// owner: platform-team
+/// permissions: net
+/// ---
+/// # pragma: strict
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+  entries: IndexMap<String, String>,
+  pragmas: Vec<&'static str>,
+}
+
+impl Metadata {
+  /// Read the frontmatter and pragmas of `src`, without parsing the rest of
+  /// the script.
+  ///
+  /// ```rust
+  /// let src = "---\nowner: ops\n---\n# pragma: no-import\nprint \"hi\"\n";
+  /// let metadata = hebi::Metadata::read(src).unwrap();
+  /// assert_eq!(metadata.get("owner"), Some("ops"));
+  /// assert_eq!(metadata.pragmas(), ["no-import"]);
+  /// ```
+  pub fn read(src: &str) -> Result<Self> {
+    let (frontmatter, pragmas) = syntax::parse_header(src).map_err(Error::Syntax)?;
+    Ok(Self::new(&frontmatter, &pragmas))
+  }
+
+  pub(crate) fn new(frontmatter: &Frontmatter<'_>, pragmas: &Pragmas) -> Self {
+    Self {
+      entries: frontmatter
+        .entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect(),
+      pragmas: pragmas.names(),
+    }
+  }
+
+  /// The pragmas at the top of the script, such as `strict` or `no-import`.
+  pub fn pragmas(&self) -> &[&'static str] {
+    &self.pragmas
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.entries.get(key).map(|v| v.as_str())
+  }
+
+  /// Iterate over the entries in the order they were written in.
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self
+      .entries
+      .iter()
+      .map(|(key, value)| (key.as_str(), value.as_str()))
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
 }
 
 #[derive(Clone)]
@@ -248,12 +1522,40 @@ impl<'cx> Global<'cx> {
     self.inner.set(key.unbind(), value.unbind());
   }
 
+  /// Define the global `name`, making it available to scripts.
+  pub fn define(&self, name: &str, value: impl IntoValue<'cx>) -> Result<()> {
+    let value = value.into_value(self.clone())?;
+    self
+      .inner
+      .set(self.inner.intern(name.to_string()), value.unbind());
+    Ok(())
+  }
+
   pub fn print(&self, f: impl Display) -> Result<()> {
-    write!(&mut self.inner.io().output.borrow_mut(), "{f}").map_err(Error::user)
+    self.write(format!("{f}"))
   }
 
   pub fn println(&self, f: impl Display) -> Result<()> {
-    writeln!(&mut self.inner.io().output.borrow_mut(), "{f}").map_err(Error::user)
+    self.write(format!("{f}\n"))
+  }
+
+  fn write(&self, s: String) -> Result<()> {
+    let mut output = self.inner.io().output.borrow_mut();
+    output.write(s.as_bytes()).map_err(Error::user)
+  }
+
+  /// Print to the error stream of the output.
+  pub fn eprint(&self, f: impl Display) -> Result<()> {
+    self.write_err(format!("{f}"))
+  }
+
+  pub fn eprintln(&self, f: impl Display) -> Result<()> {
+    self.write_err(format!("{f}\n"))
+  }
+
+  fn write_err(&self, s: String) -> Result<()> {
+    let mut output = self.inner.io().output.borrow_mut();
+    output.write_err(s.as_bytes()).map_err(Error::user)
   }
 
   pub fn output(&mut self) -> RefMut<'_, dyn Output> {
@@ -262,8 +1564,12 @@ impl<'cx> Global<'cx> {
     })
   }
 
-  pub fn input(&mut self) -> RefMut<'_, dyn Input> {
-    RefMut::map(self.inner.io().input.borrow_mut(), |input| input.as_mut())
+  /// The input configured with [`HebiBuilder::input`], if any.
+  pub fn input(&mut self) -> Option<RefMut<'_, dyn Input>> {
+    RefMut::filter_map(self.inner.io().input.borrow_mut(), |input| {
+      input.as_deref_mut()
+    })
+    .ok()
   }
 
   pub fn entries<'a>(&'a self) -> GlobalEntries<'a, 'cx> {
@@ -332,6 +1638,12 @@ impl<'cx> Scope<'cx> {
     self.args.count
   }
 
+  /// Access the arguments with arity and type checks which produce
+  /// descriptive errors.
+  pub fn args(&self) -> Arguments<'_, 'cx> {
+    Arguments::new(self)
+  }
+
   pub fn params<T: FromValuePack<'cx>>(&self) -> Result<T::Output> {
     let stack = unsafe { self.thread.stack.as_ref() };
     let range = self.args.start..self.args.start + self.args.count;
@@ -364,6 +1676,38 @@ impl<'cx> Scope<'cx> {
       .map(|value| unsafe { value.bind_raw::<'cx>() })
   }
 
+  /// Evaluate `code` from inside of a native function, like [`Hebi::eval`].
+  ///
+  /// A native function cannot use the [`Hebi`] which is calling it, because
+  /// that is borrowed for as long as the script runs. This runs `code` on
+  /// top of the calling script instead, with the same globals, and returns
+  /// to the native function once it finishes. Errors raised by `code` are
+  /// returned, so they can either be handled or propagated to the script.
+  ///
+  /// ```rust
+  /// let module = hebi::NativeModule::builder("host")
+  ///   .async_function("run", |mut scope| async move {
+  ///     let code = scope.param::<String>(0)?;
+  ///     scope.eval(&code).await
+  ///   })
+  ///   .finish();
+  ///
+  /// let mut hebi = hebi::Hebi::new();
+  /// hebi.register(&module);
+  /// let value = hebi
+  ///   .eval("from host import run\nx := 20\nrun(\"x + 1\") * 2")
+  ///   .unwrap();
+  /// assert_eq!(value.as_int(), Some(42));
+  /// ```
+  pub async fn eval(&mut self, code: &str) -> Result<Value<'cx>> {
+    let chunk = vm::compile_with_vars(&self.thread.global, code, &[])?;
+    self
+      .thread
+      .call(chunk.main.into_any(), &[])
+      .await
+      .map(|value| unsafe { value.bind_raw::<'cx>() })
+  }
+
   pub(crate) fn consume_args(&mut self, n: usize) {
     self.args.start += n;
     self.args.count -= n;
@@ -399,6 +1743,16 @@ impl<'cx> Global<'cx> {
   }
 }
 
+impl<'cx> Global<'cx> {
+  /// Decode a JSON document into a value.
+  ///
+  /// Objects become tables, and arrays become lists.
+  pub fn json_to_value(&self, json: &str) -> Result<Value<'cx>> {
+    let value = crate::internal::json::from_json_str(&self.inner, json)?;
+    Ok(unsafe { value.bind_raw::<'cx>() })
+  }
+}
+
 impl<'cx> Scope<'cx> {
   pub fn new_instance<T: Send + 'static>(&self, value: T) -> Result<Value<'cx>> {
     self.global().new_instance(value)
@@ -409,6 +1763,13 @@ impl Hebi {
   pub fn new_instance<T: Send + 'static>(&self, value: T) -> Result<Value> {
     self.global().new_instance(value)
   }
+
+  /// Decode a JSON document into a value.
+  ///
+  /// Objects become tables, and arrays become lists.
+  pub fn json_to_value(&self, json: &str) -> Result<Value<'_>> {
+    self.global().json_to_value(json)
+  }
 }
 
 pub struct This<'cx, T: Send> {
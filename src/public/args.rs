@@ -0,0 +1,211 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::internal::error::Result;
+use crate::public::object::function::Function;
+use crate::public::object::ObjectRef;
+use crate::public::{Bind, FromValue, Global, List, Scope, Str, Table, Unbind, Value};
+
+/// Validated access to the arguments of a native function call.
+///
+/// Unlike [`Scope::param`], every getter takes the name of the parameter,
+/// which is used in error messages.
+///
+/// ```rust
+/// use hebi::{Hebi, NativeModule, Scope};
+///
+/// fn greet(scope: Scope<'_>) -> hebi::Result<String> {
+///   let args = scope.args().arity(1..=3)?;
+///   let name = args.get::<String>(0, "name")?;
+///   let greeting = args.get_or(1, "greeting", "hello".to_string())?;
+///   let options = args.kwargs(2, "options")?;
+///   options.deny_unknown(&["shout"])?;
+///   let message = format!("{greeting} {name}");
+///   if options.get_or("shout", false)? {
+///     Ok(message.to_uppercase())
+///   } else {
+///     Ok(message)
+///   }
+/// }
+///
+/// let module = NativeModule::builder("greeter")
+///   .function("greet", greet)
+///   .finish();
+/// let mut hebi = Hebi::new();
+/// hebi.register(&module);
+///
+/// let source = r#"
+/// from greeter import greet
+/// greet("a", "hi", {shout: true})
+/// "#;
+/// assert_eq!(hebi.eval(source).unwrap().to_string(), "HI A");
+///
+/// let error = hebi
+///   .eval("from greeter import greet\ngreet(1)")
+///   .unwrap_err();
+/// assert!(error
+///   .to_string()
+///   .contains("invalid argument `name`: value is not a `str` (got `int`)"));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Arguments<'a, 'cx> {
+  scope: &'a Scope<'cx>,
+}
+
+impl<'a, 'cx> Arguments<'a, 'cx> {
+  pub(crate) fn new(scope: &'a Scope<'cx>) -> Self {
+    Self { scope }
+  }
+
+  pub fn len(&self) -> usize {
+    self.scope.num_args()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Fail unless the number of arguments is within `range`.
+  pub fn arity(self, range: impl RangeBounds<usize>) -> Result<Self> {
+    let len = self.len();
+    if range.contains(&len) {
+      return Ok(self);
+    }
+
+    let min = match range.start_bound() {
+      Bound::Included(&n) => n,
+      Bound::Excluded(&n) => n + 1,
+      Bound::Unbounded => 0,
+    };
+    let max = match range.end_bound() {
+      Bound::Included(&n) => Some(n),
+      Bound::Excluded(&n) => Some(n.saturating_sub(1)),
+      Bound::Unbounded => None,
+    };
+    match max {
+      Some(max) if min == max => fail!("expected {min} {}, got {len}", plural(min)),
+      Some(max) => fail!("expected {min} to {max} arguments, got {len}"),
+      None => fail!("expected at least {min} {}, got {len}", plural(min)),
+    }
+  }
+
+  /// Get the argument at `index`, which must be present.
+  pub fn get<T: FromValue<'cx>>(&self, index: usize, name: &str) -> Result<T> {
+    match self.value(index) {
+      Some(value) => convert(value, name, self.scope),
+      None => fail!("missing argument `{name}`"),
+    }
+  }
+
+  /// Get the argument at `index`, or `None` if it is missing or `none`.
+  pub fn get_opt<T: FromValue<'cx>>(&self, index: usize, name: &str) -> Result<Option<T>> {
+    match self.value(index) {
+      Some(value) if !value.is_none() => convert(value, name, self.scope).map(Some),
+      _ => Ok(None),
+    }
+  }
+
+  /// Get the argument at `index`, or `default` if it is missing or `none`.
+  pub fn get_or<T: FromValue<'cx>>(&self, index: usize, name: &str, default: T) -> Result<T> {
+    Ok(self.get_opt(index, name)?.unwrap_or(default))
+  }
+
+  /// Treat the argument at `index`, called `name`, as a table of keyword
+  /// arguments.
+  ///
+  /// Scripts have no dedicated syntax for keyword arguments, so they are
+  /// passed as a trailing table, e.g. `fetch(url, {retries: 3})`. If the
+  /// argument is missing or `none`, there are no keyword arguments.
+  pub fn kwargs(&self, index: usize, name: &str) -> Result<Kwargs<'cx>> {
+    let table = self.get_opt::<Table<'cx>>(index, name)?;
+    Ok(Kwargs {
+      table,
+      scope: self.scope.clone(),
+    })
+  }
+
+  fn value(&self, index: usize) -> Option<Value<'cx>> {
+    if index >= self.len() {
+      return None;
+    }
+    let stack = unsafe { self.scope.thread.stack.as_ref() };
+    let value = stack.regs.get(self.scope.args.start + index).cloned()?;
+    Some(unsafe { value.bind_raw::<'cx>() })
+  }
+}
+
+/// Keyword arguments, see [`Arguments::kwargs`].
+pub struct Kwargs<'cx> {
+  table: Option<Table<'cx>>,
+  scope: Scope<'cx>,
+}
+
+impl<'cx> Kwargs<'cx> {
+  /// Get the keyword argument `name`, or `None` if it is missing or `none`.
+  pub fn get<T: FromValue<'cx>>(&self, name: &str) -> Result<Option<T>> {
+    match self.table.as_ref().and_then(|table| table.get(name)) {
+      Some(value) if !value.is_none() => convert(value, name, &self.scope).map(Some),
+      _ => Ok(None),
+    }
+  }
+
+  /// Get the keyword argument `name`, or `default` if it is missing or
+  /// `none`.
+  pub fn get_or<T: FromValue<'cx>>(&self, name: &str, default: T) -> Result<T> {
+    Ok(self.get(name)?.unwrap_or(default))
+  }
+
+  /// Fail if there is a keyword argument which is not in `known`.
+  pub fn deny_unknown(&self, known: &[&str]) -> Result<()> {
+    let Some(table) = &self.table else {
+      return Ok(());
+    };
+    for key in table.keys() {
+      if !known.contains(&key.as_str()) {
+        fail!("unknown keyword argument `{key}`");
+      }
+    }
+    Ok(())
+  }
+}
+
+fn convert<'cx, T: FromValue<'cx>>(value: Value<'cx>, name: &str, scope: &Scope<'cx>) -> Result<T> {
+  let type_name = type_name(&value, scope.global());
+  T::from_value(value, scope.global())
+    .map_err(|e| error!("invalid argument `{name}`: {e} (got `{type_name}`)").into())
+}
+
+/// The name of the type of `value`, spelled the same way as
+/// [`FromValue::type_name`], e.g. `str` rather than `String`.
+fn type_name<'cx>(value: &Value<'cx>, global: Global<'cx>) -> &'static str {
+  fn is<'cx, T: ObjectRef<'cx>>(value: &Value<'cx>, global: &Global<'cx>) -> bool {
+    value.as_object::<T>(global.clone()).is_some()
+  }
+
+  if value.is_int() {
+    i32::type_name()
+  } else if value.is_float() {
+    f64::type_name()
+  } else if value.is_bool() {
+    bool::type_name()
+  } else if value.is_none() {
+    <()>::type_name()
+  } else if is::<Str>(value, &global) {
+    Str::type_name()
+  } else if is::<List>(value, &global) {
+    List::type_name()
+  } else if is::<Table>(value, &global) {
+    Table::type_name()
+  } else if is::<Function>(value, &global) {
+    Function::type_name()
+  } else {
+    value.clone().unbind().type_name()
+  }
+}
+
+fn plural(n: usize) -> &'static str {
+  if n == 1 {
+    "argument"
+  } else {
+    "arguments"
+  }
+}
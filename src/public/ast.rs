@@ -0,0 +1,1106 @@
+//! Building modules in code instead of parsing them from source.
+//!
+//! Hosts which translate something else into scripts, such as a visual
+//! scripting graph or a rules DSL, can build the module directly and
+//! compile it with [`Hebi::compile_ast`][crate::Hebi::compile_ast], instead
+//! of generating source code and then parsing it again.
+//!
+//! Every node has an empty span by default. Giving nodes a span with `at`
+//! makes compile errors point at that span, which hosts can use to map
+//! errors back to whatever the node was built from.
+//!
+//! ```rust
+//! use hebi::ast::{BinaryOp, Expr, Module, Param, Stmt};
+//!
+//! let module = Module::new([
+//!   Stmt::func(
+//!     "add",
+//!     [Param::new("a"), Param::optional("b", Expr::int(1))],
+//!     [Stmt::return_(Some(Expr::binary(
+//!       BinaryOp::Add,
+//!       Expr::var("a"),
+//!       Expr::var("b"),
+//!     )))],
+//!   ),
+//!   Stmt::expr(Expr::var("add").call([Expr::int(2)])),
+//! ]);
+//!
+//! let mut hebi = hebi::Hebi::new();
+//! let chunk = hebi.compile_ast(&module).unwrap();
+//! let value = hebi.run(chunk).unwrap();
+//! assert_eq!(value.as_int(), Some(3));
+//! ```
+//!
+//! Only the parts of the language which code generators commonly need can
+//! be built node by node, so there are no classes, imports, `try` or `with`
+//! statements. Those can still be written in a [`template`], which is
+//! source code with placeholders for the parts that are generated.
+
+use crate::internal::error::Result;
+use crate::internal::syntax::{self, ast, lexer, SyntaxError};
+use crate::internal::vm::global::Global;
+use crate::span::{Span, SpannedError};
+use crate::{Cow, Error};
+
+/// A module built out of statements, see the [module docs][self].
+pub struct Module {
+  inner: ast::Module<'static>,
+}
+
+impl Module {
+  pub fn new(body: impl IntoIterator<Item = Stmt>) -> Self {
+    Self {
+      inner: ast::Module {
+        body: stmts(body),
+        ..Default::default()
+      },
+    }
+  }
+
+  /// Check the things which the parser would otherwise check, such as
+  /// `break` only appearing inside of loops.
+  pub(crate) fn check(&self) -> Result<&ast::Module<'static>> {
+    let mut checker = Checker::default();
+    checker.block(&self.inner.body);
+    if checker.errors.is_empty() {
+      Ok(&self.inner)
+    } else {
+      Err(Error::Syntax(SyntaxError::new(checker.errors)))
+    }
+  }
+}
+
+pub struct Stmt(ast::Stmt<'static>);
+
+impl Stmt {
+  /// Evaluate `expr` and discard the result. At the end of a module, the
+  /// result is what the module evaluates to instead.
+  pub fn expr(expr: Expr) -> Self {
+    Self(ast::expr_stmt(expr.0))
+  }
+
+  /// `name := value`
+  pub fn decl(name: impl Into<String>, value: Expr) -> Self {
+    Self(ast::var_stmt(ident(name), value.0))
+  }
+
+  /// `target = value`
+  pub fn assign(target: Target, value: Expr) -> Self {
+    let target = match target {
+      Target::Var(name) => ast::expr_get_var(ident(name)),
+      Target::Field(object, name) => ast::expr_get_field(Span::default(), object.0, ident(name)),
+      Target::Index(object, key) => ast::expr_get_index(Span::default(), object.0, key.0),
+    };
+    let assign = ast::assign(target, ast::AssignKind::Op(None), value.0);
+    Self(assign.expect("all targets are assignable"))
+  }
+
+  /// `print values...`
+  pub fn print(values: impl IntoIterator<Item = Expr>) -> Self {
+    Self(ast::print_stmt(Span::default(), exprs(values)))
+  }
+
+  /// `if cond: body`, with each of `branches` being an `elif`, and `default`
+  /// the `else` branch.
+  pub fn if_(
+    branches: impl IntoIterator<Item = (Expr, Vec<Stmt>)>,
+    default: Option<Vec<Stmt>>,
+  ) -> Self {
+    let branches = branches
+      .into_iter()
+      .map(|(cond, body)| ast::branch(cond.0, stmts(body)))
+      .collect();
+    Self(ast::if_stmt(Span::default(), branches, default.map(stmts)))
+  }
+
+  /// `while cond: body`
+  pub fn while_(cond: Expr, body: impl IntoIterator<Item = Stmt>) -> Self {
+    Self(ast::while_loop_stmt(Span::default(), cond.0, stmts(body)))
+  }
+
+  /// `loop: body`
+  pub fn loop_(body: impl IntoIterator<Item = Stmt>) -> Self {
+    Self(ast::loop_stmt(Span::default(), stmts(body)))
+  }
+
+  /// `for item in start..end: body`
+  pub fn for_range(
+    item: impl Into<String>,
+    start: Expr,
+    end: Expr,
+    body: impl IntoIterator<Item = Stmt>,
+  ) -> Self {
+    let range = ast::IterRange {
+      start: start.0,
+      end: end.0,
+      inclusive: false,
+      step: None,
+    };
+    Self(ast::for_loop_stmt(
+      Span::default(),
+      ident(item),
+      ast::ForIter::Range(range),
+      stmts(body),
+    ))
+  }
+
+  /// `for item in iter: body`
+  pub fn for_in(item: impl Into<String>, iter: Expr, body: impl IntoIterator<Item = Stmt>) -> Self {
+    Self(ast::for_loop_stmt(
+      Span::default(),
+      ident(item),
+      ast::ForIter::Expr(iter.0),
+      stmts(body),
+    ))
+  }
+
+  pub fn break_() -> Self {
+    Self(ast::break_stmt(Span::default()))
+  }
+
+  pub fn continue_() -> Self {
+    Self(ast::continue_stmt(Span::default()))
+  }
+
+  pub fn return_(value: Option<Expr>) -> Self {
+    Self(ast::return_stmt(Span::default(), value.map(|v| v.0)))
+  }
+
+  /// `yield value`, which turns the function it is in into a generator.
+  pub fn yield_(value: Option<Expr>) -> Self {
+    let value = value.map(|v| v.0);
+    Self(ast::yield_stmt(crate::span::Spanned::new(
+      Span::default(),
+      ast::Yield { value },
+    )))
+  }
+
+  pub fn pass() -> Self {
+    Self(ast::pass_stmt(Span::default()))
+  }
+
+  pub fn throw(value: Expr) -> Self {
+    Self(ast::throw_stmt(Span::default(), value.0))
+  }
+
+  /// `fn name(params...): body`
+  pub fn func(
+    name: impl Into<String>,
+    params: impl IntoIterator<Item = Param>,
+    body: impl IntoIterator<Item = Stmt>,
+  ) -> Self {
+    let params = ast::Params {
+      has_self: false,
+      pos: params
+        .into_iter()
+        .map(|param| ast::Param {
+          name: ident(param.name),
+          default: param.default.map(|v| v.0),
+        })
+        .collect(),
+    };
+    let body = stmts(body);
+    let has_yield = has_yield(&body);
+    Self(ast::func_stmt(
+      Span::default(),
+      ast::func(ident(name), params, body, has_yield),
+    ))
+  }
+
+  /// Set the span which compile errors in this statement point at. The
+  /// expression of an expression statement or an assignment gets the span
+  /// too, unless it already has one.
+  pub fn at(mut self, span: impl Into<Span>) -> Self {
+    let span = span.into();
+    self.0.span = span;
+    if let ast::StmtKind::Expr(expr) = &mut *self.0 {
+      if expr.span == Span::default() {
+        expr.span = span;
+      }
+    }
+    self
+  }
+}
+
+#[derive(Clone)]
+pub struct Expr(ast::Expr<'static>);
+
+impl Expr {
+  pub fn none() -> Self {
+    Self(ast::lit::none(Span::default()))
+  }
+
+  pub fn bool(value: bool) -> Self {
+    literal(ast::Literal::Bool(value))
+  }
+
+  pub fn int(value: i32) -> Self {
+    literal(ast::Literal::Int(value))
+  }
+
+  /// A float, which must not be NaN.
+  pub fn float(value: f64) -> Self {
+    literal(ast::Literal::Float(value))
+  }
+
+  pub fn str(value: impl Into<String>) -> Self {
+    literal(ast::Literal::String(Cow::owned(value.into())))
+  }
+
+  pub fn list(items: impl IntoIterator<Item = Expr>) -> Self {
+    Self(ast::expr_list(Span::default(), exprs(items)))
+  }
+
+  pub fn table(entries: impl IntoIterator<Item = (Expr, Expr)>) -> Self {
+    let entries = entries.into_iter().map(|(k, v)| (k.0, v.0)).collect();
+    Self(ast::expr_table(Span::default(), entries))
+  }
+
+  /// A variable, or a global if no variable is named `name`.
+  pub fn var(name: impl Into<String>) -> Self {
+    Self(ast::expr_get_var(ident(name)))
+  }
+
+  /// `self.name`
+  pub fn field(self, name: impl Into<String>) -> Self {
+    Self(ast::expr_get_field(Span::default(), self.0, ident(name)))
+  }
+
+  /// `self[key]`
+  pub fn index(self, key: Expr) -> Self {
+    Self(ast::expr_get_index(Span::default(), self.0, key.0))
+  }
+
+  /// `self(args...)`
+  pub fn call(self, args: impl IntoIterator<Item = Expr>) -> Self {
+    Self(ast::expr_call(Span::default(), self.0, exprs(args)))
+  }
+
+  pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+    Self(ast::expr_binary(Span::default(), op.into(), lhs.0, rhs.0))
+  }
+
+  pub fn unary(op: UnaryOp, rhs: Expr) -> Self {
+    Self(ast::expr_unary(Span::default(), op.into(), rhs.0))
+  }
+
+  /// Set the span which compile errors in this expression point at.
+  pub fn at(mut self, span: impl Into<Span>) -> Self {
+    self.0.span = span.into();
+    self
+  }
+}
+
+/// Something which can be assigned to with [`Stmt::assign`].
+pub enum Target {
+  /// A variable, or a global if no variable is named like this.
+  Var(String),
+  /// `object.name`
+  Field(Expr, String),
+  /// `object[key]`
+  Index(Expr, Expr),
+}
+
+/// A function parameter, see [`Stmt::func`].
+#[derive(Clone)]
+pub struct Param {
+  name: String,
+  default: Option<Expr>,
+}
+
+impl Param {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      default: None,
+    }
+  }
+
+  /// A parameter which is `default` when it is not passed. Optional
+  /// parameters must come after all of the required ones.
+  pub fn optional(name: impl Into<String>, default: Expr) -> Self {
+    Self {
+      name: name.into(),
+      default: Some(default),
+    }
+  }
+}
+
+/// Parse `src` into statements, with each `{name}` placeholder in it
+/// replaced by the fragment called `name` in `fills`.
+///
+/// Placeholders may stand for names, such as those of variables, functions
+/// and parameters, for expressions, for statements and for parameter lists,
+/// depending on where they appear and what they are filled with. In a table
+/// key, a placeholder stands for the key itself, like `[key]` does. Every
+/// placeholder must be filled, and every fill must be used.
+///
+/// A template may contain `return`, `break` and the like outside of a
+/// function or loop, because it may be spliced into one. That is checked
+/// when the module is compiled. `self` and `super` may only appear in the
+/// methods of a class declared by the same template.
+///
+/// The [`ast_template!`][crate::ast_template] macro is a shorthand for
+/// calling this function.
+///
+/// ```rust
+/// use hebi::ast::{self, Fragment, Module, Param, Stmt};
+///
+/// let func = ast::template(
+///   "fn {name}({params}):\n  {body}",
+///   [
+///     ("name", Fragment::from("double")),
+///     ("params", Fragment::from(vec![Param::new("v")])),
+///     (
+///       "body",
+///       Fragment::from(ast::template("return {v} * 2", [("v", "v".into())]).unwrap()),
+///     ),
+///   ],
+/// )
+/// .unwrap();
+/// let call = ast::template_expr("double(5)", []).unwrap();
+///
+/// let mut body = func;
+/// body.push(Stmt::expr(call));
+/// let mut hebi = hebi::Hebi::new();
+/// let chunk = hebi.compile_ast(&Module::new(body)).unwrap();
+/// assert_eq!(hebi.run(chunk).unwrap().as_int(), Some(10));
+/// ```
+///
+/// The template is parsed every time, and the statements do not borrow from
+/// it, so it may be built at runtime.
+pub fn template<'a>(
+  src: &str,
+  fills: impl IntoIterator<Item = (&'a str, Fragment)>,
+) -> Result<Vec<Stmt>> {
+  let module = syntax::parse_ast_template(Global::default(), src).map_err(Error::Syntax)?;
+  let mut filler = Filler {
+    slots: fills
+      .into_iter()
+      .map(|(name, fragment)| (name.to_string(), Some(fragment), false))
+      .collect(),
+    errors: Vec::new(),
+  };
+  for (name, fragment, _) in filler.slots.iter() {
+    if let Some(Fragment::Name(value)) = fragment {
+      if !lexer::is_ident(value) {
+        filler.errors.push(SpannedError::new(
+          format!("`{{{name}}}` is filled with `{value}`, which is not a valid name"),
+          Span::default(),
+        ));
+      }
+    }
+  }
+  let mut body = module.body;
+  filler.block(&mut body);
+  for (name, _, used) in filler.slots.iter() {
+    if !used {
+      filler.errors.push(SpannedError::new(
+        format!("`{{{name}}}` does not appear in the template"),
+        Span::default(),
+      ));
+    }
+  }
+  if !filler.errors.is_empty() {
+    return Err(Error::Syntax(SyntaxError::new(filler.errors)));
+  }
+  Ok(body.into_iter().map(Stmt).collect())
+}
+
+/// Like [`template`], but for a template which is a single expression.
+pub fn template_expr<'a>(
+  src: &str,
+  fills: impl IntoIterator<Item = (&'a str, Fragment)>,
+) -> Result<Expr> {
+  let mut body = template(src, fills)?;
+  if body.len() == 1 {
+    if let ast::StmtKind::Expr(expr) = body.pop().unwrap().0.into_inner() {
+      return Ok(Expr(*expr));
+    }
+  }
+  Err(Error::Syntax(SyntaxError::new(vec![SpannedError::new(
+    "the template is not a single expression",
+    Span::default(),
+  )])))
+}
+
+/// What a placeholder in a [`template`] is replaced with.
+pub enum Fragment {
+  /// A name, which may also be used in place of an expression or a
+  /// statement, as the variable with that name. It must be an identifier,
+  /// and not a keyword.
+  Name(String),
+  /// An expression, which may also be used in place of a statement.
+  Expr(Expr),
+  /// Statements, which may only be used once, because they cannot be
+  /// copied.
+  Stmts(Vec<Stmt>),
+  /// Parameters of a function, which may also be empty.
+  Params(Vec<Param>),
+}
+
+impl From<&str> for Fragment {
+  fn from(value: &str) -> Self {
+    Fragment::Name(value.to_string())
+  }
+}
+
+impl From<String> for Fragment {
+  fn from(value: String) -> Self {
+    Fragment::Name(value)
+  }
+}
+
+impl From<Expr> for Fragment {
+  fn from(value: Expr) -> Self {
+    Fragment::Expr(value)
+  }
+}
+
+impl From<Stmt> for Fragment {
+  fn from(value: Stmt) -> Self {
+    Fragment::Stmts(vec![value])
+  }
+}
+
+impl From<Vec<Stmt>> for Fragment {
+  fn from(value: Vec<Stmt>) -> Self {
+    Fragment::Stmts(value)
+  }
+}
+
+impl From<Param> for Fragment {
+  fn from(value: Param) -> Self {
+    Fragment::Params(vec![value])
+  }
+}
+
+impl From<Vec<Param>> for Fragment {
+  fn from(value: Vec<Param>) -> Self {
+    Fragment::Params(value)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Div,
+  Mul,
+  Rem,
+  Pow,
+  Eq,
+  Neq,
+  More,
+  MoreEq,
+  Less,
+  LessEq,
+  And,
+  Or,
+  /// `a ?? b`
+  Maybe,
+  Is,
+  In,
+}
+
+impl From<BinaryOp> for ast::BinaryOp {
+  fn from(value: BinaryOp) -> Self {
+    match value {
+      BinaryOp::Add => ast::BinaryOp::Add,
+      BinaryOp::Sub => ast::BinaryOp::Sub,
+      BinaryOp::Div => ast::BinaryOp::Div,
+      BinaryOp::Mul => ast::BinaryOp::Mul,
+      BinaryOp::Rem => ast::BinaryOp::Rem,
+      BinaryOp::Pow => ast::BinaryOp::Pow,
+      BinaryOp::Eq => ast::BinaryOp::Eq,
+      BinaryOp::Neq => ast::BinaryOp::Neq,
+      BinaryOp::More => ast::BinaryOp::More,
+      BinaryOp::MoreEq => ast::BinaryOp::MoreEq,
+      BinaryOp::Less => ast::BinaryOp::Less,
+      BinaryOp::LessEq => ast::BinaryOp::LessEq,
+      BinaryOp::And => ast::BinaryOp::And,
+      BinaryOp::Or => ast::BinaryOp::Or,
+      BinaryOp::Maybe => ast::BinaryOp::Maybe,
+      BinaryOp::Is => ast::BinaryOp::Is,
+      BinaryOp::In => ast::BinaryOp::In,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+  Plus,
+  Minus,
+  Not,
+}
+
+impl From<UnaryOp> for ast::UnaryOp {
+  fn from(value: UnaryOp) -> Self {
+    match value {
+      UnaryOp::Plus => ast::UnaryOp::Plus,
+      UnaryOp::Minus => ast::UnaryOp::Minus,
+      UnaryOp::Not => ast::UnaryOp::Not,
+    }
+  }
+}
+
+fn ident(name: impl Into<String>) -> ast::Ident<'static> {
+  ast::Ident::new(Span::default(), Cow::owned(name.into()))
+}
+
+fn literal(value: ast::Literal<'static>) -> Expr {
+  Expr(ast::Expr::new(
+    Span::default(),
+    ast::ExprKind::Literal(Box::new(value)),
+  ))
+}
+
+fn stmts(body: impl IntoIterator<Item = Stmt>) -> Vec<ast::Stmt<'static>> {
+  body.into_iter().map(|v| v.0).collect()
+}
+
+fn exprs(values: impl IntoIterator<Item = Expr>) -> Vec<ast::Expr<'static>> {
+  values.into_iter().map(|v| v.0).collect()
+}
+
+/// Whether `body` yields, not counting nested functions.
+fn has_yield(body: &[ast::Stmt]) -> bool {
+  body.iter().any(|stmt| match &**stmt {
+    ast::StmtKind::Ctrl(ctrl) => matches!(&**ctrl, ast::Ctrl::Yield(_)),
+    ast::StmtKind::If(v) => {
+      v.branches.iter().any(|branch| has_yield(&branch.body))
+        || v.default.as_deref().is_some_and(has_yield)
+    }
+    ast::StmtKind::Loop(v) => match &**v {
+      ast::Loop::For(v) => has_yield(&v.body),
+      ast::Loop::While(v) => has_yield(&v.body),
+      ast::Loop::Infinite(v) => has_yield(&v.body),
+      ast::Loop::Repeat(v) => has_yield(&v.body),
+    },
+    ast::StmtKind::Try(v) => {
+      has_yield(&v.body) || v.catches.iter().any(|catch| has_yield(&catch.body))
+    }
+    ast::StmtKind::With(v) => has_yield(&v.body),
+    _ => false,
+  })
+}
+
+/// The name of the placeholder `ident` is, if it is one.
+fn hole<'a>(ident: &'a ast::Ident) -> Option<&'a str> {
+  ident.as_str().strip_prefix('{')?.strip_suffix('}')
+}
+
+/// `expr`, which is put where a placeholder at `span` was. Expressions
+/// without a span of their own take the placeholder's span, so that errors
+/// about them point somewhere into the template.
+fn placed(expr: Expr, span: Span) -> ast::Expr<'static> {
+  match expr.0.span == Span::default() {
+    true => expr.at(span).0,
+    false => expr.0,
+  }
+}
+
+/// Replaces the placeholders in a template, see [`template`].
+struct Filler {
+  /// Each fragment is taken out of its slot once it is used, unless it
+  /// can be copied.
+  slots: Vec<(String, Option<Fragment>, bool)>,
+  errors: Vec<SpannedError>,
+}
+
+impl Filler {
+  fn error(&mut self, message: String, span: Span) {
+    self.errors.push(SpannedError::new(message, span));
+  }
+
+  /// The fragment for the placeholder `ident`, or `None` if `ident` is not
+  /// a placeholder, or there is no fragment for it.
+  fn take(&mut self, ident: &ast::Ident) -> Option<Fragment> {
+    let name = hole(ident)?;
+    let Some((_, slot, used)) = self.slots.iter_mut().find(|(n, ..)| n == name) else {
+      self.error(format!("no fragment for `{{{name}}}`"), ident.span);
+      return None;
+    };
+    *used = true;
+    let fragment = match slot {
+      Some(Fragment::Name(v)) => Some(Fragment::Name(v.clone())),
+      Some(Fragment::Expr(v)) => Some(Fragment::Expr(v.clone())),
+      Some(Fragment::Params(v)) => Some(Fragment::Params(v.clone())),
+      Some(Fragment::Stmts(_)) => slot.take(),
+      None => None,
+    };
+    if fragment.is_none() {
+      self.error(
+        format!("`{{{name}}}` is filled with statements, so it may only be used once"),
+        ident.span,
+      );
+    }
+    fragment
+  }
+
+  fn wrong_kind(&mut self, ident: &ast::Ident, expected: &str) {
+    let name = hole(ident).unwrap_or_default();
+    self.error(format!("`{{{name}}}` must be {expected} here"), ident.span);
+  }
+
+  fn name(&mut self, ident: &mut ast::Ident<'static>) {
+    match self.take(ident) {
+      Some(Fragment::Name(name)) => *ident = ast::Ident::new(ident.span, Cow::owned(name)),
+      Some(_) => self.wrong_kind(ident, "a name"),
+      None => {}
+    }
+  }
+
+  fn block(&mut self, body: &mut Vec<ast::Stmt<'static>>) {
+    for mut stmt in std::mem::take(body) {
+      let placeholder = match &*stmt {
+        ast::StmtKind::Expr(expr) => match &***expr {
+          ast::ExprKind::GetVar(v) if hole(&v.name).is_some() => Some(v.name.clone()),
+          _ => None,
+        },
+        _ => None,
+      };
+      let Some(ident) = placeholder else {
+        self.stmt(&mut stmt);
+        body.push(stmt);
+        continue;
+      };
+      match self.take(&ident) {
+        Some(Fragment::Stmts(stmts)) => body.extend(stmts.into_iter().map(|v| v.0)),
+        Some(Fragment::Expr(expr)) => body.push(ast::expr_stmt(placed(expr, ident.span))),
+        Some(Fragment::Name(name)) => body.push(ast::expr_stmt(ast::expr_get_var(
+          ast::Ident::new(ident.span, Cow::owned(name)),
+        ))),
+        Some(Fragment::Params(_)) => self.wrong_kind(&ident, "statements or an expression"),
+        None => {}
+      }
+    }
+  }
+
+  fn stmt(&mut self, stmt: &mut ast::Stmt<'static>) {
+    match &mut **stmt {
+      ast::StmtKind::Var(v) => {
+        self.name(&mut v.name);
+        self.expr(&mut v.value);
+      }
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter_mut() {
+          self.expr(&mut branch.cond);
+          self.block(&mut branch.body);
+        }
+        if let Some(default) = &mut v.default {
+          self.block(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => match &mut **v {
+        ast::Loop::For(v) => {
+          self.name(&mut v.item);
+          match &mut v.iter {
+            ast::ForIter::Range(range) => {
+              self.expr(&mut range.start);
+              self.expr(&mut range.end);
+            }
+            ast::ForIter::Expr(iter) | ast::ForIter::Stream(iter) => self.expr(iter),
+          }
+          self.block(&mut v.body);
+        }
+        ast::Loop::While(v) => {
+          self.expr(&mut v.cond);
+          self.block(&mut v.body);
+        }
+        ast::Loop::Infinite(v) => self.block(&mut v.body),
+        ast::Loop::Repeat(v) => {
+          self.expr(&mut v.count);
+          self.block(&mut v.body);
+        }
+      },
+      ast::StmtKind::Ctrl(v) => match &mut **v {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => self.func(v),
+      ast::StmtKind::Class(v) => {
+        self.name(&mut v.name);
+        if let Some(parent) = &mut v.parent {
+          self.name(parent);
+        }
+        for field in v.members.fields.iter_mut() {
+          self.name(&mut field.name);
+          self.expr(&mut field.default);
+        }
+        let members = &mut v.members;
+        for method in members.init.iter_mut().chain(members.methods.iter_mut()) {
+          self.func(method);
+        }
+      }
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Pass => {}
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter_mut() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Import(v) => match &mut **v {
+        ast::Import::Module { path, alias } => {
+          path.iter_mut().chain(alias).for_each(|v| self.name(v));
+        }
+        ast::Import::Symbols { path, symbols } => {
+          path.iter_mut().for_each(|v| self.name(v));
+          for symbol in symbols.iter_mut() {
+            self.name(&mut symbol.name);
+            if let Some(alias) = &mut symbol.alias {
+              self.name(alias);
+            }
+          }
+        }
+      },
+      ast::StmtKind::Try(v) => {
+        self.block(&mut v.body);
+        for catch in v.catches.iter_mut() {
+          if let Some(class) = &mut catch.class {
+            self.expr(class);
+          }
+          if let Some(binding) = &mut catch.binding {
+            self.name(binding);
+          }
+          self.block(&mut catch.body);
+        }
+      }
+      ast::StmtKind::Throw(v) => self.expr(&mut v.value),
+      ast::StmtKind::With(v) => {
+        self.expr(&mut v.context);
+        if let Some(binding) = &mut v.binding {
+          self.name(binding);
+        }
+        self.block(&mut v.body);
+      }
+      ast::StmtKind::MultiAssign(v) => {
+        for target in v.targets.iter_mut().flatten() {
+          match target {
+            ast::AssignTarget::Var(var) => self.name(&mut var.name),
+            ast::AssignTarget::Field(get) => {
+              self.expr(&mut get.target);
+              self.name(&mut get.name);
+            }
+            ast::AssignTarget::Index(get) => {
+              self.expr(&mut get.target);
+              self.expr(&mut get.key);
+            }
+          }
+        }
+        for value in v.values.iter_mut() {
+          self.expr(value);
+        }
+      }
+    }
+  }
+
+  fn func(&mut self, func: &mut ast::Func<'static>) {
+    self.name(&mut func.name);
+    for decorator in func.decorators.iter_mut() {
+      self.expr(decorator);
+    }
+    for mut param in std::mem::take(&mut func.params.pos) {
+      if param.default.is_some() || hole(&param.name).is_none() {
+        self.name(&mut param.name);
+        if let Some(default) = &mut param.default {
+          self.expr(default);
+        }
+        func.params.pos.push(param);
+        continue;
+      }
+      match self.take(&param.name) {
+        Some(Fragment::Params(params)) => {
+          func
+            .params
+            .pos
+            .extend(params.into_iter().map(|v| ast::Param {
+              name: ast::Ident::new(param.name.span, Cow::owned(v.name)),
+              default: v.default.map(|v| placed(v, param.name.span)),
+            }))
+        }
+        Some(Fragment::Name(name)) => func.params.pos.push(ast::Param {
+          name: ast::Ident::new(param.name.span, Cow::owned(name)),
+          default: None,
+        }),
+        Some(_) => self.wrong_kind(&param.name, "parameters or a name"),
+        None => {}
+      }
+    }
+    self.block(&mut func.body);
+    func.has_yield = func.has_yield || has_yield(&func.body);
+  }
+
+  fn expr(&mut self, expr: &mut ast::Expr<'static>) {
+    match &mut **expr {
+      ast::ExprKind::GetVar(v) => match self.take(&v.name) {
+        Some(Fragment::Name(name)) => v.name = ast::Ident::new(v.name.span, Cow::owned(name)),
+        Some(Fragment::Expr(value)) => *expr = placed(value, v.name.span),
+        Some(_) => {
+          let ident = v.name.clone();
+          self.wrong_kind(&ident, "an expression or a name")
+        }
+        None => {}
+      },
+      ast::ExprKind::Literal(v) => match &mut **v {
+        ast::Literal::List(items) => items.iter_mut().for_each(|v| self.expr(v)),
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries.iter_mut() {
+            self.expr(key);
+            self.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        self.expr(&mut v.left);
+        self.expr(&mut v.right);
+      }
+      ast::ExprKind::Unary(v) => self.expr(&mut v.right),
+      ast::ExprKind::SetVar(v) => {
+        self.name(&mut v.target.name);
+        self.expr(&mut v.value);
+      }
+      ast::ExprKind::GetField(v) => {
+        self.expr(&mut v.target);
+        self.name(&mut v.name);
+      }
+      ast::ExprKind::SetField(v) => {
+        self.expr(&mut v.target.target);
+        self.name(&mut v.target.name);
+        self.expr(&mut v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        self.expr(&mut v.target);
+        self.expr(&mut v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        self.expr(&mut v.target.target);
+        self.expr(&mut v.target.key);
+        self.expr(&mut v.value);
+      }
+      ast::ExprKind::Call(v) => {
+        self.expr(&mut v.target);
+        v.args.iter_mut().for_each(|v| self.expr(v));
+      }
+      ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    }
+  }
+}
+
+/// Nodes may be nested at most this deep, same as in parsed modules.
+const MAX_NESTING_DEPTH: usize = 256;
+
+#[derive(Default)]
+struct Checker {
+  in_func: bool,
+  in_loop: bool,
+  depth: usize,
+  errors: Vec<SpannedError>,
+}
+
+impl Checker {
+  fn error(&mut self, message: impl Into<String>, span: Span) {
+    self.errors.push(SpannedError::new(message.into(), span));
+  }
+
+  /// Calls `f` one nesting level deeper, unless the limit has been reached.
+  fn nested(&mut self, span: Span, f: impl FnOnce(&mut Self)) {
+    if self.depth >= MAX_NESTING_DEPTH {
+      return self.error("nesting limit reached", span);
+    }
+    self.depth += 1;
+    f(self);
+    self.depth -= 1;
+  }
+
+  fn block(&mut self, body: &[ast::Stmt]) {
+    for stmt in body {
+      self.nested(stmt.span, |this| this.stmt(stmt));
+    }
+  }
+
+  fn loop_body(&mut self, body: &[ast::Stmt]) {
+    let in_loop = std::mem::replace(&mut self.in_loop, true);
+    self.block(body);
+    self.in_loop = in_loop;
+  }
+
+  fn stmt(&mut self, stmt: &ast::Stmt) {
+    match &**stmt {
+      ast::StmtKind::Var(v) => self.expr(&v.value),
+      ast::StmtKind::If(v) => {
+        for branch in v.branches.iter() {
+          self.expr(&branch.cond);
+          self.block(&branch.body);
+        }
+        if let Some(default) = &v.default {
+          self.block(default);
+        }
+      }
+      ast::StmtKind::Loop(v) => match &**v {
+        ast::Loop::For(v) => {
+          match &v.iter {
+            ast::ForIter::Range(range) => {
+              self.expr(&range.start);
+              self.expr(&range.end);
+            }
+            ast::ForIter::Expr(iter) | ast::ForIter::Stream(iter) => self.expr(iter),
+          }
+          self.loop_body(&v.body);
+        }
+        ast::Loop::While(v) => {
+          self.expr(&v.cond);
+          self.loop_body(&v.body);
+        }
+        ast::Loop::Infinite(v) => self.loop_body(&v.body),
+        ast::Loop::Repeat(v) => {
+          self.expr(&v.count);
+          self.loop_body(&v.body);
+        }
+      },
+      ast::StmtKind::Ctrl(v) => match &**v {
+        ast::Ctrl::Return(ast::Return { value }) | ast::Ctrl::Yield(ast::Yield { value }) => {
+          if !self.in_func {
+            let keyword = match &**v {
+              ast::Ctrl::Return(_) => "return",
+              _ => "yield",
+            };
+            self.error(format!("{keyword} outside of function"), stmt.span);
+          }
+          if let Some(value) = value {
+            self.expr(value);
+          }
+        }
+        ast::Ctrl::Continue if !self.in_loop => self.error("continue outside of loop", stmt.span),
+        ast::Ctrl::Break if !self.in_loop => self.error("break outside of loop", stmt.span),
+        ast::Ctrl::Continue | ast::Ctrl::Break => {}
+      },
+      ast::StmtKind::Func(v) => self.func(v, stmt.span),
+      ast::StmtKind::Expr(v) => self.expr(v),
+      ast::StmtKind::Print(v) => {
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+      ast::StmtKind::Throw(v) => self.expr(&v.value),
+      ast::StmtKind::Pass | ast::StmtKind::Import(_) => {}
+      ast::StmtKind::Class(v) => {
+        for field in v.members.fields.iter() {
+          self.expr(&field.default);
+        }
+        for method in v.members.init.iter().chain(v.members.methods.iter()) {
+          self.func(method, stmt.span);
+        }
+      }
+      ast::StmtKind::Try(v) => {
+        self.block(&v.body);
+        for catch in v.catches.iter() {
+          if let Some(class) = &catch.class {
+            self.expr(class);
+          }
+          self.block(&catch.body);
+        }
+      }
+      ast::StmtKind::With(v) => {
+        self.expr(&v.context);
+        self.block(&v.body);
+      }
+      ast::StmtKind::MultiAssign(v) => {
+        for target in v.targets.iter().flatten() {
+          match target {
+            ast::AssignTarget::Var(_) => {}
+            ast::AssignTarget::Field(get) => self.expr(&get.target),
+            ast::AssignTarget::Index(get) => {
+              self.expr(&get.target);
+              self.expr(&get.key);
+            }
+          }
+        }
+        for value in v.values.iter() {
+          self.expr(value);
+        }
+      }
+    }
+  }
+
+  fn func(&mut self, func: &ast::Func, span: Span) {
+    let mut has_default = false;
+    for (i, param) in func.params.pos.iter().enumerate() {
+      if func.params.pos[..i].iter().any(|p| p.name == param.name) {
+        self.error(
+          format!("duplicate argument `{}`", param.name.as_str()),
+          span,
+        );
+      }
+      match &param.default {
+        Some(default) => {
+          has_default = true;
+          self.expr(default);
+        }
+        None if has_default => {
+          self.error("non-default argument follows default argument", span);
+        }
+        None => {}
+      }
+    }
+
+    let in_func = std::mem::replace(&mut self.in_func, true);
+    let in_loop = std::mem::replace(&mut self.in_loop, false);
+    self.block(&func.body);
+    self.in_func = in_func;
+    self.in_loop = in_loop;
+  }
+
+  fn expr(&mut self, expr: &ast::Expr) {
+    self.nested(expr.span, |this| match &**expr {
+      ast::ExprKind::Literal(v) => match &**v {
+        ast::Literal::Float(v) if v.is_nan() => this.error("float must not be NaN", expr.span),
+        ast::Literal::List(items) => {
+          for item in items {
+            this.expr(item);
+          }
+        }
+        ast::Literal::Table(entries) => {
+          for (key, value) in entries {
+            this.expr(key);
+            this.expr(value);
+          }
+        }
+        _ => {}
+      },
+      ast::ExprKind::Binary(v) => {
+        this.expr(&v.left);
+        this.expr(&v.right);
+      }
+      ast::ExprKind::Unary(v) => this.expr(&v.right),
+      ast::ExprKind::GetVar(_) => {}
+      ast::ExprKind::SetVar(v) => this.expr(&v.value),
+      ast::ExprKind::GetField(v) => this.expr(&v.target),
+      ast::ExprKind::SetField(v) => {
+        this.expr(&v.target.target);
+        this.expr(&v.value);
+      }
+      ast::ExprKind::GetIndex(v) => {
+        this.expr(&v.target);
+        this.expr(&v.key);
+      }
+      ast::ExprKind::SetIndex(v) => {
+        this.expr(&v.target.target);
+        this.expr(&v.target.key);
+        this.expr(&v.value);
+      }
+      ast::ExprKind::Call(v) => {
+        this.expr(&v.target);
+        for arg in v.args.iter() {
+          this.expr(arg);
+        }
+      }
+      ast::ExprKind::GetSelf | ast::ExprKind::GetSuper => {}
+    })
+  }
+}
@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::sync::{Mutex, Weak};
+
+use futures_util::TryFutureExt;
+
+use crate::internal::error::Result;
+use crate::internal::object::{is_callable, Any, Ptr};
+use crate::internal::value::Value as OwnedValue;
+use crate::public::{Bind, FromValue, Global, Hebi, IntoValue, IntoValuePack, Unbind, Value};
+
+/// A function passed to a native function, which the host may call after
+/// the native function returns.
+///
+/// The function is kept alive by the VM it belongs to, and the callback only
+/// refers to it, so it may be stored anywhere, even on another thread. It can
+/// only be called with the same [`Hebi`], and stays alive until the
+/// callback or the [`Hebi`] is dropped.
+///
+/// ```rust
+/// use std::sync::{Arc, Mutex};
+///
+/// use hebi::{Callback, Hebi, NativeModule, Scope};
+///
+/// let handlers = Arc::new(Mutex::new(Vec::<Callback>::new()));
+/// let module = NativeModule::builder("events")
+///   .function("on_event", {
+///     let handlers = handlers.clone();
+///     move |scope: Scope<'_>| -> hebi::Result<()> {
+///       let handler = scope.param::<Callback>(0)?;
+///       handlers.lock().unwrap().push(handler);
+///       Ok(())
+///     }
+///   })
+///   .finish();
+///
+/// let mut hebi = Hebi::new();
+/// hebi.register(&module);
+/// hebi
+///   .eval(
+///     r#"
+/// from events import on_event
+/// fn double(v):
+///   return v * 2
+/// on_event(double)
+/// "#,
+///   )
+///   .unwrap();
+///
+/// let handler = handlers.lock().unwrap().pop().unwrap();
+/// let value = handler.call(&mut hebi, (21,)).unwrap();
+/// assert_eq!(value.as_int(), Some(42));
+/// drop(handler);
+/// ```
+#[derive(Debug)]
+pub struct Callback {
+  vm: u64,
+  id: u64,
+  released: Weak<Mutex<Vec<u64>>>,
+}
+
+impl Callback {
+  /// Call the function with `args`.
+  ///
+  /// Fails if `hebi` is not the VM the function belongs to, or the
+  /// function fails.
+  pub fn call<'cx, A>(&self, hebi: &'cx mut Hebi, args: A) -> Result<Value<'cx>>
+  where
+    A: IntoValuePack<'cx> + Send + 'cx,
+  {
+    pollster::block_on(self.call_async(hebi, args))
+  }
+
+  pub fn call_async<'cx, A>(
+    &self,
+    hebi: &'cx mut Hebi,
+    args: A,
+  ) -> impl Future<Output = Result<Value<'cx>>> + Send + 'cx
+  where
+    A: IntoValuePack<'cx> + Send + 'cx,
+  {
+    let callable = self.get(&hebi.vm.root.global);
+    hebi
+      .call_batch_callable_async(callable, std::iter::once(args))
+      .map_ok(|mut values| values.pop().unwrap())
+  }
+
+  /// Stop keeping the function alive right away.
+  ///
+  /// Dropping the callback also releases the function, but only the next
+  /// time its VM uses a callback, as it may be dropped on another thread.
+  pub fn release(self, hebi: &mut Hebi) {
+    let vm = self.vm;
+    drop(self);
+    if vm == hebi.vm.root.global.id() {
+      hebi.vm.root.global.remove_released_roots();
+    }
+  }
+
+  fn get(&self, global: &crate::internal::vm::global::Global) -> Result<Ptr<Any>> {
+    if self.vm != global.id() {
+      fail!("callback belongs to a different VM");
+    }
+    match global.get_root(self.id) {
+      Some(callable) => Ok(callable),
+      None => fail!("callback was released"),
+    }
+  }
+}
+
+impl Drop for Callback {
+  fn drop(&mut self) {
+    if let Some(released) = self.released.upgrade() {
+      released.lock().unwrap().push(self.id);
+    }
+  }
+}
+
+impl<'cx> FromValue<'cx> for Callback {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let value = value.unbind();
+    let Some(callable) = value.clone().to_any().filter(is_callable) else {
+      fail!("`{value}` is not callable");
+    };
+    Ok(Callback {
+      vm: global.inner.id(),
+      id: global.inner.root(callable),
+      released: global.inner.released_roots(),
+    })
+  }
+  fn type_name() -> &'static str {
+    "function"
+  }
+}
+
+impl<'cx> IntoValue<'cx> for &Callback {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let callable = self.get(&global.inner)?;
+    Ok(OwnedValue::object(callable).bind(global))
+  }
+}
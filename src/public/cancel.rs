@@ -0,0 +1,113 @@
+use std::future::{self, Future};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use crate::internal::error::Result;
+use crate::internal::object::cancel::Cancellation;
+use crate::internal::value::Value as OwnedValue;
+use crate::public::{Bind, Global, IntoValue, Value};
+
+/// A flag which the host sets to ask scripts to stop.
+///
+/// The VM never stops a script on its own, it only stops running it once a
+/// [`Hebi::step`][crate::Hebi::step] budget is used up or the future of a
+/// script is dropped, which may happen in the middle of anything the script
+/// was doing. A token lets cooperative scripts find out that they are about
+/// to be interrupted, and finish what they were doing first. The host
+/// cancels the token, keeps running the script for a while, and only
+/// interrupts it if it did not finish in time.
+///
+/// Scripts get the token by being passed it as a value, and use it through
+/// its `cancelled()` method, which returns whether the token was cancelled,
+/// and its `wait()` method, which waits until it is. Waiting suspends the
+/// whole VM, so the token has to be cancelled by a different task.
+///
+/// ```rust
+/// let mut hebi = hebi::Hebi::builder().output(hebi::io::StringWriter::new()).finish();
+/// let token = hebi::CancellationToken::new();
+/// hebi.global().define("ctx", &token).unwrap();
+/// hebi
+///   .eval(
+///     r#"
+/// fn worker():
+///   while !ctx.cancelled():
+///     yield
+///   print "cleaned up"
+/// "#,
+///   )
+///   .unwrap();
+/// hebi.spawn("worker", ()).unwrap();
+/// assert!(!hebi.step(100).unwrap());
+///
+/// token.cancel();
+/// // give the worker a grace period before dropping it
+/// assert!(hebi.step(100).unwrap());
+/// let output = hebi.global().output().as_any().downcast_ref::<hebi::io::StringWriter>().cloned();
+/// assert_eq!(output.as_deref(), Some("cleaned up\n"));
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+  cancelled: AtomicBool,
+  /// Tasks waiting for the token to be cancelled.
+  waiting: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancel the token, and wake everything waiting for it. Cancelling it
+  /// again does nothing.
+  pub fn cancel(&self) {
+    self.shared.cancelled.store(true, Ordering::Release);
+    for waker in self.shared.waiting.lock().unwrap().drain(..) {
+      waker.wake();
+    }
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.shared.cancelled.load(Ordering::Acquire)
+  }
+
+  /// Wait until the token is cancelled.
+  pub fn cancelled(&self) -> impl Future<Output = ()> + '_ {
+    future::poll_fn(move |cx| {
+      if self.is_cancelled() {
+        return Poll::Ready(());
+      }
+      let mut waiting = self.shared.waiting.lock().unwrap();
+      // checked again, in case it was cancelled before the lock was taken
+      if self.is_cancelled() {
+        return Poll::Ready(());
+      }
+      if !waiting.iter().any(|w| w.will_wake(cx.waker())) {
+        waiting.push(cx.waker().clone());
+      }
+      Poll::Pending
+    })
+  }
+}
+
+impl std::fmt::Debug for CancellationToken {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CancellationToken")
+      .field("cancelled", &self.is_cancelled())
+      .finish()
+  }
+}
+
+impl<'cx> IntoValue<'cx> for &CancellationToken {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let cancellation = global.inner.alloc(Cancellation {
+      token: self.clone(),
+    });
+    Ok(OwnedValue::object(cancellation).bind(global))
+  }
+}
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::future::{self, Future};
+use std::mem::transmute;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use crate::internal::error::Result;
+use crate::internal::object::channel::{self, ScriptChannel};
+use crate::internal::object::native::LocalBoxFuture;
+use crate::internal::value::Value as OwnedValue;
+use crate::public::{Bind, FromValue, Global, IntoValue, Scope, Unbind, Value};
+
+/// A bounded queue of values, which both the host and scripts can send
+/// into and receive from.
+///
+/// The host side may be cloned and moved to other threads, so a task can
+/// feed a long-running script, or consume what it produces, without sharing
+/// anything else with it. Scripts get their side by being passed the channel
+/// as a value, and use it through its `send(value)`, `recv()` and `close()`
+/// methods. `send` waits while the channel is full, and `recv` waits while
+/// it is empty, returning `none` once it is closed and empty. Waiting
+/// suspends the whole VM, not just the script which waits, so the other end
+/// has to be driven by a different task.
+///
+/// ```rust
+/// # pollster::block_on(async {
+/// let mut hebi = hebi::Hebi::new();
+/// let lines = hebi.channel::<String>(4);
+/// hebi.global().define("lines", &lines).unwrap();
+///
+/// let producer = async {
+///   for line in ["a", "b"] {
+///     lines.send(line.to_string()).await.unwrap();
+///   }
+///   lines.close();
+/// };
+/// let script = hebi.eval_async(
+///   r#"
+/// count := 0
+/// loop:
+///   line := lines.recv()
+///   if line == none:
+///     break
+///   count += 1
+/// count
+/// "#,
+/// );
+/// let ((), count) = futures_util::join!(producer, script);
+/// assert_eq!(count.unwrap().as_int(), Some(2));
+/// # })
+/// ```
+pub struct Channel<T> {
+  shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+  capacity: usize,
+  state: Mutex<State<T>>,
+}
+
+struct State<T> {
+  items: VecDeque<T>,
+  closed: bool,
+  /// Tasks waiting for an item or for room for one.
+  waiting: Vec<Waker>,
+}
+
+impl<T> State<T> {
+  fn wait(&mut self, waker: &Waker) {
+    if !self.waiting.iter().any(|w| w.will_wake(waker)) {
+      self.waiting.push(waker.clone());
+    }
+  }
+
+  fn wake_all(&mut self) {
+    for waker in self.waiting.drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+impl<T> Clone for Channel<T> {
+  fn clone(&self) -> Self {
+    Self {
+      shared: self.shared.clone(),
+    }
+  }
+}
+
+impl<T> Channel<T> {
+  /// `capacity` is at least `1`.
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      shared: Arc::new(Shared {
+        capacity: capacity.max(1),
+        state: Mutex::new(State {
+          items: VecDeque::new(),
+          closed: false,
+          waiting: Vec::new(),
+        }),
+      }),
+    }
+  }
+
+  /// Wait until there is room in the channel, and put `value` into it.
+  ///
+  /// Fails if the channel is closed.
+  pub fn send(&self, value: T) -> impl Future<Output = Result<()>> + '_ {
+    let mut value = Some(value);
+    future::poll_fn(move |cx| {
+      let mut state = self.shared.state.lock().unwrap();
+      if state.closed {
+        return Poll::Ready(Err(error!("channel is closed").into()));
+      }
+      if state.items.len() >= self.shared.capacity {
+        state.wait(cx.waker());
+        return Poll::Pending;
+      }
+      state.items.push_back(value.take().unwrap());
+      state.wake_all();
+      Poll::Ready(Ok(()))
+    })
+  }
+
+  /// Wait for the next value in the channel.
+  ///
+  /// Returns `None` once the channel is closed and every value sent before
+  /// that was received.
+  pub fn recv(&self) -> impl Future<Output = Option<T>> + '_ {
+    future::poll_fn(move |cx| {
+      let mut state = self.shared.state.lock().unwrap();
+      if let Some(value) = state.items.pop_front() {
+        state.wake_all();
+        return Poll::Ready(Some(value));
+      }
+      if state.closed {
+        return Poll::Ready(None);
+      }
+      state.wait(cx.waker());
+      Poll::Pending
+    })
+  }
+
+  /// Take the next value in the channel, if there is one, without waiting.
+  pub fn try_recv(&self) -> Option<T> {
+    let mut state = self.shared.state.lock().unwrap();
+    let value = state.items.pop_front();
+    if value.is_some() {
+      state.wake_all();
+    }
+    value
+  }
+
+  /// Stop accepting new values. Values which were already sent can still
+  /// be received.
+  pub fn close(&self) {
+    let mut state = self.shared.state.lock().unwrap();
+    state.closed = true;
+    state.wake_all();
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.shared.state.lock().unwrap().closed
+  }
+
+  /// The number of values waiting to be received.
+  pub fn len(&self) -> usize {
+    self.shared.state.lock().unwrap().items.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl<T> ScriptChannel for Channel<T>
+where
+  T: for<'cx> FromValue<'cx> + for<'cx> IntoValue<'cx> + Send + 'static,
+{
+  fn send(&self, scope: Scope<'_>) -> LocalBoxFuture<'static, Result<OwnedValue>> {
+    let value = match scope.param::<T>(0) {
+      Ok(value) => value,
+      Err(e) => return Box::pin(future::ready(Err(e))),
+    };
+    let channel = self.clone();
+    Box::pin(async move {
+      channel.send(value).await?;
+      Ok(OwnedValue::none())
+    })
+  }
+
+  fn recv(&self, scope: Scope<'_>) -> LocalBoxFuture<'static, Result<OwnedValue>> {
+    let scope = unsafe { transmute::<Scope<'_>, Scope<'static>>(scope) };
+    let global = scope.global();
+    let channel = self.clone();
+    Box::pin(async move {
+      match channel.recv().await {
+        Some(value) => Ok(value.into_value(global)?.unbind()),
+        None => Ok(OwnedValue::none()),
+      }
+    })
+  }
+
+  fn close(&self) {
+    Channel::close(self)
+  }
+}
+
+impl<'cx, T> IntoValue<'cx> for &Channel<T>
+where
+  T: for<'a> FromValue<'a> + for<'a> IntoValue<'a> + Send + 'static,
+{
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let channel = global.inner.alloc(channel::Channel {
+      inner: Arc::new(self.clone()),
+    });
+    Ok(OwnedValue::object(channel).bind(global))
+  }
+}
@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+use crate::internal;
+use crate::public::{Unbind, Value};
+
+/// Find where `lhs` and `rhs` differ.
+///
+/// Lists are compared item by item, tables and class instances field by
+/// field, and everything else the same way as with `==`, except that other
+/// objects are only equal to themselves. Every difference is reported, not
+/// just the first one, with the path leading to it.
+///
+/// ```rust
+/// let mut hebi = hebi::Hebi::new();
+/// hebi
+///   .eval(
+///     r#"
+/// lhs = { name: "a", items: [1, 2, 3] }
+/// rhs = { name: "b", items: [1, "2"], extra: none }
+/// "#,
+///   )
+///   .unwrap();
+/// let global = hebi.global();
+/// let diff = hebi::diff(&global.get("lhs").unwrap(), &global.get("rhs").unwrap());
+/// assert_eq!(
+///   diff.to_string(),
+///   "$.name: \"a\" != \"b\"\n\
+///    $.items[1]: type `int` != `String`\n\
+///    $.items[2]: 3 is missing on the right\n\
+///    $.extra: none is missing on the left"
+/// );
+/// ```
+pub fn diff(lhs: &Value<'_>, rhs: &Value<'_>) -> Diff {
+  Diff {
+    differences: internal::diff::diff(&lhs.clone().unbind(), &rhs.clone().unbind()),
+  }
+}
+
+/// The differences between two values, see [`diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+  pub differences: Vec<Difference>,
+}
+
+impl Diff {
+  /// Whether the values are equal.
+  pub fn is_empty(&self) -> bool {
+    self.differences.is_empty()
+  }
+}
+
+/// One line per difference.
+impl Display for Diff {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for (i, difference) in self.differences.iter().enumerate() {
+      if i > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{difference}")?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+  /// Where the values differ, starting with `$` for the values themselves,
+  /// such as `$.items[2]`.
+  pub path: String,
+  pub change: Change,
+}
+
+/// How two values differ. Values are formatted the same way as when they
+/// are printed, except that strings are quoted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+  /// The values have different types.
+  Type { lhs: String, rhs: String },
+  /// The values have the same type, but are not equal.
+  Value { lhs: String, rhs: String },
+  /// Only the right value has an item or field at this path.
+  MissingInLhs { rhs: String },
+  /// Only the left value has an item or field at this path.
+  MissingInRhs { lhs: String },
+}
+
+impl Display for Difference {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let path = &self.path;
+    match &self.change {
+      Change::Type { lhs, rhs } => write!(f, "{path}: type {lhs} != {rhs}"),
+      Change::Value { lhs, rhs } => write!(f, "{path}: {lhs} != {rhs}"),
+      Change::MissingInLhs { rhs } => write!(f, "{path}: {rhs} is missing on the left"),
+      Change::MissingInRhs { lhs } => write!(f, "{path}: {lhs} is missing on the right"),
+    }
+  }
+}
@@ -0,0 +1,88 @@
+use crate::internal::bytecode::opcode as op;
+use crate::internal::codegen;
+use crate::internal::error::{Error, Result};
+use crate::internal::syntax::{self, ast};
+use crate::internal::vm::global::Global;
+
+/// A static estimate of how much work a script does, see
+/// [`Hebi::estimate`][crate::Hebi::estimate].
+///
+/// The estimate is made without running the script, so it cannot tell how
+/// many times a loop runs, whether a function calls itself, or what the
+/// native functions it calls do. It is meant for cheaply rejecting scripts
+/// which are obviously too large or too deeply nested, and does not replace
+/// limiting how many instructions a script may execute with
+/// [`Hebi::step`][crate::Hebi::step].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Estimate {
+  /// The number of instructions in the script and all of the functions
+  /// defined in it.
+  pub instructions: usize,
+  /// Whether the script contains a `for`, `while`, or `loop` statement.
+  pub has_loops: bool,
+  /// How deeply blocks are nested in each other. A script without any
+  /// blocks has a nesting of `0`, and each `if`, loop, `try`, `with`,
+  /// function, or class body adds one.
+  pub max_nesting: usize,
+  /// How deeply loops are nested in each other within a single function.
+  pub max_loop_nesting: usize,
+}
+
+pub(crate) fn estimate(global: Global, code: &str) -> Result<Estimate> {
+  let ast = syntax::parse(global.clone(), code).map_err(Error::Syntax)?;
+  let module = codegen::emit(global, &ast, "__main__", true).map_err(Error::Syntax)?;
+
+  let mut estimate = Estimate::default();
+  module.root.visit(None, &mut |function, _| {
+    estimate.instructions += op::instructions(unsafe { function.instructions.as_ref() }).count();
+  });
+  visit_block(&ast.body, 0, 0, &mut estimate);
+  Ok(estimate)
+}
+
+fn visit_block(body: &[ast::Stmt], depth: usize, loops: usize, estimate: &mut Estimate) {
+  estimate.max_nesting = estimate.max_nesting.max(depth);
+  estimate.max_loop_nesting = estimate.max_loop_nesting.max(loops);
+
+  for stmt in body {
+    match &**stmt {
+      ast::StmtKind::If(v) => {
+        let branches = v.branches.iter().map(|branch| &branch.body);
+        for body in branches.chain(v.default.as_ref()) {
+          visit_block(body, depth + 1, loops, estimate);
+        }
+      }
+      ast::StmtKind::Loop(v) => {
+        estimate.has_loops = true;
+        let body = match &**v {
+          ast::Loop::For(v) => &v.body,
+          ast::Loop::While(v) => &v.body,
+          ast::Loop::Infinite(v) => &v.body,
+          ast::Loop::Repeat(v) => &v.body,
+        };
+        visit_block(body, depth + 1, loops + 1, estimate);
+      }
+      ast::StmtKind::Func(v) => visit_block(&v.body, depth + 1, 0, estimate),
+      ast::StmtKind::Class(v) => {
+        for method in v.members.init.iter().chain(v.members.methods.iter()) {
+          visit_block(&method.body, depth + 2, 0, estimate);
+        }
+      }
+      ast::StmtKind::Try(v) => {
+        visit_block(&v.body, depth + 1, loops, estimate);
+        for catch in v.catches.iter() {
+          visit_block(&catch.body, depth + 1, loops, estimate);
+        }
+      }
+      ast::StmtKind::With(v) => visit_block(&v.body, depth + 1, loops, estimate),
+      ast::StmtKind::Var(_)
+      | ast::StmtKind::Ctrl(_)
+      | ast::StmtKind::Expr(_)
+      | ast::StmtKind::Pass
+      | ast::StmtKind::Print(_)
+      | ast::StmtKind::Import(_)
+      | ast::StmtKind::Throw(_)
+      | ast::StmtKind::MultiAssign(_) => {}
+    }
+  }
+}
@@ -0,0 +1,11 @@
+use crate::internal::codegen;
+use crate::internal::error::{Error, Result};
+use crate::internal::syntax;
+use crate::internal::vm::global::Global;
+
+pub use crate::internal::codegen::hover::{Binding, ClassInfo, Hover, HoverKind, Literal};
+
+pub(crate) fn hover(global: Global, code: &str, offset: usize) -> Result<Option<Hover>> {
+  let ast = syntax::parse(global.clone(), code).map_err(Error::Syntax)?;
+  Ok(codegen::hover(global, &ast, offset))
+}
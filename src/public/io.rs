@@ -0,0 +1,204 @@
+//! Script input and output.
+//!
+//! Everything a script prints goes through an [`Output`], which is
+//! configured with [`HebiBuilder::output`][crate::HebiBuilder::output].
+//! Scripts may only read input if an [`Input`] is configured with
+//! [`HebiBuilder::input`][crate::HebiBuilder::input].
+
+use std::io;
+use std::ops::Deref;
+
+pub use crate::internal::vm::global::{Input, IoBase, Output};
+
+/// Collects output as text. Invalid UTF-8 is replaced with `U+FFFD`.
+///
+/// ```rust
+/// use hebi::io::StringWriter;
+///
+/// let mut hebi = hebi::Hebi::builder().output(StringWriter::new()).finish();
+/// hebi.eval("print 1").unwrap();
+/// let output = hebi.global().output().as_any().downcast_ref::<StringWriter>().cloned();
+/// assert_eq!(output.as_deref(), Some("1\n"));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct StringWriter {
+  inner: String,
+}
+
+impl StringWriter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.inner
+  }
+
+  pub fn into_string(self) -> String {
+    self.inner
+  }
+}
+
+impl Deref for StringWriter {
+  type Target = str;
+
+  fn deref(&self) -> &Self::Target {
+    &self.inner
+  }
+}
+
+impl io::Write for StringWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.inner.push_str(&String::from_utf8_lossy(buf));
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Adapts any [`std::io::BufRead`] into an [`Input`].
+///
+/// ```rust
+/// let input = std::io::Cursor::new("world\n");
+/// let mut hebi = hebi::Hebi::builder()
+///   .input(hebi::io::Reader::new(input))
+///   .output(hebi::io::StringWriter::new())
+///   .finish();
+/// hebi.eval("print \"hello \" + input(\"name: \")").unwrap();
+/// let output = hebi
+///   .global()
+///   .output()
+///   .as_any()
+///   .downcast_ref::<hebi::io::StringWriter>()
+///   .cloned();
+/// assert_eq!(output.as_deref(), Some("name: hello world\n"));
+/// ```
+pub struct Reader<R> {
+  inner: R,
+}
+
+impl<R> Reader<R> {
+  pub fn new(inner: R) -> Self {
+    Self { inner }
+  }
+
+  pub fn into_inner(self) -> R {
+    self.inner
+  }
+}
+
+impl<R: io::BufRead + Send + Sync + 'static> Input for Reader<R> {
+  fn read_line(&mut self) -> io::Result<Option<String>> {
+    crate::internal::vm::global::read_line(&mut self.inner)
+  }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::async_writer::AsyncWriter;
+
+#[cfg(feature = "tokio")]
+mod async_writer {
+  use std::io;
+  use std::sync::{Arc, Mutex};
+
+  use tokio::io::{AsyncWrite, AsyncWriteExt};
+  use tokio::sync::{mpsc, oneshot};
+
+  use super::Output;
+  use crate::public::LocalBoxFuture;
+
+  enum Message {
+    Write(Vec<u8>),
+    Flush(Option<oneshot::Sender<io::Result<()>>>),
+  }
+
+  /// Adapts a [`tokio::io::AsyncWrite`] into an [`Output`].
+  ///
+  /// Scripts write synchronously, so the writes are queued and performed by
+  /// a task on the current tokio runtime. Writes never block the script, and
+  /// an I/O error is returned by the next write or flush after it happened.
+  ///
+  /// At the end of every script run, the VM waits until the task has written
+  /// and flushed everything queued so far. The runtime must be able to run
+  /// the task in the meantime, so the blocking [`Hebi::eval`][crate::Hebi::eval]
+  /// must not be called from a thread of a current-thread runtime.
+  pub struct AsyncWriter {
+    queue: mpsc::UnboundedSender<Message>,
+    error: Arc<Mutex<Option<io::Error>>>,
+  }
+
+  impl AsyncWriter {
+    /// Spawn the task which writes to `writer`.
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn new<W: AsyncWrite + Unpin + Send + 'static>(mut writer: W) -> Self {
+      let (queue, mut messages) = mpsc::unbounded_channel();
+      let error = Arc::new(Mutex::new(None));
+
+      let task_error = error.clone();
+      tokio::spawn(async move {
+        while let Some(message) = messages.recv().await {
+          let result = match message {
+            Message::Write(buf) => writer.write_all(&buf).await,
+            Message::Flush(Some(reply)) => {
+              let result = writer.flush().await;
+              let stop = result.is_err();
+              let _ = reply.send(result);
+              if stop {
+                return;
+              }
+              continue;
+            }
+            Message::Flush(None) => writer.flush().await,
+          };
+          if let Err(e) = result {
+            *task_error.lock().unwrap() = Some(e);
+            return;
+          }
+        }
+        let _ = writer.shutdown().await;
+      });
+
+      Self { queue, error }
+    }
+
+    fn send(&self, message: Message) -> io::Result<()> {
+      if let Some(e) = self.error.lock().unwrap().take() {
+        return Err(e);
+      }
+      self.queue.send(message).map_err(|_| stopped())
+    }
+  }
+
+  fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "output task has stopped")
+  }
+
+  impl Output for AsyncWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+      self.send(Message::Write(buf.to_vec()))
+    }
+
+    /// Queue a flush, without waiting for it.
+    fn flush(&mut self) -> io::Result<()> {
+      self.send(Message::Flush(None))
+    }
+
+    /// Queue a flush, and wait until the task has performed it.
+    fn flush_async(&mut self) -> LocalBoxFuture<'static, io::Result<()>> {
+      let (reply, flushed) = oneshot::channel();
+      let sent = self.send(Message::Flush(Some(reply)));
+      let error = self.error.clone();
+      Box::pin(async move {
+        sent?;
+        match flushed.await {
+          Ok(result) => result,
+          // the task stopped before the flush, because a write failed
+          Err(_) => Err(error.lock().unwrap().take().unwrap_or_else(stopped)),
+        }
+      })
+    }
+  }
+}
@@ -40,8 +40,10 @@ macro_rules! decl_ref {
 }
 
 macro_rules! impl_object_ref {
-  ($T:ident, $Owned:ty) => {
+  ($T:ident, $Owned:ty, $name:literal) => {
     impl<'cx> $crate::public::object::ObjectRef<'cx> for $T<'cx> {
+      const NAME: &'static str = $name;
+
       fn as_any(&self, _: $crate::public::Global<'cx>) -> $crate::public::object::Any<'cx> {
         let ptr = self.inner.clone().into_any();
         unsafe { ptr.bind_raw::<'cx>() }
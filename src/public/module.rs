@@ -9,7 +9,7 @@ use std::sync::Arc;
 use futures_util::{FutureExt, TryFutureExt};
 use indexmap::IndexMap;
 
-use crate::internal::error::Result;
+use crate::internal::error::{ErrorValue, Result};
 use crate::internal::object::native::{
   AsyncCallback, NativeClassDescriptor, NativeClassInstance, NativeFieldDescriptor,
   NativeMethodDescriptor, SyncCallback,
@@ -31,6 +31,7 @@ impl NativeModule {
         fns: IndexMap::new(),
         async_fns: IndexMap::new(),
         classes: IndexMap::new(),
+        error_classes: IndexMap::new(),
       },
     }
   }
@@ -41,6 +42,8 @@ pub(crate) struct NativeModuleData {
   pub(crate) fns: IndexMap<StdString, SyncCallback>,
   pub(crate) async_fns: IndexMap<StdString, AsyncCallback>,
   pub(crate) classes: IndexMap<StdString, NativeClassDescriptor>,
+  /// Error class names, mapped to the name of their parent class.
+  pub(crate) error_classes: IndexMap<StdString, Option<StdString>>,
 }
 
 pub struct NativeModuleBuilder {
@@ -89,6 +92,34 @@ impl NativeModuleBuilder {
     self
   }
 
+  /// Declare an error class, which scripts can catch with
+  /// `catch <name> as e`.
+  ///
+  /// Any error with the code `name` is caught as an instance of this class,
+  /// for example one returned from a native function through
+  /// [`error_value`][crate::error_value].
+  pub fn error_class(mut self, name: impl ToString) -> Self {
+    self.data.error_classes.insert(name.to_string(), None);
+    self
+  }
+
+  /// Declare an error class which inherits from `parent`, so it is also
+  /// caught by `catch <parent> as e`.
+  ///
+  /// `parent` must be an error class declared earlier in the same module.
+  pub fn error_subclass(mut self, name: impl ToString, parent: impl ToString) -> Self {
+    let parent = parent.to_string();
+    assert!(
+      self.data.error_classes.contains_key(&parent),
+      "unknown error class `{parent}`"
+    );
+    self
+      .data
+      .error_classes
+      .insert(name.to_string(), Some(parent));
+    self
+  }
+
   pub fn finish(self) -> NativeModule {
     NativeModule {
       data: Arc::new(self.data),
@@ -198,6 +229,41 @@ impl<const HAS_INIT: bool, T: Send + 'static> NativeClassBuilder<HAS_INIT, T> {
     self
   }
 
+  /// Called when an instance is used as the context of a `with` statement,
+  /// before the block is entered. The result is what `with <value> as <name>`
+  /// binds to `<name>`.
+  ///
+  /// Must be paired with [`exit`][Self::exit].
+  pub fn enter<'cx, R>(
+    self,
+    f: impl Fn(Scope<'cx>, This<'cx, T>) -> R + Send + Sync + 'static,
+  ) -> Self
+  where
+    R: IntoValue<'cx>,
+  {
+    self.method("__enter__", f)
+  }
+
+  /// Called when a `with` block which uses an instance as its context is
+  /// left, along with the error which caused it to be left, if any.
+  ///
+  /// Returning `true` suppresses the error.
+  pub fn exit<'cx>(
+    self,
+    f: impl Fn(Scope<'cx>, This<'cx, T>, Option<ErrorValue>) -> Result<bool> + Send + Sync + 'static,
+  ) -> Self {
+    self.method("__exit__", move |scope: Scope<'cx>, this: This<'cx, T>| {
+      let error = scope.param::<Value>(0)?;
+      let error = if error.is_none() {
+        None
+      } else {
+        let format = scope.thread.global.float_format();
+        Some(ErrorValue::from_value(error.unbind(), format))
+      };
+      f(scope, this, error)
+    })
+  }
+
   pub fn static_method<'cx, R>(
     mut self,
     name: impl ToString,
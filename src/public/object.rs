@@ -19,6 +19,8 @@ impl<'cx> Any<'cx> {
 }
 
 impl<'cx> ObjectRef<'cx> for Any<'cx> {
+  const NAME: &'static str = "object";
+
   fn as_any(&self, _: Global<'cx>) -> Any<'cx> {
     let ptr = self.inner.clone().into_any();
     unsafe { ptr.bind_raw::<'cx>() }
@@ -32,6 +34,9 @@ impl<'cx> ObjectRef<'cx> for Any<'cx> {
 impl<'cx> private::Sealed for Any<'cx> {}
 
 pub trait ObjectRef<'cx>: private::Sealed + Sized + Debug + Display {
+  /// The name scripts know the type by, used in error messages.
+  const NAME: &'static str;
+
   fn as_any(&self, global: Global<'cx>) -> Any<'cx>;
   fn from_any(v: Any<'cx>, global: Global<'cx>) -> Option<Self>;
 
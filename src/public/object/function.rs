@@ -1,10 +1,41 @@
 use super::*;
 use crate::internal::object::{Function as OwnedFunction, Ptr};
+use crate::public::Str;
 
 decl_ref! {
   struct Function(Ptr<OwnedFunction>)
 }
 
-impl_object_ref!(Function, OwnedFunction);
+impl_object_ref!(Function, OwnedFunction, "function");
 
-impl<'cx> Function<'cx> {}
+impl<'cx> Function<'cx> {
+  pub fn name(&self) -> &str {
+    self.inner.descriptor.name.as_str()
+  }
+
+  /// Names of the function's parameters, not including `self`.
+  pub fn params(&self) -> Vec<&str> {
+    let signature = &self.inner.descriptor.signature;
+    signature
+      .params
+      .iter()
+      .map(|param| param.name.as_str())
+      .collect()
+  }
+
+  /// The minimum and maximum number of arguments the function accepts,
+  /// not including `self`.
+  pub fn arity(&self) -> (usize, usize) {
+    let params = &self.inner.descriptor.params;
+    (params.min as usize, params.max as usize)
+  }
+
+  /// Name of the module the function was defined in, or `None` if it was
+  /// defined outside of a module.
+  pub fn module(&self, global: Global<'cx>) -> Option<Str<'cx>> {
+    self
+      .inner
+      .module_name(&global.inner)
+      .map(|name| name.bind(global))
+  }
+}
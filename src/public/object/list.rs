@@ -8,7 +8,7 @@ decl_ref! {
   struct List(Ptr<OwnedList>)
 }
 
-impl_object_ref!(List, OwnedList);
+impl_object_ref!(List, OwnedList, "list");
 
 impl<'cx> List<'cx> {
   pub fn len(&self) -> usize {
@@ -42,6 +42,10 @@ impl<'cx> List<'cx> {
     self.inner.set(index, value.unbind())
   }
 
+  pub fn swap(&self, a: usize, b: usize) {
+    self.inner.swap(a, b);
+  }
+
   pub fn iter<'a>(&'a self) -> Iter<'a, 'cx> {
     Iter {
       inner: self.inner.iter(),
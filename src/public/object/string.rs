@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use super::*;
 use crate::internal::object::{Ptr, Str as OwnedStr};
 use crate::public::{Hebi, Scope};
@@ -6,28 +8,210 @@ decl_ref! {
   struct Str(Ptr<OwnedStr>)
 }
 
-impl_object_ref!(Str, OwnedStr);
+impl_object_ref!(Str, OwnedStr, "str");
 
 impl<'cx> Str<'cx> {
   pub fn as_str(&self) -> &str {
     self.inner.as_str()
   }
+
+  /// The string encoded as UTF-8.
+  pub fn as_bytes(&self) -> &[u8] {
+    self.inner.as_str().as_bytes()
+  }
+
+  /// The string encoded as UTF-16, for example to pass it to a Windows API.
+  pub fn to_utf16(&self) -> Vec<u16> {
+    self.inner.as_str().encode_utf16().collect()
+  }
+
+  /// The string encoded as Latin-1 (ISO 8859-1), which fails if it contains
+  /// a character above `U+00FF`.
+  ///
+  /// ```rust
+  /// let hebi = hebi::Hebi::new();
+  /// assert_eq!(hebi.new_string("café").to_latin1().unwrap(), b"caf\xe9");
+  ///
+  /// let e = hebi.new_string("a → b").to_latin1().unwrap_err();
+  /// assert_eq!(e.offset, 2);
+  /// ```
+  pub fn to_latin1(&self) -> Result<Vec<u8>, EncodingError> {
+    let str = self.inner.as_str();
+    let mut out = Vec::with_capacity(str.len());
+    for (offset, ch) in str.char_indices() {
+      match u8::try_from(ch) {
+        Ok(byte) => out.push(byte),
+        Err(_) => {
+          return Err(EncodingError {
+            encoding: Encoding::Latin1,
+            offset,
+          })
+        }
+      }
+    }
+    Ok(out)
+  }
+}
+
+/// An encoding which strings can be converted from or to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+  Utf8,
+  Utf16,
+  Latin1,
+}
+
+impl Display for Encoding {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Encoding::Utf8 => f.write_str("UTF-8"),
+      Encoding::Utf16 => f.write_str("UTF-16"),
+      Encoding::Latin1 => f.write_str("Latin-1"),
+    }
+  }
+}
+
+/// Text which could not be converted from or to `encoding`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingError {
+  pub encoding: Encoding,
+  /// The offset of the first unit of the input which could not be
+  /// converted. Units are bytes, except for UTF-16 input, where they are
+  /// `u16`s.
+  pub offset: usize,
+}
+
+impl Display for EncodingError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.encoding {
+      Encoding::Latin1 => write!(
+        f,
+        "the character at offset {} cannot be encoded as Latin-1",
+        self.offset
+      ),
+      encoding => write!(f, "invalid {encoding} at offset {}", self.offset),
+    }
+  }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl From<EncodingError> for crate::Error {
+  fn from(value: EncodingError) -> Self {
+    crate::Error::user(value)
+  }
+}
+
+fn decode_utf8(v: &[u8]) -> Result<&str, EncodingError> {
+  std::str::from_utf8(v).map_err(|e| EncodingError {
+    encoding: Encoding::Utf8,
+    offset: e.valid_up_to(),
+  })
+}
+
+fn decode_utf16(v: &[u16]) -> Result<String, EncodingError> {
+  let mut out = String::with_capacity(v.len());
+  let mut offset = 0;
+  for ch in char::decode_utf16(v.iter().copied()) {
+    match ch {
+      Ok(ch) => {
+        out.push(ch);
+        offset += ch.len_utf16();
+      }
+      Err(_) => {
+        return Err(EncodingError {
+          encoding: Encoding::Utf16,
+          offset,
+        })
+      }
+    }
+  }
+  Ok(out)
 }
 
 impl<'cx> Global<'cx> {
   pub fn new_string(&self, v: impl ToString) -> Str<'cx> {
     self.inner.alloc(OwnedStr::owned(v)).bind(self.clone())
   }
+
+  /// Create a string from UTF-8 bytes, which fails if they are not valid.
+  pub fn new_string_from_utf8(&self, v: &[u8]) -> Result<Str<'cx>, EncodingError> {
+    Ok(self.new_string(decode_utf8(v)?))
+  }
+
+  /// Create a string from UTF-8 bytes, replacing invalid sequences with
+  /// `U+FFFD`.
+  pub fn new_string_from_utf8_lossy(&self, v: &[u8]) -> Str<'cx> {
+    self.new_string(String::from_utf8_lossy(v))
+  }
+
+  /// Create a string from UTF-16, which fails if it contains an unpaired
+  /// surrogate.
+  pub fn new_string_from_utf16(&self, v: &[u16]) -> Result<Str<'cx>, EncodingError> {
+    Ok(self.new_string(decode_utf16(v)?))
+  }
+
+  /// Create a string from UTF-16, replacing unpaired surrogates with
+  /// `U+FFFD`.
+  pub fn new_string_from_utf16_lossy(&self, v: &[u16]) -> Str<'cx> {
+    self.new_string(String::from_utf16_lossy(v))
+  }
+
+  /// Create a string from Latin-1 (ISO 8859-1) bytes, which are always
+  /// valid.
+  pub fn new_string_from_latin1(&self, v: &[u8]) -> Str<'cx> {
+    self.new_string(v.iter().map(|&byte| byte as char).collect::<String>())
+  }
 }
 
 impl<'cx> Scope<'cx> {
   pub fn new_string(&self, v: impl ToString) -> Str<'cx> {
     self.global().new_string(v)
   }
+
+  pub fn new_string_from_utf8(&self, v: &[u8]) -> Result<Str<'cx>, EncodingError> {
+    self.global().new_string_from_utf8(v)
+  }
+
+  pub fn new_string_from_utf8_lossy(&self, v: &[u8]) -> Str<'cx> {
+    self.global().new_string_from_utf8_lossy(v)
+  }
+
+  pub fn new_string_from_utf16(&self, v: &[u16]) -> Result<Str<'cx>, EncodingError> {
+    self.global().new_string_from_utf16(v)
+  }
+
+  pub fn new_string_from_utf16_lossy(&self, v: &[u16]) -> Str<'cx> {
+    self.global().new_string_from_utf16_lossy(v)
+  }
+
+  pub fn new_string_from_latin1(&self, v: &[u8]) -> Str<'cx> {
+    self.global().new_string_from_latin1(v)
+  }
 }
 
 impl Hebi {
   pub fn new_string(&self, v: impl ToString) -> Str {
     self.global().new_string(v)
   }
+
+  pub fn new_string_from_utf8(&self, v: &[u8]) -> Result<Str<'_>, EncodingError> {
+    self.global().new_string_from_utf8(v)
+  }
+
+  pub fn new_string_from_utf8_lossy(&self, v: &[u8]) -> Str<'_> {
+    self.global().new_string_from_utf8_lossy(v)
+  }
+
+  pub fn new_string_from_utf16(&self, v: &[u16]) -> Result<Str<'_>, EncodingError> {
+    self.global().new_string_from_utf16(v)
+  }
+
+  pub fn new_string_from_utf16_lossy(&self, v: &[u16]) -> Str<'_> {
+    self.global().new_string_from_utf16_lossy(v)
+  }
+
+  pub fn new_string_from_latin1(&self, v: &[u8]) -> Str<'_> {
+    self.global().new_string_from_latin1(v)
+  }
 }
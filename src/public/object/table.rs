@@ -8,7 +8,7 @@ decl_ref! {
   struct Table(Ptr<OwnedTable>)
 }
 
-impl_object_ref!(Table, OwnedTable);
+impl_object_ref!(Table, OwnedTable, "table");
 
 impl<'cx> Table<'cx> {
   pub fn len(&self) -> usize {
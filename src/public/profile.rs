@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::span::{Source, Span};
+
+/// Where a VM spent its time, see [`Hebi::profile`][crate::Hebi::profile].
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+  /// Native functions which were called at least once, in the order they
+  /// were first called.
+  pub natives: Vec<NativeTiming>,
+  /// Call sites which were executed at least once, in the order they were
+  /// first executed.
+  pub call_sites: Vec<CallSite>,
+}
+
+impl Profile {
+  /// Call sites which called more different functions than a polymorphic
+  /// site does, with the ones executed most often first.
+  pub fn megamorphic(&self) -> Vec<&CallSite> {
+    let mut sites = (self.call_sites.iter())
+      .filter(|site| site.dispatch() == Dispatch::Megamorphic)
+      .collect::<Vec<_>>();
+    sites.sort_by_key(|site| std::cmp::Reverse(site.calls));
+    sites
+  }
+}
+
+/// How often a native function was called, and how long the calls took.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NativeTiming {
+  /// The module or class the function belongs to.
+  pub owner: Option<String>,
+  pub name: String,
+  pub calls: u64,
+  /// The sum of the time spent in each call.
+  ///
+  /// Calls to async functions take until their future completes, including
+  /// any time spent waiting.
+  pub total: Duration,
+}
+
+impl NativeTiming {
+  /// The average time spent in a single call.
+  pub fn mean(&self) -> Duration {
+    match u32::try_from(self.calls) {
+      Ok(0) => Duration::ZERO,
+      Ok(calls) => self.total / calls,
+      Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.calls as f64),
+    }
+  }
+}
+
+/// A call in a script, and how many different functions it called.
+#[derive(Clone, Debug)]
+pub struct CallSite {
+  /// The name of the function the call is in.
+  pub function: String,
+  /// The span of the call.
+  pub span: Span,
+  /// The source which `span` points into, if the module has one.
+  pub source: Option<Source>,
+  pub calls: u64,
+  /// The number of different functions which were called, counting up to
+  /// one more than [`CallSite::POLYMORPHIC_LIMIT`].
+  ///
+  /// Closures created from the same function definition count once, as do
+  /// methods called on different receivers.
+  pub targets: usize,
+}
+
+impl CallSite {
+  /// The most different functions a polymorphic call site may call.
+  pub const POLYMORPHIC_LIMIT: usize = 4;
+
+  pub fn dispatch(&self) -> Dispatch {
+    match self.targets {
+      0 | 1 => Dispatch::Monomorphic,
+      n if n <= Self::POLYMORPHIC_LIMIT => Dispatch::Polymorphic,
+      _ => Dispatch::Megamorphic,
+    }
+  }
+
+  /// The `name:line:column` of the start of the call, if its module has a
+  /// source.
+  pub fn location(&self) -> Option<String> {
+    let source = self.source.as_ref()?;
+    let (line, column) = source.line_column(self.span.start);
+    Some(format!("{}:{line}:{column}", source.name))
+  }
+}
+
+/// How many different functions a call site called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dispatch {
+  /// Always the same function.
+  Monomorphic,
+  /// A few different functions, at most [`CallSite::POLYMORPHIC_LIMIT`].
+  Polymorphic,
+  /// More different functions than a polymorphic call site.
+  Megamorphic,
+}
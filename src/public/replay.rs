@@ -0,0 +1,25 @@
+use crate::{ErrorData, ErrorValue};
+
+/// The calls which scripts made to native functions, along with their
+/// results, see [`HebiBuilder::record_native_calls`][crate::HebiBuilder::record_native_calls].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+  /// In the order they were started.
+  pub calls: Vec<NativeCall>,
+}
+
+/// A call to a native function, with its arguments and its result.
+///
+/// Values which are not plain data, such as functions or instances of
+/// native classes, are stored as their string representation, so they are
+/// replayed as strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NativeCall {
+  pub name: String,
+  pub args: Vec<ErrorData>,
+  pub result: Result<ErrorData, ErrorValue>,
+  /// The number of calls which were made while this one ran, for example
+  /// by script code it called. They follow it in the recording, and are
+  /// skipped when it is replayed, because the host is not called then.
+  pub nested: usize,
+}
@@ -0,0 +1,135 @@
+use std::string::String as StdString;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+
+use crate::internal::vm::global::Global;
+use crate::public::NativeModule;
+use crate::ErrorData;
+
+/// Read-only globals and modules which may be shared by any number of
+/// [`Hebi`][crate::Hebi] instances, on any thread.
+///
+/// Cloning is cheap, and the setup is stored once, no matter how many VMs
+/// use it: the constant values, module sources, and native module
+/// definitions. The script values themselves are not shared, because they
+/// may only be used by the VM that created them. Each VM converts the
+/// constants into script values when it is created.
+///
+/// A module is compiled by the first VM which imports it, and the compiled
+/// code is stored in the same format as a [`Bundle`][crate::Bundle], which
+/// other VMs load instead of compiling the module again. VMs built with
+/// [`HebiBuilder::inline_functions`][crate::HebiBuilder::inline_functions]
+/// compile their own copy, because it depends on the other modules they
+/// have loaded.
+///
+/// ```rust
+/// use hebi::{Hebi, SharedGlobals};
+///
+/// let shared = SharedGlobals::builder()
+///   .constant("VERSION", "1.0.0")
+///   .module("greet", "fn hello(name):\n  return \"hello, \" + name\n")
+///   .finish();
+///
+/// let mut hebi = Hebi::builder().shared(&shared).finish();
+/// let value = hebi
+///   .eval("from greet import hello\nhello(VERSION)")
+///   .unwrap();
+/// assert_eq!(value.to_string(), "hello, 1.0.0");
+/// ```
+#[derive(Clone)]
+pub struct SharedGlobals {
+  data: Arc<SharedGlobalsData>,
+}
+
+struct SharedGlobalsData {
+  constants: IndexMap<StdString, SharedValue>,
+  modules: IndexMap<StdString, Arc<str>>,
+  native_modules: Vec<NativeModule>,
+  /// Packs of the compiled modules, by name and whether they were compiled
+  /// with strict globals.
+  compiled: Mutex<IndexMap<(StdString, bool), Arc<[u8]>>>,
+}
+
+impl SharedGlobals {
+  pub fn builder() -> SharedGlobalsBuilder {
+    SharedGlobalsBuilder {
+      data: SharedGlobalsData {
+        constants: IndexMap::new(),
+        modules: IndexMap::new(),
+        native_modules: Vec::new(),
+        compiled: Mutex::new(IndexMap::new()),
+      },
+    }
+  }
+
+  /// Returns the source of the script module `name`, if it was registered.
+  pub fn module_source(&self, name: &str) -> Option<&str> {
+    self.data.modules.get(name).map(|source| &source[..])
+  }
+
+  pub(crate) fn native_modules(&self) -> &[NativeModule] {
+    &self.data.native_modules
+  }
+
+  /// The pack of the module `name`, if another VM already compiled it.
+  pub(crate) fn compiled_module(&self, name: &str, strict_globals: bool) -> Option<Arc<[u8]>> {
+    let compiled = self.data.compiled.lock();
+    let compiled = compiled.unwrap_or_else(|e| e.into_inner());
+    compiled.get(&(name.to_string(), strict_globals)).cloned()
+  }
+
+  pub(crate) fn set_compiled_module(&self, name: &str, strict_globals: bool, pack: Vec<u8>) {
+    let compiled = self.data.compiled.lock();
+    let mut compiled = compiled.unwrap_or_else(|e| e.into_inner());
+    compiled.insert((name.to_string(), strict_globals), pack.into());
+  }
+
+  pub(crate) fn define_constants(&self, global: &Global) {
+    for (name, value) in self.data.constants.iter() {
+      global.set(
+        global.intern(name.clone()),
+        value.clone().into_value(global),
+      );
+    }
+  }
+}
+
+pub struct SharedGlobalsBuilder {
+  data: SharedGlobalsData,
+}
+
+impl SharedGlobalsBuilder {
+  /// Define a global variable called `name` with the given value.
+  ///
+  /// Scripts may still re-assign it, but that only affects their own VM.
+  pub fn constant(mut self, name: impl ToString, value: impl Into<SharedValue>) -> Self {
+    self.data.constants.insert(name.to_string(), value.into());
+    self
+  }
+
+  /// Make the script module `name` importable from every VM.
+  ///
+  /// Shared modules take priority over the VM's own module loader.
+  pub fn module(mut self, name: impl ToString, source: impl Into<Arc<str>>) -> Self {
+    self.data.modules.insert(name.to_string(), source.into());
+    self
+  }
+
+  /// Register `module` in every VM.
+  pub fn native_module(mut self, module: &NativeModule) -> Self {
+    self.data.native_modules.push(module.clone());
+    self
+  }
+
+  pub fn finish(self) -> SharedGlobals {
+    SharedGlobals {
+      data: Arc::new(self.data),
+    }
+  }
+}
+
+/// A constant stored in [`SharedGlobals`].
+///
+/// This only holds plain data, so that it may be sent across threads.
+pub type SharedValue = ErrorData;
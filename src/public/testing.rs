@@ -0,0 +1,133 @@
+//! Helpers for testing code which embeds hebi.
+//!
+//! [`vm`] creates a VM which captures everything scripts print and has the
+//! [`natives`] module registered, and [`assert_script_eq!`][crate::assert_script_eq]
+//! and [`assert_script_err!`][crate::assert_script_err] check what a script
+//! evaluates to.
+//!
+//! ```rust
+//! use hebi::{assert_script_eq, assert_script_err, testing};
+//!
+//! let mut hebi = testing::vm();
+//! assert_script_eq!(hebi, "1 + 1", 2);
+//! assert_script_eq!(hebi, "\"a\" + \"b\"", String::from("ab"));
+//! assert_script_err!(hebi, "from test import assert_eq\nassert_eq(1, 2)", "1 != 2");
+//!
+//! hebi.eval("print \"hello\"").unwrap();
+//! assert_eq!(testing::take_output(&mut hebi), "hello\n");
+//! ```
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use super::io::StringWriter;
+use super::{FromValue, Global, HasOutput, Hebi, HebiBuilder, NativeModule, Scope};
+use crate::internal::object::builtin;
+use crate::Result;
+
+/// A builder for a VM which prints into a string, which [`output`] and
+/// [`take_output`] read, and which is seeded so that scripts which use
+/// random numbers do the same thing on every run.
+pub fn builder() -> HebiBuilder<(), (), HasOutput> {
+  Hebi::builder().output(StringWriter::new()).seed(0)
+}
+
+/// A VM created by [`builder`], with the [`natives`] module registered.
+pub fn vm() -> Hebi {
+  let mut hebi = builder().finish();
+  hebi.register(&natives());
+  hebi
+}
+
+/// The module `test`, with functions for checking things from scripts:
+///
+/// - `assert(cond, message=none)` fails if `cond` is `false`
+/// - `assert_eq(lhs, rhs)` is the builtin `assert_eq`, which fails if the two
+///   values are not equal, listing where they differ
+/// - `fail(message)` always fails
+pub fn natives() -> NativeModule {
+  NativeModule::builder("test")
+    .function("assert", assert)
+    .function("assert_eq", assert_eq)
+    .function("fail", fail)
+    .finish()
+}
+
+fn assert(scope: Scope<'_>) -> Result<()> {
+  let cond = scope.param::<bool>(0)?;
+  let message = match scope.num_args() > 1 {
+    true => scope.param::<Option<String>>(1)?,
+    false => None,
+  };
+  if !cond {
+    fail!(message.unwrap_or_else(|| "assertion failed".into()));
+  }
+  Ok(())
+}
+
+fn assert_eq(scope: Scope<'_>) -> Result<()> {
+  builtin::assert_eq(scope).map(|_| ())
+}
+
+fn fail(scope: Scope<'_>) -> Result<()> {
+  let message = scope.param::<String>(0)?;
+  fail!(message)
+}
+
+/// Everything which scripts printed into the output of a VM created by
+/// [`builder`].
+pub fn output(hebi: &Hebi) -> String {
+  let global = hebi.global();
+  let output = global.inner.io().output.borrow();
+  match output.as_any().downcast_ref::<StringWriter>() {
+    Some(output) => output.as_str().to_string(),
+    None => panic!("the VM does not print into a string"),
+  }
+}
+
+/// Like [`output`], but clears the output, so that the next call only
+/// returns what was printed after this one.
+pub fn take_output(hebi: &mut Hebi) -> String {
+  let mut global = hebi.global();
+  let mut output = global.output();
+  match output.as_any_mut().downcast_mut::<StringWriter>() {
+    Some(output) => std::mem::take(output).into_string(),
+    None => panic!("the VM does not print into a string"),
+  }
+}
+
+/// See [`assert_script_eq!`][crate::assert_script_eq].
+#[track_caller]
+pub fn assert_eval_eq<T>(hebi: &mut Hebi, code: &str, expected: T)
+where
+  T: for<'cx> FromValue<'cx> + PartialEq + Debug,
+{
+  let global = Global {
+    inner: hebi.vm.root.global.clone(),
+    lifetime: PhantomData,
+  };
+  let value = match hebi.eval(code) {
+    Ok(value) => value,
+    Err(e) => panic!("script failed:\n{}", e.report(code, false)),
+  };
+  let display = value.to_string();
+  match T::from_value(value, global) {
+    Ok(actual) if actual == expected => {}
+    Ok(actual) => panic!("script evaluated to {actual:?}, expected {expected:?}\n{code}"),
+    Err(e) => panic!("script evaluated to `{display}`, expected {expected:?}: {e}\n{code}"),
+  }
+}
+
+/// See [`assert_script_err!`][crate::assert_script_err].
+#[track_caller]
+pub fn assert_eval_err(hebi: &mut Hebi, code: &str, message: &str) {
+  match hebi.eval(code) {
+    Ok(value) => panic!("script evaluated to `{value}`, expected an error\n{code}"),
+    Err(e) => {
+      let report = e.report(code, false);
+      if !report.contains(message) {
+        panic!("script failed with an error which does not contain `{message}`:\n{report}");
+      }
+    }
+  }
+}
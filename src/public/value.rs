@@ -1,6 +1,8 @@
+use std::any::TypeId;
+
 use super::object::{Any, ObjectRef};
-use crate::internal::error::Result;
-use crate::internal::{object, value};
+use crate::internal::error::{Error, Result};
+use crate::internal::{json, object, value};
 use crate::public::{Bind, Global, Unbind};
 
 decl_ref! {
@@ -54,10 +56,79 @@ impl<'cx> Value<'cx> {
   pub fn is_object(&self) -> bool {
     self.inner.is_object()
   }
+
+  /// Encode the value as JSON.
+  ///
+  /// Only `none`, bools, numbers, strings, lists and tables can be
+  /// encoded.
+  pub fn to_json_string(&self) -> Result<String> {
+    json::to_json_string(&self.inner)
+  }
 }
 
 pub trait FromValue<'cx>: Sized {
   fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self>;
+
+  /// The name of the type values are converted from, used in error
+  /// messages, e.g. `str` for [`String`]. Defaults to the Rust type name.
+  fn type_name() -> &'static str {
+    ::core::any::type_name::<Self>()
+  }
+}
+
+/// A hook which converts values into `T`, see
+/// [`Hebi::register_coercion`][crate::Hebi::register_coercion].
+pub(crate) type CoercionFn<T> =
+  Box<dyn for<'cx> Fn(Value<'cx>, Global<'cx>) -> Result<T> + Send + Sync>;
+
+/// Convert `value` into `T` with the coercion hook registered for `T`, or
+/// fail with `error` if there is none.
+fn coerce<'cx, T: 'static>(
+  value: Value<'cx>,
+  global: Global<'cx>,
+  error: impl FnOnce() -> Error,
+) -> Result<T> {
+  /// Releases the hook even if it panics, so that it is still called for
+  /// later conversions if the host catches the panic.
+  struct Finish<'a>(&'a Global<'a>, TypeId);
+
+  impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+      self.0.inner.finish_coercion(self.1);
+    }
+  }
+
+  let type_id = TypeId::of::<T>();
+  let Some(coercion) = global.inner.start_coercion(type_id) else {
+    return Err(error());
+  };
+  let _finish = Finish(&global, type_id);
+  match coercion.downcast_ref::<CoercionFn<T>>() {
+    Some(f) => f(value, global.clone()),
+    None => Err(error()),
+  }
+}
+
+/// A value of a host type, which is converted by the hook registered with
+/// [`Hebi::register_coercion`][crate::Hebi::register_coercion].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Coerced<T>(pub T);
+
+impl<'cx, T: 'static> FromValue<'cx> for Coerced<T> {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    coerce(value, global, || {
+      error!(
+        "value cannot be converted into {}",
+        ::core::any::type_name::<T>()
+      )
+      .into()
+    })
+    .map(Coerced)
+  }
+
+  fn type_name() -> &'static str {
+    ::core::any::type_name::<T>()
+  }
 }
 
 pub trait IntoValue<'cx>: Sized {
@@ -74,6 +145,10 @@ impl<'cx> FromValue<'cx> for Value<'cx> {
   fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
     Ok(value)
   }
+
+  fn type_name() -> &'static str {
+    "value"
+  }
 }
 
 impl<'cx> IntoValue<'cx> for i32 {
@@ -83,14 +158,111 @@ impl<'cx> IntoValue<'cx> for i32 {
 }
 
 impl<'cx> FromValue<'cx> for i32 {
-  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
     match value.as_int() {
       Some(value) => Ok(value),
-      None => crate::fail!("value is not an int"),
+      None => coerce(value, global, || error!("value is not an `int`").into()),
     }
   }
+
+  fn type_name() -> &'static str {
+    "int"
+  }
 }
 
+// Hebi ints are 32 bits wide, so every other integer type is converted with
+// a range check.
+macro_rules! impl_int {
+  ($($T:ty),*) => {
+    $(
+      impl<'cx> IntoValue<'cx> for $T {
+        fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+          match i32::try_from(self) {
+            Ok(value) => Ok(value::Value::int(value).bind(global)),
+            Err(_) => crate::fail!(
+              "`{self}` is out of range for an int ({}..={})",
+              i32::MIN,
+              i32::MAX
+            ),
+          }
+        }
+      }
+
+      impl<'cx> FromValue<'cx> for $T {
+        fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+          let Some(int) = value.as_int() else {
+            return coerce(value, global, || error!("value is not an `int`").into());
+          };
+          match <$T>::try_from(int) {
+            Ok(int) => Ok(int),
+            Err(_) => coerce(value, global, || {
+              error!(
+                "`{int}` is out of range for {} ({}..={})",
+                stringify!($T),
+                <$T>::MIN,
+                <$T>::MAX
+              )
+              .into()
+            }),
+          }
+        }
+
+        fn type_name() -> &'static str {
+          "int"
+        }
+      }
+
+      impl<'cx> IntoValue<'cx> for Lossy<$T> {
+        fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+          match i32::try_from(self.0) {
+            Ok(value) => Ok(value::Value::int(value).bind(global)),
+            Err(_) => Ok(value::Value::float(self.0 as f64).bind(global)),
+          }
+        }
+      }
+
+      impl<'cx> FromValue<'cx> for Lossy<$T> {
+        fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+          if let Some(value) = value.as_int() {
+            let value = <$T>::try_from(value)
+              .unwrap_or(if value < 0 { <$T>::MIN } else { <$T>::MAX });
+            Ok(Lossy(value))
+          } else if let Some(value) = value.as_float() {
+            Ok(Lossy(value as $T))
+          } else {
+            crate::fail!("value is not a number")
+          }
+        }
+
+        fn type_name() -> &'static str {
+          "number"
+        }
+      }
+    )*
+  };
+}
+
+impl_int!(i8, i16, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// A number which is converted without range checks.
+///
+/// Integers which don't fit into an int become floats, possibly losing
+/// precision. In the other direction, ints and floats are saturated at the
+/// bounds of the target type, and floats are truncated.
+///
+/// ```rust
+/// use hebi::Lossy;
+///
+/// let mut hebi = hebi::Hebi::new();
+/// assert!(hebi.eval_with::<u64>("n", [("n", u64::MAX)]).is_err());
+/// let Lossy(n) = hebi
+///   .eval_with::<Lossy<u64>>("n * 2", [("n", Lossy(u64::MAX))])
+///   .unwrap();
+/// assert_eq!(n, u64::MAX);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Lossy<T>(pub T);
+
 impl<'cx> IntoValue<'cx> for f64 {
   fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
     Ok(value::Value::float(self).bind(global))
@@ -98,12 +270,16 @@ impl<'cx> IntoValue<'cx> for f64 {
 }
 
 impl<'cx> FromValue<'cx> for f64 {
-  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
     match value.as_float() {
       Some(value) => Ok(value),
-      None => crate::fail!("value is not a float"),
+      None => coerce(value, global, || error!("value is not a `float`").into()),
     }
   }
+
+  fn type_name() -> &'static str {
+    "float"
+  }
 }
 
 impl<'cx> IntoValue<'cx> for bool {
@@ -113,12 +289,16 @@ impl<'cx> IntoValue<'cx> for bool {
 }
 
 impl<'cx> FromValue<'cx> for bool {
-  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
     match value.as_bool() {
       Some(value) => Ok(value),
-      None => crate::fail!("value is not a bool"),
+      None => coerce(value, global, || error!("value is not a `bool`").into()),
     }
   }
+
+  fn type_name() -> &'static str {
+    "bool"
+  }
 }
 
 impl<'cx> IntoValue<'cx> for () {
@@ -132,6 +312,10 @@ impl<'cx> FromValue<'cx> for () {
     let _ = (value, global);
     Ok(())
   }
+
+  fn type_name() -> &'static str {
+    "none"
+  }
 }
 
 impl<'cx, T> IntoValue<'cx> for Option<T>
@@ -157,6 +341,10 @@ where
       T::from_value(value, global).map(Some)
     }
   }
+
+  fn type_name() -> &'static str {
+    T::type_name()
+  }
 }
 
 impl<'cx, T> IntoValue<'cx> for Result<T>
@@ -182,26 +370,28 @@ where
   T: ObjectRef<'cx>,
 {
   fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
-    let object = value
+    value
       .as_any()
-      .ok_or_else(|| error!("value is not an object"))?;
-    let object = T::from_any(object, global).ok_or_else(|| {
-      error!(
-        "value is not an instance of {}",
-        ::core::any::type_name::<T>()
-      )
-    })?;
-    Ok(object)
+      .and_then(|object| T::from_any(object, global))
+      .ok_or_else(|| error!("value is not a `{}`", T::NAME).into())
+  }
+
+  fn type_name() -> &'static str {
+    T::NAME
   }
 }
 
 impl<'cx> FromValue<'cx> for String {
-  fn from_value(value: Value<'cx>, _: Global<'cx>) -> Result<Self> {
-    let Some(str) = value.unbind().to_object::<object::Str>() else {
-      fail!("value is not a string")
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let Some(str) = value.clone().unbind().to_object::<object::Str>() else {
+      return coerce(value, global, || error!("value is not a `str`").into());
     };
     Ok(str.as_str().to_string())
   }
+
+  fn type_name() -> &'static str {
+    <super::Str<'cx> as ObjectRef<'cx>>::NAME
+  }
 }
 
 impl<'cx> IntoValue<'cx> for String {
@@ -210,6 +400,125 @@ impl<'cx> IntoValue<'cx> for String {
   }
 }
 
+impl<'cx> IntoValue<'cx> for std::time::Duration {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let duration = global.inner.alloc(object::time::Duration(self));
+    Ok(value::Value::object(duration).bind(global))
+  }
+}
+
+impl<'cx> FromValue<'cx> for std::time::Duration {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let bound = value.clone();
+    let value = value.unbind();
+    if let Some(duration) = value.clone().to_object::<object::time::Duration>() {
+      return Ok(duration.0);
+    }
+    // plain numbers are interpreted as seconds
+    let secs = if let Some(secs) = value.clone().to_float() {
+      secs
+    } else if let Some(secs) = value.clone().to_int() {
+      secs as f64
+    } else {
+      return coerce(bound, global, || error!("value is not a duration").into());
+    };
+    std::time::Duration::try_from_secs_f64(secs)
+      .map_err(|e| error!("`{value}` is not a valid duration: {e}").into())
+  }
+
+  fn type_name() -> &'static str {
+    "duration"
+  }
+}
+
+impl<'cx> IntoValue<'cx> for std::time::SystemTime {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let timestamp = global
+      .inner
+      .alloc(object::time::Timestamp::from_system_time(self));
+    Ok(value::Value::object(timestamp).bind(global))
+  }
+}
+
+impl<'cx> FromValue<'cx> for std::time::SystemTime {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let Some(timestamp) = value
+      .clone()
+      .unbind()
+      .to_object::<object::time::Timestamp>()
+    else {
+      return coerce(value, global, || error!("value is not a timestamp").into());
+    };
+    match timestamp.to_system_time() {
+      Some(time) => Ok(time),
+      None => fail!("`{timestamp}` is out of range for this platform"),
+    }
+  }
+
+  fn type_name() -> &'static str {
+    "timestamp"
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl<'cx> IntoValue<'cx> for chrono::DateTime<chrono::Utc> {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let timestamp = global
+      .inner
+      .alloc(object::time::Timestamp::from_chrono(self));
+    Ok(value::Value::object(timestamp).bind(global))
+  }
+}
+
+#[cfg(feature = "chrono")]
+impl<'cx> FromValue<'cx> for chrono::DateTime<chrono::Utc> {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let Some(timestamp) = value
+      .clone()
+      .unbind()
+      .to_object::<object::time::Timestamp>()
+    else {
+      return coerce(value, global, || error!("value is not a timestamp").into());
+    };
+    match timestamp.to_chrono() {
+      Some(time) => Ok(time),
+      None => fail!("`{timestamp}` is out of range for a `DateTime`"),
+    }
+  }
+
+  fn type_name() -> &'static str {
+    "timestamp"
+  }
+}
+
+#[cfg(feature = "decimal")]
+impl<'cx> IntoValue<'cx> for rust_decimal::Decimal {
+  fn into_value(self, global: Global<'cx>) -> Result<Value<'cx>> {
+    let decimal = global.inner.alloc(object::decimal::Decimal(self));
+    Ok(value::Value::object(decimal).bind(global))
+  }
+}
+
+/// Ints are converted exactly, but floats are not accepted, because they
+/// may already have lost the precision the decimal is meant to preserve.
+#[cfg(feature = "decimal")]
+impl<'cx> FromValue<'cx> for rust_decimal::Decimal {
+  fn from_value(value: Value<'cx>, global: Global<'cx>) -> Result<Self> {
+    let inner = value.clone().unbind();
+    if let Some(decimal) = inner.clone().to_object::<object::decimal::Decimal>() {
+      return Ok(decimal.0);
+    }
+    if let Some(v) = inner.to_int() {
+      return Ok(v.into());
+    }
+    coerce(value, global, || error!("value is not a decimal").into())
+  }
+
+  fn type_name() -> &'static str {
+    "decimal"
+  }
+}
+
 pub trait FromValuePack<'cx> {
   type Output: Sized;
   fn from_value_pack(args: &[value::Value], global: Global<'cx>) -> Result<Self::Output>;
@@ -286,6 +595,68 @@ impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J);
 impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J, K);
 impl_from_value_pack!(A, B, C, D, E, F, G, H, I, J, K, L);
 
+/// A fixed number of arguments, converted into values.
+pub trait IntoValuePack<'cx> {
+  /// Convert `self` into values, writing them to `out`, which has exactly
+  /// [`IntoValuePack::len`] elements.
+  fn into_value_pack(self, global: Global<'cx>, out: &mut [value::Value]) -> Result<()>;
+  fn len() -> usize;
+}
+
+impl<'cx> IntoValuePack<'cx> for () {
+  fn into_value_pack(self, _: Global<'cx>, _: &mut [value::Value]) -> Result<()> {
+    Ok(())
+  }
+
+  fn len() -> usize {
+    0
+  }
+}
+
+macro_rules! impl_into_value_pack {
+  ($($T:ident),*) => {
+    impl<'cx, $($T),*> IntoValuePack<'cx> for ($($T,)*)
+    where
+      $(
+        $T: IntoValue<'cx>,
+      )*
+    {
+      #[allow(non_snake_case)]
+      fn into_value_pack(self, global: Global<'cx>, out: &mut [$crate::internal::value::Value]) -> Result<()> {
+        debug_assert_eq!(out.len(), Self::len());
+
+        let ($($T,)*) = self;
+        let mut offset = 0;
+        $(
+          out[offset] = $T.into_value(global.clone())?.unbind();
+          offset += 1;
+        )*
+        let _ = offset;
+
+        Ok(())
+      }
+
+      #[inline]
+      fn len() -> usize {
+        __count!($($T)*)
+      }
+    }
+  };
+}
+
+impl_into_value_pack!(A);
+impl_into_value_pack!(A, B);
+impl_into_value_pack!(A, B, C);
+impl_into_value_pack!(A, B, C, D);
+impl_into_value_pack!(A, B, C, D, E);
+impl_into_value_pack!(A, B, C, D, E, F);
+impl_into_value_pack!(A, B, C, D, E, F, G);
+impl_into_value_pack!(A, B, C, D, E, F, G, H);
+impl_into_value_pack!(A, B, C, D, E, F, G, H, I);
+impl_into_value_pack!(A, B, C, D, E, F, G, H, I, J);
+impl_into_value_pack!(A, B, C, D, E, F, G, H, I, J, K);
+impl_into_value_pack!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 #[cfg(feature = "serde")]
 mod serde {
   use ::serde::Serialize;
@@ -301,3 +672,6 @@ mod serde {
     }
   }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,134 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use proptest::prelude::*;
+
+use super::*;
+use crate::public::Hebi;
+
+/// Pass `value` through a script function which returns its argument, and
+/// convert the result back into `T`.
+fn round_trip<T>(value: T) -> Result<T>
+where
+  T: for<'cx> IntoValue<'cx> + for<'cx> FromValue<'cx>,
+{
+  let mut hebi = Hebi::new();
+  hebi.eval("fn id(v):\n  return v\n").unwrap();
+  hebi.eval_with::<T>("id(v)", [("v", value)])
+}
+
+fn assert_round_trip<T>(value: T)
+where
+  T: for<'cx> IntoValue<'cx> + for<'cx> FromValue<'cx> + Clone + PartialEq + Debug,
+{
+  assert_eq!(round_trip(value.clone()).unwrap(), value);
+}
+
+macro_rules! int_round_trip {
+  ($($name:ident: $T:ty),* $(,)?) => {
+    proptest! {
+      $(
+        #[test]
+        fn $name(value: $T) {
+          // only values which fit into an int may cross the boundary
+          match (i32::try_from(value), round_trip(value)) {
+            (Ok(_), Ok(result)) => prop_assert_eq!(result, value),
+            (Err(_), Err(e)) => prop_assert!(
+              e.to_string().contains("is out of range for an int"),
+              "{}",
+              e
+            ),
+            (expected, result) => prop_assert!(
+              false,
+              "`{}`: expected {:?}, got {:?}",
+              value,
+              expected.is_ok(),
+              result.map_err(|e| e.to_string())
+            ),
+          }
+        }
+      )*
+    }
+  };
+}
+
+int_round_trip! {
+  round_trip_i8: i8,
+  round_trip_i16: i16,
+  round_trip_i32: i32,
+  round_trip_i64: i64,
+  round_trip_i128: i128,
+  round_trip_isize: isize,
+  round_trip_u8: u8,
+  round_trip_u16: u16,
+  round_trip_u32: u32,
+  round_trip_u64: u64,
+  round_trip_u128: u128,
+  round_trip_usize: usize,
+}
+
+proptest! {
+  #[test]
+  fn round_trip_f64(value: f64) {
+    let result = round_trip(value).unwrap();
+    if value.is_nan() {
+      prop_assert!(result.is_nan());
+    } else {
+      prop_assert_eq!(result.to_bits(), value.to_bits());
+    }
+  }
+
+  #[test]
+  fn round_trip_bool(value: bool) {
+    assert_round_trip(value);
+  }
+
+  #[test]
+  fn round_trip_string(value: String) {
+    assert_round_trip(value);
+  }
+
+  #[test]
+  fn round_trip_option(value: Option<i32>) {
+    assert_round_trip(value);
+  }
+
+  #[test]
+  fn round_trip_nested_option(value: Option<Option<bool>>) {
+    // `none` cannot tell the two apart
+    let expected = value.flatten().map(Some);
+    prop_assert_eq!(round_trip(value).unwrap(), expected);
+  }
+
+  #[test]
+  fn round_trip_duration(secs in 0u64..1 << 40, nanos in 0u32..1_000_000_000) {
+    // durations are not converted to float seconds, so no precision is lost
+    assert_round_trip(Duration::new(secs, nanos));
+  }
+
+  #[test]
+  fn round_trip_lossy_u64(value: u64) {
+    let Lossy(result) = round_trip(Lossy(value)).unwrap();
+    if i32::try_from(value).is_ok() {
+      prop_assert_eq!(result, value);
+    } else {
+      // converted through a float, saturating at `u64::MAX`
+      prop_assert_eq!(result, value as f64 as u64);
+    }
+  }
+
+  #[test]
+  fn round_trip_lossy_i64(value: i64) {
+    let Lossy(result) = round_trip(Lossy(value)).unwrap();
+    if i32::try_from(value).is_ok() {
+      prop_assert_eq!(result, value);
+    } else {
+      prop_assert_eq!(result, value as f64 as i64);
+    }
+  }
+}
+
+#[test]
+fn round_trip_unit() {
+  assert_round_trip(());
+}
@@ -4,6 +4,7 @@
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display, Write};
 use std::ops::{Deref, DerefMut, Index, Range};
+use std::sync::Arc;
 
 /// Represents a span of bytes in some source string.
 ///
@@ -158,10 +159,42 @@ impl<T: std::fmt::Display> std::fmt::Display for Spanned<T> {
   }
 }
 
+/// A named piece of source code, such as a script file.
+///
+/// Errors which carry a source are reported as `name:line:column`, and their
+/// snippet is taken from the source instead of the string passed to
+/// [`SpannedError::report`].
+#[derive(Clone, Debug)]
+pub struct Source {
+  pub name: Arc<str>,
+  pub text: Arc<str>,
+}
+
+impl Source {
+  pub fn new(name: impl Into<Arc<str>>, text: impl Into<Arc<str>>) -> Self {
+    Self {
+      name: name.into(),
+      text: text.into(),
+    }
+  }
+
+  /// The 1-based line and column of the byte at `offset`.
+  pub fn line_column(&self, offset: usize) -> (usize, usize) {
+    let offset = offset.min(self.text.len());
+    let before = &self.text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|v| v + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct SpannedError {
   pub span: Span,
   pub message: String,
+  // boxed to keep the parser's `Result`s small
+  pub source: Option<Box<Source>>,
 }
 
 pub trait MaybeSpan {
@@ -194,16 +227,45 @@ impl SpannedError {
     Self {
       span: span.into_span(),
       message: message.to_string(),
+      source: None,
+    }
+  }
+
+  /// Attach the source which `span` points into.
+  pub fn with_source(mut self, source: Source) -> Self {
+    self.source = Some(Box::new(source));
+    self
+  }
+
+  /// The `name:line:column` of the start of the span, if the error has a
+  /// source. Errors without a span only report the name.
+  pub fn location(&self) -> Option<String> {
+    let source = self.source.as_ref()?;
+    if self.span == Span::default() {
+      return Some(source.name.to_string());
+    }
+    let (line, column) = source.line_column(self.span.start);
+    Some(format!("{}:{line}:{column}", source.name))
+  }
+
+  fn header(&self) -> String {
+    match self.location() {
+      Some(location) => format!("{location}: {}", self.message),
+      None => self.message.clone(),
     }
   }
 
   pub fn report(&self, src: &str, use_color: bool) -> String {
+    let src = match &self.source {
+      Some(source) => &source.text[..],
+      None => src,
+    };
     if self.span.is_empty() {
-      return self.message.clone();
+      return self.header();
     }
     if self.span.start > src.len() || self.span.end > src.len() {
       // TODO: file database + interned spans will solve this
-      return self.message.clone();
+      return self.header();
       // panic!("invalid span {self}");
     }
 
@@ -227,7 +289,7 @@ impl SpannedError {
     let mut out = String::new();
     let f = &mut out;
 
-    writeln!(f, "{}", self.message).unwrap();
+    writeln!(f, "{}", self.header()).unwrap();
     let mut lines = content.lines().peekable();
     let line = lines.next().unwrap().or("_");
     if lines.peek().is_some() {
@@ -264,7 +326,7 @@ impl EmptyOr for str {
 
 impl Display for SpannedError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}", self.message)
+    write!(f, "{}", self.header())
   }
 }
 
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use std::borrow::Borrow;
 use std::fmt::Display;
 
 #[cfg(test)]
@@ -24,6 +25,20 @@ macro_rules! assert_debug_snapshot {
   };
 }
 
+/// Check an invariant which an `unsafe` block relies on, but which should
+/// already be guaranteed by the compiler, such as a register index in the
+/// bytecode being in bounds.
+///
+/// Checked in debug builds, or in any build with the `paranoid` feature.
+/// See `docs/src/unchecked.md` for what each check protects against.
+macro_rules! paranoid_assert {
+  ($($arg:tt)*) => {
+    if cfg!(any(debug_assertions, feature = "paranoid")) {
+      assert!($($arg)*);
+    }
+  };
+}
+
 /* macro_rules! static_assert_size {
   ($T:ty, $S:ty) => {
     const _: fn() = || {
@@ -68,6 +83,64 @@ where
   }
 }
 
+/// Find the candidate which is most likely what `name` was meant to be, for
+/// "did you mean" hints.
+///
+/// The allowed edit distance grows with the length of the names, so that
+/// short names do not match everything. Ties go to the candidate which
+/// sorts first, so the result does not depend on the order of `candidates`.
+pub fn closest_match<S: Borrow<str>>(
+  name: &str,
+  candidates: impl IntoIterator<Item = S>,
+) -> Option<S> {
+  let name_len = name.chars().count();
+  let mut best: Option<(usize, S)> = None;
+  for candidate in candidates {
+    let value: &str = candidate.borrow();
+    if value == name {
+      continue;
+    }
+    let max_distance = (name_len + value.chars().count()) / 6;
+    let distance = edit_distance(name, value);
+    if distance > max_distance {
+      continue;
+    }
+    let is_better = match &best {
+      Some((best_distance, best)) => (distance, value) < (*best_distance, best.borrow()),
+      None => true,
+    };
+    if is_better {
+      best = Some((distance, candidate));
+    }
+  }
+  best.map(|(_, candidate)| candidate)
+}
+
+/// `, did you mean `x`?` if one of `candidates` is close to `name`, to be
+/// appended to an error message.
+pub fn did_you_mean<S: Borrow<str>>(name: &str, candidates: impl IntoIterator<Item = S>) -> String {
+  match closest_match(name, candidates) {
+    Some(candidate) => format!(", did you mean `{}`?", candidate.borrow() as &str),
+    None => String::new(),
+  }
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, a) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, b) in b.iter().enumerate() {
+      let substitute = diagonal + (a != *b) as usize;
+      diagonal = row[j + 1];
+      row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()]
+}
+
 pub fn num_digits(v: usize) -> usize {
   use std::iter::successors;
 
@@ -0,0 +1,140 @@
+//! Runs every `.hebi` program in `tests/corpus` through the public API, and
+//! compares what it does with the expectation files next to it.
+//!
+//! See `tests/corpus/README.md` for the format.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use hebi::{testing, Cow, ModuleLoader};
+
+#[test]
+fn corpus() {
+  let dir = match std::env::var_os("HEBI_CORPUS") {
+    Some(dir) => PathBuf::from(dir),
+    None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus"),
+  };
+  let bless = std::env::var_os("HEBI_BLESS").is_some();
+
+  let mut programs = fs::read_dir(&dir)
+    .unwrap_or_else(|e| panic!("failed to read {}: {e}", dir.display()))
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "hebi"))
+    .collect::<Vec<_>>();
+  programs.sort();
+  assert!(!programs.is_empty(), "no programs in {}", dir.display());
+
+  let mut failures = String::new();
+  for program in &programs {
+    let actual = run(&dir, program);
+    if bless {
+      actual.write(program);
+    } else if let Err(e) = actual.check(program) {
+      writeln!(&mut failures, "{}:\n{e}", program.display()).unwrap();
+    }
+  }
+
+  if !failures.is_empty() {
+    panic!("{failures}\nrun with `HEBI_BLESS=1` to accept the new results");
+  }
+}
+
+/// What a program did.
+struct Outcome {
+  stdout: String,
+  /// The result or error of the program. If it failed, this is the error
+  /// report, otherwise the result of the last expression.
+  result: Result<String, String>,
+}
+
+fn run(dir: &Path, program: &Path) -> Outcome {
+  let source = fs::read_to_string(program).unwrap();
+  let mut hebi = testing::builder()
+    .module_loader(CorpusModuleLoader {
+      dir: dir.join("modules"),
+    })
+    .finish();
+  hebi.register(&testing::natives());
+  let result = match hebi.eval(&source) {
+    Ok(value) => Ok(value.to_string()),
+    Err(e) => Err(e.report(&source, false)),
+  };
+  Outcome {
+    stdout: testing::take_output(&mut hebi),
+    result,
+  }
+}
+
+impl Outcome {
+  /// The expected contents of each expectation file, where `None` means
+  /// the file should not exist.
+  fn files(&self) -> [(&'static str, Option<&str>); 3] {
+    let (result, error) = match &self.result {
+      Ok(value) if value == "none" => (None, None),
+      Ok(value) => (Some(value.as_str()), None),
+      Err(e) => (None, Some(e.as_str())),
+    };
+    let stdout = Some(self.stdout.as_str()).filter(|s| !s.is_empty());
+    [("stdout", stdout), ("result", result), ("error", error)]
+  }
+
+  fn check(&self, program: &Path) -> Result<(), String> {
+    let mut errors = String::new();
+    for (ext, actual) in self.files() {
+      let expected = fs::read_to_string(program.with_extension(ext)).ok();
+      let expected = expected.as_deref().map(str::trim_end);
+      let actual = actual.map(str::trim_end);
+      if expected != actual {
+        writeln!(
+          &mut errors,
+          "  {ext} does not match\n  expected:\n{}\n  actual:\n{}",
+          indent(expected),
+          indent(actual)
+        )
+        .unwrap();
+      }
+    }
+    match errors.is_empty() {
+      true => Ok(()),
+      false => Err(errors),
+    }
+  }
+
+  fn write(&self, program: &Path) {
+    for (ext, actual) in self.files() {
+      let path = program.with_extension(ext);
+      match actual {
+        Some(contents) => fs::write(path, format!("{}\n", contents.trim_end())).unwrap(),
+        None if path.exists() => fs::remove_file(path).unwrap(),
+        None => {}
+      }
+    }
+  }
+}
+
+fn indent(s: Option<&str>) -> String {
+  match s {
+    Some(s) => s
+      .lines()
+      .map(|line| format!("    {line}"))
+      .collect::<Vec<_>>()
+      .join("\n"),
+    None => "    (nothing)".into(),
+  }
+}
+
+/// Loads `import a.b` from `modules/a/b.hebi`.
+struct CorpusModuleLoader {
+  dir: PathBuf,
+}
+
+impl ModuleLoader for CorpusModuleLoader {
+  fn load(&self, path: &str) -> hebi::Result<Cow<'static, str>> {
+    let file = self.dir.join(path.replace('.', "/")).with_extension("hebi");
+    match fs::read_to_string(&file) {
+      Ok(source) => Ok(Cow::owned(source)),
+      Err(e) => hebi::fail!("failed to load module `{path}`: {e}"),
+    }
+  }
+}
@@ -8,6 +8,7 @@ Tasks:
   examples : run all examples
   snap     : run snapshot tests in review mode
   miri     : run cargo command under miri
+  opcodes  : generate the instruction set docs (`--check` to verify them)
   test     : run tests and examples
 ";
 
@@ -16,6 +17,7 @@ pub mod common;
 pub mod bench;
 pub mod examples;
 pub mod miri;
+pub mod opcodes;
 pub mod snap;
 pub mod template;
 pub mod test;
@@ -30,6 +32,7 @@ pub fn run(which: &str, args: &[String]) -> Result<()> {
     "examples" => examples::run(args),
     "snap" => snap::run(args),
     "miri" => miri::run(args),
+    "opcodes" => opcodes::run(args),
     "test" => test::run(args),
     "bench" => bench::run(args),
     // "template" => template::run(args),
@@ -0,0 +1,70 @@
+use std::fmt::Write;
+use std::fs;
+
+use hebi::op::{AccEffect, OPCODES};
+
+use super::common::project_root;
+use crate::Result;
+
+const HEADER: &str = "<!-- generated by `cargo xtask opcodes`, do not edit by hand -->
+
+# Instructions
+
+Every instruction operates on the accumulator and/or a set of registers.
+The `acc` column describes what the instruction does with the accumulator:
+
+- `-` leaves it untouched
+- `read` consumes it
+- `write` overwrites it
+- `read, write` consumes it and then overwrites it with the result
+
+| opcode | name | operands | acc |
+|--------|------|----------|-----|
+";
+
+pub fn run(args: &[String]) -> Result<()> {
+  let path = project_root().join("docs/src/instructions.md");
+  let table = render()?;
+
+  if args.iter().any(|arg| arg == "--check") {
+    let current = fs::read_to_string(&path)?;
+    if current != table {
+      return Err(
+        format!(
+          "{} is out of date, run `cargo xtask opcodes`",
+          path.display()
+        )
+        .into(),
+      );
+    }
+    return Ok(());
+  }
+
+  fs::write(&path, table)?;
+  eprintln!("wrote {}", path.display());
+  Ok(())
+}
+
+fn render() -> Result<String> {
+  let mut out = String::from(HEADER);
+  for info in OPCODES {
+    let operands = info
+      .operands
+      .iter()
+      .map(|operand| format!("`{}: {}`", operand.name, operand.kind))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let acc = match info.acc {
+      AccEffect::None => "-",
+      AccEffect::Read => "read",
+      AccEffect::Write => "write",
+      AccEffect::Update => "read, write",
+    };
+    writeln!(
+      out,
+      "| `0x{:02X}` | `{}` | {} | {} |",
+      info.opcode as u8, info.name, operands, acc
+    )?;
+  }
+  Ok(out)
+}